@@ -9,10 +9,10 @@
 
 use std::collections::HashMap;
 use std::path::Path;
-use std::pin::Pin;
 
 use futures::StreamExt;
 
+use fuse_adapter::auth::http as http_auth;
 use fuse_adapter::config::{GDriveAuthConfig, GDriveConnectorConfig};
 use fuse_adapter::connector::gdrive::GDriveConnector;
 use fuse_adapter::connector::{Connector, FileType};
@@ -43,8 +43,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             endpoint: "http://localhost:8000/internal/v1/service/tool-auth/google_drive/token?user_id=f72b100a-1eb9-436b-b285-d46b329736df".to_string(),
             method: "GET".to_string(),
             headers,
+            token_field: http_auth::DEFAULT_TOKEN_FIELD.to_string(),
+            expires_in_field: http_auth::DEFAULT_EXPIRES_IN_FIELD.to_string(),
+            max_attempts: http_auth::DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: http_auth::DEFAULT_INITIAL_BACKOFF,
         },
         root_folder_id: "root".to_string(),
+        drive_id: None,
+        supports_all_drives: false,
+        export_formats: HashMap::new(),
+        endpoint: None,
     };
 
     println!("Creating GDrive connector...");
@@ -69,7 +77,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Test list_dir
     println!("=== list_dir(\"{}\") ===", test_path);
-    let mut stream = Pin::from(connector.list_dir(path));
+    let mut stream = connector.list_dir(path);
     let mut count = 0;
     while let Some(result) = stream.next().await {
         match result {
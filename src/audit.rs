@@ -0,0 +1,219 @@
+//! Audit logging of filesystem mutations
+//!
+//! When a mount has `audit` configured, the FUSE layer records every
+//! operation that changes the filesystem (create, mkdir, write, copy,
+//! fallocate, truncate, chmod, unlink, rmdir, rename, symlink) along with the
+//! uid, gid and pid of the calling process and whether it succeeded. Records
+//! are JSON objects, written one per line to a file or sent to syslog.
+
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Once};
+
+use chrono::Utc;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::AuditConfig;
+use crate::error::{FuseAdapterError, Result};
+
+/// Where audit records are written
+enum AuditSink {
+    /// Append JSON lines to a file
+    File(Mutex<File>),
+    /// Send each record to the local syslog daemon
+    Syslog,
+}
+
+/// Audit log for a single mount
+pub struct AuditLog {
+    /// Mount point, included in every record
+    mount: PathBuf,
+    sink: AuditSink,
+}
+
+/// A single audited operation
+#[derive(Debug, Serialize)]
+struct AuditRecord {
+    time: String,
+    mount: String,
+    op: &'static str,
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    uid: u32,
+    gid: u32,
+    pid: u32,
+    result: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errno: Option<i32>,
+}
+
+/// An operation in progress whose outcome will be audited
+pub struct AuditEvent {
+    log: Arc<AuditLog>,
+    record: AuditRecord,
+}
+
+impl AuditLog {
+    /// Open the audit log configured for the mount at `mount`
+    pub fn open(mount: &Path, config: &AuditConfig) -> Result<Self> {
+        let sink = match config {
+            AuditConfig::File { path } => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        FuseAdapterError::Config(format!(
+                            "Failed to open audit log {:?}: {}",
+                            path, e
+                        ))
+                    })?;
+                AuditSink::File(Mutex::new(file))
+            }
+            AuditConfig::Syslog => {
+                static OPENLOG: Once = Once::new();
+                OPENLOG.call_once(|| unsafe {
+                    libc::openlog(c"fuse-adapter".as_ptr(), libc::LOG_PID, libc::LOG_USER);
+                });
+                AuditSink::Syslog
+            }
+        };
+        Ok(Self {
+            mount: mount.to_path_buf(),
+            sink,
+        })
+    }
+
+    /// Start auditing an operation on `path` by the given caller
+    pub fn event(
+        self: &Arc<Self>,
+        op: &'static str,
+        path: &Path,
+        uid: u32,
+        gid: u32,
+        pid: u32,
+    ) -> AuditEvent {
+        AuditEvent {
+            log: self.clone(),
+            record: AuditRecord {
+                time: Utc::now().to_rfc3339(),
+                mount: self.mount.display().to_string(),
+                op,
+                path: path.display().to_string(),
+                to: None,
+                size: None,
+                mode: None,
+                uid,
+                gid,
+                pid,
+                result: String::new(),
+                errno: None,
+            },
+        }
+    }
+
+    fn write(&self, record: &AuditRecord) {
+        let line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize audit record: {}", e);
+                return;
+            }
+        };
+        match &self.sink {
+            AuditSink::File(file) => {
+                let mut file = file.lock().unwrap();
+                if let Err(e) = writeln!(file, "{}", line) {
+                    warn!("Failed to write audit record: {}", e);
+                }
+            }
+            AuditSink::Syslog => {
+                let Ok(message) = CString::new(line) else {
+                    return;
+                };
+                unsafe {
+                    libc::syslog(libc::LOG_NOTICE, c"%s".as_ptr(), message.as_ptr());
+                }
+            }
+        }
+    }
+}
+
+impl AuditEvent {
+    /// Record the destination of a rename or the target of a symlink
+    pub fn to(mut self, path: &Path) -> Self {
+        self.record.to = Some(path.display().to_string());
+        self
+    }
+
+    /// Record the number of bytes written or the new size of a file
+    pub fn size(mut self, size: u64) -> Self {
+        self.record.size = Some(size);
+        self
+    }
+
+    /// Record the permission bits being set
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.record.mode = Some(format!("{:o}", mode));
+        self
+    }
+
+    /// Write the record with the operation's outcome
+    pub fn finish<T>(mut self, result: &Result<T>) {
+        match result {
+            Ok(_) => self.record.result = "ok".to_string(),
+            Err(e) => {
+                self.record.result = e.to_string();
+                self.record.errno = Some(e.to_errno());
+            }
+        }
+        self.log.write(&self.record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_sink_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let config = AuditConfig::File { path: path.clone() };
+        let log = Arc::new(AuditLog::open(Path::new("/mnt/data"), &config).unwrap());
+
+        log.event("write", Path::new("/a.txt"), 1000, 100, 42)
+            .size(4096)
+            .finish(&Ok::<_, FuseAdapterError>(()));
+        log.event("rename", Path::new("/a.txt"), 1000, 100, 42)
+            .to(Path::new("/b.txt"))
+            .finish::<()>(&Err(FuseAdapterError::PermissionDenied));
+        log.event("chmod", Path::new("/b.txt"), 0, 0, 1)
+            .mode(0o640)
+            .finish(&Ok::<_, FuseAdapterError>(()));
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["op"], "write");
+        assert_eq!(records[0]["mount"], "/mnt/data");
+        assert_eq!(records[0]["size"], 4096);
+        assert_eq!(records[0]["uid"], 1000);
+        assert_eq!(records[0]["result"], "ok");
+        assert!(records[0].get("errno").is_none());
+        assert_eq!(records[1]["to"], "/b.txt");
+        assert_eq!(records[1]["errno"], libc::EACCES);
+        assert_eq!(records[2]["mode"], "640");
+    }
+}
@@ -6,22 +6,29 @@
 
 use async_trait::async_trait;
 use parking_lot::RwLock;
-use serde::Deserialize;
 use std::error::Error as StdError;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 
 use super::TokenProviderInner;
 
 /// Buffer time before token expiry to trigger refresh (60 seconds).
 const EXPIRY_BUFFER_SECS: u64 = 60;
 
-/// Response structure from the token endpoint.
-#[derive(Debug, Deserialize)]
-struct TokenResponse {
-    access_token: String,
-    /// Token expiry in seconds from now
-    expires_in: Option<u64>,
-}
+/// Lifetime assumed for tokens whose response doesn't give one (1 hour).
+const DEFAULT_EXPIRES_IN_SECS: u64 = 3600;
+
+/// Response field holding the token, unless configured otherwise.
+pub const DEFAULT_TOKEN_FIELD: &str = "access_token";
+
+/// Response field holding the token lifetime, unless configured otherwise.
+pub const DEFAULT_EXPIRES_IN_FIELD: &str = "expires_in";
+
+/// Attempts per token fetch, unless configured otherwise.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, unless configured otherwise.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
 
 /// Cached token with expiry tracking.
 struct CachedToken {
@@ -37,6 +44,11 @@ impl CachedToken {
         let effective_expiry = self.expires_in_secs.saturating_sub(EXPIRY_BUFFER_SECS);
         elapsed < effective_expiry
     }
+
+    /// Check if the token has actually expired (ignoring the buffer).
+    fn is_expired(&self) -> bool {
+        self.fetched_at.elapsed().as_secs() >= self.expires_in_secs
+    }
 }
 
 /// Configuration for the HTTP token provider.
@@ -48,6 +60,39 @@ pub struct HttpTokenProviderConfig {
     pub method: String,
     /// HTTP headers to send with the request.
     pub headers: std::collections::HashMap<String, String>,
+    /// Response field holding the token; dots separate nested fields
+    /// (e.g. `data.token`).
+    pub token_field: String,
+    /// Response field holding the token lifetime in seconds; dots separate
+    /// nested fields.
+    pub expires_in_field: String,
+    /// Total attempts per fetch, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each one.
+    pub initial_backoff: Duration,
+}
+
+/// Why a token fetch failed, and whether trying again might help.
+#[derive(Debug)]
+struct FetchError {
+    message: String,
+    retryable: bool,
+}
+
+impl FetchError {
+    fn retryable(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retryable: true,
+        }
+    }
+
+    fn fatal(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retryable: false,
+        }
+    }
 }
 
 /// A token provider that fetches tokens from an HTTP endpoint.
@@ -55,12 +100,16 @@ pub struct HttpTokenProviderConfig {
 /// This provider is designed to work with internal token services that
 /// manage OAuth tokens on behalf of users. It includes:
 /// - Token caching to minimize API calls
-/// - Automatic refresh 60 seconds before expiry
-/// - Support for custom authentication headers
+/// - Automatic refresh 60 seconds before expiry, by one caller at a time;
+///   if the refresh fails the old token is used until it actually expires
+/// - Retries with exponential backoff on network errors, 429 and 5xx
+/// - Support for custom authentication headers and response fields
 pub struct HttpTokenProvider {
     config: HttpTokenProviderConfig,
     client: reqwest::Client,
     cached_token: RwLock<Option<CachedToken>>,
+    /// Held while fetching, so concurrent callers wait for one fetch
+    refresh: tokio::sync::Mutex<()>,
 }
 
 impl HttpTokenProvider {
@@ -70,13 +119,45 @@ impl HttpTokenProvider {
             config,
             client: reqwest::Client::new(),
             cached_token: RwLock::new(None),
+            refresh: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// The cached token, if it doesn't need refreshing yet.
+    fn valid_token(&self) -> Option<String> {
+        let cache = self.cached_token.read();
+        cache
+            .as_ref()
+            .filter(|cached| cached.is_valid())
+            .map(|cached| cached.token.clone())
+    }
+
+    /// Fetch a fresh token, retrying failures that might be temporary.
+    async fn fetch_with_retry(&self) -> Result<(String, u64), FetchError> {
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.fetch_token().await {
+                Ok(token) => return Ok(token),
+                Err(e) if e.retryable && attempt < self.config.max_attempts => {
+                    debug!(
+                        "Token fetch attempt {} failed, retrying in {:?}: {}",
+                        attempt, backoff, e.message
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /// Fetch a fresh token from the endpoint.
-    async fn fetch_token(&self) -> Result<(String, u64), Box<dyn StdError + Send + Sync>> {
-        let method = reqwest::Method::from_bytes(self.config.method.as_bytes())
-            .map_err(|_| format!("Invalid HTTP method: {}", self.config.method))?;
+    async fn fetch_token(&self) -> Result<(String, u64), FetchError> {
+        let method = reqwest::Method::from_bytes(self.config.method.as_bytes()).map_err(|_| {
+            FetchError::fatal(format!("Invalid HTTP method: {}", self.config.method))
+        })?;
 
         let mut request = self.client.request(method, &self.config.endpoint);
 
@@ -84,23 +165,66 @@ impl HttpTokenProvider {
             request = request.header(key, value);
         }
 
-        let response = request.send().await?;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| FetchError::retryable(e.to_string()))?;
 
-        if !response.status().is_success() {
-            let status = response.status();
+        let status = response.status();
+        if !status.is_success() {
             let body = response.text().await.unwrap_or_default();
-            return Err(format!("Token endpoint returned error {}: {}", status, body).into());
+            let message = format!("Token endpoint returned error {}: {}", status, body);
+            return Err(
+                if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    FetchError::retryable(message)
+                } else {
+                    FetchError::fatal(message)
+                },
+            );
         }
 
-        let token_response: TokenResponse = response.json().await?;
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| FetchError::retryable(format!("Invalid token response: {}", e)))?;
+        self.parse_response(&body).map_err(FetchError::fatal)
+    }
 
-        // Default to 1 hour if expires_in not provided
-        let expires_in = token_response.expires_in.unwrap_or(3600);
+    /// Pull the token and its lifetime out of a token endpoint response.
+    fn parse_response(&self, body: &serde_json::Value) -> Result<(String, u64), String> {
+        let token = field(body, &self.config.token_field)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                format!(
+                    "Token response has no string field {:?}",
+                    self.config.token_field
+                )
+            })?;
 
-        Ok((token_response.access_token, expires_in))
+        // Default to 1 hour if the lifetime isn't provided
+        let expires_in = match field(body, &self.config.expires_in_field) {
+            None | Some(serde_json::Value::Null) => DEFAULT_EXPIRES_IN_SECS,
+            Some(value) => value
+                .as_u64()
+                .or_else(|| value.as_f64().filter(|s| *s >= 0.0).map(|s| s as u64))
+                .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+                .ok_or_else(|| {
+                    format!(
+                        "Token response field {:?} is not a number of seconds: {}",
+                        self.config.expires_in_field, value
+                    )
+                })?,
+        };
+
+        Ok((token.to_string(), expires_in))
     }
 }
 
+/// The value at a dotted field path in a JSON object.
+fn field<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
 #[async_trait]
 impl TokenProviderInner for HttpTokenProvider {
     async fn get_token(
@@ -108,17 +232,28 @@ impl TokenProviderInner for HttpTokenProvider {
         _scopes: &[&str],
     ) -> Result<Option<String>, Box<dyn StdError + Send + Sync>> {
         // Check if we have a valid cached token
-        {
-            let cache = self.cached_token.read();
-            if let Some(cached) = cache.as_ref() {
-                if cached.is_valid() {
+        if let Some(token) = self.valid_token() {
+            return Ok(Some(token));
+        }
+
+        // One caller refreshes; the rest wait and use its token
+        let _refresh = self.refresh.lock().await;
+        if let Some(token) = self.valid_token() {
+            return Ok(Some(token));
+        }
+
+        let (token, expires_in) = match self.fetch_with_retry().await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                // Keep using a token that's due for refresh but not expired
+                let cache = self.cached_token.read();
+                if let Some(cached) = cache.as_ref().filter(|c| !c.is_expired()) {
+                    warn!("Token refresh failed, using current token: {}", e.message);
                     return Ok(Some(cached.token.clone()));
                 }
+                return Err(e.message.into());
             }
-        }
-
-        // Need to fetch a new token
-        let (token, expires_in) = self.fetch_token().await?;
+        };
 
         // Cache the new token
         {
@@ -137,7 +272,48 @@ impl TokenProviderInner for HttpTokenProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Duration;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn config(endpoint: String) -> HttpTokenProviderConfig {
+        HttpTokenProviderConfig {
+            endpoint,
+            method: "GET".to_string(),
+            headers: Default::default(),
+            token_field: DEFAULT_TOKEN_FIELD.to_string(),
+            expires_in_field: DEFAULT_EXPIRES_IN_FIELD.to_string(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(1),
+        }
+    }
+
+    /// Serve `responses` (status, body) in order, repeating the last one;
+    /// returns the endpoint and a count of requests served
+    async fn serve(responses: Vec<(u16, &'static str)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/token", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let count = served.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request).await;
+                let n = count.fetch_add(1, Ordering::SeqCst);
+                let (status, body) = responses[n.min(responses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (endpoint, served)
+    }
 
     #[test]
     fn test_cached_token_validity() {
@@ -158,6 +334,7 @@ mod tests {
             expires_in_secs: 3600,
         };
         assert!(!cached.is_valid());
+        assert!(!cached.is_expired());
     }
 
     #[test]
@@ -168,5 +345,89 @@ mod tests {
             expires_in_secs: 3600,
         };
         assert!(!cached.is_valid());
+        assert!(cached.is_expired());
+    }
+
+    #[test]
+    fn test_parse_response_fields() {
+        let mut config = config(String::new());
+        let provider = HttpTokenProvider::new(config.clone());
+        let body = serde_json::json!({"access_token": "abc", "expires_in": 120});
+        assert_eq!(
+            provider.parse_response(&body).unwrap(),
+            ("abc".to_string(), 120)
+        );
+        let body = serde_json::json!({"access_token": "abc"});
+        assert_eq!(provider.parse_response(&body).unwrap().1, 3600);
+        assert!(provider
+            .parse_response(&serde_json::json!({"token": "abc"}))
+            .is_err());
+
+        config.token_field = "data.token".to_string();
+        config.expires_in_field = "data.ttl".to_string();
+        let provider = HttpTokenProvider::new(config);
+        let body = serde_json::json!({"data": {"token": "xyz", "ttl": "90"}});
+        assert_eq!(
+            provider.parse_response(&body).unwrap(),
+            ("xyz".to_string(), 90)
+        );
+        let body = serde_json::json!({"data": {"token": "xyz", "ttl": "soon"}});
+        assert!(provider.parse_response(&body).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_caches() {
+        let (endpoint, served) = serve(vec![
+            (503, "{}"),
+            (429, "{}"),
+            (200, r#"{"access_token": "fresh", "expires_in": 3600}"#),
+        ])
+        .await;
+        let provider = Arc::new(HttpTokenProvider::new(config(endpoint)));
+
+        // Concurrent callers share one fetch
+        let calls: Vec<_> = (0..4)
+            .map(|_| {
+                let provider = provider.clone();
+                tokio::spawn(async move { provider.get_token(&[]).await.unwrap() })
+            })
+            .collect();
+        for call in calls {
+            assert_eq!(call.await.unwrap(), Some("fresh".to_string()));
+        }
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let (endpoint, served) = serve(vec![(401, "denied")]).await;
+        let provider = HttpTokenProvider::new(config(endpoint));
+        let err = provider.get_token(&[]).await.unwrap_err();
+        assert!(err.to_string().contains("401"));
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_keeps_unexpired_token() {
+        let (endpoint, _) = serve(vec![(500, "down")]).await;
+        let provider = HttpTokenProvider::new(config(endpoint));
+        *provider.cached_token.write() = Some(CachedToken {
+            token: "old".to_string(),
+            fetched_at: Instant::now() - Duration::from_secs(3580),
+            expires_in_secs: 3600,
+        });
+        assert_eq!(
+            provider.get_token(&[]).await.unwrap(),
+            Some("old".to_string())
+        );
+
+        // Once it has expired the failure is reported
+        provider
+            .cached_token
+            .write()
+            .as_mut()
+            .unwrap()
+            .expires_in_secs = 3000;
+        assert!(provider.get_token(&[]).await.is_err());
     }
 }
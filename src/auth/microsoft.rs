@@ -0,0 +1,529 @@
+//! Microsoft identity platform token provider.
+//!
+//! Tokens for Microsoft Graph come from the tenant's OAuth 2.0 endpoint,
+//! either as the application itself (client credentials, for SharePoint
+//! sites and other users' drives) or as a user who signs in once with a
+//! device code. For the device code grant the refresh token is saved to
+//! disk, so later starts don't ask again.
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use super::TokenProviderInner;
+
+/// Login endpoint of the global Azure cloud.
+pub const DEFAULT_AUTHORITY: &str = "https://login.microsoftonline.com";
+
+/// Seconds before expiry at which a token is refreshed.
+const EXPIRY_BUFFER_SECS: u64 = 60;
+
+/// Grant type for redeeming a device code.
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// How a token is obtained.
+enum Grant {
+    /// The application signs in with its own secret
+    ClientCredentials { client_secret: String },
+    /// A user signs in on another device; tokens are kept in `token_path`
+    DeviceCode { token_path: PathBuf },
+}
+
+/// Successful token endpoint response.
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: u64,
+    refresh_token: Option<String>,
+}
+
+/// Error response from the token and device code endpoints.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+/// Response starting a device code sign-in.
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// What is kept in the token file between runs.
+#[derive(Deserialize, serde::Serialize)]
+struct SavedTokens {
+    refresh_token: String,
+}
+
+/// Access token with its expiry.
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+/// A token provider for Microsoft Graph, signing in through the Microsoft
+/// identity platform (Entra ID).
+///
+/// Tokens are cached until 60 seconds before they expire, and refreshed by
+/// one caller at a time.
+pub struct MicrosoftTokenProvider {
+    client: reqwest::Client,
+    /// `{authority}/{tenant}/oauth2/v2.0`
+    base_url: String,
+    client_id: String,
+    scope: String,
+    grant: Grant,
+    cached_token: RwLock<Option<CachedToken>>,
+    /// Held while fetching, so concurrent callers wait for one fetch
+    refresh: tokio::sync::Mutex<()>,
+    /// Refresh token from the last device code sign-in or refresh
+    refresh_token: RwLock<Option<String>>,
+}
+
+impl MicrosoftTokenProvider {
+    /// Sign in as the application registered as `client_id`.
+    ///
+    /// `scope` is the resource's `.default` scope, e.g.
+    /// `https://graph.microsoft.com/.default`.
+    pub fn client_credentials(
+        authority: &str,
+        tenant_id: &str,
+        client_id: &str,
+        client_secret: &str,
+        scope: &str,
+    ) -> Self {
+        Self::new(
+            authority,
+            tenant_id,
+            client_id,
+            scope.to_string(),
+            Grant::ClientCredentials {
+                client_secret: client_secret.to_string(),
+            },
+        )
+    }
+
+    /// Sign in as a user through the device code flow, keeping the refresh
+    /// token in `token_path`.
+    ///
+    /// `offline_access` is added to `scope` so a refresh token is issued.
+    pub fn device_code(
+        authority: &str,
+        tenant_id: &str,
+        client_id: &str,
+        token_path: &Path,
+        scope: &str,
+    ) -> Self {
+        let provider = Self::new(
+            authority,
+            tenant_id,
+            client_id,
+            format!("{} offline_access", scope),
+            Grant::DeviceCode {
+                token_path: token_path.to_path_buf(),
+            },
+        );
+        if let Ok(content) = std::fs::read(token_path) {
+            match serde_json::from_slice::<SavedTokens>(&content) {
+                Ok(saved) => *provider.refresh_token.write() = Some(saved.refresh_token),
+                Err(e) => warn!("Ignoring unreadable token file {:?}: {}", token_path, e),
+            }
+        }
+        provider
+    }
+
+    fn new(authority: &str, tenant_id: &str, client_id: &str, scope: String, grant: Grant) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: format!(
+                "{}/{}/oauth2/v2.0",
+                authority.trim_end_matches('/'),
+                tenant_id
+            ),
+            client_id: client_id.to_string(),
+            scope,
+            grant,
+            cached_token: RwLock::new(None),
+            refresh: tokio::sync::Mutex::new(()),
+            refresh_token: RwLock::new(None),
+        }
+    }
+
+    /// The cached token, if it doesn't need refreshing yet.
+    fn valid_token(&self) -> Option<String> {
+        let cache = self.cached_token.read();
+        cache
+            .as_ref()
+            .filter(|cached| {
+                Instant::now() + Duration::from_secs(EXPIRY_BUFFER_SECS) < cached.expires_at
+            })
+            .map(|cached| cached.token.clone())
+    }
+
+    /// Get a new access token for the configured grant.
+    async fn fetch_token(&self) -> Result<TokenResponse, String> {
+        match &self.grant {
+            Grant::ClientCredentials { client_secret } => self
+                .request_token(&[
+                    ("grant_type", "client_credentials"),
+                    ("client_secret", client_secret),
+                ])
+                .await
+                .map_err(|e| e.to_string()),
+            Grant::DeviceCode { token_path } => {
+                let saved = self.refresh_token.read().clone();
+                if let Some(refresh_token) = saved {
+                    match self
+                        .request_token(&[
+                            ("grant_type", "refresh_token"),
+                            ("refresh_token", &refresh_token),
+                        ])
+                        .await
+                    {
+                        Ok(token) => {
+                            self.save_refresh_token(token_path, &token);
+                            return Ok(token);
+                        }
+                        // The user has to sign in again
+                        Err(TokenError::Rejected(e)) if e.error == "invalid_grant" => {
+                            warn!("Saved Microsoft sign-in is no longer valid: {}", e);
+                        }
+                        Err(e) => return Err(e.to_string()),
+                    }
+                }
+                let token = self.sign_in().await?;
+                self.save_refresh_token(token_path, &token);
+                Ok(token)
+            }
+        }
+    }
+
+    /// Ask the user to sign in with a device code and wait until they do.
+    async fn sign_in(&self) -> Result<TokenResponse, String> {
+        let response = self
+            .client
+            .post(format!("{}/devicecode", self.base_url))
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("scope", &self.scope),
+            ])
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("Device code request returned {}: {}", status, body));
+        }
+        let device: DeviceCodeResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid device code response: {}", e))?;
+
+        // The log, unlike stdout, is still around when running as a daemon
+        warn!(
+            "Microsoft sign-in required: enter code {} at {} within {} minutes",
+            device.user_code,
+            device.verification_uri,
+            device.expires_in / 60
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+        let mut interval = Duration::from_secs(device.interval);
+        loop {
+            tokio::time::sleep(interval).await;
+            match self
+                .request_token(&[
+                    ("grant_type", DEVICE_CODE_GRANT),
+                    ("device_code", &device.device_code),
+                ])
+                .await
+            {
+                Ok(token) => return Ok(token),
+                Err(TokenError::Rejected(e)) if e.error == "authorization_pending" => {}
+                Err(TokenError::Rejected(e)) if e.error == "slow_down" => {
+                    interval += Duration::from_secs(5);
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+            if Instant::now() >= deadline {
+                return Err("Microsoft sign-in was not completed in time".to_string());
+            }
+        }
+    }
+
+    /// Post to the token endpoint with the client ID and scope added.
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<TokenResponse, TokenError> {
+        let mut form = vec![
+            ("client_id", self.client_id.as_str()),
+            ("scope", self.scope.as_str()),
+        ];
+        form.extend_from_slice(params);
+
+        let response = self
+            .client
+            .post(format!("{}/token", self.base_url))
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| TokenError::Failed(e.to_string()))?;
+
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| TokenError::Failed(e.to_string()))?;
+        if status.is_success() {
+            serde_json::from_slice(&body)
+                .map_err(|e| TokenError::Failed(format!("Invalid token response: {}", e)))
+        } else {
+            match serde_json::from_slice::<ErrorResponse>(&body) {
+                Ok(error) => Err(TokenError::Rejected(error)),
+                Err(_) => Err(TokenError::Failed(format!(
+                    "Token endpoint returned {}: {}",
+                    status,
+                    String::from_utf8_lossy(&body)
+                ))),
+            }
+        }
+    }
+
+    /// Keep a newly issued refresh token, in memory and in the token file.
+    fn save_refresh_token(&self, token_path: &Path, token: &TokenResponse) {
+        let Some(refresh_token) = &token.refresh_token else {
+            return;
+        };
+        *self.refresh_token.write() = Some(refresh_token.clone());
+
+        let saved = SavedTokens {
+            refresh_token: refresh_token.clone(),
+        };
+        if let Err(e) = write_private(token_path, &serde_json::to_vec(&saved).unwrap()) {
+            warn!("Failed to save Microsoft tokens to {:?}: {}", token_path, e);
+        }
+    }
+}
+
+/// Why a token request failed.
+enum TokenError {
+    /// The endpoint answered with an OAuth error code
+    Rejected(ErrorResponse),
+    /// The request didn't get a usable answer
+    Failed(String),
+}
+
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.error_description.lines().next() {
+            Some(description) => write!(f, "{}: {}", self.error, description),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::Rejected(e) => write!(f, "Token request rejected: {}", e),
+            TokenError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Write a file only the current user can read, creating its directory.
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(content)
+}
+
+#[async_trait]
+impl TokenProviderInner for MicrosoftTokenProvider {
+    async fn get_token(
+        &self,
+        _scopes: &[&str],
+    ) -> Result<Option<String>, Box<dyn StdError + Send + Sync>> {
+        if let Some(token) = self.valid_token() {
+            return Ok(Some(token));
+        }
+
+        // One caller refreshes; the rest wait and use its token
+        let _refresh = self.refresh.lock().await;
+        if let Some(token) = self.valid_token() {
+            return Ok(Some(token));
+        }
+
+        debug!("Fetching Microsoft Graph token from {}", self.base_url);
+        let response = self.fetch_token().await?;
+        *self.cached_token.write() = Some(CachedToken {
+            token: response.access_token.clone(),
+            expires_at: Instant::now() + Duration::from_secs(response.expires_in),
+        });
+        Ok(Some(response.access_token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `responses` (status, body) in order, repeating the last one;
+    /// returns the authority and the request bodies received
+    async fn serve(responses: Vec<(u16, &'static str)>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let authority = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let body = read_body(&mut stream).await;
+                let index = {
+                    let mut seen = seen.lock().unwrap();
+                    seen.push(body);
+                    seen.len() - 1
+                };
+                let (status, body) = responses[index.min(responses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (authority, requests)
+    }
+
+    /// Read one request and return its body
+    async fn read_body(stream: &mut tokio::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap_or(0);
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse().unwrap_or(0))
+                    })
+                    .unwrap_or(0);
+                if body.len() >= length || n == 0 {
+                    return body.to_string();
+                }
+            } else if n == 0 {
+                return String::new();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials() {
+        let (authority, requests) = serve(vec![(
+            200,
+            r#"{"access_token": "app", "expires_in": 3600}"#,
+        )])
+        .await;
+        let provider = MicrosoftTokenProvider::client_credentials(
+            &authority,
+            "contoso",
+            "client",
+            "secret",
+            "https://graph.microsoft.com/.default",
+        );
+
+        assert_eq!(
+            provider.get_token(&[]).await.unwrap().as_deref(),
+            Some("app")
+        );
+        assert_eq!(
+            provider.get_token(&[]).await.unwrap().as_deref(),
+            Some("app")
+        );
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("grant_type=client_credentials"));
+        assert!(requests[0].contains("client_secret=secret"));
+    }
+
+    #[tokio::test]
+    async fn test_device_code_signs_in_once() {
+        let dir = TempDir::new().unwrap();
+        let token_path = dir.path().join("tokens").join("onedrive.json");
+        let (authority, requests) = serve(vec![
+            (
+                200,
+                r#"{"device_code": "dc", "user_code": "ABC", "verification_uri": "https://microsoft.com/devicelogin", "expires_in": 900, "interval": 0}"#,
+            ),
+            (400, r#"{"error": "authorization_pending"}"#),
+            (
+                200,
+                r#"{"access_token": "user", "expires_in": 3600, "refresh_token": "r1"}"#,
+            ),
+            (
+                200,
+                r#"{"access_token": "again", "expires_in": 3600, "refresh_token": "r2"}"#,
+            ),
+        ])
+        .await;
+
+        let provider =
+            MicrosoftTokenProvider::device_code(&authority, "common", "client", &token_path, "s");
+        assert_eq!(
+            provider.get_token(&[]).await.unwrap().as_deref(),
+            Some("user")
+        );
+        let saved: SavedTokens =
+            serde_json::from_slice(&std::fs::read(&token_path).unwrap()).unwrap();
+        assert_eq!(saved.refresh_token, "r1");
+
+        // A new provider picks up the saved sign-in instead of asking again
+        let provider =
+            MicrosoftTokenProvider::device_code(&authority, "common", "client", &token_path, "s");
+        assert_eq!(
+            provider.get_token(&[]).await.unwrap().as_deref(),
+            Some("again")
+        );
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[0].contains("offline_access"));
+        assert!(requests[3].contains("refresh_token=r1"));
+    }
+}
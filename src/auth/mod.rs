@@ -1,26 +1,38 @@
-//! Token provider abstractions for Google Drive authentication
+//! Token provider abstractions for Google Drive and OneDrive authentication
 //!
 //! This module provides a flexible authentication system supporting multiple
 //! token sources:
 //! - Service account credentials (existing behavior)
+//! - OAuth sign-in as a user account, with saved refresh tokens
 //! - HTTP-based token providers (for dynamic token fetching)
 //! - Static tokens (for testing)
+//! - Microsoft identity platform sign-in for Microsoft Graph
 
 pub mod http;
+pub mod microsoft;
+pub mod oauth;
 pub mod service_account;
 pub mod static_token;
 
 use async_trait::async_trait;
 use google_apis_common::GetToken;
+use hyper_util::client::legacy::connect::HttpConnector;
 use std::error::Error as StdError;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 
 pub use http::HttpTokenProvider;
+pub use microsoft::MicrosoftTokenProvider;
+pub use oauth::OAuthProvider;
 pub use service_account::ServiceAccountProvider;
 pub use static_token::StaticTokenProvider;
 
+/// yup_oauth2 authenticator over the default HTTPS client
+type Authenticator = google_drive3::yup_oauth2::authenticator::Authenticator<
+    hyper_rustls::HttpsConnector<HttpConnector>,
+>;
+
 /// Inner trait for token providers.
 ///
 /// This trait defines the interface that all token providers must implement.
@@ -0,0 +1,164 @@
+//! OAuth token provider for user accounts.
+//!
+//! This provider signs in as a regular Google account instead of a service
+//! account, using an OAuth client created in the Google Cloud Console. The
+//! first token needs the user to approve access in a browser, either by
+//! entering a code shown in the log (device flow) or by visiting a link that
+//! redirects back to a local port (redirect flow). The refresh token is then
+//! saved to disk, so later starts don't ask again.
+
+use async_trait::async_trait;
+use google_drive3::yup_oauth2::authenticator_delegate::{
+    DeviceAuthResponse, DeviceFlowDelegate, InstalledFlowDelegate,
+};
+use google_drive3::yup_oauth2::{
+    read_application_secret, DeviceFlowAuthenticator, InstalledFlowAuthenticator,
+    InstalledFlowReturnMethod,
+};
+use std::error::Error as StdError;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use tracing::warn;
+
+use super::{Authenticator, TokenProviderInner};
+use crate::config::OAuthFlow;
+
+/// A token provider signing in as a user through an OAuth consent flow.
+///
+/// Tokens, including the refresh token, are persisted to a file and
+/// refreshed automatically.
+pub struct OAuthProvider {
+    auth: Authenticator,
+}
+
+impl OAuthProvider {
+    /// Create a provider for the OAuth client in `client_secret_path`,
+    /// keeping tokens in `token_path`. The redirect flow listens on
+    /// `redirect_port`, or a random port if unset.
+    ///
+    /// The client secret file is the JSON downloaded from the Google Cloud
+    /// Console for a "Desktop app" (or "TVs and limited input devices" for
+    /// the device flow) OAuth client.
+    pub async fn new(
+        client_secret_path: &Path,
+        flow: OAuthFlow,
+        redirect_port: Option<u16>,
+        token_path: &Path,
+    ) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        let secret = read_application_secret(client_secret_path).await?;
+
+        let auth = match flow {
+            OAuthFlow::Device => {
+                DeviceFlowAuthenticator::builder(secret)
+                    .flow_delegate(Box::new(LogPrompt))
+                    .persist_tokens_to_disk(token_path)
+                    .build()
+                    .await?
+            }
+            OAuthFlow::Redirect => {
+                let method = match redirect_port {
+                    Some(port) => InstalledFlowReturnMethod::HTTPPortRedirect(port),
+                    None => InstalledFlowReturnMethod::HTTPRedirect,
+                };
+                InstalledFlowAuthenticator::builder(secret, method)
+                    .flow_delegate(Box::new(LogPrompt))
+                    .persist_tokens_to_disk(token_path)
+                    .build()
+                    .await?
+            }
+        };
+
+        Ok(Self { auth })
+    }
+}
+
+#[async_trait]
+impl TokenProviderInner for OAuthProvider {
+    async fn get_token(
+        &self,
+        scopes: &[&str],
+    ) -> Result<Option<String>, Box<dyn StdError + Send + Sync>> {
+        let token = self.auth.token(scopes).await?;
+        Ok(token.token().map(|t| t.to_string()))
+    }
+}
+
+/// Asks the user to approve access through the log, which (unlike stdout)
+/// is still around when running as a daemon.
+struct LogPrompt;
+
+impl DeviceFlowDelegate for LogPrompt {
+    fn present_user_code<'a>(
+        &'a self,
+        response: &'a DeviceAuthResponse,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            warn!(
+                "Google Drive sign-in required: enter code {} at {} before {}",
+                response.user_code, response.verification_uri, response.expires_at
+            );
+        })
+    }
+}
+
+impl InstalledFlowDelegate for LogPrompt {
+    fn present_user_url<'a>(
+        &'a self,
+        url: &'a str,
+        need_code: bool,
+    ) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+        Box::pin(async move {
+            if need_code {
+                return Err("interactive code entry is not supported".to_string());
+            }
+            warn!(
+                "Google Drive sign-in required: open {} in a browser that can reach this machine",
+                url
+            );
+            Ok(String::new())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const CLIENT_SECRET: &str = r#"{
+  "installed": {
+    "client_id": "123.apps.googleusercontent.com",
+    "client_secret": "secret",
+    "auth_uri": "https://accounts.google.com/o/oauth2/auth",
+    "token_uri": "https://oauth2.googleapis.com/token",
+    "redirect_uris": ["http://localhost"]
+  }
+}"#;
+
+    #[tokio::test]
+    async fn test_new_reads_client_secret() {
+        let dir = TempDir::new().unwrap();
+        let secret = dir.path().join("client_secret.json");
+        let tokens = dir.path().join("tokens.json");
+        std::fs::write(&secret, CLIENT_SECRET).unwrap();
+
+        assert!(
+            OAuthProvider::new(&secret, OAuthFlow::Device, None, &tokens)
+                .await
+                .is_ok()
+        );
+        assert!(
+            OAuthProvider::new(&secret, OAuthFlow::Redirect, Some(8085), &tokens)
+                .await
+                .is_ok()
+        );
+
+        let missing = dir.path().join("missing.json");
+        assert!(
+            OAuthProvider::new(&missing, OAuthFlow::Device, None, &tokens)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -5,15 +5,10 @@
 
 use async_trait::async_trait;
 use google_drive3::yup_oauth2::{read_service_account_key, ServiceAccountAuthenticator};
-use hyper_util::client::legacy::connect::HttpConnector;
 use std::error::Error as StdError;
 use std::path::Path;
 
-use super::TokenProviderInner;
-
-type Authenticator = google_drive3::yup_oauth2::authenticator::Authenticator<
-    hyper_rustls::HttpsConnector<HttpConnector>,
->;
+use super::{Authenticator, TokenProviderInner};
 
 /// A token provider using Google Cloud service account credentials.
 ///
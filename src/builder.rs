@@ -0,0 +1,883 @@
+//! High-level API for embedding mounts in an application
+//!
+//! `FuseAdapterBuilder` assembles the same stack the daemon builds for each
+//! configured mount (backend, encryption, compression, cache and overlays),
+//! mounts it and returns a `MountedAdapter` to flush or unmount it.
+//!
+//! ```no_run
+//! use fuse_adapter::builder::FuseAdapterBuilder;
+//! use fuse_adapter::cache::CacheConfig;
+//! # use fuse_adapter::connector::Connector;
+//!
+//! # async fn example(connector: impl Connector + 'static) -> fuse_adapter::Result<()> {
+//! let mount = FuseAdapterBuilder::new("/mnt/data", connector)
+//!     .with_cache(CacheConfig::default())
+//!     .mount()
+//!     .await?;
+//!
+//! // ... use /mnt/data ...
+//!
+//! mount.flush().await?;
+//! mount.unmount().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tracing::{debug, error, warn};
+
+use crate::audit::AuditLog;
+use crate::cache::filesystem::{FilesystemCache, FilesystemCacheConfig};
+use crate::cache::memory::{MemoryCache, MemoryCacheConfig};
+use crate::cache::none::NoCache;
+use crate::cache::peer::{PeerLink, PeerSharing};
+use crate::cache::CacheConfig;
+use crate::config::{ConnectorConfig, ErrorMode, MountConfig};
+use crate::connector::archive::ArchiveConnector;
+use crate::connector::breaker::CircuitBreakerConnector;
+use crate::connector::compressed::CompressedConnector;
+use crate::connector::encrypted::{EncryptedConnector, EncryptionKey};
+use crate::connector::gdrive::GDriveConnector;
+use crate::connector::kv::KvConnector;
+use crate::connector::memfs::MemfsConnector;
+use crate::connector::onedrive::OneDriveConnector;
+use crate::connector::registry;
+use crate::connector::routing::RoutingConnector;
+use crate::connector::s3::S3Connector;
+use crate::connector::throttle::{BandwidthLimits, ThrottledConnector};
+use crate::connector::traced::TracedConnector;
+use crate::connector::union::UnionConnector;
+use crate::connector::Connector;
+use crate::error::{FuseAdapterError, Result};
+use crate::events::ErrorEvents;
+use crate::fuse::access::AccessControl;
+use crate::integrity::IntegrityMonitor;
+use crate::mount::{self, MountHandle, MountManager, MountOptions};
+use crate::overlay::{
+    HideOverlay, QuotaOverlay, StatusOverlay, TrashOverlay, VersionsOverlay, WriteAccessOverlay,
+};
+use crate::quota::QuotaTracker;
+use crate::write_access::{WriteAccess, WriteAccessMonitor};
+
+/// How long `unmount` waits for the final sync by default
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wraps a caller-supplied connector in the configured cache
+type WrapFn =
+    Box<dyn FnOnce(&CacheConfig) -> std::result::Result<Arc<dyn Connector>, String> + Send>;
+
+/// Where the mount's connector comes from
+enum Source {
+    /// A connector built by the caller
+    Connector(WrapFn),
+    /// A mount entry, as in the config file
+    Config(Box<MountConfig>),
+}
+
+/// Builds and mounts a connector stack
+pub struct FuseAdapterBuilder {
+    path: PathBuf,
+    source: Source,
+    /// Cache in front of the connector (overrides the config's)
+    cache: Option<CacheConfig>,
+    /// FUSE session options (overrides those derived from the config)
+    options: Option<MountOptions>,
+    /// Bandwidth limits shared with other mounts
+    global_limits: BandwidthLimits,
+    /// Other adapters sharing cached content
+    peers: Option<Arc<PeerSharing>>,
+    /// How long `unmount` waits for the final sync
+    shutdown_timeout: Duration,
+}
+
+impl FuseAdapterBuilder {
+    /// Mount `connector` at `path`, uncached and with default options
+    pub fn new<C: Connector + 'static>(path: impl Into<PathBuf>, connector: C) -> Self {
+        let wrap: WrapFn = Box::new(move |cache| {
+            wrap_with_cache(connector, cache, None, None, None).map_err(|e| e.to_string())
+        });
+        Self::with_source(path.into(), Source::Connector(wrap))
+    }
+
+    /// Mount the stack described by a mount entry from the config file,
+    /// including its overlays, health checks and error handling
+    pub fn from_config(config: MountConfig) -> Self {
+        Self::with_source(config.path.clone(), Source::Config(Box::new(config)))
+    }
+
+    fn with_source(path: PathBuf, source: Source) -> Self {
+        Self {
+            path,
+            source,
+            cache: None,
+            options: None,
+            global_limits: BandwidthLimits::from_config(None),
+            peers: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// Put `cache` in front of the connector
+    pub fn with_cache(mut self, cache: CacheConfig) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Use `options` for the FUSE session instead of the defaults (or the
+    /// options derived from the mount entry)
+    pub fn with_options(mut self, options: MountOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Throttle backend traffic by `limits` too, which may be shared with
+    /// other mounts (mount entries only)
+    pub fn with_global_limits(mut self, limits: BandwidthLimits) -> Self {
+        self.global_limits = limits;
+        self
+    }
+
+    /// Share cached content with the adapters in `peers`, which may be
+    /// shared with other mounts (mount entries only)
+    pub fn with_cache_peers(mut self, peers: Arc<PeerSharing>) -> Self {
+        self.peers = Some(peers);
+        self
+    }
+
+    /// Wait at most `timeout` for pending changes to sync on unmount
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Mount with a mount manager of its own
+    pub async fn mount(self) -> Result<MountedAdapter> {
+        let manager = Arc::new(MountManager::new(Handle::current()));
+        self.mount_with(&manager).await
+    }
+
+    /// Mount alongside the other mounts of `manager`
+    pub async fn mount_with(self, manager: &Arc<MountManager>) -> Result<MountedAdapter> {
+        let path = self.path;
+        let (connector, derived, health_check) = match self.source {
+            Source::Connector(wrap) => {
+                let cache = self.cache.unwrap_or_default();
+                let connector = wrap(&cache).map_err(FuseAdapterError::Cache)?;
+                (connector, MountOptions::default(), None)
+            }
+            Source::Config(mut config) => {
+                if let Some(cache) = self.cache {
+                    config.cache = cache;
+                }
+                let peers = self.peers.map(|p| p.link(&config.path));
+                let (connector, options) = build_stack(&config, &self.global_limits, peers).await?;
+                (connector, options, config.health_check.clone())
+            }
+        };
+        let options = self.options.unwrap_or(derived);
+
+        // A crashed previous run leaves an unusable mount behind; clear it
+        // first so the existence check below works
+        mount::cleanup_stale_mount(&path)?;
+
+        if !path.exists() {
+            debug!("Creating mount point directory {:?}", path);
+            std::fs::create_dir_all(&path)?;
+        }
+
+        manager.mount(path.clone(), connector.clone(), options)?;
+        if let Some(health_check) = health_check {
+            manager.watch(path.clone(), health_check);
+        }
+
+        Ok(MountedAdapter {
+            manager: manager.clone(),
+            path,
+            connector,
+            shutdown_timeout: self.shutdown_timeout,
+        })
+    }
+}
+
+/// A mounted connector stack
+///
+/// Dropping the last reference to its manager unmounts it without waiting
+/// for pending changes to sync; call `unmount` to sync them first.
+pub struct MountedAdapter {
+    manager: Arc<MountManager>,
+    path: PathBuf,
+    connector: Arc<dyn Connector>,
+    shutdown_timeout: Duration,
+}
+
+impl MountedAdapter {
+    /// Where the stack is mounted
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The outermost layer of the stack, as served over FUSE
+    pub fn connector(&self) -> &Arc<dyn Connector> {
+        &self.connector
+    }
+
+    /// A handle observing the mount's state (None once unmounted)
+    pub fn handle(&self) -> Option<MountHandle> {
+        self.manager.mount_handle(&self.path)
+    }
+
+    /// Sync all pending changes to the backend now
+    pub async fn flush(&self) -> Result<()> {
+        self.connector.sync_all().await
+    }
+
+    /// Unmount, then wait for pending changes to sync
+    pub async fn unmount(self) -> Result<()> {
+        self.manager.remove(&self.path, self.shutdown_timeout).await
+    }
+}
+
+/// Build the connector stack for a mount entry and the FUSE session options
+/// it asks for
+async fn build_stack(
+    mount_config: &MountConfig,
+    global_limits: &BandwidthLimits,
+    peers: Option<PeerLink>,
+) -> Result<(Arc<dyn Connector>, MountOptions)> {
+    // Use per-mount error_mode (already resolved from global default)
+    let error_mode = mount_config.error_mode;
+    let has_status_overlay = mount_config.status_overlay.is_some();
+
+    // Integrity verification happens when a cache fills from the backend
+    let integrity = mount_config
+        .integrity
+        .as_ref()
+        .map(|c| Arc::new(IntegrityMonitor::new(c.clone())));
+    if integrity.is_some() && matches!(mount_config.cache, CacheConfig::None) {
+        warn!(
+            "Integrity checking for {:?} requires a memory or filesystem cache; reads will not be verified",
+            mount_config.path
+        );
+    }
+    if peers.is_some() && matches!(mount_config.cache, CacheConfig::None) {
+        warn!(
+            "Sharing cached content for {:?} requires a memory or filesystem cache; peers will not be used",
+            mount_config.path
+        );
+    }
+
+    // Runtime errors from the adapter and cache surface in the status overlay
+    let errors = mount_config
+        .status_overlay
+        .as_ref()
+        .map(|c| Arc::new(ErrorEvents::new(c.max_log_entries)));
+
+    // Lost write access is noticed beneath the cache and enforced above it
+    let write_access = mount_config
+        .read_only_fallback
+        .as_ref()
+        .map(|c| Arc::new(WriteAccess::new(c, errors.clone())));
+
+    // Open the audit log up front so a bad destination fails the mount
+    let audit = match &mount_config.audit {
+        Some(config) => match AuditLog::open(&mount_config.path, config) {
+            Ok(log) => Some(Arc::new(log)),
+            Err(e) => return Err(FuseAdapterError::Config(e.to_string())),
+        },
+        None => None,
+    };
+
+    let access = match &mount_config.access {
+        Some(config) => match AccessControl::new(config) {
+            Ok(acl) => Some(Arc::new(acl)),
+            Err(e) => return Err(FuseAdapterError::Config(e.to_string())),
+        },
+        None => None,
+    };
+
+    // Backend traffic is throttled beneath the cache by both the mount's own
+    // limits and the global ones
+    let limits = vec![
+        BandwidthLimits::from_config(mount_config.rate_limit.as_ref()),
+        global_limits.clone(),
+    ];
+
+    // Try to create connector + cache
+    let connector_result = create_connector(
+        mount_config,
+        limits,
+        integrity.clone(),
+        errors.clone(),
+        peers,
+        write_access.clone(),
+    )
+    .await;
+
+    // Handle connector creation result
+    let connector: Arc<dyn Connector> = match connector_result {
+        Ok(c) => {
+            // Reject changes the backend won't accept right above the cache
+            let c: Arc<dyn Connector> = match write_access {
+                Some(access) => Arc::new(WriteAccessOverlay::new(c, access)),
+                None => c,
+            };
+
+            // Enforce quotas right above the cache, seeding usage in the background
+            let quota = mount_config
+                .quota
+                .as_ref()
+                .map(|c| Arc::new(QuotaTracker::new(c)));
+            let c: Arc<dyn Connector> = match &quota {
+                Some(tracker) => {
+                    let (scan_tracker, scan_connector) = (tracker.clone(), c.clone());
+                    tokio::spawn(async move { scan_tracker.scan(scan_connector.as_ref()).await });
+                    Arc::new(QuotaOverlay::new(c, tracker.clone()))
+                }
+                None => c,
+            };
+
+            // Turn deletions into moves into the trash
+            let c: Arc<dyn Connector> = match &mount_config.trash {
+                Some(trash_config) => {
+                    let overlay = Arc::new(TrashOverlay::new(c, trash_config.clone()));
+                    overlay.spawn_purge();
+                    overlay
+                }
+                None => c,
+            };
+
+            // Hide configured paths beneath the virtual overlays
+            let c: Arc<dyn Connector> = if mount_config.hide_patterns.is_empty() {
+                c
+            } else {
+                match HideOverlay::new(c, &mount_config.hide_patterns) {
+                    Ok(overlay) => Arc::new(overlay),
+                    Err(e) => return Err(FuseAdapterError::Config(e.to_string())),
+                }
+            };
+
+            // Wrap with versions overlay if configured
+            let c: Arc<dyn Connector> =
+                if let Some(ref versions_config) = mount_config.versions_overlay {
+                    Arc::new(VersionsOverlay::new(c, versions_config.clone()))
+                } else {
+                    c
+                };
+
+            // Wrap with status overlay if configured
+            if let Some(ref overlay_config) = mount_config.status_overlay {
+                let mut overlay = StatusOverlay::new(c, overlay_config.clone());
+                if let Some(monitor) = integrity {
+                    overlay = overlay.with_integrity(monitor);
+                }
+                if let Some(tracker) = quota {
+                    overlay = overlay.with_quota(tracker);
+                }
+                if let Some(errors) = errors.clone() {
+                    overlay = overlay.with_error_events(errors);
+                }
+                Arc::new(overlay)
+            } else {
+                c
+            }
+        }
+        Err(init_error) => {
+            error!(
+                "Connector failed for {:?}: {}",
+                mount_config.path, init_error
+            );
+
+            // Can we mount with failed connector? Only if status_overlay is enabled and error_mode is Continue
+            if has_status_overlay && error_mode == ErrorMode::Continue {
+                let overlay_config = mount_config.status_overlay.as_ref().unwrap();
+                let mut overlay = StatusOverlay::new_failed(init_error, overlay_config.clone());
+                if let Some(errors) = errors.clone() {
+                    overlay = overlay.with_error_events(errors);
+                }
+                Arc::new(overlay)
+            } else {
+                return Err(FuseAdapterError::Backend(init_error));
+            }
+        }
+    };
+
+    let options = MountOptions {
+        read_only: mount_config.read_only,
+        uid: mount_config.uid,
+        gid: mount_config.gid,
+        operation_timeout: mount_config.operation_timeout,
+        audit,
+        access,
+        error_events: errors,
+        enforce_permissions: mount_config.enforce_permissions,
+        locking: mount_config.locking,
+        page_cache: mount_config.page_cache,
+        unicode_normalization: mount_config.unicode_normalization,
+        write_buffer: mount_config.write_buffer.clone(),
+        max_write: mount_config
+            .max_write
+            .as_deref()
+            .and_then(crate::cache::parse_size)
+            .map(|n| n as u32),
+        max_read: mount_config
+            .max_read
+            .as_deref()
+            .and_then(crate::cache::parse_size)
+            .map(|n| n as u32),
+        macos: mount_config.macos.clone(),
+    };
+    Ok((connector, options))
+}
+
+/// Create one throttled backend of a union, archive or routed connector
+async fn create_layer(
+    config: &ConnectorConfig,
+    limits: &[BandwidthLimits],
+) -> std::result::Result<Arc<dyn Connector>, String> {
+    match config {
+        ConnectorConfig::S3(s3_config) => match S3Connector::new(s3_config.clone()).await {
+            Ok(s3) => Ok(Arc::new(ThrottledConnector::new(s3, limits.to_vec()))),
+            Err(e) => Err(format!("Failed to create S3 connector: {}", e)),
+        },
+        ConnectorConfig::GDrive(gdrive_config) => {
+            match GDriveConnector::new(gdrive_config.clone()).await {
+                Ok(gdrive) => Ok(Arc::new(ThrottledConnector::new(gdrive, limits.to_vec()))),
+                Err(e) => Err(format!("Failed to create GDrive connector: {}", e)),
+            }
+        }
+        ConnectorConfig::Kv(kv_config) => match KvConnector::from_config(kv_config) {
+            Ok(kv) => Ok(Arc::new(ThrottledConnector::new(kv, limits.to_vec()))),
+            Err(e) => Err(format!("Failed to create kv connector: {}", e)),
+        },
+        ConnectorConfig::OneDrive(onedrive_config) => {
+            match OneDriveConnector::new(onedrive_config.clone()) {
+                Ok(onedrive) => Ok(Arc::new(ThrottledConnector::new(onedrive, limits.to_vec()))),
+                Err(e) => Err(format!("Failed to create OneDrive connector: {}", e)),
+            }
+        }
+        ConnectorConfig::Memfs(memfs_config) => match MemfsConnector::from_config(memfs_config) {
+            Ok(memfs) => Ok(Arc::new(ThrottledConnector::new(memfs, limits.to_vec()))),
+            Err(e) => Err(format!("Failed to create memfs connector: {}", e)),
+        },
+        ConnectorConfig::Custom(custom) => {
+            match registry::create(&custom.type_name, &custom.options).await {
+                Ok(c) => Ok(Arc::new(ThrottledConnector::new(c, limits.to_vec()))),
+                Err(e) => Err(format!(
+                    "Failed to create {} connector: {}",
+                    custom.type_name, e
+                )),
+            }
+        }
+        ConnectorConfig::Union(_) | ConnectorConfig::Archive(_) => {
+            Err("Union and archive connectors cannot be nested".to_string())
+        }
+    }
+}
+
+/// Create a mount's backend connector wrapped in encryption and cache layers
+async fn create_connector(
+    mount_config: &MountConfig,
+    limits: Vec<BandwidthLimits>,
+    integrity: Option<Arc<IntegrityMonitor>>,
+    errors: Option<Arc<ErrorEvents>>,
+    peers: Option<PeerLink>,
+    write_access: Option<Arc<WriteAccess>>,
+) -> std::result::Result<Arc<dyn Connector>, String> {
+    let encryption = mount_config
+        .encryption
+        .as_ref()
+        .map(EncryptionKey::load)
+        .transpose()
+        .map_err(|e| format!("Failed to load encryption key: {}", e))?;
+
+    if !mount_config.routes.is_empty() {
+        let default = create_layer(&mount_config.connector, &limits).await?;
+        let mut routes = Vec::with_capacity(mount_config.routes.len());
+        for route in &mount_config.routes {
+            routes.push((
+                route.path.clone(),
+                create_layer(&route.connector, &limits).await?,
+            ));
+        }
+        let router = RoutingConnector::new(default, routes)
+            .map_err(|e| format!("Failed to create routing connector: {}", e))?;
+        return wrap_backend(
+            router,
+            encryption.as_ref(),
+            mount_config,
+            integrity,
+            errors,
+            peers,
+            write_access,
+        )
+        .map_err(|e| format!("Failed to create cache: {}", e));
+    }
+
+    match &mount_config.connector {
+        ConnectorConfig::S3(s3_config) => match S3Connector::new(s3_config.clone()).await {
+            Ok(s3) => match wrap_backend(
+                ThrottledConnector::new(s3, limits),
+                encryption.as_ref(),
+                mount_config,
+                integrity,
+                errors,
+                peers,
+                write_access,
+            ) {
+                Ok(c) => Ok(c),
+                Err(e) => Err(format!("Failed to create cache: {}", e)),
+            },
+            Err(e) => Err(format!("Failed to create S3 connector: {}", e)),
+        },
+        ConnectorConfig::GDrive(gdrive_config) => {
+            match GDriveConnector::new(gdrive_config.clone()).await {
+                Ok(gdrive) => {
+                    match wrap_backend(
+                        ThrottledConnector::new(gdrive, limits),
+                        encryption.as_ref(),
+                        mount_config,
+                        integrity,
+                        errors,
+                        peers,
+                        write_access,
+                    ) {
+                        Ok(c) => Ok(c),
+                        Err(e) => Err(format!("Failed to create cache: {}", e)),
+                    }
+                }
+                Err(e) => Err(format!("Failed to create GDrive connector: {}", e)),
+            }
+        }
+        ConnectorConfig::Kv(kv_config) => match KvConnector::from_config(kv_config) {
+            Ok(kv) => wrap_backend(
+                ThrottledConnector::new(kv, limits),
+                encryption.as_ref(),
+                mount_config,
+                integrity,
+                errors,
+                peers,
+                write_access,
+            )
+            .map_err(|e| format!("Failed to create cache: {}", e)),
+            Err(e) => Err(format!("Failed to create kv connector: {}", e)),
+        },
+        ConnectorConfig::OneDrive(onedrive_config) => {
+            match OneDriveConnector::new(onedrive_config.clone()) {
+                Ok(onedrive) => wrap_backend(
+                    ThrottledConnector::new(onedrive, limits),
+                    encryption.as_ref(),
+                    mount_config,
+                    integrity,
+                    errors,
+                    peers,
+                    write_access,
+                )
+                .map_err(|e| format!("Failed to create cache: {}", e)),
+                Err(e) => Err(format!("Failed to create OneDrive connector: {}", e)),
+            }
+        }
+        ConnectorConfig::Custom(custom) => {
+            match registry::create(&custom.type_name, &custom.options).await {
+                Ok(c) => wrap_backend(
+                    ThrottledConnector::new(c, limits),
+                    encryption.as_ref(),
+                    mount_config,
+                    integrity,
+                    errors,
+                    peers,
+                    write_access,
+                )
+                .map_err(|e| format!("Failed to create cache: {}", e)),
+                Err(e) => Err(format!(
+                    "Failed to create {} connector: {}",
+                    custom.type_name, e
+                )),
+            }
+        }
+        ConnectorConfig::Memfs(memfs_config) => match MemfsConnector::from_config(memfs_config) {
+            Ok(memfs) => wrap_backend(
+                ThrottledConnector::new(memfs, limits),
+                encryption.as_ref(),
+                mount_config,
+                integrity,
+                errors,
+                peers,
+                write_access,
+            )
+            .map_err(|e| format!("Failed to create cache: {}", e)),
+            Err(e) => Err(format!("Failed to create memfs connector: {}", e)),
+        },
+        ConnectorConfig::Archive(archive_config) => {
+            let source = create_layer(&archive_config.source, &limits).await?;
+            let archive =
+                ArchiveConnector::new(source, archive_config.path.clone(), archive_config.format);
+            wrap_backend(
+                archive,
+                encryption.as_ref(),
+                mount_config,
+                integrity,
+                errors,
+                peers,
+                write_access,
+            )
+            .map_err(|e| format!("Failed to create cache: {}", e))
+        }
+        ConnectorConfig::Union(union_config) => {
+            let mut layers = Vec::with_capacity(union_config.layers.len());
+            for layer_config in &union_config.layers {
+                layers.push(create_layer(layer_config, &limits).await?);
+            }
+            let union = UnionConnector::new(layers)
+                .map_err(|e| format!("Failed to create union connector: {}", e))?;
+            wrap_backend(
+                union,
+                encryption.as_ref(),
+                mount_config,
+                integrity,
+                errors,
+                peers,
+                write_access,
+            )
+            .map_err(|e| format!("Failed to create cache: {}", e))
+        }
+    }
+}
+
+/// Wrap a backend with tracing, injected faults, a circuit breaker, a write
+/// access monitor, client-side encryption and compression (if configured)
+/// and its cache
+fn wrap_backend<C: Connector + 'static>(
+    connector: C,
+    encryption: Option<&EncryptionKey>,
+    mount_config: &MountConfig,
+    integrity: Option<Arc<IntegrityMonitor>>,
+    errors: Option<Arc<ErrorEvents>>,
+    peers: Option<PeerLink>,
+    write_access: Option<Arc<WriteAccess>>,
+) -> std::result::Result<Arc<dyn Connector>, Box<dyn std::error::Error>> {
+    let connector = TracedConnector::new(connector);
+    // Below the cache, so cached data keeps being served while it's open
+    let breaker = mount_config.circuit_breaker.as_ref();
+    // Denied writes are watched for beneath the cache, so its uploads count
+
+    // Faults hit the backend itself, so the cache's retry and error
+    // handling sees them the way it would see a real outage
+    #[cfg(feature = "mock")]
+    if let Some(faults) = &mount_config.faults {
+        use crate::connector::mock::{self, FaultController, MockConnector};
+
+        let controller = Arc::new(FaultController::from_config(faults));
+        mock::register(&mount_config.path, controller.clone());
+        let connector =
+            CircuitBreakerConnector::new(MockConnector::new(connector, controller), breaker);
+        return wrap_encryption(
+            WriteAccessMonitor::start(connector, write_access),
+            encryption,
+            mount_config,
+            integrity,
+            errors,
+            peers,
+        );
+    }
+    let connector = CircuitBreakerConnector::new(connector, breaker);
+    wrap_encryption(
+        WriteAccessMonitor::start(connector, write_access),
+        encryption,
+        mount_config,
+        integrity,
+        errors,
+        peers,
+    )
+}
+
+/// Wrap a backend with client-side encryption (if configured), then
+/// compression and cache
+fn wrap_encryption<C: Connector + 'static>(
+    connector: C,
+    encryption: Option<&EncryptionKey>,
+    mount_config: &MountConfig,
+    integrity: Option<Arc<IntegrityMonitor>>,
+    errors: Option<Arc<ErrorEvents>>,
+    peers: Option<PeerLink>,
+) -> std::result::Result<Arc<dyn Connector>, Box<dyn std::error::Error>> {
+    match encryption {
+        Some(key) => {
+            let encrypt_names = mount_config
+                .encryption
+                .as_ref()
+                .is_some_and(|c| c.encrypt_names);
+            wrap_compression(
+                EncryptedConnector::new(connector, key, encrypt_names),
+                mount_config,
+                integrity,
+                errors,
+                peers,
+            )
+        }
+        None => wrap_compression(connector, mount_config, integrity, errors, peers),
+    }
+}
+
+/// Wrap a backend with compression (if configured) and its cache
+///
+/// Compression sits above encryption, since ciphertext doesn't compress.
+fn wrap_compression<C: Connector + 'static>(
+    connector: C,
+    mount_config: &MountConfig,
+    integrity: Option<Arc<IntegrityMonitor>>,
+    errors: Option<Arc<ErrorEvents>>,
+    peers: Option<PeerLink>,
+) -> std::result::Result<Arc<dyn Connector>, Box<dyn std::error::Error>> {
+    match &mount_config.compression {
+        Some(config) => wrap_with_cache(
+            CompressedConnector::new(connector, config),
+            &mount_config.cache,
+            integrity,
+            errors,
+            peers,
+        ),
+        None => wrap_with_cache(connector, &mount_config.cache, integrity, errors, peers),
+    }
+}
+
+/// Wrap a connector with the appropriate cache layer based on configuration
+fn wrap_with_cache<C: Connector + 'static>(
+    connector: C,
+    cache_config: &CacheConfig,
+    integrity: Option<Arc<IntegrityMonitor>>,
+    errors: Option<Arc<ErrorEvents>>,
+    peers: Option<PeerLink>,
+) -> std::result::Result<Arc<dyn Connector>, Box<dyn std::error::Error>> {
+    match cache_config {
+        CacheConfig::None => Ok(Arc::new(NoCache::new(connector))),
+        CacheConfig::Memory {
+            max_entries,
+            max_size,
+            flush_interval,
+            metadata_ttl,
+            negative_ttl,
+            exclude_from_sync,
+            revalidate,
+            strict_fsync,
+            scrub_interval,
+            sync_schedule,
+            sync_large_file_threshold,
+            sync_max_inflight,
+            max_metadata_entries,
+            max_dir_listings,
+        } => {
+            let metadata_ttl = metadata_ttl.unwrap_or(std::time::Duration::from_secs(60));
+            let config = MemoryCacheConfig {
+                max_entries: max_entries.unwrap_or(1000),
+                max_size: max_size
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(100 * 1024 * 1024), // 100MB default
+                flush_interval: flush_interval.unwrap_or(std::time::Duration::from_secs(30)),
+                metadata_ttl,
+                negative_ttl: negative_ttl.unwrap_or(metadata_ttl),
+                exclude_patterns: exclude_from_sync.clone().unwrap_or_default(),
+                revalidation: *revalidate,
+                strict_fsync: *strict_fsync,
+                scrub_interval: *scrub_interval,
+                sync_schedule: sync_schedule.clone(),
+                large_file_threshold: sync_large_file_threshold
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(64 * 1024 * 1024),
+                max_inflight: sync_max_inflight
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(128 * 1024 * 1024),
+                max_metadata_entries: max_metadata_entries.unwrap_or(100_000),
+                max_dir_listings: max_dir_listings.unwrap_or(10_000),
+            };
+            let mut cache = MemoryCache::new(connector, config);
+            if let Some(monitor) = integrity {
+                cache = cache.with_integrity(monitor);
+            }
+            if let Some(errors) = errors {
+                cache = cache.with_error_events(errors);
+            }
+            if let Some(peers) = &peers {
+                cache = cache.with_peers(peers.clone());
+            }
+            // Start background sync task for write-back caching
+            let cache = cache.start();
+            if let Some(peers) = peers {
+                peers.register(Arc::downgrade(&cache) as _);
+            }
+            Ok(cache)
+        }
+        CacheConfig::Filesystem {
+            path,
+            max_size,
+            flush_interval,
+            metadata_ttl,
+            negative_ttl,
+            exclude_from_sync,
+            revalidate,
+            strict_fsync,
+            scrub_interval,
+            sync_schedule,
+            sync_large_file_threshold,
+            sync_max_inflight,
+            max_metadata_entries,
+            max_dir_listings,
+            min_free_space,
+            dedup,
+        } => {
+            let metadata_ttl = metadata_ttl.unwrap_or(std::time::Duration::from_secs(60));
+            let config = FilesystemCacheConfig {
+                cache_dir: PathBuf::from(path),
+                max_size: max_size
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(1024 * 1024 * 1024),
+                flush_interval: flush_interval.unwrap_or(std::time::Duration::from_secs(30)),
+                metadata_ttl,
+                negative_ttl: negative_ttl.unwrap_or(metadata_ttl),
+                exclude_patterns: exclude_from_sync.clone().unwrap_or_default(),
+                revalidation: *revalidate,
+                strict_fsync: *strict_fsync,
+                scrub_interval: *scrub_interval,
+                sync_schedule: sync_schedule.clone(),
+                large_file_threshold: sync_large_file_threshold
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(64 * 1024 * 1024),
+                max_inflight: sync_max_inflight
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(128 * 1024 * 1024),
+                max_metadata_entries: max_metadata_entries.unwrap_or(100_000),
+                max_dir_listings: max_dir_listings.unwrap_or(10_000),
+                min_free_space: min_free_space
+                    .as_ref()
+                    .and_then(|s| crate::cache::parse_size(s))
+                    .unwrap_or(0),
+                dedup: *dedup,
+            };
+            let mut cache = FilesystemCache::new(connector, config);
+            if let Some(monitor) = integrity {
+                cache = cache.with_integrity(monitor);
+            }
+            if let Some(errors) = errors {
+                cache = cache.with_error_events(errors);
+            }
+            if let Some(peers) = &peers {
+                cache = cache.with_peers(peers.clone());
+            }
+            // Start background sync task for write-back caching
+            let cache = cache.start();
+            if let Some(peers) = peers {
+                peers.register(Arc::downgrade(&cache) as _);
+            }
+            Ok(cache)
+        }
+    }
+}
@@ -3,24 +3,36 @@
 //! This provides a write-back cache where all operations happen locally first,
 //! and changes are synchronized to the backend periodically based on flush_interval.
 //! This design makes operations near-disk-speed rather than network-bound.
+//!
+//! Cached files mirror the mount's directory tree under a subdirectory of
+//! the cache directory, so every path has its own location and no cached
+//! name is longer than the mount's own. Caches from older versions, which
+//! stored every path as one flattened file name, are migrated on startup.
+//!
+//! A manifest in the cache directory lists the files cached clean from the
+//! backend with their size, checksum and backend ETag, so a later run can
+//! reuse them. On startup, cached files the manifest doesn't list (other
+//! than local-only ones) are dropped; listed files are checked against
+//! their checksum on first access and discarded if they don't match.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read as IoRead, Seek, SeekFrom, Write as IoWrite};
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::{Duration, Instant, SystemTime};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
 
-use async_trait::async_trait;
 use bytes::Bytes;
-use dashmap::DashMap;
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use parking_lot::RwLock;
-use tokio::sync::Notify;
-use tracing::{debug, error, info, trace, warn};
-
-use crate::connector::{
-    CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream, FileType, Metadata,
+use dashmap::{DashMap, DashSet};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, trace, warn};
+
+use crate::cache::writeback::{
+    ContentStore, LocalEntry, LocalKind, Pinned, WriteBackCache, WriteBackConfig,
 };
+use crate::cache::{RevalidationMode, SyncScheduleConfig};
+use crate::connector::{Connector, Metadata, SeekRegion, COPY_CHUNK_SIZE};
 use crate::error::{FuseAdapterError, Result};
 
 /// Filesystem cache configuration
@@ -34,8 +46,30 @@ pub struct FilesystemCacheConfig {
     pub flush_interval: Duration,
     /// TTL for cached metadata from backend
     pub metadata_ttl: Duration,
+    /// TTL for remembering paths missing from the backend
+    pub negative_ttl: Duration,
     /// Glob patterns for files to exclude from syncing to backend
     pub exclude_patterns: Vec<String>,
+    /// How cached content is revalidated against the backend
+    pub revalidation: RevalidationMode,
+    /// Upload a file's pending change before flush returns
+    pub strict_fsync: bool,
+    /// Interval for checking cached state against the backend (None disables)
+    pub scrub_interval: Option<Duration>,
+    /// When background sync is paused
+    pub sync_schedule: SyncScheduleConfig,
+    /// Files larger than this many bytes are uploaded after smaller ones
+    pub large_file_threshold: u64,
+    /// Most bytes of file content uploaded at once
+    pub max_inflight: u64,
+    /// Most entries in each of the metadata and negative caches
+    pub max_metadata_entries: usize,
+    /// Most cached directory listings
+    pub max_dir_listings: usize,
+    /// Free space to keep on the cache volume in bytes (0 disables the check)
+    pub min_free_space: u64,
+    /// Store identical fetched content once, shared between paths
+    pub dedup: bool,
 }
 
 impl Default for FilesystemCacheConfig {
@@ -45,292 +79,601 @@ impl Default for FilesystemCacheConfig {
             max_size: 1024 * 1024 * 1024, // 1GB
             flush_interval: Duration::from_secs(30),
             metadata_ttl: Duration::from_secs(60),
+            negative_ttl: Duration::from_secs(60),
             exclude_patterns: Vec::new(),
+            revalidation: RevalidationMode::None,
+            strict_fsync: false,
+            scrub_interval: None,
+            sync_schedule: SyncScheduleConfig::default(),
+            large_file_threshold: 64 * 1024 * 1024, // 64MB
+            max_inflight: 128 * 1024 * 1024,        // 128MB
+            max_metadata_entries: 100_000,
+            max_dir_listings: 10_000,
+            min_free_space: 0,
+            dedup: false,
         }
     }
 }
 
-/// Type of pending change
-#[derive(Debug, Clone)]
-enum PendingChangeType {
-    /// New file created locally
-    NewFile,
-    /// File content modified
-    ModifiedFile,
-    /// File deleted locally
-    DeletedFile,
-    /// New directory created locally
-    NewDirectory,
-    /// Directory deleted locally
-    DeletedDirectory,
-    /// New symlink created locally
-    NewSymlink { target: PathBuf },
-}
+/// Cached files modified more recently than this are never evicted, so a
+/// write that hasn't registered its pending change yet can't lose its data
+const EVICT_MIN_AGE: Duration = Duration::from_secs(5);
 
-/// A pending change that needs to be synced to backend
-#[derive(Debug, Clone)]
-struct PendingChange {
-    change_type: PendingChangeType,
-    /// File mode if applicable
-    mode: Option<u32>,
-}
+/// Subdirectory of the cache holding cached files, laid out like the mount
+const TREE_DIR: &str = ".fuse-adapter-files";
 
-/// Cached metadata entry
-#[derive(Debug, Clone)]
-struct CachedMetadata {
-    metadata: Metadata,
-    cached_at: Instant,
-}
+/// Subdirectory of the cache holding deduplicated content, named by hash
+const BLOB_DIR: &str = ".fuse-adapter-blobs";
 
-/// Cached directory listing entry (from backend)
-#[derive(Debug, Clone)]
-struct CachedDirListing {
-    entries: Vec<DirEntry>,
-    cached_at: Instant,
-}
+/// File in the cache directory listing local-only paths, so they survive a
+/// restart
+const LOCAL_MANIFEST: &str = ".fuse-adapter-local-only.json";
 
-/// Negative cache entry (path known not to exist)
-#[derive(Debug, Clone)]
-struct NegativeCacheEntry {
-    cached_at: Instant,
-}
+/// File in the cache directory listing content cached clean from the
+/// backend, so it can be reused after a restart
+const CONTENT_MANIFEST: &str = ".fuse-adapter-manifest.json";
+
+/// Distinguishes temporary files created in the blob directory
+static BLOB_TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 /// Filesystem-backed write-back caching connector wrapper
 ///
-/// This cache layer:
-/// - All mutations happen locally first (near-disk-speed)
-/// - Background task syncs dirty state to backend periodically
-/// - Stores cached file content on local filesystem
-/// - Tracks all pending changes (creates, deletes, renames, symlinks)
-/// - Caches metadata and directory listings with TTL
-/// - Preserves POSIX file modes
-/// - Supports excluding files from sync via glob patterns
-pub struct FilesystemCache<C: Connector> {
-    inner: Arc<C>,
-    config: FilesystemCacheConfig,
-    /// Pending changes that need to be synced to backend
-    pending_changes: DashMap<PathBuf, PendingChange>,
-    /// Cached metadata with TTL (from backend, for paths without pending changes)
-    metadata_cache: DashMap<PathBuf, CachedMetadata>,
-    /// Cached file modes (separate from metadata for persistence)
-    mode_cache: DashMap<PathBuf, u32>,
-    /// Cached directory listings from backend (merged with pending changes at read time)
-    dir_cache: DashMap<PathBuf, CachedDirListing>,
-    /// Negative cache: paths known not to exist on backend
-    negative_cache: DashMap<PathBuf, NegativeCacheEntry>,
-    /// Current approximate cache size
-    cache_size: RwLock<u64>,
-    /// Shutdown notification for background sync task
-    shutdown: Arc<Notify>,
-    /// Flag to track if background sync is running
-    sync_running: Arc<RwLock<bool>>,
-    /// Compiled glob patterns for excluding files from sync
-    exclude_matcher: Option<GlobSet>,
-}
+/// All mutations happen locally first (near-disk-speed); cached content is
+/// stored as files in the cache directory.
+pub type FilesystemCache<C> = WriteBackCache<C, DiskStore>;
 
 impl<C: Connector + 'static> FilesystemCache<C> {
     /// Create a new filesystem cache wrapper
     pub fn new(connector: C, config: FilesystemCacheConfig) -> Self {
-        // Ensure cache directory exists
-        if let Err(e) = std::fs::create_dir_all(&config.cache_dir) {
-            warn!(
-                "Failed to create cache directory {:?}: {}",
-                config.cache_dir, e
-            );
-        }
+        let store = DiskStore::new(config.cache_dir, config.min_free_space, config.dedup);
+        Self::from_store(
+            connector,
+            store,
+            WriteBackConfig {
+                flush_interval: config.flush_interval,
+                metadata_ttl: config.metadata_ttl,
+                negative_ttl: config.negative_ttl,
+                exclude_patterns: config.exclude_patterns,
+                revalidation: config.revalidation,
+                strict_fsync: config.strict_fsync,
+                scrub_interval: config.scrub_interval,
+                sync_schedule: config.sync_schedule,
+                large_file_threshold: config.large_file_threshold,
+                max_inflight: config.max_inflight,
+                max_metadata_entries: config.max_metadata_entries,
+                max_dir_listings: config.max_dir_listings,
+            },
+        )
+    }
+}
 
-        // Build the exclude matcher from glob patterns
-        let exclude_matcher = Self::build_exclude_matcher(&config.exclude_patterns);
+/// Content store keeping file content as files in a cache directory
+pub struct DiskStore {
+    /// Directory to store cached files
+    cache_dir: PathBuf,
+    /// Free space to keep on the cache volume in bytes (0 disables the check)
+    min_free_space: u64,
+    /// Store identical fetched content once, shared between paths
+    dedup: bool,
+    /// Current approximate cache size
+    cache_size: RwLock<u64>,
+    /// Local-only manifest as last written, to skip unchanged rewrites
+    saved_manifest: Mutex<Option<Vec<u8>>>,
+    /// Content cached clean from the backend, by path
+    clean: DashMap<PathBuf, CleanEntry>,
+    /// Clean content kept from an earlier run that hasn't been checked
+    /// against its checksum yet
+    unverified: DashSet<PathBuf>,
+    /// Whether `clean` changed since the content manifest was written
+    clean_changed: AtomicBool,
+}
 
-        Self {
-            inner: Arc::new(connector),
-            config,
-            pending_changes: DashMap::new(),
-            metadata_cache: DashMap::new(),
-            mode_cache: DashMap::new(),
-            dir_cache: DashMap::new(),
-            negative_cache: DashMap::new(),
-            cache_size: RwLock::new(0),
-            shutdown: Arc::new(Notify::new()),
-            sync_running: Arc::new(RwLock::new(false)),
-            exclude_matcher,
-        }
-    }
+/// A file cached clean from the backend, as listed in the content manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CleanEntry {
+    path: PathBuf,
+    size: u64,
+    /// BLAKE3 hash of the content, hex-encoded
+    checksum: String,
+    /// Backend version the content was cached from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+}
 
-    /// Build a GlobSet from exclude patterns
-    fn build_exclude_matcher(patterns: &[String]) -> Option<GlobSet> {
-        if patterns.is_empty() {
-            return None;
+impl DiskStore {
+    /// Create a store in `cache_dir`, creating the directory if needed
+    pub fn new(cache_dir: PathBuf, min_free_space: u64, dedup: bool) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&cache_dir) {
+            warn!("Failed to create cache directory {:?}: {}", cache_dir, e);
         }
 
-        let mut builder = GlobSetBuilder::new();
-        for pattern in patterns {
-            match Glob::new(pattern) {
-                Ok(glob) => {
-                    builder.add(glob);
-                }
+        let store = Self {
+            cache_dir,
+            min_free_space,
+            dedup,
+            cache_size: RwLock::new(0),
+            saved_manifest: Mutex::new(None),
+            clean: DashMap::new(),
+            unverified: DashSet::new(),
+            clean_changed: AtomicBool::new(false),
+        };
+        store.migrate_flat_layout();
+        store.load_content_manifest();
+        store
+    }
+
+    /// Keep the cached files the content manifest lists, to be checked on
+    /// first access, and drop the others apart from local-only files
+    ///
+    /// Unlisted files may have been changed locally without the change
+    /// being synced, or be half written; either way they can't be trusted as
+    /// backend content.
+    fn load_content_manifest(&self) {
+        let path = self.content_manifest_path();
+        let mut listed: HashMap<PathBuf, CleanEntry> = match std::fs::read(&path) {
+            Ok(data) => match serde_json::from_slice::<Vec<CleanEntry>>(&data) {
+                Ok(entries) => entries.into_iter().map(|e| (e.path.clone(), e)).collect(),
                 Err(e) => {
-                    warn!("Invalid exclude pattern '{}': {}", pattern, e);
+                    warn!("Ignoring corrupt cache manifest {:?}: {}", path, e);
+                    HashMap::new()
                 }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read cache manifest {:?}: {}", path, e);
+                HashMap::new()
+            }
+        };
+        let local_only: HashSet<PathBuf> = self
+            .load_local_only()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+
+        let mut files = Vec::new();
+        walk_files(&self.tree_dir(), &mut files);
+        let (mut kept, mut dropped, mut size) = (0, 0, 0);
+        for (cache_path, meta) in files {
+            let Some(path) = self.mount_path(&cache_path) else {
+                continue;
+            };
+            if let Some(entry) = listed.remove(&path) {
+                self.unverified.insert(path.clone());
+                self.clean.insert(path, entry);
+                kept += 1;
+            } else if !local_only.contains(&path) {
+                trace!("Dropping unlisted cache file {:?}", cache_path);
+                let _ = std::fs::remove_file(&cache_path);
+                dropped += 1;
+                continue;
             }
+            size += meta.len();
+        }
+        *self.cache_size.write() = size;
+        // Listed files that are gone were evicted before the manifest was
+        // last written
+        self.clean_changed
+            .store(dropped > 0 || !listed.is_empty(), Ordering::Relaxed);
+        if dropped > 0 && self.dedup {
+            self.collect_blobs();
+        }
+        if kept > 0 || dropped > 0 {
+            info!(
+                "Reusing {} cached files in {:?}, dropped {} not in the manifest",
+                kept, self.cache_dir, dropped
+            );
+        }
+    }
+
+    fn content_manifest_path(&self) -> PathBuf {
+        self.cache_dir.join(CONTENT_MANIFEST)
+    }
+
+    /// Check content kept from an earlier run against the manifest the
+    /// first time it is used, discarding it if it doesn't match
+    fn verify(&self, path: &Path) {
+        if !self.unverified.contains(path) {
+            return;
         }
+        let Some(expected) = self.clean.get(path).map(|e| e.clone()) else {
+            self.unverified.remove(path);
+            return;
+        };
+        let actual = std::fs::File::open(self.cache_path(path)).and_then(|file| {
+            let size = file.metadata()?.len();
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(file)?;
+            Ok((size, hasher.finalize().to_hex().to_string()))
+        });
 
-        match builder.build() {
-            Ok(set) => {
-                info!("Configured {} exclude patterns for sync", patterns.len());
-                Some(set)
+        // Content replaced while it was being checked is this run's own
+        if self.unverified.remove(path).is_none() {
+            return;
+        }
+        match actual {
+            Ok((size, checksum)) if size == expected.size && checksum == expected.checksum => {
+                trace!("Verified cached {:?}", path);
+            }
+            Ok(_) => {
+                warn!(
+                    "Discarding cached {:?}: content doesn't match the cache manifest",
+                    path
+                );
+                self.remove(path);
             }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => self.forget_clean(path),
             Err(e) => {
-                warn!("Failed to build exclude matcher: {}", e);
-                None
+                warn!("Discarding cached {:?}: {}", path, e);
+                self.remove(path);
             }
         }
     }
 
-    /// Check if a path should be excluded from syncing to backend
-    fn is_excluded(&self, path: &Path) -> bool {
-        if let Some(ref matcher) = self.exclude_matcher {
-            // Convert path to string for matching, stripping leading slash
-            let path_str = path.to_string_lossy();
-            let path_str = path_str.trim_start_matches('/');
-            matcher.is_match(path_str)
-        } else {
-            false
+    /// Check every unverified file at or beneath `path`
+    fn verify_under(&self, path: &Path) {
+        let paths: Vec<PathBuf> = self
+            .unverified
+            .iter()
+            .filter(|p| p.starts_with(path))
+            .map(|p| p.clone())
+            .collect();
+        for path in paths {
+            self.verify(&path);
+        }
+    }
+
+    /// Stop listing `path` as clean content
+    fn forget_clean(&self, path: &Path) {
+        self.unverified.remove(path);
+        if self.clean.remove(path).is_some() {
+            self.clean_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Stop listing anything at or beneath the directory `path` as clean
+    /// content
+    fn forget_clean_under(&self, path: &Path) {
+        let before = self.clean.len();
+        self.clean.retain(|p, _| !p.starts_with(path));
+        self.unverified.retain(|p| !p.starts_with(path));
+        if self.clean.len() != before {
+            self.clean_changed.store(true, Ordering::Relaxed);
         }
     }
 
-    /// Start the background sync task
-    /// This should be called after the cache is wrapped in an Arc
-    pub fn start_background_sync(self: &Arc<Self>) {
-        let cache = Arc::clone(self);
-        let flush_interval = cache.config.flush_interval;
-        let shutdown = Arc::clone(&cache.shutdown);
+    /// The mount path a file in the cache tree holds content for
+    fn mount_path(&self, cache_path: &Path) -> Option<PathBuf> {
+        let relative = cache_path.strip_prefix(self.tree_dir()).ok()?;
+        Some(Path::new("/").join(relative))
+    }
 
-        tokio::spawn(async move {
-            info!(
-                "Background sync task started with interval {:?}",
-                flush_interval
-            );
+    /// Move a cache from the old layout, which stored each path as a single
+    /// file named with `/` replaced by `_`, into the tree layout
+    ///
+    /// Flattened names are ambiguous, so only local-only files (which the
+    /// manifest names) are kept; everything else is clean content that is
+    /// fetched again when needed.
+    fn migrate_flat_layout(&self) {
+        let Ok(dir) = std::fs::read_dir(&self.cache_dir) else {
+            return;
+        };
+        let legacy: Vec<PathBuf> = dir
+            .flatten()
+            .filter(|entry| {
+                !entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".fuse-adapter")
+            })
+            .map(|entry| entry.path())
+            .collect();
+        if legacy.is_empty() {
+            return;
+        }
 
-            loop {
-                tokio::select! {
-                    _ = tokio::time::sleep(flush_interval) => {
-                        if let Err(e) = cache.sync_to_backend().await {
-                            error!("Background sync failed: {}", e);
-                        }
+        let mut local_files = HashMap::new();
+        for entry in self.load_local_only() {
+            match entry.kind {
+                LocalKind::File => {
+                    local_files.insert(self.cache_dir.join(flat_name(&entry.path)), entry.path);
+                }
+                LocalKind::Directory => {
+                    if let Err(e) = self.create_dir(&entry.path) {
+                        warn!("Failed to migrate local-only {:?}: {}", entry.path, e);
                     }
-                    _ = shutdown.notified() => {
-                        info!("Background sync task shutting down");
-                        // Final sync before shutdown
-                        if let Err(e) = cache.sync_to_backend().await {
-                            error!("Final sync failed: {}", e);
-                        }
-                        break;
+                }
+                LocalKind::Symlink { .. } => {}
+            }
+        }
+
+        let mut moved = 0;
+        for old in &legacy {
+            if let Some(path) = local_files.get(old) {
+                match self
+                    .writable_cache_path(path)
+                    .and_then(|new| std::fs::rename(old, new).map_err(FuseAdapterError::from))
+                {
+                    Ok(()) => {
+                        moved += 1;
+                        continue;
                     }
+                    Err(e) => warn!("Failed to migrate local-only {:?}: {}", path, e),
                 }
             }
-        });
+            let removed = if old.is_dir() {
+                std::fs::remove_dir_all(old)
+            } else {
+                std::fs::remove_file(old)
+            };
+            if let Err(e) = removed {
+                warn!("Failed to remove old cache entry {:?}: {}", old, e);
+            }
+        }
+        if self.dedup {
+            self.collect_blobs();
+        }
+        info!(
+            "Migrated cache {:?} to the tree layout ({} local-only files kept, {} entries dropped)",
+            self.cache_dir,
+            moved,
+            legacy.len() - moved
+        );
     }
 
-    /// Get the local cache path for a file
-    fn cache_path(&self, path: &Path) -> PathBuf {
-        let safe_name = path
-            .to_string_lossy()
-            .trim_start_matches('/')
-            .replace('/', "_");
+    fn manifest_path(&self) -> PathBuf {
+        self.cache_dir.join(LOCAL_MANIFEST)
+    }
 
-        if safe_name.is_empty() {
-            self.config.cache_dir.join("_root")
-        } else {
-            self.config.cache_dir.join(safe_name)
+    /// Free bytes available on the cache volume
+    fn free_space(&self) -> Option<u64> {
+        let path = std::ffi::CString::new(self.cache_dir.as_os_str().as_encoded_bytes()).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
         }
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
     }
 
-    /// Get the path for storing symlink target
-    fn symlink_meta_path(&self, path: &Path) -> PathBuf {
-        let safe_name = path
-            .to_string_lossy()
-            .trim_start_matches('/')
-            .replace('/', "_");
+    /// Bytes that must be freed before `needed` more can be stored without
+    /// dropping below `min_free_space`
+    fn space_shortfall(&self, needed: u64) -> u64 {
+        if self.min_free_space == 0 {
+            return 0;
+        }
+        match self.free_space() {
+            Some(free) => (self.min_free_space + needed).saturating_sub(free),
+            None => 0,
+        }
+    }
 
-        self.config.cache_dir.join(format!("{}.symlink", safe_name))
+    /// Evict clean cached files until `needed` more bytes fit, returning the
+    /// remaining shortfall
+    fn reclaim_space(&self, needed: u64, pinned: &Pinned<'_>) -> u64 {
+        let shortfall = self.space_shortfall(needed);
+        if shortfall == 0 {
+            return 0;
+        }
+        let freed = self.evict_clean(shortfall, pinned);
+        shortfall.saturating_sub(freed)
     }
 
-    /// Check if a file is in the local cache
-    fn is_cached(&self, path: &Path) -> bool {
-        self.cache_path(path).exists()
+    /// Remove cached files without pending changes, oldest first, until at
+    /// least `target` bytes are freed. Returns the number of bytes freed.
+    fn evict_clean(&self, target: u64, pinned: &Pinned<'_>) -> u64 {
+        let dirty = self.pinned_cache_paths(pinned);
+
+        let mut files = Vec::new();
+        walk_files(&self.tree_dir(), &mut files);
+        let now = SystemTime::now();
+        let mut candidates: Vec<(SystemTime, u64, u64, PathBuf)> = files
+            .into_iter()
+            .filter_map(|(path, meta)| {
+                if dirty.contains(&path) {
+                    return None;
+                }
+                let modified = meta.modified().ok()?;
+                if now.duration_since(modified).unwrap_or_default() < EVICT_MIN_AGE {
+                    return None;
+                }
+                Some((modified, meta.len(), meta.nlink(), path))
+            })
+            .collect();
+        candidates.sort_by_key(|(modified, _, _, _)| *modified);
+
+        let mut freed = 0;
+        for (_, len, links, path) in candidates {
+            if freed >= target {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                debug!("Evicted {:?} ({} bytes)", path, len);
+                if let Some(path) = self.mount_path(&path) {
+                    self.forget_clean(&path);
+                }
+                // Deduplicated content is only freed with its last path, when
+                // just the blob's own link remains
+                if links <= 2 {
+                    freed += len;
+                }
+            }
+        }
+        if self.dedup {
+            self.collect_blobs();
+        }
+
+        if freed > 0 {
+            let mut size = self.cache_size.write();
+            *size = size.saturating_sub(freed);
+            info!("Evicted {} bytes of clean cached files", freed);
+        }
+        freed
     }
 
-    /// Check if path has a pending delete
-    fn is_pending_delete(&self, path: &Path) -> bool {
-        self.pending_changes.get(path).is_some_and(|change| {
-            matches!(
-                change.change_type,
-                PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory
-            )
-        })
+    /// Cache paths of the pinned paths
+    fn pinned_cache_paths(&self, pinned: &Pinned<'_>) -> HashSet<PathBuf> {
+        pinned
+            .paths()
+            .iter()
+            .map(|path| self.cache_path(path))
+            .collect()
     }
 
-    /// Check if path has a pending create (file, dir, or symlink)
-    fn is_pending_create(&self, path: &Path) -> bool {
-        self.pending_changes.get(path).is_some_and(|change| {
-            matches!(
-                change.change_type,
-                PendingChangeType::NewFile
-                    | PendingChangeType::NewDirectory
-                    | PendingChangeType::NewSymlink { .. }
-            )
-        })
+    /// Directory holding cached files
+    fn tree_dir(&self) -> PathBuf {
+        self.cache_dir.join(TREE_DIR)
+    }
+
+    /// Directory holding deduplicated content
+    fn blob_dir(&self) -> PathBuf {
+        self.cache_dir.join(BLOB_DIR)
+    }
+
+    /// A fresh temporary path in the blob directory
+    fn blob_tmp_path(&self) -> PathBuf {
+        let n = BLOB_TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        self.blob_dir()
+            .join(format!("{}.{}.tmp", std::process::id(), n))
+    }
+
+    /// Store fetched content at `cache_path` as a hard link to a blob named
+    /// by its BLAKE3 hash, so every path with identical content shares one
+    /// copy on disk. Returns the number of bytes newly stored.
+    fn store_shared(&self, cache_path: &Path, data: &[u8]) -> std::io::Result<u64> {
+        let blob = self.blob_dir().join(blake3::hash(data).to_hex().as_str());
+        let mut stored = 0;
+        if !blob.exists() {
+            std::fs::create_dir_all(self.blob_dir())?;
+            let tmp = self.blob_tmp_path();
+            std::fs::write(&tmp, data)?;
+            std::fs::rename(&tmp, &blob)?;
+            stored = data.len() as u64;
+        } else {
+            trace!("dedup: {:?} shares existing blob {:?}", cache_path, blob);
+        }
+
+        let tmp = self.blob_tmp_path();
+        std::fs::hard_link(&blob, &tmp)?;
+        std::fs::rename(&tmp, cache_path)?;
+        Ok(stored)
     }
 
-    /// Check if any ancestor of path is a pending new directory.
-    /// If so, the path cannot exist on the backend.
-    fn has_pending_new_ancestor(&self, path: &Path) -> bool {
-        let mut current = path.parent();
-        while let Some(parent) = current {
-            if self
-                .pending_changes
-                .get(parent)
-                .is_some_and(|c| matches!(c.change_type, PendingChangeType::NewDirectory))
-            {
-                return true;
+    /// Give a cached file its own copy of deduplicated content before it is
+    /// modified in place, so other paths sharing the blob are unaffected
+    fn unshare(&self, cache_path: &Path) -> Result<()> {
+        if !self.dedup {
+            return Ok(());
+        }
+        match std::fs::metadata(cache_path) {
+            Ok(meta) if meta.nlink() > 1 => {
+                let tmp = self.blob_tmp_path();
+                std::fs::copy(cache_path, &tmp)
+                    .and_then(|_| std::fs::rename(&tmp, cache_path))
+                    .map_err(|e| cache_write_error("Failed to copy shared cache file", e))?;
+                *self.cache_size.write() += meta.len();
+                Ok(())
             }
-            current = parent.parent();
+            _ => Ok(()),
         }
-        false
     }
 
-    /// Check if path is in negative cache (known not to exist on backend)
-    fn is_negative_cached(&self, path: &Path) -> bool {
-        self.negative_cache
-            .get(path)
-            .is_some_and(|entry| entry.cached_at.elapsed() < self.config.metadata_ttl)
+    /// Remove blobs no cached path links to any more, returning bytes freed
+    fn collect_blobs(&self) -> u64 {
+        let Ok(dir) = std::fs::read_dir(self.blob_dir()) else {
+            return 0;
+        };
+        let mut freed = 0;
+        for entry in dir.flatten() {
+            // Temporary files are in use by a store or unshare in progress
+            if entry.file_name().to_string_lossy().ends_with(".tmp") {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.nlink() == 1 && std::fs::remove_file(entry.path()).is_ok() {
+                trace!("dedup: removed unreferenced blob {:?}", entry.path());
+                freed += meta.len();
+            }
+        }
+        freed
     }
 
-    /// Add path to negative cache
-    fn add_to_negative_cache(&self, path: &Path) {
-        self.negative_cache.insert(
-            path.to_path_buf(),
-            NegativeCacheEntry {
-                cached_at: Instant::now(),
-            },
-        );
+    /// Get the local cache path for a file
+    fn cache_path(&self, path: &Path) -> PathBuf {
+        let mut cache_path = self.tree_dir();
+        cache_path.extend(path.components().filter_map(|c| match c {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        }));
+        cache_path
     }
 
-    /// Remove path from negative cache (e.g., when it's created)
-    fn remove_from_negative_cache(&self, path: &Path) {
-        self.negative_cache.remove(path);
+    /// Get the cache path for `path`, creating its parent directory
+    fn writable_cache_path(&self, path: &Path) -> Result<PathBuf> {
+        let cache_path = self.cache_path(path);
+        if let Some(parent) = cache_path.parent() {
+            std::fs::create_dir_all(parent)
+                .or_else(|_| {
+                    // A file cached before the backend replaced it with a
+                    // directory is in the way
+                    self.remove_file_ancestors(parent);
+                    std::fs::create_dir_all(parent)
+                })
+                .map_err(|e| {
+                    FuseAdapterError::Cache(format!("Failed to create cache directory: {}", e))
+                })?;
+        }
+        Ok(cache_path)
+    }
+
+    /// Remove cached files at `dir` or any of its ancestors in the tree
+    fn remove_file_ancestors(&self, dir: &Path) {
+        let tree = self.tree_dir();
+        for ancestor in dir.ancestors().take_while(|a| a.starts_with(&tree)) {
+            if let Ok(meta) = std::fs::symlink_metadata(ancestor) {
+                if !meta.is_dir() && std::fs::remove_file(ancestor).is_ok() {
+                    if let Some(path) = self.mount_path(ancestor) {
+                        self.forget_clean(&path);
+                    }
+                    let mut size = self.cache_size.write();
+                    *size = (*size).saturating_sub(meta.len());
+                }
+            }
+        }
+    }
+}
+
+impl ContentStore for DiskStore {
+    const NAME: &'static str = "Filesystem";
+
+    fn contains(&self, path: &Path) -> bool {
+        self.verify(path);
+        self.cache_path(path).exists()
     }
 
-    /// Read from local cache
-    fn read_from_cache(&self, path: &Path, offset: u64, size: u32) -> Result<Option<Bytes>> {
-        // Check for pending delete
-        if self.is_pending_delete(path) {
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
+    fn stat(&self, path: &Path) -> Option<Metadata> {
+        self.verify(path);
+        let std_meta = std::fs::metadata(self.cache_path(path)).ok()?;
+        let modified = std_meta.modified().unwrap_or(SystemTime::now());
+        if std_meta.is_file() {
+            Some(Metadata::file(std_meta.len(), modified))
+        } else if std_meta.is_dir() {
+            Some(Metadata::directory(modified))
+        } else {
+            None
         }
+    }
+
+    fn content_len(&self, path: &Path) -> Option<u64> {
+        self.verify(path);
+        std::fs::metadata(self.cache_path(path))
+            .ok()
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+    }
 
+    fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Option<Bytes>> {
+        self.verify(path);
         let cache_path = self.cache_path(path);
 
         if !cache_path.exists() {
@@ -343,26 +686,34 @@ impl<C: Connector + 'static> FilesystemCache<C> {
         file.seek(SeekFrom::Start(offset))
             .map_err(|e| FuseAdapterError::Cache(format!("Failed to seek: {}", e)))?;
 
-        let mut buffer = vec![0u8; size as usize];
-        let bytes_read = file
-            .read(&mut buffer)
+        // Read straight into uninitialized capacity rather than zero-filling
+        // a buffer first; the Vec then becomes the Bytes without a copy
+        let mut buffer = Vec::with_capacity(size as usize);
+        file.take(size as u64)
+            .read_to_end(&mut buffer)
             .map_err(|e| FuseAdapterError::Cache(format!("Failed to read: {}", e)))?;
 
-        buffer.truncate(bytes_read);
         Ok(Some(Bytes::from(buffer)))
     }
 
-    /// Write to local cache
-    fn write_to_cache(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
-        let cache_path = self.cache_path(path);
-
-        // Ensure parent directory exists
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                FuseAdapterError::Cache(format!("Failed to create cache directory: {}", e))
-            })?;
+    fn read_all(&self, path: &Path) -> Result<Option<Bytes>> {
+        self.verify(path);
+        match std::fs::read(self.cache_path(path)) {
+            Ok(data) => Ok(Some(Bytes::from(data))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(FuseAdapterError::Cache(format!(
+                "Failed to read cache file: {}",
+                e
+            ))),
         }
+    }
+
+    fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<()> {
+        self.verify(path);
+        self.forget_clean(path);
+        let cache_path = self.writable_cache_path(path)?;
 
+        self.unshare(&cache_path)?;
         let mut file = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
@@ -375,1249 +726,651 @@ impl<C: Connector + 'static> FilesystemCache<C> {
             .map_err(|e| FuseAdapterError::Cache(format!("Failed to seek: {}", e)))?;
 
         file.write_all(data)
-            .map_err(|e| FuseAdapterError::Cache(format!("Failed to write: {}", e)))?;
-
-        // Mark as modified (or keep as new if it was new)
-        self.pending_changes
-            .entry(path.to_path_buf())
-            .and_modify(|change| {
-                if !matches!(change.change_type, PendingChangeType::NewFile) {
-                    change.change_type = PendingChangeType::ModifiedFile;
-                }
-            })
-            .or_insert(PendingChange {
-                change_type: PendingChangeType::ModifiedFile,
-                mode: None,
-            });
-
-        // Invalidate metadata cache
-        self.metadata_cache.remove(path);
+            .map_err(|e| cache_write_error("Failed to write", e))?;
 
         // Update cache size estimate
-        {
-            let mut size = self.cache_size.write();
-            *size += data.len() as u64;
-        }
+        *self.cache_size.write() += data.len() as u64;
+        Ok(())
+    }
 
-        Ok(data.len() as u64)
+    fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.verify(path);
+        self.forget_clean(path);
+        let cache_path = self.cache_path(path);
+        self.unshare(&cache_path)?;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&cache_path)
+            .map_err(|e| FuseAdapterError::Cache(format!("Failed to open cache file: {}", e)))?;
+        file.set_len(size)
+            .map_err(|e| FuseAdapterError::Cache(format!("Failed to truncate: {}", e)))
     }
 
-    /// Create an empty file in the cache (local only)
-    fn create_in_cache(&self, path: &Path, mode: Option<u32>) -> Result<()> {
+    /// Zero a range by punching a hole in the cache file
+    fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.verify(path);
+        self.forget_clean(path);
         let cache_path = self.cache_path(path);
+        self.unshare(&cache_path)?;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&cache_path)
+            .map_err(|e| FuseAdapterError::Cache(format!("Failed to open cache file: {}", e)))?;
+        punch_hole(&file, offset, len).map_err(|e| cache_write_error("Failed to zero range", e))
+    }
 
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                FuseAdapterError::Cache(format!("Failed to create cache directory: {}", e))
-            })?;
-        }
+    fn create_file(&self, path: &Path) -> Result<()> {
+        self.forget_clean(path);
+        let cache_path = self.writable_cache_path(path)?;
 
+        // Truncating in place would empty content shared with other paths
+        if self.dedup {
+            let _ = std::fs::remove_file(&cache_path);
+        }
         std::fs::File::create(&cache_path)
             .map_err(|e| FuseAdapterError::Cache(format!("Failed to create cache file: {}", e)))?;
+        Ok(())
+    }
 
-        // Track as pending new file
-        self.pending_changes.insert(
-            path.to_path_buf(),
-            PendingChange {
-                change_type: PendingChangeType::NewFile,
-                mode,
-            },
-        );
-
-        // Store mode if provided
-        if let Some(m) = mode {
-            self.mode_cache.insert(path.to_path_buf(), m);
-        }
+    /// Create a marker directory to help with stat()
+    fn create_dir(&self, path: &Path) -> Result<()> {
+        let cache_path = self.writable_cache_path(path)?;
+        std::fs::create_dir_all(&cache_path).map_err(|e| {
+            FuseAdapterError::Cache(format!("Failed to create cache directory marker: {}", e))
+        })
+    }
 
-        // Remove from negative cache (it now exists)
-        self.remove_from_negative_cache(path);
+    fn fill(&self, path: &Path, data: Bytes) -> Result<()> {
+        self.forget_clean(path);
+        let cache_path = self.writable_cache_path(path)?;
 
-        // Invalidate parent directory cache
-        if let Some(parent) = path.parent() {
-            self.dir_cache.remove(parent);
-        }
+        // Write to cache, sharing identical content when deduplicating
+        let stored = if self.dedup {
+            match self.store_shared(&cache_path, &data) {
+                Ok(stored) => Some(stored),
+                Err(e) => {
+                    debug!("dedup: failed to share {:?}, storing a copy: {}", path, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let stored = match stored {
+            Some(stored) => stored,
+            None => {
+                std::fs::write(&cache_path, &data)
+                    .map_err(|e| cache_write_error("Failed to write cache file", e))?;
+                data.len() as u64
+            }
+        };
 
+        *self.cache_size.write() += stored;
         Ok(())
     }
 
-    /// Create a directory locally
-    fn create_dir_in_cache(&self, path: &Path, mode: Option<u32>) -> Result<()> {
-        // We don't create actual directories in cache - we just track the pending change
-        // and create a marker file to help with stat()
+    fn remove(&self, path: &Path) {
         let cache_path = self.cache_path(path);
+        if cache_path == self.tree_dir() {
+            return;
+        }
+        let Ok(meta) = std::fs::symlink_metadata(&cache_path) else {
+            return;
+        };
+        if meta.is_dir() {
+            self.forget_clean_under(path);
+            let _ = std::fs::remove_dir_all(&cache_path);
+        } else if std::fs::remove_file(&cache_path).is_ok() {
+            self.forget_clean(path);
+            let mut size = self.cache_size.write();
+            *size = (*size).saturating_sub(meta.len());
+        }
+    }
 
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                FuseAdapterError::Cache(format!("Failed to create cache directory: {}", e))
-            })?;
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        // Moved content is no longer a copy of what the backend has at
+        // either path
+        let from_cache = self.cache_path(from);
+        if from_cache.is_dir() {
+            self.verify_under(from);
+            self.forget_clean_under(from);
+            self.forget_clean_under(to);
+        } else {
+            self.verify(from);
+            self.forget_clean(from);
+            self.forget_clean(to);
+        }
+        if !from_cache.exists() {
+            return Ok(());
         }
+        let to_cache = self.writable_cache_path(to)?;
+        std::fs::rename(&from_cache, &to_cache)
+            .map_err(|e| FuseAdapterError::Cache(format!("Failed to rename: {}", e)))
+    }
 
-        // Create a marker directory in the cache
-        std::fs::create_dir_all(&cache_path).map_err(|e| {
-            FuseAdapterError::Cache(format!("Failed to create cache directory marker: {}", e))
-        })?;
+    fn seek_region(&self, path: &Path, offset: u64, region: SeekRegion) -> Result<Option<u64>> {
+        self.verify(path);
+        let file = std::fs::File::open(self.cache_path(path))
+            .map_err(|e| FuseAdapterError::Cache(format!("Failed to open cache file: {}", e)))?;
+        seek_in_file(&file, offset, region)
+            .map_err(|e| FuseAdapterError::Cache(format!("Failed to seek: {}", e)))
+    }
 
-        // Track as pending new directory
-        self.pending_changes.insert(
-            path.to_path_buf(),
-            PendingChange {
-                change_type: PendingChangeType::NewDirectory,
-                mode,
-            },
-        );
+    fn size(&self) -> u64 {
+        *self.cache_size.read()
+    }
 
-        // Store mode if provided
-        if let Some(m) = mode {
-            self.mode_cache.insert(path.to_path_buf(), m);
+    /// Make room for `needed` bytes, failing with ENOSPC if evicting clean
+    /// files isn't enough
+    fn reserve(&self, needed: u64, pinned: &Pinned<'_>) -> Result<()> {
+        let remaining = self.reclaim_space(needed, pinned);
+        if remaining == 0 {
+            return Ok(());
         }
+        warn!(
+            "Cache volume {:?} below min_free_space, {} bytes short",
+            self.cache_dir, remaining
+        );
+        Err(FuseAdapterError::NoSpace)
+    }
 
-        // Remove from negative cache (it now exists)
-        self.remove_from_negative_cache(path);
-
-        // Invalidate parent directory cache
-        if let Some(parent) = path.parent() {
-            self.dir_cache.remove(parent);
-        }
+    fn evict(&self, pinned: &Pinned<'_>) {
+        self.reclaim_space(0, pinned);
+    }
 
-        Ok(())
+    /// Drop clean cached files, except those written in the last few
+    /// seconds whose pending change may not be registered yet, and the
+    /// directories they leave empty
+    fn clear(&self, pinned: &Pinned<'_>) {
+        self.evict_clean(u64::MAX, pinned);
+        remove_empty_dirs(&self.tree_dir(), &self.pinned_cache_paths(pinned));
     }
 
-    /// Create a symlink locally
-    fn create_symlink_in_cache(&self, target: &Path, link_path: &Path) -> Result<()> {
-        // Store symlink target in a metadata file
-        let meta_path = self.symlink_meta_path(link_path);
+    fn save_local_only(&self, entries: &[LocalEntry]) {
+        let data = match serde_json::to_vec_pretty(entries) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to encode local-only manifest: {}", e);
+                return;
+            }
+        };
+        let mut saved = self.saved_manifest.lock();
+        if saved.as_ref() == Some(&data) {
+            return;
+        }
 
-        if let Some(parent) = meta_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                FuseAdapterError::Cache(format!("Failed to create cache directory: {}", e))
-            })?;
+        let path = self.manifest_path();
+        let tmp = path.with_extension("json.tmp");
+        match std::fs::write(&tmp, &data).and_then(|_| std::fs::rename(&tmp, &path)) {
+            Ok(()) => *saved = Some(data),
+            Err(e) => warn!("Failed to write local-only manifest {:?}: {}", path, e),
         }
+    }
 
-        std::fs::write(&meta_path, target.to_string_lossy().as_bytes()).map_err(|e| {
-            FuseAdapterError::Cache(format!("Failed to write symlink metadata: {}", e))
-        })?;
+    fn load_local_only(&self) -> Vec<LocalEntry> {
+        let path = self.manifest_path();
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                warn!("Failed to read local-only manifest {:?}: {}", path, e);
+                return Vec::new();
+            }
+        };
+        match serde_json::from_slice(&data) {
+            Ok(entries) => {
+                *self.saved_manifest.lock() = Some(data);
+                entries
+            }
+            Err(e) => {
+                warn!("Ignoring corrupt local-only manifest {:?}: {}", path, e);
+                Vec::new()
+            }
+        }
+    }
 
-        // Track as pending new symlink
-        self.pending_changes.insert(
-            link_path.to_path_buf(),
-            PendingChange {
-                change_type: PendingChangeType::NewSymlink {
-                    target: target.to_path_buf(),
-                },
-                mode: None,
+    fn mark_clean(&self, path: &Path, data: &[u8], etag: Option<&str>) {
+        self.unverified.remove(path);
+        self.clean.insert(
+            path.to_path_buf(),
+            CleanEntry {
+                path: path.to_path_buf(),
+                size: data.len() as u64,
+                checksum: blake3::hash(data).to_hex().to_string(),
+                etag: etag.map(str::to_string),
             },
         );
+        self.clean_changed.store(true, Ordering::Relaxed);
+    }
 
-        // Remove from negative cache (it now exists)
-        self.remove_from_negative_cache(link_path);
+    fn clean_etag(&self, path: &Path) -> Option<String> {
+        self.verify(path);
+        self.clean.get(path).and_then(|entry| entry.etag.clone())
+    }
 
-        // Invalidate parent directory cache
-        if let Some(parent) = link_path.parent() {
-            self.dir_cache.remove(parent);
+    fn save_manifest(&self) {
+        if !self.clean_changed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let entries: Vec<CleanEntry> = self.clean.iter().map(|e| e.value().clone()).collect();
+        let path = self.content_manifest_path();
+        let tmp = path.with_extension("json.tmp");
+        let written = serde_json::to_vec(&entries)
+            .map_err(std::io::Error::from)
+            .and_then(|data| std::fs::write(&tmp, data))
+            .and_then(|_| std::fs::rename(&tmp, &path));
+        if let Err(e) = written {
+            warn!("Failed to write cache manifest {:?}: {}", path, e);
+            self.clean_changed.store(true, Ordering::Relaxed);
         }
+    }
+}
 
-        Ok(())
+impl Drop for DiskStore {
+    fn drop(&mut self) {
+        self.save_manifest();
     }
+}
 
-    /// Read symlink target from local cache
-    fn read_symlink_from_cache(&self, path: &Path) -> Result<Option<PathBuf>> {
-        let meta_path = self.symlink_meta_path(path);
-
-        if !meta_path.exists() {
-            return Ok(None);
-        }
-
-        let target = std::fs::read_to_string(&meta_path).map_err(|e| {
-            FuseAdapterError::Cache(format!("Failed to read symlink metadata: {}", e))
-        })?;
-
-        Ok(Some(PathBuf::from(target)))
-    }
-
-    /// Mark a file as deleted locally
-    fn mark_deleted(&self, path: &Path, is_dir: bool) {
-        // Remove from local cache
-        let cache_path = self.cache_path(path);
-        if cache_path.exists() {
-            if is_dir {
-                let _ = std::fs::remove_dir_all(&cache_path);
-            } else {
-                let _ = std::fs::remove_file(&cache_path);
-            }
-        }
-
-        // Remove symlink metadata if present
-        let meta_path = self.symlink_meta_path(path);
-        if meta_path.exists() {
-            let _ = std::fs::remove_file(&meta_path);
-        }
-
-        // Check if this was a pending new item - if so, just remove the pending change
-        if let Some(change) = self.pending_changes.get(path) {
-            if matches!(
-                change.change_type,
-                PendingChangeType::NewFile
-                    | PendingChangeType::NewDirectory
-                    | PendingChangeType::NewSymlink { .. }
-            ) {
-                // It was created locally but never synced - just remove it
-                drop(change);
-                self.pending_changes.remove(path);
-                self.metadata_cache.remove(path);
-                self.mode_cache.remove(path);
-
-                // Invalidate parent directory cache
-                if let Some(parent) = path.parent() {
-                    self.dir_cache.remove(parent);
-                }
-                return;
-            }
-        }
-
-        // Mark as pending delete
-        let change_type = if is_dir {
-            PendingChangeType::DeletedDirectory
-        } else {
-            PendingChangeType::DeletedFile
-        };
-
-        self.pending_changes.insert(
-            path.to_path_buf(),
-            PendingChange {
-                change_type,
-                mode: None,
-            },
-        );
-
-        self.metadata_cache.remove(path);
-        self.mode_cache.remove(path);
-
-        // Invalidate parent directory cache
-        if let Some(parent) = path.parent() {
-            self.dir_cache.remove(parent);
-        }
-    }
-
-    /// Truncate a cached file
-    fn truncate_in_cache(&self, path: &Path, size: u64) -> Result<()> {
-        let cache_path = self.cache_path(path);
-
-        if cache_path.exists() {
-            let file = std::fs::OpenOptions::new()
-                .write(true)
-                .open(&cache_path)
-                .map_err(|e| {
-                    FuseAdapterError::Cache(format!("Failed to open cache file: {}", e))
-                })?;
-
-            file.set_len(size)
-                .map_err(|e| FuseAdapterError::Cache(format!("Failed to truncate: {}", e)))?;
-
-            // Mark as modified
-            self.pending_changes
-                .entry(path.to_path_buf())
-                .and_modify(|change| {
-                    if !matches!(change.change_type, PendingChangeType::NewFile) {
-                        change.change_type = PendingChangeType::ModifiedFile;
-                    }
-                })
-                .or_insert(PendingChange {
-                    change_type: PendingChangeType::ModifiedFile,
-                    mode: None,
-                });
-        }
-
-        self.metadata_cache.remove(path);
-
-        Ok(())
+/// The single file name the old cache layout stored `path` under
+fn flat_name(path: &Path) -> String {
+    let name = path
+        .to_string_lossy()
+        .trim_start_matches('/')
+        .replace('/', "_");
+    if name.is_empty() {
+        "_root".to_string()
+    } else {
+        name
     }
+}
 
-    /// Fetch a file from backend into cache
-    async fn fetch_to_cache(&self, path: &Path) -> Result<()> {
-        // Don't fetch if pending delete
-        if self.is_pending_delete(path) {
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
-        }
-
-        // Get file metadata first
-        let meta = self.inner.stat(path).await?;
-
-        if !meta.is_file() {
-            return Err(FuseAdapterError::IsADirectory(
-                path.to_string_lossy().to_string(),
-            ));
-        }
-
-        let cache_path = self.cache_path(path);
-
-        if let Some(parent) = cache_path.parent() {
-            std::fs::create_dir_all(parent).map_err(|e| {
-                FuseAdapterError::Cache(format!("Failed to create cache directory: {}", e))
-            })?;
-        }
-
-        // Read entire file from backend
-        let data = if meta.size > 0 {
-            self.inner.read(path, 0, meta.size as u32).await?
-        } else {
-            Bytes::new()
+/// Collect every file under `dir` with its metadata
+fn walk_files(dir: &Path, files: &mut Vec<(PathBuf, std::fs::Metadata)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.metadata() else {
+            continue;
         };
-
-        // Write to cache
-        std::fs::write(&cache_path, &data)
-            .map_err(|e| FuseAdapterError::Cache(format!("Failed to write cache file: {}", e)))?;
-
-        // Update cache size
-        {
-            let mut size = self.cache_size.write();
-            *size += data.len() as u64;
-        }
-
-        // Cache the metadata and mode
-        if let Some(mode) = meta.mode {
-            self.mode_cache.insert(path.to_path_buf(), mode);
-        }
-        self.cache_metadata(path, meta);
-
-        Ok(())
-    }
-
-    /// Get cached metadata if still valid
-    fn get_cached_metadata(&self, path: &Path) -> Option<Metadata> {
-        self.metadata_cache.get(path).and_then(|entry| {
-            if entry.cached_at.elapsed() < self.config.metadata_ttl {
-                Some(entry.metadata.clone())
-            } else {
-                None
-            }
-        })
-    }
-
-    /// Cache metadata
-    fn cache_metadata(&self, path: &Path, metadata: Metadata) {
-        self.metadata_cache.insert(
-            path.to_path_buf(),
-            CachedMetadata {
-                metadata,
-                cached_at: Instant::now(),
-            },
-        );
-    }
-
-    /// Get metadata for a pending change
-    fn get_pending_metadata(&self, path: &Path) -> Option<Metadata> {
-        let change = self.pending_changes.get(path)?;
-        let mode = self.mode_cache.get(path).map(|r| *r);
-        let now = SystemTime::now();
-
-        match &change.change_type {
-            PendingChangeType::NewFile | PendingChangeType::ModifiedFile => {
-                let cache_path = self.cache_path(path);
-                let size = std::fs::metadata(&cache_path).map(|m| m.len()).unwrap_or(0);
-                if let Some(m) = mode {
-                    Some(Metadata::file_with_mode(size, now, m))
-                } else {
-                    Some(Metadata::file(size, now))
-                }
-            }
-            PendingChangeType::NewDirectory => {
-                if let Some(m) = mode {
-                    Some(Metadata::directory_with_mode(now, m))
-                } else {
-                    Some(Metadata::directory(now))
-                }
-            }
-            PendingChangeType::NewSymlink { .. } => Some(Metadata::symlink(now)),
-            PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory => None,
+        if meta.is_dir() {
+            walk_files(&entry.path(), files);
+        } else if meta.is_file() {
+            files.push((entry.path(), meta));
         }
     }
+}
 
-    /// Sync all pending changes to backend
-    pub async fn sync_to_backend(&self) -> Result<()> {
-        // Prevent concurrent syncs
-        {
-            let mut running = self.sync_running.write();
-            if *running {
-                debug!("Sync already in progress, skipping");
-                return Ok(());
-            }
-            *running = true;
-        }
-
-        // Ensure we release the lock when done
-        let _guard = scopeguard::guard((), |_| {
-            *self.sync_running.write() = false;
-        });
-
-        let pending: Vec<(PathBuf, PendingChange)> = self
-            .pending_changes
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().clone()))
-            .collect();
-
-        if pending.is_empty() {
-            trace!("No pending changes to sync");
-            return Ok(());
-        }
-
-        // Filter out excluded paths - they stay local only
-        let (excluded, syncable): (Vec<_>, Vec<_>) = pending
-            .into_iter()
-            .partition(|(path, _)| self.is_excluded(path));
-
-        // Remove excluded paths from pending_changes - they won't be synced
-        // but we clear them so they don't keep accumulating
-        for (path, _) in &excluded {
-            trace!("Excluding from sync (matches exclude pattern): {:?}", path);
-            self.pending_changes.remove(path);
-        }
-
-        if !excluded.is_empty() {
-            debug!("{} changes excluded from sync by pattern", excluded.len());
-        }
-
-        if syncable.is_empty() {
-            trace!("No syncable pending changes");
-            return Ok(());
-        }
-
-        info!("Syncing {} pending changes to backend", syncable.len());
-
-        // Sort to process directories before files (for creates) and files before directories (for deletes)
-        let mut creates: Vec<_> = syncable
-            .iter()
-            .filter(|(_, c)| {
-                matches!(
-                    c.change_type,
-                    PendingChangeType::NewFile
-                        | PendingChangeType::ModifiedFile
-                        | PendingChangeType::NewDirectory
-                        | PendingChangeType::NewSymlink { .. }
-                )
-            })
-            .collect();
-        let mut deletes: Vec<_> = syncable
-            .iter()
-            .filter(|(_, c)| {
-                matches!(
-                    c.change_type,
-                    PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory
-                )
-            })
-            .collect();
-
-        // Sort creates: directories first, then by path depth
-        creates.sort_by(|(a, _), (b, _)| a.components().count().cmp(&b.components().count()));
-
-        // Sort deletes: files first, then directories in reverse depth order
-        deletes.sort_by(|(a, ca), (b, cb)| {
-            let a_is_dir = matches!(ca.change_type, PendingChangeType::DeletedDirectory);
-            let b_is_dir = matches!(cb.change_type, PendingChangeType::DeletedDirectory);
-            match (a_is_dir, b_is_dir) {
-                (false, true) => std::cmp::Ordering::Less,
-                (true, false) => std::cmp::Ordering::Greater,
-                _ => b.components().count().cmp(&a.components().count()),
-            }
-        });
-
-        // Process creates
-        for (path, change) in creates {
-            match &change.change_type {
-                PendingChangeType::NewDirectory => {
-                    debug!("Syncing new directory: {:?}", path);
-                    if let Some(mode) = change.mode {
-                        if let Err(e) = self.inner.create_dir_with_mode(path, mode).await {
-                            error!("Failed to sync directory {:?}: {}", path, e);
-                            continue;
-                        }
-                    } else if let Err(e) = self.inner.create_dir(path).await {
-                        error!("Failed to sync directory {:?}: {}", path, e);
-                        continue;
-                    }
-                    self.pending_changes.remove(path);
-                }
-                PendingChangeType::NewSymlink { target } => {
-                    debug!("Syncing new symlink: {:?} -> {:?}", path, target);
-                    if let Err(e) = self.inner.symlink(target, path).await {
-                        error!("Failed to sync symlink {:?}: {}", path, e);
-                        continue;
-                    }
-                    // Remove the local symlink metadata file
-                    let meta_path = self.symlink_meta_path(path);
-                    let _ = std::fs::remove_file(&meta_path);
-                    self.pending_changes.remove(path);
-                }
-                PendingChangeType::NewFile | PendingChangeType::ModifiedFile => {
-                    debug!("Syncing file: {:?}", path);
-                    let cache_path = self.cache_path(path);
-
-                    if !cache_path.exists() {
-                        warn!("Cache file missing for {:?}, skipping", path);
-                        self.pending_changes.remove(path);
-                        continue;
-                    }
-
-                    // Create file on backend if new
-                    if matches!(change.change_type, PendingChangeType::NewFile) {
-                        if let Some(mode) = change.mode {
-                            if let Err(e) = self.inner.create_file_with_mode(path, mode).await {
-                                error!("Failed to create file {:?}: {}", path, e);
-                                continue;
-                            }
-                        } else if let Err(e) = self.inner.create_file(path).await {
-                            error!("Failed to create file {:?}: {}", path, e);
-                            continue;
-                        }
-                    }
-
-                    // Read and upload content
-                    let data = match std::fs::read(&cache_path) {
-                        Ok(d) => d,
-                        Err(e) => {
-                            error!("Failed to read cache file {:?}: {}", path, e);
-                            continue;
-                        }
-                    };
-
-                    if let Err(e) = self.inner.write(path, 0, &data).await {
-                        error!("Failed to write file {:?}: {}", path, e);
-                        continue;
-                    }
-
-                    self.pending_changes.remove(path);
-                }
-                _ => {}
-            }
-        }
-
-        // Process deletes
-        for (path, change) in deletes {
-            match change.change_type {
-                PendingChangeType::DeletedFile => {
-                    debug!("Syncing file deletion: {:?}", path);
-                    if let Err(e) = self.inner.remove_file(path).await {
-                        // Ignore NotFound errors - file might not exist on backend
-                        if !matches!(e, FuseAdapterError::NotFound(_)) {
-                            error!("Failed to delete file {:?}: {}", path, e);
-                            continue;
-                        }
-                    }
-                    self.pending_changes.remove(path);
-                }
-                PendingChangeType::DeletedDirectory => {
-                    debug!("Syncing directory deletion: {:?}", path);
-                    if let Err(e) = self.inner.remove_dir(path, false).await {
-                        if !matches!(e, FuseAdapterError::NotFound(_)) {
-                            error!("Failed to delete directory {:?}: {}", path, e);
-                            continue;
-                        }
-                    }
-                    self.pending_changes.remove(path);
-                }
-                _ => {}
-            }
-        }
-
-        info!(
-            "Sync complete, {} changes remaining",
-            self.pending_changes.len()
-        );
-        Ok(())
-    }
-
-    /// Flush all pending changes (sync version for shutdown)
-    pub async fn flush_all(&self) -> Result<()> {
-        self.sync_to_backend().await
-    }
-
-    /// Get list of pending changes for a directory (for merging with backend listing)
-    fn get_pending_entries_for_dir(&self, dir: &Path) -> Vec<DirEntry> {
-        let mut entries = Vec::new();
-
-        for entry in self.pending_changes.iter() {
-            let path = entry.key();
-
-            // Check if this path is a direct child of dir
-            if let Some(parent) = path.parent() {
-                if parent == dir {
-                    let name = path.file_name().unwrap_or_default();
-                    let file_type = match &entry.value().change_type {
-                        PendingChangeType::NewFile | PendingChangeType::ModifiedFile => {
-                            FileType::File
-                        }
-                        PendingChangeType::NewDirectory => FileType::Directory,
-                        PendingChangeType::NewSymlink { .. } => FileType::Symlink,
-                        PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory => {
-                            continue; // Skip deletes
-                        }
-                    };
-
-                    entries.push(DirEntry {
-                        name: name.to_os_string(),
-                        file_type,
-                    });
-                }
-            }
-        }
-
-        entries
-    }
-
-    /// Get set of paths that are pending delete in a directory
-    fn get_pending_deletes_for_dir(&self, dir: &Path) -> HashSet<PathBuf> {
-        let mut deletes = HashSet::new();
-
-        for entry in self.pending_changes.iter() {
-            let path = entry.key();
-
-            if let Some(parent) = path.parent() {
-                if parent == dir
-                    && matches!(
-                        entry.value().change_type,
-                        PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory
-                    )
-                {
-                    deletes.insert(path.clone());
-                }
+/// Remove the directories under `dir` that are empty, except those in `keep`
+fn remove_empty_dirs(dir: &Path, keep: &HashSet<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            remove_empty_dirs(&path, keep);
+            if !keep.contains(&path) {
+                // Fails while the directory still has content
+                let _ = std::fs::remove_dir(&path);
             }
         }
-
-        deletes
     }
 }
 
-impl<C: Connector> Drop for FilesystemCache<C> {
-    fn drop(&mut self) {
-        // Signal shutdown to background task
-        self.shutdown.notify_waiters();
-
-        let pending_count = self.pending_changes.len();
-        if pending_count > 0 {
-            warn!(
-                "{} pending changes not synced to backend (cache preserved for recovery)",
-                pending_count
-            );
-        }
+/// Report a full cache volume as ENOSPC rather than a generic cache error
+fn cache_write_error(context: &str, e: std::io::Error) -> FuseAdapterError {
+    if e.raw_os_error() == Some(libc::ENOSPC) {
+        FuseAdapterError::NoSpace
+    } else {
+        FuseAdapterError::Cache(format!("{}: {}", context, e))
     }
 }
 
-#[async_trait]
-impl<C: Connector + 'static> Connector for FilesystemCache<C> {
-    fn capabilities(&self) -> Capabilities {
-        let mut caps = self.inner.capabilities();
-        // Cache layer enables all write operations via local cache
-        if caps.write {
-            caps.random_write = true;
-            caps.truncate = true;
-            caps.rename = true;
-        }
-        // Cache layer can always store mode locally
-        caps.set_mode = true;
-        // Symlink capability - we can cache symlinks locally now
-        caps.symlink = true;
-        caps
-    }
-
-    fn cache_requirements(&self) -> CacheRequirements {
-        CacheRequirements::default()
-    }
-
-    async fn stat(&self, path: &Path) -> Result<Metadata> {
-        // Check for pending delete first
-        if self.is_pending_delete(path) {
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
-        }
-
-        // Check for pending create/modify - use local metadata
-        if let Some(meta) = self.get_pending_metadata(path) {
-            trace!("stat from pending change: {:?}", path);
-            return Ok(meta);
-        }
-
-        // Check cached metadata
-        if let Some(meta) = self.get_cached_metadata(path) {
-            trace!("stat cache hit: {:?}", path);
-            return Ok(meta);
-        }
-
-        // Check if we have a local cached file (fetched from backend earlier)
-        let cache_path = self.cache_path(path);
-        if cache_path.exists() {
-            let std_meta = std::fs::metadata(&cache_path).map_err(|e| {
-                FuseAdapterError::Cache(format!("Failed to stat cache file: {}", e))
-            })?;
-
-            let cached_mode = self.mode_cache.get(path).map(|r| *r);
-
-            let meta = if std_meta.is_file() {
-                if let Some(mode) = cached_mode {
-                    Metadata::file_with_mode(
-                        std_meta.len(),
-                        std_meta.modified().unwrap_or(SystemTime::now()),
-                        mode,
-                    )
-                } else {
-                    Metadata::file(
-                        std_meta.len(),
-                        std_meta.modified().unwrap_or(SystemTime::now()),
-                    )
-                }
-            } else if std_meta.is_dir() {
-                if let Some(mode) = cached_mode {
-                    Metadata::directory_with_mode(
-                        std_meta.modified().unwrap_or(SystemTime::now()),
-                        mode,
-                    )
-                } else {
-                    Metadata::directory(std_meta.modified().unwrap_or(SystemTime::now()))
-                }
-            } else {
-                // Could be a symlink metadata file
-                return Err(FuseAdapterError::NotFound(
-                    path.to_string_lossy().to_string(),
-                ));
-            };
-
-            self.cache_metadata(path, meta.clone());
-            return Ok(meta);
-        }
-
-        // Check for local symlink
-        if let Ok(Some(_)) = self.read_symlink_from_cache(path) {
-            return Ok(Metadata::symlink(SystemTime::now()));
-        }
-
-        // OPTIMIZATION: If any ancestor is a pending new directory, path can't exist on backend
-        if self.has_pending_new_ancestor(path) {
-            trace!(
-                "stat: path {:?} has pending new ancestor, skipping backend",
-                path
-            );
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
-        }
-
-        // OPTIMIZATION: Check negative cache (known not to exist on backend)
-        if self.is_negative_cached(path) {
-            trace!("stat negative cache hit: {:?}", path);
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
-        }
-
-        // Fall through to backend
-        match self.inner.stat(path).await {
-            Ok(meta) => {
-                if let Some(mode) = meta.mode {
-                    self.mode_cache.insert(path.to_path_buf(), mode);
-                }
-                self.cache_metadata(path, meta.clone());
-                Ok(meta)
-            }
-            Err(FuseAdapterError::NotFound(_)) => {
-                // Add to negative cache so we don't check again
-                self.add_to_negative_cache(path);
-                Err(FuseAdapterError::NotFound(
-                    path.to_string_lossy().to_string(),
-                ))
-            }
-            Err(e) => Err(e),
-        }
-    }
-
-    async fn exists(&self, path: &Path) -> Result<bool> {
-        // Check pending delete
-        if self.is_pending_delete(path) {
-            return Ok(false);
-        }
-
-        // Check pending create
-        if self.is_pending_create(path) {
-            return Ok(true);
-        }
-
-        // Check local cache
-        if self.cache_path(path).exists() {
-            return Ok(true);
-        }
-
-        // Check symlink cache
-        if self.symlink_meta_path(path).exists() {
-            return Ok(true);
-        }
-
-        // Check metadata cache
-        if self.get_cached_metadata(path).is_some() {
-            return Ok(true);
-        }
-
-        // OPTIMIZATION: If any ancestor is a pending new directory, path can't exist on backend
-        if self.has_pending_new_ancestor(path) {
-            trace!(
-                "exists: path {:?} has pending new ancestor, skipping backend",
-                path
-            );
-            return Ok(false);
-        }
-
-        // OPTIMIZATION: Check negative cache (known not to exist on backend)
-        if self.is_negative_cached(path) {
-            trace!("exists negative cache hit: {:?}", path);
-            return Ok(false);
+/// Find the next data or hole region of a sparse file
+fn seek_in_file(
+    file: &std::fs::File,
+    offset: u64,
+    region: SeekRegion,
+) -> std::io::Result<Option<u64>> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let whence = match region {
+            SeekRegion::Data => libc::SEEK_DATA,
+            SeekRegion::Hole => libc::SEEK_HOLE,
+        };
+        let ret = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if ret >= 0 {
+            return Ok(Some(ret as u64));
         }
-
-        // Fall through to backend
-        match self.inner.exists(path).await {
-            Ok(true) => Ok(true),
-            Ok(false) => {
-                // Add to negative cache
-                self.add_to_negative_cache(path);
-                Ok(false)
-            }
-            Err(e) => Err(e),
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::ENXIO) => return Ok(None),
+            // The cache filesystem doesn't track holes
+            Some(libc::EINVAL) => {}
+            _ => return Err(err),
         }
     }
 
-    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
-        // Check pending delete
-        if self.is_pending_delete(path) {
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
-        }
+    Ok(region.in_dense_file(file.metadata()?.len(), offset))
+}
 
-        // Try reading from cache first
-        if let Some(data) = self.read_from_cache(path, offset, size)? {
-            trace!("read cache hit: {:?} offset={} size={}", path, offset, size);
-            return Ok(data);
+/// Zero `len` bytes at `offset`, deallocating them where the filesystem can
+fn punch_hole(file: &std::fs::File, offset: u64, len: u64) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let ret = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                offset as libc::off_t,
+                len as libc::off_t,
+            )
+        };
+        if ret == 0 {
+            return Ok(());
         }
-
-        // Fetch from backend if not in cache
-        if !self.is_cached(path) {
-            debug!("Fetching {:?} to cache", path);
-            self.fetch_to_cache(path).await?;
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::EOPNOTSUPP) {
+            return Err(err);
         }
-
-        // Read from cache
-        self.read_from_cache(path, offset, size)?
-            .ok_or_else(|| FuseAdapterError::NotFound(path.to_string_lossy().to_string()))
     }
 
-    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
-        // If file doesn't exist in cache and we're writing at non-zero offset, fetch first
-        if !self.is_cached(path)
-            && offset > 0
-            && !self.is_pending_create(path)
-            && self.inner.stat(path).await.is_ok()
-        {
-            self.fetch_to_cache(path).await?;
-        }
-
-        // Write to local cache only
-        self.write_to_cache(path, offset, data)
+    // Write zeros instead
+    let zeros = vec![0; len.min(COPY_CHUNK_SIZE as u64) as usize];
+    let mut pos = offset;
+    while pos < offset + len {
+        let n = (offset + len - pos).min(zeros.len() as u64) as usize;
+        file.write_all_at(&zeros[..n], pos)?;
+        pos += n as u64;
     }
+    Ok(())
+}
 
-    async fn create_file(&self, path: &Path) -> Result<()> {
-        // Create locally only - will be synced later
-        self.create_in_cache(path, None)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StatusOverlayConfig;
+    use crate::overlay::StatusOverlay;
+
+    fn cache(dir: &Path, min_free_space: u64) -> FilesystemCache<StatusOverlay> {
+        let inner = StatusOverlay::new_failed("unused".to_string(), StatusOverlayConfig::default());
+        FilesystemCache::new(
+            inner,
+            FilesystemCacheConfig {
+                cache_dir: dir.to_path_buf(),
+                min_free_space,
+                ..Default::default()
+            },
+        )
     }
 
-    async fn create_dir(&self, path: &Path) -> Result<()> {
-        // Create locally only - will be synced later
-        self.create_dir_in_cache(path, None)
+    #[tokio::test]
+    async fn test_write_fails_with_enospc_below_min_free_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), u64::MAX / 2);
+
+        let err = cache
+            .write(Path::new("/file.txt"), 0, b"data")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::ENOSPC);
+        assert!(cache.pending_sync().is_empty());
     }
 
-    async fn remove_file(&self, path: &Path) -> Result<()> {
-        // Mark as deleted locally - will be synced later
-        self.mark_deleted(path, false);
-        Ok(())
+    #[tokio::test]
+    async fn test_zero_range_keeps_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 0);
+        let path = Path::new("/disk.img");
+
+        cache.create_file(path).await.unwrap();
+        cache.write(path, 0, &[7; 3 * 4096]).await.unwrap();
+        cache.zero_range(path, 4096, 1 << 20).await.unwrap();
+
+        let data = cache.read(path, 0, 1 << 20).await.unwrap();
+        assert_eq!(data.len(), 3 * 4096);
+        assert!(data[..4096].iter().all(|&b| b == 7));
+        assert!(data[4096..].iter().all(|&b| b == 0));
+        assert_eq!(cache.pending_sync().len(), 1);
     }
 
-    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
-        if !recursive {
-            // Check if directory has any pending non-deleted children
-            let pending_entries = self.get_pending_entries_for_dir(path);
-            if !pending_entries.is_empty() {
-                return Err(FuseAdapterError::NotEmpty(format!(
-                    "Directory not empty: {:?}",
-                    path
-                )));
-            }
-
-            // Check cached entries (filtering out pending deletes)
-            let pending_deletes = self.get_pending_deletes_for_dir(path);
-            if let Some(cached) = self.dir_cache.get(path) {
-                let has_entries = cached.entries.iter().any(|e| {
-                    let entry_path = path.join(&e.name);
-                    !pending_deletes.contains(&entry_path)
-                });
-                if has_entries {
-                    return Err(FuseAdapterError::NotEmpty(format!(
-                        "Directory not empty: {:?}",
-                        path
-                    )));
-                }
-            } else {
-                // No cache entry - check if it's a pending new directory
-                let is_pending_dir = self
-                    .pending_changes
-                    .get(path)
-                    .is_some_and(|c| matches!(c.change_type, PendingChangeType::NewDirectory));
-
-                if !is_pending_dir {
-                    // Need to check backend for entries
-                    use futures::StreamExt;
-                    let mut stream = self.inner.list_dir(path);
-                    while let Some(entry) = stream.next().await {
-                        if let Ok(entry) = entry {
-                            let entry_path = path.join(&entry.name);
-                            if !pending_deletes.contains(&entry_path) {
-                                return Err(FuseAdapterError::NotEmpty(format!(
-                                    "Directory not empty: {:?}",
-                                    path
-                                )));
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        // Mark as deleted locally - will be synced later
-        self.mark_deleted(path, true);
-        Ok(())
-    }
-
-    fn list_dir(&self, path: &Path) -> DirEntryStream {
-        // Get pending entries for this directory
-        let pending_entries = self.get_pending_entries_for_dir(path);
-        let pending_deletes = self.get_pending_deletes_for_dir(path);
-
-        // Check if we have pending directory entries (new local dir with entries)
-        let is_pending_dir = self
-            .pending_changes
-            .get(path)
-            .is_some_and(|c| matches!(c.change_type, PendingChangeType::NewDirectory));
-
-        // If it's a pending new directory, just return pending entries
-        if is_pending_dir {
-            return Box::pin(futures::stream::iter(pending_entries.into_iter().map(Ok)));
-        }
-
-        // Check cache first
-        if let Some(cached) = self.dir_cache.get(path) {
-            if cached.cached_at.elapsed() < self.config.metadata_ttl {
-                trace!("list_dir cache hit: {:?}", path);
-
-                // Merge cached entries with pending changes
-                let mut entries: Vec<DirEntry> = cached
-                    .entries
-                    .iter()
-                    .filter(|e| {
-                        let entry_path = path.join(&e.name);
-                        !pending_deletes.contains(&entry_path)
-                    })
-                    .cloned()
-                    .collect();
-
-                // Add pending creates (avoiding duplicates)
-                let existing_names: HashSet<_> = entries.iter().map(|e| e.name.clone()).collect();
-                for entry in pending_entries {
-                    if !existing_names.contains(&entry.name) {
-                        entries.push(entry);
-                    }
-                }
-
-                return Box::pin(futures::stream::iter(entries.into_iter().map(Ok)));
-            }
-        }
-
-        // Fetch from backend and merge with pending changes
-        let inner = self.inner.clone();
-        let path_owned = path.to_path_buf();
-        let dir_cache = self.dir_cache.clone();
-
-        Box::pin(async_stream::try_stream! {
-            debug!("list_dir fetching from backend: {:?}", path_owned);
-            let stream = inner.list_dir(&path_owned);
-
-            use futures::StreamExt;
-            let backend_entries: Vec<Result<DirEntry>> = stream.collect().await;
-
-            let mut cached_entries = Vec::new();
-            let mut seen_names: HashSet<std::ffi::OsString> = HashSet::new();
-
-            // Yield backend entries (filtered by pending deletes)
-            for entry_result in backend_entries {
-                match entry_result {
-                    Ok(entry) => {
-                        let entry_path = path_owned.join(&entry.name);
-                        if !pending_deletes.contains(&entry_path) {
-                            seen_names.insert(entry.name.clone());
-                            cached_entries.push(entry.clone());
-                            yield entry;
-                        }
-                    }
-                    Err(e) => {
-                        Err(e)?;
-                    }
-                }
-            }
-
-            // Yield pending creates (avoiding duplicates)
-            for entry in pending_entries {
-                if !seen_names.contains(&entry.name) {
-                    yield entry;
-                }
-            }
-
-            // Cache the backend listing
-            dir_cache.insert(path_owned, CachedDirListing {
-                entries: cached_entries,
-                cached_at: Instant::now(),
-            });
-        })
+    #[tokio::test]
+    async fn test_seek_region_sees_punched_holes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 0);
+        let path = Path::new("/sparse.img");
+
+        cache.write(path, 0, &[7; 64 * 1024]).await.unwrap();
+        cache.zero_range(path, 0, 32 * 1024).await.unwrap();
+
+        let data = cache.seek_region(path, 0, SeekRegion::Data).await.unwrap();
+        let hole = cache.seek_region(path, 0, SeekRegion::Hole).await.unwrap();
+        // Cache filesystems that don't track holes report the file as dense
+        assert!(matches!(
+            (data, hole),
+            (Some(32768), Some(0)) | (Some(0), Some(65536))
+        ));
+        assert_eq!(
+            cache
+                .seek_region(path, 32 * 1024, SeekRegion::Hole)
+                .await
+                .unwrap(),
+            Some(64 * 1024)
+        );
+        assert_eq!(
+            cache
+                .seek_region(path, 64 * 1024, SeekRegion::Data)
+                .await
+                .unwrap(),
+            None
+        );
     }
 
-    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
-        // Rename locally only
-
-        // Copy content
-        let from_cache = self.cache_path(from);
-        let to_cache = self.cache_path(to);
-
-        // Check if this is a directory rename
-        let is_directory = from_cache.is_dir()
-            || self
-                .pending_changes
-                .get(from)
-                .is_some_and(|c| matches!(c.change_type, PendingChangeType::NewDirectory));
-
-        if from_cache.exists() {
-            if let Some(parent) = to_cache.parent() {
-                std::fs::create_dir_all(parent).map_err(|e| {
-                    FuseAdapterError::Cache(format!("Failed to create directory: {}", e))
-                })?;
-            }
-            std::fs::rename(&from_cache, &to_cache)
-                .map_err(|e| FuseAdapterError::Cache(format!("Failed to rename: {}", e)))?;
-        }
-
-        // Handle symlink metadata
-        let from_meta = self.symlink_meta_path(from);
-        let to_meta = self.symlink_meta_path(to);
-        if from_meta.exists() {
-            std::fs::rename(&from_meta, &to_meta)
-                .map_err(|e| FuseAdapterError::Cache(format!("Failed to rename symlink: {}", e)))?;
-        }
-
-        // If this is a directory, we need to update all child entries in the caches
-        if is_directory {
-            let from_prefix = from.to_path_buf();
-
-            // Collect all child paths that need to be renamed in pending_changes
-            let child_paths: Vec<PathBuf> = self
-                .pending_changes
-                .iter()
-                .filter_map(|entry| {
-                    let path = entry.key();
-                    if path.starts_with(&from_prefix) && path != &from_prefix {
-                        Some(path.clone())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            // Update pending_changes for children and rename their cache files
-            for old_path in child_paths {
-                if let Some((_, change)) = self.pending_changes.remove(&old_path) {
-                    let relative = old_path.strip_prefix(&from_prefix).unwrap();
-                    let new_path = to.join(relative);
-
-                    // Rename the cache file for this child
-                    let old_child_cache = self.cache_path(&old_path);
-                    let new_child_cache = self.cache_path(&new_path);
-                    if old_child_cache.exists() {
-                        if let Some(parent) = new_child_cache.parent() {
-                            let _ = std::fs::create_dir_all(parent);
-                        }
-                        let _ = std::fs::rename(&old_child_cache, &new_child_cache);
-                    }
-
-                    self.pending_changes.insert(new_path, change);
-                }
-            }
-
-            // Collect all child paths in mode_cache
-            let mode_child_paths: Vec<PathBuf> = self
-                .mode_cache
-                .iter()
-                .filter_map(|entry| {
-                    let path = entry.key();
-                    if path.starts_with(&from_prefix) && path != &from_prefix {
-                        Some(path.clone())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            // Update mode_cache for children
-            for old_path in mode_child_paths {
-                if let Some((_, mode)) = self.mode_cache.remove(&old_path) {
-                    let relative = old_path.strip_prefix(&from_prefix).unwrap();
-                    let new_path = to.join(relative);
-                    self.mode_cache.insert(new_path, mode);
-                }
-            }
-
-            // Invalidate metadata_cache for children
-            let meta_child_paths: Vec<PathBuf> = self
-                .metadata_cache
-                .iter()
-                .filter_map(|entry| {
-                    let path = entry.key();
-                    if path.starts_with(&from_prefix) && path != &from_prefix {
-                        Some(path.clone())
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            for old_path in meta_child_paths {
-                self.metadata_cache.remove(&old_path);
-            }
-
-            // Also invalidate dir_cache for the renamed directory itself
-            self.dir_cache.remove(from);
-        }
-
-        // Update pending changes for the item itself
-        if let Some((_, change)) = self.pending_changes.remove(from) {
-            self.pending_changes.insert(to.to_path_buf(), change);
-        } else {
-            // File/dir exists on backend - mark source as deleted, destination as new
-            let change_type = if is_directory {
-                PendingChangeType::DeletedDirectory
-            } else {
-                PendingChangeType::DeletedFile
-            };
-            self.pending_changes.insert(
-                from.to_path_buf(),
-                PendingChange {
-                    change_type,
-                    mode: None,
-                },
-            );
-            let new_change_type = if is_directory {
-                PendingChangeType::NewDirectory
-            } else {
-                PendingChangeType::NewFile
-            };
-            self.pending_changes.insert(
-                to.to_path_buf(),
-                PendingChange {
-                    change_type: new_change_type,
-                    mode: self.mode_cache.get(from).map(|r| *r),
-                },
-            );
-        }
-
-        // Update mode cache for the item itself
-        if let Some((_, mode)) = self.mode_cache.remove(from) {
-            self.mode_cache.insert(to.to_path_buf(), mode);
-        }
-
-        // Invalidate metadata and directory caches
-        self.metadata_cache.remove(from);
-        self.metadata_cache.remove(to);
-        if let Some(parent) = from.parent() {
-            self.dir_cache.remove(parent);
-        }
-        if let Some(parent) = to.parent() {
-            self.dir_cache.remove(parent);
+    #[tokio::test]
+    async fn test_evict_keeps_dirty_and_recent_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 0);
+
+        cache
+            .write(Path::new("/dirty.txt"), 0, b"dirty")
+            .await
+            .unwrap();
+        let store = cache.store();
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        for name in ["/dirty.txt", "/sub/clean.txt"] {
+            let path = store.writable_cache_path(Path::new(name)).unwrap();
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.set_modified(old).unwrap();
         }
+        std::fs::write(store.cache_path(Path::new("/recent.txt")), b"recent").unwrap();
 
-        Ok(())
-    }
-
-    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
-        // Ensure file is in cache
-        if !self.is_cached(path) && !self.is_pending_create(path) {
-            self.fetch_to_cache(path).await?;
-        }
+        store.evict_clean(u64::MAX, &cache.pinned());
 
-        // Truncate locally
-        self.truncate_in_cache(path, size)
+        assert!(store.contains(Path::new("/dirty.txt")));
+        assert!(!store.contains(Path::new("/sub/clean.txt")));
+        assert!(store.contains(Path::new("/recent.txt")));
     }
 
-    async fn flush(&self, path: &Path) -> Result<()> {
-        // In write-back mode, flush doesn't immediately sync to backend
-        // The background task handles that
-        // But we should ensure data is persisted to local cache
-        trace!("flush called for {:?} (write-back mode)", path);
-        Ok(())
+    #[tokio::test]
+    async fn test_cache_layout_keeps_paths_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path(), 0);
+        let long = "x".repeat(200);
+        let paths = [
+            PathBuf::from("/a/b_c"),
+            PathBuf::from("/a_b/c"),
+            PathBuf::from(format!("/{long}/{long}/{long}")),
+        ];
+
+        for (i, path) in paths.iter().enumerate() {
+            cache.write(path, 0, &[i as u8; 4]).await.unwrap();
+        }
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(cache.read(path, 0, 10).await.unwrap(), vec![i as u8; 4]);
+        }
+
+        // A stale file where a directory is now needed is replaced
+        let store = cache.store();
+        store
+            .fill(Path::new("/d"), Bytes::from_static(b"old"))
+            .unwrap();
+        store
+            .fill(Path::new("/d/e"), Bytes::from_static(b"new"))
+            .unwrap();
+        assert!(store.stat(Path::new("/d")).unwrap().is_dir());
     }
 
-    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
-        self.create_in_cache(path, Some(mode))
-    }
+    #[tokio::test]
+    async fn test_flat_layout_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("docs_report.txt"), b"clean").unwrap();
+        std::fs::write(dir.path().join("build_out.o"), b"object").unwrap();
+        std::fs::create_dir(dir.path().join("build")).unwrap();
+        let manifest = serde_json::json!([
+            {"path": "/build", "kind": "directory", "mode": null},
+            {"path": "/build/out.o", "kind": "file", "mode": null},
+        ]);
+        std::fs::write(dir.path().join(LOCAL_MANIFEST), manifest.to_string()).unwrap();
+
+        let inner = StatusOverlay::new_failed("unused".to_string(), StatusOverlayConfig::default());
+        let cache = FilesystemCache::new(
+            inner,
+            FilesystemCacheConfig {
+                cache_dir: dir.path().to_path_buf(),
+                exclude_patterns: vec!["build".to_string()],
+                ..Default::default()
+            },
+        );
 
-    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
-        self.create_dir_in_cache(path, Some(mode))
+        assert_eq!(cache.cache_stats().unwrap().local_only, 2);
+        assert_eq!(
+            cache.read(Path::new("/build/out.o"), 0, 100).await.unwrap(),
+            "object"
+        );
+        let mut names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, [TREE_DIR, LOCAL_MANIFEST]);
     }
 
-    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
-        self.mode_cache.insert(path.to_path_buf(), mode);
-        self.metadata_cache.remove(path);
+    #[tokio::test]
+    async fn test_dedup_shares_identical_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = StatusOverlay::new_failed("unused".to_string(), StatusOverlayConfig::default());
+        let cache = FilesystemCache::new(
+            inner,
+            FilesystemCacheConfig {
+                cache_dir: dir.path().to_path_buf(),
+                min_free_space: 0,
+                dedup: true,
+                ..Default::default()
+            },
+        );
+        let store = cache.store();
+        let (a, b) = (Path::new("/a.bin"), Path::new("/b.bin"));
+        let data = vec![7u8; 1000];
 
-        // Mark as modified if it exists
-        if self.is_cached(path) || self.is_pending_create(path) {
-            self.pending_changes
-                .entry(path.to_path_buf())
-                .and_modify(|change| {
-                    change.mode = Some(mode);
-                });
-        }
+        let (a_cache, b_cache) = (
+            store.writable_cache_path(a).unwrap(),
+            store.writable_cache_path(b).unwrap(),
+        );
+        assert_eq!(store.store_shared(&a_cache, &data).unwrap(), 1000);
+        assert_eq!(store.store_shared(&b_cache, &data).unwrap(), 0);
+        let meta = std::fs::metadata(store.cache_path(a)).unwrap();
+        assert_eq!(meta.nlink(), 3);
+
+        // Writing to one path leaves the shared content alone
+        cache.write(b, 0, b"changed").await.unwrap();
+        assert_eq!(std::fs::read(store.cache_path(a)).unwrap(), data);
+        assert_eq!(
+            &std::fs::read(store.cache_path(b)).unwrap()[..7],
+            b"changed"
+        );
 
-        Ok(())
+        // The blob is freed with the last path that uses it
+        assert_eq!(store.collect_blobs(), 0);
+        std::fs::remove_file(store.cache_path(a)).unwrap();
+        assert_eq!(store.collect_blobs(), 1000);
+        assert_eq!(std::fs::read_dir(store.blob_dir()).unwrap().count(), 0);
     }
 
-    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
-        // Check pending delete
-        if self.is_pending_delete(path) {
-            return Err(FuseAdapterError::NotFound(
-                path.to_string_lossy().to_string(),
-            ));
-        }
-
-        // Check local symlink cache first
-        if let Some(target) = self.read_symlink_from_cache(path)? {
-            return Ok(target);
-        }
+    #[tokio::test]
+    async fn test_local_only_paths_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = || {
+            let inner =
+                StatusOverlay::new_failed("unused".to_string(), StatusOverlayConfig::default());
+            FilesystemCache::new(
+                inner,
+                FilesystemCacheConfig {
+                    cache_dir: dir.path().to_path_buf(),
+                    exclude_patterns: vec!["build".to_string()],
+                    ..Default::default()
+                },
+            )
+        };
 
-        // Fall through to backend
-        self.inner.readlink(path).await
+        let cache = open();
+        cache.create_dir(Path::new("/build")).await.unwrap();
+        cache
+            .write(Path::new("/build/out.o"), 0, b"object")
+            .await
+            .unwrap();
+        cache
+            .symlink(Path::new("out.o"), Path::new("/build/latest"))
+            .await
+            .unwrap();
+        cache.sync_to_backend().await.unwrap();
+        drop(cache);
+
+        let cache = open();
+        assert_eq!(cache.cache_stats().unwrap().local_only, 3);
+        assert!(cache.stat(Path::new("/build")).await.unwrap().is_dir());
+        assert_eq!(
+            cache.read(Path::new("/build/out.o"), 0, 100).await.unwrap(),
+            "object"
+        );
+        assert_eq!(
+            cache.readlink(Path::new("/build/latest")).await.unwrap(),
+            Path::new("out.o")
+        );
     }
 
-    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
-        // Create locally only - will be synced later
-        // Clear any existing cache at this path
-        self.mark_deleted(link_path, false);
-        self.pending_changes.remove(link_path); // Remove the delete we just added
-
-        self.create_symlink_in_cache(target, link_path)
+    #[test]
+    fn test_manifest_content_is_verified_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DiskStore::new(dir.path().to_path_buf(), 0, false);
+        for (path, data) in [("/a", b"alpha"), ("/b", b"bravo")] {
+            let path = Path::new(path);
+            store.fill(path, Bytes::from_static(data)).unwrap();
+            store.mark_clean(path, data, Some("v1"));
+        }
+        store.write(Path::new("/c"), 0, b"unsynced").unwrap();
+        drop(store);
+
+        // Damage one cached file behind the store's back
+        std::fs::write(dir.path().join(TREE_DIR).join("b"), b"BRAVO").unwrap();
+
+        let store = DiskStore::new(dir.path().to_path_buf(), 0, false);
+        assert!(!store.cache_path(Path::new("/c")).exists());
+        assert_eq!(store.size(), 10);
+        assert_eq!(store.clean_etag(Path::new("/a")).as_deref(), Some("v1"));
+        assert_eq!(store.read_all(Path::new("/a")).unwrap().unwrap(), "alpha");
+        assert!(!store.contains(Path::new("/b")));
+        assert!(store.clean_etag(Path::new("/b")).is_none());
+
+        // Local changes take content out of the manifest
+        store.write(Path::new("/a"), 0, b"A").unwrap();
+        assert!(store.clean_etag(Path::new("/a")).is_none());
     }
 }
-
-#[cfg(test)]
-mod tests {
-    // Tests would go here
-}
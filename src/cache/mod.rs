@@ -1,14 +1,39 @@
 pub mod filesystem;
 pub mod memory;
 pub mod none;
+pub mod peer;
+pub mod queue;
+pub mod schedule;
+pub mod writeback;
 
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use tracing::debug;
+
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{Connector, DirtyRanges};
+use crate::error::Result;
+
+pub use schedule::SyncScheduleConfig;
+
+/// How cached file content is checked against the backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevalidationMode {
+    /// Serve cached content until it is evicted
+    #[default]
+    None,
+    /// Once the metadata TTL expires, issue a conditional HEAD with the
+    /// stored ETag and drop the cached content if the object changed
+    Etag,
+}
 
 /// Cache configuration
 #[derive(Debug, Clone, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
 #[derive(Default)]
 pub enum CacheConfig {
     /// No caching
@@ -24,9 +49,43 @@ pub enum CacheConfig {
         #[serde(default)]
         #[serde(with = "humantime_serde")]
         flush_interval: Option<Duration>,
-        /// Glob patterns for files to exclude from syncing to backend
+        /// How long backend metadata and directory listings are cached
+        /// (e.g., "60s", "5m")
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        metadata_ttl: Option<Duration>,
+        /// How long a path is remembered as missing from the backend
+        /// (defaults to `metadata_ttl`)
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        negative_ttl: Option<Duration>,
+        /// Glob patterns for local-only paths, never synced to or fetched
+        /// from the backend
         #[serde(default)]
         exclude_from_sync: Option<Vec<String>>,
+        /// How cached content is revalidated against the backend
+        #[serde(default)]
+        revalidate: RevalidationMode,
+        /// Upload a file's pending change before fsync/flush returns
+        #[serde(default)]
+        strict_fsync: bool,
+        /// How often cached metadata and pending changes are checked
+        /// against the backend (e.g., "10m"); disabled when unset
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        scrub_interval: Option<Duration>,
+        /// When background sync is paused
+        #[serde(default)]
+        sync_schedule: SyncScheduleConfig,
+        /// Files larger than this (e.g., "64MB") are uploaded after smaller
+        /// ones
+        sync_large_file_threshold: Option<String>,
+        /// Most file content uploaded at once (e.g., "128MB")
+        sync_max_inflight: Option<String>,
+        /// Most paths whose backend metadata (or absence) is remembered
+        max_metadata_entries: Option<usize>,
+        /// Most backend directory listings kept
+        max_dir_listings: Option<usize>,
     },
     /// Filesystem-backed cache
     Filesystem {
@@ -37,12 +96,297 @@ pub enum CacheConfig {
         #[serde(default)]
         #[serde(with = "humantime_serde")]
         flush_interval: Option<Duration>,
-        /// Glob patterns for files to exclude from syncing to backend
+        /// How long backend metadata and directory listings are cached
+        /// (e.g., "60s", "5m")
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        metadata_ttl: Option<Duration>,
+        /// How long a path is remembered as missing from the backend
+        /// (defaults to `metadata_ttl`)
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        negative_ttl: Option<Duration>,
+        /// Glob patterns for local-only paths, never synced to or fetched
+        /// from the backend
         #[serde(default)]
         exclude_from_sync: Option<Vec<String>>,
+        /// How cached content is revalidated against the backend
+        #[serde(default)]
+        revalidate: RevalidationMode,
+        /// Upload a file's pending change before fsync/flush returns
+        #[serde(default)]
+        strict_fsync: bool,
+        /// How often cached metadata and pending changes are checked
+        /// against the backend (e.g., "10m"); disabled when unset
+        #[serde(default)]
+        #[serde(with = "humantime_serde")]
+        scrub_interval: Option<Duration>,
+        /// When background sync is paused
+        #[serde(default)]
+        sync_schedule: SyncScheduleConfig,
+        /// Files larger than this (e.g., "64MB") are uploaded after smaller
+        /// ones
+        sync_large_file_threshold: Option<String>,
+        /// Most file content uploaded at once (e.g., "128MB")
+        sync_max_inflight: Option<String>,
+        /// Most paths whose backend metadata (or absence) is remembered
+        max_metadata_entries: Option<usize>,
+        /// Most backend directory listings kept
+        max_dir_listings: Option<usize>,
+        /// Free space to keep on the cache volume (e.g., "5GB"); writes fail
+        /// with ENOSPC once clean files can't be evicted to stay above it
+        min_free_space: Option<String>,
+        /// Store identical file content once, shared between paths
+        #[serde(default)]
+        dedup: bool,
     },
 }
 
+impl CacheConfig {
+    /// Size options that are set, by name, as written (e.g. "100MB")
+    pub fn size_options(&self) -> Vec<(&'static str, &str)> {
+        let sizes = match self {
+            CacheConfig::None => return Vec::new(),
+            CacheConfig::Memory {
+                max_size,
+                sync_large_file_threshold,
+                sync_max_inflight,
+                ..
+            } => [
+                ("max_size", max_size),
+                ("sync_large_file_threshold", sync_large_file_threshold),
+                ("sync_max_inflight", sync_max_inflight),
+                ("min_free_space", &None),
+            ],
+            CacheConfig::Filesystem {
+                max_size,
+                sync_large_file_threshold,
+                sync_max_inflight,
+                min_free_space,
+                ..
+            } => [
+                ("max_size", max_size),
+                ("sync_large_file_threshold", sync_large_file_threshold),
+                ("sync_max_inflight", sync_max_inflight),
+                ("min_free_space", min_free_space),
+            ],
+        };
+        sizes
+            .into_iter()
+            .filter_map(|(name, size)| Some((name, size.as_deref()?)))
+            .collect()
+    }
+
+    /// Add glob patterns to `exclude_from_sync`, skipping ones already listed
+    ///
+    /// Does nothing without a cache, since there's nowhere to keep the files.
+    pub fn exclude_from_sync(&mut self, patterns: Vec<String>) {
+        let (CacheConfig::Memory {
+            exclude_from_sync, ..
+        }
+        | CacheConfig::Filesystem {
+            exclude_from_sync, ..
+        }) = self
+        else {
+            return;
+        };
+        let excluded = exclude_from_sync.get_or_insert_with(Vec::new);
+        for pattern in patterns {
+            if !excluded.contains(&pattern) {
+                excluded.push(pattern);
+            }
+        }
+    }
+}
+
+/// Point-in-time statistics reported by a cache layer
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    /// Reads served from cached content
+    pub hits: u64,
+    /// Reads that had to fetch from the backend
+    pub misses: u64,
+    /// Local changes not yet synced to the backend
+    pub pending_changes: usize,
+    /// Local-only paths (matching `exclude_from_sync`), which are never synced
+    pub local_only: usize,
+    /// Approximate size of cached content in bytes
+    pub cache_size: u64,
+    /// Paths with cached backend metadata
+    pub metadata_entries: usize,
+    /// Paths remembered as missing from the backend
+    pub negative_entries: usize,
+    /// Cached backend directory listings
+    pub dir_listings: usize,
+    /// When the last sync to the backend finished
+    pub last_sync: Option<DateTime<Utc>>,
+    /// Most recent sync failure, cleared once everything has synced
+    pub last_sync_error: Option<String>,
+    /// Why background sync is paused ("manual", "schedule" or "metered"),
+    /// if it is
+    pub sync_paused: Option<String>,
+    /// Directory renames moving backend content right now
+    pub renames: Vec<RenameProgress>,
+    /// Results of the most recent consistency scrub, if one has run
+    pub scrub: Option<ScrubReport>,
+    /// Throttling seen from the backend, if its connector paces requests
+    pub throttle: Option<ThrottleStats>,
+}
+
+/// Outcome of one pass comparing cached state against the backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrubReport {
+    /// When the pass finished
+    pub finished: DateTime<Utc>,
+    /// Cached entries compared against the backend
+    pub checked: usize,
+    /// Entries that no longer matched the backend
+    pub divergent: usize,
+    /// Divergent entries that were dropped or corrected
+    pub repaired: usize,
+    /// Divergent paths that need an operator's attention, such as local
+    /// creates that now collide with a backend file
+    pub conflicts: Vec<PathBuf>,
+}
+
+/// Progress of a directory rename that moves backend content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameProgress {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Files moved so far
+    pub moved: usize,
+    /// Files to move, once known (None while listing, or when the backend
+    /// renames the tree itself)
+    pub total: Option<usize>,
+}
+
+impl CacheStats {
+    /// Render as a JSON document
+    pub fn to_json(&self) -> String {
+        let value = serde_json::json!({
+            "hits": self.hits,
+            "misses": self.misses,
+            "pending_changes": self.pending_changes,
+            "local_only": self.local_only,
+            "cache_size": self.cache_size,
+            "metadata_entries": self.metadata_entries,
+            "negative_entries": self.negative_entries,
+            "dir_listings": self.dir_listings,
+            "last_sync": self.last_sync.map(|t| t.to_rfc3339()),
+            "last_sync_error": self.last_sync_error,
+            "sync_paused": self.sync_paused,
+            "renames": self.renames.iter().map(|r| serde_json::json!({
+                "from": r.from,
+                "to": r.to,
+                "moved": r.moved,
+                "total": r.total,
+            })).collect::<Vec<_>>(),
+            "scrub": self.scrub.as_ref().map(|s| serde_json::json!({
+                "finished": s.finished.to_rfc3339(),
+                "checked": s.checked,
+                "divergent": s.divergent,
+                "repaired": s.repaired,
+                "conflicts": s.conflicts,
+            })),
+            "throttle": self.throttle.as_ref().map(|t| serde_json::json!({
+                "throttled": t.throttled,
+                "rate_limit": t.rate_limit,
+                "last_throttled": t.last_throttled.map(|t| t.to_rfc3339()),
+            })),
+        });
+        format!("{:#}\n", value)
+    }
+}
+
+/// Operator controls for a caching layer, to make a mount reflect changes
+/// made to the backend from elsewhere without waiting for TTLs to expire
+///
+/// Local changes that haven't been synced yet are never dropped.
+pub trait CacheControl: Send + Sync {
+    /// Drop cached metadata, listings and content for `path` and anything
+    /// beneath it, so the next access refetches them from the backend
+    fn invalidate(&self, path: &Path);
+
+    /// Drop all cached metadata, listings and content
+    fn invalidate_all(&self);
+
+    /// Hold back (or let through again) background syncs; pending changes
+    /// are kept and explicit syncs still run
+    fn set_sync_paused(&self, paused: bool);
+}
+
+/// Kind of local change waiting to be synced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingKind {
+    New,
+    Modified,
+    Deleted,
+}
+
+impl PendingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingKind::New => "new",
+            PendingKind::Modified => "modified",
+            PendingKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// Sync state of a single path with pending changes
+#[derive(Debug, Clone)]
+pub struct PendingSync {
+    pub path: PathBuf,
+    pub kind: PendingKind,
+    /// Failed sync attempts so far
+    pub retries: u32,
+    /// Error from the most recent failed attempt
+    pub last_error: Option<String>,
+}
+
+impl PendingSync {
+    /// Format as a tab-separated line: state, retries, path, last error
+    pub fn format(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.kind.as_str(),
+            self.retries,
+            self.path.display(),
+            self.last_error.as_deref().unwrap_or("")
+        )
+    }
+}
+
+/// Upload a cached file's content to the backend
+///
+/// When the changed ranges are known the backend is asked to patch just
+/// those; otherwise, or if it can't, the whole file is written.
+pub(crate) async fn upload_content<C: Connector + ?Sized>(
+    inner: &C,
+    path: &Path,
+    data: &[u8],
+    dirty: Option<&DirtyRanges>,
+) -> Result<()> {
+    if let Some(dirty) = dirty {
+        if inner.write_dirty(path, data, dirty).await? {
+            debug!(
+                "Uploaded {} changed bytes of {:?} ({} total)",
+                dirty.len(),
+                path,
+                data.len()
+            );
+            return Ok(());
+        }
+    }
+    inner.write(path, 0, data).await?;
+    // Positional writes leave the tail of a longer backend copy in place
+    if inner.capabilities().random_write {
+        inner.truncate(path, data.len() as u64).await?;
+    }
+    Ok(())
+}
+
 /// Parse size string like "1GB" to bytes
 pub fn parse_size(s: &str) -> Option<u64> {
     let s = s.trim().to_uppercase();
@@ -73,4 +417,71 @@ mod tests {
         assert_eq!(parse_size("1024B"), Some(1024));
         assert_eq!(parse_size("1024"), Some(1024));
     }
+
+    #[test]
+    fn test_cache_stats_json() {
+        let stats = CacheStats {
+            hits: 3,
+            misses: 1,
+            last_sync_error: Some("Failed to write file".to_string()),
+            ..Default::default()
+        };
+        let value: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(value["hits"], 3);
+        assert_eq!(value["misses"], 1);
+        assert_eq!(value["pending_changes"], 0);
+        assert_eq!(value["local_only"], 0);
+        assert_eq!(value["metadata_entries"], 0);
+        assert_eq!(value["dir_listings"], 0);
+        assert!(value["last_sync"].is_null());
+        assert!(value["sync_paused"].is_null());
+        assert_eq!(value["last_sync_error"], "Failed to write file");
+        assert_eq!(value["renames"], serde_json::json!([]));
+        assert!(value["scrub"].is_null());
+
+        let stats = CacheStats {
+            renames: vec![RenameProgress {
+                from: PathBuf::from("/a"),
+                to: PathBuf::from("/b"),
+                moved: 2,
+                total: Some(5),
+            }],
+            ..Default::default()
+        };
+        let value: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(
+            value["renames"],
+            serde_json::json!([{"from": "/a", "to": "/b", "moved": 2, "total": 5}])
+        );
+
+        let stats = CacheStats {
+            scrub: Some(ScrubReport {
+                finished: Utc::now(),
+                checked: 10,
+                divergent: 2,
+                repaired: 1,
+                conflicts: vec![PathBuf::from("/new")],
+            }),
+            ..Default::default()
+        };
+        let value: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(value["scrub"]["checked"], 10);
+        assert_eq!(value["scrub"]["divergent"], 2);
+        assert_eq!(value["scrub"]["repaired"], 1);
+        assert_eq!(value["scrub"]["conflicts"], serde_json::json!(["/new"]));
+        assert!(value["throttle"].is_null());
+
+        let stats = CacheStats {
+            throttle: Some(ThrottleStats {
+                throttled: 7,
+                rate_limit: Some(20),
+                last_throttled: Some(Utc::now()),
+            }),
+            ..Default::default()
+        };
+        let value: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+        assert_eq!(value["throttle"]["throttled"], 7);
+        assert_eq!(value["throttle"]["rate_limit"], 20);
+        assert!(value["throttle"]["last_throttled"].is_string());
+    }
 }
@@ -9,8 +9,12 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use bytes::Bytes;
 
-use crate::connector::{CacheRequirements, Capabilities, Connector, DirEntryStream, Metadata};
+use crate::cache::PendingSync;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileVersion, Metadata,
+};
 use crate::error::Result;
+use crate::integrity::ContentHash;
 
 /// Passthrough connector that provides no caching
 pub struct NoCache<C: Connector> {
@@ -39,6 +43,14 @@ impl<C: Connector + 'static> Connector for NoCache<C> {
         self.inner.cache_requirements()
     }
 
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
     async fn stat(&self, path: &Path) -> Result<Metadata> {
         self.inner.stat(path).await
     }
@@ -98,4 +110,44 @@ impl<C: Connector + 'static> Connector for NoCache<C> {
     async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
         self.inner.set_mode(path, mode).await
     }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.inner.zero_range(path, offset, len).await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
 }
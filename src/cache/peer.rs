@@ -0,0 +1,423 @@
+//! Sharing cached content between adapters
+//!
+//! Adapters on the same network can serve each other's caches: before a
+//! cold read goes to the backend, the cache asks each configured peer for
+//! the file, and takes the first copy whose backend ETag and size match the
+//! object it is about to read. A fleet reading the same dataset then fetches
+//! each object from the backend roughly once.
+//!
+//! The protocol is a single HTTP request:
+//!
+//! ```text
+//! GET /v1/content?mount=/mnt/data&path=/dir/file.bin
+//! If-Match: <backend etag>
+//! Authorization: Bearer <token>     (when a token is configured)
+//! ```
+//!
+//! A peer answers 200 with the full content only if its mount at `mount`
+//! holds a clean copy (no local changes) of `path` cached from that exact
+//! backend version; anything else is a 404. Mounts are matched by path, so
+//! peers need the same mount points for the same backends.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::header::{AUTHORIZATION, IF_MATCH};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use parking_lot::RwLock;
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+/// Path of the content endpoint
+const CONTENT_PATH: &str = "/v1/content";
+
+/// Sharing cached content with other adapters
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CachePeersConfig {
+    /// Address to serve this adapter's cached content on (e.g.,
+    /// "0.0.0.0:7070"); unset = don't serve
+    #[serde(default)]
+    pub listen: Option<SocketAddr>,
+    /// Base URLs of other adapters to ask before reading from the backend
+    /// (e.g., "http://render-01:7070"), tried in order
+    #[serde(default)]
+    pub peers: Vec<String>,
+    /// How long to wait for a peer before moving on
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Shared secret peers must present (supports ${VAR} substitution)
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(2)
+}
+
+impl CachePeersConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.listen.is_none() && self.peers.is_empty() {
+            return Err("set listen, peers or both".to_string());
+        }
+        for peer in &self.peers {
+            match reqwest::Url::parse(peer) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                _ => return Err(format!("invalid peer URL {:?}", peer)),
+            }
+        }
+        if self.timeout.is_zero() {
+            return Err("timeout must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A cache whose content can be served to peers
+pub trait PeerSource: Send + Sync {
+    /// The full cached content of `path`, if the cache holds a clean copy
+    /// of the backend version tagged `etag`
+    fn peer_content(&self, path: &Path, etag: &str) -> Option<Bytes>;
+}
+
+/// Peers to ask for content, and the local caches served to them
+pub struct PeerSharing {
+    client: reqwest::Client,
+    peers: Vec<String>,
+    token: Option<String>,
+    /// Caches served to peers, by mount path
+    sources: RwLock<HashMap<PathBuf, Weak<dyn PeerSource>>>,
+}
+
+impl PeerSharing {
+    pub fn from_config(config: &CachePeersConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            peers: config
+                .peers
+                .iter()
+                .map(|peer| peer.trim_end_matches('/').to_string())
+                .collect(),
+            token: config.token.clone(),
+            sources: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The handle a cache for the mount at `mount` uses
+    pub fn link(self: &Arc<Self>, mount: impl Into<PathBuf>) -> PeerLink {
+        PeerLink {
+            sharing: self.clone(),
+            mount: mount.into(),
+        }
+    }
+
+    /// Ask each peer in turn for `path` of the mount at `mount`, accepting
+    /// only content of the backend version tagged `etag`, `size` bytes long
+    pub async fn fetch(&self, mount: &Path, path: &Path, size: u64, etag: &str) -> Option<Bytes> {
+        for peer in &self.peers {
+            match self.fetch_from(peer, mount, path, etag).await {
+                Ok(Some(data)) if data.len() as u64 == size => {
+                    debug!("peer: fetched {:?} from {}", path, peer);
+                    return Some(data);
+                }
+                Ok(Some(data)) => {
+                    warn!(
+                        "peer: {} returned {} bytes for {:?}, expected {}",
+                        peer,
+                        data.len(),
+                        path,
+                        size
+                    );
+                }
+                Ok(None) => {}
+                Err(e) => debug!("peer: request to {} failed: {}", peer, e),
+            }
+        }
+        None
+    }
+
+    async fn fetch_from(
+        &self,
+        peer: &str,
+        mount: &Path,
+        path: &Path,
+        etag: &str,
+    ) -> reqwest::Result<Option<Bytes>> {
+        let mut request = self
+            .client
+            .get(format!("{}{}", peer, CONTENT_PATH))
+            .query(&[
+                ("mount", mount.to_string_lossy()),
+                ("path", path.to_string_lossy()),
+            ])
+            .header(IF_MATCH.as_str(), etag);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
+        match response.status() {
+            reqwest::StatusCode::OK => Ok(Some(response.bytes().await?)),
+            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            status => {
+                debug!("peer: {} answered {} for {:?}", peer, status, path);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Serve the cache of the mount at `mount` to peers, replacing any
+    /// cache registered for it before
+    pub fn register(&self, mount: &Path, source: Weak<dyn PeerSource>) {
+        let mut sources = self.sources.write();
+        sources.retain(|_, source| source.strong_count() > 0);
+        sources.insert(mount.to_path_buf(), source);
+    }
+
+    /// Answer peers' requests arriving on `listener` until the task is
+    /// dropped
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) {
+        if let Ok(addr) = listener.local_addr() {
+            info!("Serving cached content to peers on {}", addr);
+            if self.token.is_none() {
+                warn!(
+                    "No cache_peers.token set: anyone who can reach {} can read cached files",
+                    addr
+                );
+            }
+        }
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("peer: failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            let sharing = self.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let response = sharing.respond(&request);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    debug!("peer: connection from {} failed: {}", addr, e);
+                }
+            });
+        }
+    }
+
+    fn respond(&self, request: &Request<Incoming>) -> Response<Full<Bytes>> {
+        if request.method() != Method::GET || request.uri().path() != CONTENT_PATH {
+            return status(StatusCode::NOT_FOUND);
+        }
+        if let Some(token) = &self.token {
+            let presented = request
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            if !presented.is_some_and(|presented| tokens_match(presented, token)) {
+                return status(StatusCode::UNAUTHORIZED);
+            }
+        }
+
+        // The query is form-encoded; let the URL parser decode it
+        let query = request.uri().query().unwrap_or_default();
+        let Ok(url) = reqwest::Url::parse(&format!("http://peer/?{}", query)) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+        let (mut mount, mut path) = (None, None);
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "mount" => mount = Some(PathBuf::from(value.as_ref())),
+                "path" => path = Some(PathBuf::from(value.as_ref())),
+                _ => {}
+            }
+        }
+        let etag = request
+            .headers()
+            .get(IF_MATCH)
+            .and_then(|value| value.to_str().ok());
+        let (Some(mount), Some(path), Some(etag)) = (mount, path, etag) else {
+            return status(StatusCode::BAD_REQUEST);
+        };
+
+        let source = self.sources.read().get(&mount).and_then(Weak::upgrade);
+        match source.and_then(|source| source.peer_content(&path, etag)) {
+            Some(data) => {
+                debug!("peer: serving {:?} of {:?}", path, mount);
+                Response::new(Full::new(data))
+            }
+            None => status(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+/// Compare tokens in time that doesn't depend on where they differ
+fn tokens_match(presented: &str, token: &str) -> bool {
+    let (presented, token) = (presented.as_bytes(), token.as_bytes());
+    presented.len() == token.len()
+        && presented
+            .iter()
+            .zip(token)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// An empty response with `code`
+fn status(code: StatusCode) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = code;
+    response
+}
+
+/// A mount's connection to the shared peers
+#[derive(Clone)]
+pub struct PeerLink {
+    sharing: Arc<PeerSharing>,
+    mount: PathBuf,
+}
+
+impl PeerLink {
+    /// Ask peers for `path`; see [`PeerSharing::fetch`]
+    pub async fn fetch(&self, path: &Path, size: u64, etag: &str) -> Option<Bytes> {
+        self.sharing.fetch(&self.mount, path, size, etag).await
+    }
+
+    /// Serve `source` to peers as this mount's cache
+    pub fn register(&self, source: Weak<dyn PeerSource>) {
+        self.sharing.register(&self.mount, source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cache holding fixed content for one version of each path
+    struct FixedSource(HashMap<PathBuf, (String, Bytes)>);
+
+    impl PeerSource for FixedSource {
+        fn peer_content(&self, path: &Path, etag: &str) -> Option<Bytes> {
+            self.0
+                .get(path)
+                .filter(|(tag, _)| tag == etag)
+                .map(|(_, data)| data.clone())
+        }
+    }
+
+    fn config(peers: Vec<String>, token: Option<&str>) -> CachePeersConfig {
+        CachePeersConfig {
+            listen: None,
+            peers,
+            timeout: Duration::from_secs(2),
+            token: token.map(str::to_string),
+        }
+    }
+
+    /// Serve `source` as the cache of /mnt/data, returning the base URL
+    async fn serve(source: &Arc<FixedSource>, token: Option<&str>) -> String {
+        let server = Arc::new(PeerSharing::from_config(&config(Vec::new(), token)));
+        let weak: Weak<dyn PeerSource> = Arc::downgrade(source) as Weak<dyn PeerSource>;
+        server.register(Path::new("/mnt/data"), weak);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(server.serve(listener));
+        url
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_peer() {
+        let source = Arc::new(FixedSource(HashMap::from([(
+            PathBuf::from("/dir/a file.bin"),
+            ("\"v1\"".to_string(), Bytes::from_static(b"hello")),
+        )])));
+        let url = serve(&source, None).await;
+        let client = Arc::new(PeerSharing::from_config(&config(vec![url], None)));
+        let link = client.link("/mnt/data");
+        let path = Path::new("/dir/a file.bin");
+
+        assert_eq!(
+            link.fetch(path, 5, "\"v1\"").await.unwrap(),
+            Bytes::from_static(b"hello")
+        );
+        // Another version, another length or another mount is a miss
+        assert!(link.fetch(path, 5, "\"v2\"").await.is_none());
+        assert!(link.fetch(path, 6, "\"v1\"").await.is_none());
+        assert!(client
+            .link("/mnt/other")
+            .fetch(path, 5, "\"v1\"")
+            .await
+            .is_none());
+
+        // Nothing is served once the cache is gone
+        drop(source);
+        assert!(link.fetch(path, 5, "\"v1\"").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_peer_token() {
+        let source = Arc::new(FixedSource(HashMap::from([(
+            PathBuf::from("/a"),
+            ("v1".to_string(), Bytes::from_static(b"abc")),
+        )])));
+        let url = serve(&source, Some("secret")).await;
+        let path = Path::new("/a");
+
+        let anonymous = Arc::new(PeerSharing::from_config(&config(vec![url.clone()], None)));
+        assert!(anonymous
+            .link("/mnt/data")
+            .fetch(path, 3, "v1")
+            .await
+            .is_none());
+
+        for guess in ["guess", "secreT", "secret2"] {
+            let wrong = Arc::new(PeerSharing::from_config(&config(
+                vec![url.clone()],
+                Some(guess),
+            )));
+            assert!(wrong.link("/mnt/data").fetch(path, 3, "v1").await.is_none());
+        }
+
+        // An unreachable peer is skipped
+        let trusted = Arc::new(PeerSharing::from_config(&config(
+            vec!["http://127.0.0.1:1".to_string(), url],
+            Some("secret"),
+        )));
+        assert!(trusted
+            .link("/mnt/data")
+            .fetch(path, 3, "v1")
+            .await
+            .is_some());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config(Vec::new(), None).validate().is_err());
+        assert!(config(vec!["ftp://peer".to_string()], None)
+            .validate()
+            .is_err());
+        assert!(config(vec!["http://peer:7070".to_string()], None)
+            .validate()
+            .is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+//! Upload order for a write-back cache's pending files
+//!
+//! Files that were flushed or fsynced go first, since an application is
+//! waiting on them or done with them. Files above the large-file threshold
+//! go after smaller ones, so one huge upload doesn't hold back many small
+//! changes. Within each group the file that has waited longest goes first.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+
+/// A pending file's place in the queue and whether it has been flushed
+#[derive(Debug, Clone, Copy)]
+struct QueueEntry {
+    /// Increases with every newly queued file, so lower is older
+    ticket: u64,
+    flushed: bool,
+}
+
+/// Priorities of files waiting to be uploaded
+#[derive(Debug)]
+pub struct SyncQueue {
+    entries: DashMap<PathBuf, QueueEntry>,
+    next_ticket: AtomicU64,
+    /// Files larger than this many bytes are uploaded after smaller ones
+    large_file_threshold: u64,
+}
+
+impl SyncQueue {
+    pub fn new(large_file_threshold: u64) -> Self {
+        Self {
+            entries: DashMap::new(),
+            next_ticket: AtomicU64::new(0),
+            large_file_threshold,
+        }
+    }
+
+    /// Note that `path` has a change to upload; it keeps its place if it
+    /// is already queued
+    pub fn enqueue(&self, path: &Path) {
+        self.entries
+            .entry(path.to_path_buf())
+            .or_insert_with(|| QueueEntry {
+                ticket: self.next_ticket.fetch_add(1, Ordering::Relaxed),
+                flushed: false,
+            });
+    }
+
+    /// Move `path` ahead of files nobody has flushed
+    pub fn prioritize(&self, path: &Path) {
+        self.enqueue(path);
+        if let Some(mut entry) = self.entries.get_mut(path) {
+            entry.flushed = true;
+        }
+    }
+
+    /// Drop `path` once its change is uploaded or gone
+    pub fn remove(&self, path: &Path) {
+        self.entries.remove(path);
+    }
+
+    /// Forget files that no longer have anything to upload
+    pub fn retain(&self, mut pending: impl FnMut(&Path) -> bool) {
+        self.entries.retain(|path, _| pending(path));
+    }
+
+    /// Sort `files` (path, size in bytes and anything else the caller
+    /// needs) into upload order
+    pub fn order<T>(&self, files: &mut [(PathBuf, u64, T)]) {
+        files.sort_by_cached_key(|(path, size, _)| {
+            let entry = self.entries.get(path).map(|e| *e);
+            (
+                !entry.is_some_and(|e| e.flushed),
+                *size > self.large_file_threshold,
+                entry.map_or(u64::MAX, |e| e.ticket),
+                path.clone(),
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(queue: &SyncQueue, files: &[(&str, u64)]) -> Vec<String> {
+        let mut files: Vec<(PathBuf, u64, ())> = files
+            .iter()
+            .map(|(path, size)| (PathBuf::from(path), *size, ()))
+            .collect();
+        queue.order(&mut files);
+        files
+            .into_iter()
+            .map(|(path, _, _)| path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_oldest_first() {
+        let queue = SyncQueue::new(1000);
+        queue.enqueue(Path::new("/b"));
+        queue.enqueue(Path::new("/a"));
+        queue.enqueue(Path::new("/b"));
+        assert_eq!(
+            order(&queue, &[("/a", 10), ("/b", 10), ("/c", 10)]),
+            ["/b", "/a", "/c"]
+        );
+    }
+
+    #[test]
+    fn test_flushed_then_small_files_first() {
+        let queue = SyncQueue::new(1000);
+        for path in ["/huge", "/flushed-huge", "/small", "/flushed"] {
+            queue.enqueue(Path::new(path));
+        }
+        queue.prioritize(Path::new("/flushed"));
+        queue.prioritize(Path::new("/flushed-huge"));
+        assert_eq!(
+            order(
+                &queue,
+                &[
+                    ("/huge", 5000),
+                    ("/flushed-huge", 5000),
+                    ("/small", 10),
+                    ("/flushed", 10),
+                ]
+            ),
+            ["/flushed", "/flushed-huge", "/small", "/huge"]
+        );
+    }
+
+    #[test]
+    fn test_requeued_file_goes_last() {
+        let queue = SyncQueue::new(1000);
+        queue.enqueue(Path::new("/new"));
+        queue.enqueue(Path::new("/other"));
+        assert_eq!(
+            order(&queue, &[("/other", 10), ("/new", 10)]),
+            ["/new", "/other"]
+        );
+
+        queue.retain(|path| path != Path::new("/new"));
+        queue.enqueue(Path::new("/new"));
+        assert_eq!(
+            order(&queue, &[("/other", 10), ("/new", 10)]),
+            ["/other", "/new"]
+        );
+    }
+}
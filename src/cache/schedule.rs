@@ -0,0 +1,200 @@
+//! When background sync is held back
+//!
+//! A write-back cache can pause its periodic uploads during configured
+//! local-time windows, while the network connection is metered, or on an
+//! operator's request. Pending changes stay in the cache until sync resumes.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{Local, NaiveTime};
+use serde::Deserialize;
+use tracing::debug;
+
+/// How long the NetworkManager query may take before the connection is
+/// assumed to be unmetered
+const METERED_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Background sync pauses configured for a cache
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncScheduleConfig {
+    /// Local time windows ("HH:MM-HH:MM") during which background sync is
+    /// paused; a window ending before it starts runs past midnight
+    #[serde(default)]
+    pub pause_during: Vec<TimeWindow>,
+    /// Pause background sync while NetworkManager reports the connection as
+    /// metered
+    #[serde(default)]
+    pub pause_when_metered: bool,
+}
+
+impl SyncScheduleConfig {
+    /// Why background sync should be held back right now, if it should
+    pub async fn pause_reason(&self) -> Option<&'static str> {
+        let now = Local::now().time();
+        if self.pause_during.iter().any(|w| w.contains(now)) {
+            return Some("schedule");
+        }
+        if self.pause_when_metered && is_metered().await {
+            return Some("metered");
+        }
+        None
+    }
+}
+
+/// A daily span of local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// Whether `time` falls in the window (start inclusive, end exclusive)
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .map_err(|_| format!("invalid time window {:?}, expected HH:MM-HH:MM", s))
+        };
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("invalid time window {:?}, expected HH:MM-HH:MM", s))?;
+        let window = TimeWindow {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if window.start == window.end {
+            return Err(format!("time window {:?} is empty", s));
+        }
+        Ok(window)
+    }
+}
+
+impl TryFrom<String> for TimeWindow {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Ask NetworkManager whether the primary connection is metered
+///
+/// Anything but a definite (or guessed) yes, including NetworkManager not
+/// running, counts as unmetered.
+async fn is_metered() -> bool {
+    let query = tokio::process::Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(METERED_CHECK_TIMEOUT, query).await {
+        Ok(Ok(output)) if output.status.success() => output,
+        Ok(Ok(output)) => {
+            debug!(
+                "Metered check failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return false;
+        }
+        Ok(Err(e)) => {
+            debug!("Metered check failed: {}", e);
+            return false;
+        }
+        Err(_) => {
+            debug!("Metered check timed out");
+            return false;
+        }
+    };
+    parse_metered(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Interpret `busctl` output for NetworkManager's `Metered` property
+/// ("u 1"), where 1 is yes and 3 is guessed yes
+fn parse_metered(output: &str) -> bool {
+    matches!(output.trim().strip_prefix("u "), Some("1") | Some("3"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_time_window() {
+        let window: TimeWindow = "09:00-17:30".parse().unwrap();
+        assert_eq!(window.start, time("09:00"));
+        assert_eq!(window.end, time("17:30"));
+        assert_eq!(window.to_string(), "09:00-17:30");
+        assert_eq!(
+            " 22:00 - 06:00 ".parse::<TimeWindow>().unwrap().to_string(),
+            "22:00-06:00"
+        );
+
+        assert!("09:00".parse::<TimeWindow>().is_err());
+        assert!("9am-5pm".parse::<TimeWindow>().is_err());
+        assert!("25:00-26:00".parse::<TimeWindow>().is_err());
+        assert!("10:00-10:00".parse::<TimeWindow>().is_err());
+    }
+
+    #[test]
+    fn test_time_window_contains() {
+        let day: TimeWindow = "09:00-17:00".parse().unwrap();
+        assert!(day.contains(time("09:00")));
+        assert!(day.contains(time("12:00")));
+        assert!(!day.contains(time("17:00")));
+        assert!(!day.contains(time("08:59")));
+
+        let night: TimeWindow = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(time("23:30")));
+        assert!(night.contains(time("00:00")));
+        assert!(night.contains(time("05:59")));
+        assert!(!night.contains(time("06:00")));
+        assert!(!night.contains(time("12:00")));
+    }
+
+    #[test]
+    fn test_parse_metered() {
+        assert!(parse_metered("u 1\n"));
+        assert!(parse_metered("u 3"));
+        assert!(!parse_metered("u 2"));
+        assert!(!parse_metered("u 4"));
+        assert!(!parse_metered("u 0"));
+        assert!(!parse_metered(""));
+    }
+}
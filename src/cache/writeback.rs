@@ -0,0 +1,4283 @@
+//! Generic write-back cache core
+//!
+//! All operations happen locally first and changes are synchronized to the
+//! backend periodically based on flush_interval. The bookkeeping (pending
+//! changes, negative cache, directory merging, sync ordering) lives here;
+//! where file content is kept is up to a pluggable [`ContentStore`].
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, Semaphore};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::cache::peer::{PeerLink, PeerSource};
+use crate::cache::queue::SyncQueue;
+use crate::cache::{
+    upload_content, CacheControl, CacheStats, PendingKind, PendingSync, RenameProgress,
+    RevalidationMode, ScrubReport, SyncScheduleConfig,
+};
+use crate::connector::{
+    copy_content, CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream,
+    DirtyRanges, FileType, FileVersion, Metadata, SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::events::ErrorEvents;
+use crate::integrity::IntegrityMonitor;
+
+/// Files a directory rename moves on the backend at once
+const RENAME_BATCH: usize = 16;
+
+/// Files uploaded at once by a sync, within the in-flight byte limit
+const SYNC_CONCURRENCY: usize = 8;
+
+/// Bounds on how often the janitor sweeps expired metadata, negative and
+/// listing entries; within them it runs once per shortest TTL
+const JANITOR_MIN_INTERVAL: Duration = Duration::from_secs(1);
+const JANITOR_MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bytes per in-flight upload permit
+const INFLIGHT_UNIT: u64 = 1024;
+
+/// Entries of each kind (metadata, listings, negative entries, pending
+/// changes) a consistency scrub checks per pass
+const SCRUB_SAMPLE: usize = 64;
+
+/// Settings shared by every write-back cache, whatever stores its content
+#[derive(Debug, Clone)]
+pub struct WriteBackConfig {
+    /// Interval for syncing dirty state to backend
+    pub flush_interval: Duration,
+    /// TTL for cached metadata from backend
+    pub metadata_ttl: Duration,
+    /// TTL for remembering paths missing from the backend
+    pub negative_ttl: Duration,
+    /// Glob patterns for local-only paths, which are never synced to or
+    /// fetched from the backend
+    pub exclude_patterns: Vec<String>,
+    /// How cached content is revalidated against the backend
+    pub revalidation: RevalidationMode,
+    /// Upload a file's pending change before flush returns
+    pub strict_fsync: bool,
+    /// Interval for checking cached state against the backend (None disables)
+    pub scrub_interval: Option<Duration>,
+    /// When background sync is paused
+    pub sync_schedule: SyncScheduleConfig,
+    /// Files larger than this many bytes are uploaded after smaller ones
+    pub large_file_threshold: u64,
+    /// Most bytes of file content uploaded at once
+    pub max_inflight: u64,
+    /// Most entries in each of the metadata and negative caches
+    pub max_metadata_entries: usize,
+    /// Most cached directory listings
+    pub max_dir_listings: usize,
+}
+
+/// A local-only item, saved so it can be restored after a restart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalEntry {
+    pub path: PathBuf,
+    pub kind: LocalKind,
+    #[serde(default)]
+    pub mode: Option<u32>,
+}
+
+/// Kind of local-only item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalKind {
+    /// A file whose content is held by the store
+    File,
+    Directory,
+    Symlink {
+        target: PathBuf,
+    },
+}
+
+/// Where a write-back cache keeps file content
+///
+/// Stores only hold bytes; whether content is dirty, new or deleted is
+/// tracked by [`WriteBackCache`]. Paths are the logical paths seen by the
+/// filesystem.
+pub trait ContentStore: Send + Sync + 'static {
+    /// Label used in log messages
+    const NAME: &'static str;
+
+    /// Whether the store holds content (or a directory marker) for `path`
+    fn contains(&self, path: &Path) -> bool;
+
+    /// Metadata of stored content, without a mode
+    fn stat(&self, path: &Path) -> Option<Metadata>;
+
+    /// Length of stored file content
+    fn content_len(&self, path: &Path) -> Option<u64>;
+
+    /// Read part of stored content, or None if it isn't stored
+    fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Option<Bytes>>;
+
+    /// Read all stored content for upload, or None if it isn't stored
+    fn read_all(&self, path: &Path) -> Result<Option<Bytes>>;
+
+    /// Write at `offset`, creating the content if needed
+    fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<()>;
+
+    /// Shrink or zero-extend stored content
+    fn truncate(&self, path: &Path, size: u64) -> Result<()>;
+
+    /// Zero `len` bytes at `offset`; the range is within the content
+    fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()>;
+
+    /// Store empty content, replacing anything already stored
+    fn create_file(&self, path: &Path) -> Result<()>;
+
+    /// Record a locally created directory
+    fn create_dir(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Store content fetched from the backend
+    fn fill(&self, path: &Path, data: Bytes) -> Result<()>;
+
+    /// Drop whatever is stored for `path`
+    fn remove(&self, path: &Path);
+
+    /// Move stored content; does nothing if `from` isn't stored
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Find the next data or hole region of stored file content
+    fn seek_region(&self, path: &Path, offset: u64, region: SeekRegion) -> Result<Option<u64>>;
+
+    /// Approximate size of all stored content in bytes
+    fn size(&self) -> u64;
+
+    /// Make room for `needed` more bytes, failing with `NoSpace` if that
+    /// would mean evicting content in `pinned`
+    fn reserve(&self, _needed: u64, _pinned: &Pinned<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Evict content that isn't in `pinned` if the store is over its limits
+    fn evict(&self, _pinned: &Pinned<'_>) {}
+
+    /// Drop all content that isn't in `pinned`
+    fn clear(&self, pinned: &Pinned<'_>);
+
+    /// Remember the current local-only items so a later run can restore
+    /// them; stores that don't outlive the process keep nothing
+    fn save_local_only(&self, _entries: &[LocalEntry]) {}
+
+    /// Local-only items saved by a previous run
+    fn load_local_only(&self) -> Vec<LocalEntry> {
+        Vec::new()
+    }
+
+    /// Note that `data`, now stored for `path`, is a clean copy of the
+    /// backend version tagged `etag`, so a later run can reuse it
+    fn mark_clean(&self, _path: &Path, _data: &[u8], _etag: Option<&str>) {}
+
+    /// Backend version of clean content kept from an earlier run
+    fn clean_etag(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Write out what `mark_clean` noted; stores that don't outlive the
+    /// process keep nothing
+    fn save_manifest(&self) {}
+}
+
+/// Paths with pending changes, whose stored content is the only copy and
+/// must not be evicted
+pub struct Pinned<'a> {
+    pending: &'a DashMap<PathBuf, PendingChange>,
+}
+
+impl Pinned<'_> {
+    /// Whether `path` has a pending change
+    pub fn contains(&self, path: &Path) -> bool {
+        self.pending.contains_key(path)
+    }
+
+    /// All paths with pending changes
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.pending
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+}
+
+/// Type of pending change
+#[derive(Debug, Clone)]
+enum PendingChangeType {
+    /// New file created locally
+    NewFile,
+    /// File content modified
+    ModifiedFile,
+    /// File deleted locally
+    DeletedFile,
+    /// New directory created locally
+    NewDirectory,
+    /// Directory deleted locally
+    DeletedDirectory,
+    /// New symlink created locally
+    NewSymlink { target: PathBuf },
+}
+
+/// Source of `PendingChange::generation`
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A pending change that needs to be synced to backend
+#[derive(Debug, Clone)]
+struct PendingChange {
+    change_type: PendingChangeType,
+    /// File mode if applicable
+    mode: Option<u32>,
+    /// Changes with every local change to the item, so a sync only
+    /// clears the change it uploaded
+    generation: u64,
+}
+
+impl PendingChange {
+    fn new(change_type: PendingChangeType, mode: Option<u32>) -> Self {
+        Self {
+            change_type,
+            mode,
+            generation: next_generation(),
+        }
+    }
+
+    /// Whether this creates something the backend doesn't have yet
+    fn is_create(&self) -> bool {
+        matches!(
+            self.change_type,
+            PendingChangeType::NewFile
+                | PendingChangeType::NewDirectory
+                | PendingChangeType::NewSymlink { .. }
+        )
+    }
+}
+
+/// Cached metadata entry
+#[derive(Debug, Clone)]
+struct CachedMetadata {
+    metadata: Metadata,
+    cached_at: Instant,
+}
+
+/// Cached directory listing entry (from backend)
+#[derive(Debug, Clone)]
+struct CachedDirListing {
+    entries: Vec<DirEntry>,
+    cached_at: Instant,
+}
+
+/// Keep `map` within `max` entries, dropping entries older than `ttl`
+/// first and then the least recently cached ones
+///
+/// A full map is trimmed to 90% of `max`, so it isn't trimmed again on
+/// every insert.
+fn trim_cache<V>(
+    map: &DashMap<PathBuf, V>,
+    max: usize,
+    ttl: Duration,
+    cached_at: impl Fn(&V) -> Instant,
+) {
+    if map.len() <= max {
+        return;
+    }
+    map.retain(|_, v| cached_at(v).elapsed() < ttl);
+
+    let target = max - max / 10;
+    if map.len() <= target {
+        return;
+    }
+    let mut entries: Vec<(Instant, PathBuf)> = map
+        .iter()
+        .map(|entry| (cached_at(entry.value()), entry.key().clone()))
+        .collect();
+    entries.sort();
+    let excess = entries.len().saturating_sub(target);
+    for (_, path) in entries.into_iter().take(excess) {
+        map.remove(&path);
+    }
+}
+
+/// Failed sync attempts for a path with a pending change
+#[derive(Debug, Clone, Default)]
+struct SyncFailure {
+    retries: u32,
+    last_error: String,
+}
+
+/// Backend ETag of cached content and when it was last confirmed
+#[derive(Debug, Clone)]
+struct CachedEtag {
+    etag: String,
+    validated_at: Instant,
+}
+
+/// Negative cache entry (path known not to exist)
+#[derive(Debug, Clone)]
+struct NegativeCacheEntry {
+    cached_at: Instant,
+}
+
+/// Write-back caching connector wrapper
+///
+/// This cache layer:
+/// - All mutations happen locally first
+/// - Background task syncs dirty state to backend periodically
+/// - Stores cached file content in a [`ContentStore`]
+/// - Tracks all pending changes (creates, deletes, renames, symlinks)
+/// - Caches metadata and directory listings with TTL
+/// - Preserves POSIX file modes
+/// - Supports excluding files from sync via glob patterns
+pub struct WriteBackCache<C: Connector, S: ContentStore> {
+    inner: Arc<C>,
+    config: WriteBackConfig,
+    /// Cached file content
+    store: S,
+    /// Pending changes that need to be synced to backend
+    pending_changes: DashMap<PathBuf, PendingChange>,
+    /// Upload order of files with pending changes
+    queue: SyncQueue,
+    /// Limits file content being uploaded at once, in `INFLIGHT_UNIT`s
+    inflight: Semaphore,
+    /// Cached metadata with TTL (from backend, for paths without pending changes)
+    metadata_cache: Arc<DashMap<PathBuf, CachedMetadata>>,
+    /// Cached file modes (separate from metadata for persistence)
+    mode_cache: DashMap<PathBuf, u32>,
+    /// Cached directory listings from backend (merged with pending changes at read time)
+    dir_cache: Arc<DashMap<PathBuf, CachedDirListing>>,
+    /// Negative cache: paths known not to exist on backend
+    negative_cache: DashMap<PathBuf, NegativeCacheEntry>,
+    /// Backend ETags of cached content (only tracked when revalidating or
+    /// sharing with peers)
+    etags: DashMap<PathBuf, CachedEtag>,
+    /// Changed byte ranges of modified files whose backend copy is known
+    dirty: DashMap<PathBuf, DirtyRanges>,
+    /// Shutdown notification for background sync task
+    shutdown: Arc<Notify>,
+    /// Wakes the background task for an early sync-and-evict pass
+    space_low: Arc<Notify>,
+    /// Flag to track if background sync is running
+    sync_running: Arc<RwLock<bool>>,
+    /// Compiled glob patterns for excluding files from sync
+    exclude_matcher: Option<GlobSet>,
+    /// Verifies backend content against stored hashes on cache fill
+    integrity: Option<Arc<IntegrityMonitor>>,
+    /// Receives sync failures for the status overlay
+    errors: Option<Arc<ErrorEvents>>,
+    /// Other adapters asked for content before the backend
+    peers: Option<PeerLink>,
+    /// Reads served from cache
+    hits: AtomicU64,
+    /// Reads that fetched from the backend
+    misses: AtomicU64,
+    /// When the last sync finished
+    last_sync: RwLock<Option<DateTime<Utc>>>,
+    /// Most recent sync failure
+    last_sync_error: RwLock<Option<String>>,
+    /// Failed sync attempts for paths that are still pending
+    sync_failures: DashMap<PathBuf, SyncFailure>,
+    /// Directory renames moving backend content, by source path
+    renames: DashMap<PathBuf, RenameProgress>,
+    /// Results of the most recent consistency scrub
+    last_scrub: RwLock<Option<ScrubReport>>,
+    /// Background sync paused by an operator
+    sync_paused: AtomicBool,
+    /// Why the sync schedule held back the last background pass, if it did
+    scheduled_pause: RwLock<Option<&'static str>>,
+}
+
+impl<C: Connector + 'static, S: ContentStore> WriteBackCache<C, S> {
+    /// Create a write-back cache keeping content in `store`
+    pub fn from_store(connector: C, store: S, config: WriteBackConfig) -> Self {
+        // Build the exclude matcher from glob patterns
+        let exclude_matcher = Self::build_exclude_matcher(&config.exclude_patterns);
+        let queue = SyncQueue::new(config.large_file_threshold);
+        let inflight = Semaphore::new(Self::inflight_permits(config.max_inflight) as usize);
+
+        let cache = Self {
+            inner: Arc::new(connector),
+            config,
+            store,
+            pending_changes: DashMap::new(),
+            queue,
+            inflight,
+            metadata_cache: Arc::new(DashMap::new()),
+            mode_cache: DashMap::new(),
+            dir_cache: Arc::new(DashMap::new()),
+            negative_cache: DashMap::new(),
+            etags: DashMap::new(),
+            dirty: DashMap::new(),
+            shutdown: Arc::new(Notify::new()),
+            space_low: Arc::new(Notify::new()),
+            sync_running: Arc::new(RwLock::new(false)),
+            exclude_matcher,
+            integrity: None,
+            errors: None,
+            peers: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            last_sync: RwLock::new(None),
+            last_sync_error: RwLock::new(None),
+            sync_failures: DashMap::new(),
+            renames: DashMap::new(),
+            last_scrub: RwLock::new(None),
+            sync_paused: AtomicBool::new(false),
+            scheduled_pause: RwLock::new(None),
+        };
+        cache.restore_local_only();
+        cache
+    }
+
+    /// Verify content fetched from the backend against its stored hash
+    pub fn with_integrity(mut self, monitor: Arc<IntegrityMonitor>) -> Self {
+        self.integrity = Some(monitor);
+        self
+    }
+
+    /// Report sync failures to `errors`
+    pub fn with_error_events(mut self, errors: Arc<ErrorEvents>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    /// Ask `peers` for content before reading it from the backend, and
+    /// track ETags so this cache's content can be served to them
+    pub fn with_peers(mut self, peers: PeerLink) -> Self {
+        self.peers = Some(peers);
+        self
+    }
+
+    /// The store holding cached content
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Paths whose content must stay in the store until synced
+    pub(crate) fn pinned(&self) -> Pinned<'_> {
+        Pinned {
+            pending: &self.pending_changes,
+        }
+    }
+
+    /// Build a GlobSet from exclude patterns
+    fn build_exclude_matcher(patterns: &[String]) -> Option<GlobSet> {
+        if patterns.is_empty() {
+            return None;
+        }
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            match Glob::new(pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                }
+                Err(e) => {
+                    warn!("Invalid exclude pattern '{}': {}", pattern, e);
+                }
+            }
+        }
+
+        match builder.build() {
+            Ok(set) => {
+                info!(
+                    "{} cache: configured {} exclude patterns for sync",
+                    S::NAME,
+                    patterns.len()
+                );
+                Some(set)
+            }
+            Err(e) => {
+                warn!("Failed to build exclude matcher: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Local-only items that currently exist
+    fn local_only_entries(&self) -> Vec<LocalEntry> {
+        let mut entries: Vec<LocalEntry> = self
+            .pending_changes
+            .iter()
+            .filter(|entry| self.is_excluded(entry.key()))
+            .filter_map(|entry| {
+                let kind = match &entry.value().change_type {
+                    PendingChangeType::NewFile | PendingChangeType::ModifiedFile => LocalKind::File,
+                    PendingChangeType::NewDirectory => LocalKind::Directory,
+                    PendingChangeType::NewSymlink { target } => LocalKind::Symlink {
+                        target: target.clone(),
+                    },
+                    PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory => {
+                        return None
+                    }
+                };
+                Some(LocalEntry {
+                    path: entry.key().clone(),
+                    kind,
+                    mode: self
+                        .mode_cache
+                        .get(entry.key())
+                        .map(|m| *m)
+                        .or(entry.value().mode),
+                })
+            })
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Bring back the local-only items saved by a previous run
+    fn restore_local_only(&self) {
+        let mut restored = 0;
+        for entry in self.store.load_local_only() {
+            let change_type = match entry.kind {
+                LocalKind::File if !self.store.contains(&entry.path) => {
+                    warn!("Local-only file {:?} missing from cache", entry.path);
+                    continue;
+                }
+                LocalKind::File => PendingChangeType::NewFile,
+                LocalKind::Directory => PendingChangeType::NewDirectory,
+                LocalKind::Symlink { target } => PendingChangeType::NewSymlink { target },
+            };
+            if let Some(mode) = entry.mode {
+                self.mode_cache.insert(entry.path.clone(), mode);
+            }
+            self.pending_changes
+                .insert(entry.path, PendingChange::new(change_type, entry.mode));
+            restored += 1;
+        }
+        if restored > 0 {
+            info!("{} cache restored {} local-only paths", S::NAME, restored);
+        }
+    }
+
+    /// Wrap the cache in an Arc with its background sync task running
+    ///
+    /// Prefer this over calling `start_background_sync` separately, which is
+    /// easy to forget and leaves pending changes unsynced.
+    pub fn start(self) -> Arc<Self> {
+        let cache = Arc::new(self);
+        cache.start_background_sync();
+        cache
+    }
+
+    /// Start the background sync task, and the janitor task that drops
+    /// expired cache entries
+    /// This should be called after the cache is wrapped in an Arc
+    pub fn start_background_sync(self: &Arc<Self>) {
+        self.start_janitor();
+        let cache = Arc::clone(self);
+        let flush_interval = cache.config.flush_interval;
+        let shutdown = Arc::clone(&cache.shutdown);
+        let space_low = Arc::clone(&cache.space_low);
+        let mut scrub_timer = cache.config.scrub_interval.map(|interval| {
+            let mut timer =
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            timer
+        });
+
+        tokio::spawn(async move {
+            info!(
+                "{} cache background sync task started with interval {:?}",
+                S::NAME,
+                flush_interval
+            );
+
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(flush_interval) => {
+                        if cache.background_paused().await {
+                            cache.store.evict(&cache.pinned());
+                            continue;
+                        }
+                        if let Err(e) = cache.sync_to_backend().await {
+                            error!("{} cache background sync failed: {}", S::NAME, e);
+                        }
+                        cache.store.evict(&cache.pinned());
+                    }
+                    _ = space_low.notified() => {
+                        if cache.background_paused().await {
+                            warn!("{} cache low on space but sync is paused", S::NAME);
+                            cache.store.evict(&cache.pinned());
+                            continue;
+                        }
+                        // Syncing turns dirty content into clean content that can be evicted
+                        info!("{} cache low on space, syncing early", S::NAME);
+                        if let Err(e) = cache.sync_to_backend().await {
+                            error!("{} cache background sync failed: {}", S::NAME, e);
+                        }
+                        cache.store.evict(&cache.pinned());
+                    }
+                    _ = async { scrub_timer.as_mut().unwrap().tick().await },
+                        if scrub_timer.is_some() => {
+                        if !cache.background_paused().await {
+                            cache.scrub().await;
+                        }
+                    }
+                    _ = shutdown.notified() => {
+                        info!("{} cache background sync task shutting down", S::NAME);
+                        // Final sync before shutdown
+                        if let Err(e) = cache.sync_to_backend().await {
+                            error!("{} cache final sync failed: {}", S::NAME, e);
+                        }
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Periodically sweep expired entries out of the metadata, negative and
+    /// listing caches, which are otherwise only ignored once stale
+    ///
+    /// The task holds the cache weakly and stops on shutdown or once the
+    /// cache is dropped.
+    fn start_janitor(self: &Arc<Self>) {
+        let cache = Arc::downgrade(self);
+        let shutdown = Arc::clone(&self.shutdown);
+        let interval = self
+            .config
+            .metadata_ttl
+            .min(self.config.negative_ttl)
+            .clamp(JANITOR_MIN_INTERVAL, JANITOR_MAX_INTERVAL);
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {
+                        let Some(cache) = cache.upgrade() else {
+                            break;
+                        };
+                        cache.sweep_expired();
+                    }
+                    _ = shutdown.notified() => {
+                        // Pass the notification on, in case it was meant for
+                        // the sync task
+                        shutdown.notify_one();
+                        break;
+                    }
+                }
+            }
+            debug!("{} cache janitor task stopped", S::NAME);
+        });
+    }
+
+    /// Drop metadata, negative and listing entries whose TTL has expired
+    fn sweep_expired(&self) {
+        let before = self.metadata_cache.len() + self.negative_cache.len() + self.dir_cache.len();
+        self.metadata_cache
+            .retain(|_, m| m.cached_at.elapsed() < self.config.metadata_ttl);
+        self.negative_cache
+            .retain(|_, n| n.cached_at.elapsed() < self.config.negative_ttl);
+        self.dir_cache
+            .retain(|_, l| l.cached_at.elapsed() < self.config.metadata_ttl);
+        let after = self.metadata_cache.len() + self.negative_cache.len() + self.dir_cache.len();
+        if after < before {
+            trace!(
+                "{} cache janitor dropped {} expired entries",
+                S::NAME,
+                before - after
+            );
+        }
+    }
+
+    /// Why background sync is held back right now, if it is
+    fn pause_reason(&self) -> Option<&'static str> {
+        if self.sync_paused.load(Ordering::Relaxed) {
+            Some("manual")
+        } else {
+            *self.scheduled_pause.read()
+        }
+    }
+
+    /// Check whether background sync and scrubbing should be skipped,
+    /// logging when the sync schedule starts or stops holding them back
+    async fn background_paused(&self) -> bool {
+        if self.sync_paused.load(Ordering::Relaxed) {
+            return true;
+        }
+        let reason = self.config.sync_schedule.pause_reason().await;
+        let previous = std::mem::replace(&mut *self.scheduled_pause.write(), reason);
+        if previous != reason {
+            match reason {
+                Some(reason) => info!("{} cache background sync paused ({})", S::NAME, reason),
+                None => info!("{} cache background sync resumed", S::NAME),
+            }
+        }
+        reason.is_some()
+    }
+
+    /// Make room in the store for `needed` more bytes. Content with pending
+    /// changes can't be evicted until it is synced, so running out of room
+    /// also wakes the background task for an early sync.
+    fn reserve(&self, needed: u64) -> Result<()> {
+        let result = self.store.reserve(needed, &self.pinned());
+        if matches!(result, Err(FuseAdapterError::NoSpace)) {
+            self.space_low.notify_one();
+        }
+        result
+    }
+
+    /// Check if path has a pending delete
+    fn is_pending_delete(&self, path: &Path) -> bool {
+        self.pending_changes.get(path).is_some_and(|change| {
+            matches!(
+                change.change_type,
+                PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory
+            )
+        })
+    }
+
+    /// Check if path has a pending create (file, dir, or symlink)
+    fn is_pending_create(&self, path: &Path) -> bool {
+        self.pending_changes
+            .get(path)
+            .is_some_and(|change| change.is_create())
+    }
+
+    /// Check if path is a pending new directory
+    fn is_pending_dir(&self, path: &Path) -> bool {
+        self.pending_changes
+            .get(path)
+            .is_some_and(|c| matches!(c.change_type, PendingChangeType::NewDirectory))
+    }
+
+    /// Check if any ancestor of path is a pending new directory.
+    /// If so, the path cannot exist on the backend.
+    fn has_pending_new_ancestor(&self, path: &Path) -> bool {
+        path.ancestors()
+            .skip(1)
+            .any(|parent| self.is_pending_dir(parent))
+    }
+
+    /// Check if path is in negative cache (known not to exist on backend)
+    fn is_negative_cached(&self, path: &Path) -> bool {
+        self.negative_cache
+            .get(path)
+            .is_some_and(|entry| entry.cached_at.elapsed() < self.config.negative_ttl)
+    }
+
+    /// Add path to negative cache
+    fn add_to_negative_cache(&self, path: &Path) {
+        self.negative_cache.insert(
+            path.to_path_buf(),
+            NegativeCacheEntry {
+                cached_at: Instant::now(),
+            },
+        );
+        trim_cache(
+            &self.negative_cache,
+            self.config.max_metadata_entries,
+            self.config.negative_ttl,
+            |e| e.cached_at,
+        );
+    }
+
+    /// Remove path from negative cache (e.g., when it's created)
+    fn remove_from_negative_cache(&self, path: &Path) {
+        self.negative_cache.remove(path);
+    }
+
+    /// Read from the content store
+    fn read_from_cache(&self, path: &Path, offset: u64, size: u32) -> Result<Option<Bytes>> {
+        // Check for pending delete
+        if self.is_pending_delete(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        self.store.read(path, offset, size)
+    }
+
+    /// Record that `range` of a cached file is about to change
+    ///
+    /// Tracking only starts while the cached copy still matches the backend
+    /// (no pending change), so files with an unknown history are uploaded in
+    /// full.
+    fn mark_dirty(&self, path: &Path, range: Range<u64>) {
+        // The cached copy no longer matches the backend version it came from
+        self.etags.remove(path);
+        if let Some(mut dirty) = self.dirty.get_mut(path) {
+            dirty.add(range);
+            return;
+        }
+        if self.pending_changes.contains_key(path) {
+            return;
+        }
+        if let Some(base_size) = self.store.content_len(path) {
+            let mut dirty = DirtyRanges::new(base_size);
+            dirty.add(range);
+            self.dirty.insert(path.to_path_buf(), dirty);
+        }
+    }
+
+    /// Changed ranges to upload for a pending change (None = upload in full)
+    fn dirty_for_upload(&self, path: &Path, change: &PendingChange) -> Option<DirtyRanges> {
+        if !matches!(change.change_type, PendingChangeType::ModifiedFile) {
+            return None;
+        }
+        self.dirty.get(path).map(|d| d.clone())
+    }
+
+    /// Stop tracking changed ranges for `path` and anything beneath it
+    fn forget_dirty(&self, path: &Path) {
+        self.dirty.retain(|p, _| !p.starts_with(path));
+    }
+
+    /// Mark a file's content as modified (or keep it new if it was new)
+    fn mark_modified(&self, path: &Path) {
+        self.pending_changes
+            .entry(path.to_path_buf())
+            .and_modify(|change| {
+                if !matches!(change.change_type, PendingChangeType::NewFile) {
+                    change.change_type = PendingChangeType::ModifiedFile;
+                }
+                change.generation = next_generation();
+            })
+            .or_insert(PendingChange::new(PendingChangeType::ModifiedFile, None));
+        self.queue.enqueue(path);
+        self.metadata_cache.remove(path);
+    }
+
+    /// Write to the content store
+    fn write_to_cache(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.reserve(data.len() as u64)?;
+        self.mark_dirty(path, offset..offset + data.len() as u64);
+        self.store.write(path, offset, data)?;
+        self.mark_modified(path);
+
+        // Evict if necessary, now that the new content is pinned
+        self.store.evict(&self.pinned());
+
+        Ok(data.len() as u64)
+    }
+
+    /// Track a new local item, replacing any pending change at `path`
+    fn track_new(&self, path: &Path, change_type: PendingChangeType, mode: Option<u32>) {
+        self.forget_dirty(path);
+
+        // Recreating something whose deletion hasn't synced yet replaces the
+        // backend copy, which still exists, instead of creating a new one
+        let deleted = self
+            .pending_changes
+            .get(path)
+            .map(|change| change.change_type.clone());
+        match (deleted, change_type) {
+            (Some(PendingChangeType::DeletedDirectory), PendingChangeType::NewDirectory) => {
+                self.pending_changes.remove(path);
+            }
+            (Some(PendingChangeType::DeletedFile), PendingChangeType::NewFile) => {
+                let change_type = PendingChangeType::ModifiedFile;
+                self.pending_changes
+                    .insert(path.to_path_buf(), PendingChange::new(change_type, mode));
+            }
+            (_, change_type) => {
+                self.pending_changes
+                    .insert(path.to_path_buf(), PendingChange::new(change_type, mode));
+            }
+        }
+
+        self.queue.enqueue(path);
+
+        // Store mode if provided
+        if let Some(m) = mode {
+            self.mode_cache.insert(path.to_path_buf(), m);
+        }
+
+        // Remove from negative cache (it now exists)
+        self.remove_from_negative_cache(path);
+
+        // Invalidate parent directory cache
+        if let Some(parent) = path.parent() {
+            self.dir_cache.remove(parent);
+        }
+    }
+
+    /// Create an empty file in the cache (local only)
+    fn create_in_cache(&self, path: &Path, mode: Option<u32>) -> Result<()> {
+        self.store.create_file(path)?;
+        self.track_new(path, PendingChangeType::NewFile, mode);
+        Ok(())
+    }
+
+    /// Create a directory locally
+    fn create_dir_in_cache(&self, path: &Path, mode: Option<u32>) -> Result<()> {
+        self.store.create_dir(path)?;
+        self.track_new(path, PendingChangeType::NewDirectory, mode);
+        Ok(())
+    }
+
+    /// Create a symlink locally
+    fn create_symlink_in_cache(&self, target: &Path, link_path: &Path) -> Result<()> {
+        let change_type = PendingChangeType::NewSymlink {
+            target: target.to_path_buf(),
+        };
+        self.track_new(link_path, change_type, None);
+        Ok(())
+    }
+
+    /// Read the target of a symlink created locally
+    fn read_symlink_from_cache(&self, path: &Path) -> Option<PathBuf> {
+        self.pending_changes.get(path).and_then(|change| {
+            if let PendingChangeType::NewSymlink { target } = &change.change_type {
+                Some(target.clone())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Mark a file as deleted locally
+    fn mark_deleted(&self, path: &Path, is_dir: bool) {
+        // Remove from content store
+        self.store.remove(path);
+        self.etags.remove(path);
+        self.forget_dirty(path);
+
+        // A pending new item was never synced - just forget it
+        if self.is_pending_create(path) || self.is_excluded(path) {
+            self.pending_changes.remove(path);
+        } else {
+            let change_type = if is_dir {
+                PendingChangeType::DeletedDirectory
+            } else {
+                PendingChangeType::DeletedFile
+            };
+            self.pending_changes
+                .insert(path.to_path_buf(), PendingChange::new(change_type, None));
+        }
+
+        self.metadata_cache.remove(path);
+        self.mode_cache.remove(path);
+
+        // Invalidate parent directory cache
+        if let Some(parent) = path.parent() {
+            self.dir_cache.remove(parent);
+        }
+    }
+
+    /// Truncate a cached file
+    fn truncate_in_cache(&self, path: &Path, size: u64) -> Result<()> {
+        if let Some(old_size) = self.store.content_len(path) {
+            if size > old_size {
+                self.reserve(size - old_size)?;
+            }
+            self.mark_dirty(path, size.min(old_size)..size.max(old_size));
+            self.store.truncate(path, size)?;
+            self.mark_modified(path);
+        }
+
+        self.metadata_cache.remove(path);
+
+        Ok(())
+    }
+
+    /// Zero a range of a cached file
+    fn zero_range_in_cache(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        let size = self.store.content_len(path).unwrap_or(0);
+        let end = offset.saturating_add(len).min(size);
+        if offset >= end {
+            return Ok(());
+        }
+        self.mark_dirty(path, offset..end);
+        self.store.zero_range(path, offset, end - offset)?;
+        self.mark_modified(path);
+
+        Ok(())
+    }
+
+    /// Fetch a file from backend into cache
+    async fn fetch_to_cache(&self, path: &Path) -> Result<()> {
+        // Don't fetch if pending delete or local-only
+        if self.is_pending_delete(path) || self.is_excluded(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // Get file metadata first
+        let meta = self.inner.stat(path).await?;
+
+        if !meta.is_file() {
+            return Err(FuseAdapterError::IsADirectory(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        self.reserve(meta.size)?;
+
+        // Read entire file from a peer holding this version, or the backend
+        let from_peer = match (&self.peers, &meta.etag) {
+            (Some(peers), Some(etag)) if meta.size > 0 => peers.fetch(path, meta.size, etag).await,
+            _ => None,
+        };
+        let data = match from_peer {
+            Some(data) => data,
+            None if meta.size > 0 => self.inner.read(path, 0, meta.size as u32).await?,
+            None => Bytes::new(),
+        };
+
+        if let Some(ref integrity) = self.integrity {
+            integrity
+                .verify(self.inner.as_ref(), path, meta.etag.as_deref(), &data)
+                .await?;
+        }
+
+        self.store.fill(path, data.clone())?;
+        self.store.mark_clean(path, &data, meta.etag.as_deref());
+
+        // Remember which version we cached so it can be revalidated
+        self.record_etag(path, meta.etag.clone());
+
+        // Cache the metadata and mode
+        if let Some(mode) = meta.mode {
+            self.mode_cache.insert(path.to_path_buf(), mode);
+        }
+        self.cache_metadata(path, meta);
+
+        // Evict if necessary
+        self.store.evict(&self.pinned());
+
+        Ok(())
+    }
+
+    /// Whether ETags of cached content are needed
+    fn tracks_etags(&self) -> bool {
+        self.config.revalidation != RevalidationMode::None || self.peers.is_some()
+    }
+
+    /// Store the ETag of freshly cached content
+    fn record_etag(&self, path: &Path, etag: Option<String>) {
+        if !self.tracks_etags() {
+            return;
+        }
+        match etag {
+            Some(etag) => {
+                self.etags.insert(
+                    path.to_path_buf(),
+                    CachedEtag {
+                        etag,
+                        validated_at: Instant::now(),
+                    },
+                );
+            }
+            None => {
+                self.etags.remove(path);
+            }
+        }
+    }
+
+    /// Revalidate cached content against the backend once the metadata TTL
+    /// has expired, dropping it if the object changed underneath us.
+    ///
+    /// Content with pending local changes is never dropped.
+    async fn revalidate_content(&self, path: &Path) {
+        if self.config.revalidation == RevalidationMode::None
+            || self.pending_changes.contains_key(path)
+        {
+            return;
+        }
+
+        // Nothing to revalidate once the store has evicted the content
+        if !self.store.contains(path) {
+            self.etags.remove(path);
+            return;
+        }
+
+        let (etag, reused) = match self.etags.get(path) {
+            Some(entry) if entry.validated_at.elapsed() >= self.config.metadata_ttl => {
+                (entry.etag.clone(), false)
+            }
+            Some(_) => return,
+            // Content kept from an earlier run is checked on first use
+            None => match self.store.clean_etag(path) {
+                Some(etag) => (etag, true),
+                None => return,
+            },
+        };
+
+        match self.inner.revalidate(path, &etag).await {
+            Ok(true) => {
+                trace!("revalidate: {:?} unchanged", path);
+                if reused {
+                    self.record_etag(path, Some(etag));
+                } else if let Some(mut entry) = self.etags.get_mut(path) {
+                    entry.validated_at = Instant::now();
+                }
+            }
+            Ok(false) => {
+                debug!("revalidate: {:?} changed on backend, invalidating", path);
+                self.invalidate_content(path);
+            }
+            Err(e) => {
+                // Keep serving the cached copy; we'll retry on the next access
+                warn!("revalidate: failed to check {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// Drop cached content and metadata for a path
+    fn invalidate_content(&self, path: &Path) {
+        self.store.remove(path);
+        self.etags.remove(path);
+        self.metadata_cache.remove(path);
+        self.negative_cache.remove(path);
+    }
+
+    /// Get cached metadata if still valid
+    fn get_cached_metadata(&self, path: &Path) -> Option<Metadata> {
+        self.metadata_cache.get(path).and_then(|entry| {
+            if entry.cached_at.elapsed() < self.config.metadata_ttl {
+                Some(entry.metadata.clone())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Cache metadata
+    fn cache_metadata(&self, path: &Path, metadata: Metadata) {
+        self.metadata_cache.insert(
+            path.to_path_buf(),
+            CachedMetadata {
+                metadata,
+                cached_at: Instant::now(),
+            },
+        );
+        trim_cache(
+            &self.metadata_cache,
+            self.config.max_metadata_entries,
+            self.config.metadata_ttl,
+            |m| m.cached_at,
+        );
+    }
+
+    /// Get metadata for a pending change
+    fn get_pending_metadata(&self, path: &Path) -> Option<Metadata> {
+        let change = self.pending_changes.get(path)?;
+        let mode = self.mode_cache.get(path).map(|r| *r);
+        let now = SystemTime::now();
+
+        match &change.change_type {
+            PendingChangeType::NewFile | PendingChangeType::ModifiedFile => {
+                let size = self.store.content_len(path).unwrap_or(0);
+                if let Some(m) = mode {
+                    Some(Metadata::file_with_mode(size, now, m))
+                } else {
+                    Some(Metadata::file(size, now))
+                }
+            }
+            PendingChangeType::NewDirectory => {
+                if let Some(m) = mode {
+                    Some(Metadata::directory_with_mode(now, m))
+                } else {
+                    Some(Metadata::directory(now))
+                }
+            }
+            PendingChangeType::NewSymlink { .. } => Some(Metadata::symlink(now)),
+            PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory => None,
+        }
+    }
+
+    /// Sync all pending changes to backend
+    pub async fn sync_to_backend(&self) -> Result<()> {
+        // Prevent concurrent syncs
+        {
+            let mut running = self.sync_running.write();
+            if *running {
+                debug!("{} cache sync already in progress, skipping", S::NAME);
+                return Ok(());
+            }
+            *running = true;
+        }
+
+        // Ensure we release the lock when done
+        let _guard = scopeguard::guard((), |_| {
+            *self.sync_running.write() = false;
+        });
+
+        self.sync_pending().await
+    }
+
+    /// Wait for any in-progress sync to finish and set the sync_running
+    /// flag, which is cleared when the returned guard drops
+    async fn claim_sync(&self) -> impl Drop + '_ {
+        loop {
+            {
+                let mut running = self.sync_running.write();
+                if !*running {
+                    *running = true;
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        scopeguard::guard((), |_| {
+            *self.sync_running.write() = false;
+        })
+    }
+
+    /// Wait for any in-progress sync to finish, then sync everything pending
+    pub async fn sync_to_backend_wait(&self) -> Result<()> {
+        let _guard = self.claim_sync().await;
+
+        self.sync_pending().await?;
+        match self.last_sync_error.read().clone() {
+            Some(e) if self.unsynced_count() > 0 => Err(FuseAdapterError::Backend(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sync all pending changes; the caller must hold the sync_running flag
+    async fn sync_pending(&self) -> Result<()> {
+        self.store.save_local_only(&self.local_only_entries());
+        self.store.save_manifest();
+
+        // Local-only paths stay pending, which keeps them listable and their
+        // content pinned, but they are never uploaded
+        let syncable: Vec<(PathBuf, PendingChange)> = self
+            .pending_changes
+            .iter()
+            .filter(|entry| !self.is_excluded(entry.key()))
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        if syncable.is_empty() {
+            trace!("No syncable pending changes");
+            return Ok(());
+        }
+
+        info!(
+            "{} cache syncing {} pending changes to backend",
+            S::NAME,
+            syncable.len()
+        );
+
+        // Sort to process directories before files (for creates) and files before directories (for deletes)
+        let creates: Vec<_> = syncable
+            .iter()
+            .filter(|(_, c)| {
+                matches!(
+                    c.change_type,
+                    PendingChangeType::NewFile
+                        | PendingChangeType::ModifiedFile
+                        | PendingChangeType::NewDirectory
+                        | PendingChangeType::NewSymlink { .. }
+                )
+            })
+            .collect();
+        let mut deletes: Vec<_> = syncable
+            .iter()
+            .filter(|(_, c)| {
+                matches!(
+                    c.change_type,
+                    PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory
+                )
+            })
+            .collect();
+
+        // Directories and symlinks go first, outermost first, so files have
+        // their parents by the time they upload
+        let (files, mut creates): (Vec<_>, Vec<_>) = creates.into_iter().partition(|(_, c)| {
+            matches!(
+                c.change_type,
+                PendingChangeType::NewFile | PendingChangeType::ModifiedFile
+            )
+        });
+        creates.sort_by_key(|(path, _)| path.components().count());
+
+        // Sort deletes: files first, then directories in reverse depth order
+        deletes.sort_by(|(a, ca), (b, cb)| {
+            let a_is_dir = matches!(ca.change_type, PendingChangeType::DeletedDirectory);
+            let b_is_dir = matches!(cb.change_type, PendingChangeType::DeletedDirectory);
+            match (a_is_dir, b_is_dir) {
+                (false, true) => std::cmp::Ordering::Less,
+                (true, false) => std::cmp::Ordering::Greater,
+                _ => b.components().count().cmp(&a.components().count()),
+            }
+        });
+
+        // Process creates
+        for (path, change) in creates {
+            match &change.change_type {
+                PendingChangeType::NewDirectory => {
+                    debug!("Syncing new directory: {:?}", path);
+                    let created = match change.mode {
+                        Some(mode) => self.inner.create_dir_with_mode(path, mode).await,
+                        None => self.inner.create_dir(path).await,
+                    };
+                    if let Err(e) = created {
+                        self.record_sync_error(
+                            path,
+                            format!("Failed to sync directory {:?}: {}", path, e),
+                        );
+                        continue;
+                    }
+                    self.mark_synced(path, change);
+                }
+                PendingChangeType::NewSymlink { target } => {
+                    debug!("Syncing new symlink: {:?} -> {:?}", path, target);
+                    if let Err(e) = self.inner.symlink(target, path).await {
+                        self.record_sync_error(
+                            path,
+                            format!("Failed to sync symlink {:?}: {}", path, e),
+                        );
+                        continue;
+                    }
+                    self.mark_synced(path, change);
+                }
+                _ => {}
+            }
+        }
+
+        // Upload files in queue order, several at once
+        use futures::StreamExt;
+        let mut files: Vec<(PathBuf, u64, PendingChange)> = files
+            .into_iter()
+            .map(|(path, change)| {
+                let size = self.store.content_len(path).unwrap_or(0);
+                (path.clone(), size, change.clone())
+            })
+            .collect();
+        self.queue.order(&mut files);
+        futures::stream::iter(files)
+            .map(|(path, size, change)| async move {
+                self.sync_file(&path, size, &change).await;
+            })
+            .buffer_unordered(SYNC_CONCURRENCY)
+            .collect::<Vec<()>>()
+            .await;
+
+        // Process deletes
+        for (path, change) in deletes {
+            let (deleted, what) = match change.change_type {
+                PendingChangeType::DeletedFile => {
+                    debug!("Syncing file deletion: {:?}", path);
+                    (self.inner.remove_file(path).await, "file")
+                }
+                PendingChangeType::DeletedDirectory => {
+                    debug!("Syncing directory deletion: {:?}", path);
+                    (self.inner.remove_dir(path, false).await, "directory")
+                }
+                _ => continue,
+            };
+            // Ignore NotFound errors - it might not exist on backend
+            if let Err(e) = deleted {
+                if !matches!(e, FuseAdapterError::NotFound(_)) {
+                    self.record_sync_error(
+                        path,
+                        format!("Failed to delete {} {:?}: {}", what, path, e),
+                    );
+                    continue;
+                }
+            }
+            self.mark_synced(path, change);
+        }
+
+        *self.last_sync.write() = Some(Utc::now());
+        self.sync_failures
+            .retain(|path, _| self.pending_changes.contains_key(path));
+        self.queue
+            .retain(|path| self.pending_changes.contains_key(path));
+        let remaining = self.unsynced_count();
+        if remaining == 0 {
+            *self.last_sync_error.write() = None;
+        }
+
+        info!(
+            "{} cache sync complete, {} changes remaining",
+            S::NAME,
+            remaining
+        );
+        Ok(())
+    }
+
+    /// Upload one file's pending change as part of a sync, once `size`
+    /// bytes fit within the in-flight limit
+    async fn sync_file(&self, path: &Path, size: u64, change: &PendingChange) {
+        let permits = Self::inflight_permits(size.min(self.config.max_inflight));
+        let Ok(_permit) = self.inflight.acquire_many(permits).await else {
+            return;
+        };
+        debug!("Syncing file: {:?}", path);
+
+        // Get content from the store
+        let data = match self.store.read_all(path) {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                warn!("Cache content missing for {:?}, skipping", path);
+                self.pending_changes
+                    .remove_if(path, |_, c| c.generation == change.generation);
+                return;
+            }
+            Err(e) => {
+                self.record_sync_error(
+                    path,
+                    format!("Failed to read cached content {:?}: {}", path, e),
+                );
+                return;
+            }
+        };
+
+        // Create file on backend if new
+        if matches!(change.change_type, PendingChangeType::NewFile) {
+            let created = match change.mode {
+                Some(mode) => self.inner.create_file_with_mode(path, mode).await,
+                None => self.inner.create_file(path).await,
+            };
+            if let Err(e) = created {
+                self.record_sync_error(path, format!("Failed to create file {:?}: {}", path, e));
+                return;
+            }
+        }
+
+        // Upload content
+        let dirty = self.dirty_for_upload(path, change);
+        if let Err(e) = upload_content(self.inner.as_ref(), path, &data, dirty.as_ref()).await {
+            self.record_sync_error(path, format!("Failed to write file {:?}: {}", path, e));
+            return;
+        }
+
+        // A write since the content was read keeps the change queued, with
+        // its dirty ranges, for the next sync
+        if !self.mark_synced(path, change) {
+            return;
+        }
+        self.dirty.remove(path);
+
+        // The upload produced a new version; track its ETag
+        if self.tracks_etags() {
+            match self.inner.stat(path).await {
+                Ok(meta) => {
+                    self.store.mark_clean(path, &data, meta.etag.as_deref());
+                    self.record_etag(path, meta.etag);
+                }
+                Err(_) => {
+                    self.store.mark_clean(path, &data, None);
+                    self.etags.remove(path);
+                }
+            }
+        } else {
+            self.store.mark_clean(path, &data, None);
+        }
+    }
+
+    /// In-flight permits covering `bytes`; every upload takes at least one
+    fn inflight_permits(bytes: u64) -> u32 {
+        bytes
+            .div_ceil(INFLIGHT_UNIT)
+            .clamp(1, Semaphore::MAX_PERMITS.min(u32::MAX as usize) as u64) as u32
+    }
+
+    /// Upload one file's pending change now, creating any parent
+    /// directories that only exist locally first
+    async fn sync_path(&self, path: &Path) -> Result<()> {
+        if self.is_excluded(path) {
+            return Ok(());
+        }
+        let change = match self.pending_changes.get(path) {
+            Some(change) => change.clone(),
+            None => return Ok(()),
+        };
+        if !matches!(
+            change.change_type,
+            PendingChangeType::NewFile | PendingChangeType::ModifiedFile
+        ) {
+            return Ok(());
+        }
+
+        debug!("Syncing file on fsync: {:?}", path);
+
+        self.sync_new_ancestors(path).await?;
+
+        let data = self.store.read_all(path)?.ok_or_else(|| {
+            FuseAdapterError::Cache(format!("Cache content missing for {:?}", path))
+        })?;
+
+        // Upload content
+        if matches!(change.change_type, PendingChangeType::NewFile) {
+            let created = match change.mode {
+                Some(mode) => self.inner.create_file_with_mode(path, mode).await,
+                None => self.inner.create_file(path).await,
+            };
+            if let Err(e) = created {
+                self.record_sync_error(path, format!("Failed to create file {:?}: {}", path, e));
+                return Err(e);
+            }
+        }
+        let dirty = self.dirty_for_upload(path, &change);
+        if let Err(e) = upload_content(self.inner.as_ref(), path, &data, dirty.as_ref()).await {
+            self.record_sync_error(path, format!("Failed to write file {:?}: {}", path, e));
+            return Err(e);
+        }
+
+        self.sync_failures.remove(path);
+        if !self.mark_synced(path, &change) {
+            return Ok(());
+        }
+        self.dirty.remove(path);
+        if self.tracks_etags() {
+            if let Ok(meta) = self.inner.stat(path).await {
+                self.store.mark_clean(path, &data, meta.etag.as_deref());
+                self.record_etag(path, meta.etag);
+                return Ok(());
+            }
+        }
+        self.store.mark_clean(path, &data, None);
+        Ok(())
+    }
+
+    /// Create the directories above `path` that only exist locally,
+    /// outermost first
+    async fn sync_new_ancestors(&self, path: &Path) -> Result<()> {
+        let mut new_dirs: Vec<(PathBuf, PendingChange)> = path
+            .ancestors()
+            .skip(1)
+            .filter_map(|dir| {
+                let change = self.pending_changes.get(dir)?;
+                matches!(change.change_type, PendingChangeType::NewDirectory)
+                    .then(|| (dir.to_path_buf(), change.clone()))
+            })
+            .collect();
+        new_dirs.reverse();
+        for (dir, change) in new_dirs {
+            let created = match change.mode {
+                Some(mode) => self.inner.create_dir_with_mode(&dir, mode).await,
+                None => self.inner.create_dir(&dir).await,
+            };
+            if let Err(e) = created {
+                self.record_sync_error(&dir, format!("Failed to sync directory {:?}: {}", dir, e));
+                return Err(e);
+            }
+            self.mark_synced(&dir, &change);
+        }
+        Ok(())
+    }
+
+    /// Move the backend directory `from` and everything beneath it to `to`
+    ///
+    /// Backends that can rename do it themselves; otherwise every file is
+    /// copied and the original deleted, a batch at a time. The caller holds
+    /// the sync_running flag, so no sync touches either tree meanwhile.
+    async fn rename_on_backend(&self, from: &Path, to: &Path) -> Result<()> {
+        // Deletes at the destination have to land before anything moves there
+        let deletes_at_dest = self.is_pending_delete(to)
+            || Self::children_of(&self.pending_changes, to)
+                .iter()
+                .any(|path| self.is_pending_delete(path));
+        if deletes_at_dest {
+            self.sync_pending().await?;
+            if self.is_pending_delete(to) {
+                return Err(FuseAdapterError::Backend(format!(
+                    "Can't rename onto {:?} until its deletion syncs",
+                    to
+                )));
+            }
+        }
+        self.sync_new_ancestors(to).await?;
+
+        self.renames.insert(
+            from.to_path_buf(),
+            RenameProgress {
+                from: from.to_path_buf(),
+                to: to.to_path_buf(),
+                moved: 0,
+                total: None,
+            },
+        );
+        let _progress = scopeguard::guard((), |_| {
+            self.renames.remove(from);
+        });
+
+        info!("Renaming {:?} to {:?} on the backend", from, to);
+        let result = if self.inner.capabilities().rename {
+            self.inner.rename(from, to).await
+        } else {
+            self.copy_backend_tree(from, to).await
+        };
+
+        // Whatever moved, backend state cached for either tree is stale
+        for dir in [from, to] {
+            for path in Self::children_of(&self.metadata_cache, dir) {
+                self.metadata_cache.remove(&path);
+            }
+            for path in Self::children_of(&self.dir_cache, dir) {
+                self.dir_cache.remove(&path);
+            }
+            for path in Self::children_of(&self.negative_cache, dir) {
+                self.negative_cache.remove(&path);
+            }
+            self.metadata_cache.remove(dir);
+            self.dir_cache.remove(dir);
+            self.negative_cache.remove(dir);
+            if let Some(parent) = dir.parent() {
+                self.dir_cache.remove(parent);
+            }
+        }
+
+        match &result {
+            Ok(()) => info!("Renamed {:?} to {:?} on the backend", from, to),
+            Err(e) => error!(
+                "Failed to rename {:?} to {:?} on the backend: {}",
+                from, to, e
+            ),
+        }
+        result
+    }
+
+    /// Move a backend directory tree by copying each file and deleting the
+    /// original
+    async fn copy_backend_tree(&self, from: &Path, to: &Path) -> Result<()> {
+        use futures::{StreamExt, TryStreamExt};
+
+        // Walk the whole tree first so progress has a total; parents come
+        // before their children
+        let mut dirs = vec![from.to_path_buf()];
+        let mut files = Vec::new();
+        let mut next = 0;
+        while let Some(dir) = dirs.get(next).cloned() {
+            next += 1;
+            let mut entries = self.inner.list_dir(&dir);
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                let path = dir.join(&entry.name);
+                match entry.file_type {
+                    FileType::Directory => dirs.push(path),
+                    FileType::File | FileType::Symlink => files.push(path),
+                }
+            }
+        }
+        if let Some(mut progress) = self.renames.get_mut(from) {
+            progress.total = Some(files.len());
+        }
+
+        let dest = |path: &Path| to.join(path.strip_prefix(from).unwrap());
+        for dir in &dirs {
+            let mode = self.inner.stat(dir).await?.mode;
+            let created = match mode {
+                Some(mode) => self.inner.create_dir_with_mode(&dest(dir), mode).await,
+                None => self.inner.create_dir(&dest(dir)).await,
+            };
+            match created {
+                Ok(()) | Err(FuseAdapterError::AlreadyExists(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        futures::stream::iter(files)
+            .map(|path| async move {
+                self.inner.copy(&path, &dest(&path)).await?;
+                self.inner.remove_file(&path).await?;
+                if let Some(mut progress) = self.renames.get_mut(from) {
+                    progress.moved += 1;
+                }
+                Ok::<_, FuseAdapterError>(())
+            })
+            .buffer_unordered(RENAME_BATCH)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        // Deepest first, so each is empty by the time it's removed
+        for dir in dirs.iter().rev() {
+            match self.inner.remove_dir(dir, false).await {
+                Ok(()) | Err(FuseAdapterError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget `change` once the backend has it, dropping cached backend
+    /// state from before it. Returns false, leaving the item pending, if it
+    /// changed locally while it was syncing
+    fn mark_synced(&self, path: &Path, change: &PendingChange) -> bool {
+        let latest = self
+            .pending_changes
+            .remove_if(path, |_, c| c.generation == change.generation)
+            .is_some();
+        self.forget_backend_state(path);
+        latest
+    }
+
+    /// Drop cached backend metadata for `path` and its parent's listing
+    fn forget_backend_state(&self, path: &Path) {
+        self.metadata_cache.remove(path);
+        if let Some(parent) = path.parent() {
+            self.dir_cache.remove(parent);
+        }
+    }
+
+    /// Log a sync failure and remember it for the status overlay
+    fn record_sync_error(&self, path: &Path, message: String) {
+        error!("{}", message);
+        if let Some(errors) = &self.errors {
+            errors.push("cache", "sync", path, &message);
+        }
+        {
+            let mut failure = self.sync_failures.entry(path.to_path_buf()).or_default();
+            failure.retries += 1;
+            failure.last_error = message.clone();
+        }
+        *self.last_sync_error.write() = Some(message);
+    }
+
+    /// Compare a sample of cached state against the backend, dropping
+    /// entries that no longer match and reporting local changes that now
+    /// conflict with it
+    ///
+    /// Only cached backend state is repaired; pending changes that can't be
+    /// dropped safely are left for the next sync and counted as conflicts.
+    /// Skipped, returning None, while a sync is running.
+    pub async fn scrub(&self) -> Option<ScrubReport> {
+        if *self.sync_running.read() {
+            debug!("{} cache sync in progress, skipping scrub", S::NAME);
+            return None;
+        }
+
+        let mut report = ScrubReport {
+            finished: Utc::now(),
+            checked: 0,
+            divergent: 0,
+            repaired: 0,
+            conflicts: Vec::new(),
+        };
+        self.scrub_metadata(&mut report).await;
+        self.scrub_listings(&mut report).await;
+        self.scrub_negative(&mut report).await;
+        self.scrub_pending(&mut report).await;
+        report.finished = Utc::now();
+
+        if report.divergent > 0 {
+            info!(
+                "{} cache scrub: {} of {} checked entries diverged from the backend, {} repaired",
+                S::NAME,
+                report.divergent,
+                report.checked,
+                report.repaired
+            );
+        } else {
+            debug!(
+                "{} cache scrub: {} entries checked",
+                S::NAME,
+                report.checked
+            );
+        }
+        *self.last_scrub.write() = Some(report.clone());
+        Some(report)
+    }
+
+    /// Paths of the least recently cached entries of `map`, leaving out
+    /// local-only paths
+    fn scrub_sample<V>(
+        &self,
+        map: &DashMap<PathBuf, V>,
+        cached_at: impl Fn(&V) -> Instant,
+    ) -> Vec<PathBuf> {
+        let mut entries: Vec<(Instant, PathBuf)> = map
+            .iter()
+            .filter(|entry| !self.is_excluded(entry.key()))
+            .map(|entry| (cached_at(entry.value()), entry.key().clone()))
+            .collect();
+        entries.sort();
+        entries
+            .into_iter()
+            .take(SCRUB_SAMPLE)
+            .map(|(_, path)| path)
+            .collect()
+    }
+
+    /// Check cached metadata, dropping entries (and their content) the
+    /// backend no longer agrees with
+    async fn scrub_metadata(&self, report: &mut ScrubReport) {
+        for path in self.scrub_sample(&self.metadata_cache, |m| m.cached_at) {
+            let Some(cached) = self.metadata_cache.get(&path).map(|m| m.metadata.clone()) else {
+                continue;
+            };
+            let current = match self.inner.stat(&path).await {
+                Ok(metadata) => Some(metadata),
+                Err(FuseAdapterError::NotFound(_)) => None,
+                Err(e) => {
+                    debug!("scrub: failed to stat {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            report.checked += 1;
+
+            let matches = current.as_ref().is_some_and(|current| {
+                current.file_type == cached.file_type
+                    && (cached.is_dir()
+                        || (current.size == cached.size
+                            && match (&current.etag, &cached.etag) {
+                                (Some(a), Some(b)) => a == b,
+                                _ => current.mtime == cached.mtime,
+                            }))
+            });
+            if matches {
+                continue;
+            }
+
+            warn!("scrub: cached metadata for {:?} is stale", path);
+            report.divergent += 1;
+            // Content with pending changes is the only copy
+            if !self.pending_changes.contains_key(&path) {
+                self.invalidate_content(&path);
+                if let Some(parent) = path.parent() {
+                    self.dir_cache.remove(parent);
+                }
+                report.repaired += 1;
+            }
+        }
+    }
+
+    /// Re-list cached directories, dropping listings whose entries changed
+    async fn scrub_listings(&self, report: &mut ScrubReport) {
+        use futures::TryStreamExt;
+
+        for dir in self.scrub_sample(&self.dir_cache, |l| l.cached_at) {
+            let Some(cached) = self.dir_cache.get(&dir).map(|l| l.entries.clone()) else {
+                continue;
+            };
+            let current: Vec<DirEntry> = match self.inner.list_dir(&dir).try_collect().await {
+                Ok(entries) => entries,
+                Err(FuseAdapterError::NotFound(_)) => Vec::new(),
+                Err(e) => {
+                    debug!("scrub: failed to list {:?}: {}", dir, e);
+                    continue;
+                }
+            };
+            report.checked += 1;
+
+            // Cached listings leave out pending deletes
+            let pending_deletes = self.get_pending_deletes_for_dir(&dir);
+            let names = |entries: &[DirEntry]| -> HashSet<(std::ffi::OsString, FileType)> {
+                entries
+                    .iter()
+                    .filter(|e| !pending_deletes.contains(&dir.join(&e.name)))
+                    .map(|e| (e.name.clone(), e.file_type))
+                    .collect()
+            };
+            if names(&cached) == names(&current) {
+                continue;
+            }
+
+            warn!("scrub: cached listing of {:?} is stale", dir);
+            report.divergent += 1;
+            self.dir_cache.remove(&dir);
+            report.repaired += 1;
+        }
+    }
+
+    /// Check paths remembered as missing, forgetting those that now exist
+    async fn scrub_negative(&self, report: &mut ScrubReport) {
+        for path in self.scrub_sample(&self.negative_cache, |n| n.cached_at) {
+            if !self.is_negative_cached(&path) {
+                continue;
+            }
+            let exists = match self.inner.exists(&path).await {
+                Ok(exists) => exists,
+                Err(e) => {
+                    debug!("scrub: failed to check {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            report.checked += 1;
+            if !exists {
+                continue;
+            }
+
+            warn!("scrub: {:?} is cached as missing but exists", path);
+            report.divergent += 1;
+            self.remove_from_negative_cache(&path);
+            if let Some(parent) = path.parent() {
+                self.dir_cache.remove(parent);
+            }
+            report.repaired += 1;
+        }
+    }
+
+    /// Check pending changes against the backend: deletes of paths that are
+    /// already gone are dropped, while creates of paths that now exist and
+    /// modifications of paths that no longer do are reported as conflicts
+    async fn scrub_pending(&self, report: &mut ScrubReport) {
+        let mut paths: Vec<PathBuf> = self
+            .pending_changes
+            .iter()
+            .filter(|entry| !self.is_excluded(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        paths.sort();
+        paths.truncate(SCRUB_SAMPLE);
+
+        for path in paths {
+            // Nothing under a pending new directory exists on the backend yet
+            if self.has_pending_new_ancestor(&path) {
+                continue;
+            }
+            let Some(change_type) = self
+                .pending_changes
+                .get(&path)
+                .map(|c| c.change_type.clone())
+            else {
+                continue;
+            };
+            let exists = match self.inner.exists(&path).await {
+                Ok(exists) => exists,
+                Err(e) => {
+                    debug!("scrub: failed to check {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            report.checked += 1;
+
+            match change_type {
+                PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory if !exists => {
+                    debug!("scrub: {:?} already deleted on the backend", path);
+                    report.divergent += 1;
+                    let removed = self
+                        .pending_changes
+                        .remove_if(&path, |_, c| {
+                            matches!(
+                                c.change_type,
+                                PendingChangeType::DeletedFile
+                                    | PendingChangeType::DeletedDirectory
+                            )
+                        })
+                        .is_some();
+                    if removed {
+                        self.sync_failures.remove(&path);
+                        self.forget_backend_state(&path);
+                        report.repaired += 1;
+                    }
+                }
+                PendingChangeType::NewFile
+                | PendingChangeType::NewDirectory
+                | PendingChangeType::NewSymlink { .. }
+                    if exists =>
+                {
+                    warn!(
+                        "scrub: local create of {:?} conflicts with a backend entry",
+                        path
+                    );
+                    report.divergent += 1;
+                    report.conflicts.push(path);
+                }
+                PendingChangeType::ModifiedFile if !exists => {
+                    warn!(
+                        "scrub: {:?} was modified locally but deleted on the backend",
+                        path
+                    );
+                    report.divergent += 1;
+                    report.conflicts.push(path);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Flush all pending changes (explicit sync)
+    pub async fn flush_all(&self) -> Result<()> {
+        self.sync_to_backend().await
+    }
+
+    /// Get list of pending changes for a directory (for merging with backend listing)
+    fn get_pending_entries_for_dir(&self, dir: &Path) -> Vec<DirEntry> {
+        let mut entries = Vec::new();
+
+        for entry in self.pending_changes.iter() {
+            let path = entry.key();
+
+            // Check if this path is a direct child of dir
+            if path.parent() != Some(dir) {
+                continue;
+            }
+            let file_type = match &entry.value().change_type {
+                PendingChangeType::NewFile | PendingChangeType::ModifiedFile => FileType::File,
+                PendingChangeType::NewDirectory => FileType::Directory,
+                PendingChangeType::NewSymlink { .. } => FileType::Symlink,
+                PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory => {
+                    continue; // Skip deletes
+                }
+            };
+
+            entries.push(DirEntry {
+                name: path.file_name().unwrap_or_default().to_os_string(),
+                file_type,
+                metadata: None,
+            });
+        }
+
+        entries
+    }
+
+    /// Get set of paths that are pending delete in a directory
+    fn get_pending_deletes_for_dir(&self, dir: &Path) -> HashSet<PathBuf> {
+        self.pending_changes
+            .iter()
+            .filter(|entry| {
+                entry.key().parent() == Some(dir)
+                    && matches!(
+                        entry.value().change_type,
+                        PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory
+                    )
+            })
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Paths strictly beneath `dir` among the keys of `map`
+    fn children_of<V>(map: &DashMap<PathBuf, V>, dir: &Path) -> Vec<PathBuf> {
+        map.iter()
+            .map(|entry| entry.key().clone())
+            .filter(|path| path.starts_with(dir) && path != dir)
+            .collect()
+    }
+}
+
+impl<C: Connector, S: ContentStore> WriteBackCache<C, S> {
+    /// Check if a path is local-only: it, or a directory containing it,
+    /// matches an exclude pattern
+    fn is_excluded(&self, path: &Path) -> bool {
+        let Some(ref matcher) = self.exclude_matcher else {
+            return false;
+        };
+        path.ancestors().any(|path| {
+            // Convert path to string for matching, stripping leading slash
+            let path_str = path.to_string_lossy();
+            let path_str = path_str.trim_start_matches('/');
+            !path_str.is_empty() && matcher.is_match(path_str)
+        })
+    }
+
+    /// Number of pending changes still to be uploaded
+    fn unsynced_count(&self) -> usize {
+        self.pending_changes
+            .iter()
+            .filter(|entry| !self.is_excluded(entry.key()))
+            .count()
+    }
+}
+
+impl<C: Connector, S: ContentStore> PeerSource for WriteBackCache<C, S> {
+    fn peer_content(&self, path: &Path, etag: &str) -> Option<Bytes> {
+        // Only content cached from the backend, unchanged since
+        if self.pending_changes.contains_key(path) || self.is_excluded(path) {
+            return None;
+        }
+        let clean = match self.etags.get(path) {
+            Some(entry) => entry.etag == etag,
+            None => self.store.clean_etag(path).is_some_and(|e| e == etag),
+        };
+        if !clean {
+            return None;
+        }
+        self.store.read_all(path).ok().flatten()
+    }
+}
+
+impl<C: Connector, S: ContentStore> Drop for WriteBackCache<C, S> {
+    fn drop(&mut self) {
+        // Signal shutdown to background task
+        self.shutdown.notify_waiters();
+
+        let pending_count = self.unsynced_count();
+        if pending_count > 0 {
+            warn!("{} pending changes not synced to backend", pending_count);
+        }
+    }
+}
+
+impl<C: Connector + 'static, S: ContentStore> CacheControl for WriteBackCache<C, S> {
+    fn invalidate(&self, path: &Path) {
+        info!("{} cache invalidating {:?}", S::NAME, path);
+
+        // Content is only known by path, so drop what the caches have seen
+        let mut cached: HashSet<PathBuf> = Self::children_of(&self.metadata_cache, path)
+            .into_iter()
+            .chain(Self::children_of(&self.etags, path))
+            .collect();
+        cached.insert(path.to_path_buf());
+        // Removing a directory from the store removes what it contains
+        let pending = self.pinned().paths();
+        for cached_path in cached {
+            if !pending.iter().any(|p| p.starts_with(&cached_path)) {
+                self.invalidate_content(&cached_path);
+            }
+        }
+
+        self.metadata_cache.retain(|p, _| !p.starts_with(path));
+        self.negative_cache.retain(|p, _| !p.starts_with(path));
+        self.dir_cache.retain(|p, _| !p.starts_with(path));
+        if let Some(parent) = path.parent() {
+            self.dir_cache.remove(parent);
+        }
+        self.inner.invalidate_caches();
+    }
+
+    fn invalidate_all(&self) {
+        info!("{} cache invalidating everything", S::NAME);
+        self.invalidate(Path::new("/"));
+        self.store.clear(&self.pinned());
+        self.invalidate_caches();
+    }
+
+    fn set_sync_paused(&self, paused: bool) {
+        if self.sync_paused.swap(paused, Ordering::Relaxed) != paused {
+            if paused {
+                info!("{} cache background sync paused", S::NAME);
+            } else {
+                info!("{} cache background sync resumed", S::NAME);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<C: Connector + 'static, S: ContentStore> Connector for WriteBackCache<C, S> {
+    fn capabilities(&self) -> Capabilities {
+        let mut caps = self.inner.capabilities();
+        // Cache layer enables all write operations via local cache
+        if caps.write {
+            caps.random_write = true;
+            caps.truncate = true;
+            caps.rename = true;
+        }
+        // Cache layer can always store mode locally
+        caps.set_mode = true;
+        // Symlink capability - we can cache symlinks locally
+        caps.symlink = true;
+        caps
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        CacheRequirements::default()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        let unsynced = self.unsynced_count();
+        Some(CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            pending_changes: unsynced,
+            local_only: self.pending_changes.len().saturating_sub(unsynced),
+            cache_size: self.store.size(),
+            metadata_entries: self.metadata_cache.len(),
+            negative_entries: self.negative_cache.len(),
+            dir_listings: self.dir_cache.len(),
+            last_sync: *self.last_sync.read(),
+            last_sync_error: self.last_sync_error.read().clone(),
+            sync_paused: self.pause_reason().map(str::to_string),
+            renames: self.renames.iter().map(|r| r.value().clone()).collect(),
+            scrub: self.last_scrub.read().clone(),
+            throttle: self.inner.throttle_stats(),
+        })
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        let mut pending: Vec<PendingSync> = self
+            .pending_changes
+            .iter()
+            .filter(|entry| !self.is_excluded(entry.key()))
+            .map(|entry| {
+                let kind = match entry.value().change_type {
+                    PendingChangeType::NewFile
+                    | PendingChangeType::NewDirectory
+                    | PendingChangeType::NewSymlink { .. } => PendingKind::New,
+                    PendingChangeType::ModifiedFile => PendingKind::Modified,
+                    PendingChangeType::DeletedFile | PendingChangeType::DeletedDirectory => {
+                        PendingKind::Deleted
+                    }
+                };
+                let failure = self.sync_failures.get(entry.key());
+                PendingSync {
+                    path: entry.key().clone(),
+                    kind,
+                    retries: failure.as_ref().map_or(0, |f| f.retries),
+                    last_error: failure.map(|f| f.last_error.clone()),
+                }
+            })
+            .collect();
+        pending.sort_by(|a, b| a.path.cmp(&b.path));
+        pending
+    }
+
+    fn invalidate_caches(&self) {
+        self.metadata_cache.clear();
+        self.dir_cache.clear();
+        self.negative_cache.clear();
+        self.inner.invalidate_caches();
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        Some(self)
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        // Check for pending delete first
+        if self.is_pending_delete(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // Check for pending create/modify - use local metadata
+        if let Some(meta) = self.get_pending_metadata(path) {
+            trace!("stat from pending change: {:?}", path);
+            return Ok(meta);
+        }
+
+        // Drop cached content if it changed on the backend
+        self.revalidate_content(path).await;
+
+        // Check cached metadata
+        if let Some(meta) = self.get_cached_metadata(path) {
+            trace!("stat metadata cache hit: {:?}", path);
+            return Ok(meta);
+        }
+
+        // Check if we have content cached (fetched from backend earlier)
+        if let Some(mut meta) = self.store.stat(path) {
+            if let Some(mode) = self.mode_cache.get(path) {
+                meta.mode = Some(*mode);
+            }
+            self.cache_metadata(path, meta.clone());
+            return Ok(meta);
+        }
+
+        // Local-only paths are never looked up on the backend
+        if self.is_excluded(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // OPTIMIZATION: If any ancestor is a pending new directory, path can't exist on backend
+        if self.has_pending_new_ancestor(path) {
+            trace!(
+                "stat: path {:?} has pending new ancestor, skipping backend",
+                path
+            );
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // OPTIMIZATION: Check negative cache (known not to exist on backend)
+        if self.is_negative_cached(path) {
+            trace!("stat negative cache hit: {:?}", path);
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // Fall through to backend
+        match self.inner.stat(path).await {
+            Ok(meta) => {
+                if let Some(mode) = meta.mode {
+                    self.mode_cache.insert(path.to_path_buf(), mode);
+                }
+                self.cache_metadata(path, meta.clone());
+                Ok(meta)
+            }
+            Err(FuseAdapterError::NotFound(_)) => {
+                // Add to negative cache so we don't check again
+                self.add_to_negative_cache(path);
+                Err(FuseAdapterError::NotFound(
+                    path.to_string_lossy().to_string(),
+                ))
+            }
+            Err(FuseAdapterError::Unavailable(reason)) => {
+                // The backend is down; expired metadata beats an error
+                match self.metadata_cache.get(path) {
+                    Some(entry) => {
+                        debug!("stat serving expired metadata: {:?}", path);
+                        Ok(entry.metadata.clone())
+                    }
+                    None => Err(FuseAdapterError::Unavailable(reason)),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        // Check pending delete
+        if self.is_pending_delete(path) {
+            return Ok(false);
+        }
+
+        // Check pending create
+        if self.is_pending_create(path) {
+            return Ok(true);
+        }
+
+        // Check content store
+        if self.store.contains(path) {
+            return Ok(true);
+        }
+
+        // Check metadata cache
+        if self.get_cached_metadata(path).is_some() {
+            return Ok(true);
+        }
+
+        // Local-only paths are never looked up on the backend
+        if self.is_excluded(path) {
+            return Ok(false);
+        }
+
+        // OPTIMIZATION: If any ancestor is a pending new directory, path can't exist on backend
+        if self.has_pending_new_ancestor(path) {
+            trace!(
+                "exists: path {:?} has pending new ancestor, skipping backend",
+                path
+            );
+            return Ok(false);
+        }
+
+        // OPTIMIZATION: Check negative cache (known not to exist on backend)
+        if self.is_negative_cached(path) {
+            trace!("exists negative cache hit: {:?}", path);
+            return Ok(false);
+        }
+
+        // Fall through to backend
+        match self.inner.exists(path).await {
+            Ok(true) => Ok(true),
+            Ok(false) => {
+                // Add to negative cache
+                self.add_to_negative_cache(path);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        // Check pending delete
+        if self.is_pending_delete(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // Drop cached content if it changed on the backend
+        self.revalidate_content(path).await;
+
+        // Try reading from cache first
+        if let Some(data) = self.read_from_cache(path, offset, size)? {
+            trace!("read cache hit: {:?} offset={} size={}", path, offset, size);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(data);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Fetch from backend if not in cache
+        if !self.store.contains(path) {
+            debug!("Fetching {:?} to {} cache", path, S::NAME);
+            self.fetch_to_cache(path).await?;
+        }
+
+        // Read from cache
+        self.read_from_cache(path, offset, size)?
+            .ok_or_else(|| FuseAdapterError::NotFound(path.to_string_lossy().to_string()))
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        // If file doesn't exist in cache and we're writing at non-zero offset, fetch first
+        if !self.store.contains(path)
+            && offset > 0
+            && !self.is_pending_create(path)
+            && !self.is_excluded(path)
+            && self.inner.stat(path).await.is_ok()
+        {
+            self.fetch_to_cache(path).await?;
+        }
+
+        // Write to local cache only
+        self.write_to_cache(path, offset, data)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        // Create locally only - will be synced later
+        self.create_in_cache(path, None)
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        // Create locally only - will be synced later
+        self.create_dir_in_cache(path, None)
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        // Mark as deleted locally - will be synced later
+        self.mark_deleted(path, false);
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        if !recursive {
+            let not_empty =
+                || FuseAdapterError::NotEmpty(format!("Directory not empty: {:?}", path));
+
+            // Check if directory has any pending non-deleted children
+            if !self.get_pending_entries_for_dir(path).is_empty() {
+                return Err(not_empty());
+            }
+
+            // Check cached entries (filtering out pending deletes)
+            let pending_deletes = self.get_pending_deletes_for_dir(path);
+            if let Some(cached) = self.dir_cache.get(path) {
+                let has_entries = cached.entries.iter().any(|e| {
+                    let entry_path = path.join(&e.name);
+                    !pending_deletes.contains(&entry_path)
+                });
+                if has_entries {
+                    return Err(not_empty());
+                }
+            } else if !self.is_pending_dir(path) && !self.is_excluded(path) {
+                // Need to check backend for entries
+                use futures::StreamExt;
+                let mut stream = self.inner.list_dir(path);
+                while let Some(entry) = stream.next().await {
+                    if let Ok(entry) = entry {
+                        let entry_path = path.join(&entry.name);
+                        if !pending_deletes.contains(&entry_path) {
+                            return Err(not_empty());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Mark as deleted locally - will be synced later
+        self.mark_deleted(path, true);
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        // Get pending entries for this directory
+        let pending_entries = self.get_pending_entries_for_dir(path);
+        let pending_deletes = self.get_pending_deletes_for_dir(path);
+
+        // A pending new or local-only directory has nothing on the backend
+        if self.is_pending_dir(path) || self.is_excluded(path) {
+            return Box::pin(futures::stream::iter(pending_entries.into_iter().map(Ok)));
+        }
+
+        // Check cache first
+        if let Some(cached) = self.dir_cache.get(path) {
+            if cached.cached_at.elapsed() < self.config.metadata_ttl {
+                trace!("list_dir cache hit: {:?}", path);
+
+                // Merge cached entries with pending changes
+                let mut entries: Vec<DirEntry> = cached
+                    .entries
+                    .iter()
+                    .filter(|e| {
+                        let entry_path = path.join(&e.name);
+                        !pending_deletes.contains(&entry_path)
+                    })
+                    .cloned()
+                    .collect();
+
+                // Add pending creates (avoiding duplicates)
+                let existing_names: HashSet<_> = entries.iter().map(|e| e.name.clone()).collect();
+                for entry in pending_entries {
+                    if !existing_names.contains(&entry.name) {
+                        entries.push(entry);
+                    }
+                }
+
+                return Box::pin(futures::stream::iter(entries.into_iter().map(Ok)));
+            }
+        }
+
+        // Fetch from backend and merge with pending changes
+        let inner = self.inner.clone();
+        let path_owned = path.to_path_buf();
+        let dir_cache = self.dir_cache.clone();
+        let metadata_cache = self.metadata_cache.clone();
+        let ttl = self.config.metadata_ttl;
+        let max_metadata = self.config.max_metadata_entries;
+        let max_listings = self.config.max_dir_listings;
+
+        Box::pin(async_stream::try_stream! {
+            debug!("list_dir fetching from backend: {:?}", path_owned);
+            let stream = inner.list_dir(&path_owned);
+
+            use futures::StreamExt;
+            let mut backend_entries: Vec<Result<DirEntry>> = stream.collect().await;
+
+            // The backend is down; an expired listing beats an error
+            let unavailable = backend_entries
+                .iter()
+                .any(|e| matches!(e, Err(FuseAdapterError::Unavailable(_))));
+            let stale = if unavailable {
+                dir_cache.get(&path_owned).map(|cached| cached.entries.clone())
+            } else {
+                None
+            };
+            let fresh = stale.is_none();
+            if let Some(entries) = stale {
+                debug!("list_dir serving expired listing: {:?}", path_owned);
+                backend_entries = entries.into_iter().map(Ok).collect();
+            }
+
+            let mut cached_entries = Vec::new();
+            let mut seen_names: HashSet<std::ffi::OsString> = HashSet::new();
+
+            // Yield backend entries (filtered by pending deletes)
+            for entry_result in backend_entries {
+                match entry_result {
+                    Ok(entry) => {
+                        let entry_path = path_owned.join(&entry.name);
+                        if !pending_deletes.contains(&entry_path) {
+                            // Warm the metadata cache so a stat after ls needn't hit the backend
+                            if let Some(metadata) = entry.metadata.as_ref().filter(|_| fresh) {
+                                metadata_cache.insert(entry_path, CachedMetadata {
+                                    metadata: metadata.clone(),
+                                    cached_at: Instant::now(),
+                                });
+                            }
+                            seen_names.insert(entry.name.clone());
+                            cached_entries.push(entry.clone());
+                            yield entry;
+                        }
+                    }
+                    Err(e) => {
+                        Err(e)?;
+                    }
+                }
+            }
+
+            // Yield pending creates (avoiding duplicates)
+            for entry in pending_entries {
+                if !seen_names.contains(&entry.name) {
+                    yield entry;
+                }
+            }
+
+            // Cache the backend listing
+            if fresh {
+                dir_cache.insert(path_owned, CachedDirListing {
+                    entries: cached_entries,
+                    cached_at: Instant::now(),
+                });
+            }
+            trim_cache(&dir_cache, max_listings, ttl, |l| l.cached_at);
+            trim_cache(&metadata_cache, max_metadata, ttl, |m| m.cached_at);
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        // The store only knows directories it has cached content under
+        let is_directory = self.store.stat(from).is_some_and(|m| m.is_dir())
+            || self.is_pending_dir(from)
+            || self.stat(from).await.is_ok_and(|m| m.is_dir());
+
+        // A directory the backend has is moved there right away, so the
+        // children the cache has never seen move with it. Everything else is
+        // renamed locally only
+        let on_backend = is_directory
+            && !self.is_pending_create(from)
+            && !self.has_pending_new_ancestor(from)
+            && !self.is_excluded(from)
+            && !self.is_excluded(to);
+        let _sync = if on_backend {
+            let guard = self.claim_sync().await;
+            self.rename_on_backend(from, to).await?;
+            Some(guard)
+        } else {
+            None
+        };
+
+        // Whether a file lands on one the backend still has, even if it was
+        // deleted locally
+        let replaces_backend_file = !is_directory
+            && !self.is_excluded(to)
+            && (self.is_pending_delete(to)
+                || (!self.is_pending_create(to) && self.exists(to).await.unwrap_or(false)));
+
+        // Move content if it exists
+        self.store.rename(from, to)?;
+
+        // If this is a directory, we need to update all child entries in the caches
+        if is_directory {
+            // Update pending_changes for children and move their content
+            for old_path in Self::children_of(&self.pending_changes, from) {
+                if let Some((_, change)) = self.pending_changes.remove(&old_path) {
+                    let new_path = to.join(old_path.strip_prefix(from).unwrap());
+                    if let Err(e) = self.store.rename(&old_path, &new_path) {
+                        warn!("Failed to move cached {:?}: {}", old_path, e);
+                    }
+                    self.pending_changes.insert(new_path, change);
+                }
+            }
+
+            // Update mode_cache for children
+            for old_path in Self::children_of(&self.mode_cache, from) {
+                if let Some((_, mode)) = self.mode_cache.remove(&old_path) {
+                    let new_path = to.join(old_path.strip_prefix(from).unwrap());
+                    self.mode_cache.insert(new_path, mode);
+                }
+            }
+
+            // Invalidate metadata_cache for children
+            for old_path in Self::children_of(&self.metadata_cache, from) {
+                self.metadata_cache.remove(&old_path);
+            }
+
+            // Invalidate dir_cache for the renamed directory itself
+            self.dir_cache.remove(from);
+
+            // Backend copies moved along, so what's known about them still holds
+            if on_backend {
+                for old_path in Self::children_of(&self.dirty, from) {
+                    if let Some((_, dirty)) = self.dirty.remove(&old_path) {
+                        self.dirty
+                            .insert(to.join(old_path.strip_prefix(from).unwrap()), dirty);
+                    }
+                }
+                for old_path in Self::children_of(&self.etags, from) {
+                    if let Some((_, etag)) = self.etags.remove(&old_path) {
+                        self.etags
+                            .insert(to.join(old_path.strip_prefix(from).unwrap()), etag);
+                    }
+                }
+            }
+        }
+
+        // The renamed content no longer lines up with either backend copy
+        self.forget_dirty(from);
+        self.forget_dirty(to);
+
+        // Update pending changes for the item itself. Only a create that
+        // never reached the backend can simply move; anything else still
+        // exists there under the old name
+        if let Some((_, change)) = self.pending_changes.remove_if(from, |_, c| c.is_create()) {
+            self.pending_changes.insert(to.to_path_buf(), change);
+        } else if on_backend {
+            // The backend already has it under the new name
+            self.pending_changes.remove(from);
+            self.pending_changes.remove(to);
+        } else {
+            // File/dir exists on backend - mark source as deleted, destination as new
+            let (deleted, created) = if is_directory {
+                (
+                    PendingChangeType::DeletedDirectory,
+                    PendingChangeType::NewDirectory,
+                )
+            } else {
+                (PendingChangeType::DeletedFile, PendingChangeType::NewFile)
+            };
+            if !self.is_excluded(from) {
+                self.pending_changes
+                    .insert(from.to_path_buf(), PendingChange::new(deleted, None));
+            }
+            self.pending_changes.insert(
+                to.to_path_buf(),
+                PendingChange::new(created, self.mode_cache.get(from).map(|r| *r)),
+            );
+        }
+
+        // The destination's backend copy is overwritten rather than created
+        if replaces_backend_file {
+            if let Some(mut change) = self.pending_changes.get_mut(to) {
+                change.change_type = PendingChangeType::ModifiedFile;
+            }
+        }
+
+        // Update mode cache for the item itself
+        if let Some((_, mode)) = self.mode_cache.remove(from) {
+            self.mode_cache.insert(to.to_path_buf(), mode);
+        }
+
+        // Invalidate metadata and directory caches
+        self.metadata_cache.remove(from);
+        self.metadata_cache.remove(to);
+        self.etags.remove(from);
+        self.etags.remove(to);
+        if let Some(parent) = from.parent() {
+            self.dir_cache.remove(parent);
+        }
+        if let Some(parent) = to.parent() {
+            self.dir_cache.remove(parent);
+        }
+
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        // Ensure file is in cache
+        if !self.store.contains(path) && !self.is_pending_create(path) {
+            self.fetch_to_cache(path).await?;
+        }
+
+        // Truncate locally
+        self.truncate_in_cache(path, size)
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        if !self.store.contains(path) && !self.is_pending_create(path) {
+            self.fetch_to_cache(path).await?;
+        }
+
+        self.zero_range_in_cache(path, offset, len)
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        // Only the stored content knows where holes were punched
+        let stored = self.store.stat(path).is_some_and(|m| m.is_file());
+        if self.is_pending_delete(path) || !stored {
+            let size = self.stat(path).await?.size;
+            return Ok(region.in_dense_file(size, offset));
+        }
+
+        self.store.seek_region(path, offset, region)
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        if self.config.strict_fsync {
+            return self.sync_path(path).await;
+        }
+
+        // In write-back mode, flush doesn't immediately sync to backend
+        // The background task handles that, uploading flushed files first
+        trace!("flush called for {:?} (write-back mode)", path);
+        if self.pending_changes.contains_key(path) {
+            self.queue.prioritize(path);
+        }
+        Ok(())
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.create_in_cache(path, Some(mode))
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.create_dir_in_cache(path, Some(mode))
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.mode_cache.insert(path.to_path_buf(), mode);
+        self.metadata_cache.remove(path);
+
+        // Mark as modified if it exists
+        if self.store.contains(path) || self.is_pending_create(path) {
+            self.pending_changes
+                .entry(path.to_path_buf())
+                .and_modify(|change| {
+                    change.mode = Some(mode);
+                    change.generation = next_generation();
+                });
+        }
+
+        Ok(())
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        // Check pending delete
+        if self.is_pending_delete(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // Check local symlinks first
+        if let Some(target) = self.read_symlink_from_cache(path) {
+            return Ok(target);
+        }
+        if self.is_excluded(path) {
+            return Err(FuseAdapterError::NotFound(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+
+        // Fall through to backend
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        // Create locally only - will be synced later
+        // Clear any existing cache at this path
+        self.mark_deleted(link_path, false);
+        self.pending_changes.remove(link_path); // Remove the delete we just added
+
+        self.create_symlink_in_cache(target, link_path)
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.sync_to_backend_wait().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        // Stop the background task, then run the final sync ourselves so the
+        // caller learns whether everything reached the backend
+        self.shutdown.notify_one();
+        self.sync_to_backend_wait().await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        // Versions only exist once a file has been synced to the backend
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        // Copy on the backend only when it has the source as we see it and
+        // can take the destination directly; otherwise copy through the cache
+        let replaceable = self.pending_changes.get(to).is_none_or(|c| {
+            matches!(
+                c.change_type,
+                PendingChangeType::NewFile | PendingChangeType::ModifiedFile
+            )
+        });
+        if self.pending_changes.contains_key(from)
+            || !replaceable
+            || self.has_pending_new_ancestor(to)
+            || self.is_excluded(from)
+            || self.is_excluded(to)
+        {
+            return copy_content(self, from, to).await;
+        }
+
+        // The copy replaces whatever was waiting to be uploaded
+        let replaced = self.pending_changes.remove(to);
+        if let Err(e) = self.inner.copy(from, to).await {
+            if let Some((path, change)) = replaced {
+                self.pending_changes.insert(path, change);
+            }
+            return Err(e);
+        }
+        self.forget_dirty(to);
+        self.sync_failures.remove(to);
+        self.mode_cache.remove(to);
+        self.invalidate_content(to);
+        if let Some(parent) = to.parent() {
+            self.dir_cache.remove(parent);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::filesystem::{FilesystemCache, FilesystemCacheConfig};
+    use crate::cache::memory::{MemoryCache, MemoryCacheConfig};
+    use crate::config::StatusOverlayConfig;
+    use crate::connector::memfs::MemfsConnector;
+    use crate::overlay::StatusOverlay;
+    use futures::{stream, StreamExt};
+    use std::time::UNIX_EPOCH;
+
+    /// Backend holding one file whose content can be replaced externally
+    struct ChangingConnector {
+        content: RwLock<(String, Bytes)>,
+        revalidations: std::sync::atomic::AtomicUsize,
+        stats: std::sync::atomic::AtomicUsize,
+        /// Accept uploads (otherwise writes fail)
+        writable: bool,
+        /// Dirty ranges of each partial upload
+        patches: parking_lot::Mutex<Vec<DirtyRanges>>,
+        /// Server-side copies made
+        copies: std::sync::atomic::AtomicUsize,
+        /// Paths uploaded, in order
+        uploads: parking_lot::Mutex<Vec<PathBuf>>,
+        /// Fail lookups as if the circuit breaker were open
+        down: std::sync::atomic::AtomicBool,
+    }
+
+    impl ChangingConnector {
+        fn new(etag: &str, data: &'static [u8]) -> Self {
+            Self {
+                content: RwLock::new((etag.to_string(), Bytes::from_static(data))),
+                revalidations: std::sync::atomic::AtomicUsize::new(0),
+                stats: std::sync::atomic::AtomicUsize::new(0),
+                writable: false,
+                patches: parking_lot::Mutex::new(Vec::new()),
+                copies: std::sync::atomic::AtomicUsize::new(0),
+                uploads: parking_lot::Mutex::new(Vec::new()),
+                down: std::sync::atomic::AtomicBool::new(false),
+            }
+        }
+
+        fn is_down(&self) -> bool {
+            self.down.load(std::sync::atomic::Ordering::Relaxed)
+        }
+
+        fn replace(&self, etag: &str, data: &'static [u8]) {
+            *self.content.write() = (etag.to_string(), Bytes::from_static(data));
+        }
+    }
+
+    #[async_trait]
+    impl Connector for ChangingConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::read_only()
+        }
+
+        async fn stat(&self, _path: &Path) -> Result<Metadata> {
+            self.stats
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            if self.is_down() {
+                return Err(FuseAdapterError::Unavailable("down".to_string()));
+            }
+            let (etag, data) = self.content.read().clone();
+            Ok(Metadata::file(data.len() as u64, UNIX_EPOCH).with_etag(Some(etag)))
+        }
+
+        async fn read(&self, _path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let data = self.content.read().1.clone();
+            let start = (offset as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            Ok(data.slice(start..end))
+        }
+
+        async fn write(&self, path: &Path, _offset: u64, data: &[u8]) -> Result<u64> {
+            if !self.writable {
+                return Err(FuseAdapterError::ReadOnly);
+            }
+            self.uploads.lock().push(path.to_path_buf());
+            *self.content.write() = ("uploaded".to_string(), Bytes::copy_from_slice(data));
+            Ok(data.len() as u64)
+        }
+
+        async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+            if !self.writable {
+                return Ok(false);
+            }
+            self.uploads.lock().push(path.to_path_buf());
+            self.patches.lock().push(dirty.clone());
+            *self.content.write() = ("patched".to_string(), Bytes::copy_from_slice(data));
+            Ok(true)
+        }
+
+        async fn create_file(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn remove_file(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        fn list_dir(&self, _path: &Path) -> DirEntryStream {
+            if self.is_down() {
+                let down = FuseAdapterError::Unavailable("down".to_string());
+                return Box::pin(stream::iter(vec![Err(down)]));
+            }
+            let (etag, data) = self.content.read().clone();
+            let entry = DirEntry::file("file.txt")
+                .with_metadata(Metadata::file(data.len() as u64, UNIX_EPOCH).with_etag(Some(etag)));
+            Box::pin(stream::iter(vec![Ok(entry)]))
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn revalidate(&self, _path: &Path, etag: &str) -> Result<bool> {
+            self.revalidations
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(self.content.read().0 == etag)
+        }
+
+        async fn copy(&self, _from: &Path, _to: &Path) -> Result<()> {
+            if !self.writable {
+                return Err(FuseAdapterError::ReadOnly);
+            }
+            self.copies
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    fn cache(revalidation: RevalidationMode) -> MemoryCache<ChangingConnector> {
+        MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig {
+                metadata_ttl: Duration::ZERO,
+                revalidation,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_strict_fsync_uploads_on_flush() {
+        let mut backend = ChangingConnector::new("v1", b"old");
+        backend.writable = true;
+        let cache = MemoryCache::new(
+            backend,
+            MemoryCacheConfig {
+                strict_fsync: true,
+                ..Default::default()
+            },
+        );
+        let path = Path::new("/file.txt");
+
+        cache.write(path, 0, b"new").await.unwrap();
+        assert_eq!(cache.pending_sync().len(), 1);
+
+        cache.flush(path).await.unwrap();
+        assert!(cache.pending_sync().is_empty());
+        assert_eq!(cache.inner.content.read().1, "new");
+    }
+
+    #[tokio::test]
+    async fn test_started_cache_syncs_on_flush_interval() {
+        let mut backend = ChangingConnector::new("v1", b"old");
+        backend.writable = true;
+        let cache = MemoryCache::new(
+            backend,
+            MemoryCacheConfig {
+                flush_interval: Duration::from_millis(20),
+                ..Default::default()
+            },
+        )
+        .start();
+        let path = Path::new("/file.txt");
+
+        cache.write(path, 0, b"new").await.unwrap();
+        for _ in 0..50 {
+            if cache.pending_sync().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(cache.pending_sync().is_empty());
+        assert_eq!(cache.inner.content.read().1, "new");
+    }
+
+    #[tokio::test]
+    async fn test_paused_cache_holds_back_background_sync() {
+        let mut backend = ChangingConnector::new("v1", b"old");
+        backend.writable = true;
+        let cache = MemoryCache::new(
+            backend,
+            MemoryCacheConfig {
+                flush_interval: Duration::from_millis(20),
+                ..Default::default()
+            },
+        )
+        .start();
+        let path = Path::new("/file.txt");
+
+        cache.set_sync_paused(true);
+        assert_eq!(
+            cache.cache_stats().unwrap().sync_paused.as_deref(),
+            Some("manual")
+        );
+        cache.write(path, 0, b"new").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(cache.pending_sync().len(), 1);
+        assert_eq!(cache.inner.content.read().1, "old");
+
+        cache.set_sync_paused(false);
+        assert!(cache.cache_stats().unwrap().sync_paused.is_none());
+        for _ in 0..50 {
+            if cache.pending_sync().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(cache.pending_sync().is_empty());
+        assert_eq!(cache.inner.content.read().1, "new");
+    }
+
+    #[tokio::test]
+    async fn test_sync_uploads_flushed_then_small_files_first() {
+        let mut backend = ChangingConnector::new("v1", b"old");
+        backend.writable = true;
+        let cache = MemoryCache::new(
+            backend,
+            MemoryCacheConfig {
+                large_file_threshold: 4,
+                // One upload at a time, so the order is observable
+                max_inflight: 1,
+                ..Default::default()
+            },
+        );
+
+        cache
+            .write(Path::new("/huge"), 0, b"0123456789")
+            .await
+            .unwrap();
+        for path in ["/small", "/later", "/flushed"] {
+            cache.write(Path::new(path), 0, b"new").await.unwrap();
+        }
+        cache.flush(Path::new("/flushed")).await.unwrap();
+
+        cache.sync_to_backend_wait().await.unwrap();
+        assert_eq!(
+            *cache.inner.uploads.lock(),
+            ["/flushed", "/small", "/later", "/huge"].map(PathBuf::from)
+        );
+        assert!(cache.pending_sync().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metadata_cache_is_bounded() {
+        let cache = MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig {
+                max_metadata_entries: 10,
+                ..Default::default()
+            },
+        );
+        for i in 0..30 {
+            let path = PathBuf::from(format!("/file{}", i));
+            cache.stat(&path).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert!(cache.cache_stats().unwrap().metadata_entries <= 10);
+
+        // The most recently cached entries are the ones kept
+        let stats = cache.inner.stats.load(std::sync::atomic::Ordering::Relaxed);
+        cache.stat(Path::new("/file29")).await.unwrap();
+        assert_eq!(
+            cache.inner.stats.load(std::sync::atomic::Ordering::Relaxed),
+            stats
+        );
+        cache.stat(Path::new("/file0")).await.unwrap();
+        assert_eq!(
+            cache.inner.stats.load(std::sync::atomic::Ordering::Relaxed),
+            stats + 1
+        );
+    }
+
+    #[test]
+    fn test_trim_cache_drops_expired_then_oldest() {
+        let map: DashMap<PathBuf, Instant> = DashMap::new();
+        let now = Instant::now();
+        map.insert(PathBuf::from("/expired"), now - Duration::from_secs(120));
+        for i in 0..10u64 {
+            map.insert(
+                PathBuf::from(format!("/{}", i)),
+                now - Duration::from_secs(10 - i),
+            );
+        }
+
+        trim_cache(&map, 20, Duration::from_secs(60), |t| *t);
+        assert_eq!(map.len(), 11);
+
+        trim_cache(&map, 10, Duration::from_secs(60), |t| *t);
+        assert_eq!(map.len(), 9);
+        assert!(!map.contains_key(Path::new("/expired")));
+        assert!(!map.contains_key(Path::new("/0")));
+        assert!(map.contains_key(Path::new("/9")));
+    }
+
+    #[tokio::test]
+    async fn test_sync_uploads_only_dirty_ranges() {
+        let mut backend = ChangingConnector::new("v1", b"0123456789");
+        backend.writable = true;
+        let cache = MemoryCache::new(backend, MemoryCacheConfig::default());
+        let path = Path::new("/file.txt");
+
+        // A cached copy of the backend file is patched in place
+        assert_eq!(&cache.read(path, 0, 4096).await.unwrap()[..], b"0123456789");
+        cache.write(path, 2, b"ab").await.unwrap();
+        cache.write(path, 4, b"c").await.unwrap();
+        cache.truncate(path, 12).await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+
+        let patches = cache.inner.patches.lock().clone();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].base_size, 10);
+        assert_eq!(patches[0].ranges, vec![2..5, 10..12]);
+        assert_eq!(&cache.inner.content.read().1[..], b"01abc56789\0\0");
+
+        // A file replaced without being read first has no known base
+        cache.remove_file(path).await.unwrap();
+        cache.create_file(path).await.unwrap();
+        cache.write(path, 0, b"new").await.unwrap();
+        assert!(cache.dirty.get(path).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_copy_on_backend_only_for_synced_sources() {
+        let mut backend = ChangingConnector::new("v1", b"old");
+        backend.writable = true;
+        let cache = MemoryCache::new(backend, MemoryCacheConfig::default());
+        let (from, to) = (Path::new("/file.txt"), Path::new("/copy.txt"));
+        let copies = || {
+            cache
+                .inner
+                .copies
+                .load(std::sync::atomic::Ordering::Relaxed)
+        };
+
+        // The backend copy replaces the empty file cp creates first
+        cache.create_file(to).await.unwrap();
+        cache.copy(from, to).await.unwrap();
+        assert_eq!(copies(), 1);
+        assert!(cache.pending_sync().is_empty());
+
+        // Unsynced changes to the source are copied through the cache
+        cache.write(from, 0, b"new").await.unwrap();
+        cache.copy(from, to).await.unwrap();
+        assert_eq!(copies(), 1);
+        assert_eq!(&cache.read(to, 0, 4096).await.unwrap()[..], b"new");
+    }
+
+    #[tokio::test]
+    async fn test_strict_fsync_reports_upload_failure() {
+        let cache = MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig {
+                strict_fsync: true,
+                ..Default::default()
+            },
+        );
+        let path = Path::new("/file.txt");
+
+        cache.write(path, 0, b"new").await.unwrap();
+        assert!(cache.flush(path).await.is_err());
+        assert_eq!(cache.pending_sync()[0].retries, 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_running_sync() {
+        let mut backend = ChangingConnector::new("v1", b"old");
+        backend.writable = true;
+        let cache = MemoryCache::new(backend, MemoryCacheConfig::default());
+        let path = Path::new("/file.txt");
+
+        cache.write(path, 0, b"new").await.unwrap();
+
+        // A background sync that is already running must not make the final
+        // sync a no-op
+        *cache.sync_running.write() = true;
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            *cache.sync_running.write() = false;
+        };
+        let (result, _) = tokio::join!(cache.shutdown(), release);
+
+        result.unwrap();
+        assert!(cache.pending_sync().is_empty());
+        assert_eq!(cache.inner.content.read().1, "new");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_background_tasks() {
+        let cache = MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig::default(),
+        )
+        .start();
+        // Let both tasks start waiting
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        cache.shutdown().await.unwrap();
+        for _ in 0..50 {
+            if Arc::strong_count(&cache) == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(Arc::strong_count(&cache), 1);
+    }
+
+    #[tokio::test]
+    async fn test_janitor_sweeps_expired_entries() {
+        let cache = MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig {
+                metadata_ttl: Duration::from_millis(50),
+                negative_ttl: Duration::from_millis(50),
+                ..Default::default()
+            },
+        );
+        cache.stat(Path::new("/file")).await.unwrap();
+        cache.add_to_negative_cache(Path::new("/missing"));
+        cache.sweep_expired();
+        assert_eq!(cache.cache_stats().unwrap().metadata_entries, 1);
+        assert_eq!(cache.cache_stats().unwrap().negative_entries, 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        cache.sweep_expired();
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!((stats.metadata_entries, stats.negative_entries), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_listing_metadata_warms_stat() {
+        let cache = MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig::default(),
+        );
+
+        let entries: Vec<_> = cache.list_dir(Path::new("/")).collect().await;
+        assert_eq!(entries.len(), 1);
+
+        let meta = cache.stat(Path::new("/file.txt")).await.unwrap();
+        assert_eq!(meta.size, 3);
+        assert_eq!(meta.etag.as_deref(), Some("v1"));
+        assert_eq!(
+            cache.inner.stats.load(std::sync::atomic::Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_backend_serves_expired_metadata() {
+        let cache = cache(RevalidationMode::None);
+
+        let entries: Vec<_> = cache.list_dir(Path::new("/")).collect().await;
+        assert_eq!(entries.len(), 1);
+        cache
+            .inner
+            .down
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+
+        // Both entries have expired (zero TTL) but are still served
+        let entries: Vec<_> = cache.list_dir(Path::new("/")).collect().await;
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_ok());
+        let meta = cache.stat(Path::new("/file.txt")).await.unwrap();
+        assert_eq!(meta.etag.as_deref(), Some("v1"));
+
+        // Nothing cached to fall back on
+        assert!(matches!(
+            cache.stat(Path::new("/other.txt")).await,
+            Err(FuseAdapterError::Unavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_etag_revalidation_invalidates_changed_content() {
+        let cache = cache(RevalidationMode::Etag);
+        let path = Path::new("/file.txt");
+
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+        cache.inner.replace("v2", b"new content");
+
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "new content");
+        assert_eq!(cache.stat(path).await.unwrap().size, 11);
+    }
+
+    #[tokio::test]
+    async fn test_reused_cache_content_is_revalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = |backend, revalidation| {
+            FilesystemCache::new(
+                backend,
+                FilesystemCacheConfig {
+                    cache_dir: dir.path().to_path_buf(),
+                    metadata_ttl: Duration::ZERO,
+                    revalidation,
+                    ..Default::default()
+                },
+            )
+        };
+        let path = Path::new("/file.txt");
+
+        let cache = open(ChangingConnector::new("v1", b"old"), RevalidationMode::None);
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+        drop(cache);
+
+        // Kept content is served after a restart...
+        let cache = open(ChangingConnector::new("v1", b"xyz"), RevalidationMode::Etag);
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+        drop(cache);
+
+        // ...until the backend has a new version
+        let cache = open(ChangingConnector::new("v2", b"new"), RevalidationMode::Etag);
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "new");
+        assert_eq!(
+            cache
+                .inner
+                .revalidations
+                .load(std::sync::atomic::Ordering::Relaxed),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats_counts_hits_and_misses() {
+        let cache = cache(RevalidationMode::None);
+        let path = Path::new("/file.txt");
+
+        cache.read(path, 0, 100).await.unwrap();
+        cache.read(path, 0, 100).await.unwrap();
+        cache.read(path, 1, 100).await.unwrap();
+
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.cache_size, 3);
+        assert_eq!(stats.pending_changes, 0);
+        assert!(stats.last_sync.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pending_sync_reports_failures() {
+        let cache = cache(RevalidationMode::None);
+        let path = Path::new("/file.txt");
+
+        cache.write(path, 0, b"local").await.unwrap();
+        cache.sync_to_backend().await.unwrap();
+        cache.sync_to_backend().await.unwrap();
+
+        let pending = cache.pending_sync();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].path, path);
+        assert_eq!(pending[0].kind, PendingKind::Modified);
+        assert_eq!(pending[0].retries, 2);
+        assert!(pending[0].last_error.is_some());
+        assert!(pending[0].format().starts_with("modified\t2\t/file.txt\t"));
+    }
+
+    #[tokio::test]
+    async fn test_without_revalidation_serves_cached_content() {
+        let cache = cache(RevalidationMode::None);
+        let path = Path::new("/file.txt");
+
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+        cache.inner.replace("v2", b"new content");
+
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+        assert_eq!(
+            cache
+                .inner
+                .revalidations
+                .load(std::sync::atomic::Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cold_reads_come_from_peers() {
+        use crate::cache::peer::{CachePeersConfig, PeerSharing};
+
+        let config = |peers| CachePeersConfig {
+            listen: None,
+            peers,
+            timeout: Duration::from_secs(2),
+            token: None,
+        };
+        let path = Path::new("/file.txt");
+
+        // One adapter caches the file and serves its cache
+        let serving = Arc::new(PeerSharing::from_config(&config(Vec::new())));
+        let warm = Arc::new(cache(RevalidationMode::None).with_peers(serving.link("/mnt")));
+        serving.link("/mnt").register(Arc::downgrade(&warm) as _);
+        assert_eq!(warm.read(path, 0, 100).await.unwrap(), "old");
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serving.serve(listener));
+
+        // Another reads the same version from it instead of its backend
+        let asking = Arc::new(PeerSharing::from_config(&config(vec![url])));
+        let cold = MemoryCache::new(
+            ChangingConnector::new("v1", b"xyz"),
+            MemoryCacheConfig::default(),
+        )
+        .with_peers(asking.link("/mnt"));
+        assert_eq!(cold.read(path, 0, 100).await.unwrap(), "old");
+
+        // Local changes stop the content being shared
+        warm.write(path, 0, b"new").await.unwrap();
+        let other = MemoryCache::new(
+            ChangingConnector::new("v1", b"xyz"),
+            MemoryCacheConfig::default(),
+        )
+        .with_peers(asking.link("/mnt"));
+        assert_eq!(other.read(path, 0, 100).await.unwrap(), "xyz");
+    }
+
+    #[tokio::test]
+    async fn test_cache_control_drops_cached_content() {
+        let cache = cache(RevalidationMode::None);
+        let path = Path::new("/file.txt");
+        let control = cache.cache_control().unwrap();
+
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+        cache.inner.replace("v2", b"new content");
+        control.invalidate(Path::new("/other"));
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "old");
+
+        control.invalidate(path);
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "new content");
+
+        cache.inner.replace("v3", b"newer");
+        control.invalidate_all();
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "newer");
+
+        // Unsynced local changes survive
+        cache.write(path, 0, b"local").await.unwrap();
+        control.invalidate_all();
+        assert_eq!(cache.read(path, 0, 100).await.unwrap(), "local");
+    }
+
+    #[tokio::test]
+    async fn test_local_only_paths_stay_pending_and_skip_backend() {
+        let cache = MemoryCache::new(
+            ChangingConnector::new("v1", b"old"),
+            MemoryCacheConfig {
+                exclude_patterns: vec!["scratch".to_string()],
+                ..Default::default()
+            },
+        );
+        let dir = Path::new("/scratch");
+        let file = Path::new("/scratch/notes.txt");
+
+        cache.create_dir(dir).await.unwrap();
+        cache.write(file, 0, b"local").await.unwrap();
+        cache.sync_to_backend().await.unwrap();
+
+        // Nothing was uploaded, and the files are still there after a sync
+        let stats = cache.cache_stats().unwrap();
+        assert_eq!(stats.pending_changes, 0);
+        assert_eq!(stats.local_only, 2);
+        assert!(stats.last_sync_error.is_none());
+        assert!(cache.pending_sync().is_empty());
+        assert_eq!(cache.read(file, 0, 100).await.unwrap(), "local");
+        let names: Vec<_> = cache.list_dir(dir).map(|e| e.unwrap().name).collect().await;
+        assert_eq!(names, vec!["notes.txt"]);
+
+        // Missing local-only paths are never looked up on the backend
+        let missing = Path::new("/scratch/missing.txt");
+        assert!(cache.stat(missing).await.is_err());
+        assert!(!cache.exists(missing).await.unwrap());
+        assert_eq!(
+            cache.inner.stats.load(std::sync::atomic::Ordering::Relaxed),
+            0
+        );
+    }
+
+    /// Memfs without native rename, so trees are moved file by file
+    struct NoRename(Arc<MemfsConnector>);
+
+    #[async_trait]
+    impl Connector for NoRename {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                rename: false,
+                ..Capabilities::full()
+            }
+        }
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            self.0.stat(path).await
+        }
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            self.0.read(path, offset, size).await
+        }
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            self.0.write(path, offset, data).await
+        }
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.0.create_file(path).await
+        }
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.0.create_dir(path).await
+        }
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.0.remove_file(path).await
+        }
+        async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+            self.0.remove_dir(path, recursive).await
+        }
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            self.0.list_dir(path)
+        }
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Err(FuseAdapterError::NotSupported("rename".to_string()))
+        }
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            self.0.truncate(path, size).await
+        }
+        async fn flush(&self, path: &Path) -> Result<()> {
+            self.0.flush(path).await
+        }
+    }
+
+    /// Memfs whose uploads take a while, so writes can land mid-sync
+    struct SlowWrite(Arc<MemfsConnector>);
+
+    #[async_trait]
+    impl Connector for SlowWrite {
+        fn capabilities(&self) -> Capabilities {
+            self.0.capabilities()
+        }
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            self.0.stat(path).await
+        }
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            self.0.read(path, offset, size).await
+        }
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            self.0.write(path, offset, data).await
+        }
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.0.create_file(path).await
+        }
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.0.create_dir(path).await
+        }
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.0.remove_file(path).await
+        }
+        async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+            self.0.remove_dir(path, recursive).await
+        }
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            self.0.list_dir(path)
+        }
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.0.rename(from, to).await
+        }
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            self.0.truncate(path, size).await
+        }
+        async fn flush(&self, path: &Path) -> Result<()> {
+            self.0.flush(path).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_during_upload_stays_pending() {
+        let backend = Arc::new(MemfsConnector::new(None));
+        let cache = MemoryCache::new(SlowWrite(backend.clone()), MemoryCacheConfig::default());
+        let path = Path::new("/a");
+        cache.create_file(path).await.unwrap();
+        cache.write(path, 0, b"old").await.unwrap();
+
+        // The upload has already read "old" when "new" is written
+        let write = async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            cache.write(path, 0, b"new").await.unwrap();
+        };
+        let (synced, ()) = tokio::join!(cache.sync_to_backend_wait(), write);
+        synced.unwrap();
+        assert_eq!(&backend.read(path, 0, 16).await.unwrap()[..], b"old");
+        assert_eq!(cache.pending_sync().len(), 1);
+
+        // Same for an upload made on fsync
+        let write = async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            cache.write(path, 0, b"two").await.unwrap();
+        };
+        let (synced, ()) = tokio::join!(cache.sync_path(path), write);
+        synced.unwrap();
+        assert_eq!(&backend.read(path, 0, 16).await.unwrap()[..], b"new");
+        assert_eq!(cache.pending_sync().len(), 1);
+
+        cache.sync_to_backend_wait().await.unwrap();
+        assert_eq!(&backend.read(path, 0, 16).await.unwrap()[..], b"two");
+        assert!(cache.pending_sync().is_empty());
+    }
+
+    /// Memfs whose files are replaced right after each read, with etags
+    /// and checksums derived from the content like S3's
+    struct OverwrittenAfterRead(Arc<MemfsConnector>);
+
+    impl OverwrittenAfterRead {
+        async fn current_hash(&self, path: &Path) -> Result<String> {
+            let meta = self.0.stat(path).await?;
+            let data = self.0.read(path, 0, meta.size as u32).await?;
+            Ok(crate::integrity::sha256_hex(&data))
+        }
+    }
+
+    #[async_trait]
+    impl Connector for OverwrittenAfterRead {
+        fn capabilities(&self) -> Capabilities {
+            self.0.capabilities()
+        }
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            let etag = self.current_hash(path).await?;
+            Ok(self.0.stat(path).await?.with_etag(Some(etag)))
+        }
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let data = self.0.read(path, offset, size).await?;
+            self.0.write(path, 0, b"replaced").await?;
+            Ok(data)
+        }
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            self.0.write(path, offset, data).await
+        }
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.0.create_file(path).await
+        }
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.0.create_dir(path).await
+        }
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.0.remove_file(path).await
+        }
+        async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+            self.0.remove_dir(path, recursive).await
+        }
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            self.0.list_dir(path)
+        }
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.0.rename(from, to).await
+        }
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            self.0.truncate(path, size).await
+        }
+        async fn flush(&self, path: &Path) -> Result<()> {
+            self.0.flush(path).await
+        }
+        async fn content_hash(
+            &self,
+            path: &Path,
+            etag: Option<&str>,
+        ) -> Result<Option<crate::integrity::ContentHash>> {
+            let hash = self.current_hash(path).await?;
+            if etag.is_some_and(|etag| etag != hash) {
+                return Ok(None);
+            }
+            Ok(Some(crate::integrity::ContentHash::Sha256(hash)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_integrity_ignores_overwrite_after_read() {
+        let backend = Arc::new(MemfsConnector::new(None));
+        let path = Path::new("/a");
+        backend.create_file(path).await.unwrap();
+        backend.write(path, 0, b"original").await.unwrap();
+
+        let monitor = Arc::new(crate::integrity::IntegrityMonitor::new(
+            crate::config::IntegrityConfig::default(),
+        ));
+        let cache = MemoryCache::new(
+            OverwrittenAfterRead(backend.clone()),
+            MemoryCacheConfig::default(),
+        )
+        .with_integrity(monitor.clone());
+
+        // The checksum is asked for the version that was read, which is gone
+        assert_eq!(&cache.read(path, 0, 16).await.unwrap()[..], b"original");
+        assert_eq!(monitor.mismatch_count(), 0);
+        assert!(monitor.report().contains("unverified: 1"));
+    }
+
+    async fn check_directory_rename<C: Connector + 'static>(inner: C, backend: &MemfsConnector) {
+        for dir in ["/dir", "/dir/sub", "/dest"] {
+            backend.create_dir(Path::new(dir)).await.unwrap();
+        }
+        for (path, data) in [("/dir/a", b"a"), ("/dir/sub/b", b"b"), ("/dir/sub/c", b"c")] {
+            backend.create_file(Path::new(path)).await.unwrap();
+            backend.write(Path::new(path), 0, data).await.unwrap();
+        }
+        let cache = MemoryCache::new(inner, MemoryCacheConfig::default());
+
+        // Only /dir/a has ever been read through the cache
+        assert_eq!(
+            &cache.read(Path::new("/dir/a"), 0, 16).await.unwrap()[..],
+            b"a"
+        );
+        cache
+            .rename(Path::new("/dir"), Path::new("/dest/moved"))
+            .await
+            .unwrap();
+
+        // The backend has the whole tree under the new name right away
+        for (path, data) in [("/a", b"a"), ("/sub/b", b"b"), ("/sub/c", b"c")] {
+            let moved = format!("/dest/moved{}", path);
+            assert_eq!(
+                &backend.read(Path::new(&moved), 0, 16).await.unwrap()[..],
+                data
+            );
+            assert_eq!(
+                &cache.read(Path::new(&moved), 0, 16).await.unwrap()[..],
+                data
+            );
+        }
+        assert!(!backend.exists(Path::new("/dir")).await.unwrap());
+        assert!(!cache.exists(Path::new("/dir")).await.unwrap());
+        assert!(cache.pending_sync().is_empty());
+        assert!(cache.cache_stats().unwrap().renames.is_empty());
+
+        cache.sync_to_backend_wait().await.unwrap();
+        assert!(backend
+            .exists(Path::new("/dest/moved/sub/c"))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_directory_rename_moves_backend_children() {
+        let backend = Arc::new(MemfsConnector::new(None));
+        check_directory_rename(backend.clone(), &backend).await;
+    }
+
+    #[tokio::test]
+    async fn test_directory_rename_copies_without_backend_rename() {
+        let backend = Arc::new(MemfsConnector::new(None));
+        check_directory_rename(NoRename(backend.clone()), &backend).await;
+    }
+
+    #[tokio::test]
+    async fn test_scrub_repairs_stale_cache_entries() {
+        let backend = Arc::new(MemfsConnector::new(None));
+        for path in ["/a", "/gone", "/deleted"] {
+            backend.create_file(Path::new(path)).await.unwrap();
+            backend.write(Path::new(path), 0, b"old").await.unwrap();
+        }
+        let cache = MemoryCache::new(backend.clone(), MemoryCacheConfig::default());
+        assert_eq!(
+            &cache.read(Path::new("/a"), 0, 16).await.unwrap()[..],
+            b"old"
+        );
+        cache.stat(Path::new("/gone")).await.unwrap();
+        assert!(cache.stat(Path::new("/missing")).await.is_err());
+        cache.remove_file(Path::new("/deleted")).await.unwrap();
+        cache.create_file(Path::new("/new")).await.unwrap();
+
+        // Change the backend behind the cache's back
+        backend.write(Path::new("/a"), 0, b"newer").await.unwrap();
+        backend.remove_file(Path::new("/gone")).await.unwrap();
+        backend.remove_file(Path::new("/deleted")).await.unwrap();
+        for path in ["/missing", "/new"] {
+            backend.create_file(Path::new(path)).await.unwrap();
+        }
+
+        let report = cache.scrub().await.unwrap();
+        assert_eq!(report.conflicts, vec![PathBuf::from("/new")]);
+        assert_eq!(report.divergent, report.repaired + 1);
+        assert!(report.repaired >= 4);
+        assert_eq!(cache.cache_stats().unwrap().scrub, Some(report));
+
+        assert_eq!(
+            &cache.read(Path::new("/a"), 0, 16).await.unwrap()[..],
+            b"newer"
+        );
+        assert!(cache.stat(Path::new("/gone")).await.is_err());
+        assert!(cache.stat(Path::new("/missing")).await.is_ok());
+        let pending: Vec<PathBuf> = cache.pending_sync().into_iter().map(|p| p.path).collect();
+        assert_eq!(pending, vec![PathBuf::from("/new")]);
+
+        // A second pass finds only the unresolved conflict
+        let report = cache.scrub().await.unwrap();
+        assert_eq!((report.divergent, report.repaired), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_dirty_ranges_track_clean_cached_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = FilesystemCache::new(
+            StatusOverlay::new_failed("unused".to_string(), StatusOverlayConfig::default()),
+            FilesystemCacheConfig {
+                cache_dir: dir.path().to_path_buf(),
+                ..Default::default()
+            },
+        );
+        let cached = Path::new("/cached.bin");
+        cache
+            .store()
+            .fill(cached, Bytes::from(vec![0u8; 100]))
+            .unwrap();
+
+        cache.write(cached, 10, b"abc").await.unwrap();
+        cache.truncate(cached, 50).await.unwrap();
+        cache.write(cached, 12, b"de").await.unwrap();
+        let dirty = cache.dirty.get(cached).unwrap().clone();
+        assert_eq!(dirty.base_size, 100);
+        assert_eq!(dirty.ranges, vec![10..14, 50..100]);
+
+        // Renaming loses the link to the backend copy
+        cache.rename(cached, Path::new("/moved.bin")).await.unwrap();
+        assert!(cache.dirty.is_empty());
+
+        // A file that wasn't cached before the write has no known base
+        cache.write(Path::new("/new.bin"), 0, b"x").await.unwrap();
+        assert!(cache.dirty.is_empty());
+    }
+
+    /// Memory cache over a memfs backend the test can inspect
+    fn memfs_cache() -> (Arc<MemfsConnector>, MemoryCache<Arc<MemfsConnector>>) {
+        let backend = Arc::new(MemfsConnector::new(None));
+        let cache = MemoryCache::new(backend.clone(), MemoryCacheConfig::default());
+        (backend, cache)
+    }
+
+    async fn names<C: Connector>(fs: &C, dir: &str) -> Vec<std::ffi::OsString> {
+        let mut names: Vec<_> = fs
+            .list_dir(Path::new(dir))
+            .map(|e| e.unwrap().name)
+            .collect()
+            .await;
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_synced_changes_drop_cached_backend_state() {
+        let (_, cache) = memfs_cache();
+        let (file, dir) = (Path::new("/a"), Path::new("/dir"));
+
+        // The backend listing cached while the create was pending doesn't
+        // have the file
+        cache.create_file(file).await.unwrap();
+        assert_eq!(names(&cache, "/").await, ["a"]);
+        cache.sync_to_backend_wait().await.unwrap();
+        assert_eq!(names(&cache, "/").await, ["a"]);
+
+        cache.create_dir(dir).await.unwrap();
+        assert!(cache.stat(dir).await.unwrap().is_dir());
+        cache.sync_to_backend_wait().await.unwrap();
+        cache.remove_dir(dir, false).await.unwrap();
+        assert!(cache.stat(dir).await.is_err());
+        cache.sync_to_backend_wait().await.unwrap();
+        assert!(cache.stat(dir).await.is_err());
+        assert_eq!(names(&cache, "/").await, ["a"]);
+    }
+
+    #[tokio::test]
+    async fn test_rename_modified_file_removes_backend_copy() {
+        let (backend, cache) = memfs_cache();
+        let (from, to) = (Path::new("/a"), Path::new("/b"));
+        cache.create_file(from).await.unwrap();
+        cache.write(from, 0, b"old").await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+
+        // Only an unsynced create can move without touching the backend
+        cache.write(from, 0, b"new").await.unwrap();
+        cache.rename(from, to).await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+        assert!(backend.stat(from).await.is_err());
+        assert_eq!(&backend.read(to, 0, 16).await.unwrap()[..], b"new");
+    }
+
+    #[tokio::test]
+    async fn test_recreate_before_delete_syncs_replaces_backend_copy() {
+        let (backend, cache) = memfs_cache();
+        let (file, dir) = (Path::new("/a"), Path::new("/dir"));
+        cache.create_file(file).await.unwrap();
+        cache.write(file, 0, b"old").await.unwrap();
+        cache.create_dir(dir).await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+
+        // The backend copies still exist when they are created again
+        cache.remove_file(file).await.unwrap();
+        cache.create_file(file).await.unwrap();
+        cache.write(file, 0, b"new").await.unwrap();
+        cache.remove_dir(dir, false).await.unwrap();
+        cache.create_dir(dir).await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+        assert!(cache.pending_sync().is_empty());
+        assert_eq!(&backend.read(file, 0, 16).await.unwrap()[..], b"new");
+        assert!(backend.stat(dir).await.unwrap().is_dir());
+    }
+
+    #[tokio::test]
+    async fn test_rename_over_backend_file_overwrites_it() {
+        let (backend, cache) = memfs_cache();
+        let (a, b, c) = (Path::new("/a"), Path::new("/b"), Path::new("/c"));
+        for path in [a, b, c] {
+            cache.create_file(path).await.unwrap();
+            cache.write(path, 0, b"old").await.unwrap();
+        }
+        cache.sync_to_backend_wait().await.unwrap();
+
+        // The destination isn't new, so removing it again has to reach
+        // the backend
+        cache.rename(a, b).await.unwrap();
+        cache.remove_file(b).await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+        assert_eq!(names(backend.as_ref(), "/").await, ["c"]);
+
+        // Same for a destination whose delete hasn't synced yet
+        cache.create_file(a).await.unwrap();
+        cache.remove_file(c).await.unwrap();
+        cache.rename(a, c).await.unwrap();
+        cache.remove_file(c).await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+        assert!(cache.pending_sync().is_empty());
+        assert!(names(backend.as_ref(), "/").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_full_upload_truncates_positional_backend() {
+        let (backend, cache) = memfs_cache();
+        let path = Path::new("/a");
+        cache.create_file(path).await.unwrap();
+        cache.write(path, 0, b"0123456789").await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+
+        // Replacing the file uploads all of it over the longer backend copy
+        cache.remove_file(path).await.unwrap();
+        cache.create_file(path).await.unwrap();
+        cache.write(path, 0, b"new").await.unwrap();
+        cache.sync_to_backend_wait().await.unwrap();
+        assert_eq!(&backend.read(path, 0, 16).await.unwrap()[..], b"new");
+    }
+
+    /// Operations applied to both a cache and the reference model
+    #[derive(Debug, Clone)]
+    enum ModelOp {
+        Create(usize),
+        Write(usize, u64, Vec<u8>),
+        Truncate(usize, u64),
+        Rename(usize, usize),
+        Remove(usize),
+        Mkdir,
+        Rmdir,
+        Sync,
+    }
+
+    /// Files the operations pick from; the last two live in `MODEL_DIR`
+    const MODEL_FILES: [&str; 4] = ["/a", "/b", "/dir/a", "/dir/b"];
+    const MODEL_DIR: &str = "/dir";
+
+    fn model_op() -> impl proptest::strategy::Strategy<Value = ModelOp> {
+        use proptest::prelude::*;
+        let file = 0..MODEL_FILES.len();
+        prop_oneof![
+            1 => file.clone().prop_map(ModelOp::Create),
+            3 => (file.clone(), 0..64u64, prop::collection::vec(any::<u8>(), 1..32))
+                .prop_map(|(f, offset, data)| ModelOp::Write(f, offset, data)),
+            1 => (file.clone(), 0..96u64).prop_map(|(f, size)| ModelOp::Truncate(f, size)),
+            1 => (file.clone(), file.clone()).prop_map(|(from, to)| ModelOp::Rename(from, to)),
+            1 => file.prop_map(ModelOp::Remove),
+            1 => Just(ModelOp::Mkdir),
+            1 => Just(ModelOp::Rmdir),
+            1 => Just(ModelOp::Sync),
+        ]
+    }
+
+    /// Reference filesystem: file contents and whether `MODEL_DIR` exists
+    #[derive(Default)]
+    struct Model {
+        files: std::collections::BTreeMap<&'static str, Vec<u8>>,
+        dir: bool,
+    }
+
+    impl Model {
+        fn parent_exists(&self, path: &str) -> bool {
+            !path.starts_with(MODEL_DIR) || self.dir
+        }
+
+        /// Names listed in `dir` ("/" or `MODEL_DIR`)
+        fn list(&self, dir: &str) -> Vec<&'static str> {
+            let mut names: Vec<&'static str> = self
+                .files
+                .keys()
+                .filter(|path| Path::new(path).parent() == Some(Path::new(dir)))
+                .map(|path| path.rsplit('/').next().unwrap())
+                .collect();
+            if dir == "/" && self.dir {
+                names.push("dir");
+            }
+            names.sort();
+            names
+        }
+    }
+
+    /// Apply `op` to the model and, if it is valid there, to `fs`
+    async fn apply_op<C: Connector>(
+        fs: &C,
+        model: &mut Model,
+        op: &ModelOp,
+        sync: impl std::future::Future<Output = Result<()>>,
+    ) {
+        match op {
+            ModelOp::Create(f) => {
+                let path = MODEL_FILES[*f];
+                if model.parent_exists(path) && !model.files.contains_key(path) {
+                    fs.create_file(Path::new(path)).await.unwrap();
+                    model.files.insert(path, Vec::new());
+                }
+            }
+            ModelOp::Write(f, offset, data) => {
+                let path = MODEL_FILES[*f];
+                if let Some(content) = model.files.get_mut(path) {
+                    fs.write(Path::new(path), *offset, data).await.unwrap();
+                    let end = *offset as usize + data.len();
+                    if content.len() < end {
+                        content.resize(end, 0);
+                    }
+                    content[*offset as usize..end].copy_from_slice(data);
+                }
+            }
+            ModelOp::Truncate(f, size) => {
+                let path = MODEL_FILES[*f];
+                if let Some(content) = model.files.get_mut(path) {
+                    fs.truncate(Path::new(path), *size).await.unwrap();
+                    content.resize(*size as usize, 0);
+                }
+            }
+            ModelOp::Rename(from, to) => {
+                let (from, to) = (MODEL_FILES[*from], MODEL_FILES[*to]);
+                if from != to && model.files.contains_key(from) && model.parent_exists(to) {
+                    fs.rename(Path::new(from), Path::new(to)).await.unwrap();
+                    let content = model.files.remove(from).unwrap();
+                    model.files.insert(to, content);
+                }
+            }
+            ModelOp::Remove(f) => {
+                let path = MODEL_FILES[*f];
+                if model.files.remove(path).is_some() {
+                    fs.remove_file(Path::new(path)).await.unwrap();
+                }
+            }
+            ModelOp::Mkdir => {
+                if !model.dir {
+                    fs.create_dir(Path::new(MODEL_DIR)).await.unwrap();
+                    model.dir = true;
+                }
+            }
+            ModelOp::Rmdir => {
+                if model.dir && model.list(MODEL_DIR).is_empty() {
+                    fs.remove_dir(Path::new(MODEL_DIR), false).await.unwrap();
+                    model.dir = false;
+                }
+            }
+            ModelOp::Sync => sync.await.unwrap(),
+        }
+    }
+
+    /// Check that `fs` shows exactly what the model holds
+    async fn check_model<C: Connector>(fs: &C, model: &Model, context: &str) {
+        for path in MODEL_FILES {
+            match model.files.get(path) {
+                Some(content) => {
+                    let meta = fs.stat(Path::new(path)).await.unwrap();
+                    assert_eq!(
+                        meta.size,
+                        content.len() as u64,
+                        "{}: size of {}",
+                        context,
+                        path
+                    );
+                    let data = fs.read(Path::new(path), 0, 1024).await.unwrap();
+                    assert_eq!(&data[..], &content[..], "{}: content of {}", context, path);
+                }
+                None => assert!(
+                    fs.stat(Path::new(path)).await.is_err(),
+                    "{}: {} should not exist",
+                    context,
+                    path
+                ),
+            }
+        }
+
+        let mut dirs = vec!["/"];
+        if model.dir {
+            dirs.push(MODEL_DIR);
+        } else {
+            assert!(
+                fs.stat(Path::new(MODEL_DIR)).await.is_err(),
+                "{}: dir exists",
+                context
+            );
+        }
+        for dir in dirs {
+            assert_eq!(
+                names(fs, dir).await,
+                model.list(dir),
+                "{}: listing of {}",
+                context,
+                dir
+            );
+        }
+    }
+
+    /// Run `ops` against `cache` over `backend`, checking the cache after
+    /// every step and the backend after a final sync
+    async fn run_model<S: ContentStore>(
+        cache: WriteBackCache<Arc<MemfsConnector>, S>,
+        backend: &MemfsConnector,
+        ops: &[ModelOp],
+    ) {
+        let mut model = Model::default();
+        for (i, op) in ops.iter().enumerate() {
+            apply_op(&cache, &mut model, op, cache.sync_to_backend_wait()).await;
+            check_model(&cache, &model, &format!("after op {} ({:?})", i, op)).await;
+        }
+        cache.sync_to_backend_wait().await.unwrap();
+        check_model(backend, &model, "backend after sync").await;
+    }
+
+    fn model_runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    proptest::proptest! {
+        #![proptest_config(proptest::prelude::ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_memory_cache_matches_model(ops in proptest::collection::vec(model_op(), 1..40)) {
+            let backend = Arc::new(MemfsConnector::new(None));
+            let cache = MemoryCache::new(backend.clone(), MemoryCacheConfig::default());
+            model_runtime().block_on(run_model(cache, &backend, &ops));
+        }
+
+        #[test]
+        fn prop_filesystem_cache_matches_model(ops in proptest::collection::vec(model_op(), 1..40)) {
+            let dir = tempfile::tempdir().unwrap();
+            let backend = Arc::new(MemfsConnector::new(None));
+            let cache = FilesystemCache::new(
+                backend.clone(),
+                FilesystemCacheConfig {
+                    cache_dir: dir.path().to_path_buf(),
+                    ..Default::default()
+                },
+            );
+            model_runtime().block_on(run_model(cache, &backend, &ops));
+        }
+    }
+}
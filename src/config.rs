@@ -1,10 +1,15 @@
 //! Configuration parsing and structures
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use serde::de::DeserializeSeed;
 use serde::Deserialize;
 
+use crate::auth::http as http_auth;
+use crate::auth::microsoft as microsoft_auth;
+use crate::cache::peer::CachePeersConfig;
 use crate::cache::CacheConfig;
+use crate::connector::registry::{self, BUILTIN_TYPES as BUILTIN_CONNECTOR_TYPES};
 use crate::env::substitute_env_vars;
 
 /// Error handling mode for connector failures during startup
@@ -20,7 +25,7 @@ pub enum ErrorMode {
 
 /// Status overlay configuration for virtual status directory
 #[derive(Debug, Clone, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct StatusOverlayConfig {
     /// Virtual directory name (default: ".fuse-adapter")
     pub prefix: String,
@@ -37,6 +42,636 @@ impl Default for StatusOverlayConfig {
     }
 }
 
+/// End-to-end integrity verification configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegrityConfig {
+    /// Return EIO instead of serving data whose hash doesn't match (default: false)
+    pub fail_on_mismatch: bool,
+    /// Maximum number of recent failures to retain for reporting (default: 100)
+    pub max_failures: usize,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            fail_on_mismatch: false,
+            max_failures: 100,
+        }
+    }
+}
+
+/// Versions overlay configuration for browsing prior object versions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VersionsOverlayConfig {
+    /// Suffix appended to a file name to form its versions directory (default: ".versions")
+    pub suffix: String,
+    /// Group versions under this directory name in each directory instead,
+    /// as `<dir>/<directory>/<file>/` (e.g. ".versions")
+    pub directory: Option<String>,
+}
+
+impl Default for VersionsOverlayConfig {
+    fn default() -> Self {
+        Self {
+            suffix: ".versions".to_string(),
+            directory: None,
+        }
+    }
+}
+
+/// Mount health check configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthCheckConfig {
+    /// How often to check the mount (default: 30s)
+    #[serde(with = "humantime_serde")]
+    pub interval: std::time::Duration,
+    /// How long a check may take before the mount counts as hung (default: 10s)
+    #[serde(with = "humantime_serde")]
+    pub timeout: std::time::Duration,
+    /// Remount automatically when a check fails (default: true)
+    pub auto_remount: bool,
+    /// Upper bound on the delay between failed remount attempts (default: 5m)
+    #[serde(with = "humantime_serde")]
+    pub max_backoff: std::time::Duration,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(30),
+            timeout: std::time::Duration::from_secs(10),
+            auto_remount: true,
+            max_backoff: std::time::Duration::from_secs(300),
+        }
+    }
+}
+
+/// Circuit breaker configuration: stop calling a backend that keeps failing
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive backend failures that open the circuit (default: 5)
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a probe request (default: 30s)
+    #[serde(with = "humantime_serde")]
+    pub cooldown: std::time::Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: std::time::Duration::from_secs(30),
+        }
+    }
+}
+
+/// Read-only fallback: stop accepting changes while the backend denies writes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadOnlyFallbackConfig {
+    /// How often to test whether writes work again (default: 1m)
+    #[serde(with = "humantime_serde")]
+    pub probe_interval: std::time::Duration,
+    /// File created and removed on the backend to test writes
+    /// (default: /.fuse-adapter-write-probe)
+    pub probe_path: PathBuf,
+}
+
+impl Default for ReadOnlyFallbackConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval: std::time::Duration::from_secs(60),
+            probe_path: PathBuf::from("/.fuse-adapter-write-probe"),
+        }
+    }
+}
+
+/// Backend bandwidth limits in bytes per second (e.g., "10MB"); unset
+/// directions are unlimited
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Maximum upload rate to the backend
+    pub upload: Option<String>,
+    /// Maximum download rate from the backend
+    pub download: Option<String>,
+}
+
+impl RateLimitConfig {
+    fn validate(&self) -> Result<(), String> {
+        for (direction, rate) in [("upload", &self.upload), ("download", &self.download)] {
+            if let Some(rate) = rate {
+                if crate::cache::parse_size(rate).is_none() {
+                    return Err(format!("invalid {} rate limit {:?}", direction, rate));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Client-side encryption configuration; exactly one key source must be set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// Base64-encoded 256-bit key (supports ${VAR} substitution)
+    pub key: Option<String>,
+    /// File containing the base64-encoded key
+    pub key_file: Option<PathBuf>,
+    /// Shell command printing the base64-encoded key (e.g., a KMS decrypt call)
+    pub key_command: Option<String>,
+    /// Also encrypt file and directory names (default: false)
+    #[serde(default)]
+    pub encrypt_names: bool,
+}
+
+impl EncryptionConfig {
+    fn validate(&self) -> Result<(), String> {
+        let sources = [
+            self.key.is_some(),
+            self.key_file.is_some(),
+            self.key_command.is_some(),
+        ];
+        if sources.iter().filter(|set| **set).count() != 1 {
+            return Err("encryption needs exactly one of key, key_file or key_command".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Compression algorithm for stored objects
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionAlgorithm {
+    #[default]
+    Zstd,
+    Gzip,
+}
+
+/// Transparent compression configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// Algorithm used for new uploads (default: zstd)
+    pub algorithm: CompressionAlgorithm,
+    /// Compression level (default: 3 for zstd, 6 for gzip)
+    pub level: Option<i32>,
+    /// Files smaller than this are stored as-is (default: "1KB")
+    pub min_size: Option<String>,
+    /// Only compress files with these extensions (default: all)
+    pub extensions: Option<Vec<String>>,
+    /// Never compress files with these extensions (default: common
+    /// already-compressed formats such as gz, zip, jpg and mp4)
+    pub exclude_extensions: Option<Vec<String>>,
+}
+
+impl CompressionConfig {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(min_size) = &self.min_size {
+            if crate::cache::parse_size(min_size).is_none() {
+                return Err(format!("invalid compression min_size {:?}", min_size));
+            }
+        }
+        if let Some(level) = self.level {
+            let range = match self.algorithm {
+                CompressionAlgorithm::Zstd => 1..=22,
+                CompressionAlgorithm::Gzip => 0..=9,
+            };
+            if !range.contains(&level) {
+                return Err(format!(
+                    "compression level {} out of range {}-{}",
+                    level,
+                    range.start(),
+                    range.end()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Coalescing of sequential writes before they reach the connector
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WriteBufferConfig {
+    /// Bytes buffered per file before they're written out (default: "1MB")
+    pub size: Option<String>,
+    /// Longest time data stays buffered (default: 1s)
+    #[serde(with = "humantime_serde")]
+    pub interval: std::time::Duration,
+}
+
+impl Default for WriteBufferConfig {
+    fn default() -> Self {
+        Self {
+            size: None,
+            interval: std::time::Duration::from_secs(1),
+        }
+    }
+}
+
+impl WriteBufferConfig {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(size) = &self.size {
+            match crate::cache::parse_size(size) {
+                Some(bytes) if bytes > 0 => {}
+                _ => return Err(format!("invalid write_buffer size {:?}", size)),
+            }
+        }
+        if self.interval.is_zero() {
+            return Err("write_buffer interval must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Per-mount storage quota
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotaConfig {
+    /// Maximum total size of all files in the mount (e.g., "10GB")
+    pub max_total_size: Option<String>,
+    /// Maximum size of a single file (e.g., "1GB")
+    pub max_file_size: Option<String>,
+}
+
+impl QuotaConfig {
+    fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("max_total_size", &self.max_total_size),
+            ("max_file_size", &self.max_file_size),
+        ] {
+            if let Some(value) = value {
+                if crate::cache::parse_size(value).is_none() {
+                    return Err(format!("invalid quota {} {:?}", name, value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Trash for a mount: deletions are moved aside instead of removed
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// Directory at the mount root holding deleted files (default: ".trash")
+    pub directory: String,
+    /// How long deleted files are kept before being purged (default: 7d)
+    #[serde(with = "humantime_serde")]
+    pub retention: std::time::Duration,
+    /// How often expired deletions are purged (default: 1h)
+    #[serde(with = "humantime_serde")]
+    pub purge_interval: std::time::Duration,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            directory: ".trash".to_string(),
+            retention: std::time::Duration::from_secs(7 * 24 * 3600),
+            purge_interval: std::time::Duration::from_secs(3600),
+        }
+    }
+}
+
+impl TrashConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.directory.is_empty() || self.directory.contains('/') {
+            return Err("trash directory must be non-empty and contain no '/'".to_string());
+        }
+        if self.purge_interval.is_zero() {
+            return Err("trash purge_interval must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Connector operations a fault can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultOperation {
+    /// Every operation without a fault of its own
+    All,
+    /// stat, exists, readlink and other metadata lookups
+    Stat,
+    /// Reads of current or earlier versions
+    Read,
+    /// Writes, including set_mode and zero_range
+    Write,
+    /// Creating files, directories and symlinks, and copies
+    Create,
+    /// Removing files and directories
+    Remove,
+    /// Directory listings
+    List,
+    /// Renames
+    Rename,
+    /// Truncation
+    Truncate,
+    /// Flushing a single file
+    Flush,
+    /// Syncing everything
+    Sync,
+}
+
+impl FaultOperation {
+    /// All operations, in declaration order
+    pub const ALL: [FaultOperation; 11] = [
+        FaultOperation::All,
+        FaultOperation::Stat,
+        FaultOperation::Read,
+        FaultOperation::Write,
+        FaultOperation::Create,
+        FaultOperation::Remove,
+        FaultOperation::List,
+        FaultOperation::Rename,
+        FaultOperation::Truncate,
+        FaultOperation::Flush,
+        FaultOperation::Sync,
+    ];
+
+    /// Name as used in the config and control commands
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FaultOperation::All => "all",
+            FaultOperation::Stat => "stat",
+            FaultOperation::Read => "read",
+            FaultOperation::Write => "write",
+            FaultOperation::Create => "create",
+            FaultOperation::Remove => "remove",
+            FaultOperation::List => "list",
+            FaultOperation::Rename => "rename",
+            FaultOperation::Truncate => "truncate",
+            FaultOperation::Flush => "flush",
+            FaultOperation::Sync => "sync",
+        }
+    }
+
+    /// Look up an operation by name
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.as_str() == name)
+    }
+}
+
+/// Error returned by calls a fault makes fail
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FaultError {
+    /// Generic backend failure (EIO)
+    #[default]
+    Backend,
+    /// Timed out request
+    Timeout,
+    /// Missing file
+    NotFound,
+    /// Backend out of space
+    NoSpace,
+    /// Access denied by the backend
+    PermissionDenied,
+}
+
+/// Fault injected into one kind of operation
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultConfig {
+    /// Fraction of calls that fail, from 0.0 to 1.0 (default: 0)
+    pub error_rate: f64,
+    /// Fail exactly this many calls first, before error_rate applies
+    pub fail_next: u32,
+    /// Error failing calls return (default: backend)
+    pub error: FaultError,
+    /// Delay added before every call
+    #[serde(with = "humantime_serde")]
+    pub latency: Option<std::time::Duration>,
+    /// Return only the first half of the data read
+    pub partial_reads: bool,
+    /// Never complete calls (until operation_timeout, if set)
+    pub hang: bool,
+}
+
+impl FaultConfig {
+    /// Check the fault is within range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.error_rate) {
+            return Err(format!(
+                "fault error_rate {} must be between 0 and 1",
+                self.error_rate
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Fault injection for testing error paths (requires the `mock` feature)
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FaultsConfig {
+    /// Seed deciding which calls fail at a given error_rate (default: 0)
+    pub seed: u64,
+    /// Faults by operation name, e.g. `read` or `all`
+    #[serde(flatten)]
+    pub operations: std::collections::HashMap<FaultOperation, FaultConfig>,
+}
+
+impl FaultsConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !cfg!(feature = "mock") {
+            return Err("faults require building with the `mock` feature".to_string());
+        }
+        for (op, fault) in &self.operations {
+            fault
+                .validate()
+                .map_err(|e| format!("{}: {}", op.as_str(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Destination for a mount's audit log
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum AuditConfig {
+    /// Append JSON lines to a file
+    File { path: PathBuf },
+    /// Send records to the local syslog daemon
+    Syslog,
+}
+
+/// Level of access granted to a user
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// No access (EACCES on every operation)
+    None,
+    /// Lookups, listings and reads
+    Read,
+    /// Full access
+    Write,
+}
+
+/// How `fcntl` and `flock` locks on a mount are handled
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LockingMode {
+    /// Grant locks from a table kept by this process, so they exclude other
+    /// processes using the same mount (but not other hosts)
+    #[default]
+    Local,
+    /// Refuse every lock request with ENOLCK
+    Deny,
+}
+
+/// How the kernel page cache is used for file contents
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PageCacheMode {
+    /// Drop cached pages whenever a file is opened
+    #[default]
+    Invalidate,
+    /// Keep cached pages across opens (libfuse `kernel_cache`)
+    Keep,
+    /// Keep cached pages unless the file's size or mtime changed since it
+    /// was last opened (libfuse `auto_cache`)
+    Auto,
+    /// Bypass the page cache, sending every read and write to the adapter
+    /// (libfuse `direct_io`)
+    DirectIo,
+}
+
+/// How file names are written into S3 keys
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum S3KeyEncoding {
+    /// Use names as keys unchanged (names that aren't valid UTF-8 are
+    /// converted lossily)
+    #[default]
+    Raw,
+    /// Write `%`, control characters, bytes that aren't valid UTF-8 and any
+    /// `escape_chars` as `%XX`, so every name round-trips through its key
+    Escape,
+}
+
+/// Where the mtime of an S3 directory comes from
+///
+/// S3 has no real directories, so nothing records when one last changed.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum S3DirMtime {
+    /// The time of the stat, so the mtime changes on every lookup
+    #[default]
+    Now,
+    /// The newest LastModified among the objects directly in the directory
+    LatestChild,
+    /// The LastModified of the directory's `name/` marker object
+    Marker,
+    /// Always the Unix epoch
+    Epoch,
+}
+
+/// Unicode normalization form that file names are converted to
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeNormalization {
+    /// Pass names through as the client sent them
+    #[default]
+    None,
+    /// Composed form, used by Linux and Windows clients
+    Nfc,
+    /// Decomposed form, used by macOS clients
+    Nfd,
+}
+
+/// macOS (macFUSE) settings for a mount
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MacosConfig {
+    /// Volume name shown in Finder (default: the mount directory's name)
+    pub volname: Option<String>,
+    /// `.icns` file used as the volume icon in Finder
+    pub icon: Option<PathBuf>,
+    /// What happens to `._*` AppleDouble files (default: sync)
+    #[serde(default)]
+    pub apple_double: AppleFileMode,
+    /// What happens to `.DS_Store` files (default: sync)
+    #[serde(default)]
+    pub ds_store: AppleFileMode,
+}
+
+impl MacosConfig {
+    /// Glob matching AppleDouble files anywhere in the mount
+    pub const APPLE_DOUBLE_PATTERN: &'static str = "**/._*";
+    /// Glob matching `.DS_Store` files anywhere in the mount
+    pub const DS_STORE_PATTERN: &'static str = "**/.DS_Store";
+
+    /// Patterns for the Finder files handled with `mode`
+    pub fn patterns(&self, mode: AppleFileMode) -> Vec<String> {
+        [
+            (self.apple_double, Self::APPLE_DOUBLE_PATTERN),
+            (self.ds_store, Self::DS_STORE_PATTERN),
+        ]
+        .into_iter()
+        .filter(|(m, _)| *m == mode)
+        .map(|(_, pattern)| pattern.to_string())
+        .collect()
+    }
+
+    /// Whether macOS is told not to create AppleDouble files at all
+    pub fn no_apple_double(&self) -> bool {
+        self.apple_double == AppleFileMode::Reject
+    }
+}
+
+/// How Finder metadata files written by macOS are handled
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AppleFileMode {
+    /// Store them in the backend like any other file
+    #[default]
+    Sync,
+    /// Keep them in the local cache only; they're never uploaded
+    Local,
+    /// Refuse to create them, and hide any already in the backend
+    Reject,
+}
+
+/// Access rule for paths matching a glob
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessRule {
+    /// Glob matched against the path relative to the mount root; matching a
+    /// directory covers everything beneath it
+    pub path: String,
+    /// Users the rule applies to
+    #[serde(default)]
+    pub uids: Vec<u32>,
+    /// Groups (by primary gid) the rule applies to
+    #[serde(default)]
+    pub gids: Vec<u32>,
+    /// Access granted to matching users
+    pub permission: Permission,
+}
+
+/// Per-user access control for a mount
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessConfig {
+    /// Only these users may access the mount (default: everyone)
+    pub allowed_uids: Vec<u32>,
+    /// Only users with these primary groups may access the mount
+    pub allowed_gids: Vec<u32>,
+    /// Users limited to read access
+    pub read_only_uids: Vec<u32>,
+    /// Groups limited to read access
+    pub read_only_gids: Vec<u32>,
+    /// Path rules, first match wins (overrides the read-only lists)
+    pub rules: Vec<AccessRule>,
+}
+
 // =============================================================================
 // Raw Config (Deserialized from YAML)
 // =============================================================================
@@ -44,6 +679,7 @@ impl Default for StatusOverlayConfig {
 /// Raw configuration as deserialized from YAML.
 /// This is converted to `Config` via `resolve()`.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RawConfig {
     /// Logging configuration
     #[serde(default)]
@@ -53,16 +689,48 @@ pub struct RawConfig {
     #[serde(default)]
     pub error_mode: ErrorMode,
 
+    /// How long to wait for caches to sync on shutdown (e.g., "30s", "2m")
+    #[serde(default = "default_shutdown_timeout", with = "humantime_serde")]
+    pub shutdown_timeout: std::time::Duration,
+
+    /// Default limit on how long a single filesystem operation may take
+    /// before failing with ETIMEDOUT (e.g., "30s"; unset = no limit)
+    #[serde(default, with = "humantime_serde")]
+    pub operation_timeout: Option<std::time::Duration>,
+
+    /// Bandwidth limits shared by all mounts together
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Unix socket accepting admin commands (unset = no socket)
+    #[serde(default)]
+    pub control_socket: Option<PathBuf>,
+
+    /// Sharing cached content with other adapters on the network
+    #[serde(default)]
+    pub cache_peers: Option<CachePeersConfig>,
+
+    /// Span export for distributed tracing
+    #[serde(default)]
+    pub tracing: TracingConfig,
+
     /// Top-level connector defaults
     #[serde(default)]
     pub connectors: ConnectorDefaults,
 
+    /// Other config files, or directories of them, merged into this one;
+    /// relative paths are relative to this file's directory
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+
     /// Mount points
+    #[serde(default)]
     pub mounts: Vec<RawMountConfig>,
 }
 
 /// Top-level connector defaults section
 #[derive(Debug, Clone, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ConnectorDefaults {
     /// S3 connector defaults
     pub s3: Option<S3ConnectorDefaults>,
@@ -73,6 +741,7 @@ pub struct ConnectorDefaults {
 
 /// S3 connector defaults (bucket is required)
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct S3ConnectorDefaults {
     /// S3 bucket name
     pub bucket: String,
@@ -90,12 +759,66 @@ pub struct S3ConnectorDefaults {
     #[serde(default)]
     pub force_path_style: bool,
 
+    /// Attach size and mtime from ListObjectsV2 to directory entries so
+    /// caches can skip the per-file HEAD after a listing
+    #[serde(default)]
+    pub list_metadata: bool,
+
+    /// Maximum number of S3 operations in flight at once (default: no limit)
+    pub max_concurrent_requests: Option<usize>,
+
+    /// Keys requested per ListObjectsV2 page (1-1000, default: 1000)
+    pub max_keys: Option<u32>,
+
+    /// Where directory mtimes come from (default: now)
+    #[serde(default)]
+    pub dir_mtime: S3DirMtime,
+
+    /// Create a `name/` marker object for each new directory (default: true)
+    pub directory_markers: Option<bool>,
+
+    /// Rename in the bucket by journaled copy + delete (default: false)
+    #[serde(default)]
+    pub atomic_rename: bool,
+
+    /// Create the prefix's marker object at mount time if the prefix is
+    /// empty (default: false)
+    #[serde(default)]
+    pub create_prefix: bool,
+
+    /// Timeout for establishing a connection (e.g., "5s")
+    #[serde(default, with = "humantime_serde")]
+    pub connect_timeout: Option<std::time::Duration>,
+
+    /// Timeout for the first byte of a response (e.g., "30s")
+    #[serde(default, with = "humantime_serde")]
+    pub read_timeout: Option<std::time::Duration>,
+
+    /// Total attempts per request, including the first (SDK default: 3)
+    pub max_attempts: Option<u32>,
+
+    /// Base delay before the first retry (e.g., "100ms")
+    #[serde(default, with = "humantime_serde")]
+    pub initial_backoff: Option<std::time::Duration>,
+
+    /// Slow requests down while S3 is throttling them (default: true)
+    pub adaptive_rate: Option<bool>,
+
+    /// How file names are written into keys (default: raw)
+    #[serde(default)]
+    pub key_encoding: S3KeyEncoding,
+
+    /// Characters escaped in addition to the defaults with `escape` encoding
+    #[serde(default)]
+    pub escape_chars: String,
+
     /// Default cache configuration for S3 mounts
     pub cache: Option<CacheConfig>,
 }
 
 /// Google Drive connector defaults
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct GDriveConnectorDefaults {
     /// Authentication configuration
     pub auth: Option<RawGDriveAuthConfig>,
@@ -103,20 +826,55 @@ pub struct GDriveConnectorDefaults {
     /// Root folder ID (defaults to "root" for My Drive)
     pub root_folder_id: Option<String>,
 
+    /// Shared drive ID to mount instead of My Drive
+    pub drive_id: Option<String>,
+
+    /// Include shared drive items in API calls (implied by drive_id)
+    pub supports_all_drives: Option<bool>,
+
+    /// Export formats for native Google files (e.g. document: docx)
+    pub export_formats: Option<std::collections::HashMap<String, String>>,
+
+    /// Drive API endpoint (for emulators and testing)
+    pub endpoint: Option<String>,
+
     /// Default cache configuration
     pub cache: Option<CacheConfig>,
 }
 
+/// How a user approves Google Drive access the first time
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuthFlow {
+    /// Enter a code shown in the log on another device
+    #[default]
+    Device,
+    /// Open a link in a browser, which redirects back to a local port
+    Redirect,
+}
+
 /// Raw authentication configuration for Google Drive (deserialized from YAML).
 /// Environment variable substitution is applied during resolution.
 #[derive(Debug, Clone, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum RawGDriveAuthConfig {
     /// Service account credentials file
     ServiceAccount {
         /// Path to the service account JSON credentials file
         credentials_path: String,
     },
+    /// Sign in as a user account through an OAuth consent flow
+    Oauth {
+        /// Path to the OAuth client JSON downloaded from the Cloud Console
+        client_secret_path: String,
+        /// File the tokens (including the refresh token) are kept in
+        token_path: String,
+        /// How the user approves access (default: device)
+        #[serde(default)]
+        flow: OAuthFlow,
+        /// Local port for the redirect flow (default: a random port)
+        redirect_port: Option<u16>,
+    },
     /// HTTP-based token provider with arbitrary headers
     Http {
         /// Token endpoint URL
@@ -126,6 +884,17 @@ pub enum RawGDriveAuthConfig {
         /// HTTP headers to send with token requests (supports env var substitution)
         #[serde(default)]
         headers: std::collections::HashMap<String, String>,
+        /// Response field holding the token; dots reach into nested objects
+        /// (e.g. "data.token"). Defaults to access_token.
+        token_field: Option<String>,
+        /// Response field holding the token lifetime in seconds. Defaults
+        /// to expires_in.
+        expires_in_field: Option<String>,
+        /// Total attempts per token fetch, including the first (default: 3)
+        max_attempts: Option<u32>,
+        /// Delay before the first retry, doubled after each (default: 200ms)
+        #[serde(default, with = "humantime_serde")]
+        initial_backoff: Option<std::time::Duration>,
     },
     /// Static access token (for testing)
     Token {
@@ -136,6 +905,7 @@ pub enum RawGDriveAuthConfig {
 
 /// Raw mount configuration before resolution
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct RawMountConfig {
     /// Path where the filesystem will be mounted
     pub path: PathBuf,
@@ -156,28 +926,330 @@ pub struct RawMountConfig {
     /// Status overlay configuration (opt-in)
     pub status_overlay: Option<StatusOverlayConfig>,
 
-    /// Connector configuration (may be partial, inheriting from defaults)
-    pub connector: MountConnectorConfig,
+    /// Versions overlay configuration (opt-in)
+    pub versions_overlay: Option<VersionsOverlayConfig>,
 
-    /// Cache configuration (overrides connector default)
-    pub cache: Option<CacheConfig>,
-}
+    /// Glob patterns for paths hidden from the mount (e.g., "**/.DS_Store")
+    #[serde(default)]
+    pub hide_patterns: Vec<String>,
 
-/// Mount-level connector configuration (tagged enum)
-/// All fields except `type` are optional - missing values inherit from top-level defaults
-#[derive(Debug, Clone, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-pub enum MountConnectorConfig {
-    /// S3 connector
-    S3(S3MountConnectorConfig),
+    /// Content integrity verification on cache fill (opt-in)
+    pub integrity: Option<IntegrityConfig>,
 
-    /// Google Drive connector
-    #[serde(rename = "gdrive")]
-    GDrive(GDriveMountConnectorConfig),
-}
+    /// Periodic health checks and automatic remount (opt-in)
+    pub health_check: Option<HealthCheckConfig>,
+
+    /// Fail fast while the backend is down (opt-in)
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    /// Turn read-only while the backend denies writes (opt-in)
+    pub read_only_fallback: Option<ReadOnlyFallbackConfig>,
+
+    /// Per-mount operation timeout (overrides global operation_timeout)
+    #[serde(default, with = "humantime_serde")]
+    pub operation_timeout: Option<std::time::Duration>,
+
+    /// Bandwidth limits for this mount (applied on top of the global limits)
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Client-side encryption of content (and optionally names)
+    pub encryption: Option<EncryptionConfig>,
+
+    /// Transparent compression of stored objects
+    pub compression: Option<CompressionConfig>,
+
+    /// Storage quota for this mount
+    pub quota: Option<QuotaConfig>,
+
+    /// Move deleted files into a trash directory (opt-in)
+    pub trash: Option<TrashConfig>,
+
+    /// Audit log of filesystem mutations (opt-in)
+    pub audit: Option<AuditConfig>,
+
+    /// Per-user access rules (opt-in)
+    pub access: Option<AccessConfig>,
+
+    /// Check file modes against the requesting uid/gid (default: false)
+    #[serde(default)]
+    pub enforce_permissions: bool,
+
+    /// File locking behavior (default: local)
+    #[serde(default)]
+    pub locking: LockingMode,
+
+    /// Kernel page cache behavior (default: invalidate)
+    #[serde(default)]
+    pub page_cache: PageCacheMode,
+
+    /// Normalization form file names are converted to (default: none)
+    #[serde(default)]
+    pub unicode_normalization: UnicodeNormalization,
+
+    /// macOS volume name, icon and Finder file handling (opt-in)
+    pub macos: Option<MacosConfig>,
+
+    /// Coalesce sequential writes in memory (opt-in)
+    pub write_buffer: Option<WriteBufferConfig>,
+
+    /// Largest write request the kernel sends, e.g. "1MB" (default: kernel limit)
+    pub max_write: Option<String>,
+
+    /// Largest read request the kernel sends, e.g. "1MB" (default: kernel limit)
+    pub max_read: Option<String>,
+
+    /// Injected faults for testing (requires the `mock` feature)
+    pub faults: Option<FaultsConfig>,
+
+    /// Connector configuration (may be partial, inheriting from defaults)
+    pub connector: MountConnectorConfig,
+
+    /// Paths sent to other connectors, first match wins (default: none)
+    #[serde(default)]
+    pub routes: Vec<RawRouteConfig>,
+
+    /// Cache configuration (overrides connector default)
+    pub cache: Option<CacheConfig>,
+}
+
+/// Mount-level connector configuration (tagged enum)
+/// All fields except `type` are optional - missing values inherit from top-level defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "serde_yaml::Value")]
+pub enum MountConnectorConfig {
+    /// S3 connector
+    S3(S3MountConnectorConfig),
+
+    /// Google Drive connector
+    GDrive(GDriveMountConnectorConfig),
+
+    /// Several connectors layered into one view
+    Union(UnionMountConnectorConfig),
+
+    /// Key-value store connector
+    Kv(KvMountConnectorConfig),
+
+    /// OneDrive or SharePoint document library via Microsoft Graph
+    OneDrive(OneDriveMountConnectorConfig),
+
+    /// Members of a zip or tar archive on another connector
+    Archive(ArchiveMountConnectorConfig),
+
+    /// In-memory scratch filesystem
+    Memfs(MemfsConnectorConfig),
+
+    /// Connector type provided by a registered `ConnectorFactory`
+    Custom(CustomConnectorConfig),
+}
+
+/// The built-in connector types, as tagged in YAML
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum BuiltinMountConnectorConfig {
+    S3(S3MountConnectorConfig),
+    #[serde(rename = "gdrive")]
+    GDrive(GDriveMountConnectorConfig),
+    Union(UnionMountConnectorConfig),
+    Kv(KvMountConnectorConfig),
+    #[serde(rename = "onedrive")]
+    OneDrive(OneDriveMountConnectorConfig),
+    Archive(ArchiveMountConnectorConfig),
+    Memfs(MemfsConnectorConfig),
+}
+
+impl TryFrom<serde_yaml::Value> for MountConnectorConfig {
+    type Error = String;
+
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        let type_name = match value.get("type") {
+            Some(serde_yaml::Value::String(t)) => t.clone(),
+            Some(_) => return Err("connector `type` must be a string".to_string()),
+            None => return Err("missing field `type`".to_string()),
+        };
+        if !BUILTIN_CONNECTOR_TYPES.contains(&type_name.as_str()) {
+            let mut options = value;
+            if let serde_yaml::Value::Mapping(map) = &mut options {
+                map.remove("type");
+            }
+            return Ok(MountConnectorConfig::Custom(CustomConnectorConfig {
+                type_name,
+                options,
+            }));
+        }
+        let builtin: BuiltinMountConnectorConfig =
+            serde_yaml::from_value(value).map_err(|e| e.to_string())?;
+        Ok(match builtin {
+            BuiltinMountConnectorConfig::S3(c) => MountConnectorConfig::S3(c),
+            BuiltinMountConnectorConfig::GDrive(c) => MountConnectorConfig::GDrive(c),
+            BuiltinMountConnectorConfig::Union(c) => MountConnectorConfig::Union(c),
+            BuiltinMountConnectorConfig::Kv(c) => MountConnectorConfig::Kv(c),
+            BuiltinMountConnectorConfig::OneDrive(c) => MountConnectorConfig::OneDrive(c),
+            BuiltinMountConnectorConfig::Archive(c) => MountConnectorConfig::Archive(c),
+            BuiltinMountConnectorConfig::Memfs(c) => MountConnectorConfig::Memfs(c),
+        })
+    }
+}
+
+/// Connector of a type registered at runtime; its options are passed to
+/// the factory as they are
+#[derive(Debug, Clone)]
+pub struct CustomConnectorConfig {
+    /// The `type` the factory is registered under
+    pub type_name: String,
+    /// The rest of the connector section
+    pub options: serde_yaml::Value,
+}
+
+/// Key-value store mount connector
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KvMountConnectorConfig {
+    /// Store implementation (default: sled)
+    #[serde(default)]
+    pub backend: KvBackend,
+
+    /// Location of the store (supports ${VAR} substitution)
+    pub path: String,
+
+    /// Only expose keys below this prefix
+    pub prefix: Option<String>,
+}
+
+/// Implementations of the key-value store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KvBackend {
+    /// Embedded sled database in a local directory
+    #[default]
+    Sled,
+}
+
+/// OneDrive / SharePoint mount connector (Microsoft Graph)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OneDriveMountConnectorConfig {
+    /// How to sign in to Microsoft Graph
+    pub auth: RawOneDriveAuthConfig,
+
+    /// ID of the drive (document library) to mount
+    pub drive_id: Option<String>,
+
+    /// SharePoint site whose default document library is mounted, as a site
+    /// ID or `hostname:/sites/name`
+    pub site_id: Option<String>,
+
+    /// User (ID or principal name) whose OneDrive is mounted
+    pub user: Option<String>,
+
+    /// Folder within the drive to mount (default: the drive root)
+    pub root: Option<String>,
+
+    /// Microsoft Graph endpoint, for national clouds
+    /// (default: https://graph.microsoft.com/v1.0)
+    pub endpoint: Option<String>,
+}
+
+/// Raw authentication configuration for Microsoft Graph.
+/// Environment variable substitution is applied during resolution.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RawOneDriveAuthConfig {
+    /// Sign in as an application with a client secret
+    ClientCredentials {
+        /// Directory (tenant) ID or domain
+        tenant_id: String,
+        /// Application (client) ID of the app registration
+        client_id: String,
+        /// Client secret of the app registration
+        client_secret: String,
+        /// Login endpoint (default: https://login.microsoftonline.com)
+        authority: Option<String>,
+    },
+    /// Sign in as a user by entering a code shown in the log
+    DeviceCode {
+        /// Directory (tenant) ID or domain; "common" allows any account
+        tenant_id: String,
+        /// Application (client) ID of a public client app registration
+        client_id: String,
+        /// File the refresh token is kept in
+        token_path: String,
+        /// Login endpoint (default: https://login.microsoftonline.com)
+        authority: Option<String>,
+    },
+}
+
+/// In-memory connector configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemfsConnectorConfig {
+    /// Most file content held at once, e.g. "1GB" (default: unlimited)
+    pub max_size: Option<String>,
+}
+
+/// Archive mount connector - a zip or tar file read through another connector
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchiveMountConnectorConfig {
+    /// Path of the archive on the source connector
+    pub path: String,
+
+    /// Archive format (default: detected from the file extension)
+    pub format: Option<ArchiveFormat>,
+
+    /// Connector the archive is read from
+    pub source: Box<MountConnectorConfig>,
+}
+
+/// Archive formats the archive connector reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz", alias = "tgz")]
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// The format suggested by a file name's extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        let path = path.to_ascii_lowercase();
+        if path.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if path.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Union mount connector - a list of S3/GDrive layers
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnionMountConnectorConfig {
+    /// Layers from top to bottom; the first is the writable upper layer and
+    /// the rest are read-only lower layers
+    pub layers: Vec<MountConnectorConfig>,
+}
+
+/// Route sending paths that match a glob to another connector
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawRouteConfig {
+    /// Glob matched against the path relative to the mount root; matching a
+    /// directory covers everything beneath it
+    pub path: String,
+    /// Connector the matching paths are stored in; never a union or archive
+    pub connector: MountConnectorConfig,
+}
 
 /// S3 mount connector - all fields optional for override mode
 #[derive(Debug, Clone, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct S3MountConnectorConfig {
     /// S3 bucket name (if present, full config mode; otherwise inherits from defaults)
     pub bucket: Option<String>,
@@ -193,16 +1265,79 @@ pub struct S3MountConnectorConfig {
 
     /// Force path-style addressing
     pub force_path_style: Option<bool>,
+
+    /// Feed listing metadata to the cache
+    pub list_metadata: Option<bool>,
+
+    /// Maximum number of S3 operations in flight at once
+    pub max_concurrent_requests: Option<usize>,
+
+    /// Keys requested per ListObjectsV2 page
+    pub max_keys: Option<u32>,
+
+    /// Where directory mtimes come from
+    pub dir_mtime: Option<S3DirMtime>,
+
+    /// Create a `name/` marker object for each new directory
+    pub directory_markers: Option<bool>,
+
+    /// Rename in the bucket by journaled copy + delete
+    pub atomic_rename: Option<bool>,
+
+    /// Create the prefix's marker object at mount time if the prefix is empty
+    pub create_prefix: Option<bool>,
+
+    /// Timeout for establishing a connection
+    #[serde(default, with = "humantime_serde")]
+    pub connect_timeout: Option<std::time::Duration>,
+
+    /// Timeout for the first byte of a response
+    #[serde(default, with = "humantime_serde")]
+    pub read_timeout: Option<std::time::Duration>,
+
+    /// Total attempts per request, including the first
+    pub max_attempts: Option<u32>,
+
+    /// Base delay before the first retry
+    #[serde(default, with = "humantime_serde")]
+    pub initial_backoff: Option<std::time::Duration>,
+
+    /// Slow requests down while S3 is throttling them
+    pub adaptive_rate: Option<bool>,
+
+    /// How file names are written into keys
+    pub key_encoding: Option<S3KeyEncoding>,
+
+    /// Characters escaped in addition to the defaults with `escape` encoding
+    pub escape_chars: Option<String>,
+
+    /// Mount a read-only snapshot of a versioned bucket as it was at this
+    /// time (RFC 3339, e.g. "2024-05-01T12:00:00Z")
+    #[serde(default, with = "humantime_serde")]
+    pub as_of: Option<std::time::SystemTime>,
 }
 
 /// Google Drive mount connector - all fields optional
 #[derive(Debug, Clone, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct GDriveMountConnectorConfig {
     /// Authentication configuration (overrides default if present)
     pub auth: Option<RawGDriveAuthConfig>,
 
     /// Root folder ID (defaults to "root" for My Drive)
     pub root_folder_id: Option<String>,
+
+    /// Shared drive ID to mount instead of My Drive
+    pub drive_id: Option<String>,
+
+    /// Include shared drive items in API calls (implied by drive_id)
+    pub supports_all_drives: Option<bool>,
+
+    /// Export formats for native Google files (overrides defaults)
+    pub export_formats: Option<std::collections::HashMap<String, String>>,
+
+    /// Drive API endpoint (overrides default)
+    pub endpoint: Option<String>,
 }
 
 // =============================================================================
@@ -218,16 +1353,164 @@ pub struct Config {
     /// Error handling mode for connector failures
     pub error_mode: ErrorMode,
 
+    /// How long to wait for caches to sync on shutdown
+    pub shutdown_timeout: std::time::Duration,
+
+    /// Bandwidth limits shared by all mounts together
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Unix socket accepting admin commands
+    pub control_socket: Option<PathBuf>,
+
+    /// Sharing cached content with other adapters on the network
+    pub cache_peers: Option<CachePeersConfig>,
+
+    /// Span export for distributed tracing
+    pub tracing: TracingConfig,
+
     /// Mount points (fully resolved)
     pub mounts: Vec<MountConfig>,
 }
 
 /// Logging configuration
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct LoggingConfig {
     /// Log level (trace, debug, info, warn, error)
     #[serde(default = "default_log_level")]
     pub level: String,
+
+    /// Write logs to this file instead of stderr
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+
+    /// How each log line is formatted
+    #[serde(default)]
+    pub format: LogFormat,
+
+    /// Where logs go (default: `file` if a file is set, else `stderr`)
+    #[serde(default)]
+    pub output: Option<LogOutput>,
+
+    /// When the log file is rotated
+    #[serde(default)]
+    pub rotation: LogRotationConfig,
+}
+
+impl LoggingConfig {
+    /// Where logs go, after applying the default
+    pub fn output(&self) -> LogOutput {
+        self.output.unwrap_or(if self.file.is_some() {
+            LogOutput::File
+        } else {
+            LogOutput::Stderr
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.output() == LogOutput::File && self.file.is_none() {
+            return Err("logging output is file but no file is set".to_string());
+        }
+        if let Some(size) = &self.rotation.max_size {
+            if matches!(crate::cache::parse_size(size), None | Some(0)) {
+                return Err(format!("invalid log rotation max_size {:?}", size));
+            }
+        }
+        if self.rotation.keep == 0 {
+            return Err("log rotation keep must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Distributed tracing configuration
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TracingConfig {
+    /// OTLP/HTTP collector to export spans to (e.g., "http://localhost:4318");
+    /// no spans are recorded without one
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+
+    /// Service name spans are reported under
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: default_service_name(),
+        }
+    }
+}
+
+fn default_service_name() -> String {
+    "fuse-adapter".to_string()
+}
+
+/// Log line format
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregators
+    Json,
+}
+
+/// Log destination
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    Stderr,
+    File,
+}
+
+/// Log file rotation; the current file is renamed to `<file>.1` (shifting
+/// older ones up) once it outgrows `max_size` or a new `interval` begins
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LogRotationConfig {
+    /// Rotate once the file reaches this size (e.g., "100MB")
+    #[serde(default)]
+    pub max_size: Option<String>,
+    /// Rotate at the start of every local hour or day
+    #[serde(default)]
+    pub interval: RotationInterval,
+    /// Rotated files to keep
+    #[serde(default = "default_log_keep")]
+    pub keep: usize,
+}
+
+impl Default for LogRotationConfig {
+    fn default() -> Self {
+        Self {
+            max_size: None,
+            interval: RotationInterval::default(),
+            keep: default_log_keep(),
+        }
+    }
+}
+
+fn default_log_keep() -> usize {
+    5
+}
+
+/// Time-based log rotation period
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationInterval {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+fn default_shutdown_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(30)
 }
 
 fn default_log_level() -> String {
@@ -238,6 +1521,10 @@ impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
             level: default_log_level(),
+            file: None,
+            format: LogFormat::default(),
+            output: None,
+            rotation: LogRotationConfig::default(),
         }
     }
 }
@@ -263,9 +1550,80 @@ pub struct MountConfig {
     /// Status overlay configuration (None if not enabled)
     pub status_overlay: Option<StatusOverlayConfig>,
 
+    /// Versions overlay configuration (None if not enabled)
+    pub versions_overlay: Option<VersionsOverlayConfig>,
+
+    /// Glob patterns for paths hidden from the mount
+    pub hide_patterns: Vec<String>,
+
+    /// Content integrity verification (None if not enabled)
+    pub integrity: Option<IntegrityConfig>,
+
+    /// Health checking (None if not enabled)
+    pub health_check: Option<HealthCheckConfig>,
+
+    /// Circuit breaker around the backend (None if not enabled)
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    /// Read-only fallback on lost write access (None if not enabled)
+    pub read_only_fallback: Option<ReadOnlyFallbackConfig>,
+
+    /// Limit on a single filesystem operation (resolved from per-mount or global)
+    pub operation_timeout: Option<std::time::Duration>,
+
+    /// Bandwidth limits for this mount alone
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Client-side encryption (None if not enabled)
+    pub encryption: Option<EncryptionConfig>,
+
+    /// Transparent compression (None if not enabled)
+    pub compression: Option<CompressionConfig>,
+
+    /// Storage quota (None if not enabled)
+    pub quota: Option<QuotaConfig>,
+    /// Trash for deleted files (None if not enabled)
+    pub trash: Option<TrashConfig>,
+
+    /// Audit log destination (None if not enabled)
+    pub audit: Option<AuditConfig>,
+
+    /// Per-user access rules (None if not enabled)
+    pub access: Option<AccessConfig>,
+
+    /// Check file modes against the requesting uid/gid
+    pub enforce_permissions: bool,
+
+    /// File locking behavior
+    pub locking: LockingMode,
+
+    /// Kernel page cache behavior
+    pub page_cache: PageCacheMode,
+
+    /// Normalization form file names are converted to
+    pub unicode_normalization: UnicodeNormalization,
+
+    /// macOS settings (None if not configured)
+    pub macos: Option<MacosConfig>,
+
+    /// Write coalescing (None if not enabled)
+    pub write_buffer: Option<WriteBufferConfig>,
+
+    /// Largest write request the kernel sends (None = kernel limit)
+    pub max_write: Option<String>,
+
+    /// Largest read request the kernel sends (None = kernel limit)
+    pub max_read: Option<String>,
+
+    /// Injected faults (None if not enabled)
+    pub faults: Option<FaultsConfig>,
+
     /// Connector configuration (fully resolved)
     pub connector: ConnectorConfig,
 
+    /// Paths sent to other connectors, in order
+    pub routes: Vec<RouteConfig>,
+
     /// Cache configuration (resolved from inheritance chain)
     pub cache: CacheConfig,
 }
@@ -278,6 +1636,120 @@ pub enum ConnectorConfig {
 
     /// Google Drive connector
     GDrive(GDriveConnectorConfig),
+
+    /// Union of several connectors
+    Union(UnionConnectorConfig),
+
+    /// Key-value store connector
+    Kv(KvConnectorConfig),
+
+    /// OneDrive / SharePoint connector
+    OneDrive(OneDriveConnectorConfig),
+
+    /// Archive connector
+    Archive(ArchiveConnectorConfig),
+
+    /// In-memory connector
+    Memfs(MemfsConnectorConfig),
+
+    /// Connector type provided by a registered `ConnectorFactory`
+    Custom(CustomConnectorConfig),
+}
+
+/// Union connector configuration (fully resolved)
+#[derive(Debug, Clone)]
+pub struct UnionConnectorConfig {
+    /// Layers from top to bottom; the first is the writable upper layer.
+    /// Layers are never unions themselves
+    pub layers: Vec<ConnectorConfig>,
+}
+
+/// Route configuration (fully resolved)
+#[derive(Debug, Clone)]
+pub struct RouteConfig {
+    /// Glob matched against the path relative to the mount root
+    pub path: String,
+    /// Connector the matching paths are stored in; never a union or archive
+    pub connector: ConnectorConfig,
+}
+
+/// Key-value store connector configuration (fully resolved)
+#[derive(Debug, Clone)]
+pub struct KvConnectorConfig {
+    /// Store implementation
+    pub backend: KvBackend,
+
+    /// Location of the store
+    pub path: PathBuf,
+
+    /// Key prefix for mounting a subset of the keys
+    pub prefix: Option<String>,
+}
+
+/// OneDrive connector configuration (fully resolved)
+///
+/// At most one of `drive_id`, `site_id` and `user` is set; with none of
+/// them the signed-in user's own OneDrive is mounted.
+#[derive(Debug, Clone)]
+pub struct OneDriveConnectorConfig {
+    /// Authentication configuration
+    pub auth: OneDriveAuthConfig,
+
+    /// ID of the drive to mount
+    pub drive_id: Option<String>,
+
+    /// SharePoint site whose default document library is mounted
+    pub site_id: Option<String>,
+
+    /// User whose OneDrive is mounted
+    pub user: Option<String>,
+
+    /// Folder within the drive to mount ("" for the drive root)
+    pub root: String,
+
+    /// Microsoft Graph endpoint
+    pub endpoint: String,
+}
+
+/// Resolved authentication configuration for Microsoft Graph.
+/// Environment variables have been substituted.
+#[derive(Debug, Clone)]
+pub enum OneDriveAuthConfig {
+    /// Sign in as an application with a client secret
+    ClientCredentials {
+        /// Login endpoint
+        authority: String,
+        /// Directory (tenant) ID or domain
+        tenant_id: String,
+        /// Application (client) ID
+        client_id: String,
+        /// Client secret
+        client_secret: String,
+    },
+    /// Sign in as a user with a device code
+    DeviceCode {
+        /// Login endpoint
+        authority: String,
+        /// Directory (tenant) ID or domain
+        tenant_id: String,
+        /// Application (client) ID
+        client_id: String,
+        /// File the refresh token is kept in
+        token_path: PathBuf,
+    },
+}
+
+/// Archive connector configuration (fully resolved)
+#[derive(Debug, Clone)]
+pub struct ArchiveConnectorConfig {
+    /// Path of the archive on the source connector
+    pub path: PathBuf,
+
+    /// Archive format
+    pub format: ArchiveFormat,
+
+    /// Connector the archive is read from; never a union or archive
+    pub source: Box<ConnectorConfig>,
 }
 
 /// S3 connector configuration (fully resolved)
@@ -297,7 +1769,52 @@ pub struct S3ConnectorConfig {
 
     /// Force path-style addressing (for MinIO, LocalStack, etc.)
     pub force_path_style: bool,
-}
+
+    /// Attach size and mtime from listings to directory entries
+    pub list_metadata: bool,
+
+    /// Maximum number of S3 operations in flight at once (None = no limit)
+    pub max_concurrent_requests: Option<usize>,
+
+    /// Keys requested per ListObjectsV2 page (None = S3's limit of 1000)
+    pub max_keys: Option<u32>,
+
+    /// Where directory mtimes come from
+    pub dir_mtime: S3DirMtime,
+
+    /// Create a `name/` marker object for each new directory
+    pub directory_markers: bool,
+
+    /// Rename in the bucket by journaled copy + delete
+    pub atomic_rename: bool,
+
+    /// Create the prefix's marker object at mount time if the prefix is empty
+    pub create_prefix: bool,
+
+    /// Timeout for establishing a connection (None = SDK default)
+    pub connect_timeout: Option<std::time::Duration>,
+
+    /// Timeout for the first byte of a response (None = SDK default)
+    pub read_timeout: Option<std::time::Duration>,
+
+    /// Total attempts per request, including the first (None = SDK default)
+    pub max_attempts: Option<u32>,
+
+    /// Base delay before the first retry (None = SDK default)
+    pub initial_backoff: Option<std::time::Duration>,
+
+    /// Cap the operation rate while S3 is throttling requests
+    pub adaptive_rate: bool,
+
+    /// How file names are written into keys
+    pub key_encoding: S3KeyEncoding,
+
+    /// Characters escaped in addition to the defaults with `escape` encoding
+    pub escape_chars: String,
+
+    /// Serve the bucket as it was at this time (None = live)
+    pub as_of: Option<std::time::SystemTime>,
+}
 
 /// Google Drive connector configuration (fully resolved)
 #[derive(Debug, Clone)]
@@ -305,8 +1822,21 @@ pub struct GDriveConnectorConfig {
     /// Authentication configuration
     pub auth: GDriveAuthConfig,
 
-    /// Root folder ID (defaults to "root" for My Drive)
+    /// Root folder ID (defaults to "root" for My Drive, or the shared drive root)
     pub root_folder_id: String,
+
+    /// Shared drive ID (None for My Drive)
+    pub drive_id: Option<String>,
+
+    /// Pass supportsAllDrives/includeItemsFromAllDrives on Drive API calls
+    pub supports_all_drives: bool,
+
+    /// Native Google file kind (document, spreadsheet, ...) -> export extension.
+    /// Kinds without an entry are listed with size 0 and can't be read.
+    pub export_formats: std::collections::HashMap<String, String>,
+
+    /// Drive API endpoint (None = https://www.googleapis.com)
+    pub endpoint: Option<String>,
 }
 
 /// Resolved authentication configuration for Google Drive.
@@ -318,6 +1848,17 @@ pub enum GDriveAuthConfig {
         /// Path to the service account JSON credentials file
         credentials_path: PathBuf,
     },
+    /// Sign in as a user account through an OAuth consent flow
+    Oauth {
+        /// Path to the OAuth client JSON
+        client_secret_path: PathBuf,
+        /// File the tokens are kept in
+        token_path: PathBuf,
+        /// How the user approves access
+        flow: OAuthFlow,
+        /// Local port for the redirect flow (None = random)
+        redirect_port: Option<u16>,
+    },
     /// HTTP-based token provider with arbitrary headers
     Http {
         /// Token endpoint URL
@@ -326,6 +1867,14 @@ pub enum GDriveAuthConfig {
         method: String,
         /// HTTP headers to send with token requests
         headers: std::collections::HashMap<String, String>,
+        /// Response field holding the token (dotted path)
+        token_field: String,
+        /// Response field holding the token lifetime (dotted path)
+        expires_in_field: String,
+        /// Total attempts per token fetch, including the first
+        max_attempts: u32,
+        /// Delay before the first retry
+        initial_backoff: std::time::Duration,
     },
     /// Static access token (for testing)
     Token {
@@ -344,20 +1893,48 @@ impl RawConfig {
         let RawConfig {
             logging,
             error_mode,
+            shutdown_timeout,
+            operation_timeout,
+            rate_limit,
+            control_socket,
+            cache_peers,
+            tracing,
             connectors,
+            include,
             mounts,
         } = self;
 
+        // Includes are followed when loading from files; see `ConfigSource`
+        if !include.is_empty() {
+            return Err(ConfigError::Invalid(vec![ConfigProblem::new(
+                "include",
+                "includes can only be used in config files",
+            )]));
+        }
+
+        let cache_peers = cache_peers
+            .map(|mut peers| -> Result<CachePeersConfig, ConfigError> {
+                peers.token = peers.token.map(|t| substitute_env_vars(&t)).transpose()?;
+                Ok(peers)
+            })
+            .transpose()?;
+
         let mut resolved_mounts = Vec::with_capacity(mounts.len());
 
         for raw_mount in mounts {
-            let resolved = Self::resolve_mount(&connectors, raw_mount, error_mode)?;
+            let resolved =
+                Self::resolve_mount(&connectors, raw_mount, error_mode, operation_timeout)?;
             resolved_mounts.push(resolved);
         }
 
         Ok(Config {
             logging,
             error_mode,
+            shutdown_timeout,
+            rate_limit,
+            control_socket,
+            cache_peers,
+            tracing,
             mounts: resolved_mounts,
         })
     }
@@ -366,6 +1943,7 @@ impl RawConfig {
         connectors: &ConnectorDefaults,
         raw: RawMountConfig,
         global_error_mode: ErrorMode,
+        global_operation_timeout: Option<std::time::Duration>,
     ) -> Result<MountConfig, ConfigError> {
         // Resolve per-mount error_mode with inheritance from global
         let error_mode = raw.error_mode.unwrap_or(global_error_mode);
@@ -373,41 +1951,200 @@ impl RawConfig {
         let read_only = raw.read_only;
         // Pass through status_overlay as-is (already has defaults via serde)
         let status_overlay = raw.status_overlay;
+        let versions_overlay = raw.versions_overlay;
+        let integrity = raw.integrity;
+        let health_check = raw.health_check;
+        let operation_timeout = raw.operation_timeout.or(global_operation_timeout);
+        let rate_limit = raw.rate_limit;
+        let encryption = raw
+            .encryption
+            .map(|mut encryption| -> Result<EncryptionConfig, ConfigError> {
+                encryption.key = encryption
+                    .key
+                    .map(|k| substitute_env_vars(&k))
+                    .transpose()?;
+                Ok(encryption)
+            })
+            .transpose()?;
 
-        match raw.connector {
-            MountConnectorConfig::S3(mount_s3) => {
-                let resolved_connector =
-                    Self::resolve_s3_connector(connectors, mount_s3, &raw.path)?;
-                let cache = Self::resolve_s3_cache(connectors, &raw.cache);
-                Ok(MountConfig {
-                    path: raw.path,
-                    error_mode,
-                    read_only,
-                    uid: raw.uid,
-                    gid: raw.gid,
-                    status_overlay,
-                    connector: ConnectorConfig::S3(resolved_connector),
+        let (connector, mut cache) = match raw.connector {
+            MountConnectorConfig::S3(mount_s3) => (
+                ConnectorConfig::S3(Self::resolve_s3_connector(connectors, mount_s3, &raw.path)?),
+                Self::resolve_s3_cache(connectors, &raw.cache),
+            ),
+            MountConnectorConfig::GDrive(mount_gdrive) => (
+                ConnectorConfig::GDrive(Self::resolve_gdrive_connector(
+                    connectors,
+                    mount_gdrive,
+                    &raw.path,
+                )?),
+                Self::resolve_gdrive_cache(connectors, &raw.cache),
+            ),
+            MountConnectorConfig::Union(mount_union) => {
+                // The cache sits above the whole union, so it defaults from
+                // the upper layer's connector type
+                let cache = match mount_union.layers.first() {
+                    Some(MountConnectorConfig::GDrive(_)) => {
+                        Self::resolve_gdrive_cache(connectors, &raw.cache)
+                    }
+                    _ => Self::resolve_s3_cache(connectors, &raw.cache),
+                };
+                let layers = mount_union
+                    .layers
+                    .into_iter()
+                    .map(|layer| Self::resolve_nested_connector(connectors, layer, &raw.path))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (
+                    ConnectorConfig::Union(UnionConnectorConfig { layers }),
                     cache,
-                })
+                )
             }
-            MountConnectorConfig::GDrive(mount_gdrive) => {
-                let resolved_connector =
-                    Self::resolve_gdrive_connector(connectors, mount_gdrive, &raw.path)?;
-                let cache = Self::resolve_gdrive_cache(connectors, &raw.cache);
-                Ok(MountConfig {
-                    path: raw.path,
-                    error_mode,
-                    read_only,
-                    uid: raw.uid,
-                    gid: raw.gid,
-                    status_overlay,
-                    connector: ConnectorConfig::GDrive(resolved_connector),
-                    cache,
+            MountConnectorConfig::Kv(mount_kv) => (
+                ConnectorConfig::Kv(Self::resolve_kv_connector(mount_kv)?),
+                raw.cache.clone().unwrap_or_default(),
+            ),
+            MountConnectorConfig::OneDrive(mount_onedrive) => (
+                ConnectorConfig::OneDrive(Self::resolve_onedrive_connector(mount_onedrive)?),
+                raw.cache.clone().unwrap_or_default(),
+            ),
+            MountConnectorConfig::Archive(mount_archive) => (
+                ConnectorConfig::Archive(Self::resolve_archive_connector(
+                    connectors,
+                    mount_archive,
+                    &raw.path,
+                )?),
+                raw.cache.clone().unwrap_or_default(),
+            ),
+            MountConnectorConfig::Memfs(memfs) => (
+                ConnectorConfig::Memfs(memfs),
+                raw.cache.clone().unwrap_or_default(),
+            ),
+            MountConnectorConfig::Custom(custom) => (
+                ConnectorConfig::Custom(custom),
+                raw.cache.clone().unwrap_or_default(),
+            ),
+        };
+
+        let routes = raw
+            .routes
+            .into_iter()
+            .map(|route| {
+                Ok(RouteConfig {
+                    path: route.path,
+                    connector: Self::resolve_nested_connector(
+                        connectors,
+                        route.connector,
+                        &raw.path,
+                    )?,
                 })
+            })
+            .collect::<Result<Vec<_>, ConfigError>>()?;
+
+        // Finder files kept local are excluded from sync; rejected ones
+        // are hidden
+        let mut hide_patterns = raw.hide_patterns;
+        if let Some(macos) = &raw.macos {
+            cache.exclude_from_sync(macos.patterns(AppleFileMode::Local));
+            hide_patterns.extend(macos.patterns(AppleFileMode::Reject));
+        }
+
+        // Point-in-time snapshots and archives can't be written to
+        let read_only = read_only
+            || matches!(&connector, ConnectorConfig::S3(s3) if s3.as_of.is_some())
+            || matches!(&connector, ConnectorConfig::Archive(_));
+
+        Ok(MountConfig {
+            path: raw.path,
+            error_mode,
+            read_only,
+            uid: raw.uid,
+            gid: raw.gid,
+            status_overlay,
+            versions_overlay,
+            hide_patterns,
+            integrity,
+            health_check,
+            circuit_breaker: raw.circuit_breaker,
+            read_only_fallback: raw.read_only_fallback,
+            operation_timeout,
+            rate_limit,
+            encryption,
+            compression: raw.compression,
+            quota: raw.quota,
+            trash: raw.trash,
+            audit: raw.audit,
+            access: raw.access,
+            enforce_permissions: raw.enforce_permissions,
+            locking: raw.locking,
+            page_cache: raw.page_cache,
+            unicode_normalization: raw.unicode_normalization,
+            macos: raw.macos,
+            write_buffer: raw.write_buffer,
+            max_write: raw.max_write,
+            max_read: raw.max_read,
+            faults: raw.faults,
+            connector,
+            routes,
+            cache,
+        })
+    }
+
+    /// Resolve a union layer or archive source, which can't be a union or
+    /// archive itself
+    fn resolve_nested_connector(
+        connectors: &ConnectorDefaults,
+        layer: MountConnectorConfig,
+        mount_path: &PathBuf,
+    ) -> Result<ConnectorConfig, ConfigError> {
+        match layer {
+            MountConnectorConfig::S3(mount_s3) => Ok(ConnectorConfig::S3(
+                Self::resolve_s3_connector(connectors, mount_s3, mount_path)?,
+            )),
+            MountConnectorConfig::GDrive(mount_gdrive) => Ok(ConnectorConfig::GDrive(
+                Self::resolve_gdrive_connector(connectors, mount_gdrive, mount_path)?,
+            )),
+            MountConnectorConfig::Kv(mount_kv) => {
+                Ok(ConnectorConfig::Kv(Self::resolve_kv_connector(mount_kv)?))
+            }
+            MountConnectorConfig::OneDrive(mount_onedrive) => Ok(ConnectorConfig::OneDrive(
+                Self::resolve_onedrive_connector(mount_onedrive)?,
+            )),
+            MountConnectorConfig::Memfs(memfs) => Ok(ConnectorConfig::Memfs(memfs)),
+            MountConnectorConfig::Custom(custom) => Ok(ConnectorConfig::Custom(custom)),
+            MountConnectorConfig::Union(_) | MountConnectorConfig::Archive(_) => {
+                Err(ConfigError::ValidationError(format!(
+                    "Mount {:?}: union and archive connectors cannot be nested",
+                    mount_path
+                )))
             }
         }
     }
 
+    fn resolve_archive_connector(
+        connectors: &ConnectorDefaults,
+        mount: ArchiveMountConnectorConfig,
+        mount_path: &PathBuf,
+    ) -> Result<ArchiveConnectorConfig, ConfigError> {
+        let format = mount
+            .format
+            .or_else(|| ArchiveFormat::from_path(&mount.path))
+            .ok_or_else(|| {
+                ConfigError::ValidationError(format!(
+                    "Mount {:?}: can't tell the format of archive {:?}; set `format`",
+                    mount_path, mount.path
+                ))
+            })?;
+        Ok(ArchiveConnectorConfig {
+            path: Path::new("/").join(&mount.path),
+            format,
+            source: Box::new(Self::resolve_nested_connector(
+                connectors,
+                *mount.source,
+                mount_path,
+            )?),
+        })
+    }
+
     fn resolve_s3_connector(
         connectors: &ConnectorDefaults,
         mount: S3MountConnectorConfig,
@@ -453,6 +2190,55 @@ impl RawConfig {
                 .force_path_style
                 .or_else(|| defaults.map(|d| d.force_path_style))
                 .unwrap_or(false),
+            list_metadata: mount
+                .list_metadata
+                .or_else(|| defaults.map(|d| d.list_metadata))
+                .unwrap_or(false),
+            max_concurrent_requests: mount
+                .max_concurrent_requests
+                .or_else(|| defaults.and_then(|d| d.max_concurrent_requests)),
+            max_keys: mount.max_keys.or_else(|| defaults.and_then(|d| d.max_keys)),
+            dir_mtime: mount
+                .dir_mtime
+                .or_else(|| defaults.map(|d| d.dir_mtime))
+                .unwrap_or_default(),
+            directory_markers: mount
+                .directory_markers
+                .or_else(|| defaults.and_then(|d| d.directory_markers))
+                .unwrap_or(true),
+            atomic_rename: mount
+                .atomic_rename
+                .or_else(|| defaults.map(|d| d.atomic_rename))
+                .unwrap_or(false),
+            create_prefix: mount
+                .create_prefix
+                .or_else(|| defaults.map(|d| d.create_prefix))
+                .unwrap_or(false),
+            connect_timeout: mount
+                .connect_timeout
+                .or_else(|| defaults.and_then(|d| d.connect_timeout)),
+            read_timeout: mount
+                .read_timeout
+                .or_else(|| defaults.and_then(|d| d.read_timeout)),
+            max_attempts: mount
+                .max_attempts
+                .or_else(|| defaults.and_then(|d| d.max_attempts)),
+            initial_backoff: mount
+                .initial_backoff
+                .or_else(|| defaults.and_then(|d| d.initial_backoff)),
+            adaptive_rate: mount
+                .adaptive_rate
+                .or_else(|| defaults.and_then(|d| d.adaptive_rate))
+                .unwrap_or(true),
+            key_encoding: mount
+                .key_encoding
+                .or_else(|| defaults.map(|d| d.key_encoding))
+                .unwrap_or_default(),
+            escape_chars: mount
+                .escape_chars
+                .or_else(|| defaults.map(|d| d.escape_chars.clone()))
+                .unwrap_or_default(),
+            as_of: mount.as_of,
         })
     }
 
@@ -493,15 +2279,94 @@ impl RawConfig {
         // Resolve auth with environment variable substitution
         let auth = Self::resolve_gdrive_auth(raw_auth)?;
 
-        // root_folder_id defaults to "root" (My Drive)
+        let drive_id = mount
+            .drive_id
+            .or_else(|| defaults.and_then(|d| d.drive_id.clone()));
+
+        // Shared drives can't be reached without supportsAllDrives
+        let supports_all_drives = drive_id.is_some()
+            || mount
+                .supports_all_drives
+                .or_else(|| defaults.and_then(|d| d.supports_all_drives))
+                .unwrap_or(false);
+
+        // root_folder_id defaults to the shared drive root, or "root" (My Drive)
         let root_folder_id = mount
             .root_folder_id
             .or_else(|| defaults.and_then(|d| d.root_folder_id.clone()))
+            .or_else(|| drive_id.clone())
             .unwrap_or_else(|| "root".to_string());
 
+        let export_formats = mount
+            .export_formats
+            .or_else(|| defaults.and_then(|d| d.export_formats.clone()))
+            .unwrap_or_default();
+
+        let endpoint = mount
+            .endpoint
+            .or_else(|| defaults.and_then(|d| d.endpoint.clone()));
+
         Ok(GDriveConnectorConfig {
             auth,
             root_folder_id,
+            drive_id,
+            supports_all_drives,
+            export_formats,
+            endpoint,
+        })
+    }
+
+    fn resolve_kv_connector(
+        mount: KvMountConnectorConfig,
+    ) -> Result<KvConnectorConfig, ConfigError> {
+        Ok(KvConnectorConfig {
+            backend: mount.backend,
+            path: PathBuf::from(substitute_env_vars(&mount.path)?),
+            prefix: mount.prefix,
+        })
+    }
+
+    fn resolve_onedrive_connector(
+        mount: OneDriveMountConnectorConfig,
+    ) -> Result<OneDriveConnectorConfig, ConfigError> {
+        let auth = match mount.auth {
+            RawOneDriveAuthConfig::ClientCredentials {
+                tenant_id,
+                client_id,
+                client_secret,
+                authority,
+            } => OneDriveAuthConfig::ClientCredentials {
+                authority: authority
+                    .unwrap_or_else(|| microsoft_auth::DEFAULT_AUTHORITY.to_string()),
+                tenant_id,
+                client_id,
+                client_secret: substitute_env_vars(&client_secret)?,
+            },
+            RawOneDriveAuthConfig::DeviceCode {
+                tenant_id,
+                client_id,
+                token_path,
+                authority,
+            } => OneDriveAuthConfig::DeviceCode {
+                authority: authority
+                    .unwrap_or_else(|| microsoft_auth::DEFAULT_AUTHORITY.to_string()),
+                tenant_id,
+                client_id,
+                token_path: PathBuf::from(substitute_env_vars(&token_path)?),
+            },
+        };
+        Ok(OneDriveConnectorConfig {
+            auth,
+            drive_id: mount.drive_id,
+            site_id: mount.site_id,
+            user: mount.user,
+            root: mount
+                .root
+                .map(|root| root.trim_matches('/').to_string())
+                .unwrap_or_default(),
+            endpoint: mount
+                .endpoint
+                .unwrap_or_else(|| crate::connector::onedrive::DEFAULT_ENDPOINT.to_string()),
         })
     }
 
@@ -513,10 +2378,25 @@ impl RawConfig {
                     credentials_path: PathBuf::from(resolved_path),
                 })
             }
+            RawGDriveAuthConfig::Oauth {
+                client_secret_path,
+                token_path,
+                flow,
+                redirect_port,
+            } => Ok(GDriveAuthConfig::Oauth {
+                client_secret_path: PathBuf::from(substitute_env_vars(&client_secret_path)?),
+                token_path: PathBuf::from(substitute_env_vars(&token_path)?),
+                flow,
+                redirect_port,
+            }),
             RawGDriveAuthConfig::Http {
                 endpoint,
                 method,
                 headers,
+                token_field,
+                expires_in_field,
+                max_attempts,
+                initial_backoff,
             } => {
                 let endpoint = substitute_env_vars(&endpoint)?;
                 let method = method.unwrap_or_else(|| "GET".to_string());
@@ -531,6 +2411,12 @@ impl RawConfig {
                     endpoint,
                     method,
                     headers: resolved_headers,
+                    token_field: token_field
+                        .unwrap_or_else(|| http_auth::DEFAULT_TOKEN_FIELD.to_string()),
+                    expires_in_field: expires_in_field
+                        .unwrap_or_else(|| http_auth::DEFAULT_EXPIRES_IN_FIELD.to_string()),
+                    max_attempts: max_attempts.unwrap_or(http_auth::DEFAULT_MAX_ATTEMPTS),
+                    initial_backoff: initial_backoff.unwrap_or(http_auth::DEFAULT_INITIAL_BACKOFF),
                 })
             }
             RawGDriveAuthConfig::Token { access_token } => {
@@ -557,674 +2443,3914 @@ impl RawConfig {
 }
 
 impl Config {
-    /// Load configuration from a YAML file
-    pub fn from_file(path: &PathBuf) -> Result<Self, ConfigError> {
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| ConfigError::ReadError(path.clone(), e.to_string()))?;
+    /// Load configuration from a file, in the format its extension implies
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        Self::parse(&ConfigSource::new(path.to_path_buf()).yaml()?)
+    }
+
+    /// Load and validate configuration from `source`, reporting every
+    /// problem found
+    ///
+    /// Problems are reported with their line and column when they are in a
+    /// YAML file and nothing is overridden; otherwise they are reported by
+    /// key, since the checked document isn't the file as written. When the
+    /// config is split across files, each problem names its file.
+    pub fn load(source: &ConfigSource) -> Result<Self, ConfigError> {
+        let merged = source.merge()?;
+        if let [file] = merged.files.as_slice() {
+            if file.format == ConfigFormat::Yaml && source.overrides.is_empty() {
+                return Self::check(&file.content);
+            }
+        }
 
-        Self::parse(&content)
+        let content = serde_yaml::to_string(&merged.root)
+            .map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        Self::check(&content).map_err(|e| match e {
+            ConfigError::Invalid(problems) => ConfigError::Invalid(
+                problems
+                    .into_iter()
+                    .map(|p| merged.attribute(p, source.overrides.is_empty()))
+                    .collect(),
+            ),
+            e => e,
+        })
     }
 
     /// Parse configuration from a YAML string
+    ///
+    /// If the YAML doesn't match the config schema, every mount entry is
+    /// checked so all of their problems are reported at once.
     pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let raw: RawConfig = match serde_yaml::from_str(content) {
+            Ok(raw) => raw,
+            Err(e) => return Err(ConfigError::Invalid(parse_problems(content, e))),
+        };
+        raw.resolve()
+    }
+
+    /// Parse and validate configuration from a YAML string, reporting every
+    /// problem found with its line and column
+    pub fn check(content: &str) -> Result<Self, ConfigError> {
+        let config = Self::parse(content)?;
+        match config.validate() {
+            Ok(()) => Ok(config),
+            Err(ConfigError::Invalid(problems)) => Err(ConfigError::Invalid(
+                problems
+                    .into_iter()
+                    .map(|problem| problem.located_in(content))
+                    .collect(),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Parse and validate a single mount entry (as it would appear in the
+    /// `mounts` list), resolved against the defaults of the config in
+    /// `content`
+    pub fn parse_mount(content: &str, mount: &str) -> Result<MountConfig, ConfigError> {
         let raw: RawConfig =
             serde_yaml::from_str(content).map_err(|e| ConfigError::ParseError(e.to_string()))?;
-        raw.resolve()
+        let raw_mount: RawMountConfig =
+            serde_yaml::from_str(mount).map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        let resolved = RawConfig::resolve_mount(
+            &raw.connectors,
+            raw_mount,
+            raw.error_mode,
+            raw.operation_timeout,
+        )?;
+
+        // Run the per-mount checks on the new entry alone
+        let config = Config {
+            logging: raw.logging,
+            error_mode: raw.error_mode,
+            shutdown_timeout: raw.shutdown_timeout,
+            rate_limit: None,
+            control_socket: None,
+            cache_peers: None,
+            tracing: TracingConfig::default(),
+            mounts: vec![resolved],
+        };
+        config.validate()?;
+        Ok(config.mounts.into_iter().next().unwrap())
     }
 
-    /// Validate the configuration
+    /// Validate the configuration, reporting every problem found
     pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        self.check_settings(&mut problems);
+        for (i, mount) in self.mounts.iter().enumerate() {
+            Self::check_mount(&format!("mounts[{}]", i), mount, &mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// Check everything but the individual mounts
+    fn check_settings(&self, problems: &mut Vec<ConfigProblem>) {
         if self.mounts.is_empty() {
-            return Err(ConfigError::ValidationError(
-                "At least one mount point is required".to_string(),
+            problems.push(ConfigProblem::new(
+                "mounts",
+                "At least one mount point is required",
             ));
         }
 
         // Check for duplicate mount paths
         let mut paths = std::collections::HashSet::new();
-        for mount in &self.mounts {
+        for (i, mount) in self.mounts.iter().enumerate() {
             if !paths.insert(&mount.path) {
-                return Err(ConfigError::ValidationError(format!(
-                    "Duplicate mount path: {:?}",
-                    mount.path
-                )));
-            }
-        }
-
-        // Validate connector configs
-        for mount in &self.mounts {
-            match &mount.connector {
-                ConnectorConfig::S3(s3) => {
-                    if s3.bucket.is_empty() {
-                        return Err(ConfigError::ValidationError(format!(
-                            "Mount {:?}: S3 bucket cannot be empty",
-                            mount.path
-                        )));
-                    }
-                }
-                ConnectorConfig::GDrive(_) => {
-                    // No validation needed - root_folder_id defaults to "root"
-                }
+                problems.push(ConfigProblem::new(
+                    format!("mounts[{}].path", i),
+                    format!("Duplicate mount path: {:?}", mount.path),
+                ));
             }
         }
 
-        Ok(())
-    }
-}
-
-/// Configuration error types
-#[derive(Debug, thiserror::Error)]
-pub enum ConfigError {
-    #[error("Failed to read config file {0}: {1}")]
-    ReadError(PathBuf, String),
+        if let Err(e) = self.logging.validate() {
+            problems.push(ConfigProblem::new("logging", e));
+        }
 
-    #[error("Failed to parse config: {0}")]
-    ParseError(String),
+        if let Some(rate_limit) = &self.rate_limit {
+            if let Err(e) = rate_limit.validate() {
+                problems.push(ConfigProblem::new("rate_limit", e));
+            }
+        }
 
-    #[error("Configuration validation error: {0}")]
-    ValidationError(String),
-}
+        if let Some(cache_peers) = &self.cache_peers {
+            if let Err(e) = cache_peers.validate() {
+                problems.push(ConfigProblem::new("cache_peers", e));
+            }
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Check one mount entry found at `at` (e.g. `mounts[0]`)
+    fn check_mount(at: &str, mount: &MountConfig, problems: &mut Vec<ConfigProblem>) {
+        let mut problem = |key: &str, message: String| {
+            problems.push(ConfigProblem::new(
+                format!("{}.{}", at, key),
+                format!("Mount {:?}: {}", mount.path, message),
+            ));
+        };
 
-    #[test]
-    fn test_parse_config_backward_compat() {
-        // Test that existing config format still works
-        let yaml = r#"
-logging:
-  level: debug
+        if !mount.path.is_absolute() {
+            problem("path", "mount path must be absolute".to_string());
+        }
 
-mounts:
-  - path: /mnt/data
-    connector:
-      type: s3
-      bucket: my-bucket
-      region: us-east-1
-      prefix: "data/"
-    cache:
-      type: none
-"#;
+        let sections: [(&str, Option<Result<(), String>>); 7] = [
+            (
+                "rate_limit",
+                mount.rate_limit.as_ref().map(|c| c.validate()),
+            ),
+            (
+                "encryption",
+                mount.encryption.as_ref().map(|c| c.validate()),
+            ),
+            (
+                "compression",
+                mount.compression.as_ref().map(|c| c.validate()),
+            ),
+            ("quota", mount.quota.as_ref().map(|c| c.validate())),
+            ("trash", mount.trash.as_ref().map(|c| c.validate())),
+            ("faults", mount.faults.as_ref().map(|c| c.validate())),
+            (
+                "write_buffer",
+                mount.write_buffer.as_ref().map(|c| c.validate()),
+            ),
+        ];
+        for (key, result) in sections {
+            if let Some(Err(e)) = result {
+                problem(key, e);
+            }
+        }
 
-        let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.logging.level, "debug");
-        assert_eq!(config.mounts.len(), 1);
+        for (name, size) in [
+            ("max_write", &mount.max_write),
+            ("max_read", &mount.max_read),
+        ] {
+            if let Some(size) = size {
+                match crate::cache::parse_size(size) {
+                    Some(bytes) if bytes > 0 && bytes <= u32::MAX as u64 => {}
+                    _ => problem(name, format!("invalid {} {:?}", name, size)),
+                }
+            }
+        }
 
-        match &config.mounts[0].connector {
-            ConnectorConfig::S3(s3) => {
-                assert_eq!(s3.bucket, "my-bucket");
-                assert_eq!(s3.region, Some("us-east-1".to_string()));
-                assert_eq!(s3.prefix, Some("data/".to_string()));
+        for (name, size) in mount.cache.size_options() {
+            if crate::cache::parse_size(size).is_none() {
+                problem(
+                    &format!("cache.{}", name),
+                    format!("invalid cache {} {:?}", name, size),
+                );
             }
-            _ => panic!("Expected S3 connector"),
         }
-    }
 
-    #[test]
-    fn test_connector_defaults_with_overrides() {
-        let yaml = r#"
-connectors:
-  s3:
-    bucket: shared-bucket
-    region: us-west-2
-    endpoint: "http://localhost:4566"
-    force_path_style: true
-    cache:
-      type: filesystem
-      path: /tmp/cache/s3
+        if let CacheConfig::Filesystem { path, .. } = &mount.cache {
+            if let Some(e) = cache_dir_problem(Path::new(path)) {
+                problem("cache.path", e);
+            }
+        }
 
-mounts:
-  - path: /mnt/exports
-    connector:
-      type: s3
-      prefix: "exports/"
-  - path: /mnt/imports
-    connector:
-      type: s3
-      prefix: "imports/"
-      region: us-east-1
-    cache:
-      type: memory
-"#;
+        Self::check_connector("connector", &mount.connector, &mut problem);
 
-        let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 2);
+        for (i, route) in mount.routes.iter().enumerate() {
+            if let Err(e) = globset::Glob::new(route.path.trim_start_matches('/')) {
+                problem(
+                    &format!("routes[{}].path", i),
+                    format!("invalid route path '{}': {}", route.path, e),
+                );
+            }
+            Self::check_connector(
+                &format!("routes[{}].connector", i),
+                &route.connector,
+                &mut problem,
+            );
+        }
+        if !mount.routes.is_empty()
+            && matches!(
+                mount.connector,
+                ConnectorConfig::Union(_) | ConnectorConfig::Archive(_)
+            )
+        {
+            problem(
+                "routes",
+                "routes can't be combined with a union or archive connector".to_string(),
+            );
+        }
 
-        // First mount inherits everything from defaults
-        match &config.mounts[0].connector {
-            ConnectorConfig::S3(s3) => {
-                assert_eq!(s3.bucket, "shared-bucket");
-                assert_eq!(s3.region, Some("us-west-2".to_string()));
-                assert_eq!(s3.endpoint, Some("http://localhost:4566".to_string()));
-                assert!(s3.force_path_style);
-                assert_eq!(s3.prefix, Some("exports/".to_string()));
+        for (i, pattern) in mount.hide_patterns.iter().enumerate() {
+            if let Err(e) = globset::Glob::new(pattern) {
+                problem(
+                    &format!("hide_patterns[{}]", i),
+                    format!("invalid hide pattern '{}': {}", pattern, e),
+                );
             }
-            _ => panic!("Expected S3 connector"),
         }
-        // First mount inherits cache from connector defaults
-        match &config.mounts[0].cache {
-            CacheConfig::Filesystem { path, .. } => {
-                assert_eq!(path, "/tmp/cache/s3");
+
+        if let Some(breaker) = &mount.circuit_breaker {
+            if breaker.failure_threshold == 0 {
+                problem(
+                    "circuit_breaker.failure_threshold",
+                    "circuit_breaker failure_threshold must be at least 1".to_string(),
+                );
+            }
+            if breaker.cooldown.is_zero() {
+                problem(
+                    "circuit_breaker.cooldown",
+                    "circuit_breaker cooldown must be greater than zero".to_string(),
+                );
             }
-            _ => panic!("Expected filesystem cache"),
         }
 
-        // Second mount overrides region and cache
-        match &config.mounts[1].connector {
-            ConnectorConfig::S3(s3) => {
-                assert_eq!(s3.bucket, "shared-bucket");
-                assert_eq!(s3.region, Some("us-east-1".to_string())); // overridden
-                assert_eq!(s3.endpoint, Some("http://localhost:4566".to_string()));
-                assert!(s3.force_path_style);
-                assert_eq!(s3.prefix, Some("imports/".to_string()));
+        if let Some(fallback) = &mount.read_only_fallback {
+            if fallback.probe_interval.is_zero() {
+                problem(
+                    "read_only_fallback.probe_interval",
+                    "read_only_fallback probe_interval must be greater than zero".to_string(),
+                );
+            }
+            if !fallback.probe_path.is_absolute() || fallback.probe_path.parent().is_none() {
+                problem(
+                    "read_only_fallback.probe_path",
+                    format!(
+                        "read_only_fallback probe_path {:?} must be an absolute file path",
+                        fallback.probe_path
+                    ),
+                );
+            }
+            if mount.read_only {
+                problem(
+                    "read_only_fallback",
+                    "read_only_fallback has no effect on a read_only mount".to_string(),
+                );
             }
-            _ => panic!("Expected S3 connector"),
         }
-        // Second mount overrides cache
-        match &config.mounts[1].cache {
-            CacheConfig::Memory { .. } => {}
-            _ => panic!("Expected memory cache"),
+
+        if let Some(macos) = &mount.macos {
+            if matches!(mount.cache, CacheConfig::None) {
+                for (name, mode) in [
+                    ("macos.apple_double", macos.apple_double),
+                    ("macos.ds_store", macos.ds_store),
+                ] {
+                    if mode == AppleFileMode::Local {
+                        problem(
+                            name,
+                            format!("{} local needs a memory or filesystem cache", name),
+                        );
+                    }
+                }
+            }
+            if macos
+                .volname
+                .as_ref()
+                .is_some_and(|v| v.is_empty() || v.contains(','))
+            {
+                problem(
+                    "macos.volname",
+                    "macos volname must be non-empty and contain no ','".to_string(),
+                );
+            }
+            if let Some(icon) = &macos.icon {
+                if !icon.is_file() {
+                    problem(
+                        "macos.icon",
+                        format!("macos icon {} is not a file", icon.display()),
+                    );
+                }
+            }
         }
-    }
 
-    #[test]
-    fn test_missing_defaults_error() {
-        let yaml = r#"
-mounts:
-  - path: /mnt/data
-    connector:
-      type: s3
-      prefix: "data/"
-"#;
+        if let Some(access) = &mount.access {
+            for (i, rule) in access.rules.iter().enumerate() {
+                if let Err(e) = globset::Glob::new(&rule.path) {
+                    problem(
+                        &format!("access.rules[{}].path", i),
+                        format!("invalid access rule path '{}': {}", rule.path, e),
+                    );
+                }
+            }
+        }
 
-        let result = Config::parse(yaml);
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(
-            err.to_string().contains("no bucket specified"),
-            "Error should mention missing bucket: {}",
-            err
-        );
+        if let Some(versions) = &mount.versions_overlay {
+            if versions.suffix.is_empty() || versions.suffix.contains('/') {
+                problem(
+                    "versions_overlay.suffix",
+                    "versions_overlay suffix must be non-empty and contain no '/'".to_string(),
+                );
+            }
+            if versions
+                .directory
+                .as_ref()
+                .is_some_and(|d| d.is_empty() || d.contains('/'))
+            {
+                problem(
+                    "versions_overlay.directory",
+                    "versions_overlay directory must be non-empty and contain no '/'".to_string(),
+                );
+            }
+        }
     }
 
-    #[test]
-    fn test_validate_empty_mounts() {
-        let config = Config {
-            logging: LoggingConfig::default(),
-            error_mode: ErrorMode::default(),
-            mounts: vec![],
+    /// Check a connector found at `at` within a mount entry
+    fn check_connector(
+        at: &str,
+        connector: &ConnectorConfig,
+        problem: &mut impl FnMut(&str, String),
+    ) {
+        let key = |field: &str| format!("{}.{}", at, field);
+        match connector {
+            ConnectorConfig::S3(s3) => {
+                if s3.bucket.is_empty() {
+                    problem(&key("bucket"), "S3 bucket cannot be empty".to_string());
+                }
+                if s3.max_concurrent_requests == Some(0) {
+                    problem(
+                        &key("max_concurrent_requests"),
+                        "max_concurrent_requests must be at least 1".to_string(),
+                    );
+                }
+                if s3.max_keys.is_some_and(|n| !(1..=1000).contains(&n)) {
+                    problem(
+                        &key("max_keys"),
+                        "max_keys must be between 1 and 1000".to_string(),
+                    );
+                }
+                if s3.max_attempts == Some(0) {
+                    problem(
+                        &key("max_attempts"),
+                        "max_attempts must be at least 1".to_string(),
+                    );
+                }
+            }
+            ConnectorConfig::GDrive(gdrive) => {
+                if let GDriveAuthConfig::Oauth {
+                    flow: OAuthFlow::Device,
+                    redirect_port: Some(_),
+                    ..
+                } = &gdrive.auth
+                {
+                    problem(
+                        &key("auth.redirect_port"),
+                        "redirect_port only applies to the redirect flow".to_string(),
+                    );
+                }
+                if let GDriveAuthConfig::Http {
+                    token_field,
+                    expires_in_field,
+                    max_attempts,
+                    ..
+                } = &gdrive.auth
+                {
+                    if *max_attempts == 0 {
+                        problem(
+                            &key("auth.max_attempts"),
+                            "max_attempts must be at least 1".to_string(),
+                        );
+                    }
+                    for (name, field) in [
+                        ("token_field", token_field),
+                        ("expires_in_field", expires_in_field),
+                    ] {
+                        if field.split('.').any(str::is_empty) {
+                            problem(
+                                &key(&format!("auth.{}", name)),
+                                format!("{} must be a field name, got {:?}", name, field),
+                            );
+                        }
+                    }
+                }
+                for (kind, extension) in &gdrive.export_formats {
+                    if crate::connector::gdrive::export_mime_type(kind, extension).is_none() {
+                        problem(
+                            &key(&format!("export_formats.{}", kind)),
+                            format!("unsupported GDrive export format {}: {}", kind, extension),
+                        );
+                    }
+                }
+            }
+            ConnectorConfig::Union(union) => {
+                if union.layers.len() < 2 {
+                    problem(
+                        &key("layers"),
+                        "union connector needs at least two layers".to_string(),
+                    );
+                }
+                for (i, layer) in union.layers.iter().enumerate() {
+                    Self::check_connector(&key(&format!("layers[{}]", i)), layer, problem);
+                }
+            }
+            ConnectorConfig::Archive(archive) => {
+                if archive.path == Path::new("/") {
+                    problem(&key("path"), "archive path cannot be empty".to_string());
+                }
+                Self::check_connector(&key("source"), &archive.source, problem);
+            }
+            ConnectorConfig::Memfs(memfs) => {
+                if let Some(size) = &memfs.max_size {
+                    if crate::cache::parse_size(size).is_none() {
+                        problem(
+                            &key("max_size"),
+                            format!("invalid memfs max_size {:?}", size),
+                        );
+                    }
+                }
+            }
+            ConnectorConfig::Kv(kv) => {
+                if kv.path.as_os_str().is_empty() {
+                    problem(&key("path"), "kv store path cannot be empty".to_string());
+                }
+            }
+            ConnectorConfig::OneDrive(onedrive) => {
+                let drives = [
+                    ("drive_id", &onedrive.drive_id),
+                    ("site_id", &onedrive.site_id),
+                    ("user", &onedrive.user),
+                ];
+                let set: Vec<&str> = drives
+                    .iter()
+                    .filter(|(_, value)| value.is_some())
+                    .map(|(name, _)| *name)
+                    .collect();
+                if set.len() > 1 {
+                    problem(
+                        &key(set[1]),
+                        format!(
+                            "only one of drive_id, site_id and user can be set, got {}",
+                            set.join(" and ")
+                        ),
+                    );
+                }
+                // An application has no drive of its own
+                if set.is_empty()
+                    && matches!(onedrive.auth, OneDriveAuthConfig::ClientCredentials { .. })
+                {
+                    problem(
+                        &key("auth"),
+                        "client_credentials auth needs drive_id, site_id or user".to_string(),
+                    );
+                }
+            }
+            ConnectorConfig::Custom(custom) => {
+                if !registry::is_registered(&custom.type_name) {
+                    problem(
+                        &key("type"),
+                        format!("unknown connector type {:?}", custom.type_name),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Sources
+// =============================================================================
+
+/// Config file formats; all of them describe the same settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// The format a file's extension implies: JSON for `.json`, TOML for
+    /// `.toml` and YAML for anything else
+    pub fn from_path(path: &Path) -> Self {
+        Self::from_extension(path).unwrap_or_default()
+    }
+
+    /// The format of a file with a config extension, if it has one
+    fn from_extension(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "yaml" | "yml" => Some(Self::Yaml),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Parse a document in this format
+    fn read(self, content: &str) -> Result<serde_yaml::Value, ConfigProblem> {
+        match self {
+            Self::Yaml => serde_yaml::from_str(content).map_err(yaml_problem),
+            Self::Json => serde_json::from_str(content).map_err(|e| {
+                let suffix = format!(" at line {} column {}", e.line(), e.column());
+                let message = e.to_string();
+                ConfigProblem {
+                    path: String::new(),
+                    message: message
+                        .strip_suffix(&suffix)
+                        .unwrap_or(&message)
+                        .to_string(),
+                    location: Some((e.line(), e.column())),
+                    file: None,
+                }
+            }),
+            Self::Toml => toml::from_str(content).map_err(|e| ConfigProblem {
+                path: String::new(),
+                message: e.message().to_string(),
+                location: e.span().map(|span| line_column(content, span.start)),
+                file: None,
+            }),
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => Err(format!(
+                "unknown config format {:?} (yaml, json or toml)",
+                s
+            )),
+        }
+    }
+}
+
+/// Where configuration comes from: a file, its format and the overrides
+/// applied on top of it
+///
+/// The file can pull in others with `include`, and the path can also be a
+/// directory, meaning every config file in it. The files are merged into
+/// one document: their `mounts` lists are joined, and other settings may
+/// be split between files but not set differently in two of them.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    /// A config file, or a directory of them
+    pub path: PathBuf,
+    /// Format of the file at `path`; included files and files in a
+    /// directory go by their extension
+    pub format: ConfigFormat,
+    /// Applied in order, so later ones win
+    pub overrides: Vec<ConfigOverride>,
+}
+
+impl ConfigSource {
+    /// `path` in the format its extension implies, with nothing overridden
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            format: ConfigFormat::from_path(&path),
+            path,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Read the configuration as one YAML document with the overrides
+    /// applied
+    ///
+    /// A single YAML file with nothing overridden is returned as written,
+    /// so problems found in it can be located.
+    pub fn yaml(&self) -> Result<String, ConfigError> {
+        let merged = self.merge()?;
+        if let [file] = merged.files.as_slice() {
+            if file.format == ConfigFormat::Yaml && self.overrides.is_empty() {
+                return Ok(file.content.clone());
+            }
+        }
+        serde_yaml::to_string(&merged.root).map_err(|e| ConfigError::ParseError(e.to_string()))
+    }
+
+    /// Read every file, check each against the config schema and merge
+    /// them, then apply the overrides
+    fn merge(&self) -> Result<Merged, ConfigError> {
+        let mut files = Vec::new();
+        let mut problems = Vec::new();
+        let format = (!self.path.is_dir()).then_some(self.format);
+        read_config_files(&self.path, format, &mut files, &mut problems)
+            .map_err(|e| ConfigError::ReadError(self.path.clone(), e.to_string()))?;
+        for file in &files {
+            problems.extend(file.schema_problems());
+        }
+
+        let mut merged = Merged {
+            root: serde_yaml::Value::Null,
+            mounts: Vec::new(),
+            settings: Vec::new(),
+            files,
+        };
+        if problems.is_empty() {
+            problems = merged.merge_files();
+        }
+        if problems.is_empty() {
+            problems = self
+                .overrides
+                .iter()
+                .filter_map(|o| {
+                    o.apply(&mut merged.root).err().map(|e| {
+                        ConfigProblem::new(
+                            o.key.clone(),
+                            format!("can't override {}: {}", o.key, e),
+                        )
+                    })
+                })
+                .collect();
+        }
+
+        if problems.is_empty() {
+            Ok(merged)
+        } else {
+            if merged.files.len() <= 1 {
+                for problem in &mut problems {
+                    problem.file = None;
+                }
+            }
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+/// One file configuration is read from
+#[derive(Debug)]
+struct ConfigFile {
+    path: PathBuf,
+    format: ConfigFormat,
+    content: String,
+    /// The parsed document, without its `include` key
+    root: serde_yaml::Value,
+}
+
+impl ConfigFile {
+    /// Problems with the file's structure on its own: unknown keys and
+    /// values of the wrong type
+    fn schema_problems(&self) -> Vec<ConfigProblem> {
+        let yaml = match self.format {
+            ConfigFormat::Yaml => self.content.clone(),
+            _ => serde_yaml::to_string(&self.root).unwrap_or_default(),
+        };
+        match serde_yaml::from_str::<RawConfig>(&yaml) {
+            Ok(_) => Vec::new(),
+            Err(e) => parse_problems(&yaml, e)
+                .into_iter()
+                .map(|mut problem| {
+                    // Positions in a converted document mean nothing to the user
+                    if self.format != ConfigFormat::Yaml {
+                        problem.location = None;
+                    }
+                    problem.file = Some(self.path.clone());
+                    problem
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Read the config file at `path`, or every config file in it if it's a
+/// directory, along with the files each one includes
+///
+/// Only failing to read `path` itself is an error; every other problem is
+/// collected.
+fn read_config_files(
+    path: &Path,
+    format: Option<ConfigFormat>,
+    files: &mut Vec<ConfigFile>,
+    problems: &mut Vec<ConfigProblem>,
+) -> std::io::Result<()> {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(path)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|p| p.is_file() && ConfigFormat::from_extension(p).is_some())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            if let Err(e) = read_config_files(&entry, None, files, problems) {
+                problems.push(ConfigProblem {
+                    file: Some(entry),
+                    ..ConfigProblem::new("", format!("can't read file: {}", e))
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    let canonical = std::fs::canonicalize(path)?;
+    if files
+        .iter()
+        .any(|f| std::fs::canonicalize(&f.path).is_ok_and(|p| p == canonical))
+    {
+        problems.push(ConfigProblem {
+            file: Some(path.to_path_buf()),
+            ..ConfigProblem::new("", "file is included more than once")
+        });
+        return Ok(());
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+    let mut root = match format.read(&content) {
+        Ok(root) => root,
+        Err(problem) => {
+            problems.push(ConfigProblem {
+                file: Some(path.to_path_buf()),
+                ..problem
+            });
+            return Ok(());
+        }
+    };
+
+    // Anything but a list of paths is left for the schema check to report
+    let includes: Vec<PathBuf> = root
+        .as_mapping_mut()
+        .and_then(|map| map.remove("include"))
+        .and_then(|include| serde_yaml::from_value(include).ok())
+        .unwrap_or_default();
+    let index = files.len();
+    files.push(ConfigFile {
+        path: path.to_path_buf(),
+        format,
+        content,
+        root,
+    });
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for (i, include) in includes.iter().enumerate() {
+        let target = dir.join(include);
+        if let Err(e) = read_config_files(&target, None, files, problems) {
+            let file = &files[index];
+            let mut problem = ConfigProblem::new(
+                format!("include[{}]", i),
+                format!("can't read {:?}: {}", target, e),
+            );
+            if file.format == ConfigFormat::Yaml {
+                problem = problem.located_in(&file.content);
+            }
+            problem.file = Some(file.path.clone());
+            problems.push(problem);
+        }
+    }
+    Ok(())
+}
+
+/// Config files merged into one document
+struct Merged {
+    files: Vec<ConfigFile>,
+    root: serde_yaml::Value,
+    /// File, and index within it, of each entry in the merged `mounts`
+    mounts: Vec<(usize, usize)>,
+    /// File each setting came from, by dotted key path
+    settings: Vec<(String, usize)>,
+}
+
+impl Merged {
+    /// Merge the files into `root`, returning settings set differently in
+    /// two of them
+    fn merge_files(&mut self) -> Vec<ConfigProblem> {
+        use serde_yaml::{Mapping, Value};
+
+        let mut root = Mapping::new();
+        let mut mounts = Vec::new();
+        let mut conflicts = Vec::new();
+        for (index, file) in self.files.iter().enumerate() {
+            let Value::Mapping(map) = file.root.clone() else {
+                continue;
+            };
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    (Some("mounts"), Value::Sequence(entries)) => {
+                        for (i, entry) in entries.into_iter().enumerate() {
+                            self.mounts.push((index, i));
+                            mounts.push(entry);
+                        }
+                    }
+                    (Some(name), value) => {
+                        let at = name.to_string();
+                        let mut merge = Merge {
+                            file: index,
+                            settings: &mut self.settings,
+                            conflicts: &mut conflicts,
+                        };
+                        merge.setting(&mut root, key, value, at);
+                    }
+                    (None, _) => {}
+                }
+            }
+        }
+        root.insert(Value::from("mounts"), Value::Sequence(mounts));
+        self.root = Value::Mapping(root);
+
+        conflicts
+            .into_iter()
+            .map(|(at, index)| {
+                let earlier = self.setting_file(&at).map(|f| &self.files[f].path);
+                let problem = ConfigProblem::new(
+                    at.as_str(),
+                    match earlier {
+                        Some(path) => format!("{} is already set in {}", at, path.display()),
+                        None => format!("{} is already set", at),
+                    },
+                );
+                self.in_file(problem, index, true)
+            })
+            .collect()
+    }
+
+    /// The file that set the value at `at`
+    fn setting_file(&self, at: &str) -> Option<usize> {
+        self.settings
+            .iter()
+            .find(|(path, _)| {
+                at.strip_prefix(path.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .map(|(_, file)| *file)
+    }
+
+    /// Point a problem found in the merged document at the file it's in
+    fn attribute(&self, mut problem: ConfigProblem, locate: bool) -> ConfigProblem {
+        problem.location = None;
+        let keys = parse_key_path(&problem.path);
+        let index = match keys.as_slice() {
+            [ConfigKey::Field(field), ConfigKey::Index(i), ..] if field == "mounts" => {
+                self.mounts.get(*i).map(|&(file, local)| {
+                    let rest = &problem.path[format!("mounts[{}]", i).len()..];
+                    problem.path = format!("mounts[{}]{}", local, rest);
+                    file
+                })
+            }
+            _ => self.setting_file(&problem.path),
         };
+        match index {
+            Some(index) => self.in_file(problem, index, locate),
+            None => problem,
+        }
+    }
+
+    /// Place a problem in one of the files, locating it there if it's YAML
+    fn in_file(&self, mut problem: ConfigProblem, index: usize, locate: bool) -> ConfigProblem {
+        let file = &self.files[index];
+        if locate && file.format == ConfigFormat::Yaml {
+            problem = problem.located_in(&file.content);
+        }
+        if self.files.len() > 1 {
+            problem.file = Some(file.path.clone());
+        }
+        problem
+    }
+}
+
+/// Merging one file's settings into the document
+struct Merge<'a> {
+    file: usize,
+    settings: &'a mut Vec<(String, usize)>,
+    /// Settings already set to something else, with the file setting them
+    conflicts: &'a mut Vec<(String, usize)>,
+}
+
+impl Merge<'_> {
+    /// Merge `value` into `map` at `key`, whose path is `at`; mappings are
+    /// merged key by key, and anything else may only be set once (or to
+    /// the same value again)
+    fn setting(
+        &mut self,
+        map: &mut serde_yaml::Mapping,
+        key: serde_yaml::Value,
+        value: serde_yaml::Value,
+        at: String,
+    ) {
+        use serde_yaml::Value;
+
+        match (map.get_mut(&key), value) {
+            (None, value) => {
+                map.insert(key, value);
+                self.settings.push((at, self.file));
+            }
+            (Some(Value::Mapping(existing)), Value::Mapping(values)) => {
+                for (key, value) in values {
+                    let at = format!("{}.{}", at, key.as_str().unwrap_or_default());
+                    self.setting(existing, key, value, at);
+                }
+            }
+            (Some(existing), value) if *existing == value => {}
+            (Some(_), _) => self.conflicts.push((at, self.file)),
+        }
+    }
+}
+
+/// 1-based line and column of the byte at `offset` in `content`
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+// =============================================================================
+// Overrides
+// =============================================================================
+
+/// Prefix of environment variables that override config values
+pub const ENV_OVERRIDE_PREFIX: &str = "FUSE_ADAPTER__";
+
+/// A config value set from outside the config file
+///
+/// Setting a section's `type` to something else replaces the whole section,
+/// since the old type's options rarely apply to the new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigOverride {
+    /// Dotted path to the value, where numbers index into lists (e.g.
+    /// `mounts.0.cache.type`; `mounts[0].cache.type` also works)
+    pub key: String,
+    /// The new value, read as YAML so `30s`, `true` and `[a, b]` work
+    pub value: String,
+}
+
+impl ConfigOverride {
+    /// Overrides from environment variables named like
+    /// `FUSE_ADAPTER__MOUNTS__0__CACHE__TYPE`, with `__` between keys
+    pub fn from_env() -> Vec<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Overrides from `(name, value)` variables, ordered so a section's
+    /// `type` is set before its other options
+    fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Vec<Self> {
+        let mut overrides: Vec<Self> = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix(ENV_OVERRIDE_PREFIX)?;
+                Some(Self {
+                    key: key.split("__").collect::<Vec<_>>().join(".").to_lowercase(),
+                    value,
+                })
+            })
+            .collect();
+        overrides.sort_by_cached_key(|o| {
+            o.keys()
+                .into_iter()
+                .map(|k| if k == "type" { String::new() } else { k })
+                .collect::<Vec<_>>()
+        });
+        overrides
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.key
+            .replace('[', ".")
+            .replace(']', "")
+            .split('.')
+            .filter(|k| !k.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Set the value in the parsed config file
+    fn apply(&self, root: &mut serde_yaml::Value) -> Result<(), String> {
+        use serde_yaml::Value;
+
+        let keys = self.keys();
+        let (last, parents) = keys.split_last().ok_or("empty key")?;
+        let mut node = root;
+        for key in parents {
+            node = child(node, key)?;
+        }
+
+        let value =
+            serde_yaml::from_str(&self.value).unwrap_or_else(|_| Value::String(self.value.clone()));
+        if let Value::Mapping(map) = node {
+            if last == "type" && map.get("type").is_some_and(|t| *t != value) {
+                map.clear();
+            }
+        }
+        *child(node, last)? = value;
+        Ok(())
+    }
+}
+
+/// The value at `key` in a mapping, or at index `key` in a list, created
+/// if missing; an index one past the end appends to the list
+fn child<'a>(
+    node: &'a mut serde_yaml::Value,
+    key: &str,
+) -> Result<&'a mut serde_yaml::Value, String> {
+    use serde_yaml::Value;
+
+    if node.is_null() {
+        *node = Value::Mapping(Default::default());
+    }
+    match node {
+        Value::Mapping(map) => Ok(map
+            .entry(Value::String(key.to_string()))
+            .or_insert(Value::Null)),
+        Value::Sequence(items) => {
+            let index: usize = key
+                .parse()
+                .map_err(|_| format!("{:?} is not a list index", key))?;
+            if index == items.len() {
+                items.push(Value::Null);
+            }
+            let len = items.len();
+            items
+                .get_mut(index)
+                .ok_or_else(|| format!("index {} is past the end of a list of {}", index, len))
+        }
+        _ => Err(format!("{:?} is inside a plain value", key)),
+    }
+}
+
+impl std::str::FromStr for ConfigOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Ok(Self {
+                key: key.trim().to_string(),
+                value: value.to_string(),
+            }),
+            _ => Err(format!("expected KEY=VALUE, got {:?}", s)),
+        }
+    }
+}
+
+/// Why a filesystem cache can't use `path`, if it can't: it must be a
+/// directory we can read and write, or be creatable
+fn cache_dir_problem(path: &Path) -> Option<String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors().find(|p| p.exists())?;
+    if !existing.is_dir() {
+        return Some(format!("cache path {:?} is not a directory", existing));
+    }
+    let mode = if existing == path {
+        libc::R_OK | libc::W_OK | libc::X_OK
+    } else {
+        libc::W_OK | libc::X_OK
+    };
+    let c_path = std::ffi::CString::new(existing.as_os_str().as_bytes()).ok()?;
+    // SAFETY: c_path is a valid NUL-terminated string
+    if unsafe { libc::access(c_path.as_ptr(), mode) } != 0 {
+        return Some(format!(
+            "cache directory {:?} is not accessible: {}",
+            existing,
+            std::io::Error::last_os_error()
+        ));
+    }
+    None
+}
+
+// =============================================================================
+// Problem Reporting
+// =============================================================================
+
+/// One problem with a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Where the problem is, e.g. `mounts[0].cache.max_size` (empty when
+    /// the message already says)
+    pub path: String,
+    pub message: String,
+    /// Line and column (both from 1) in the YAML, when known
+    pub location: Option<(usize, usize)>,
+    /// File the problem is in, when the config is split across several
+    pub file: Option<PathBuf>,
+}
+
+impl ConfigProblem {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            location: None,
+            file: None,
+        }
+    }
+
+    /// Find where the problem is in the YAML it came from; a value that
+    /// isn't in the file (e.g. one inherited from connector defaults) is
+    /// located at its closest enclosing value that is
+    fn located_in(mut self, content: &str) -> Self {
+        let keys = parse_key_path(&self.path);
+        self.location = (0..=keys.len())
+            .rev()
+            .find_map(|len| locate(content, &keys[..len]));
+        self
+    }
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file.display())?;
+        }
+        match self.location {
+            Some((line, column)) => write!(f, "line {}, column {}: ", line, column)?,
+            None if !self.path.is_empty() => write!(f, "{}: ", self.path)?,
+            None => {}
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// One step in a path through the YAML
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ConfigKey {
+    Field(String),
+    Index(usize),
+}
+
+/// Split a path like `mounts[0].cache.max_size` into its steps
+fn parse_key_path(path: &str) -> Vec<ConfigKey> {
+    let mut keys = Vec::new();
+    for part in path.split('.').filter(|p| !p.is_empty()) {
+        let mut pieces = part.split('[');
+        if let Some(field) = pieces.next().filter(|f| !f.is_empty()) {
+            keys.push(ConfigKey::Field(field.to_string()));
+        }
+        for index in pieces {
+            if let Ok(i) = index.trim_end_matches(']').parse() {
+                keys.push(ConfigKey::Index(i));
+            }
+        }
+    }
+    keys
+}
+
+/// Everything wrong with `content` that `error` (from deserializing the
+/// whole file) is the first of
+///
+/// Deserialization stops at the first error, so each mount entry is
+/// deserialized again on its own to find problems in the others too.
+fn parse_problems(content: &str, error: serde_yaml::Error) -> Vec<ConfigProblem> {
+    let mut problems = vec![yaml_problem(error)];
+    let mounts = serde_yaml::from_str::<serde_yaml::Value>(content)
+        .ok()
+        .and_then(|v| v.get("mounts")?.as_sequence().map(|s| s.len()))
+        .unwrap_or(0);
+    for i in 0..mounts {
+        let keys = [ConfigKey::Field("mounts".to_string()), ConfigKey::Index(i)];
+        let probe = Probe::<RawMountConfig>::new(&keys);
+        if let Err(e) = probe.deserialize(serde_yaml::Deserializer::from_str(content)) {
+            let problem = yaml_problem(e);
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+    problems.sort_by_key(|p| p.location);
+    problems
+}
+
+/// A serde_yaml error as a problem, its location split out of the message
+fn yaml_problem(error: serde_yaml::Error) -> ConfigProblem {
+    let location = error.location().map(|l| (l.line(), l.column()));
+    let mut message = error.to_string();
+    if let Some((line, column)) = location {
+        let suffix = format!(" at line {} column {}", line, column);
+        if let Some(stripped) = message.strip_suffix(&suffix) {
+            message = stripped.to_string();
+        }
+    }
+    ConfigProblem {
+        path: String::new(),
+        message,
+        location,
+        file: None,
+    }
+}
+
+/// Line and column of the value at `keys` in `content`, if it's there
+fn locate(content: &str, keys: &[ConfigKey]) -> Option<(usize, usize)> {
+    let probe = Probe::<Here>::new(keys);
+    match probe.deserialize(serde_yaml::Deserializer::from_str(content)) {
+        Err(e) if e.to_string().contains(Here::MARKER) => {
+            e.location().map(|l| (l.line(), l.column()))
+        }
+        _ => None,
+    }
+}
+
+/// Deserializes the value at a path as `T`, skipping everything else
+struct Probe<'a, T> {
+    keys: &'a [ConfigKey],
+    target: std::marker::PhantomData<T>,
+}
+
+impl<'a, T> Probe<'a, T> {
+    fn new(keys: &'a [ConfigKey]) -> Self {
+        Self {
+            keys,
+            target: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> serde::de::DeserializeSeed<'de> for Probe<'_, T> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        if self.keys.is_empty() {
+            T::deserialize(deserializer).map(drop)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for Probe<'_, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let (first, rest) = self.keys.split_first().expect("probe path is not empty");
+        while let Some(key) = map.next_key::<serde_yaml::Value>()? {
+            match first {
+                ConfigKey::Field(name) if key.as_str() == Some(name) => {
+                    map.next_value_seed(Probe::<T>::new(rest))?
+                }
+                _ => map.next_value::<serde::de::IgnoredAny>().map(drop)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let (first, rest) = self.keys.split_first().expect("probe path is not empty");
+        for i in 0.. {
+            let more = if *first == ConfigKey::Index(i) {
+                seq.next_element_seed(Probe::<T>::new(rest))?.is_some()
+            } else {
+                seq.next_element::<serde::de::IgnoredAny>()?.is_some()
+            };
+            if !more {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // The path leads through a scalar, so the value isn't there
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Fails as soon as it's deserialized, so the error carries the position
+/// of the value it was asked to read
+struct Here;
+
+impl Here {
+    const MARKER: &'static str = "value located";
+}
+
+impl<'de> Deserialize<'de> for Here {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(Here)
+    }
+}
+
+// Accepts nothing, so whatever the value is fails as an invalid type
+impl serde::de::Visitor<'_> for Here {
+    type Value = Here;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(Self::MARKER)
+    }
+}
+
+/// Configuration error types
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {0}: {1}")]
+    ReadError(PathBuf, String),
+
+    #[error("Failed to parse config: {0}")]
+    ParseError(String),
+
+    #[error("Configuration validation error: {0}")]
+    ValidationError(String),
+
+    #[error("{}", format_problems(.0))]
+    Invalid(Vec<ConfigProblem>),
+}
+
+fn format_problems(problems: &[ConfigProblem]) -> String {
+    let mut out = String::from("Invalid configuration:");
+    for problem in problems {
+        out.push_str("\n  ");
+        out.push_str(&problem.to_string());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::RevalidationMode;
+
+    #[test]
+    fn test_parse_config_backward_compat() {
+        // Test that existing config format still works
+        let yaml = r#"
+logging:
+  level: debug
+
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+      region: us-east-1
+      prefix: "data/"
+    cache:
+      type: none
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.logging.level, "debug");
+        assert_eq!(config.mounts.len(), 1);
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "my-bucket");
+                assert_eq!(s3.region, Some("us-east-1".to_string()));
+                assert_eq!(s3.prefix, Some("data/".to_string()));
+            }
+            _ => panic!("Expected S3 connector"),
+        }
+    }
+
+    #[test]
+    fn test_connector_defaults_with_overrides() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared-bucket
+    region: us-west-2
+    endpoint: "http://localhost:4566"
+    force_path_style: true
+    cache:
+      type: filesystem
+      path: /tmp/cache/s3
+
+mounts:
+  - path: /mnt/exports
+    connector:
+      type: s3
+      prefix: "exports/"
+  - path: /mnt/imports
+    connector:
+      type: s3
+      prefix: "imports/"
+      region: us-east-1
+    cache:
+      type: memory
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 2);
+
+        // First mount inherits everything from defaults
+        match &config.mounts[0].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "shared-bucket");
+                assert_eq!(s3.region, Some("us-west-2".to_string()));
+                assert_eq!(s3.endpoint, Some("http://localhost:4566".to_string()));
+                assert!(s3.force_path_style);
+                assert_eq!(s3.prefix, Some("exports/".to_string()));
+            }
+            _ => panic!("Expected S3 connector"),
+        }
+        // First mount inherits cache from connector defaults
+        match &config.mounts[0].cache {
+            CacheConfig::Filesystem { path, .. } => {
+                assert_eq!(path, "/tmp/cache/s3");
+            }
+            _ => panic!("Expected filesystem cache"),
+        }
+
+        // Second mount overrides region and cache
+        match &config.mounts[1].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "shared-bucket");
+                assert_eq!(s3.region, Some("us-east-1".to_string())); // overridden
+                assert_eq!(s3.endpoint, Some("http://localhost:4566".to_string()));
+                assert!(s3.force_path_style);
+                assert_eq!(s3.prefix, Some("imports/".to_string()));
+            }
+            _ => panic!("Expected S3 connector"),
+        }
+        // Second mount overrides cache
+        match &config.mounts[1].cache {
+            CacheConfig::Memory { .. } => {}
+            _ => panic!("Expected memory cache"),
+        }
+    }
+
+    #[test]
+    fn test_missing_defaults_error() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      prefix: "data/"
+"#;
+
+        let result = Config::parse(yaml);
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains("no bucket specified"),
+            "Error should mention missing bucket: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_validate_empty_mounts() {
+        let config = Config {
+            logging: LoggingConfig::default(),
+            error_mode: ErrorMode::default(),
+            shutdown_timeout: default_shutdown_timeout(),
+            rate_limit: None,
+            control_socket: None,
+            cache_peers: None,
+            tracing: TracingConfig::default(),
+            mounts: vec![],
+        };
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_mount_uses_defaults() {
+        let config = r#"
+control_socket: /run/fuse-adapter.sock
+connectors:
+  s3:
+    bucket: default-bucket
+    region: eu-west-1
+mounts: []
+"#;
+        let parsed = Config::parse(config).unwrap();
+        assert_eq!(
+            parsed.control_socket,
+            Some(PathBuf::from("/run/fuse-adapter.sock"))
+        );
+
+        let mount = Config::parse_mount(
+            config,
+            r#"
+path: /mnt/new
+connector:
+  type: s3
+  bucket: new-bucket
+"#,
+        )
+        .unwrap();
+        assert_eq!(mount.path, PathBuf::from("/mnt/new"));
+        match &mount.connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "new-bucket");
+                assert_eq!(s3.region.as_deref(), Some("eu-west-1"));
+            }
+            _ => panic!("expected S3 connector"),
+        }
+
+        // Per-mount validation still applies
+        let invalid = r#"
+path: /mnt/new
+connector:
+  type: s3
+  bucket: new-bucket
+write_buffer:
+  size: "0"
+"#;
+        assert!(Config::parse_mount(config, invalid).is_err());
+    }
+
+    #[test]
+    fn test_kv_connector() {
+        std::env::set_var("TEST_KV_DIR", "/var/lib/kv");
+        let yaml = r#"
+mounts:
+  - path: /mnt/settings
+    connector:
+      type: kv
+      path: ${TEST_KV_DIR}/settings.db
+      prefix: app/
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::Kv(kv) => {
+                assert_eq!(kv.backend, KvBackend::Sled);
+                assert_eq!(kv.path, PathBuf::from("/var/lib/kv/settings.db"));
+                assert_eq!(kv.prefix.as_deref(), Some("app/"));
+            }
+            _ => panic!("expected kv connector"),
+        }
+        assert!(matches!(config.mounts[0].cache, CacheConfig::None));
+
+        let unknown_backend = yaml.replace("type: kv", "type: kv\n      backend: etcd");
+        assert!(Config::parse(&unknown_backend).is_err());
+    }
+
+    #[test]
+    fn test_onedrive_connector() {
+        std::env::set_var("TEST_ONEDRIVE_SECRET", "s3cret");
+        let yaml = r#"
+mounts:
+  - path: /mnt/sharepoint
+    connector:
+      type: onedrive
+      site_id: contoso.sharepoint.com:/sites/engineering
+      root: /Shared Documents/Specs/
+      auth:
+        type: client_credentials
+        tenant_id: contoso.onmicrosoft.com
+        client_id: 00000000-0000-0000-0000-000000000001
+        client_secret: ${TEST_ONEDRIVE_SECRET}
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::OneDrive(onedrive) => {
+                assert_eq!(
+                    onedrive.site_id.as_deref(),
+                    Some("contoso.sharepoint.com:/sites/engineering")
+                );
+                assert_eq!(onedrive.root, "Shared Documents/Specs");
+                assert_eq!(onedrive.endpoint, "https://graph.microsoft.com/v1.0");
+                match &onedrive.auth {
+                    OneDriveAuthConfig::ClientCredentials {
+                        authority,
+                        client_secret,
+                        ..
+                    } => {
+                        assert_eq!(authority, "https://login.microsoftonline.com");
+                        assert_eq!(client_secret, "s3cret");
+                    }
+                    _ => panic!("expected client credentials"),
+                }
+            }
+            _ => panic!("expected onedrive connector"),
+        }
+
+        // An application has no drive of its own to fall back to
+        let no_drive = yaml.replace(
+            "      site_id: contoso.sharepoint.com:/sites/engineering\n",
+            "",
+        );
+        let found = problems(Config::check(&no_drive));
+        assert_eq!(found[0].path, "mounts[0].connector.auth");
+
+        let two_drives = yaml.replace("      root:", "      user: alice@contoso.com\n      root:");
+        let found = problems(Config::check(&two_drives));
+        assert_eq!(found[0].path, "mounts[0].connector.user");
+
+        // A signed-in user mounts their own OneDrive
+        let device_code = r#"
+mounts:
+  - path: /mnt/onedrive
+    connector:
+      type: onedrive
+      auth:
+        type: device_code
+        tenant_id: common
+        client_id: 00000000-0000-0000-0000-000000000002
+        token_path: /var/lib/fuse-adapter/onedrive.json
+"#;
+        let config = Config::parse(device_code).unwrap();
+        config.validate().unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::OneDrive(onedrive) => {
+                assert!(onedrive.drive_id.is_none() && onedrive.user.is_none());
+                assert_eq!(onedrive.root, "");
+                assert!(matches!(
+                    &onedrive.auth,
+                    OneDriveAuthConfig::DeviceCode { token_path, .. }
+                        if token_path == Path::new("/var/lib/fuse-adapter/onedrive.json")
+                ));
+            }
+            _ => panic!("expected onedrive connector"),
+        }
+        assert!(Config::parse(&device_code.replace("token_path", "tokens")).is_err());
+    }
+
+    #[test]
+    fn test_memfs_connector() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/scratch
+    connector:
+      type: memfs
+      max_size: 512MB
+  - path: /mnt/overlay
+    connector:
+      type: union
+      layers:
+        - type: memfs
+        - type: s3
+          bucket: base
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::Memfs(memfs) => {
+                assert_eq!(memfs.max_size.as_deref(), Some("512MB"));
+            }
+            _ => panic!("expected memfs connector"),
+        }
+        match &config.mounts[1].connector {
+            ConnectorConfig::Union(union) => {
+                assert!(matches!(union.layers[0], ConnectorConfig::Memfs(_)));
+            }
+            _ => panic!("expected union connector"),
+        }
+
+        let invalid = Config::parse(&yaml.replace("512MB", "lots")).unwrap();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_archive_connector() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/dataset
+    connector:
+      type: archive
+      path: datasets/images.tar.gz
+      source:
+        type: s3
+        bucket: my-datasets
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let mount = &config.mounts[0];
+        assert!(mount.read_only);
+        match &mount.connector {
+            ConnectorConfig::Archive(archive) => {
+                assert_eq!(archive.path, PathBuf::from("/datasets/images.tar.gz"));
+                assert_eq!(archive.format, ArchiveFormat::TarGz);
+                assert!(matches!(*archive.source, ConnectorConfig::S3(_)));
+            }
+            _ => panic!("expected archive connector"),
+        }
+
+        // The format can't be guessed without a known extension
+        let unknown = yaml.replace("images.tar.gz", "images.bin");
+        assert!(Config::parse(&unknown).is_err());
+        let explicit = unknown.replace("path: datasets", "format: zip\n      path: datasets");
+        assert!(Config::parse(&explicit).is_ok());
+
+        let nested = r#"
+mounts:
+  - path: /mnt/dataset
+    connector:
+      type: archive
+      path: a.zip
+      source:
+        type: union
+        layers: []
+"#;
+        assert!(Config::parse(nested).is_err());
+    }
+
+    #[test]
+    fn test_custom_connector_type() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/custom
+    connector:
+      type: config-test-backend
+      endpoint: https://backend.example.com
+"#;
+        let config = Config::parse(yaml).unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::Custom(custom) => {
+                assert_eq!(custom.type_name, "config-test-backend");
+                assert_eq!(
+                    custom.options.get("endpoint").and_then(|v| v.as_str()),
+                    Some("https://backend.example.com")
+                );
+                assert!(custom.options.get("type").is_none());
+            }
+            _ => panic!("expected custom connector"),
+        }
+
+        // Nothing is registered under that name
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("unknown connector type"));
+
+        // Built-in types are still checked by their own schema
+        let invalid = r#"
+mounts:
+  - path: /mnt/s3
+    connector:
+      type: s3
+      bucket: [not, a, string]
+"#;
+        assert!(Config::parse(invalid).is_err());
+    }
+
+    #[test]
+    fn test_mixed_full_and_override_configs() {
+        // Test that some mounts can use full config while others use defaults
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: default-bucket
+    region: us-west-2
+
+mounts:
+  - path: /mnt/default
+    connector:
+      type: s3
+      prefix: "default/"
+  - path: /mnt/custom
+    connector:
+      type: s3
+      bucket: custom-bucket
+      region: eu-west-1
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 2);
+
+        // First uses defaults
+        match &config.mounts[0].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "default-bucket");
+                assert_eq!(s3.region, Some("us-west-2".to_string()));
+            }
+            _ => panic!("Expected S3 connector"),
+        }
+
+        // Second overrides bucket and region from defaults
+        match &config.mounts[1].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "custom-bucket");
+                assert_eq!(s3.region, Some("eu-west-1".to_string()));
+            }
+            _ => panic!("Expected S3 connector"),
+        }
+    }
+
+    #[test]
+    fn test_different_bucket_inherits_other_settings() {
+        // Test that specifying a different bucket still inherits endpoint, force_path_style, etc.
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: default-bucket
+    region: us-west-2
+    endpoint: "http://minio:9000"
+    force_path_style: true
+
+mounts:
+  - path: /mnt/other-bucket
+    connector:
+      type: s3
+      bucket: other-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 1);
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "other-bucket"); // overridden
+                assert_eq!(s3.region, Some("us-west-2".to_string())); // inherited
+                assert_eq!(s3.endpoint, Some("http://minio:9000".to_string())); // inherited
+                assert!(s3.force_path_style); // inherited
+            }
+            _ => panic!("Expected S3 connector"),
+        }
+    }
+
+    #[test]
+    fn test_read_only_mount() {
+        // Test read_only is a mount-level option
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: default-bucket
+
+mounts:
+  - path: /mnt/readonly
+    read_only: true
+    connector:
+      type: s3
+      prefix: "ro/"
+  - path: /mnt/writable
+    connector:
+      type: s3
+      prefix: "rw/"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 2);
+
+        // First mount is read-only
+        assert!(config.mounts[0].read_only);
+
+        // Second mount defaults to writable
+        assert!(!config.mounts[1].read_only);
+    }
+
+    #[test]
+    fn test_gdrive_service_account_auth() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      root_folder_id: "folder123"
+      auth:
+        type: service_account
+        credentials_path: "/path/to/creds.json"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 1);
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => {
+                assert_eq!(gdrive.root_folder_id, "folder123");
+                match &gdrive.auth {
+                    GDriveAuthConfig::ServiceAccount { credentials_path } => {
+                        assert_eq!(credentials_path, &PathBuf::from("/path/to/creds.json"));
+                    }
+                    _ => panic!("Expected ServiceAccount auth"),
+                }
+            }
+            _ => panic!("Expected GDrive connector"),
+        }
+    }
+
+    #[test]
+    fn test_gdrive_http_auth() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      root_folder_id: "root"
+      auth:
+        type: http
+        endpoint: "https://api.example.com/token"
+        method: POST
+        headers:
+          Authorization: "Bearer my-token"
+          X-User-Id: "user-123"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 1);
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => {
+                assert_eq!(gdrive.root_folder_id, "root");
+                match &gdrive.auth {
+                    GDriveAuthConfig::Http {
+                        endpoint,
+                        method,
+                        headers,
+                        token_field,
+                        max_attempts,
+                        ..
+                    } => {
+                        assert_eq!(endpoint, "https://api.example.com/token");
+                        assert_eq!(method, "POST");
+                        assert_eq!(
+                            headers.get("Authorization"),
+                            Some(&"Bearer my-token".to_string())
+                        );
+                        assert_eq!(headers.get("X-User-Id"), Some(&"user-123".to_string()));
+                        assert_eq!(token_field, "access_token");
+                        assert_eq!(*max_attempts, 3);
+                    }
+                    _ => panic!("Expected Http auth"),
+                }
+            }
+            _ => panic!("Expected GDrive connector"),
+        }
+    }
+
+    #[test]
+    fn test_gdrive_http_auth_default_method() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      root_folder_id: "root"
+      auth:
+        type: http
+        endpoint: "https://api.example.com/token"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
+                GDriveAuthConfig::Http {
+                    method, headers, ..
+                } => {
+                    assert_eq!(method, "GET");
+                    assert!(headers.is_empty());
+                }
+                _ => panic!("Expected Http auth"),
+            },
+            _ => panic!("Expected GDrive connector"),
+        }
+    }
+
+    #[test]
+    fn test_gdrive_http_auth_response_fields_and_retries() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      auth:
+        type: http
+        endpoint: "https://api.example.com/token"
+        token_field: data.token
+        expires_in_field: data.ttl
+        max_attempts: 5
+        initial_backoff: 1s
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
+                GDriveAuthConfig::Http {
+                    token_field,
+                    expires_in_field,
+                    max_attempts,
+                    initial_backoff,
+                    ..
+                } => {
+                    assert_eq!(token_field, "data.token");
+                    assert_eq!(expires_in_field, "data.ttl");
+                    assert_eq!(*max_attempts, 5);
+                    assert_eq!(*initial_backoff, std::time::Duration::from_secs(1));
+                }
+                _ => panic!("Expected Http auth"),
+            },
+            _ => panic!("Expected GDrive connector"),
+        }
+        assert!(config.validate().is_ok());
+
+        let yaml = yaml
+            .replace("max_attempts: 5", "max_attempts: 0")
+            .replace("data.ttl", "data.");
+        let found = problems(Config::check(&yaml));
+        let paths: Vec<_> = found.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "mounts[0].connector.auth.max_attempts",
+                "mounts[0].connector.auth.expires_in_field"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gdrive_oauth_auth() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      auth:
+        type: oauth
+        client_secret_path: /etc/fuse-adapter/client_secret.json
+        token_path: /var/lib/fuse-adapter/tokens.json
+        flow: redirect
+        redirect_port: 8085
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
+                GDriveAuthConfig::Oauth {
+                    client_secret_path,
+                    token_path,
+                    flow,
+                    redirect_port,
+                } => {
+                    assert_eq!(
+                        client_secret_path,
+                        Path::new("/etc/fuse-adapter/client_secret.json")
+                    );
+                    assert_eq!(token_path, Path::new("/var/lib/fuse-adapter/tokens.json"));
+                    assert_eq!(*flow, OAuthFlow::Redirect);
+                    assert_eq!(*redirect_port, Some(8085));
+                }
+                _ => panic!("Expected OAuth auth"),
+            },
+            _ => panic!("Expected GDrive connector"),
+        }
+        assert!(config.validate().is_ok());
+
+        // The device flow is the default, and has no port
+        let yaml = yaml.replace("        flow: redirect\n", "");
+        let found = problems(Config::check(&yaml));
+        assert_eq!(found[0].path, "mounts[0].connector.auth.redirect_port");
+        assert!(Config::parse(&yaml.replace("token_path", "tokens")).is_err());
+    }
+
+    #[test]
+    fn test_gdrive_static_token_auth() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      root_folder_id: "root"
+      auth:
+        type: token
+        access_token: "ya29.test_token"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 1);
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
+                GDriveAuthConfig::Token { access_token } => {
+                    assert_eq!(access_token, "ya29.test_token");
+                }
+                _ => panic!("Expected Token auth"),
+            },
+            _ => panic!("Expected GDrive connector"),
+        }
+    }
+
+    #[test]
+    fn test_gdrive_auth_env_var_substitution() {
+        use std::env;
+        env::set_var("TEST_GDRIVE_SECRET", "secret_from_env");
+
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      root_folder_id: "root"
+      auth:
+        type: http
+        endpoint: "https://api.example.com/token"
+        headers:
+          Authorization: "Bearer ${TEST_GDRIVE_SECRET}"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
+                GDriveAuthConfig::Http { headers, .. } => {
+                    assert_eq!(
+                        headers.get("Authorization"),
+                        Some(&"Bearer secret_from_env".to_string())
+                    );
+                }
+                _ => panic!("Expected Http auth"),
+            },
+            _ => panic!("Expected GDrive connector"),
+        }
+
+        env::remove_var("TEST_GDRIVE_SECRET");
+    }
+
+    #[test]
+    fn test_gdrive_auth_inheritance_from_defaults() {
+        let yaml = r#"
+connectors:
+  gdrive:
+    root_folder_id: "default-folder"
+    auth:
+      type: service_account
+      credentials_path: "/default/creds.json"
+
+mounts:
+  - path: /mnt/gdrive1
+    connector:
+      type: gdrive
+  - path: /mnt/gdrive2
+    connector:
+      type: gdrive
+      root_folder_id: "custom-folder"
+      auth:
+        type: token
+        access_token: "custom-token"
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts.len(), 2);
+
+        // First mount inherits everything from defaults
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => {
+                assert_eq!(gdrive.root_folder_id, "default-folder");
+                match &gdrive.auth {
+                    GDriveAuthConfig::ServiceAccount { credentials_path } => {
+                        assert_eq!(credentials_path, &PathBuf::from("/default/creds.json"));
+                    }
+                    _ => panic!("Expected ServiceAccount auth"),
+                }
+            }
+            _ => panic!("Expected GDrive connector"),
+        }
+
+        // Second mount overrides both root_folder_id and auth
+        match &config.mounts[1].connector {
+            ConnectorConfig::GDrive(gdrive) => {
+                assert_eq!(gdrive.root_folder_id, "custom-folder");
+                match &gdrive.auth {
+                    GDriveAuthConfig::Token { access_token } => {
+                        assert_eq!(access_token, "custom-token");
+                    }
+                    _ => panic!("Expected Token auth"),
+                }
+            }
+            _ => panic!("Expected GDrive connector"),
+        }
+    }
+
+    #[test]
+    fn test_gdrive_missing_auth_error() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      root_folder_id: "root"
+"#;
+
+        let result = Config::parse(yaml);
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains("no auth specified"),
+            "Error should mention missing auth: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_gdrive_shared_drive() {
+        let yaml = r#"
+connectors:
+  gdrive:
+    auth:
+      type: token
+      access_token: "abc"
+
+mounts:
+  - path: /mnt/shared
+    connector:
+      type: gdrive
+      drive_id: "0AbCdEfGhIjKlUk9PVA"
+  - path: /mnt/shared-folder
+    connector:
+      type: gdrive
+      drive_id: "0AbCdEfGhIjKlUk9PVA"
+      root_folder_id: "folder123"
+  - path: /mnt/all-drives
+    connector:
+      type: gdrive
+      supports_all_drives: true
+  - path: /mnt/my-drive
+    connector:
+      type: gdrive
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let gdrive = |i: usize| match &config.mounts[i].connector {
+            ConnectorConfig::GDrive(gdrive) => gdrive.clone(),
+            _ => panic!("Expected GDrive connector"),
+        };
+
+        // Shared drive root is the drive itself
+        let shared = gdrive(0);
+        assert_eq!(shared.drive_id.as_deref(), Some("0AbCdEfGhIjKlUk9PVA"));
+        assert_eq!(shared.root_folder_id, "0AbCdEfGhIjKlUk9PVA");
+        assert!(shared.supports_all_drives);
+
+        let folder = gdrive(1);
+        assert_eq!(folder.root_folder_id, "folder123");
+        assert!(folder.supports_all_drives);
+
+        let all = gdrive(2);
+        assert!(all.drive_id.is_none());
+        assert_eq!(all.root_folder_id, "root");
+        assert!(all.supports_all_drives);
+
+        let mine = gdrive(3);
+        assert!(mine.drive_id.is_none());
+        assert!(!mine.supports_all_drives);
+    }
+
+    #[test]
+    fn test_gdrive_export_formats() {
+        let yaml = r#"
+connectors:
+  gdrive:
+    auth:
+      type: token
+      access_token: "abc"
+    export_formats:
+      document: docx
+      spreadsheet: xlsx
+
+mounts:
+  - path: /mnt/inherit
+    connector:
+      type: gdrive
+  - path: /mnt/override
+    connector:
+      type: gdrive
+      export_formats:
+        presentation: pdf
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        match &config.mounts[0].connector {
+            ConnectorConfig::GDrive(gdrive) => {
+                assert_eq!(gdrive.export_formats.len(), 2);
+                assert_eq!(gdrive.export_formats["document"], "docx");
+            }
+            _ => panic!("Expected GDrive connector"),
+        }
+        match &config.mounts[1].connector {
+            ConnectorConfig::GDrive(gdrive) => {
+                assert_eq!(gdrive.export_formats.len(), 1);
+                assert_eq!(gdrive.export_formats["presentation"], "pdf");
+            }
+            _ => panic!("Expected GDrive connector"),
+        }
+    }
+
+    #[test]
+    fn test_gdrive_endpoint() {
+        let yaml = r#"
+connectors:
+  gdrive:
+    endpoint: http://localhost:4443
+    auth:
+      type: token
+      access_token: "abc"
+
+mounts:
+  - path: /mnt/inherit
+    connector:
+      type: gdrive
+  - path: /mnt/override
+    connector:
+      type: gdrive
+      endpoint: http://localhost:8080
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let endpoint = |i: usize| match &config.mounts[i].connector {
+            ConnectorConfig::GDrive(gdrive) => gdrive.endpoint.clone(),
+            _ => panic!("Expected GDrive connector"),
+        };
+        assert_eq!(endpoint(0).as_deref(), Some("http://localhost:4443"));
+        assert_eq!(endpoint(1).as_deref(), Some("http://localhost:8080"));
+    }
+
+    #[test]
+    fn test_gdrive_export_format_validation() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/gdrive
+    connector:
+      type: gdrive
+      auth:
+        type: token
+        access_token: "abc"
+      export_formats:
+        document: xlsx
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("unsupported GDrive export format"));
+    }
+
+    #[test]
+    fn test_error_mode_default() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.error_mode, ErrorMode::Continue);
+    }
+
+    #[test]
+    fn test_error_mode_continue() {
+        let yaml = r#"
+error_mode: continue
+
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.error_mode, ErrorMode::Continue);
+    }
+
+    #[test]
+    fn test_error_mode_exit() {
+        let yaml = r#"
+error_mode: exit
+
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.error_mode, ErrorMode::Exit);
+    }
+
+    #[test]
+    fn test_per_mount_error_mode_override() {
+        let yaml = r#"
+error_mode: continue
+
+mounts:
+  - path: /mnt/critical
+    error_mode: exit
+    connector:
+      type: s3
+      bucket: production
+  - path: /mnt/optional
+    connector:
+      type: s3
+      bucket: cache
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.error_mode, ErrorMode::Continue); // Global default
+
+        // First mount overrides to exit
+        assert_eq!(config.mounts[0].error_mode, ErrorMode::Exit);
+        // Second mount inherits global default
+        assert_eq!(config.mounts[1].error_mode, ErrorMode::Continue);
+    }
+
+    #[test]
+    fn test_status_overlay_with_defaults() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    status_overlay: {}
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let overlay = config.mounts[0].status_overlay.as_ref().unwrap();
+        assert_eq!(overlay.prefix, ".fuse-adapter");
+        assert_eq!(overlay.max_log_entries, 1000);
+    }
+
+    #[test]
+    fn test_status_overlay_with_custom_values() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    status_overlay:
+      prefix: ".status"
+      max_log_entries: 500
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let overlay = config.mounts[0].status_overlay.as_ref().unwrap();
+        assert_eq!(overlay.prefix, ".status");
+        assert_eq!(overlay.max_log_entries, 500);
+    }
+
+    #[test]
+    fn test_status_overlay_not_present() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        assert!(config.mounts[0].status_overlay.is_none());
+    }
+
+    #[test]
+    fn test_integrity_config() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/default
+    integrity: {}
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/strict
+    integrity:
+      fail_on_mismatch: true
+      max_failures: 10
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let integrity = config.mounts[0].integrity.as_ref().unwrap();
+        assert!(!integrity.fail_on_mismatch);
+        assert_eq!(integrity.max_failures, 100);
+        let integrity = config.mounts[1].integrity.as_ref().unwrap();
+        assert!(integrity.fail_on_mismatch);
+        assert_eq!(integrity.max_failures, 10);
+    }
+
+    #[test]
+    fn test_health_check_config() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/default
+    health_check: {}
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/custom
+    health_check:
+      interval: 5s
+      auto_remount: false
+      max_backoff: 1m
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/none
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::parse(yaml).unwrap();
+        let hc = config.mounts[0].health_check.as_ref().unwrap();
+        assert_eq!(hc.interval, std::time::Duration::from_secs(30));
+        assert_eq!(hc.timeout, std::time::Duration::from_secs(10));
+        assert!(hc.auto_remount);
+        let hc = config.mounts[1].health_check.as_ref().unwrap();
+        assert_eq!(hc.interval, std::time::Duration::from_secs(5));
+        assert!(!hc.auto_remount);
+        assert_eq!(hc.max_backoff, std::time::Duration::from_secs(60));
+        assert!(config.mounts[2].health_check.is_none());
+    }
+
+    #[test]
+    fn test_circuit_breaker_config() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/default
+    circuit_breaker: {}
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/custom
+    circuit_breaker:
+      failure_threshold: 3
+      cooldown: 1m
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::check(yaml).unwrap();
+        let breaker = config.mounts[0].circuit_breaker.as_ref().unwrap();
+        assert_eq!(breaker.failure_threshold, 5);
+        assert_eq!(breaker.cooldown, std::time::Duration::from_secs(30));
+        let breaker = config.mounts[1].circuit_breaker.as_ref().unwrap();
+        assert_eq!(breaker.failure_threshold, 3);
+        assert_eq!(breaker.cooldown, std::time::Duration::from_secs(60));
+
+        let err = Config::check(&yaml.replace("failure_threshold: 3", "failure_threshold: 0"))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "line 10, column 26: Mount \"/mnt/custom\": circuit_breaker failure_threshold"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_read_only_fallback_config() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/default
+    read_only_fallback: {}
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/custom
+    read_only_fallback:
+      probe_interval: 5m
+      probe_path: /probe/.write-test
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+
+        let config = Config::check(yaml).unwrap();
+        let fallback = config.mounts[0].read_only_fallback.as_ref().unwrap();
+        assert_eq!(fallback.probe_interval, std::time::Duration::from_secs(60));
+        assert_eq!(
+            fallback.probe_path,
+            PathBuf::from("/.fuse-adapter-write-probe")
+        );
+        let fallback = config.mounts[1].read_only_fallback.as_ref().unwrap();
+        assert_eq!(fallback.probe_interval, std::time::Duration::from_secs(300));
+        assert_eq!(fallback.probe_path, PathBuf::from("/probe/.write-test"));
+
+        let err = Config::check(&yaml.replace("/probe/.write-test", "relative")).unwrap_err();
+        assert!(
+            err.to_string().contains("must be an absolute file path"),
+            "{}",
+            err
+        );
+
+        let err = Config::check(&yaml.replace(
+            "  - path: /mnt/custom\n",
+            "  - path: /mnt/custom\n    read_only: true\n",
+        ))
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("no effect on a read_only mount"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_routes_config() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: main-bucket
+      region: eu-west-1
+    routes:
+      - path: raw/**
+        connector:
+          type: s3
+          bucket: raw-bucket
+      - path: "**/*.tmp"
+        connector:
+          type: memfs
+"#;
+
+        let config = Config::check(yaml).unwrap();
+        let routes = &config.mounts[0].routes;
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].path, "raw/**");
+        match &routes[0].connector {
+            ConnectorConfig::S3(s3) => {
+                assert_eq!(s3.bucket, "raw-bucket");
+            }
+            _ => panic!("expected S3 route connector"),
+        }
+        assert!(matches!(routes[1].connector, ConnectorConfig::Memfs(_)));
+
+        let err = Config::check(&yaml.replace("raw/**", "raw/[a")).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid route path 'raw/[a'"),
+            "{}",
+            err
+        );
+
+        let err = Config::check(&yaml.replace("bucket: raw-bucket", "bucket: \"\"")).unwrap_err();
+        assert!(
+            err.to_string().contains("S3 bucket cannot be empty"),
+            "{}",
+            err
+        );
+
+        let nested = yaml.replace("type: memfs", "type: union\n          layers: []");
+        assert!(Config::parse(&nested).is_err());
+    }
+
+    #[test]
+    fn test_s3_request_tuning() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    max_concurrent_requests: 32
+    max_keys: 200
+    connect_timeout: 5s
+    max_attempts: 5
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      max_concurrent_requests: 8
+      read_timeout: 30s
+      initial_backoff: 200ms
+      adaptive_rate: false
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let s3: Vec<&S3ConnectorConfig> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => s3,
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+
+        assert_eq!(s3[0].max_concurrent_requests, Some(32));
+        assert_eq!(
+            s3[0].connect_timeout,
+            Some(std::time::Duration::from_secs(5))
+        );
+        assert_eq!(s3[0].max_attempts, Some(5));
+        assert_eq!(s3[0].max_keys, Some(200));
+        assert!(s3[0].read_timeout.is_none());
+        assert!(s3[0].adaptive_rate);
+        assert!(!s3[1].adaptive_rate);
+
+        assert_eq!(s3[1].max_concurrent_requests, Some(8));
+        assert_eq!(s3[1].read_timeout, Some(std::time::Duration::from_secs(30)));
+        assert_eq!(
+            s3[1].initial_backoff,
+            Some(std::time::Duration::from_millis(200))
+        );
+        assert_eq!(
+            s3[1].connect_timeout,
+            Some(std::time::Duration::from_secs(5))
+        );
+
+        let yaml = r#"
+mounts:
+  - path: /mnt/s3
+    connector:
+      type: s3
+      bucket: my-bucket
+      max_concurrent_requests: 0
+"#;
+        assert!(Config::parse(yaml).unwrap().validate().is_err());
+
+        let yaml = r#"
+mounts:
+  - path: /mnt/s3
+    connector:
+      type: s3
+      bucket: my-bucket
+      max_keys: 5000
+"#;
+        assert!(Config::parse(yaml).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_s3_list_metadata() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    list_metadata: true
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      list_metadata: false
+"#;
+        let config = Config::parse(yaml).unwrap();
+        let list_metadata: Vec<bool> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => s3.list_metadata,
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+        assert_eq!(list_metadata, vec![true, false]);
+    }
+
+    #[test]
+    fn test_s3_key_encoding() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    key_encoding: escape
+    escape_chars: "\\"
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      key_encoding: raw
+"#;
+        let config = Config::parse(yaml).unwrap();
+        let encodings: Vec<(S3KeyEncoding, String)> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => (s3.key_encoding, s3.escape_chars.clone()),
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+        assert_eq!(
+            encodings,
+            vec![
+                (S3KeyEncoding::Escape, "\\".to_string()),
+                (S3KeyEncoding::Raw, "\\".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_s3_dir_mtime() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    dir_mtime: latest_child
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      dir_mtime: epoch
+"#;
+        let config = Config::parse(yaml).unwrap();
+        let strategies: Vec<S3DirMtime> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => s3.dir_mtime,
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+        assert_eq!(strategies, vec![S3DirMtime::LatestChild, S3DirMtime::Epoch]);
+    }
+
+    #[test]
+    fn test_s3_directory_markers() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    directory_markers: false
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      directory_markers: true
+"#;
+        let config = Config::parse(yaml).unwrap();
+        let markers: Vec<bool> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => s3.directory_markers,
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+        assert_eq!(markers, vec![false, true]);
+
+        let yaml = r#"
+mounts:
+  - path: /mnt/s3
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        match &Config::parse(yaml).unwrap().mounts[0].connector {
+            ConnectorConfig::S3(s3) => assert!(s3.directory_markers),
+            _ => panic!("Expected S3 connector"),
+        }
+    }
+
+    #[test]
+    fn test_s3_atomic_rename() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    atomic_rename: true
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      atomic_rename: false
+"#;
+        let config = Config::parse(yaml).unwrap();
+        let atomic_rename: Vec<bool> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => s3.atomic_rename,
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+        assert_eq!(atomic_rename, vec![true, false]);
+    }
+
+    #[test]
+    fn test_s3_create_prefix() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: shared
+    create_prefix: true
+mounts:
+  - path: /mnt/inherits
+    connector:
+      type: s3
+      prefix: team-a/
+  - path: /mnt/overrides
+    connector:
+      type: s3
+      prefix: team-b/
+      create_prefix: false
+"#;
+        let config = Config::parse(yaml).unwrap();
+        let create_prefix: Vec<bool> = config
+            .mounts
+            .iter()
+            .map(|m| match &m.connector {
+                ConnectorConfig::S3(s3) => s3.create_prefix,
+                _ => panic!("Expected S3 connector"),
+            })
+            .collect();
+        assert_eq!(create_prefix, vec![true, false]);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let yaml = r#"
+rate_limit:
+  upload: 10MB
+mounts:
+  - path: /mnt/limited
+    rate_limit:
+      upload: 1MB
+      download: 5MB
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/global-only
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let global = config.rate_limit.as_ref().unwrap();
+        assert_eq!(global.upload.as_deref(), Some("10MB"));
+        assert!(global.download.is_none());
+        let mount = config.mounts[0].rate_limit.as_ref().unwrap();
+        assert_eq!(mount.upload.as_deref(), Some("1MB"));
+        assert_eq!(mount.download.as_deref(), Some("5MB"));
+        assert!(config.mounts[1].rate_limit.is_none());
+
+        let yaml = r#"
+mounts:
+  - path: /mnt/bad
+    rate_limit:
+      upload: fast
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        let err = Config::parse(yaml).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("invalid upload rate limit"));
+    }
+
+    #[test]
+    fn test_cache_peers() {
+        let yaml = r#"
+cache_peers:
+  listen: 0.0.0.0:7070
+  peers:
+    - http://render-02:7070
+  timeout: 500ms
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let peers = config.cache_peers.as_ref().unwrap();
+        assert_eq!(peers.listen, Some("0.0.0.0:7070".parse().unwrap()));
+        assert_eq!(peers.peers, vec!["http://render-02:7070"]);
+        assert_eq!(peers.timeout, std::time::Duration::from_millis(500));
+        assert!(peers.token.is_none());
+
+        let yaml = r#"
+cache_peers:
+  peers:
+    - render-02:7070
+mounts:
+  - path: /mnt/data
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        let err = Config::parse(yaml).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("invalid peer URL"));
+    }
+
+    #[test]
+    fn test_union_connector() {
+        let yaml = r#"
+connectors:
+  s3:
+    bucket: base
+    cache:
+      type: memory
+mounts:
+  - path: /mnt/union
+    connector:
+      type: union
+      layers:
+        - type: s3
+          bucket: scratch
+        - type: s3
+        - type: gdrive
+          auth:
+            type: token
+            access_token: "token"
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+
+        let mount = &config.mounts[0];
+        assert!(matches!(mount.cache, CacheConfig::Memory { .. }));
+        match &mount.connector {
+            ConnectorConfig::Union(union) => {
+                assert_eq!(union.layers.len(), 3);
+                match (&union.layers[0], &union.layers[1]) {
+                    (ConnectorConfig::S3(upper), ConnectorConfig::S3(lower)) => {
+                        assert_eq!(upper.bucket, "scratch");
+                        assert_eq!(lower.bucket, "base");
+                    }
+                    _ => panic!("Expected S3 layers"),
+                }
+                assert!(matches!(union.layers[2], ConnectorConfig::GDrive(_)));
+            }
+            _ => panic!("Expected union connector"),
+        }
+
+        let single = r#"
+mounts:
+  - path: /mnt/union
+    connector:
+      type: union
+      layers:
+        - type: s3
+          bucket: only
+"#;
+        assert!(Config::parse(single).unwrap().validate().is_err());
+
+        let nested = r#"
+mounts:
+  - path: /mnt/union
+    connector:
+      type: union
+      layers:
+        - type: union
+          layers: []
+        - type: s3
+          bucket: base
+"#;
+        assert!(Config::parse(nested).is_err());
+    }
+
+    #[test]
+    fn test_encryption() {
+        std::env::set_var("TEST_ENCRYPTION_KEY", "c2VjcmV0");
+        let yaml = r#"
+mounts:
+  - path: /mnt/private
+    encryption:
+      key: "${TEST_ENCRYPTION_KEY}"
+      encrypt_names: true
+    connector:
+      type: s3
+      bucket: private
+  - path: /mnt/plain
+    connector:
+      type: s3
+      bucket: plain
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let encryption = config.mounts[0].encryption.as_ref().unwrap();
+        assert_eq!(encryption.key.as_deref(), Some("c2VjcmV0"));
+        assert!(encryption.encrypt_names);
+        assert!(config.mounts[1].encryption.is_none());
+
+        let two_sources = r#"
+mounts:
+  - path: /mnt/private
+    encryption:
+      key: "c2VjcmV0"
+      key_command: "cat /etc/key"
+    connector:
+      type: s3
+      bucket: private
+"#;
+        assert!(Config::parse(two_sources).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_compression() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/logs
+    compression:
+      algorithm: gzip
+      level: 9
+      min_size: 4KB
+      extensions: [log, txt]
+    connector:
+      type: s3
+      bucket: logs
+  - path: /mnt/defaults
+    compression: {}
+    connector:
+      type: s3
+      bucket: data
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let logs = config.mounts[0].compression.as_ref().unwrap();
+        assert_eq!(logs.algorithm, CompressionAlgorithm::Gzip);
+        assert_eq!(logs.level, Some(9));
+        assert_eq!(logs.min_size.as_deref(), Some("4KB"));
+        assert_eq!(
+            logs.extensions,
+            Some(vec!["log".to_string(), "txt".to_string()])
+        );
+        let defaults = config.mounts[1].compression.as_ref().unwrap();
+        assert_eq!(defaults.algorithm, CompressionAlgorithm::Zstd);
+        assert!(defaults.exclude_extensions.is_none());
+
+        let bad_level = r#"
+mounts:
+  - path: /mnt/logs
+    compression:
+      algorithm: gzip
+      level: 12
+    connector:
+      type: s3
+      bucket: logs
+"#;
+        assert!(Config::parse(bad_level).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_hide_patterns() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    hide_patterns:
+      - "**/.DS_Store"
+      - "_tmp"
+    connector:
+      type: s3
+      bucket: data
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.mounts[0].hide_patterns, vec!["**/.DS_Store", "_tmp"]);
+
+        let invalid = yaml.replace("_tmp", "[unclosed");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_quota() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    quota:
+      max_total_size: 10GB
+      max_file_size: 512MB
+    connector:
+      type: s3
+      bucket: data
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let quota = config.mounts[0].quota.as_ref().unwrap();
+        assert_eq!(quota.max_total_size.as_deref(), Some("10GB"));
+        assert_eq!(quota.max_file_size.as_deref(), Some("512MB"));
+
+        let invalid = yaml.replace("512MB", "lots");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_audit() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    audit:
+      type: file
+      path: /var/log/fuse-adapter/audit.jsonl
+    connector:
+      type: s3
+      bucket: data
+  - path: /mnt/logs
+    audit:
+      type: syslog
+    connector:
+      type: s3
+      bucket: logs
+"#;
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(
+            config.mounts[0].audit,
+            Some(AuditConfig::File {
+                path: PathBuf::from("/var/log/fuse-adapter/audit.jsonl")
+            })
+        );
+        assert_eq!(config.mounts[1].audit, Some(AuditConfig::Syslog));
+        assert!(config.mounts[1].quota.is_none());
+    }
+
+    #[test]
+    fn test_access() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/data
+    access:
+      allowed_uids: [1000, 1001]
+      read_only_uids: [1001]
+      rules:
+        - path: "secrets/**"
+          uids: [1000]
+          permission: read
+        - path: "secrets"
+          permission: none
+    connector:
+      type: s3
+      bucket: data
+"#;
+        let config = Config::parse(yaml).unwrap();
+        config.validate().unwrap();
+        let access = config.mounts[0].access.as_ref().unwrap();
+        assert_eq!(access.allowed_uids, vec![1000, 1001]);
+        assert!(access.allowed_gids.is_empty());
+        assert_eq!(access.rules.len(), 2);
+        assert_eq!(access.rules[0].permission, Permission::Read);
+        assert!(access.rules[1].uids.is_empty());
+        assert_eq!(access.rules[1].permission, Permission::None);
+        assert!(!config.mounts[0].enforce_permissions);
+
+        let invalid = yaml.replace("\"secrets\"", "\"[unclosed\"");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_enforce_permissions() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/shared
+    enforce_permissions: true
+    connector:
+      type: s3
+      bucket: shared
+"#;
+        let config = Config::parse(yaml).unwrap();
+        assert!(config.mounts[0].enforce_permissions);
+    }
+
+    #[test]
+    fn test_locking_mode() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/a
+    connector:
+      type: s3
+      bucket: a
+  - path: /mnt/b
+    locking: deny
+    connector:
+      type: s3
+      bucket: b
+"#;
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.mounts[0].locking, LockingMode::Local);
+        assert_eq!(config.mounts[1].locking, LockingMode::Deny);
 
-        assert!(config.validate().is_err());
+        let invalid = yaml.replace("locking: deny", "locking: remote");
+        assert!(Config::parse(&invalid).is_err());
     }
 
     #[test]
-    fn test_mixed_full_and_override_configs() {
-        // Test that some mounts can use full config while others use defaults
+    fn test_unicode_normalization() {
         let yaml = r#"
-connectors:
-  s3:
-    bucket: default-bucket
-    region: us-west-2
-
 mounts:
-  - path: /mnt/default
+  - path: /mnt/nfc
+    unicode_normalization: nfc
     connector:
       type: s3
-      prefix: "default/"
-  - path: /mnt/custom
+      bucket: test
+  - path: /mnt/default
     connector:
       type: s3
-      bucket: custom-bucket
-      region: eu-west-1
+      bucket: test
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 2);
-
-        // First uses defaults
-        match &config.mounts[0].connector {
-            ConnectorConfig::S3(s3) => {
-                assert_eq!(s3.bucket, "default-bucket");
-                assert_eq!(s3.region, Some("us-west-2".to_string()));
-            }
-            _ => panic!("Expected S3 connector"),
-        }
-
-        // Second overrides bucket and region from defaults
-        match &config.mounts[1].connector {
-            ConnectorConfig::S3(s3) => {
-                assert_eq!(s3.bucket, "custom-bucket");
-                assert_eq!(s3.region, Some("eu-west-1".to_string()));
-            }
-            _ => panic!("Expected S3 connector"),
-        }
+        assert_eq!(
+            config.mounts[0].unicode_normalization,
+            UnicodeNormalization::Nfc
+        );
+        assert_eq!(
+            config.mounts[1].unicode_normalization,
+            UnicodeNormalization::None
+        );
     }
 
     #[test]
-    fn test_different_bucket_inherits_other_settings() {
-        // Test that specifying a different bucket still inherits endpoint, force_path_style, etc.
+    fn test_macos_finder_files() {
         let yaml = r#"
-connectors:
-  s3:
-    bucket: default-bucket
-    region: us-west-2
-    endpoint: "http://minio:9000"
-    force_path_style: true
-
 mounts:
-  - path: /mnt/other-bucket
+  - path: /mnt/mac
+    hide_patterns:
+      - _tmp
+    macos:
+      volname: Shared
+      apple_double: reject
+      ds_store: local
     connector:
       type: s3
-      bucket: other-bucket
+      bucket: test
+    cache:
+      type: memory
+      exclude_from_sync:
+        - scratch
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 1);
-
-        match &config.mounts[0].connector {
-            ConnectorConfig::S3(s3) => {
-                assert_eq!(s3.bucket, "other-bucket"); // overridden
-                assert_eq!(s3.region, Some("us-west-2".to_string())); // inherited
-                assert_eq!(s3.endpoint, Some("http://minio:9000".to_string())); // inherited
-                assert!(s3.force_path_style); // inherited
-            }
-            _ => panic!("Expected S3 connector"),
+        let mount = &config.mounts[0];
+        let macos = mount.macos.as_ref().unwrap();
+        assert_eq!(macos.volname.as_deref(), Some("Shared"));
+        assert!(macos.no_apple_double());
+        assert_eq!(mount.hide_patterns, vec!["_tmp", "**/._*"]);
+        match &mount.cache {
+            CacheConfig::Memory {
+                exclude_from_sync, ..
+            } => assert_eq!(
+                exclude_from_sync.as_deref(),
+                Some(&["scratch".to_string(), "**/.DS_Store".to_string()][..])
+            ),
+            other => panic!("unexpected cache {:?}", other),
         }
+
+        // Keeping files local needs somewhere to keep them
+        let uncached = yaml.replace(
+            "type: memory\n      exclude_from_sync:\n        - scratch",
+            "type: none",
+        );
+        let err = Config::check(&uncached).unwrap_err();
+        assert!(err.to_string().contains("macos.ds_store"), "{}", err);
+        assert!(Config::parse(&yaml.replace("ds_store: local", "ds_store: hide")).is_err());
     }
 
     #[test]
-    fn test_read_only_mount() {
-        // Test read_only is a mount-level option
+    fn test_page_cache_mode() {
         let yaml = r#"
-connectors:
-  s3:
-    bucket: default-bucket
-
 mounts:
-  - path: /mnt/readonly
-    read_only: true
+  - path: /mnt/live
+    page_cache: direct_io
     connector:
       type: s3
-      prefix: "ro/"
-  - path: /mnt/writable
+      bucket: live
+  - path: /mnt/static
+    page_cache: keep
     connector:
       type: s3
-      prefix: "rw/"
+      bucket: static
+  - path: /mnt/default
+    connector:
+      type: s3
+      bucket: default
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 2);
-
-        // First mount is read-only
-        assert!(config.mounts[0].read_only);
-
-        // Second mount defaults to writable
-        assert!(!config.mounts[1].read_only);
+        assert_eq!(config.mounts[0].page_cache, PageCacheMode::DirectIo);
+        assert_eq!(config.mounts[1].page_cache, PageCacheMode::Keep);
+        assert_eq!(config.mounts[2].page_cache, PageCacheMode::Invalidate);
     }
 
     #[test]
-    fn test_gdrive_service_account_auth() {
+    fn test_write_buffer() {
         let yaml = r#"
 mounts:
-  - path: /mnt/gdrive
+  - path: /mnt/a
+    write_buffer:
+      size: 4MB
+      interval: 250ms
     connector:
-      type: gdrive
-      root_folder_id: "folder123"
-      auth:
-        type: service_account
-        credentials_path: "/path/to/creds.json"
+      type: s3
+      bucket: a
+  - path: /mnt/b
+    write_buffer: {}
+    connector:
+      type: s3
+      bucket: b
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 1);
-
-        match &config.mounts[0].connector {
-            ConnectorConfig::GDrive(gdrive) => {
-                assert_eq!(gdrive.root_folder_id, "folder123");
-                match &gdrive.auth {
-                    GDriveAuthConfig::ServiceAccount { credentials_path } => {
-                        assert_eq!(credentials_path, &PathBuf::from("/path/to/creds.json"));
-                    }
-                    _ => panic!("Expected ServiceAccount auth"),
-                }
-            }
-            _ => panic!("Expected GDrive connector"),
-        }
+        config.validate().unwrap();
+        let buffer = config.mounts[0].write_buffer.as_ref().unwrap();
+        assert_eq!(buffer.size.as_deref(), Some("4MB"));
+        assert_eq!(buffer.interval, std::time::Duration::from_millis(250));
+        let buffer = config.mounts[1].write_buffer.as_ref().unwrap();
+        assert!(buffer.size.is_none());
+        assert_eq!(buffer.interval, std::time::Duration::from_secs(1));
+
+        let invalid = yaml.replace("size: 4MB", "size: 0");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
+        let invalid = yaml.replace("interval: 250ms", "interval: 0s");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
     }
 
     #[test]
-    fn test_gdrive_http_auth() {
+    fn test_max_write_and_max_read() {
         let yaml = r#"
 mounts:
-  - path: /mnt/gdrive
+  - path: /mnt/a
+    max_write: 1MB
+    max_read: 512KB
     connector:
-      type: gdrive
-      root_folder_id: "root"
-      auth:
-        type: http
-        endpoint: "https://api.example.com/token"
-        method: POST
-        headers:
-          Authorization: "Bearer my-token"
-          X-User-Id: "user-123"
+      type: s3
+      bucket: a
+  - path: /mnt/b
+    connector:
+      type: s3
+      bucket: b
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 1);
-
-        match &config.mounts[0].connector {
-            ConnectorConfig::GDrive(gdrive) => {
-                assert_eq!(gdrive.root_folder_id, "root");
-                match &gdrive.auth {
-                    GDriveAuthConfig::Http {
-                        endpoint,
-                        method,
-                        headers,
-                    } => {
-                        assert_eq!(endpoint, "https://api.example.com/token");
-                        assert_eq!(method, "POST");
-                        assert_eq!(
-                            headers.get("Authorization"),
-                            Some(&"Bearer my-token".to_string())
-                        );
-                        assert_eq!(headers.get("X-User-Id"), Some(&"user-123".to_string()));
-                    }
-                    _ => panic!("Expected Http auth"),
-                }
-            }
-            _ => panic!("Expected GDrive connector"),
-        }
+        config.validate().unwrap();
+        assert_eq!(config.mounts[0].max_write.as_deref(), Some("1MB"));
+        assert_eq!(config.mounts[0].max_read.as_deref(), Some("512KB"));
+        assert!(config.mounts[1].max_write.is_none());
+        assert!(config.mounts[1].max_read.is_none());
+
+        let invalid = yaml.replace("max_write: 1MB", "max_write: 0");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
+        let invalid = yaml.replace("max_read: 512KB", "max_read: lots");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
     }
 
     #[test]
-    fn test_gdrive_http_auth_default_method() {
+    fn test_s3_as_of() {
         let yaml = r#"
 mounts:
-  - path: /mnt/gdrive
+  - path: /mnt/yesterday
     connector:
-      type: gdrive
-      root_folder_id: "root"
-      auth:
-        type: http
-        endpoint: "https://api.example.com/token"
+      type: s3
+      bucket: data
+      as_of: "2024-05-01T12:00:00Z"
+  - path: /mnt/live
+    connector:
+      type: s3
+      bucket: data
 "#;
-
         let config = Config::parse(yaml).unwrap();
-
-        match &config.mounts[0].connector {
-            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
-                GDriveAuthConfig::Http {
-                    method, headers, ..
-                } => {
-                    assert_eq!(method, "GET");
-                    assert!(headers.is_empty());
-                }
-                _ => panic!("Expected Http auth"),
-            },
-            _ => panic!("Expected GDrive connector"),
-        }
+        let ConnectorConfig::S3(s3) = &config.mounts[0].connector else {
+            panic!("expected S3 connector");
+        };
+        let expected = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1714564800);
+        assert_eq!(s3.as_of, Some(expected));
+        assert!(config.mounts[0].read_only);
+        assert!(!config.mounts[1].read_only);
     }
 
     #[test]
-    fn test_gdrive_static_token_auth() {
+    fn test_trash() {
         let yaml = r#"
 mounts:
-  - path: /mnt/gdrive
+  - path: /mnt/default
+    trash: {}
     connector:
-      type: gdrive
-      root_folder_id: "root"
-      auth:
-        type: token
-        access_token: "ya29.test_token"
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/custom
+    trash:
+      directory: .deleted
+      retention: 30d
+      purge_interval: 10m
+    connector:
+      type: s3
+      bucket: my-bucket
 "#;
 
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 1);
+        let trash = config.mounts[0].trash.as_ref().unwrap();
+        assert_eq!(trash.directory, ".trash");
+        assert_eq!(trash.retention, std::time::Duration::from_secs(7 * 86400));
+        let trash = config.mounts[1].trash.as_ref().unwrap();
+        assert_eq!(trash.directory, ".deleted");
+        assert_eq!(trash.retention, std::time::Duration::from_secs(30 * 86400));
+        assert_eq!(trash.purge_interval, std::time::Duration::from_secs(600));
+        config.validate().unwrap();
 
-        match &config.mounts[0].connector {
-            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
-                GDriveAuthConfig::Token { access_token } => {
-                    assert_eq!(access_token, "ya29.test_token");
-                }
-                _ => panic!("Expected Token auth"),
-            },
-            _ => panic!("Expected GDrive connector"),
-        }
+        let yaml = r#"
+mounts:
+  - path: /mnt/bad
+    trash:
+      directory: a/b
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        assert!(Config::parse(yaml).unwrap().validate().is_err());
     }
 
     #[test]
-    fn test_gdrive_auth_env_var_substitution() {
-        use std::env;
-        env::set_var("TEST_GDRIVE_SECRET", "secret_from_env");
-
+    fn test_faults() {
         let yaml = r#"
 mounts:
-  - path: /mnt/gdrive
+  - path: /mnt/flaky
+    faults:
+      seed: 42
+      read:
+        error_rate: 0.25
+        latency: 50ms
+      all:
+        fail_next: 2
+        error: timeout
     connector:
-      type: gdrive
-      root_folder_id: "root"
-      auth:
-        type: http
-        endpoint: "https://api.example.com/token"
-        headers:
-          Authorization: "Bearer ${TEST_GDRIVE_SECRET}"
+      type: memfs
 "#;
 
         let config = Config::parse(yaml).unwrap();
-
-        match &config.mounts[0].connector {
-            ConnectorConfig::GDrive(gdrive) => match &gdrive.auth {
-                GDriveAuthConfig::Http { headers, .. } => {
-                    assert_eq!(
-                        headers.get("Authorization"),
-                        Some(&"Bearer secret_from_env".to_string())
-                    );
-                }
-                _ => panic!("Expected Http auth"),
-            },
-            _ => panic!("Expected GDrive connector"),
-        }
-
-        env::remove_var("TEST_GDRIVE_SECRET");
+        let faults = config.mounts[0].faults.as_ref().unwrap();
+        assert_eq!(faults.seed, 42);
+        let read = &faults.operations[&FaultOperation::Read];
+        assert_eq!(read.error_rate, 0.25);
+        assert_eq!(read.latency, Some(std::time::Duration::from_millis(50)));
+        let all = &faults.operations[&FaultOperation::All];
+        assert_eq!(all.fail_next, 2);
+        assert_eq!(all.error, FaultError::Timeout);
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "mock"));
+
+        let invalid = yaml.replace("0.25", "1.5");
+        assert!(Config::parse(&invalid).unwrap().validate().is_err());
+        let invalid = yaml.replace("read:", "reed:");
+        assert!(Config::parse(&invalid).is_err());
     }
 
     #[test]
-    fn test_gdrive_auth_inheritance_from_defaults() {
+    fn test_operation_timeout() {
         let yaml = r#"
-connectors:
-  gdrive:
-    root_folder_id: "default-folder"
-    auth:
-      type: service_account
-      credentials_path: "/default/creds.json"
+operation_timeout: 30s
+mounts:
+  - path: /mnt/inherit
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/override
+    operation_timeout: 5s
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(
+            config.mounts[0].operation_timeout,
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            config.mounts[1].operation_timeout,
+            Some(std::time::Duration::from_secs(5))
+        );
 
+        let yaml = r#"
 mounts:
-  - path: /mnt/gdrive1
-    connector:
-      type: gdrive
-  - path: /mnt/gdrive2
+  - path: /mnt/s3
     connector:
-      type: gdrive
-      root_folder_id: "custom-folder"
-      auth:
-        type: token
-        access_token: "custom-token"
+      type: s3
+      bucket: my-bucket
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.mounts.len(), 2);
-
-        // First mount inherits everything from defaults
-        match &config.mounts[0].connector {
-            ConnectorConfig::GDrive(gdrive) => {
-                assert_eq!(gdrive.root_folder_id, "default-folder");
-                match &gdrive.auth {
-                    GDriveAuthConfig::ServiceAccount { credentials_path } => {
-                        assert_eq!(credentials_path, &PathBuf::from("/default/creds.json"));
-                    }
-                    _ => panic!("Expected ServiceAccount auth"),
-                }
-            }
-            _ => panic!("Expected GDrive connector"),
-        }
-
-        // Second mount overrides both root_folder_id and auth
-        match &config.mounts[1].connector {
-            ConnectorConfig::GDrive(gdrive) => {
-                assert_eq!(gdrive.root_folder_id, "custom-folder");
-                match &gdrive.auth {
-                    GDriveAuthConfig::Token { access_token } => {
-                        assert_eq!(access_token, "custom-token");
-                    }
-                    _ => panic!("Expected Token auth"),
-                }
-            }
-            _ => panic!("Expected GDrive connector"),
-        }
+        assert!(config.mounts[0].operation_timeout.is_none());
     }
 
     #[test]
-    fn test_gdrive_missing_auth_error() {
+    fn test_shutdown_timeout() {
         let yaml = r#"
 mounts:
-  - path: /mnt/gdrive
+  - path: /mnt/s3
     connector:
-      type: gdrive
-      root_folder_id: "root"
+      type: s3
+      bucket: my-bucket
 "#;
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.shutdown_timeout, std::time::Duration::from_secs(30));
 
-        let result = Config::parse(yaml);
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(
-            err.to_string().contains("no auth specified"),
-            "Error should mention missing auth: {}",
-            err
-        );
+        let yaml = r#"
+shutdown_timeout: 2m
+mounts:
+  - path: /mnt/s3
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        let config = Config::parse(yaml).unwrap();
+        assert_eq!(config.shutdown_timeout, std::time::Duration::from_secs(120));
     }
 
     #[test]
-    fn test_error_mode_default() {
+    fn test_logging_file() {
         let yaml = r#"
+logging:
+  level: debug
+  file: /var/log/fuse-adapter.log
 mounts:
-  - path: /mnt/data
+  - path: /mnt/s3
     connector:
       type: s3
       bucket: my-bucket
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.error_mode, ErrorMode::Continue);
+        assert_eq!(
+            config.logging.file,
+            Some(PathBuf::from("/var/log/fuse-adapter.log"))
+        );
+        assert_eq!(config.logging.output(), LogOutput::File);
+        assert_eq!(config.logging.format, LogFormat::Text);
+        assert_eq!(config.logging.rotation, LogRotationConfig::default());
     }
 
     #[test]
-    fn test_error_mode_continue() {
+    fn test_logging_json_rotation() {
         let yaml = r#"
-error_mode: continue
-
+logging:
+  format: json
+  output: file
+  file: /var/log/fuse-adapter.log
+  rotation:
+    max_size: 100MB
+    interval: daily
+    keep: 7
 mounts:
-  - path: /mnt/data
+  - path: /mnt/s3
     connector:
       type: s3
       bucket: my-bucket
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.error_mode, ErrorMode::Continue);
+        config.validate().unwrap();
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(
+            config.logging.rotation,
+            LogRotationConfig {
+                max_size: Some("100MB".to_string()),
+                interval: RotationInterval::Daily,
+                keep: 7,
+            }
+        );
+
+        let stderr = Config::parse(&yaml.replace("output: file", "output: stderr")).unwrap();
+        assert_eq!(stderr.logging.output(), LogOutput::Stderr);
+
+        for invalid in [
+            yaml.replace("  file: /var/log/fuse-adapter.log\n", ""),
+            yaml.replace("100MB", "lots"),
+            yaml.replace("keep: 7", "keep: 0"),
+        ] {
+            assert!(Config::parse(&invalid).unwrap().validate().is_err());
+        }
+        assert!(Config::parse(&yaml.replace("daily", "weekly")).is_err());
     }
 
     #[test]
-    fn test_error_mode_exit() {
+    fn test_tracing_config() {
         let yaml = r#"
-error_mode: exit
-
+tracing:
+  otlp_endpoint: http://localhost:4318
 mounts:
-  - path: /mnt/data
+  - path: /mnt/s3
     connector:
       type: s3
       bucket: my-bucket
 "#;
-
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.error_mode, ErrorMode::Exit);
+        assert_eq!(
+            config.tracing,
+            TracingConfig {
+                otlp_endpoint: Some("http://localhost:4318".to_string()),
+                service_name: "fuse-adapter".to_string(),
+            }
+        );
+
+        let config =
+            Config::parse(&yaml.replace("tracing:\n  otlp_endpoint: http://localhost:4318\n", ""))
+                .unwrap();
+        assert_eq!(config.tracing.otlp_endpoint, None);
     }
 
     #[test]
-    fn test_per_mount_error_mode_override() {
+    fn test_cache_metadata_ttl() {
         let yaml = r#"
-error_mode: continue
-
 mounts:
-  - path: /mnt/critical
-    error_mode: exit
+  - path: /mnt/default
     connector:
       type: s3
-      bucket: production
-  - path: /mnt/optional
+      bucket: my-bucket
+    cache:
+      type: memory
+  - path: /mnt/tuned
     connector:
       type: s3
-      bucket: cache
+      bucket: my-bucket
+    cache:
+      type: filesystem
+      path: /tmp/cache
+      metadata_ttl: 5m
+      negative_ttl: 1s
 "#;
 
         let config = Config::parse(yaml).unwrap();
-        assert_eq!(config.error_mode, ErrorMode::Continue); // Global default
-
-        // First mount overrides to exit
-        assert_eq!(config.mounts[0].error_mode, ErrorMode::Exit);
-        // Second mount inherits global default
-        assert_eq!(config.mounts[1].error_mode, ErrorMode::Continue);
+        assert!(matches!(
+            config.mounts[0].cache,
+            CacheConfig::Memory {
+                metadata_ttl: None,
+                negative_ttl: None,
+                ..
+            }
+        ));
+        match &config.mounts[1].cache {
+            CacheConfig::Filesystem {
+                metadata_ttl,
+                negative_ttl,
+                ..
+            } => {
+                assert_eq!(*metadata_ttl, Some(std::time::Duration::from_secs(300)));
+                assert_eq!(*negative_ttl, Some(std::time::Duration::from_secs(1)));
+            }
+            _ => panic!("Expected filesystem cache"),
+        }
     }
 
     #[test]
-    fn test_status_overlay_with_defaults() {
+    fn test_cache_revalidate() {
         let yaml = r#"
 mounts:
-  - path: /mnt/data
-    status_overlay: {}
+  - path: /mnt/default
+    connector:
+      type: s3
+      bucket: my-bucket
+    cache:
+      type: memory
+  - path: /mnt/etag
     connector:
       type: s3
       bucket: my-bucket
+    cache:
+      type: filesystem
+      path: /tmp/cache
+      revalidate: etag
+      strict_fsync: true
+      scrub_interval: 15m
+      min_free_space: 5GB
+      dedup: true
 "#;
 
         let config = Config::parse(yaml).unwrap();
-        let overlay = config.mounts[0].status_overlay.as_ref().unwrap();
-        assert_eq!(overlay.prefix, ".fuse-adapter");
-        assert_eq!(overlay.max_log_entries, 1000);
+        assert!(matches!(
+            config.mounts[0].cache,
+            CacheConfig::Memory {
+                revalidate: RevalidationMode::None,
+                strict_fsync: false,
+                scrub_interval: None,
+                ..
+            }
+        ));
+        assert!(matches!(
+            config.mounts[1].cache,
+            CacheConfig::Filesystem {
+                revalidate: RevalidationMode::Etag,
+                strict_fsync: true,
+                ..
+            }
+        ));
+        match &config.mounts[1].cache {
+            CacheConfig::Filesystem {
+                scrub_interval,
+                min_free_space,
+                dedup,
+                ..
+            } => {
+                assert_eq!(*scrub_interval, Some(std::time::Duration::from_secs(900)));
+                assert_eq!(min_free_space.as_deref(), Some("5GB"));
+                assert!(dedup);
+            }
+            _ => panic!("Expected filesystem cache"),
+        }
     }
 
     #[test]
-    fn test_status_overlay_with_custom_values() {
+    fn test_cache_sync_schedule() {
         let yaml = r#"
 mounts:
-  - path: /mnt/data
-    status_overlay:
-      prefix: ".status"
-      max_log_entries: 500
+  - path: /mnt/laptop
     connector:
       type: s3
       bucket: my-bucket
+    cache:
+      type: memory
+      sync_schedule:
+        pause_during:
+          - "08:00-18:00"
+          - "22:30-06:00"
+        pause_when_metered: true
+      sync_large_file_threshold: 16MB
+      sync_max_inflight: 64MB
 "#;
 
         let config = Config::parse(yaml).unwrap();
-        let overlay = config.mounts[0].status_overlay.as_ref().unwrap();
-        assert_eq!(overlay.prefix, ".status");
-        assert_eq!(overlay.max_log_entries, 500);
+        match &config.mounts[0].cache {
+            CacheConfig::Memory {
+                sync_schedule,
+                sync_large_file_threshold,
+                sync_max_inflight,
+                ..
+            } => {
+                assert_eq!(sync_large_file_threshold.as_deref(), Some("16MB"));
+                assert_eq!(sync_max_inflight.as_deref(), Some("64MB"));
+                let windows: Vec<String> = sync_schedule
+                    .pause_during
+                    .iter()
+                    .map(|w| w.to_string())
+                    .collect();
+                assert_eq!(windows, vec!["08:00-18:00", "22:30-06:00"]);
+                assert!(sync_schedule.pause_when_metered);
+            }
+            _ => panic!("Expected memory cache"),
+        }
+
+        let invalid = yaml.replace("22:30-06:00", "late");
+        assert!(Config::parse(&invalid).is_err());
     }
 
     #[test]
-    fn test_status_overlay_not_present() {
+    fn test_versions_overlay() {
         let yaml = r#"
 mounts:
-  - path: /mnt/data
+  - path: /mnt/default
+    versions_overlay: {}
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/custom
+    versions_overlay:
+      suffix: "@history"
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/none
+    connector:
+      type: s3
+      bucket: my-bucket
+  - path: /mnt/grouped
+    versions_overlay:
+      directory: .versions
     connector:
       type: s3
       bucket: my-bucket
 "#;
 
         let config = Config::parse(yaml).unwrap();
-        assert!(config.mounts[0].status_overlay.is_none());
+        let overlay = config.mounts[0].versions_overlay.as_ref().unwrap();
+        assert_eq!(overlay.suffix, ".versions");
+        assert!(overlay.directory.is_none());
+        let overlay = config.mounts[1].versions_overlay.as_ref().unwrap();
+        assert_eq!(overlay.suffix, "@history");
+        assert!(config.mounts[2].versions_overlay.is_none());
+        let overlay = config.mounts[3].versions_overlay.as_ref().unwrap();
+        assert_eq!(overlay.directory.as_deref(), Some(".versions"));
+
+        let yaml = r#"
+mounts:
+  - path: /mnt/bad
+    versions_overlay:
+      directory: a/b
+    connector:
+      type: s3
+      bucket: my-bucket
+"#;
+        assert!(Config::parse(yaml).unwrap().validate().is_err());
     }
 
     #[test]
@@ -1301,4 +6427,421 @@ mounts:
         assert_eq!(config.mounts[0].uid, Some(500));
         assert_eq!(config.mounts[0].gid, None);
     }
+
+    fn problems(result: Result<Config, ConfigError>) -> Vec<ConfigProblem> {
+        match result {
+            Err(ConfigError::Invalid(problems)) => problems,
+            other => panic!("expected config problems, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields_rejected() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/s3
+    connector:
+      type: s3
+      bucket: my-bucket
+    cache:
+      type: memory
+      max_size: 100MB
+"#;
+        assert!(Config::parse(yaml).is_ok());
+
+        for (from, to) in [
+            ("mounts:", "lgging:\n  level: debug\nmounts:"),
+            ("    cache:", "    cahce: {}\n    cache:"),
+            (
+                "max_size: 100MB",
+                "max_size: 100MB\n      flush_intervall: 30s",
+            ),
+            (
+                "bucket: my-bucket",
+                "bucket: my-bucket\n      regoin: us-east-1",
+            ),
+        ] {
+            let problems = problems(Config::parse(&yaml.replace(from, to)));
+            assert_eq!(problems.len(), 1, "{:?}", problems);
+            assert!(
+                problems[0].message.contains("unknown field"),
+                "{:?}",
+                problems
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_problems_in_every_mount() {
+        let yaml = r#"
+mounts:
+  - path: /mnt/a
+    connector:
+      type: s3
+      bucket: a
+    cache:
+      type: disk
+  - path: /mnt/b
+    connector:
+      type: s3
+      bucket: b
+  - path: /mnt/c
+    connector:
+      type: s3
+      bucket: c
+    read_only: maybe
+"#;
+        let problems = problems(Config::parse(yaml));
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert_eq!(problems[0].location.map(|(line, _)| line), Some(8));
+        assert!(problems[0].message.contains("unknown variant `disk`"));
+        assert_eq!(problems[1].location.map(|(line, _)| line), Some(17));
+
+        let message = ConfigError::Invalid(problems).to_string();
+        assert!(message.starts_with("Invalid configuration:\n  line 8, column"));
+        assert_eq!(message.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_check_reports_all_problems_with_locations() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let yaml = format!(
+            r#"
+mounts:
+  - path: mnt/relative
+    connector:
+      type: memfs
+  - path: /mnt/cached
+    connector:
+      type: memfs
+    cache:
+      type: filesystem
+      path: {}
+      max_size: lots
+"#,
+            file.join("cache").display()
+        );
+        assert!(Config::parse(&yaml).is_ok());
+
+        let problems = problems(Config::check(&yaml));
+        let found: Vec<_> = problems
+            .iter()
+            .map(|p| (p.path.as_str(), p.location))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("mounts[0].path", Some((3, 11))),
+                ("mounts[1].cache.max_size", Some((12, 17))),
+                ("mounts[1].cache.path", Some((11, 13))),
+            ]
+        );
+        assert!(problems[0].message.contains("must be absolute"));
+        assert!(problems[1].message.contains("invalid cache max_size"));
+        assert!(problems[2].message.contains("is not a directory"));
+
+        // Without the source, problems are reported by path
+        let config = Config::parse(&yaml).unwrap();
+        let problems = problems_of(config.validate());
+        assert_eq!(
+            problems[0].to_string(),
+            "mounts[0].path: Mount \"mnt/relative\": mount path must be absolute"
+        );
+    }
+
+    fn problems_of(result: Result<(), ConfigError>) -> Vec<ConfigProblem> {
+        problems(result.map(|_| unreachable!()))
+    }
+
+    #[test]
+    fn test_locate() {
+        let yaml = "a:\n  b: [1, {c: 2}]\n  d:\n    - x\n    - y: z\n";
+        let at = |path: &str| locate(yaml, &parse_key_path(path));
+        assert_eq!(at("a.b[1].c"), Some((2, 14)));
+        assert_eq!(at("a.d[1].y"), Some((5, 10)));
+        assert_eq!(at("a.d"), Some((4, 5)));
+        assert_eq!(at("a.missing"), None);
+        assert_eq!(at("a.d[5]"), None);
+    }
+
+    #[test]
+    fn test_overrides_from_env_vars() {
+        let vars = [
+            ("FUSE_ADAPTER__MOUNTS__0__CACHE__MAX_SIZE", "1GB"),
+            ("HOME", "/root"),
+            ("FUSE_ADAPTER__MOUNTS__0__CACHE__TYPE", "memory"),
+            ("FUSE_ADAPTER__LOGGING__LEVEL", "debug"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let overrides = ConfigOverride::from_vars(vars);
+        let keys: Vec<_> = overrides.iter().map(|o| o.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "logging.level",
+                "mounts.0.cache.type",
+                "mounts.0.cache.max_size"
+            ]
+        );
+
+        let set: ConfigOverride = "mounts[1].path=/mnt/a=b".parse().unwrap();
+        assert_eq!(set.keys(), ["mounts", "1", "path"]);
+        assert_eq!(set.value, "/mnt/a=b");
+        assert!("novalue".parse::<ConfigOverride>().is_err());
+        assert!("=value".parse::<ConfigOverride>().is_err());
+    }
+
+    #[test]
+    fn test_override_apply() {
+        let mut root: serde_yaml::Value = serde_yaml::from_str(
+            "mounts:\n  - path: /mnt/a\n    cache:\n      type: memory\n      max_size: 1GB\n",
+        )
+        .unwrap();
+        let set = |root: &mut serde_yaml::Value, s: &str| {
+            s.parse::<ConfigOverride>().unwrap().apply(root)
+        };
+
+        set(&mut root, "mounts.0.cache.max_size=2GB").unwrap();
+        set(&mut root, "mounts.0.read_only=true").unwrap();
+        assert_eq!(root["mounts"][0]["cache"]["max_size"], "2GB");
+        assert_eq!(root["mounts"][0]["read_only"], true);
+
+        // Re-setting the type keeps options; changing it drops them
+        set(&mut root, "mounts.0.cache.type=memory").unwrap();
+        assert_eq!(root["mounts"][0]["cache"]["max_size"], "2GB");
+        set(&mut root, "mounts.0.cache.type=none").unwrap();
+        assert!(root["mounts"][0]["cache"].get("max_size").is_none());
+
+        // One past the end appends
+        set(&mut root, "mounts.1.path=/mnt/b").unwrap();
+        assert_eq!(root["mounts"][1]["path"], "/mnt/b");
+        assert!(set(&mut root, "mounts.5.path=/mnt/c")
+            .unwrap_err()
+            .contains("past the end"));
+        assert!(set(&mut root, "mounts.x.path=/mnt/c").is_err());
+        assert!(set(&mut root, "mounts.0.path.deeper=1").is_err());
+    }
+
+    #[test]
+    fn test_load_with_overrides() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(
+            &path,
+            "logging:\n  level: info\nmounts:\n  - path: /mnt/a\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+
+        // Later overrides win
+        let mut source = ConfigSource::new(path);
+        source.overrides = ["logging.level=debug", "logging.level=trace"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let config = Config::load(&source).unwrap();
+        assert_eq!(config.logging.level, "trace");
+
+        // Problems in overridden values are reported by key
+        source.overrides = vec!["mounts.0.path=relative".parse().unwrap()];
+        let found = problems(Config::load(&source));
+        assert_eq!(found[0].path, "mounts[0].path");
+        assert_eq!(found[0].location, None);
+
+        source.overrides = vec!["logging.level.deeper=1".parse().unwrap()];
+        let found = problems(Config::load(&source));
+        assert!(found[0]
+            .message
+            .starts_with("can't override logging.level.deeper"));
+    }
+
+    #[test]
+    fn test_config_format() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a/config.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!("yml".parse(), Ok(ConfigFormat::Yaml));
+        assert_eq!("JSON".parse(), Ok(ConfigFormat::Json));
+        assert!("ini".parse::<ConfigFormat>().is_err());
+    }
+
+    #[test]
+    fn test_load_json_and_toml() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let json = dir.path().join("config.json");
+        std::fs::write(
+            &json,
+            r#"{
+  "logging": {"level": "debug"},
+  "mounts": [
+    {"path": "/mnt/a", "connector": {"type": "memfs"}, "cache": {"type": "memory", "max_size": "1GB"}}
+  ]
+}"#,
+        )
+        .unwrap();
+        let toml = dir.path().join("config.toml");
+        std::fs::write(
+            &toml,
+            r#"
+[logging]
+level = "debug"
+
+[[mounts]]
+path = "/mnt/a"
+connector = { type = "memfs" }
+cache = { type = "memory", max_size = "1GB" }
+"#,
+        )
+        .unwrap();
+
+        for path in [&json, &toml] {
+            let config = Config::load(&ConfigSource::new(path.clone())).unwrap();
+            assert_eq!(config.logging.level, "debug");
+            assert_eq!(config.mounts[0].path, PathBuf::from("/mnt/a"));
+            assert!(matches!(config.mounts[0].cache, CacheConfig::Memory { .. }));
+        }
+
+        // The same file read as another format fails
+        let mut source = ConfigSource::new(toml.clone());
+        source.format = ConfigFormat::Json;
+        assert!(Config::load(&source).is_err());
+
+        // Problems are reported by key, syntax errors by position
+        std::fs::write(
+            &json,
+            r#"{"mounts": [{"path": "relative", "connector": {"type": "memfs"}}]}"#,
+        )
+        .unwrap();
+        let found = problems(Config::load(&ConfigSource::new(json.clone())));
+        assert_eq!(found[0].path, "mounts[0].path");
+        assert_eq!(found[0].location, None);
+
+        std::fs::write(&json, "{\n  \"mounts\": [,]\n}").unwrap();
+        let found = problems(Config::load(&ConfigSource::new(json)));
+        assert_eq!(found[0].location, Some((2, 14)));
+
+        std::fs::write(&toml, "[logging]\nlevel = \n").unwrap();
+        let found = problems(Config::load(&ConfigSource::new(toml)));
+        assert_eq!(found[0].location.map(|(line, _)| line), Some(2));
+    }
+
+    #[test]
+    fn test_load_includes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let teams = dir.path().join("teams");
+        std::fs::create_dir(&teams).unwrap();
+        let main = dir.path().join("main.yaml");
+        std::fs::write(
+            &main,
+            "include: [teams]\nlogging:\n  level: debug\nmounts:\n  - path: /mnt/main\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+        std::fs::write(
+            teams.join("a.yaml"),
+            "logging:\n  level: debug\n  format: json\nmounts:\n  - path: /mnt/a\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+        std::fs::write(
+            teams.join("b.json"),
+            r#"{"mounts": [{"path": "/mnt/b", "connector": {"type": "memfs"}}]}"#,
+        )
+        .unwrap();
+        std::fs::write(teams.join("notes.txt"), "not config").unwrap();
+
+        let config = Config::load(&ConfigSource::new(main.clone())).unwrap();
+        let paths: Vec<_> = config.mounts.iter().map(|m| m.path.clone()).collect();
+        assert_eq!(paths, ["/mnt/main", "/mnt/a", "/mnt/b"].map(PathBuf::from));
+        assert_eq!(config.logging.format, LogFormat::Json);
+
+        // A directory on its own works too
+        let config = Config::load(&ConfigSource::new(teams.clone())).unwrap();
+        assert_eq!(config.mounts.len(), 2);
+
+        // Parsing a string can't follow includes
+        let content = std::fs::read_to_string(&main).unwrap();
+        assert!(Config::parse(&content).is_err());
+    }
+
+    #[test]
+    fn test_load_includes_reports_problems_by_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let main = dir.path().join("main.yaml");
+        let a = dir.path().join("a.yaml");
+        let b = dir.path().join("b.yaml");
+        std::fs::write(
+            &main,
+            "include: [a.yaml, b.yaml]\nmounts:\n  - path: /mnt/a\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &a,
+            "logging:\n  level: info\nmounts:\n  - path: /mnt/b\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &b,
+            "logging:\n  level: debug\nmounts:\n  - path: /mnt/c\n    connector:\n      type: memfs\n  - path: /mnt/a\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+
+        // Conflicting settings are reported where they're set again
+        let found = problems(Config::load(&ConfigSource::new(main.clone())));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].file.as_ref(), Some(&b));
+        assert_eq!(found[0].location, Some((2, 10)));
+        assert_eq!(
+            found[0].message,
+            format!("logging.level is already set in {}", a.display())
+        );
+
+        // Duplicate mounts are reported in the file and entry repeating them
+        std::fs::write(
+            &a,
+            "mounts:\n  - path: /mnt/b\n    connector:\n      type: memfs\n",
+        )
+        .unwrap();
+        let found = problems(Config::load(&ConfigSource::new(main.clone())));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, "mounts[1].path");
+        assert_eq!(found[0].location, Some((7, 11)));
+        assert!(found[0].to_string().starts_with(&format!(
+            "{}: line 7, column 11: Duplicate mount path",
+            b.display()
+        )));
+
+        // Unknown keys in any file, unreadable and repeated includes
+        std::fs::write(&b, "include: [main.yaml, missing.yaml]\nmounts:\n  - path: /mnt/c\n    connector:\n      type: memfs\n      bukcet: x\n")
+            .unwrap();
+        let found = problems(Config::load(&ConfigSource::new(main)));
+        let found: Vec<_> = found
+            .iter()
+            .map(|p| {
+                (
+                    p.file.as_deref(),
+                    p.path.as_str(),
+                    p.location.map(|(l, _)| l),
+                )
+            })
+            .collect();
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found.contains(&(Some(dir.path().join("main.yaml").as_path()), "", None)));
+        assert!(found.contains(&(Some(b.as_path()), "include[1]", Some(1))));
+        assert!(found
+            .iter()
+            .any(|&(file, _, line)| file == Some(b.as_path()) && line == Some(3)));
+    }
 }
@@ -0,0 +1,1139 @@
+//! Read-only connector serving the members of a zip or tar archive
+//!
+//! The archive is a file on another connector (e.g. an S3 object). Its index
+//! is read on first access: a zip's central directory is fetched with a few
+//! range reads from the end of the file, while a tar has no index, so its
+//! member headers are read one after another (skipping member data unless
+//! the tar is gzipped). Directories that only appear in member paths are
+//! filled in.
+//!
+//! Stored zip members and members of plain tars are served with range reads
+//! of the archive. Deflated zip members and members of gzipped tars can't be
+//! read from the middle, so they are decompressed whole when first read and
+//! the last few are kept in memory.
+//!
+//! The index isn't refreshed; remount to pick up a changed archive.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use flate2::read::DeflateDecoder;
+use flate2::write::GzDecoder;
+use parking_lot::Mutex;
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+use crate::config::ArchiveFormat;
+use crate::connector::{
+    Capabilities, Connector, DirEntry, DirEntryStream, FileType, Metadata, COPY_CHUNK_SIZE,
+};
+use crate::error::{FuseAdapterError, Result};
+
+/// Decompressed members kept in memory
+const MEMBER_CACHE_ENTRIES: usize = 4;
+/// Read-ahead when walking the headers of a plain tar
+const TAR_READ_AHEAD: u64 = 64 * 1024;
+/// Read size when decompressing a gzipped tar
+const GZIP_READ_AHEAD: u64 = 1024 * 1024;
+/// Size of tar headers and the unit member data is padded to
+const TAR_BLOCK: u64 = 512;
+
+const ZIP_EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP_CENTRAL_SIGNATURE: u32 = 0x0201_4b50;
+const ZIP_LOCAL_SIGNATURE: u32 = 0x0403_4b50;
+/// End of central directory record without the trailing comment
+const ZIP_EOCD_LEN: u64 = 22;
+/// Longest comment a zip can end with
+const ZIP_MAX_COMMENT: u64 = 65535;
+const ZIP_METHOD_STORED: u16 = 0;
+const ZIP_METHOD_DEFLATED: u16 = 8;
+
+/// Where a member's content is in the archive
+#[derive(Debug)]
+enum Location {
+    Zip {
+        /// Offset of the member's local header
+        header_offset: u64,
+        compressed_size: u64,
+        method: u16,
+        encrypted: bool,
+        /// Offset of the data, once the local header has been read
+        data_offset: OnceLock<u64>,
+    },
+    Tar {
+        /// Offset of the data in the (decompressed) tar
+        offset: u64,
+    },
+}
+
+/// A file or directory as listed by the archive
+struct RawEntry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    mtime: SystemTime,
+    mode: Option<u32>,
+    location: Option<Location>,
+}
+
+enum Entry {
+    Dir(Metadata),
+    File(Metadata, Location),
+}
+
+/// The archive's members by path
+struct Index {
+    /// Size of the archive file
+    size: u64,
+    entries: HashMap<PathBuf, Entry>,
+    /// Names in each directory
+    children: HashMap<PathBuf, BTreeMap<OsString, FileType>>,
+}
+
+impl Index {
+    fn new(size: u64, mtime: SystemTime, raw: Vec<RawEntry>) -> Self {
+        let mut index = Self {
+            size,
+            entries: HashMap::new(),
+            children: HashMap::new(),
+        };
+        index
+            .entries
+            .insert(PathBuf::from("/"), Entry::Dir(Metadata::directory(mtime)));
+
+        for entry in raw {
+            let Some(path) = member_path(&entry.name) else {
+                warn!("Skipping archive member {:?}", entry.name);
+                continue;
+            };
+            if path == Path::new("/") {
+                continue;
+            }
+            index.add_parents(&path, mtime);
+            let (metadata, file_type) = if entry.is_dir {
+                let metadata = match entry.mode {
+                    Some(mode) => Metadata::directory_with_mode(entry.mtime, mode),
+                    None => Metadata::directory(entry.mtime),
+                };
+                (Entry::Dir(metadata), FileType::Directory)
+            } else {
+                let metadata = match entry.mode {
+                    Some(mode) => Metadata::file_with_mode(entry.size, entry.mtime, mode),
+                    None => Metadata::file(entry.size, entry.mtime),
+                };
+                let Some(location) = entry.location else {
+                    continue;
+                };
+                (Entry::File(metadata, location), FileType::File)
+            };
+            index.add_child(&path, file_type);
+            index.entries.insert(path, metadata);
+        }
+        index
+    }
+
+    /// Create the directories above `path` that the archive doesn't list
+    fn add_parents(&mut self, path: &Path, mtime: SystemTime) {
+        let parents: Vec<PathBuf> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|p| *p != Path::new("/"))
+            .map(Path::to_path_buf)
+            .collect();
+        for parent in parents.into_iter().rev() {
+            if !matches!(self.entries.get(&parent), Some(Entry::Dir(_))) {
+                self.add_child(&parent, FileType::Directory);
+                self.entries
+                    .insert(parent, Entry::Dir(Metadata::directory(mtime)));
+            }
+        }
+    }
+
+    fn add_child(&mut self, path: &Path, file_type: FileType) {
+        if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+            self.children
+                .entry(parent.to_path_buf())
+                .or_default()
+                .insert(name.to_os_string(), file_type);
+        }
+    }
+
+    fn get(&self, path: &Path) -> Result<&Entry> {
+        self.entries
+            .get(path)
+            .ok_or_else(|| FuseAdapterError::NotFound(path.display().to_string()))
+    }
+}
+
+/// The mount path of an archive member, or None if it would escape the root
+fn member_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::from("/");
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+fn corrupt(message: impl std::fmt::Display) -> FuseAdapterError {
+    FuseAdapterError::Backend(format!("Corrupt archive: {}", message))
+}
+
+/// Bytes `at..at + N` of `data`, failing if the record is cut short
+fn field<const N: usize>(data: &[u8], at: usize) -> Result<[u8; N]> {
+    data.get(at..at + N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| corrupt("record cut short"))
+}
+
+fn le16(data: &[u8], at: usize) -> Result<u16> {
+    Ok(u16::from_le_bytes(field(data, at)?))
+}
+
+fn le32(data: &[u8], at: usize) -> Result<u32> {
+    Ok(u32::from_le_bytes(field(data, at)?))
+}
+
+fn le64(data: &[u8], at: usize) -> Result<u64> {
+    Ok(u64::from_le_bytes(field(data, at)?))
+}
+
+/// Read exactly `len` bytes of `path` starting at `offset`
+async fn read_exact(source: &dyn Connector, path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(len as usize);
+    while (data.len() as u64) < len {
+        let remaining = len - data.len() as u64;
+        let chunk = source
+            .read(
+                path,
+                offset + data.len() as u64,
+                remaining.min(COPY_CHUNK_SIZE as u64) as u32,
+            )
+            .await?;
+        if chunk.is_empty() {
+            return Err(corrupt("unexpected end of file"));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Convert an MS-DOS date and time (local time, taken as UTC)
+fn dos_time(date: u16, time: u16) -> SystemTime {
+    chrono::NaiveDate::from_ymd_opt(
+        1980 + (date >> 9) as i32,
+        ((date >> 5) & 0xf) as u32,
+        (date & 0x1f) as u32,
+    )
+    .and_then(|d| {
+        d.and_hms_opt(
+            (time >> 11) as u32,
+            ((time >> 5) & 0x3f) as u32,
+            ((time & 0x1f) * 2) as u32,
+        )
+    })
+    .map_or(UNIX_EPOCH, |dt| SystemTime::from(dt.and_utc()))
+}
+
+/// Read a zip's central directory
+async fn read_zip_index(source: &dyn Connector, path: &Path, size: u64) -> Result<Vec<RawEntry>> {
+    // The end of central directory record is followed only by a comment
+    let tail_len = size.min(ZIP_EOCD_LEN + ZIP_MAX_COMMENT);
+    let tail_start = size - tail_len;
+    let tail = read_exact(source, path, tail_start, tail_len).await?;
+    let eocd = (0..=tail.len().saturating_sub(ZIP_EOCD_LEN as usize))
+        .rev()
+        .find(|&i| le32(&tail, i).ok() == Some(ZIP_EOCD_SIGNATURE))
+        .ok_or_else(|| corrupt("no zip end of central directory record"))?;
+
+    let mut count = le16(&tail, eocd + 10)? as u64;
+    let mut cd_size = le32(&tail, eocd + 12)? as u64;
+    let mut cd_offset = le32(&tail, eocd + 16)? as u64;
+    if count == 0xffff || cd_size == 0xffff_ffff || cd_offset == 0xffff_ffff {
+        // Zip64: the real values are in a record found through the locator
+        // right before the end of central directory record
+        let locator_offset = (tail_start + eocd as u64)
+            .checked_sub(20)
+            .ok_or_else(|| corrupt("missing zip64 locator"))?;
+        let locator = read_exact(source, path, locator_offset, 20).await?;
+        if le32(&locator, 0)? != ZIP64_LOCATOR_SIGNATURE {
+            return Err(corrupt("missing zip64 locator"));
+        }
+        let record = read_exact(source, path, le64(&locator, 8)?, 56).await?;
+        if le32(&record, 0)? != ZIP64_EOCD_SIGNATURE {
+            return Err(corrupt("missing zip64 end of central directory record"));
+        }
+        count = le64(&record, 32)?;
+        cd_size = le64(&record, 40)?;
+        cd_offset = le64(&record, 48)?;
+    }
+    if cd_offset + cd_size > size {
+        return Err(corrupt("central directory past end of file"));
+    }
+
+    let cd = read_exact(source, path, cd_offset, cd_size).await?;
+    let mut entries = Vec::with_capacity(count.min(1 << 20) as usize);
+    let mut p = 0;
+    for _ in 0..count {
+        if le32(&cd, p)? != ZIP_CENTRAL_SIGNATURE {
+            return Err(corrupt("bad central directory header"));
+        }
+        let made_by = le16(&cd, p + 4)?;
+        let flags = le16(&cd, p + 8)?;
+        let method = le16(&cd, p + 10)?;
+        let mtime = dos_time(le16(&cd, p + 14)?, le16(&cd, p + 12)?);
+        let mut compressed_size = le32(&cd, p + 20)? as u64;
+        let mut size = le32(&cd, p + 24)? as u64;
+        let name_len = le16(&cd, p + 28)? as usize;
+        let extra_len = le16(&cd, p + 30)? as usize;
+        let comment_len = le16(&cd, p + 32)? as usize;
+        let external = le32(&cd, p + 38)?;
+        let mut header_offset = le32(&cd, p + 42)? as u64;
+        let name_start = p + 46;
+        let extra_start = name_start + name_len;
+        let name = cd
+            .get(name_start..extra_start)
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .ok_or_else(|| corrupt("record cut short"))?;
+
+        // Sizes and offset that don't fit 32 bits are in the zip64 extra
+        // field, in this order, if their 32-bit field is saturated
+        let extra = cd
+            .get(extra_start..extra_start + extra_len)
+            .ok_or_else(|| corrupt("record cut short"))?;
+        let mut e = 0;
+        while e + 4 <= extra.len() {
+            let id = le16(extra, e)?;
+            let len = le16(extra, e + 2)? as usize;
+            if id == 0x0001 {
+                let mut at = e + 4;
+                for value in [&mut size, &mut compressed_size, &mut header_offset] {
+                    if *value == 0xffff_ffff {
+                        *value = le64(extra, at)?;
+                        at += 8;
+                    }
+                }
+            }
+            e += 4 + len;
+        }
+
+        // Unix zips keep the mode in the upper half of the external attributes
+        let unix_mode = (made_by >> 8 == 3 && external >> 16 != 0).then_some(external >> 16);
+        let is_symlink = unix_mode.is_some_and(|m| m & libc::S_IFMT == libc::S_IFLNK);
+        let is_dir =
+            name.ends_with('/') || unix_mode.is_some_and(|m| m & libc::S_IFMT == libc::S_IFDIR);
+        if !is_symlink {
+            entries.push(RawEntry {
+                name,
+                is_dir,
+                size,
+                mtime,
+                mode: unix_mode.map(|m| m & 0o7777),
+                location: Some(Location::Zip {
+                    header_offset,
+                    compressed_size,
+                    method,
+                    encrypted: flags & 1 != 0,
+                    data_offset: OnceLock::new(),
+                }),
+            });
+        }
+        p = extra_start + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// Sequential reader over an archive's content, gunzipping it if needed
+struct ArchiveStream<'a> {
+    source: &'a dyn Connector,
+    path: &'a Path,
+    size: u64,
+    /// Next offset of the archive file to fetch
+    pos: u64,
+    read_ahead: u64,
+    decoder: Option<GzDecoder<Vec<u8>>>,
+    buf: Vec<u8>,
+    /// Start of the unconsumed part of `buf`
+    start: usize,
+    /// Stream offset of the next unconsumed byte
+    offset: u64,
+}
+
+impl<'a> ArchiveStream<'a> {
+    fn new(source: &'a dyn Connector, path: &'a Path, size: u64, gzip: bool) -> Self {
+        Self {
+            source,
+            path,
+            size,
+            pos: 0,
+            read_ahead: if gzip {
+                GZIP_READ_AHEAD
+            } else {
+                TAR_READ_AHEAD
+            },
+            decoder: gzip.then(|| GzDecoder::new(Vec::new())),
+            buf: Vec::new(),
+            start: 0,
+            offset: 0,
+        }
+    }
+
+    fn buffered(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    /// Append more content to the buffer; Ok(false) at the end of the archive
+    async fn fill(&mut self) -> Result<bool> {
+        if self.pos >= self.size {
+            return Ok(false);
+        }
+        let len = (self.size - self.pos).min(self.read_ahead);
+        let chunk = self.source.read(self.path, self.pos, len as u32).await?;
+        if chunk.is_empty() {
+            return Err(corrupt("unexpected end of file"));
+        }
+        self.pos += chunk.len() as u64;
+
+        self.buf.drain(..self.start);
+        self.start = 0;
+        match &mut self.decoder {
+            None => self.buf.extend_from_slice(&chunk),
+            Some(decoder) => {
+                decoder.write_all(&chunk).map_err(corrupt)?;
+                if self.pos >= self.size {
+                    decoder.try_finish().map_err(corrupt)?;
+                }
+                self.buf.append(decoder.get_mut());
+            }
+        }
+        Ok(true)
+    }
+
+    /// The next `n` bytes, or None if the archive ends first
+    async fn take(&mut self, n: usize) -> Result<Option<Vec<u8>>> {
+        while self.buffered() < n {
+            if !self.fill().await? {
+                return Ok(None);
+            }
+        }
+        let data = self.buf[self.start..self.start + n].to_vec();
+        self.start += n;
+        self.offset += n as u64;
+        Ok(Some(data))
+    }
+
+    /// Skip `n` bytes; plain archives seek past them without reading
+    async fn skip(&mut self, n: u64) -> Result<()> {
+        let mut remaining = n;
+        loop {
+            let buffered = self.buffered() as u64;
+            if remaining <= buffered {
+                self.start += remaining as usize;
+                break;
+            }
+            remaining -= buffered;
+            self.buf.clear();
+            self.start = 0;
+            if self.decoder.is_none() {
+                self.pos += remaining;
+                break;
+            }
+            if !self.fill().await? {
+                return Err(corrupt("unexpected end of file"));
+            }
+        }
+        self.offset += n;
+        Ok(())
+    }
+}
+
+/// Parse a numeric tar header field (octal, or base-256 for large values)
+fn tar_number(field: &[u8]) -> Result<u64> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        let mut value = (field[0] & 0x7f) as u64;
+        for &b in &field[1..] {
+            value = value
+                .checked_mul(256)
+                .and_then(|v| v.checked_add(b as u64))
+                .ok_or_else(|| corrupt("tar number too large"))?;
+        }
+        return Ok(value);
+    }
+    let text = std::str::from_utf8(field).map_err(corrupt)?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(corrupt)
+}
+
+/// A NUL-terminated tar header string
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Parse pax extended header records (`<len> <key>=<value>\n`)
+fn pax_records(data: &[u8]) -> HashMap<String, String> {
+    let mut records = HashMap::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let Some(len) = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|l| l.parse::<usize>().ok())
+        else {
+            break;
+        };
+        if len <= space || len > rest.len() {
+            break;
+        }
+        let record = String::from_utf8_lossy(&rest[space + 1..len]);
+        if let Some((key, value)) = record.trim_end_matches('\n').split_once('=') {
+            records.insert(key.to_string(), value.to_string());
+        }
+        rest = &rest[len..];
+    }
+    records
+}
+
+/// Read the member headers of a tar
+async fn read_tar_index(stream: &mut ArchiveStream<'_>) -> Result<Vec<RawEntry>> {
+    let mut entries = Vec::new();
+    let mut long_name = None;
+    let mut pax = HashMap::new();
+    while let Some(header) = stream.take(TAR_BLOCK as usize).await? {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let checksum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+            .sum();
+        if tar_number(&header[148..156])? != checksum {
+            return Err(corrupt("bad tar header checksum"));
+        }
+
+        let typeflag = header[156];
+        let mut size = tar_number(&header[124..136])?;
+        let padding = size.next_multiple_of(TAR_BLOCK) - size;
+        match typeflag {
+            // GNU long name and pax headers apply to the next member
+            b'L' | b'x' => {
+                let data = stream
+                    .take(size as usize)
+                    .await?
+                    .ok_or_else(|| corrupt("unexpected end of file"))?;
+                if typeflag == b'L' {
+                    long_name = Some(tar_string(&data));
+                } else {
+                    pax = pax_records(&data);
+                }
+                stream.skip(padding).await?;
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut name = tar_string(&header[0..100]);
+        if &header[257..262] == b"ustar" {
+            let prefix = tar_string(&header[345..500]);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+        if let Some(long) = long_name.take() {
+            name = long;
+        }
+        if let Some(path) = pax.remove("path") {
+            name = path;
+        }
+        if let Some(pax_size) = pax.remove("size").and_then(|s| s.parse().ok()) {
+            size = pax_size;
+        }
+        let mtime = match pax.remove("mtime") {
+            Some(t) => t
+                .parse::<f64>()
+                .ok()
+                .filter(|t| *t >= 0.0)
+                .map_or(UNIX_EPOCH, |t| UNIX_EPOCH + Duration::from_secs_f64(t)),
+            None => UNIX_EPOCH + Duration::from_secs(tar_number(&header[136..148])?),
+        };
+        pax.clear();
+        let padding = size.next_multiple_of(TAR_BLOCK) - size;
+
+        let is_dir = typeflag == b'5' || (typeflag == 0 && name.ends_with('/'));
+        let is_file = matches!(typeflag, 0 | b'0' | b'7') && !is_dir;
+        if is_dir || is_file {
+            entries.push(RawEntry {
+                name,
+                is_dir,
+                size: if is_dir { 0 } else { size },
+                mtime,
+                mode: Some(tar_number(&header[100..108])? as u32 & 0o7777),
+                location: is_file.then(|| Location::Tar {
+                    offset: stream.offset,
+                }),
+            });
+        }
+        stream.skip(size + padding).await?;
+    }
+    Ok(entries)
+}
+
+struct Inner {
+    source: Arc<dyn Connector>,
+    /// Path of the archive on the source connector
+    archive: PathBuf,
+    format: ArchiveFormat,
+    index: OnceCell<Arc<Index>>,
+    /// Recently decompressed members, most recent last
+    members: Mutex<VecDeque<(PathBuf, Bytes)>>,
+}
+
+impl Inner {
+    /// The archive's index, reading it on first use
+    async fn index(&self) -> Result<Arc<Index>> {
+        self.index
+            .get_or_try_init(|| async {
+                let metadata = self.source.stat(&self.archive).await?;
+                if !metadata.is_file() {
+                    return Err(FuseAdapterError::IsADirectory(
+                        self.archive.display().to_string(),
+                    ));
+                }
+                let source = self.source.as_ref();
+                let entries = match self.format {
+                    ArchiveFormat::Zip => {
+                        read_zip_index(source, &self.archive, metadata.size).await?
+                    }
+                    ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+                        let gzip = self.format == ArchiveFormat::TarGz;
+                        let mut stream =
+                            ArchiveStream::new(source, &self.archive, metadata.size, gzip);
+                        read_tar_index(&mut stream).await?
+                    }
+                };
+                debug!(
+                    "Indexed {} members of archive {:?}",
+                    entries.len(),
+                    self.archive
+                );
+                Ok(Arc::new(Index::new(metadata.size, metadata.mtime, entries)))
+            })
+            .await
+            .cloned()
+    }
+
+    /// Offset of a zip member's data, read from its local header
+    async fn zip_data_offset(&self, header_offset: u64, cached: &OnceLock<u64>) -> Result<u64> {
+        if let Some(offset) = cached.get() {
+            return Ok(*offset);
+        }
+        let header = read_exact(self.source.as_ref(), &self.archive, header_offset, 30).await?;
+        if le32(&header, 0)? != ZIP_LOCAL_SIGNATURE {
+            return Err(corrupt("bad local file header"));
+        }
+        let offset = header_offset + 30 + le16(&header, 26)? as u64 + le16(&header, 28)? as u64;
+        Ok(*cached.get_or_init(|| offset))
+    }
+
+    /// The whole content of a compressed member
+    async fn decompressed(
+        &self,
+        index: &Index,
+        path: &Path,
+        metadata: &Metadata,
+        location: &Location,
+    ) -> Result<Bytes> {
+        {
+            let mut members = self.members.lock();
+            if let Some(i) = members.iter().position(|(p, _)| p == path) {
+                let member = members.remove(i).unwrap();
+                let data = member.1.clone();
+                members.push_back(member);
+                return Ok(data);
+            }
+        }
+
+        let data = match location {
+            Location::Zip {
+                header_offset,
+                compressed_size,
+                data_offset,
+                ..
+            } => {
+                let offset = self.zip_data_offset(*header_offset, data_offset).await?;
+                let compressed = read_exact(
+                    self.source.as_ref(),
+                    &self.archive,
+                    offset,
+                    *compressed_size,
+                )
+                .await?;
+                let mut data = Vec::with_capacity(metadata.size as usize);
+                DeflateDecoder::new(&compressed[..])
+                    .read_to_end(&mut data)
+                    .map_err(corrupt)?;
+                data
+            }
+            Location::Tar { offset } => {
+                let mut stream =
+                    ArchiveStream::new(self.source.as_ref(), &self.archive, index.size, true);
+                stream.skip(*offset).await?;
+                stream
+                    .take(metadata.size as usize)
+                    .await?
+                    .ok_or_else(|| corrupt("unexpected end of file"))?
+            }
+        };
+        if data.len() as u64 != metadata.size {
+            return Err(corrupt(format!("wrong size for member {:?}", path)));
+        }
+
+        let data = Bytes::from(data);
+        let mut members = self.members.lock();
+        members.push_back((path.to_path_buf(), data.clone()));
+        if members.len() > MEMBER_CACHE_ENTRIES {
+            members.pop_front();
+        }
+        Ok(data)
+    }
+}
+
+/// Connector exposing the members of an archive on another connector
+pub struct ArchiveConnector {
+    inner: Arc<Inner>,
+}
+
+impl ArchiveConnector {
+    /// Serve the archive at `archive` on `source`
+    pub fn new(source: Arc<dyn Connector>, archive: PathBuf, format: ArchiveFormat) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                source,
+                archive,
+                format,
+                index: OnceCell::new(),
+                members: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Connector for ArchiveConnector {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::read_only()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let index = self.inner.index().await?;
+        match index.get(path)? {
+            Entry::Dir(metadata) | Entry::File(metadata, _) => Ok(metadata.clone()),
+        }
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        let index = self.inner.index().await?;
+        let (metadata, location) = match index.get(path)? {
+            Entry::File(metadata, location) => (metadata, location),
+            Entry::Dir(_) => {
+                return Err(FuseAdapterError::IsADirectory(path.display().to_string()))
+            }
+        };
+        if offset >= metadata.size {
+            return Ok(Bytes::new());
+        }
+        let len = (metadata.size - offset).min(size as u64);
+
+        let stored_offset = match location {
+            Location::Zip {
+                encrypted: true, ..
+            } => {
+                return Err(FuseAdapterError::NotSupported(
+                    "encrypted zip members".to_string(),
+                ))
+            }
+            Location::Zip {
+                method: ZIP_METHOD_STORED,
+                header_offset,
+                data_offset,
+                ..
+            } => Some(
+                self.inner
+                    .zip_data_offset(*header_offset, data_offset)
+                    .await?,
+            ),
+            Location::Zip {
+                method: ZIP_METHOD_DEFLATED,
+                ..
+            } => None,
+            Location::Zip { method, .. } => {
+                return Err(FuseAdapterError::NotSupported(format!(
+                    "zip compression method {}",
+                    method
+                )))
+            }
+            Location::Tar { offset } if self.inner.format == ArchiveFormat::Tar => Some(*offset),
+            Location::Tar { .. } => None,
+        };
+
+        match stored_offset {
+            Some(start) => {
+                self.inner
+                    .source
+                    .read(&self.inner.archive, start + offset, len as u32)
+                    .await
+            }
+            None => {
+                let data = self
+                    .inner
+                    .decompressed(&index, path, metadata, location)
+                    .await?;
+                Ok(data.slice(offset as usize..(offset + len) as usize))
+            }
+        }
+    }
+
+    async fn write(&self, _path: &Path, _offset: u64, _data: &[u8]) -> Result<u64> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    async fn create_file(&self, _path: &Path) -> Result<()> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    async fn create_dir(&self, _path: &Path) -> Result<()> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    async fn remove_file(&self, _path: &Path) -> Result<()> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let inner = self.inner.clone();
+        let path = path.to_path_buf();
+        Box::pin(try_stream! {
+            let index = inner.index().await?;
+            if let Entry::File(..) = index.get(&path)? {
+                Err(FuseAdapterError::NotADirectory(path.display().to_string()))?;
+            }
+            for (name, file_type) in index.children.get(&path).into_iter().flatten() {
+                let child = path.join(name);
+                let mut entry = match file_type {
+                    FileType::Directory => DirEntry::directory(name.clone()),
+                    _ => DirEntry::file(name.clone()),
+                };
+                if let Some(Entry::Dir(metadata) | Entry::File(metadata, _)) =
+                    index.entries.get(&child)
+                {
+                    entry = entry.with_metadata(metadata.clone());
+                }
+                yield entry;
+            }
+        })
+    }
+
+    async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+        Err(FuseAdapterError::ReadOnly)
+    }
+
+    async fn flush(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use futures::StreamExt;
+
+    /// Backend holding a single archive file at /archive
+    struct ArchiveFile(Vec<u8>);
+
+    #[async_trait]
+    impl Connector for ArchiveFile {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::read_only()
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            if path != Path::new("/archive") {
+                return Err(FuseAdapterError::NotFound(path.display().to_string()));
+            }
+            Ok(Metadata::file(self.0.len() as u64, UNIX_EPOCH))
+        }
+
+        async fn read(&self, _path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let start = (offset as usize).min(self.0.len());
+            let end = (start + size as usize).min(self.0.len());
+            Ok(Bytes::copy_from_slice(&self.0[start..end]))
+        }
+
+        async fn write(&self, _path: &Path, _offset: u64, _data: &[u8]) -> Result<u64> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn create_file(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn remove_file(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        fn list_dir(&self, _path: &Path) -> DirEntryStream {
+            Box::pin(futures::stream::empty())
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Build a zip; each member is stored or deflated
+    fn zip(members: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, data, deflate) in members {
+            let (method, body) = if *deflate {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                (ZIP_METHOD_DEFLATED, encoder.finish().unwrap())
+            } else {
+                (ZIP_METHOD_STORED, data.to_vec())
+            };
+            let offset = out.len() as u32;
+            // 2024-03-01 12:00:00
+            let (time, date) = (12u16 << 11, (44u16 << 9) | (3 << 5) | 1);
+
+            out.extend_from_slice(&ZIP_LOCAL_SIGNATURE.to_le_bytes());
+            out.extend_from_slice(&[20, 0, 0, 0]);
+            out.extend_from_slice(&method.to_le_bytes());
+            out.extend_from_slice(&time.to_le_bytes());
+            out.extend_from_slice(&date.to_le_bytes());
+            out.extend_from_slice(&0u32.to_le_bytes()); // crc, unchecked
+            out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&4u16.to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&[0xff, 0xff, 0, 0]); // unknown extra field
+            out.extend_from_slice(&body);
+
+            central.extend_from_slice(&ZIP_CENTRAL_SIGNATURE.to_le_bytes());
+            central.extend_from_slice(&[20, 3, 20, 0, 0, 0]); // made by unix
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&time.to_le_bytes());
+            central.extend_from_slice(&date.to_le_bytes());
+            central.extend_from_slice(&0u32.to_le_bytes());
+            central.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 8]); // extra, comment, disk, internal
+            central.extend_from_slice(&((0o100640u32) << 16).to_le_bytes());
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let cd_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(&ZIP_EOCD_SIGNATURE.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&cd_offset.to_le_bytes());
+        out.extend_from_slice(&3u16.to_le_bytes());
+        out.extend_from_slice(b"end");
+        out
+    }
+
+    /// Build a ustar archive of files and (names ending in `/`) directories
+    fn tar(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (name, data) in members {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[100..107].copy_from_slice(b"0000644");
+            header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+            header[136..147].copy_from_slice(b"14577213000");
+            header[156] = if name.ends_with('/') { b'5' } else { b'0' };
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[148..156].fill(b' ');
+            let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+            header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+            out.extend_from_slice(&header);
+            out.extend_from_slice(data);
+            out.resize(out.len().next_multiple_of(512), 0);
+        }
+        out.resize(out.len() + 1024, 0);
+        out
+    }
+
+    fn connector(archive: Vec<u8>, format: ArchiveFormat) -> ArchiveConnector {
+        ArchiveConnector::new(
+            Arc::new(ArchiveFile(archive)),
+            PathBuf::from("/archive"),
+            format,
+        )
+    }
+
+    async fn list(connector: &ArchiveConnector, path: &str) -> Vec<(String, FileType)> {
+        connector
+            .list_dir(Path::new(path))
+            .map(|e| {
+                let e = e.unwrap();
+                (e.name.to_string_lossy().into_owned(), e.file_type)
+            })
+            .collect()
+            .await
+    }
+
+    async fn read_all(connector: &ArchiveConnector, path: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        loop {
+            let chunk = connector
+                .read(Path::new(path), data.len() as u64, 7)
+                .await
+                .unwrap();
+            if chunk.is_empty() {
+                return data;
+            }
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zip() {
+        let archive = zip(&[
+            ("data/raw.csv", b"a,b\n1,2\n", false),
+            ("data/deep/packed.txt", &b"packed ".repeat(50), true),
+            ("empty/", b"", false),
+            ("../escape", b"nope", false),
+        ]);
+        let zip = connector(archive, ArchiveFormat::Zip);
+
+        assert_eq!(
+            list(&zip, "/").await,
+            vec![
+                ("data".to_string(), FileType::Directory),
+                ("empty".to_string(), FileType::Directory)
+            ]
+        );
+        assert_eq!(
+            list(&zip, "/data").await,
+            vec![
+                ("deep".to_string(), FileType::Directory),
+                ("raw.csv".to_string(), FileType::File)
+            ]
+        );
+
+        let metadata = zip.stat(Path::new("/data/raw.csv")).await.unwrap();
+        assert_eq!(metadata.size, 8);
+        assert_eq!(metadata.mode, Some(0o640));
+        assert_eq!(
+            metadata.mtime,
+            UNIX_EPOCH + Duration::from_secs(1_709_294_400)
+        );
+        assert_eq!(
+            zip.read(Path::new("/data/raw.csv"), 4, 100).await.unwrap(),
+            Bytes::from_static(b"1,2\n")
+        );
+        assert_eq!(
+            read_all(&zip, "/data/deep/packed.txt").await,
+            b"packed ".repeat(50)
+        );
+        assert!(matches!(
+            zip.stat(Path::new("/escape")).await,
+            Err(FuseAdapterError::NotFound(_))
+        ));
+        assert!(matches!(
+            zip.create_file(Path::new("/new")).await,
+            Err(FuseAdapterError::ReadOnly)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tar() {
+        let long_name = format!("{}/file.txt", "d".repeat(120));
+        let record = format!(" path={}\n", long_name);
+        let record = format!("{}{}", record.len() + 3, record);
+        let mut pax = tar(&[("pax", record.as_bytes())]);
+        pax[156] = b'x';
+        pax[148..156].fill(b' ');
+        let checksum: u32 = pax[..512].iter().map(|&b| b as u32).sum();
+        pax[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        pax.truncate(1024);
+
+        let mut archive = tar(&[("dir/", b""), ("dir/a.txt", b"hello tar")]);
+        archive.truncate(archive.len() - 1024);
+        archive.extend_from_slice(&pax);
+        archive.extend_from_slice(&tar(&[("short", b"long named")]));
+
+        for format in [ArchiveFormat::Tar, ArchiveFormat::TarGz] {
+            let data = if format == ArchiveFormat::TarGz {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&archive).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                archive.clone()
+            };
+            let tar = connector(data, format);
+
+            assert_eq!(
+                list(&tar, "/").await,
+                vec![
+                    ("d".repeat(120), FileType::Directory),
+                    ("dir".to_string(), FileType::Directory)
+                ]
+            );
+            let metadata = tar.stat(Path::new("/dir/a.txt")).await.unwrap();
+            assert_eq!(metadata.size, 9);
+            assert_eq!(metadata.mode, Some(0o644));
+            assert_eq!(read_all(&tar, "/dir/a.txt").await, b"hello tar");
+            assert_eq!(
+                read_all(&tar, &format!("/{}", long_name)).await,
+                b"long named"
+            );
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! Circuit breaker decorator
+//!
+//! Wraps a backend connector and stops calling it once it keeps failing.
+//! After `failure_threshold` consecutive outage errors (I/O errors, timeouts
+//! and generic backend errors, but not "not found" and the like) the circuit
+//! opens: every call fails at once with `Unavailable` (EIO) instead of
+//! waiting on a backend that is down. After `cooldown` one probe call is let
+//! through (half-open); if it succeeds the circuit closes again, otherwise it
+//! stays open for another cooldown.
+//!
+//! It sits below the cache layer, so cached content keeps being served while
+//! the circuit is open, and the cache falls back to expired metadata and
+//! listings rather than failing.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{Future, StreamExt};
+use parking_lot::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::config::CircuitBreakerConfig;
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, DirtyRanges, FileVersion, Metadata,
+    SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Whether an error means the backend itself is failing, as opposed to
+/// rejecting this particular request
+fn is_outage(error: &FuseAdapterError) -> bool {
+    matches!(
+        error,
+        FuseAdapterError::Backend(_) | FuseAdapterError::Io(_) | FuseAdapterError::Timeout(_)
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Calls go through; counts consecutive outage errors
+    Closed { failures: u32 },
+    /// Calls fail fast until the cooldown ends
+    Open { until: Instant },
+    /// One probe call is in flight; others fail fast
+    HalfOpen { probe_started: Instant },
+}
+
+/// Circuit state shared by a connector and the listings it has started
+struct Breaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl Breaker {
+    /// Fail with `Unavailable` unless a call may go to the backend now
+    fn admit(&self) -> Result<()> {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now >= until => {
+                *state = State::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            // A probe that never reported back (e.g. its caller gave up)
+            // doesn't block the circuit forever
+            State::HalfOpen { probe_started } if now >= probe_started + self.cooldown => {
+                *state = State::HalfOpen { probe_started: now };
+                Ok(())
+            }
+            State::Open { .. } | State::HalfOpen { .. } => Err(FuseAdapterError::Unavailable(
+                "circuit breaker is open after repeated backend failures".to_string(),
+            )),
+        }
+    }
+
+    /// Update the circuit with the outcome of a backend call
+    fn record(&self, error: Option<&FuseAdapterError>) {
+        let failed = error.is_some_and(is_outage);
+        let mut state = self.state.lock();
+        match (*state, failed) {
+            (State::Closed { failures }, true) => {
+                let failures = failures + 1;
+                if failures >= self.failure_threshold {
+                    warn!(
+                        "Backend failed {} times in a row, failing fast for {:?}",
+                        failures, self.cooldown
+                    );
+                    *state = State::Open {
+                        until: Instant::now() + self.cooldown,
+                    };
+                } else {
+                    *state = State::Closed { failures };
+                }
+            }
+            (State::HalfOpen { .. }, true) => {
+                warn!(
+                    "Backend still failing, failing fast for another {:?}",
+                    self.cooldown
+                );
+                *state = State::Open {
+                    until: Instant::now() + self.cooldown,
+                };
+            }
+            // Calls admitted before the circuit opened may finish late
+            (State::Open { .. }, _) => {}
+            (State::HalfOpen { .. }, false) => {
+                info!("Backend recovered, closing circuit breaker");
+                *state = State::Closed { failures: 0 };
+            }
+            (State::Closed { .. }, false) => *state = State::Closed { failures: 0 },
+        }
+    }
+}
+
+/// Connector decorator that fails fast while the backend is down
+pub struct CircuitBreakerConnector<C: Connector> {
+    inner: C,
+    /// None when no breaker is configured; calls then pass straight through
+    breaker: Option<Arc<Breaker>>,
+}
+
+impl<C: Connector> CircuitBreakerConnector<C> {
+    /// Wrap a connector, with a breaker if one is configured
+    pub fn new(connector: C, config: Option<&CircuitBreakerConfig>) -> Self {
+        Self {
+            inner: connector,
+            breaker: config.map(|config| {
+                Arc::new(Breaker {
+                    failure_threshold: config.failure_threshold.max(1),
+                    cooldown: config.cooldown,
+                    state: Mutex::new(State::Closed { failures: 0 }),
+                })
+            }),
+        }
+    }
+
+    /// Run a backend call through the breaker
+    async fn call<T>(&self, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let Some(breaker) = &self.breaker else {
+            return call.await;
+        };
+        breaker.admit()?;
+        let result = call.await;
+        breaker.record(result.as_ref().err());
+        result
+    }
+}
+
+#[async_trait]
+impl<C: Connector + 'static> Connector for CircuitBreakerConnector<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.call(self.inner.stat(path)).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.call(self.inner.exists(path)).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.call(self.inner.read(path, offset, size)).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.call(self.inner.write(path, offset, data)).await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        self.call(self.inner.write_dirty(path, data, dirty)).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.call(self.inner.create_file(path)).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.call(self.inner.create_dir(path)).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.call(self.inner.remove_file(path)).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.call(self.inner.remove_dir(path, recursive)).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let Some(breaker) = self.breaker.clone() else {
+            return self.inner.list_dir(path);
+        };
+        if let Err(e) = breaker.admit() {
+            return Box::pin(futures::stream::once(async { Err(e) }));
+        }
+        let mut stream = self.inner.list_dir(path);
+        Box::pin(async_stream::stream! {
+            let mut failed = false;
+            while let Some(entry) = stream.next().await {
+                if let Err(e) = &entry {
+                    breaker.record(Some(e));
+                    failed = true;
+                }
+                yield entry;
+            }
+            if !failed {
+                breaker.record(None);
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.call(self.inner.rename(from, to)).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.call(self.inner.truncate(path, size)).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.call(self.inner.flush(path)).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.call(self.inner.create_file_with_mode(path, mode))
+            .await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.call(self.inner.create_dir_with_mode(path, mode)).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.call(self.inner.set_mode(path, mode)).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.call(self.inner.readlink(path)).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.call(self.inner.symlink(target, link_path)).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.call(self.inner.list_versions(path)).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.call(self.inner.read_version(path, version_id, offset, size))
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.call(self.inner.content_hash(path, etag)).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.call(self.inner.revalidate(path, etag)).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.call(self.inner.copy(from, to)).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.call(self.inner.zero_range(path, offset, len)).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.call(self.inner.seek_region(path, offset, region))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::memfs::MemfsConnector;
+
+    fn breaker() -> Breaker {
+        Breaker {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    fn outage() -> FuseAdapterError {
+        FuseAdapterError::Timeout("backend".to_string())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_breaker_opens_then_probes() {
+        let breaker = breaker();
+
+        // Errors that aren't outages, and successes, reset the count
+        breaker.record(Some(&outage()));
+        breaker.record(Some(&outage()));
+        breaker.record(Some(&FuseAdapterError::NotFound("/a".to_string())));
+        breaker.record(Some(&outage()));
+        breaker.record(None);
+        assert!(breaker.admit().is_ok());
+
+        for _ in 0..3 {
+            breaker.record(Some(&outage()));
+        }
+        assert!(matches!(
+            breaker.admit(),
+            Err(FuseAdapterError::Unavailable(_))
+        ));
+
+        // One probe after the cooldown; a failed probe reopens the circuit
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.admit().is_ok());
+        assert!(breaker.admit().is_err());
+        breaker.record(Some(&outage()));
+        assert!(breaker.admit().is_err());
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.admit().is_ok());
+        breaker.record(None);
+        assert!(breaker.admit().is_ok());
+        assert!(breaker.admit().is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stuck_probe_does_not_block_forever() {
+        let breaker = breaker();
+        for _ in 0..3 {
+            breaker.record(Some(&outage()));
+        }
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.admit().is_ok());
+
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert!(breaker.admit().is_err());
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(breaker.admit().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_without_config_passes_through() {
+        let connector = CircuitBreakerConnector::new(MemfsConnector::new(None), None);
+        for _ in 0..10 {
+            assert!(connector.stat(Path::new("/missing")).await.is_err());
+        }
+        connector.create_file(Path::new("/file")).await.unwrap();
+        assert!(connector.stat(Path::new("/file")).await.is_ok());
+    }
+}
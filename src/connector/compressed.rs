@@ -0,0 +1,727 @@
+//! Transparent compression decorator
+//!
+//! Wraps a backend connector so eligible files are compressed before upload
+//! and decompressed on read. Eligibility is decided by file extension; files
+//! below `min_size`, or that don't get smaller, are stored as-is.
+//!
+//! A compressed object starts with an 8-byte magic, the algorithm and the
+//! uncompressed size, so `stat` can report the real size from a small range
+//! read without downloading the object. Objects without the header are
+//! served unchanged, so compression can be enabled on an existing bucket.
+//!
+//! Compressed objects can only be read and written whole, which makes
+//! partial reads and writes expensive; use a memory or filesystem cache.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use futures::StreamExt;
+
+use crate::cache::{parse_size, CacheControl, CacheStats, PendingSync};
+use crate::config::{CompressionAlgorithm, CompressionConfig};
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileVersion, Metadata,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Marks a compressed object; the last byte is the format version
+const MAGIC: &[u8; 8] = b"FACMP\0\0\x01";
+
+/// Magic, algorithm byte and little-endian uncompressed size
+const HEADER_LEN: usize = MAGIC.len() + 1 + 8;
+
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// Formats that are already compressed and rarely shrink further
+const DEFAULT_EXCLUDED: &[&str] = &[
+    "7z", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jpeg", "jpg", "lz4", "m4a",
+    "mkv", "mov", "mp3", "mp4", "ogg", "png", "pptx", "rar", "tgz", "webm", "webp", "xlsx", "xz",
+    "zip", "zst",
+];
+
+/// Size of each backend read when fetching a whole object
+const FETCH_SIZE: u32 = 8 * 1024 * 1024;
+
+/// Uncompressed size of a stored object, valid while the object is unchanged
+#[derive(Clone, Copy)]
+struct SizeInfo {
+    stored: u64,
+    mtime: SystemTime,
+    size: u64,
+}
+
+fn algorithm_id(algorithm: CompressionAlgorithm) -> u8 {
+    match algorithm {
+        CompressionAlgorithm::Zstd => 1,
+        CompressionAlgorithm::Gzip => 2,
+    }
+}
+
+/// Parse a header; `None` if the object isn't compressed
+fn parse_header(data: &[u8]) -> Option<(u8, u64)> {
+    if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    let size = u64::from_le_bytes(data[MAGIC.len() + 1..HEADER_LEN].try_into().ok()?);
+    Some((data[MAGIC.len()], size))
+}
+
+fn corrupt(path: &Path, what: impl std::fmt::Display) -> FuseAdapterError {
+    FuseAdapterError::IntegrityMismatch(format!("{}: {}", path.display(), what))
+}
+
+/// Decode a stored object into its content
+fn decode(path: &Path, stored: Bytes) -> Result<Bytes> {
+    let Some((algorithm, size)) = parse_header(&stored) else {
+        return Ok(stored);
+    };
+    let body = &stored[HEADER_LEN..];
+    let mut out = Vec::with_capacity(size as usize);
+    match algorithm {
+        1 => zstd::stream::read::Decoder::new(body)
+            .and_then(|mut d| d.read_to_end(&mut out))
+            .map_err(|e| corrupt(path, e))?,
+        2 => GzDecoder::new(body)
+            .read_to_end(&mut out)
+            .map_err(|e| corrupt(path, e))?,
+        other => return Err(corrupt(path, format!("unknown compression {}", other))),
+    };
+    if out.len() as u64 != size {
+        return Err(corrupt(path, "decompressed size mismatch"));
+    }
+    Ok(Bytes::from(out))
+}
+
+/// Decides by extension which files may be compressed
+#[derive(Clone)]
+struct ExtensionFilter {
+    allowed: Option<Vec<String>>,
+    excluded: Vec<String>,
+}
+
+impl ExtensionFilter {
+    fn allows(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if self.excluded.contains(&extension) {
+            return false;
+        }
+        match &self.allowed {
+            Some(allowed) => allowed.contains(&extension),
+            None => true,
+        }
+    }
+}
+
+/// Connector decorator compressing eligible files
+pub struct CompressedConnector<C: Connector> {
+    inner: C,
+    algorithm: CompressionAlgorithm,
+    level: i32,
+    min_size: u64,
+    filter: ExtensionFilter,
+    /// Uncompressed sizes learned from headers, keyed by path
+    sizes: DashMap<PathBuf, SizeInfo>,
+}
+
+impl<C: Connector> CompressedConnector<C> {
+    /// Wrap a connector with the given compression settings
+    pub fn new(connector: C, config: &CompressionConfig) -> Self {
+        let lowercase = |list: &[String]| -> Vec<String> {
+            list.iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect()
+        };
+        Self {
+            inner: connector,
+            algorithm: config.algorithm,
+            level: config.level.unwrap_or(match config.algorithm {
+                CompressionAlgorithm::Zstd => 3,
+                CompressionAlgorithm::Gzip => 6,
+            }),
+            min_size: config
+                .min_size
+                .as_deref()
+                .and_then(parse_size)
+                .unwrap_or(DEFAULT_MIN_SIZE),
+            filter: ExtensionFilter {
+                allowed: config.extensions.as_deref().map(lowercase),
+                excluded: match &config.exclude_extensions {
+                    Some(list) => lowercase(list),
+                    None => DEFAULT_EXCLUDED.iter().map(|e| e.to_string()).collect(),
+                },
+            },
+            sizes: DashMap::new(),
+        }
+    }
+
+    /// Whether `path` may be stored compressed
+    fn eligible(&self, path: &Path) -> bool {
+        self.filter.allows(path)
+    }
+
+    fn compress(&self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(HEADER_LEN + data.len() / 2);
+        out.extend_from_slice(MAGIC);
+        out.push(algorithm_id(self.algorithm));
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        match self.algorithm {
+            CompressionAlgorithm::Zstd => {
+                zstd::stream::copy_encode(data, &mut out, self.level)?;
+            }
+            CompressionAlgorithm::Gzip => {
+                let mut encoder = GzEncoder::new(out, flate2::Compression::new(self.level as u32));
+                encoder.write_all(data)?;
+                out = encoder.finish()?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Read an object from the backend in full
+    async fn fetch(&self, path: &Path) -> Result<Bytes> {
+        let mut data = Vec::new();
+        loop {
+            let chunk = self.inner.read(path, data.len() as u64, FETCH_SIZE).await?;
+            data.extend_from_slice(&chunk);
+            if chunk.len() < FETCH_SIZE as usize {
+                return Ok(Bytes::from(data));
+            }
+        }
+    }
+
+    /// Current content of an eligible file (empty if it doesn't exist)
+    async fn content(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.fetch(path).await {
+            Ok(stored) => Ok(decode(path, stored)?.to_vec()),
+            Err(FuseAdapterError::NotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store the whole content of an eligible file
+    async fn store(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.sizes.remove(path);
+        // Content that looks like a header must be wrapped to read back as-is
+        let stored = if data.len() as u64 >= self.min_size || data.starts_with(MAGIC) {
+            let compressed = self
+                .compress(data)
+                .map_err(|e| FuseAdapterError::Backend(format!("compression failed: {}", e)))?;
+            if compressed.len() < data.len() || data.starts_with(MAGIC) {
+                compressed
+            } else {
+                data.to_vec()
+            }
+        } else {
+            data.to_vec()
+        };
+
+        // A positional write wouldn't shrink an existing object
+        if stored.is_empty() || self.inner.capabilities().random_write {
+            self.inner.truncate(path, 0).await?;
+        }
+        if !stored.is_empty() {
+            self.inner.write(path, 0, &stored).await?;
+        }
+        Ok(())
+    }
+
+    /// Report the uncompressed size of an eligible file
+    async fn uncompressed(&self, path: &Path, mut meta: Metadata) -> Result<Metadata> {
+        if !meta.is_file() || !self.eligible(path) || meta.size < HEADER_LEN as u64 {
+            return Ok(meta);
+        }
+        if let Some(info) = self.sizes.get(path) {
+            if info.stored == meta.size && info.mtime == meta.mtime {
+                meta.size = info.size;
+                return Ok(meta);
+            }
+        }
+        let header = self.inner.read(path, 0, HEADER_LEN as u32).await?;
+        let size = parse_header(&header).map_or(meta.size, |(_, size)| size);
+        self.sizes.insert(
+            path.to_path_buf(),
+            SizeInfo {
+                stored: meta.size,
+                mtime: meta.mtime,
+                size,
+            },
+        );
+        meta.size = size;
+        Ok(meta)
+    }
+
+    fn forget(&self, path: &Path) {
+        self.sizes.retain(|p, _| !p.starts_with(path));
+    }
+}
+
+fn slice(data: &Bytes, offset: u64, size: u32) -> Bytes {
+    let start = (offset as usize).min(data.len());
+    let end = (start + size as usize).min(data.len());
+    data.slice(start..end)
+}
+
+#[async_trait]
+impl<C: Connector> Connector for CompressedConnector<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        CacheRequirements {
+            read_cache: true,
+            ..self.inner.cache_requirements()
+        }
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.sizes.clear();
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let meta = self.inner.stat(path).await?;
+        self.uncompressed(path, meta).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        if !self.eligible(path) {
+            return self.inner.read(path, offset, size).await;
+        }
+
+        // Fetching the header first tells whether a plain range read will do
+        let head_size = if offset == 0 {
+            size.max(HEADER_LEN as u32)
+        } else {
+            HEADER_LEN as u32
+        };
+        let head = self.inner.read(path, 0, head_size).await?;
+        if parse_header(&head).is_none() {
+            return match offset {
+                0 => Ok(slice(&head, 0, size)),
+                _ => self.inner.read(path, offset, size).await,
+            };
+        }
+
+        let content = decode(path, self.fetch(path).await?)?;
+        Ok(slice(&content, offset, size))
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        if !self.eligible(path) {
+            return self.inner.write(path, offset, data).await;
+        }
+        if offset == 0 && !self.inner.capabilities().random_write {
+            // Backends without random writes replace the object on write at 0
+            self.store(path, data).await?;
+        } else {
+            let mut content = self.content(path).await?;
+            let end = offset as usize + data.len();
+            if content.len() < end {
+                content.resize(end, 0);
+            }
+            content[offset as usize..end].copy_from_slice(data);
+            self.store(path, &content).await?;
+        }
+        Ok(data.len() as u64)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.sizes.remove(path);
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.sizes.remove(path);
+        self.inner.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.forget(path);
+        self.inner.remove_dir(path, recursive).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let dir = path.to_path_buf();
+        let entries = self.inner.list_dir(path);
+        // Listed sizes of eligible files are stored sizes; keep only those
+        // known to be current and let the rest be stat'ed
+        let known: Vec<(PathBuf, SizeInfo)> = self
+            .sizes
+            .iter()
+            .filter(|e| e.key().parent() == Some(path))
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect();
+        let filter = self.filter.clone();
+
+        Box::pin(entries.map(move |entry| {
+            entry.map(|mut entry| {
+                let child = dir.join(&entry.name);
+                let eligible = filter.allows(&child);
+                if let Some(meta) = entry.metadata.as_mut().filter(|m| m.is_file() && eligible) {
+                    let info = known.iter().find(|(p, _)| *p == child).map(|(_, i)| i);
+                    match info {
+                        Some(info) if info.stored == meta.size && info.mtime == meta.mtime => {
+                            meta.size = info.size;
+                        }
+                        _ => entry.metadata = None,
+                    }
+                }
+                entry
+            })
+        }))
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.forget(from);
+        self.forget(to);
+        self.inner.rename(from, to).await?;
+
+        // A file leaving compression's scope must be stored plain
+        if self.eligible(from) && !self.eligible(to) && self.inner.stat(to).await?.is_file() {
+            let stored = self.fetch(to).await?;
+            if parse_header(&stored).is_some() {
+                let content = decode(to, stored)?;
+                if self.inner.capabilities().random_write {
+                    self.inner.truncate(to, 0).await?;
+                }
+                self.inner.write(to, 0, &content).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        if !self.eligible(path) {
+            return self.inner.truncate(path, size).await;
+        }
+        let mut content = self.content(path).await?;
+        content.resize(size as usize, 0);
+        self.store(path, &content).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.sizes.remove(path);
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        let mut versions = self.inner.list_versions(path).await?;
+        if self.eligible(path) {
+            for version in &mut versions {
+                let header = self
+                    .inner
+                    .read_version(path, &version.version_id, 0, HEADER_LEN as u32)
+                    .await?;
+                if let Some((_, size)) = parse_header(&header) {
+                    version.size = size;
+                }
+            }
+        }
+        Ok(versions)
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        if !self.eligible(path) {
+            return self
+                .inner
+                .read_version(path, version_id, offset, size)
+                .await;
+        }
+        let mut stored = Vec::new();
+        loop {
+            let chunk = self
+                .inner
+                .read_version(path, version_id, stored.len() as u64, FETCH_SIZE)
+                .await?;
+            stored.extend_from_slice(&chunk);
+            if chunk.len() < FETCH_SIZE as usize {
+                break;
+            }
+        }
+        let content = decode(path, Bytes::from(stored))?;
+        Ok(slice(&content, offset, size))
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        // The backend's hash covers the stored bytes, which may be compressed
+        if self.eligible(path) {
+            return Ok(None);
+        }
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::DirEntry;
+    use futures::stream;
+    use std::time::UNIX_EPOCH;
+
+    /// In-memory backend that replaces the whole object on every write
+    #[derive(Default)]
+    struct MemBackend {
+        files: DashMap<PathBuf, Vec<u8>>,
+    }
+
+    #[async_trait]
+    impl Connector for MemBackend {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                random_write: false,
+                ..Capabilities::full()
+            }
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            match self.files.get(path) {
+                Some(data) => Ok(Metadata::file(data.len() as u64, UNIX_EPOCH)),
+                None => Err(FuseAdapterError::NotFound(path.display().to_string())),
+            }
+        }
+
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let data = self
+                .files
+                .get(path)
+                .ok_or_else(|| FuseAdapterError::NotFound(path.display().to_string()))?;
+            let start = (offset as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            Ok(Bytes::copy_from_slice(&data[start..end]))
+        }
+
+        async fn write(&self, path: &Path, _offset: u64, data: &[u8]) -> Result<u64> {
+            self.files.insert(path.to_path_buf(), data.to_vec());
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.files.insert(path.to_path_buf(), Vec::new());
+            Ok(())
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.files.remove(path);
+            Ok(())
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            let entries: Vec<Result<DirEntry>> = self
+                .files
+                .iter()
+                .filter(|e| e.key().parent() == Some(path))
+                .map(|e| {
+                    Ok(DirEntry::file(e.key().file_name().unwrap())
+                        .with_metadata(Metadata::file(e.value().len() as u64, UNIX_EPOCH)))
+                })
+                .collect();
+            Box::pin(stream::iter(entries))
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            if let Some((_, data)) = self.files.remove(from) {
+                self.files.insert(to.to_path_buf(), data);
+            }
+            Ok(())
+        }
+
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            self.files
+                .entry(path.to_path_buf())
+                .or_default()
+                .resize(size as usize, 0);
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connector(algorithm: CompressionAlgorithm) -> CompressedConnector<MemBackend> {
+        let config = CompressionConfig {
+            algorithm,
+            ..Default::default()
+        };
+        CompressedConnector::new(MemBackend::default(), &config)
+    }
+
+    fn text(len: usize) -> Vec<u8> {
+        b"the quick brown fox jumps over the lazy dog\n"
+            .iter()
+            .copied()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_reports_uncompressed_size() {
+        for algorithm in [CompressionAlgorithm::Zstd, CompressionAlgorithm::Gzip] {
+            let conn = connector(algorithm);
+            let path = Path::new("/log.txt");
+            let data = text(100_000);
+            conn.write(path, 0, &data).await.unwrap();
+
+            let stored = conn.inner.files.get(path).unwrap().len();
+            assert!(stored < data.len() / 10);
+            assert_eq!(conn.stat(path).await.unwrap().size, data.len() as u64);
+            assert_eq!(
+                &conn.read(path, 5000, 100).await.unwrap()[..],
+                &data[5000..5100]
+            );
+
+            // Listing sizes are only reported once known to be current
+            let entries: Vec<DirEntry> = conn
+                .list_dir(Path::new("/"))
+                .map(|e| e.unwrap())
+                .collect()
+                .await;
+            assert_eq!(entries[0].metadata.as_ref().unwrap().size, 100_000);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_small_excluded_and_incompressible_files_stored_as_is() {
+        let conn = connector(CompressionAlgorithm::Zstd);
+
+        conn.write(Path::new("/small.txt"), 0, b"tiny")
+            .await
+            .unwrap();
+        assert_eq!(
+            *conn.inner.files.get(Path::new("/small.txt")).unwrap(),
+            b"tiny"
+        );
+
+        let data = text(10_000);
+        conn.write(Path::new("/photo.JPG"), 0, &data).await.unwrap();
+        assert_eq!(
+            *conn.inner.files.get(Path::new("/photo.JPG")).unwrap(),
+            data
+        );
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        conn.write(Path::new("/noise.bin"), 0, &noise)
+            .await
+            .unwrap();
+        assert_eq!(
+            *conn.inner.files.get(Path::new("/noise.bin")).unwrap(),
+            noise
+        );
+        assert_eq!(
+            &conn.read(Path::new("/noise.bin"), 10, 20).await.unwrap()[..],
+            &noise[10..30]
+        );
+
+        // Excluded files keep their listed size
+        let entries: Vec<DirEntry> = conn
+            .list_dir(Path::new("/"))
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+        let photo = entries.iter().find(|e| e.name == "photo.JPG").unwrap();
+        assert_eq!(photo.metadata.as_ref().unwrap().size, 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_rename_out_of_scope_decompresses() {
+        let conn = connector(CompressionAlgorithm::Zstd);
+        let data = text(50_000);
+        conn.write(Path::new("/data.txt"), 0, &data).await.unwrap();
+
+        conn.rename(Path::new("/data.txt"), Path::new("/data.gz"))
+            .await
+            .unwrap();
+        assert_eq!(*conn.inner.files.get(Path::new("/data.gz")).unwrap(), data);
+    }
+}
@@ -0,0 +1,899 @@
+//! Client-side encryption decorator
+//!
+//! Wraps a backend connector so file content is encrypted before it leaves
+//! the machine and decrypted on the way back. Content is split into 64 KiB
+//! chunks, each sealed with XChaCha20-Poly1305 under its own random nonce,
+//! so a range read only has to fetch and decrypt the chunks it touches.
+//!
+//! Stored layout: an 8-byte magic and a random 16-byte file id, followed by
+//! the sealed chunks (nonce, ciphertext, tag). The file id and chunk index
+//! are authenticated with every chunk, so chunks can't be swapped within or
+//! between files. Dropping whole trailing chunks is not detected.
+//!
+//! With `encrypt_names`, each path component is encrypted deterministically
+//! (the nonce is derived from the name) so paths can still be looked up, and
+//! stored base64url-encoded. An encrypted name is about 4/3 the length of the
+//! plain name plus 54 characters. Symlink targets are stored as-is.
+
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use bytes::Bytes;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use dashmap::DashMap;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::debug;
+
+use crate::cache::{CacheStats, PendingSync};
+use crate::config::EncryptionConfig;
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileVersion, Metadata,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Marks an encrypted file; the last byte is the format version
+const MAGIC: &[u8; 8] = b"FAENC\0\0\x01";
+
+const FILE_ID_LEN: usize = 16;
+
+/// Magic plus file id
+const HEADER_LEN: u64 = MAGIC.len() as u64 + FILE_ID_LEN as u64;
+
+/// Plaintext bytes per chunk
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+const NONCE_LEN: u64 = 24;
+const TAG_LEN: u64 = 16;
+
+/// Stored size of a full chunk
+const SEALED_CHUNK_SIZE: u64 = NONCE_LEN + CHUNK_SIZE + TAG_LEN;
+
+/// Largest zero-fill written at once when extending a file
+const FILL_SIZE: u64 = 16 * CHUNK_SIZE;
+
+type FileId = [u8; FILE_ID_LEN];
+
+/// Plaintext size of a file stored as `stored` bytes
+pub fn plaintext_size(stored: u64) -> u64 {
+    let Some(body) = stored.checked_sub(HEADER_LEN) else {
+        return 0;
+    };
+    let full = body / SEALED_CHUNK_SIZE;
+    let partial = (body % SEALED_CHUNK_SIZE).saturating_sub(NONCE_LEN + TAG_LEN);
+    full * CHUNK_SIZE + partial
+}
+
+/// Stored size of a file holding `size` plaintext bytes
+pub fn stored_size(size: u64) -> u64 {
+    if size == 0 {
+        return 0;
+    }
+    let chunks = size.div_ceil(CHUNK_SIZE);
+    HEADER_LEN + size + chunks * (NONCE_LEN + TAG_LEN)
+}
+
+fn corrupt(path: &Path, what: &str) -> FuseAdapterError {
+    FuseAdapterError::IntegrityMismatch(format!("{}: {}", path.display(), what))
+}
+
+/// Keys derived from the configured master key
+#[derive(Clone)]
+pub struct EncryptionKey {
+    content: [u8; 32],
+    names: [u8; 32],
+    name_nonces: [u8; 32],
+}
+
+impl EncryptionKey {
+    /// Derive content and name keys from a 256-bit master key
+    pub fn from_bytes(master: &[u8; 32]) -> Self {
+        let derive = |label: &[u8]| -> [u8; 32] {
+            let mut mac =
+                <Hmac<Sha256> as Mac>::new_from_slice(master).expect("HMAC accepts any key length");
+            mac.update(label);
+            mac.finalize().into_bytes().into()
+        };
+        Self {
+            content: derive(b"fuse-adapter content"),
+            names: derive(b"fuse-adapter names"),
+            name_nonces: derive(b"fuse-adapter name nonces"),
+        }
+    }
+
+    /// Load the master key from the configured source
+    pub fn load(config: &EncryptionConfig) -> Result<Self> {
+        let encoded = if let Some(key) = &config.key {
+            key.clone()
+        } else if let Some(path) = &config.key_file {
+            std::fs::read_to_string(path).map_err(|e| {
+                FuseAdapterError::Config(format!("Failed to read key file {:?}: {}", path, e))
+            })?
+        } else if let Some(command) = &config.key_command {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .output()
+                .map_err(|e| {
+                    FuseAdapterError::Config(format!("Failed to run key command: {}", e))
+                })?;
+            if !output.status.success() {
+                return Err(FuseAdapterError::Config(format!(
+                    "Key command failed ({}): {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        } else {
+            return Err(FuseAdapterError::Config(
+                "No encryption key source configured".to_string(),
+            ));
+        };
+
+        let master: [u8; 32] = STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| {
+                FuseAdapterError::Config(
+                    "Encryption key must be 32 bytes, base64-encoded".to_string(),
+                )
+            })?;
+        Ok(Self::from_bytes(&master))
+    }
+}
+
+/// Deterministic encryption of path components
+#[derive(Clone)]
+struct NameCipher {
+    cipher: XChaCha20Poly1305,
+    nonce_key: [u8; 32],
+}
+
+impl NameCipher {
+    fn encrypt(&self, name: &OsStr) -> OsString {
+        let plain = name.as_bytes();
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.nonce_key)
+            .expect("HMAC accepts any key length");
+        mac.update(plain);
+        let digest = mac.finalize().into_bytes();
+        let nonce = XNonce::from_slice(&digest[..NONCE_LEN as usize]);
+        let sealed = self
+            .cipher
+            .encrypt(nonce, plain)
+            .expect("encrypting in memory cannot fail");
+
+        let mut stored = nonce.to_vec();
+        stored.extend_from_slice(&sealed);
+        OsString::from(URL_SAFE_NO_PAD.encode(stored))
+    }
+
+    /// Decrypt a stored name; `None` if it wasn't written by this key
+    fn decrypt(&self, stored: &OsStr) -> Option<OsString> {
+        let bytes = URL_SAFE_NO_PAD.decode(stored.as_bytes()).ok()?;
+        if bytes.len() < (NONCE_LEN + TAG_LEN) as usize {
+            return None;
+        }
+        let (nonce, sealed) = bytes.split_at(NONCE_LEN as usize);
+        let plain = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), sealed)
+            .ok()?;
+        Some(OsString::from_vec(plain))
+    }
+}
+
+/// Connector decorator encrypting content (and optionally names)
+pub struct EncryptedConnector<C: Connector> {
+    inner: C,
+    cipher: XChaCha20Poly1305,
+    names: Option<NameCipher>,
+    /// File ids of recently used files, keyed by plaintext path
+    file_ids: DashMap<PathBuf, FileId>,
+}
+
+impl<C: Connector> EncryptedConnector<C> {
+    /// Wrap a connector, encrypting names as well if `encrypt_names` is set
+    pub fn new(connector: C, key: &EncryptionKey, encrypt_names: bool) -> Self {
+        let names = encrypt_names.then(|| NameCipher {
+            cipher: XChaCha20Poly1305::new((&key.names).into()),
+            nonce_key: key.name_nonces,
+        });
+        Self {
+            inner: connector,
+            cipher: XChaCha20Poly1305::new((&key.content).into()),
+            names,
+            file_ids: DashMap::new(),
+        }
+    }
+
+    /// Path of `path` in the backend
+    fn backend_path(&self, path: &Path) -> PathBuf {
+        let Some(names) = &self.names else {
+            return path.to_path_buf();
+        };
+        path.components()
+            .map(|component| match component {
+                Component::Normal(name) => names.encrypt(name),
+                other => other.as_os_str().to_os_string(),
+            })
+            .collect()
+    }
+
+    fn seal_chunk(&self, id: &FileId, index: u64, plain: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let aad = chunk_aad(id, index);
+        let sealed = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plain,
+                    aad: &aad,
+                },
+            )
+            .expect("encrypting in memory cannot fail");
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&sealed);
+        out
+    }
+
+    /// Seal `plain` as consecutive chunks starting at chunk `first`
+    fn seal(&self, id: &FileId, first: u64, plain: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(stored_size(plain.len() as u64) as usize);
+        for (i, chunk) in plain.chunks(CHUNK_SIZE as usize).enumerate() {
+            out.extend(self.seal_chunk(id, first + i as u64, chunk));
+        }
+        out
+    }
+
+    /// Open consecutive sealed chunks starting at chunk `first`
+    fn open(&self, path: &Path, id: &FileId, first: u64, sealed: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(sealed.len());
+        for (i, chunk) in sealed.chunks(SEALED_CHUNK_SIZE as usize).enumerate() {
+            if chunk.len() < (NONCE_LEN + TAG_LEN) as usize {
+                return Err(corrupt(path, "truncated chunk"));
+            }
+            let (nonce, body) = chunk.split_at(NONCE_LEN as usize);
+            let aad = chunk_aad(id, first + i as u64);
+            let plain = self
+                .cipher
+                .decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: body,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| corrupt(path, "chunk failed to decrypt"))?;
+            out.extend(plain);
+        }
+        Ok(out)
+    }
+
+    /// Read the file id of `path`; `None` for an empty file
+    async fn file_id(&self, path: &Path, use_cached: bool) -> Result<Option<FileId>> {
+        if use_cached {
+            if let Some(id) = self.file_ids.get(path) {
+                return Ok(Some(*id));
+            }
+        }
+        let header = self
+            .inner
+            .read(&self.backend_path(path), 0, HEADER_LEN as u32)
+            .await?;
+        let id = parse_header(path, &header)?;
+        if let Some(id) = id {
+            self.file_ids.insert(path.to_path_buf(), id);
+        }
+        Ok(id)
+    }
+
+    /// Read and decrypt chunks `first..=last` of `path`
+    async fn read_chunks(&self, path: &Path, first: u64, last: u64) -> Result<Vec<u8>> {
+        let backend = self.backend_path(path);
+        let offset = HEADER_LEN + first * SEALED_CHUNK_SIZE;
+        let size = (last - first + 1) * SEALED_CHUNK_SIZE;
+
+        // A cached file id may be stale if the file was replaced elsewhere
+        for use_cached in [true, false] {
+            let cached = use_cached && self.file_ids.contains_key(path);
+            let Some(id) = self.file_id(path, use_cached).await? else {
+                return Ok(Vec::new());
+            };
+            let sealed = self.inner.read(&backend, offset, size as u32).await?;
+            match self.open(path, &id, first, &sealed) {
+                Err(FuseAdapterError::IntegrityMismatch(_)) if cached => {
+                    self.file_ids.remove(path);
+                }
+                result => return result,
+            }
+        }
+        unreachable!("the uncached attempt always returns")
+    }
+
+    /// Plaintext size of `path` in the backend
+    async fn size(&self, path: &Path) -> Result<u64> {
+        let meta = self.inner.stat(&self.backend_path(path)).await?;
+        Ok(plaintext_size(meta.size))
+    }
+
+    /// Replace the whole content of `path`
+    async fn write_whole(&self, path: &Path, plain: &[u8]) -> Result<()> {
+        let backend = self.backend_path(path);
+        if plain.is_empty() {
+            self.file_ids.remove(path);
+            return self.inner.truncate(&backend, 0).await;
+        }
+        let mut id = [0u8; FILE_ID_LEN];
+        OsRng.fill_bytes(&mut id);
+        let mut stored = Vec::with_capacity(stored_size(plain.len() as u64) as usize);
+        stored.extend_from_slice(MAGIC);
+        stored.extend_from_slice(&id);
+        stored.extend(self.seal(&id, 0, plain));
+
+        self.inner.write(&backend, 0, &stored).await?;
+        self.file_ids.insert(path.to_path_buf(), id);
+        Ok(())
+    }
+
+    /// Write `data` at `offset` by rewriting the chunks it touches
+    async fn write_chunks(&self, path: &Path, offset: u64, data: &[u8]) -> Result<()> {
+        let size = self.size(path).await?;
+        let id = match size {
+            0 => None,
+            _ => self.file_id(path, true).await?,
+        };
+        let Some(id) = id else {
+            // Empty file: pad any hole with zeros and write it whole
+            let mut plain = vec![0u8; offset as usize];
+            plain.extend_from_slice(data);
+            return self.write_whole(path, &plain).await;
+        };
+
+        // Start at the chunk holding the write, or at EOF if that's earlier,
+        // so a hole past EOF gets filled with zeros
+        let start = offset.min(size);
+        let first = start / CHUNK_SIZE;
+        let chunk_start = first * CHUNK_SIZE;
+        let end = offset + data.len() as u64;
+
+        let existing_end = size.min(end.div_ceil(CHUNK_SIZE) * CHUNK_SIZE);
+        let mut plain = if existing_end > chunk_start {
+            self.read_chunks(path, first, (existing_end - 1) / CHUNK_SIZE)
+                .await?
+        } else {
+            Vec::new()
+        };
+        plain.truncate((existing_end.max(chunk_start) - chunk_start) as usize);
+        if (plain.len() as u64) < end - chunk_start {
+            plain.resize((end - chunk_start) as usize, 0);
+        }
+        let at = (offset - chunk_start) as usize;
+        plain[at..at + data.len()].copy_from_slice(data);
+
+        let sealed = self.seal(&id, first, &plain);
+        self.inner
+            .write(
+                &self.backend_path(path),
+                HEADER_LEN + first * SEALED_CHUNK_SIZE,
+                &sealed,
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn forget(&self, path: &Path) {
+        self.file_ids.retain(|p, _| !p.starts_with(path));
+    }
+
+    fn decrypt_metadata(&self, mut meta: Metadata) -> Metadata {
+        if meta.is_file() {
+            meta.size = plaintext_size(meta.size);
+        }
+        meta
+    }
+}
+
+fn chunk_aad(id: &FileId, index: u64) -> [u8; FILE_ID_LEN + 8] {
+    let mut aad = [0u8; FILE_ID_LEN + 8];
+    aad[..FILE_ID_LEN].copy_from_slice(id);
+    aad[FILE_ID_LEN..].copy_from_slice(&index.to_be_bytes());
+    aad
+}
+
+fn parse_header(path: &Path, header: &[u8]) -> Result<Option<FileId>> {
+    if header.is_empty() {
+        return Ok(None);
+    }
+    if header.len() < HEADER_LEN as usize || &header[..MAGIC.len()] != MAGIC {
+        return Err(corrupt(path, "not an encrypted file"));
+    }
+    let mut id = [0u8; FILE_ID_LEN];
+    id.copy_from_slice(&header[MAGIC.len()..HEADER_LEN as usize]);
+    Ok(Some(id))
+}
+
+#[async_trait]
+impl<C: Connector> Connector for EncryptedConnector<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.file_ids.clear();
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let meta = self.inner.stat(&self.backend_path(path)).await?;
+        Ok(self.decrypt_metadata(meta))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(&self.backend_path(path)).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        if size == 0 {
+            return Ok(Bytes::new());
+        }
+        let first = offset / CHUNK_SIZE;
+        let last = (offset + size as u64 - 1) / CHUNK_SIZE;
+        let plain = self.read_chunks(path, first, last).await?;
+
+        let start = ((offset - first * CHUNK_SIZE) as usize).min(plain.len());
+        let end = (start + size as usize).min(plain.len());
+        Ok(Bytes::copy_from_slice(&plain[start..end]))
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        if offset == 0 && !self.inner.capabilities().random_write {
+            // Backends without random writes replace the object on write at 0
+            self.write_whole(path, data).await?;
+        } else if !data.is_empty() {
+            self.write_chunks(path, offset, data).await?;
+        }
+        Ok(data.len() as u64)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.file_ids.remove(path);
+        self.inner.create_file(&self.backend_path(path)).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(&self.backend_path(path)).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.file_ids.remove(path);
+        self.inner.remove_file(&self.backend_path(path)).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.forget(path);
+        self.inner
+            .remove_dir(&self.backend_path(path), recursive)
+            .await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let entries = self.inner.list_dir(&self.backend_path(path));
+        let names = self.names.clone();
+
+        Box::pin(entries.filter_map(move |entry| {
+            let entry = entry.map(|mut entry| {
+                if let Some(names) = &names {
+                    match names.decrypt(&entry.name) {
+                        Some(name) => entry.name = name,
+                        None => {
+                            debug!("encrypted: skipping foreign entry {:?}", entry.name);
+                            return None;
+                        }
+                    }
+                }
+                if let Some(meta) = entry.metadata.as_mut() {
+                    if meta.is_file() {
+                        meta.size = plaintext_size(meta.size);
+                    }
+                }
+                Some(entry)
+            });
+            futures::future::ready(entry.transpose())
+        }))
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.forget(from);
+        self.forget(to);
+        self.inner
+            .rename(&self.backend_path(from), &self.backend_path(to))
+            .await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        let current = self.size(path).await?;
+        if size == current {
+            return Ok(());
+        }
+        if size == 0 {
+            return self.write_whole(path, &[]).await;
+        }
+
+        if !self.inner.capabilities().random_write {
+            let mut plain = self.read(path, 0, current.min(size) as u32).await?.to_vec();
+            plain.resize(size as usize, 0);
+            return self.write_whole(path, &plain).await;
+        }
+
+        if size > current {
+            let mut offset = current;
+            while offset < size {
+                let len = (size - offset).min(FILL_SIZE);
+                self.write_chunks(path, offset, &vec![0u8; len as usize])
+                    .await?;
+                offset += len;
+            }
+            return Ok(());
+        }
+
+        // Cut the stored file at the new last chunk and reseal that chunk
+        let last = (size - 1) / CHUNK_SIZE;
+        let mut tail = self.read_chunks(path, last, last).await?;
+        tail.truncate((size - last * CHUNK_SIZE) as usize);
+        let id = self
+            .file_id(path, true)
+            .await?
+            .ok_or_else(|| corrupt(path, "missing header"))?;
+        let backend = self.backend_path(path);
+        let at = HEADER_LEN + last * SEALED_CHUNK_SIZE;
+        self.inner.truncate(&backend, at).await?;
+        self.inner
+            .write(&backend, at, &self.seal_chunk(&id, last, &tail))
+            .await?;
+        Ok(())
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(&self.backend_path(path)).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.file_ids.remove(path);
+        self.inner
+            .create_file_with_mode(&self.backend_path(path), mode)
+            .await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner
+            .create_dir_with_mode(&self.backend_path(path), mode)
+            .await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.set_mode(&self.backend_path(path), mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(&self.backend_path(path)).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.inner
+            .symlink(target, &self.backend_path(link_path))
+            .await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        let mut versions = self.inner.list_versions(&self.backend_path(path)).await?;
+        for version in &mut versions {
+            version.size = plaintext_size(version.size);
+        }
+        Ok(versions)
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        if size == 0 {
+            return Ok(Bytes::new());
+        }
+        let backend = self.backend_path(path);
+        let header = self
+            .inner
+            .read_version(&backend, version_id, 0, HEADER_LEN as u32)
+            .await?;
+        let Some(id) = parse_header(path, &header)? else {
+            return Ok(Bytes::new());
+        };
+
+        let first = offset / CHUNK_SIZE;
+        let last = (offset + size as u64 - 1) / CHUNK_SIZE;
+        let sealed = self
+            .inner
+            .read_version(
+                &backend,
+                version_id,
+                HEADER_LEN + first * SEALED_CHUNK_SIZE,
+                ((last - first + 1) * SEALED_CHUNK_SIZE) as u32,
+            )
+            .await?;
+        let plain = self.open(path, &id, first, &sealed)?;
+
+        let start = ((offset - first * CHUNK_SIZE) as usize).min(plain.len());
+        let end = (start + size as usize).min(plain.len());
+        Ok(Bytes::copy_from_slice(&plain[start..end]))
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, _path: &Path, _etag: Option<&str>) -> Result<Option<ContentHash>> {
+        // The backend's hash covers the ciphertext, not what callers read
+        Ok(None)
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(&self.backend_path(path), etag).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::DirEntry;
+    use futures::stream;
+    use std::time::UNIX_EPOCH;
+
+    /// In-memory backend storing whatever bytes it is given
+    #[derive(Default)]
+    struct MemBackend {
+        files: DashMap<PathBuf, Vec<u8>>,
+        random_write: bool,
+    }
+
+    #[async_trait]
+    impl Connector for MemBackend {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities {
+                random_write: self.random_write,
+                ..Capabilities::full()
+            }
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            match self.files.get(path) {
+                Some(data) => Ok(Metadata::file(data.len() as u64, UNIX_EPOCH)),
+                None => Ok(Metadata::directory(UNIX_EPOCH)),
+            }
+        }
+
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let data = self
+                .files
+                .get(path)
+                .ok_or_else(|| FuseAdapterError::NotFound(path.display().to_string()))?;
+            let start = (offset as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            Ok(Bytes::copy_from_slice(&data[start..end]))
+        }
+
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            let mut file = self.files.entry(path.to_path_buf()).or_default();
+            if !self.random_write {
+                file.clear();
+            }
+            let end = offset as usize + data.len();
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            file[offset as usize..end].copy_from_slice(data);
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.files.insert(path.to_path_buf(), Vec::new());
+            Ok(())
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.files.remove(path);
+            Ok(())
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            let entries: Vec<Result<DirEntry>> = self
+                .files
+                .iter()
+                .filter(|e| e.key().parent() == Some(path))
+                .map(|e| {
+                    Ok(DirEntry::file(e.key().file_name().unwrap())
+                        .with_metadata(Metadata::file(e.value().len() as u64, UNIX_EPOCH)))
+                })
+                .collect();
+            Box::pin(stream::iter(entries))
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            if let Some((_, data)) = self.files.remove(from) {
+                self.files.insert(to.to_path_buf(), data);
+            }
+            Ok(())
+        }
+
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            self.files
+                .entry(path.to_path_buf())
+                .or_default()
+                .resize(size as usize, 0);
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connector(random_write: bool, encrypt_names: bool) -> EncryptedConnector<MemBackend> {
+        let backend = MemBackend {
+            random_write,
+            ..Default::default()
+        };
+        EncryptedConnector::new(backend, &EncryptionKey::from_bytes(&[7; 32]), encrypt_names)
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_size_mapping_round_trips() {
+        for size in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            5 * CHUNK_SIZE,
+        ] {
+            assert_eq!(plaintext_size(stored_size(size)), size);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_range_reads_decrypt_across_chunks() {
+        let conn = connector(false, false);
+        let path = Path::new("/data.bin");
+        let data = pattern(3 * CHUNK_SIZE as usize + 100);
+        conn.write(path, 0, &data).await.unwrap();
+
+        // Content never reaches the backend in the clear
+        let stored = conn.inner.files.get(path).unwrap().clone();
+        assert_eq!(stored.len() as u64, stored_size(data.len() as u64));
+        assert!(!stored.windows(64).any(|w| w == &data[1000..1064]));
+
+        assert_eq!(conn.stat(path).await.unwrap().size, data.len() as u64);
+        let offset = CHUNK_SIZE as usize - 10;
+        let read = conn.read(path, offset as u64, 20_000).await.unwrap();
+        assert_eq!(&read[..], &data[offset..offset + 20_000]);
+
+        let tail = conn.read(path, data.len() as u64 - 50, 4096).await.unwrap();
+        assert_eq!(&tail[..], &data[data.len() - 50..]);
+    }
+
+    #[tokio::test]
+    async fn test_random_writes_and_truncate() {
+        let conn = connector(true, false);
+        let path = Path::new("/file");
+        let mut expected = pattern(2 * CHUNK_SIZE as usize);
+        conn.write(path, 0, &expected).await.unwrap();
+
+        // Overwrite across a chunk boundary and extend past EOF with a hole
+        let patch = vec![0xAB; 100];
+        let at = CHUNK_SIZE as usize - 50;
+        conn.write(path, at as u64, &patch).await.unwrap();
+        expected[at..at + 100].copy_from_slice(&patch);
+        let far = 3 * CHUNK_SIZE as usize;
+        conn.write(path, far as u64, b"end").await.unwrap();
+        expected.resize(far, 0);
+        expected.extend_from_slice(b"end");
+
+        let size = conn.stat(path).await.unwrap().size;
+        assert_eq!(size, expected.len() as u64);
+        assert_eq!(
+            &conn.read(path, 0, size as u32).await.unwrap()[..],
+            &expected[..]
+        );
+
+        conn.truncate(path, CHUNK_SIZE + 7).await.unwrap();
+        expected.truncate(CHUNK_SIZE as usize + 7);
+        let size = conn.stat(path).await.unwrap().size;
+        assert_eq!(size, expected.len() as u64);
+        assert_eq!(
+            &conn.read(path, 0, size as u32).await.unwrap()[..],
+            &expected[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tampered_chunk_fails_to_read() {
+        let conn = connector(false, false);
+        let path = Path::new("/secret");
+        conn.write(path, 0, b"attack at dawn").await.unwrap();
+
+        conn.inner.files.get_mut(path).unwrap()[HEADER_LEN as usize + 30] ^= 1;
+        conn.invalidate_caches();
+        let err = conn.read(path, 0, 100).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EIO);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_names() {
+        let conn = connector(false, true);
+        conn.write(Path::new("/report.txt"), 0, b"numbers")
+            .await
+            .unwrap();
+
+        let stored: Vec<PathBuf> = conn.inner.files.iter().map(|e| e.key().clone()).collect();
+        assert_eq!(stored.len(), 1);
+        assert!(!stored[0].to_string_lossy().contains("report"));
+
+        let entries: Vec<DirEntry> = conn
+            .list_dir(Path::new("/"))
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "report.txt");
+        assert_eq!(entries[0].metadata.as_ref().unwrap().size, 7);
+        assert_eq!(
+            conn.read(Path::new("/report.txt"), 0, 100).await.unwrap(),
+            "numbers"
+        );
+    }
+}
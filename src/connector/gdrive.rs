@@ -13,37 +13,328 @@ use std::time::{Duration, SystemTime};
 use async_stream::try_stream;
 use async_trait::async_trait;
 use bytes::Bytes;
-use google_drive3::api::{File, Scope};
+use chrono::{DateTime, Utc};
+use google_drive3::api::{File, FileListCall, Scope};
 use google_drive3::DriveHub;
 use http_body_util::BodyExt;
 use hyper_util::client::legacy::connect::HttpConnector;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use tracing::{debug, trace};
 
 use crate::auth::http::{HttpTokenProvider, HttpTokenProviderConfig};
-use crate::auth::{ServiceAccountProvider, StaticTokenProvider, TokenProviderWrapper};
+use crate::auth::{
+    OAuthProvider, ServiceAccountProvider, StaticTokenProvider, TokenProviderWrapper,
+};
 use crate::config::{GDriveAuthConfig, GDriveConnectorConfig};
 use crate::connector::{
-    CacheRequirement, CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream,
-    Metadata,
+    copy_content, CacheRequirement, CacheRequirements, Capabilities, Connector, DirEntry,
+    DirEntryStream, Metadata,
 };
 use crate::error::{FuseAdapterError, Result};
+use crate::integrity::{md5_hex, ContentHash};
 
 /// MIME type for Google Drive folders
 const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
 
+/// MIME type prefix for native Google files (Docs, Sheets, ...)
+const GOOGLE_APPS_MIME_PREFIX: &str = "application/vnd.google-apps.";
+
+/// Supported exports: (native kind, file extension, export MIME type)
+const EXPORT_FORMATS: &[(&str, &str, &str)] = &[
+    (
+        "document",
+        "docx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    ),
+    ("document", "odt", "application/vnd.oasis.opendocument.text"),
+    ("document", "rtf", "application/rtf"),
+    ("document", "pdf", "application/pdf"),
+    ("document", "txt", "text/plain"),
+    ("document", "html", "text/html"),
+    ("document", "md", "text/markdown"),
+    ("document", "epub", "application/epub+zip"),
+    (
+        "spreadsheet",
+        "xlsx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    ),
+    (
+        "spreadsheet",
+        "ods",
+        "application/vnd.oasis.opendocument.spreadsheet",
+    ),
+    ("spreadsheet", "csv", "text/csv"),
+    ("spreadsheet", "tsv", "text/tab-separated-values"),
+    ("spreadsheet", "pdf", "application/pdf"),
+    (
+        "presentation",
+        "pptx",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    ),
+    (
+        "presentation",
+        "odp",
+        "application/vnd.oasis.opendocument.presentation",
+    ),
+    ("presentation", "pdf", "application/pdf"),
+    ("presentation", "txt", "text/plain"),
+    ("drawing", "png", "image/png"),
+    ("drawing", "jpg", "image/jpeg"),
+    ("drawing", "svg", "image/svg+xml"),
+    ("drawing", "pdf", "application/pdf"),
+];
+
+/// Drive refuses to export anything larger, so this bounds the size of
+/// every export
+const EXPORT_SIZE_LIMIT: u64 = 10 * 1024 * 1024;
+
+/// Total size of the exports kept in memory
+const EXPORT_CACHE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Look up the export MIME type for a native Google file kind and extension
+pub fn export_mime_type(kind: &str, extension: &str) -> Option<&'static str> {
+    EXPORT_FORMATS
+        .iter()
+        .find(|(k, ext, _)| *k == kind && *ext == extension)
+        .map(|(_, _, mime)| *mime)
+}
+
+/// Get the configured export (extension, MIME type) for a Drive MIME type
+fn export_target<'a>(
+    export_formats: &'a HashMap<String, String>,
+    mime_type: Option<&str>,
+) -> Option<(&'a str, &'static str)> {
+    let kind = mime_type?.strip_prefix(GOOGLE_APPS_MIME_PREFIX)?;
+    let extension = export_formats.get(kind)?;
+    let export_mime = export_mime_type(kind, extension)?;
+    Some((extension.as_str(), export_mime))
+}
+
+/// Name a file is listed under, with the export extension for native files
+fn display_name(export_formats: &HashMap<String, String>, file: &File) -> Option<String> {
+    let name = file.name.clone()?;
+    match export_target(export_formats, file.mime_type.as_deref()) {
+        Some((extension, _)) => Some(format!("{}.{}", name, extension)),
+        None => Some(name),
+    }
+}
+
+/// Exported bytes tagged with the modification time they were exported at
+struct CachedExport {
+    modified: Option<DateTime<Utc>>,
+    bytes: Bytes,
+    /// Value of the cache's clock when last used
+    last_used: u64,
+}
+
+/// Exports of native files by file ID, dropping the least recently used
+/// ones once they hold more than `capacity` bytes
+struct ExportCache {
+    capacity: u64,
+    size: u64,
+    clock: u64,
+    entries: HashMap<String, CachedExport>,
+}
+
+impl ExportCache {
+    fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            clock: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// The export of `file_id` and the modification time it was made at
+    fn get(&mut self, file_id: &str) -> Option<(Option<DateTime<Utc>>, Bytes)> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(file_id)?;
+        entry.last_used = self.clock;
+        Some((entry.modified, entry.bytes.clone()))
+    }
+
+    fn insert(&mut self, file_id: &str, modified: Option<DateTime<Utc>>, bytes: Bytes) {
+        self.remove(file_id);
+        self.clock += 1;
+        self.size += bytes.len() as u64;
+        self.entries.insert(
+            file_id.to_string(),
+            CachedExport {
+                modified,
+                bytes,
+                last_used: self.clock,
+            },
+        );
+        while self.size > self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(id, _)| id.as_str() != file_id)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| id.clone());
+            match oldest {
+                Some(id) => self.remove(&id),
+                None => break,
+            }
+        }
+    }
+
+    fn remove(&mut self, file_id: &str) {
+        if let Some(entry) = self.entries.remove(file_id) {
+            self.size -= entry.bytes.len() as u64;
+        }
+    }
+}
+
+/// Map a failed Drive API call to the error its errno should reflect
+///
+/// Rate limiting (429, 503 or a `*RateLimitExceeded` reason) becomes EAGAIN,
+/// a full storage quota EDQUOT and other 401/403s EACCES, instead of a
+/// generic EIO. `context` names the call in the message.
+fn drive_error(context: &str, e: google_drive3::Error) -> FuseAdapterError {
+    let message = format!("{} error: {}", context, e);
+    let (status, reasons) = match &e {
+        google_drive3::Error::BadRequest(body) => {
+            let error = &body["error"];
+            let reasons = error["errors"]
+                .as_array()
+                .map(|errors| {
+                    errors
+                        .iter()
+                        .filter_map(|e| e["reason"].as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default();
+            (error["code"].as_u64().map(|c| c as u16), reasons)
+        }
+        google_drive3::Error::Failure(response) => (Some(response.status().as_u16()), Vec::new()),
+        google_drive3::Error::UploadSizeLimitExceeded(..) => return FuseAdapterError::FileTooLarge,
+        _ => (None, Vec::new()),
+    };
+    let has_reason = |names: &[&str]| reasons.iter().any(|r| names.contains(&r.as_str()));
+
+    if status == Some(429)
+        || status == Some(503)
+        || has_reason(&[
+            "rateLimitExceeded",
+            "userRateLimitExceeded",
+            "sharingRateLimitExceeded",
+        ])
+    {
+        FuseAdapterError::TooManyRequests(message)
+    } else if has_reason(&["storageQuotaExceeded"]) {
+        FuseAdapterError::QuotaExceeded(message)
+    } else if status == Some(404) || has_reason(&["notFound"]) {
+        FuseAdapterError::NotFound(message)
+    } else if matches!(status, Some(401 | 403)) {
+        FuseAdapterError::PermissionDenied
+    } else {
+        FuseAdapterError::Backend(message)
+    }
+}
+
 /// Fields to request for file metadata
-const FILE_FIELDS: &str = "id, name, mimeType, size, modifiedTime, parents";
+const FILE_FIELDS: &str = "id, name, mimeType, size, modifiedTime, parents, md5Checksum";
 
 /// Fields to request for file list
 const LIST_FIELDS: &str = "nextPageToken, files(id, name, mimeType, size, modifiedTime)";
 
-type DriveClient = DriveHub<hyper_rustls::HttpsConnector<HttpConnector>>;
+type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+type DriveClient = DriveHub<HttpsConnector>;
+
+/// Start a files.list call scoped to the configured drive.
+///
+/// Shared drive items are only returned when both `supportsAllDrives` and
+/// `includeItemsFromAllDrives` are set; `driveId` narrows the search corpus
+/// to a single shared drive.
+fn list_files<'a>(
+    hub: &'a DriveClient,
+    drive_id: Option<&str>,
+    supports_all_drives: bool,
+) -> FileListCall<'a, HttpsConnector> {
+    let mut call = hub.files().list().add_scope(Scope::Full);
+    if supports_all_drives {
+        call = call
+            .supports_all_drives(true)
+            .include_items_from_all_drives(true);
+    }
+    if let Some(drive_id) = drive_id {
+        call = call.corpora("drive").drive_id(drive_id);
+    }
+    call
+}
+
+/// Find the ID of a child by its listed name.
+///
+/// Exact name matches win; otherwise a name carrying an export extension
+/// (e.g. `Report.docx`) is matched against native files of that kind.
+async fn find_child(
+    hub: &DriveClient,
+    drive_id: Option<&str>,
+    supports_all_drives: bool,
+    export_formats: &HashMap<String, String>,
+    parent_id: &str,
+    name: &str,
+) -> Result<Option<String>> {
+    let mut queries = vec![format!(
+        "'{}' in parents and name = '{}' and trashed = false",
+        parent_id, name
+    )];
+    for (kind, extension) in export_formats {
+        if let Some(base) = name.strip_suffix(&format!(".{}", extension)) {
+            if !base.is_empty() {
+                queries.push(format!(
+                    "'{}' in parents and name = '{}' and mimeType = '{}{}' and trashed = false",
+                    parent_id, base, GOOGLE_APPS_MIME_PREFIX, kind
+                ));
+            }
+        }
+    }
+
+    for (i, query) in queries.iter().enumerate() {
+        let result = list_files(hub, drive_id, supports_all_drives)
+            .q(query)
+            .param("fields", LIST_FIELDS)
+            .page_size(1)
+            .doit()
+            .await
+            .map_err(|e| drive_error("Drive API", e))?;
+
+        let files = result.1.files.unwrap_or_default();
+        let Some(file) = files.into_iter().next() else {
+            continue;
+        };
+
+        // A native file matched by its bare name is only reachable via its
+        // export name, so don't let "Report" resolve to the Doc "Report"
+        if i == 0 && export_target(export_formats, file.mime_type.as_deref()).is_some() {
+            continue;
+        }
+
+        let file_id = file
+            .id
+            .ok_or_else(|| FuseAdapterError::Backend("File has no ID".to_string()))?;
+        return Ok(Some(file_id));
+    }
+
+    Ok(None)
+}
 
 /// Google Drive connector
 pub struct GDriveConnector {
     hub: Arc<DriveClient>,
     root_folder_id: String,
+    /// Shared drive ID (None for My Drive)
+    drive_id: Option<String>,
+    /// Whether to address shared drive items in API calls
+    supports_all_drives: bool,
+    /// Native Google file kind -> export extension
+    export_formats: HashMap<String, String>,
+    /// Export MIME type of each file ID looked up (None if not native)
+    export_kinds: RwLock<HashMap<String, Option<&'static str>>>,
+    /// Recent exports of native files
+    export_cache: Mutex<ExportCache>,
     /// Cache mapping paths to file IDs
     path_cache: RwLock<HashMap<String, String>>,
 }
@@ -69,7 +360,12 @@ impl GDriveConnector {
                 .build(https);
 
         // Create Drive hub with token provider
-        let hub = DriveHub::new(client, token_provider);
+        let mut hub = DriveHub::new(client, token_provider);
+        if let Some(endpoint) = &config.endpoint {
+            let root = format!("{}/", endpoint.trim_end_matches('/'));
+            hub.base_url(format!("{}drive/v3/", root));
+            hub.root_url(root);
+        }
 
         // Initialize path cache with root
         let mut path_cache = HashMap::new();
@@ -79,6 +375,11 @@ impl GDriveConnector {
         Ok(Self {
             hub: Arc::new(hub),
             root_folder_id: config.root_folder_id,
+            drive_id: config.drive_id,
+            supports_all_drives: config.supports_all_drives,
+            export_formats: config.export_formats,
+            export_kinds: RwLock::new(HashMap::new()),
+            export_cache: Mutex::new(ExportCache::new(EXPORT_CACHE_SIZE)),
             path_cache: RwLock::new(path_cache),
         })
     }
@@ -97,15 +398,40 @@ impl GDriveConnector {
                     })?;
                 Ok(TokenProviderWrapper::new(provider))
             }
+            GDriveAuthConfig::Oauth {
+                client_secret_path,
+                token_path,
+                flow,
+                redirect_port,
+            } => {
+                let provider =
+                    OAuthProvider::new(client_secret_path, *flow, *redirect_port, token_path)
+                        .await
+                        .map_err(|e| {
+                            FuseAdapterError::Backend(format!(
+                                "Failed to create OAuth provider: {}",
+                                e
+                            ))
+                        })?;
+                Ok(TokenProviderWrapper::new(provider))
+            }
             GDriveAuthConfig::Http {
                 endpoint,
                 method,
                 headers,
+                token_field,
+                expires_in_field,
+                max_attempts,
+                initial_backoff,
             } => {
                 let config = HttpTokenProviderConfig {
                     endpoint: endpoint.clone(),
                     method: method.clone(),
                     headers: headers.clone(),
+                    token_field: token_field.clone(),
+                    expires_in_field: expires_in_field.clone(),
+                    max_attempts: *max_attempts,
+                    initial_backoff: *initial_backoff,
                 };
                 let provider = HttpTokenProvider::new(config);
                 Ok(TokenProviderWrapper::new(provider))
@@ -162,36 +488,18 @@ impl GDriveConnector {
             }
 
             // Query for the child with this name
-            let query = format!(
-                "'{}' in parents and name = '{}' and trashed = false",
-                current_id, component
-            );
-
-            let result = self
-                .hub
-                .files()
-                .list()
-                .q(&query)
-                .add_scope(Scope::Full)
-                .param("fields", LIST_FIELDS)
-                .page_size(1)
-                .doit()
-                .await
-                .map_err(|e| FuseAdapterError::Backend(format!("Drive API error: {}", e)))?;
-
-            let files = result.1.files.unwrap_or_default();
-            if files.is_empty() {
-                return Err(FuseAdapterError::NotFound(format!(
-                    "Path not found: {}",
-                    current_path
-                )));
-            }
-
-            let file = &files[0];
-            let file_id = file
-                .id
-                .clone()
-                .ok_or_else(|| FuseAdapterError::Backend("File has no ID".to_string()))?;
+            let file_id = find_child(
+                &self.hub,
+                self.drive_id.as_deref(),
+                self.supports_all_drives,
+                &self.export_formats,
+                &current_id,
+                component,
+            )
+            .await?
+            .ok_or_else(|| {
+                FuseAdapterError::NotFound(format!("Path not found: {}", current_path))
+            })?;
 
             // Cache this path
             self.path_cache
@@ -234,15 +542,15 @@ impl GDriveConnector {
             .files()
             .get(file_id)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
             .param("fields", FILE_FIELDS)
             .doit()
             .await
-            .map_err(|e| {
-                if e.to_string().contains("404") || e.to_string().contains("notFound") {
+            .map_err(|e| match drive_error("Drive API", e) {
+                FuseAdapterError::NotFound(_) => {
                     FuseAdapterError::NotFound(format!("File not found: {}", file_id))
-                } else {
-                    FuseAdapterError::Backend(format!("Drive API error: {}", e))
                 }
+                e => e,
             })?;
 
         Ok(result.1)
@@ -279,6 +587,67 @@ impl GDriveConnector {
         }
     }
 
+    /// Export a native Google file, reusing the last export if unmodified
+    async fn export_file(&self, file: &File, export_mime: &str) -> Result<Bytes> {
+        let file_id = file
+            .id
+            .as_deref()
+            .ok_or_else(|| FuseAdapterError::Backend("File has no ID".to_string()))?;
+
+        if let Some((modified, bytes)) = self.export_cache.lock().get(file_id) {
+            if modified == file.modified_time {
+                return Ok(bytes);
+            }
+        }
+
+        debug!("export: {} as {}", file_id, export_mime);
+        let response = self
+            .hub
+            .files()
+            .export(file_id, export_mime)
+            .add_scope(Scope::Full)
+            .doit()
+            .await
+            .map_err(|e| drive_error("Drive export", e))?;
+
+        let collected =
+            response.into_body().collect().await.map_err(|e| {
+                FuseAdapterError::Backend(format!("Failed to read export body: {}", e))
+            })?;
+        let bytes = collected.to_bytes();
+
+        self.export_cache
+            .lock()
+            .insert(file_id, file.modified_time, bytes.clone());
+        Ok(bytes)
+    }
+
+    /// Remember whether `file` is exported, returning its export MIME type
+    fn record_export_kind(&self, file: &File) -> Option<&'static str> {
+        if self.export_formats.is_empty() {
+            return None;
+        }
+        let export_mime = export_target(&self.export_formats, file.mime_type.as_deref())
+            .map(|(_, export_mime)| export_mime);
+        if let Some(id) = &file.id {
+            self.export_kinds.write().insert(id.clone(), export_mime);
+        }
+        export_mime
+    }
+
+    /// Export MIME type of a file if it is a native file with an export
+    /// mapping; the Drive lookup is only made the first time
+    async fn export_mime(&self, file_id: &str) -> Result<Option<&'static str>> {
+        if self.export_formats.is_empty() {
+            return Ok(None);
+        }
+        if let Some(export_mime) = self.export_kinds.read().get(file_id) {
+            return Ok(*export_mime);
+        }
+        let file = self.get_file_metadata(file_id).await?;
+        Ok(self.record_export_kind(&file))
+    }
+
     /// Invalidate a path from the cache
     fn invalidate_path(&self, path: &Path) {
         let normalized = Self::normalize_path(path);
@@ -323,7 +692,25 @@ impl Connector for GDriveConnector {
 
         let file_id = self.resolve_path(path).await?;
         let file = self.get_file_metadata(&file_id).await?;
-        Self::file_to_metadata(&file)
+        let mut metadata = Self::file_to_metadata(&file)?;
+
+        // Native files report no size. Exporting one here would export every
+        // file a listing stats, so use the size of an export already made
+        // of this version, or else the most an export can be: reads stop
+        // short at the real end, which the kernel takes as end of file
+        if self.record_export_kind(&file).is_some() {
+            let mut exports = self.export_cache.lock();
+            metadata.size = match exports.get(&file_id) {
+                Some((modified, bytes)) if modified == file.modified_time => bytes.len() as u64,
+                Some(_) => {
+                    exports.remove(&file_id);
+                    EXPORT_SIZE_LIMIT
+                }
+                None => EXPORT_SIZE_LIMIT,
+            };
+            metadata.mode = Some(0o444);
+        }
+        Ok(metadata)
     }
 
     async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
@@ -331,21 +718,37 @@ impl Connector for GDriveConnector {
 
         let file_id = self.resolve_path(path).await?;
 
+        if let Some(export_mime) = self.export_mime(&file_id).await? {
+            // A file is read in chunks; later ones reuse the export. Stat
+            // drops an export once the file changes
+            let cached = self.export_cache.lock().get(&file_id);
+            let bytes = match cached {
+                Some((_, bytes)) => bytes,
+                None => {
+                    let file = self.get_file_metadata(&file_id).await?;
+                    self.export_file(&file, export_mime).await?
+                }
+            };
+            let start = std::cmp::min(offset as usize, bytes.len());
+            let end = std::cmp::min(start + size as usize, bytes.len());
+            return Ok(bytes.slice(start..end));
+        }
+
         // Download the file content
         let response = self
             .hub
             .files()
             .get(&file_id)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
             .param("alt", "media")
             .doit()
             .await
-            .map_err(|e| {
-                if e.to_string().contains("404") || e.to_string().contains("notFound") {
+            .map_err(|e| match drive_error("Drive API", e) {
+                FuseAdapterError::NotFound(_) => {
                     FuseAdapterError::NotFound(format!("File not found: {:?}", path))
-                } else {
-                    FuseAdapterError::Backend(format!("Drive API error: {}", e))
                 }
+                e => e,
             })?;
 
         // Read the body
@@ -378,16 +781,37 @@ impl Connector for GDriveConnector {
 
         let file_id = self.resolve_path(path).await?;
 
+        // Exports are one-way; native files can only be edited in Drive
+        if self.export_mime(&file_id).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+
         // Upload using media upload
         let cursor = std::io::Cursor::new(data.to_vec());
 
-        self.hub
+        let (_, file) = self
+            .hub
             .files()
             .update(File::default(), &file_id)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
+            .param("fields", "id, md5Checksum")
             .upload(cursor, "application/octet-stream".parse().unwrap())
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("Drive upload error: {}", e)))?;
+            .map_err(|e| drive_error("Drive upload", e))?;
+
+        // Drive computes an MD5 of what it stored; make sure that's what we sent
+        if let Some(stored) = file.md5_checksum {
+            let sent = md5_hex(data);
+            if !stored.eq_ignore_ascii_case(&sent) {
+                return Err(FuseAdapterError::IntegrityMismatch(format!(
+                    "{}: uploaded md5:{} but Drive stored md5:{}",
+                    path.display(),
+                    sent,
+                    stored
+                )));
+            }
+        }
 
         Ok(data.len() as u64)
     }
@@ -410,9 +834,10 @@ impl Connector for GDriveConnector {
             .files()
             .create(file_metadata)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
             .upload(cursor, "application/octet-stream".parse().unwrap())
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("Drive create error: {}", e)))?;
+            .map_err(|e| drive_error("Drive create", e))?;
 
         // Cache the new file's ID
         if let Some(id) = result.1.id {
@@ -444,9 +869,10 @@ impl Connector for GDriveConnector {
             .files()
             .create(folder_metadata)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
             .upload(cursor, FOLDER_MIME_TYPE.parse().unwrap())
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("Drive create folder error: {}", e)))?;
+            .map_err(|e| drive_error("Drive create folder", e))?;
 
         // Cache the new folder's ID
         if let Some(id) = result.1.id {
@@ -466,11 +892,14 @@ impl Connector for GDriveConnector {
             .files()
             .delete(&file_id)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
             .doit()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("Drive delete error: {}", e)))?;
+            .map_err(|e| drive_error("Drive delete", e))?;
 
         self.invalidate_path(path);
+        self.export_kinds.write().remove(&file_id);
+        self.export_cache.lock().remove(&file_id);
         Ok(())
     }
 
@@ -482,16 +911,16 @@ impl Connector for GDriveConnector {
         if !recursive {
             // Check if folder is empty
             let query = format!("'{}' in parents and trashed = false", file_id);
-            let result = self
-                .hub
-                .files()
-                .list()
-                .q(&query)
-                .add_scope(Scope::Full)
-                .page_size(1)
-                .doit()
-                .await
-                .map_err(|e| FuseAdapterError::Backend(format!("Drive API error: {}", e)))?;
+            let result = list_files(
+                &self.hub,
+                self.drive_id.as_deref(),
+                self.supports_all_drives,
+            )
+            .q(&query)
+            .page_size(1)
+            .doit()
+            .await
+            .map_err(|e| drive_error("Drive API", e))?;
 
             let files = result.1.files.unwrap_or_default();
             if !files.is_empty() {
@@ -507,9 +936,10 @@ impl Connector for GDriveConnector {
             .files()
             .delete(&file_id)
             .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
             .doit()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("Drive delete error: {}", e)))?;
+            .map_err(|e| drive_error("Drive delete", e))?;
 
         self.invalidate_path_recursive(path);
         Ok(())
@@ -527,11 +957,12 @@ impl Connector for GDriveConnector {
             let mut page_token: Option<String> = None;
 
             loop {
-                let mut request = hub
-                    .files()
-                    .list()
+                let mut request = list_files(
+                    &hub,
+                    connector.drive_id.as_deref(),
+                    connector.supports_all_drives,
+                )
                     .q(&query)
-                    .add_scope(Scope::Full)
                     .param("fields", LIST_FIELDS)
                     .page_size(100);
 
@@ -539,14 +970,12 @@ impl Connector for GDriveConnector {
                     request = request.page_token(&token);
                 }
 
-                let result = request.doit().await.map_err(|e| {
-                    FuseAdapterError::Backend(format!("Drive list error: {}", e))
-                })?;
+                let result = request.doit().await.map_err(|e| drive_error("Drive list", e))?;
 
                 let files = result.1.files.unwrap_or_default();
 
                 for file in files {
-                    let name = match file.name {
+                    let name = match display_name(&connector.export_formats, &file) {
                         Some(n) => n,
                         None => continue,
                     };
@@ -580,7 +1009,17 @@ impl Connector for GDriveConnector {
 
         // Get current parents
         let file = self.get_file_metadata(&file_id).await?;
-        let current_parents = file.parents.unwrap_or_default().join(",");
+        let current_parents = file.parents.clone().unwrap_or_default().join(",");
+
+        // Native files are listed with their export extension; don't store it
+        let new_name = match export_target(&self.export_formats, file.mime_type.as_deref()) {
+            Some((extension, _)) => new_name
+                .strip_suffix(&format!(".{}", extension))
+                .filter(|base| !base.is_empty())
+                .map(str::to_string)
+                .unwrap_or(new_name),
+            None => new_name,
+        };
 
         // Update file with new name and parent using PATCH (param method)
         let update = File {
@@ -588,18 +1027,17 @@ impl Connector for GDriveConnector {
             ..Default::default()
         };
 
-        // For metadata-only update, use upload with empty content
-        let cursor = std::io::Cursor::new(Vec::<u8>::new());
-
+        // Metadata-only update; uploading empty media would replace the content
         self.hub
             .files()
             .update(update, &file_id)
             .add_parents(&new_parent_id)
             .remove_parents(&current_parents)
             .add_scope(Scope::Full)
-            .upload(cursor, "application/octet-stream".parse().unwrap())
+            .supports_all_drives(self.supports_all_drives)
+            .doit_without_upload()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("Drive rename error: {}", e)))?;
+            .map_err(|e| drive_error("Drive rename", e))?;
 
         // Invalidate cache for both paths
         self.invalidate_path_recursive(from);
@@ -622,6 +1060,68 @@ impl Connector for GDriveConnector {
         // Google Drive writes are immediately durable
         Ok(())
     }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        debug!("copy: {:?} -> {:?}", from, to);
+
+        let file_id = self.resolve_path(from).await?;
+        let file = self.get_file_metadata(&file_id).await?;
+        if file.mime_type.as_deref() == Some(FOLDER_MIME_TYPE) {
+            return Err(FuseAdapterError::IsADirectory(from.display().to_string()));
+        }
+        // A copy of a native file would be native too; copy the export instead
+        if self.record_export_kind(&file).is_some() {
+            return copy_content(self, from, to).await;
+        }
+
+        // Drive allows duplicate names, so the old file is removed afterwards
+        let replaced = match self.resolve_path(to).await {
+            Ok(id) => Some(id),
+            Err(FuseAdapterError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let (parent_id, file_name) = self.resolve_parent(to).await?;
+        let copy_metadata = File {
+            name: Some(file_name),
+            parents: Some(vec![parent_id]),
+            ..Default::default()
+        };
+
+        let (_, copied) = self
+            .hub
+            .files()
+            .copy(copy_metadata, &file_id)
+            .add_scope(Scope::Full)
+            .supports_all_drives(self.supports_all_drives)
+            .param("fields", "id")
+            .doit()
+            .await
+            .map_err(|e| drive_error("Drive copy", e))?;
+
+        if let Some(old_id) = replaced {
+            self.hub
+                .files()
+                .delete(&old_id)
+                .add_scope(Scope::Full)
+                .supports_all_drives(self.supports_all_drives)
+                .doit()
+                .await
+                .map_err(|e| drive_error("Drive delete", e))?;
+        }
+        if let Some(id) = copied.id {
+            let normalized = Self::normalize_path(to);
+            self.path_cache.write().insert(normalized, id);
+        }
+
+        Ok(())
+    }
+
+    async fn content_hash(&self, path: &Path, _etag: Option<&str>) -> Result<Option<ContentHash>> {
+        let file_id = self.resolve_path(path).await?;
+        // Native Google files have no md5Checksum
+        let file = self.get_file_metadata(&file_id).await?;
+        Ok(file.md5_checksum.map(ContentHash::Md5))
+    }
 }
 
 impl GDriveConnector {
@@ -630,6 +1130,9 @@ impl GDriveConnector {
         GDriveConnectorInner {
             hub: self.hub.clone(),
             root_folder_id: self.root_folder_id.clone(),
+            drive_id: self.drive_id.clone(),
+            supports_all_drives: self.supports_all_drives,
+            export_formats: self.export_formats.clone(),
             path_cache: self.path_cache.read().clone(),
         }
     }
@@ -639,6 +1142,9 @@ impl GDriveConnector {
 struct GDriveConnectorInner {
     hub: Arc<DriveClient>,
     root_folder_id: String,
+    drive_id: Option<String>,
+    supports_all_drives: bool,
+    export_formats: HashMap<String, String>,
     path_cache: HashMap<String, String>,
 }
 
@@ -666,38 +1172,82 @@ impl GDriveConnectorInner {
 
         for component in components {
             // Query for the child with this name
-            let query = format!(
-                "'{}' in parents and name = '{}' and trashed = false",
-                current_id, component
-            );
+            current_id = find_child(
+                &self.hub,
+                self.drive_id.as_deref(),
+                self.supports_all_drives,
+                &self.export_formats,
+                &current_id,
+                component,
+            )
+            .await?
+            .ok_or_else(|| FuseAdapterError::NotFound(format!("Path not found: {:?}", path)))?;
+        }
 
-            let result = self
-                .hub
-                .files()
-                .list()
-                .q(&query)
-                .add_scope(Scope::Full)
-                .param("fields", LIST_FIELDS)
-                .page_size(1)
-                .doit()
-                .await
-                .map_err(|e| FuseAdapterError::Backend(format!("Drive API error: {}", e)))?;
+        Ok(current_id)
+    }
+}
 
-            let files = result.1.files.unwrap_or_default();
-            if files.is_empty() {
-                return Err(FuseAdapterError::NotFound(format!(
-                    "Path not found: {:?}",
-                    path
-                )));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drive_errno(code: u16, reason: &str) -> i32 {
+        let body = serde_json::json!({
+            "error": {
+                "code": code,
+                "message": "failed",
+                "errors": [{ "domain": "usageLimits", "reason": reason }],
             }
+        });
+        drive_error("Drive API", google_drive3::Error::BadRequest(body)).to_errno()
+    }
 
-            let file = &files[0];
-            current_id = file
-                .id
-                .clone()
-                .ok_or_else(|| FuseAdapterError::Backend("File has no ID".to_string()))?;
-        }
+    #[test]
+    fn test_drive_error_errno() {
+        assert_eq!(drive_errno(403, "userRateLimitExceeded"), libc::EAGAIN);
+        assert_eq!(drive_errno(403, "rateLimitExceeded"), libc::EAGAIN);
+        assert_eq!(drive_errno(429, "rateLimitExceeded"), libc::EAGAIN);
+        assert_eq!(drive_errno(503, "backendError"), libc::EAGAIN);
+        assert_eq!(drive_errno(403, "storageQuotaExceeded"), libc::EDQUOT);
+        assert_eq!(
+            drive_errno(403, "insufficientFilePermissions"),
+            libc::EACCES
+        );
+        assert_eq!(drive_errno(404, "notFound"), libc::ENOENT);
+        assert_eq!(drive_errno(500, "internalError"), libc::EIO);
+
+        let too_large = google_drive3::Error::UploadSizeLimitExceeded(10, 5);
+        assert_eq!(
+            drive_error("Drive upload", too_large).to_errno(),
+            libc::EFBIG
+        );
+    }
 
-        Ok(current_id)
+    #[test]
+    fn test_export_cache_evicts_least_recently_used() {
+        let mut cache = ExportCache::new(10);
+        let export = |n: usize| Bytes::from(vec![0u8; n]);
+        cache.insert("a", None, export(4));
+        cache.insert("b", None, export(4));
+        assert!(cache.get("a").is_some());
+
+        // "b" was used longest ago, so it makes room for "c"
+        cache.insert("c", None, export(4));
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert_eq!(cache.size, 8);
+
+        // Replacing an export doesn't count it twice, and one bigger than
+        // the whole cache is still kept until the next insert
+        cache.insert("a", None, export(2));
+        assert_eq!(cache.size, 6);
+        cache.insert("d", None, export(12));
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.size, 12);
+
+        cache.remove("d");
+        assert!(cache.get("d").is_none());
+        assert_eq!(cache.size, 0);
     }
 }
@@ -0,0 +1,204 @@
+//! Mapping between file names and S3 key components
+//!
+//! S3 keys are UTF-8 strings of at most 1024 bytes, while file names are any
+//! bytes other than `/` and NUL, up to NAME_MAX. With `raw` encoding names
+//! are used as key components unchanged. With `escape` encoding `%`, control
+//! characters (which S3's XML listings can't carry), bytes that aren't valid
+//! UTF-8 and any configured characters are written as `%XX`, so every name
+//! maps to its own key and back.
+//!
+//! Keys written by other tools may contain `%` sequences that decode to a
+//! different name than the key they came from; such objects are listed but
+//! can't be opened with `escape` encoding. Key components that can't be file
+//! names at all (empty, `.`, `..` or longer than NAME_MAX) are left out of
+//! listings.
+
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path};
+
+use percent_encoding::percent_decode_str;
+
+use crate::config::S3KeyEncoding;
+
+/// Longest file name the kernel passes to a filesystem
+const NAME_MAX: usize = 255;
+
+/// Longest key S3 accepts, in bytes
+pub const MAX_KEY_LEN: usize = 1024;
+
+/// Converts file names to key components and back
+#[derive(Debug, Clone, Default)]
+pub struct KeyCodec {
+    encoding: S3KeyEncoding,
+    /// Characters escaped in addition to the defaults with `escape` encoding
+    escape_chars: Vec<char>,
+}
+
+impl KeyCodec {
+    pub fn new(encoding: S3KeyEncoding, escape_chars: &str) -> Self {
+        Self {
+            encoding,
+            escape_chars: escape_chars.chars().collect(),
+        }
+    }
+
+    /// The key for `path`, relative to the bucket prefix
+    pub fn encode_path(&self, path: &Path) -> String {
+        let names: Vec<String> = path
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(name) => Some(self.encode_name(name)),
+                _ => None,
+            })
+            .collect();
+        names.join("/")
+    }
+
+    /// The key component for a file name
+    pub fn encode_name(&self, name: &OsStr) -> String {
+        if self.encoding == S3KeyEncoding::Raw {
+            return name.to_string_lossy().into_owned();
+        }
+
+        let mut key = String::with_capacity(name.len());
+        for chunk in name.as_bytes().utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if self.escapes(c) {
+                    let mut buf = [0; 4];
+                    for byte in c.encode_utf8(&mut buf).bytes() {
+                        push_escaped(&mut key, byte);
+                    }
+                } else {
+                    key.push(c);
+                }
+            }
+            for &byte in chunk.invalid() {
+                push_escaped(&mut key, byte);
+            }
+        }
+        key
+    }
+
+    /// The file name a key component is listed under, or None if the
+    /// component can't be a file name
+    pub fn decode_name(&self, component: &str) -> Option<OsString> {
+        let name = match self.encoding {
+            S3KeyEncoding::Raw => component.as_bytes().to_vec(),
+            S3KeyEncoding::Escape => percent_decode_str(component).collect(),
+        };
+        let valid = !name.is_empty()
+            && name != b"."
+            && name != b".."
+            && name.len() <= NAME_MAX
+            && !name.contains(&b'/')
+            && !name.contains(&0);
+        valid.then(|| OsString::from_vec(name))
+    }
+
+    /// Whether `c` is written as `%XX` with `escape` encoding
+    fn escapes(&self, c: char) -> bool {
+        c == '%' || c.is_control() || self.escape_chars.contains(&c)
+    }
+}
+
+/// Append `byte` to `key` as `%XX`
+fn push_escaped(key: &mut String, byte: u8) {
+    key.push_str(&format!("%{:02X}", byte));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn escape() -> KeyCodec {
+        KeyCodec::new(S3KeyEncoding::Escape, "\\")
+    }
+
+    /// Any name the kernel can pass: 1 to NAME_MAX bytes without `/` or NUL
+    fn file_name() -> impl Strategy<Value = OsString> {
+        prop::collection::vec(
+            any::<u8>().prop_filter("no / or NUL", |b| *b != b'/' && *b != 0),
+            1..=NAME_MAX,
+        )
+        .prop_filter("not . or ..", |name| name != b"." && name != b"..")
+        .prop_map(OsString::from_vec)
+    }
+
+    #[test]
+    fn test_escape_encoding() {
+        let codec = escape();
+        assert_eq!(codec.encode_name(OsStr::new("café.txt")), "café.txt");
+        assert_eq!(codec.encode_name(OsStr::new("a\nb")), "a%0Ab");
+        assert_eq!(codec.encode_name(OsStr::new("100%")), "100%25");
+        assert_eq!(codec.encode_name(OsStr::new("a\\b")), "a%5Cb");
+        assert_eq!(codec.encode_name(OsStr::from_bytes(b"\xffx")), "%FFx");
+        assert_eq!(
+            codec.encode_path(Path::new("/dir/a\tb")),
+            "dir/a%09b".to_string()
+        );
+
+        // Raw encoding keeps names as they are
+        let raw = KeyCodec::default();
+        assert_eq!(raw.encode_name(OsStr::new("a\nb")), "a\nb");
+        assert_eq!(raw.decode_name("100%25"), Some(OsString::from("100%25")));
+    }
+
+    #[test]
+    fn test_decode_skips_invalid_names() {
+        for codec in [KeyCodec::default(), escape()] {
+            for component in ["", ".", "..", &"x".repeat(NAME_MAX + 1)] {
+                assert_eq!(codec.decode_name(component), None, "{:?}", component);
+            }
+        }
+        assert_eq!(escape().decode_name("a%2Fb"), None);
+        assert_eq!(escape().decode_name("a%00"), None);
+        // Stray `%` from keys written elsewhere are kept
+        assert_eq!(
+            escape().decode_name("50%off"),
+            Some(OsString::from("50%off"))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_names_round_trip(name in file_name()) {
+            let codec = escape();
+            let key = codec.encode_name(&name);
+            prop_assert!(!key.contains('/'));
+            prop_assert!(!key.chars().any(char::is_control));
+            prop_assert_eq!(codec.decode_name(&key), Some(name));
+        }
+
+        #[test]
+        fn prop_distinct_names_get_distinct_keys(a in file_name(), b in file_name()) {
+            let codec = escape();
+            prop_assume!(a != b);
+            prop_assert_ne!(codec.encode_name(&a), codec.encode_name(&b));
+        }
+
+        #[test]
+        fn prop_paths_round_trip(names in prop::collection::vec(file_name(), 0..4)) {
+            let codec = escape();
+            let path: std::path::PathBuf = std::iter::once(OsString::from("/")).chain(names.clone()).collect();
+            let key = codec.encode_path(&path);
+            prop_assert!(!key.starts_with('/') && !key.ends_with('/'));
+            let decoded: Vec<_> = key
+                .split('/')
+                .filter(|component| !component.is_empty())
+                .map(|component| codec.decode_name(component))
+                .collect();
+            prop_assert_eq!(decoded, names.into_iter().map(Some).collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn prop_keys_round_trip(key in "[^/%\\p{Cc}\\\\]{1,60}") {
+            // Keys without anything to escape are used as they are
+            let codec = escape();
+            prop_assume!(key != "." && key != "..");
+            let name = codec.decode_name(&key).unwrap();
+            prop_assert_eq!(codec.encode_name(&name), key);
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Key-value store connector
+//!
+//! Exposes a key-value store (e.g. a config store) as files: a file's path,
+//! under an optional key prefix, is its key and its content is the value.
+//! Directories are key prefixes ending in `/`. Like S3 they exist as long as
+//! any key is below them, and empty directories are kept with an empty
+//! marker key (`dir/`).
+//!
+//! The store is pluggable through the `KvStore` trait; `SledStore` keeps it
+//! in an embedded sled database. Stores keep no timestamps, so every entry
+//! reports the time the connector was created as its mtime.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::config::{KvBackend, KvConnectorConfig};
+use crate::connector::{Capabilities, Connector, DirEntry, DirEntryStream, FileType, Metadata};
+use crate::error::{FuseAdapterError, Result};
+
+/// A key-value store the connector keeps files in
+#[async_trait]
+pub trait KvStore: Send + Sync + 'static {
+    /// Get the value of `key`
+    async fn get(&self, key: &str) -> Result<Option<Bytes>>;
+
+    /// Set `key` to `value`, creating or replacing it
+    async fn put(&self, key: &str, value: Bytes) -> Result<()>;
+
+    /// Remove `key`; removing a missing key is not an error
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// All keys starting with `prefix`, in ascending order
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Whether any key starts with `prefix`
+    ///
+    /// Default implementation lists the keys
+    async fn has_prefix(&self, prefix: &str) -> Result<bool> {
+        Ok(!self.keys(prefix).await?.is_empty())
+    }
+
+    /// Make all earlier writes durable
+    ///
+    /// Default implementation does nothing (writes are durable immediately)
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Store backed by an embedded sled database
+pub struct SledStore {
+    db: sled::Db,
+}
+
+impl SledStore {
+    /// Open (or create) the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        let db = sled::open(path).map_err(|e| {
+            FuseAdapterError::Backend(format!("Failed to open sled database {:?}: {}", path, e))
+        })?;
+        Ok(Self { db })
+    }
+
+    /// Use an already opened database
+    pub fn from_db(db: sled::Db) -> Self {
+        Self { db }
+    }
+}
+
+fn sled_error(e: sled::Error) -> FuseAdapterError {
+    FuseAdapterError::Backend(format!("sled error: {}", e))
+}
+
+#[async_trait]
+impl KvStore for SledStore {
+    async fn get(&self, key: &str) -> Result<Option<Bytes>> {
+        let value = self.db.get(key).map_err(sled_error)?;
+        Ok(value.map(|v| Bytes::copy_from_slice(&v)))
+    }
+
+    async fn put(&self, key: &str, value: Bytes) -> Result<()> {
+        self.db.insert(key, value.as_ref()).map_err(sled_error)?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.db.remove(key).map_err(sled_error)?;
+        Ok(())
+    }
+
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for entry in self.db.scan_prefix(prefix) {
+            let (key, _) = entry.map_err(sled_error)?;
+            // Keys written by other tools may not be UTF-8; they can't be
+            // file names here
+            if let Ok(key) = String::from_utf8(key.to_vec()) {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn has_prefix(&self, prefix: &str) -> Result<bool> {
+        match self.db.scan_prefix(prefix).next() {
+            Some(entry) => entry.map(|_| true).map_err(sled_error),
+            None => Ok(false),
+        }
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.db.flush_async().await.map_err(sled_error)?;
+        Ok(())
+    }
+}
+
+/// Connector storing files as values of a `KvStore`
+pub struct KvConnector<S: KvStore> {
+    store: Arc<S>,
+    /// Prepended to every key, with a trailing `/` unless empty
+    prefix: String,
+    /// Reported as the mtime of every entry
+    mtime: SystemTime,
+    /// Serializes read-modify-write updates of values
+    write_lock: Mutex<()>,
+}
+
+impl KvConnector<SledStore> {
+    /// Open the store described by `config`
+    pub fn from_config(config: &KvConnectorConfig) -> Result<Self> {
+        let store = match config.backend {
+            KvBackend::Sled => SledStore::open(&config.path)?,
+        };
+        Ok(Self::new(store, config.prefix.as_deref()))
+    }
+}
+
+impl<S: KvStore> KvConnector<S> {
+    /// Expose the keys of `store` below `prefix` (None = the whole store)
+    pub fn new(store: S, prefix: Option<&str>) -> Self {
+        let prefix = match prefix.map(|p| p.trim_matches('/')) {
+            Some(p) if !p.is_empty() => format!("{}/", p),
+            _ => String::new(),
+        };
+        Self {
+            store: Arc::new(store),
+            prefix,
+            mtime: SystemTime::now(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// The key of the file at `path` (the prefix itself for the root)
+    fn path_to_key(&self, path: &Path) -> Result<String> {
+        let mut key = self.prefix.clone();
+        for component in path.components() {
+            if let Component::Normal(name) = component {
+                let name = name.to_str().ok_or_else(|| {
+                    FuseAdapterError::InvalidPath(format!("{:?} is not valid UTF-8", path))
+                })?;
+                if !key.is_empty() && !key.ends_with('/') {
+                    key.push('/');
+                }
+                key.push_str(name);
+            }
+        }
+        Ok(key)
+    }
+
+    /// The prefix of the keys inside the directory at `path`
+    fn dir_prefix(&self, path: &Path) -> Result<String> {
+        let mut key = self.path_to_key(path)?;
+        if !key.is_empty() && !key.ends_with('/') {
+            key.push('/');
+        }
+        Ok(key)
+    }
+
+    fn is_root(&self, key: &str) -> bool {
+        key == self.prefix
+    }
+
+    /// Read the value of a file, failing if it is missing or a directory
+    async fn get_file(&self, path: &Path) -> Result<Bytes> {
+        let key = self.path_to_key(path)?;
+        if let Some(value) = self.store.get(&key).await? {
+            if !self.is_root(&key) {
+                return Ok(value);
+            }
+        }
+        if self.is_dir(path).await? {
+            return Err(FuseAdapterError::IsADirectory(format!("{:?}", path)));
+        }
+        Err(FuseAdapterError::NotFound(format!("{:?}", path)))
+    }
+
+    async fn is_dir(&self, path: &Path) -> Result<bool> {
+        let key = self.path_to_key(path)?;
+        if self.is_root(&key) {
+            return Ok(true);
+        }
+        self.store.has_prefix(&self.dir_prefix(path)?).await
+    }
+}
+
+#[async_trait]
+impl<S: KvStore> Connector for KvConnector<S> {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: true,
+            range_read: true,
+            random_write: true,
+            rename: true,
+            truncate: true,
+            set_mtime: false, // Stores keep no timestamps
+            seekable: true,
+            set_mode: false,
+            symlink: false,
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let key = self.path_to_key(path)?;
+        if !self.is_root(&key) {
+            if let Some(value) = self.store.get(&key).await? {
+                return Ok(Metadata::file(value.len() as u64, self.mtime));
+            }
+        }
+        if self.is_dir(path).await? {
+            return Ok(Metadata::directory(self.mtime));
+        }
+        Err(FuseAdapterError::NotFound(format!("{:?}", path)))
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        let value = self.get_file(path).await?;
+        let start = (offset as usize).min(value.len());
+        let end = start.saturating_add(size as usize).min(value.len());
+        Ok(value.slice(start..end))
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        let _lock = self.write_lock.lock().await;
+        let key = self.path_to_key(path)?;
+        let mut value = match self.store.get(&key).await? {
+            Some(value) => value.to_vec(),
+            None => Vec::new(),
+        };
+        let end = offset as usize + data.len();
+        if value.len() < end {
+            value.resize(end, 0);
+        }
+        value[offset as usize..end].copy_from_slice(data);
+        self.store.put(&key, Bytes::from(value)).await?;
+        Ok(data.len() as u64)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        let _lock = self.write_lock.lock().await;
+        let key = self.path_to_key(path)?;
+        debug!("create_file: path={:?} key={}", path, key);
+        self.store.put(&key, Bytes::new()).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        let key = self.dir_prefix(path)?;
+        debug!("create_dir: path={:?} key={}", path, key);
+        self.store.put(&key, Bytes::new()).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        let _lock = self.write_lock.lock().await;
+        // Fails for directories and missing files
+        self.get_file(path).await?;
+        self.store.delete(&self.path_to_key(path)?).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        let _lock = self.write_lock.lock().await;
+        let prefix = self.dir_prefix(path)?;
+        debug!(
+            "remove_dir: path={:?} key={} recursive={}",
+            path, prefix, recursive
+        );
+        let keys = self.store.keys(&prefix).await?;
+        if !recursive && keys.iter().any(|k| *k != prefix) {
+            return Err(FuseAdapterError::NotEmpty(format!(
+                "Directory not empty: {:?}",
+                path
+            )));
+        }
+        for key in keys {
+            self.store.delete(&key).await?;
+        }
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let prefix = self.dir_prefix(path);
+        let store = self.store.clone();
+
+        Box::pin(try_stream! {
+            let prefix = prefix?;
+            let keys = store.keys(&prefix).await?;
+
+            // Keys below subdirectories collapse into one entry each
+            let mut entries = BTreeMap::new();
+            for key in &keys {
+                let rest = &key[prefix.len()..];
+                match rest.split_once('/') {
+                    Some((name, _)) => {
+                        entries.insert(name.to_string(), FileType::Directory);
+                    }
+                    None if !rest.is_empty() => {
+                        entries.entry(rest.to_string()).or_insert(FileType::File);
+                    }
+                    // The directory's own marker
+                    None => {}
+                }
+            }
+            for (name, file_type) in entries {
+                if name.is_empty() {
+                    continue;
+                }
+                yield match file_type {
+                    FileType::Directory => DirEntry::directory(name),
+                    _ => DirEntry::file(name),
+                };
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let _lock = self.write_lock.lock().await;
+        let from_key = self.path_to_key(from)?;
+        let to_key = self.path_to_key(to)?;
+        debug!("rename: {} -> {}", from_key, to_key);
+
+        if let Some(value) = self.store.get(&from_key).await? {
+            self.store.put(&to_key, value).await?;
+            return self.store.delete(&from_key).await;
+        }
+
+        let from_prefix = self.dir_prefix(from)?;
+        let to_prefix = self.dir_prefix(to)?;
+        let keys = self.store.keys(&from_prefix).await?;
+        if keys.is_empty() {
+            return Err(FuseAdapterError::NotFound(format!("{:?}", from)));
+        }
+        for key in keys {
+            let new_key = format!("{}{}", to_prefix, &key[from_prefix.len()..]);
+            if let Some(value) = self.store.get(&key).await? {
+                self.store.put(&new_key, value).await?;
+            }
+            self.store.delete(&key).await?;
+        }
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        let _lock = self.write_lock.lock().await;
+        let mut value = self.get_file(path).await?.to_vec();
+        value.resize(size as usize, 0);
+        self.store
+            .put(&self.path_to_key(path)?, Bytes::from(value))
+            .await
+    }
+
+    async fn flush(&self, _path: &Path) -> Result<()> {
+        self.store.flush().await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.store.flush().await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let value = self.get_file(from).await?;
+        self.store.put(&self.path_to_key(to)?, value).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn connector(prefix: Option<&str>) -> KvConnector<SledStore> {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        KvConnector::new(SledStore::from_db(db), prefix)
+    }
+
+    async fn list(connector: &KvConnector<SledStore>, path: &str) -> Vec<(String, FileType)> {
+        connector
+            .list_dir(Path::new(path))
+            .map(|e| {
+                let e = e.unwrap();
+                (e.name.to_string_lossy().into_owned(), e.file_type)
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_files_are_values() {
+        let kv = connector(Some("app"));
+        kv.write(Path::new("/db/host"), 0, b"localhost")
+            .await
+            .unwrap();
+        kv.write(Path::new("/db/host"), 5, b"HOST!").await.unwrap();
+
+        assert_eq!(
+            kv.store.get("app/db/host").await.unwrap().unwrap(),
+            Bytes::from_static(b"localHOST!")
+        );
+        assert_eq!(
+            kv.read(Path::new("/db/host"), 5, 100).await.unwrap(),
+            Bytes::from_static(b"HOST!")
+        );
+        assert!(kv.stat(Path::new("/db")).await.unwrap().is_dir());
+        assert_eq!(kv.stat(Path::new("/db/host")).await.unwrap().size, 10);
+        assert!(matches!(
+            kv.read(Path::new("/db"), 0, 10).await,
+            Err(FuseAdapterError::IsADirectory(_))
+        ));
+        assert!(matches!(
+            kv.stat(Path::new("/missing")).await,
+            Err(FuseAdapterError::NotFound(_))
+        ));
+
+        kv.truncate(Path::new("/db/host"), 5).await.unwrap();
+        assert_eq!(
+            kv.read(Path::new("/db/host"), 0, 100).await.unwrap(),
+            Bytes::from_static(b"local")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_directories_are_prefixes() {
+        let kv = connector(None);
+        kv.store.put("top", Bytes::new()).await.unwrap();
+        kv.store.put("a/one", Bytes::new()).await.unwrap();
+        kv.store.put("a/b/two", Bytes::new()).await.unwrap();
+        kv.create_dir(Path::new("/a/empty")).await.unwrap();
+
+        assert_eq!(
+            list(&kv, "/").await,
+            vec![
+                ("a".to_string(), FileType::Directory),
+                ("top".to_string(), FileType::File)
+            ]
+        );
+        assert_eq!(
+            list(&kv, "/a").await,
+            vec![
+                ("b".to_string(), FileType::Directory),
+                ("empty".to_string(), FileType::Directory),
+                ("one".to_string(), FileType::File)
+            ]
+        );
+        assert!(list(&kv, "/a/empty").await.is_empty());
+
+        assert!(matches!(
+            kv.remove_dir(Path::new("/a"), false).await,
+            Err(FuseAdapterError::NotEmpty(_))
+        ));
+        kv.remove_dir(Path::new("/a/empty"), false).await.unwrap();
+
+        kv.rename(Path::new("/a"), Path::new("/c")).await.unwrap();
+        assert_eq!(
+            kv.store.keys("").await.unwrap(),
+            vec!["c/b/two", "c/one", "top"]
+        );
+
+        kv.remove_dir(Path::new("/c"), true).await.unwrap();
+        assert_eq!(kv.store.keys("").await.unwrap(), vec!["top"]);
+    }
+}
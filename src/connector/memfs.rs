@@ -0,0 +1,496 @@
+//! In-memory connector
+//!
+//! A complete filesystem kept in process memory: files, directories and
+//! symlinks with modes and mtimes. Everything is lost on unmount, which
+//! makes it a scratch space for production mounts and a fast fake backend
+//! in tests.
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream;
+use parking_lot::RwLock;
+
+use crate::cache::parse_size;
+use crate::config::MemfsConnectorConfig;
+use crate::connector::{Capabilities, Connector, DirEntry, DirEntryStream, FileType, Metadata};
+use crate::error::{FuseAdapterError, Result};
+
+#[derive(Debug, Clone)]
+enum Content {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    content: Content,
+    mode: Option<u32>,
+    mtime: SystemTime,
+}
+
+impl Node {
+    fn new(content: Content, mode: Option<u32>) -> Self {
+        Self {
+            content,
+            mode,
+            mtime: SystemTime::now(),
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        let (file_type, size) = match &self.content {
+            Content::File(data) => (FileType::File, data.len() as u64),
+            Content::Dir => (FileType::Directory, 0),
+            Content::Symlink(_) => (FileType::Symlink, 0),
+        };
+        Metadata {
+            file_type,
+            size,
+            mtime: self.mtime,
+            mode: self.mode,
+            etag: None,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.content, Content::Dir)
+    }
+}
+
+fn not_found(path: &Path) -> FuseAdapterError {
+    FuseAdapterError::NotFound(path.display().to_string())
+}
+
+#[derive(Debug)]
+struct State {
+    nodes: BTreeMap<PathBuf, Node>,
+    /// Total size of all file contents
+    used: u64,
+}
+
+impl State {
+    fn node(&self, path: &Path) -> Result<&Node> {
+        self.nodes.get(path).ok_or_else(|| not_found(path))
+    }
+
+    fn file_mut(&mut self, path: &Path) -> Result<&mut Node> {
+        let node = self.nodes.get_mut(path).ok_or_else(|| not_found(path))?;
+        match node.content {
+            Content::File(_) => Ok(node),
+            Content::Dir => Err(FuseAdapterError::IsADirectory(path.display().to_string())),
+            Content::Symlink(_) => Err(FuseAdapterError::InvalidArgument(format!(
+                "{} is a symlink",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Paths below `path`, in order
+    fn descendants(&self, path: &Path) -> Vec<PathBuf> {
+        self.nodes
+            .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+            .map(|(p, _)| p)
+            .take_while(|p| p.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    fn has_children(&self, path: &Path) -> bool {
+        self.nodes
+            .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+            .next()
+            .is_some_and(|(p, _)| p.starts_with(path))
+    }
+
+    /// Check that a new entry at `path` has a directory to go in, and mark
+    /// that directory modified
+    fn touch_parent(&mut self, path: &Path) -> Result<()> {
+        let parent = path
+            .parent()
+            .ok_or_else(|| FuseAdapterError::InvalidPath("the root has no parent".to_string()))?;
+        let node = self
+            .nodes
+            .get_mut(parent)
+            .ok_or_else(|| not_found(parent))?;
+        if !node.is_dir() {
+            return Err(FuseAdapterError::NotADirectory(
+                parent.display().to_string(),
+            ));
+        }
+        node.mtime = SystemTime::now();
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<Node> {
+        let node = self.nodes.remove(path)?;
+        if let Content::File(data) = &node.content {
+            self.used -= data.len() as u64;
+        }
+        Some(node)
+    }
+}
+
+/// Connector keeping the whole filesystem in memory
+#[derive(Debug)]
+pub struct MemfsConnector {
+    state: RwLock<State>,
+    /// Most bytes of file content held at once
+    max_size: Option<u64>,
+}
+
+impl Default for MemfsConnector {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl MemfsConnector {
+    /// Create an empty filesystem holding at most `max_size` bytes of file
+    /// content (None = unlimited)
+    pub fn new(max_size: Option<u64>) -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(PathBuf::from("/"), Node::new(Content::Dir, None));
+        Self {
+            state: RwLock::new(State { nodes, used: 0 }),
+            max_size,
+        }
+    }
+
+    /// Create the filesystem described by `config`
+    pub fn from_config(config: &MemfsConnectorConfig) -> Result<Self> {
+        let max_size = config
+            .max_size
+            .as_deref()
+            .map(|size| {
+                parse_size(size).ok_or_else(|| {
+                    FuseAdapterError::Config(format!("invalid memfs max_size {:?}", size))
+                })
+            })
+            .transpose()?;
+        Ok(Self::new(max_size))
+    }
+
+    /// Resize a file's content, failing if that would exceed `max_size`
+    fn resize(&self, state: &mut State, path: &Path, len: u64) -> Result<()> {
+        let old_len = match &state.file_mut(path)?.content {
+            Content::File(data) => data.len() as u64,
+            _ => unreachable!(),
+        };
+        let used = state.used - old_len + len;
+        if len > old_len && self.max_size.is_some_and(|max| used > max) {
+            return Err(FuseAdapterError::NoSpace);
+        }
+        state.used = used;
+        let node = state.file_mut(path)?;
+        if let Content::File(data) = &mut node.content {
+            data.resize(len as usize, 0);
+        }
+        node.mtime = SystemTime::now();
+        Ok(())
+    }
+
+    fn create(&self, path: &Path, content: Content, mode: Option<u32>) -> Result<()> {
+        let mut state = self.state.write();
+        if let Some(existing) = state.nodes.get(path) {
+            // Creating a file over an existing one empties it, like S3
+            if existing.is_dir() || !matches!(content, Content::File(_)) {
+                return Err(FuseAdapterError::AlreadyExists(path.display().to_string()));
+            }
+        }
+        state.touch_parent(path)?;
+        state.remove(path);
+        state
+            .nodes
+            .insert(path.to_path_buf(), Node::new(content, mode));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Connector for MemfsConnector {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::full()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        Ok(self.state.read().node(path)?.metadata())
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        let state = self.state.read();
+        match &state.node(path)?.content {
+            Content::File(data) => {
+                let start = (offset as usize).min(data.len());
+                let end = start.saturating_add(size as usize).min(data.len());
+                Ok(Bytes::copy_from_slice(&data[start..end]))
+            }
+            Content::Dir => Err(FuseAdapterError::IsADirectory(path.display().to_string())),
+            Content::Symlink(_) => Err(FuseAdapterError::InvalidArgument(format!(
+                "{} is a symlink",
+                path.display()
+            ))),
+        }
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        let mut state = self.state.write();
+        if !state.nodes.contains_key(path) {
+            state.touch_parent(path)?;
+            state.nodes.insert(
+                path.to_path_buf(),
+                Node::new(Content::File(Vec::new()), None),
+            );
+        }
+        let end = offset + data.len() as u64;
+        let len = match &state.file_mut(path)?.content {
+            Content::File(content) => content.len() as u64,
+            _ => unreachable!(),
+        };
+        if end > len {
+            self.resize(&mut state, path, end)?;
+        }
+        let node = state.file_mut(path)?;
+        if let Content::File(content) = &mut node.content {
+            content[offset as usize..end as usize].copy_from_slice(data);
+        }
+        node.mtime = SystemTime::now();
+        Ok(data.len() as u64)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.create(path, Content::File(Vec::new()), None)
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.create(path, Content::File(Vec::new()), Some(mode))
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.create(path, Content::Dir, None)
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.create(path, Content::Dir, Some(mode))
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        let mut state = self.state.write();
+        if state.node(path)?.is_dir() {
+            return Err(FuseAdapterError::IsADirectory(path.display().to_string()));
+        }
+        state.touch_parent(path)?;
+        state.remove(path);
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        let mut state = self.state.write();
+        if !state.node(path)?.is_dir() {
+            return Err(FuseAdapterError::NotADirectory(path.display().to_string()));
+        }
+        if !recursive && state.has_children(path) {
+            return Err(FuseAdapterError::NotEmpty(path.display().to_string()));
+        }
+        state.touch_parent(path)?;
+        for descendant in state.descendants(path) {
+            state.remove(&descendant);
+        }
+        state.remove(path);
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let state = self.state.read();
+        let entries: Vec<Result<DirEntry>> = match state.node(path) {
+            Ok(node) if node.is_dir() => state
+                .nodes
+                .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+                .take_while(|(p, _)| p.starts_with(path))
+                .filter(|(p, _)| p.parent() == Some(path))
+                .map(|(p, node)| {
+                    let name = p.file_name().unwrap_or_default();
+                    let metadata = node.metadata();
+                    let entry = match metadata.file_type {
+                        FileType::File => DirEntry::file(name),
+                        FileType::Directory => DirEntry::directory(name),
+                        FileType::Symlink => DirEntry::symlink(name),
+                    };
+                    Ok(entry.with_metadata(metadata))
+                })
+                .collect(),
+            Ok(_) => vec![Err(FuseAdapterError::NotADirectory(
+                path.display().to_string(),
+            ))],
+            Err(e) => vec![Err(e)],
+        };
+        Box::pin(stream::iter(entries))
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut state = self.state.write();
+        let is_dir = state.node(from)?.is_dir();
+        if from == to {
+            return Ok(());
+        }
+        if to.starts_with(from) {
+            return Err(FuseAdapterError::InvalidArgument(format!(
+                "cannot move {} into itself",
+                from.display()
+            )));
+        }
+        if let Some(existing) = state.nodes.get(to) {
+            match (is_dir, existing.is_dir()) {
+                (true, false) => {
+                    return Err(FuseAdapterError::NotADirectory(to.display().to_string()))
+                }
+                (false, true) => {
+                    return Err(FuseAdapterError::IsADirectory(to.display().to_string()))
+                }
+                (true, true) if state.has_children(to) => {
+                    return Err(FuseAdapterError::NotEmpty(to.display().to_string()))
+                }
+                _ => {}
+            }
+        }
+        state.touch_parent(to)?;
+        state.touch_parent(from)?;
+        state.remove(to);
+
+        let mut moved = vec![from.to_path_buf()];
+        moved.extend(state.descendants(from));
+        for old in moved {
+            let new = to.join(old.strip_prefix(from).unwrap());
+            if let Some(node) = state.nodes.remove(&old) {
+                state.nodes.insert(new, node);
+            }
+        }
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        let mut state = self.state.write();
+        self.resize(&mut state, path, size)
+    }
+
+    async fn flush(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        let mut state = self.state.write();
+        let node = state.nodes.get_mut(path).ok_or_else(|| not_found(path))?;
+        node.mode = Some(mode);
+        Ok(())
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        match &self.state.read().node(path)?.content {
+            Content::Symlink(target) => Ok(target.clone()),
+            _ => Err(FuseAdapterError::InvalidArgument(format!(
+                "{} is not a symlink",
+                path.display()
+            ))),
+        }
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.create(link_path, Content::Symlink(target.to_path_buf()), None)
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        let mut state = self.state.write();
+        let node = state.file_mut(path)?;
+        if let Content::File(data) = &mut node.content {
+            let start = (offset as usize).min(data.len());
+            let end = (offset.saturating_add(len) as usize).min(data.len());
+            data[start..end].fill(0);
+        }
+        node.mtime = SystemTime::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    async fn names(memfs: &MemfsConnector, path: &str) -> Vec<String> {
+        memfs
+            .list_dir(Path::new(path))
+            .map(|e| e.unwrap().name.to_string_lossy().into_owned())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_files_and_directories() {
+        let memfs = MemfsConnector::default();
+        memfs.create_dir(Path::new("/a")).await.unwrap();
+        memfs
+            .create_file_with_mode(Path::new("/a/f"), 0o600)
+            .await
+            .unwrap();
+        memfs.write(Path::new("/a/f"), 2, b"xy").await.unwrap();
+        memfs
+            .symlink(Path::new("f"), Path::new("/a/link"))
+            .await
+            .unwrap();
+
+        let metadata = memfs.stat(Path::new("/a/f")).await.unwrap();
+        assert_eq!(metadata.size, 4);
+        assert_eq!(metadata.mode, Some(0o600));
+        assert_eq!(
+            memfs.read(Path::new("/a/f"), 0, 10).await.unwrap(),
+            Bytes::from_static(b"\0\0xy")
+        );
+        assert_eq!(
+            memfs.readlink(Path::new("/a/link")).await.unwrap(),
+            PathBuf::from("f")
+        );
+        assert_eq!(names(&memfs, "/a").await, vec!["f", "link"]);
+
+        assert!(matches!(
+            memfs.create_file(Path::new("/missing/f")).await,
+            Err(FuseAdapterError::NotFound(_))
+        ));
+        assert!(matches!(
+            memfs.remove_dir(Path::new("/a"), false).await,
+            Err(FuseAdapterError::NotEmpty(_))
+        ));
+
+        memfs
+            .rename(Path::new("/a"), Path::new("/b"))
+            .await
+            .unwrap();
+        assert_eq!(names(&memfs, "/").await, vec!["b"]);
+        assert_eq!(names(&memfs, "/b").await, vec!["f", "link"]);
+        assert!(matches!(
+            memfs.rename(Path::new("/b"), Path::new("/b/c")).await,
+            Err(FuseAdapterError::InvalidArgument(_))
+        ));
+
+        memfs.remove_dir(Path::new("/b"), true).await.unwrap();
+        assert!(names(&memfs, "/").await.is_empty());
+        assert_eq!(memfs.state.read().used, 0);
+    }
+
+    #[tokio::test]
+    async fn test_max_size() {
+        let memfs = MemfsConnector::new(Some(8));
+        memfs.write(Path::new("/a"), 0, b"12345").await.unwrap();
+        assert!(matches!(
+            memfs.write(Path::new("/b"), 0, b"6789").await,
+            Err(FuseAdapterError::NoSpace)
+        ));
+        memfs.truncate(Path::new("/a"), 1).await.unwrap();
+        memfs.write(Path::new("/b"), 0, b"6789").await.unwrap();
+        assert_eq!(memfs.state.read().used, 5);
+    }
+}
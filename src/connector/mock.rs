@@ -0,0 +1,502 @@
+//! Fault-injecting connector for testing (`mock` feature)
+//!
+//! `MockConnector` wraps another connector and makes its calls fail, slow
+//! down, return short reads or hang, per operation type. The faults live in
+//! a shared `FaultController` and can be changed while the connector is in
+//! use. Which calls fail at a given `error_rate` is drawn from a seeded
+//! generator, so the same seed and call sequence always fail the same calls;
+//! `fail_next` fails an exact number of calls for tests that can't tolerate
+//! any randomness.
+//!
+//! Daemon mounts with a `faults` section register their controller under the
+//! mount path, where the control socket's `fault` commands look it up.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::config::{FaultConfig, FaultError, FaultOperation, FaultsConfig};
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, DirtyRanges, FileVersion, Metadata,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Controllers of the daemon's mounts, by mount path
+static CONTROLLERS: Lazy<RwLock<HashMap<PathBuf, Arc<FaultController>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Make `controller` reachable under `mount`, replacing any earlier one
+pub fn register(mount: &Path, controller: Arc<FaultController>) {
+    CONTROLLERS.write().insert(mount.to_path_buf(), controller);
+}
+
+/// The controller registered for `mount`, if any
+pub fn controller(mount: &Path) -> Option<Arc<FaultController>> {
+    CONTROLLERS.read().get(mount).cloned()
+}
+
+/// Runtime-adjustable faults shared by a `MockConnector` and its tests
+pub struct FaultController {
+    state: Mutex<State>,
+    injected: AtomicU64,
+}
+
+struct State {
+    faults: HashMap<FaultOperation, ActiveFault>,
+    /// splitmix64 state
+    rng: u64,
+}
+
+struct ActiveFault {
+    config: FaultConfig,
+    /// Calls still to be failed by `fail_next`
+    remaining: u32,
+}
+
+/// What to do to one call
+#[derive(Default)]
+struct Decision {
+    latency: Option<std::time::Duration>,
+    hang: bool,
+    error: Option<FaultError>,
+    partial_reads: bool,
+}
+
+impl FaultController {
+    /// Create a controller with no faults
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(State {
+                faults: HashMap::new(),
+                rng: seed,
+            }),
+            injected: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a controller with the configured faults
+    pub fn from_config(config: &FaultsConfig) -> Self {
+        let controller = Self::new(config.seed);
+        for (op, fault) in &config.operations {
+            controller.set(*op, fault.clone());
+        }
+        controller
+    }
+
+    /// Inject `fault` into `op`, replacing its previous fault
+    ///
+    /// A fault for `FaultOperation::All` applies to every operation that
+    /// has none of its own.
+    pub fn set(&self, op: FaultOperation, fault: FaultConfig) {
+        let remaining = fault.fail_next;
+        self.state.lock().faults.insert(
+            op,
+            ActiveFault {
+                config: fault,
+                remaining,
+            },
+        );
+    }
+
+    /// Stop injecting faults into `op`
+    pub fn clear(&self, op: FaultOperation) {
+        self.state.lock().faults.remove(&op);
+    }
+
+    /// Stop injecting faults altogether
+    pub fn clear_all(&self) {
+        self.state.lock().faults.clear();
+    }
+
+    /// Number of calls failed so far
+    pub fn injected(&self) -> u64 {
+        self.injected.load(Ordering::Relaxed)
+    }
+
+    fn decide(&self, op: FaultOperation) -> Decision {
+        let mut state = self.state.lock();
+        let State { faults, rng } = &mut *state;
+        let key = if faults.contains_key(&op) {
+            op
+        } else {
+            FaultOperation::All
+        };
+        let Some(fault) = faults.get_mut(&key) else {
+            return Decision::default();
+        };
+
+        let fail = if fault.remaining > 0 {
+            fault.remaining -= 1;
+            true
+        } else {
+            fault.config.error_rate > 0.0 && next_f64(rng) < fault.config.error_rate
+        };
+        Decision {
+            latency: fault.config.latency,
+            hang: fault.config.hang,
+            error: fail.then_some(fault.config.error),
+            partial_reads: fault.config.partial_reads,
+        }
+    }
+
+    /// Apply the faults for one call of `op`
+    ///
+    /// Returns whether a read should be cut short.
+    async fn inject(&self, op: FaultOperation) -> Result<bool> {
+        let decision = self.decide(op);
+        if let Some(latency) = decision.latency {
+            tokio::time::sleep(latency).await;
+        }
+        if decision.hang {
+            std::future::pending::<()>().await;
+        }
+        if let Some(error) = decision.error {
+            self.injected.fetch_add(1, Ordering::Relaxed);
+            let reason = format!("injected {} fault", op.as_str());
+            return Err(match error {
+                FaultError::Backend => FuseAdapterError::Backend(reason),
+                FaultError::Timeout => FuseAdapterError::Timeout(reason),
+                FaultError::NotFound => FuseAdapterError::NotFound(reason),
+                FaultError::NoSpace => FuseAdapterError::NoSpace,
+                FaultError::PermissionDenied => FuseAdapterError::PermissionDenied,
+            });
+        }
+        Ok(decision.partial_reads)
+    }
+}
+
+/// Next value in [0, 1) from a splitmix64 generator
+fn next_f64(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Cut a read short to its first half, keeping at least one byte
+fn truncate_read(data: Bytes) -> Bytes {
+    if data.len() <= 1 {
+        return data;
+    }
+    data.slice(..data.len() / 2)
+}
+
+/// Connector decorator injecting faults into another connector's calls
+pub struct MockConnector<C: Connector> {
+    inner: C,
+    controller: Arc<FaultController>,
+}
+
+impl<C: Connector> MockConnector<C> {
+    /// Wrap `inner`, with faults controlled by `controller`
+    pub fn new(inner: C, controller: Arc<FaultController>) -> Self {
+        Self { inner, controller }
+    }
+
+    /// The controller adjusting this connector's faults
+    pub fn controller(&self) -> Arc<FaultController> {
+        self.controller.clone()
+    }
+}
+
+#[async_trait]
+impl<C: Connector> Connector for MockConnector<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.controller.inject(FaultOperation::Stat).await?;
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.controller.inject(FaultOperation::Stat).await?;
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        let partial = self.controller.inject(FaultOperation::Read).await?;
+        let data = self.inner.read(path, offset, size).await?;
+        Ok(if partial { truncate_read(data) } else { data })
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.controller.inject(FaultOperation::Write).await?;
+        self.inner.write(path, offset, data).await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        self.controller.inject(FaultOperation::Write).await?;
+        self.inner.write_dirty(path, data, dirty).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Create).await?;
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Create).await?;
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Remove).await?;
+        self.inner.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.controller.inject(FaultOperation::Remove).await?;
+        self.inner.remove_dir(path, recursive).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let controller = self.controller.clone();
+        let entries = self.inner.list_dir(path);
+        Box::pin(try_stream! {
+            controller.inject(FaultOperation::List).await?;
+            for await entry in entries {
+                yield entry?;
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Rename).await?;
+        self.inner.rename(from, to).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.controller.inject(FaultOperation::Truncate).await?;
+        self.inner.truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Flush).await?;
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.controller.inject(FaultOperation::Create).await?;
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.controller.inject(FaultOperation::Create).await?;
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.controller.inject(FaultOperation::Write).await?;
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.controller.inject(FaultOperation::Stat).await?;
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Create).await?;
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.controller.inject(FaultOperation::Stat).await?;
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        let partial = self.controller.inject(FaultOperation::Read).await?;
+        let data = self
+            .inner
+            .read_version(path, version_id, offset, size)
+            .await?;
+        Ok(if partial { truncate_read(data) } else { data })
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.controller.inject(FaultOperation::Sync).await?;
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.controller.inject(FaultOperation::Stat).await?;
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.controller.inject(FaultOperation::Stat).await?;
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.controller.inject(FaultOperation::Create).await?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.controller.inject(FaultOperation::Write).await?;
+        self.inner.zero_range(path, offset, len).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::memfs::MemfsConnector;
+    use futures::StreamExt;
+
+    async fn mock_with_file(seed: u64) -> MockConnector<MemfsConnector> {
+        let memfs = MemfsConnector::default();
+        memfs.create_file(Path::new("/file")).await.unwrap();
+        memfs
+            .write(Path::new("/file"), 0, b"hello world")
+            .await
+            .unwrap();
+        MockConnector::new(memfs, Arc::new(FaultController::new(seed)))
+    }
+
+    #[tokio::test]
+    async fn test_fail_next_and_clear() {
+        let mock = mock_with_file(1).await;
+        let controller = mock.controller();
+        let path = Path::new("/file");
+
+        controller.set(
+            FaultOperation::Read,
+            FaultConfig {
+                fail_next: 2,
+                ..Default::default()
+            },
+        );
+        for _ in 0..2 {
+            assert!(matches!(
+                mock.read(path, 0, 5).await,
+                Err(FuseAdapterError::Backend(_))
+            ));
+        }
+        assert_eq!(&mock.read(path, 0, 5).await.unwrap()[..], b"hello");
+        // Other operations are untouched
+        assert!(mock.stat(path).await.is_ok());
+        assert_eq!(controller.injected(), 2);
+
+        controller.set(
+            FaultOperation::All,
+            FaultConfig {
+                error_rate: 1.0,
+                error: FaultError::NoSpace,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            mock.write(path, 0, b"x").await,
+            Err(FuseAdapterError::NoSpace)
+        ));
+        let mut entries = mock.list_dir(Path::new("/"));
+        assert!(entries.next().await.unwrap().is_err());
+
+        controller.clear_all();
+        assert!(mock.write(path, 0, b"x").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_error_rate_is_seeded() {
+        let failures = |seed| async move {
+            let mock = mock_with_file(seed).await;
+            mock.controller.set(
+                FaultOperation::Stat,
+                FaultConfig {
+                    error_rate: 0.5,
+                    ..Default::default()
+                },
+            );
+            let mut failed = Vec::new();
+            for _ in 0..64 {
+                failed.push(mock.stat(Path::new("/file")).await.is_err());
+            }
+            failed
+        };
+
+        let first = failures(7).await;
+        assert_eq!(first, failures(7).await);
+        let count = first.iter().filter(|f| **f).count();
+        assert!((16..=48).contains(&count), "{} of 64 failed", count);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency_partial_reads_and_hang() {
+        let mock = mock_with_file(1).await;
+        let controller = mock.controller();
+        let path = Path::new("/file");
+
+        controller.set(
+            FaultOperation::Read,
+            FaultConfig {
+                latency: Some(std::time::Duration::from_secs(2)),
+                partial_reads: true,
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        assert_eq!(&mock.read(path, 0, 11).await.unwrap()[..], b"hello");
+        assert!(start.elapsed() >= std::time::Duration::from_secs(2));
+
+        controller.set(
+            FaultOperation::Stat,
+            FaultConfig {
+                hang: true,
+                ..Default::default()
+            },
+        );
+        let hung = tokio::time::timeout(std::time::Duration::from_secs(60), mock.stat(path)).await;
+        assert!(hung.is_err());
+    }
+}
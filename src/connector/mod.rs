@@ -1,7 +1,24 @@
+pub mod archive;
+pub mod breaker;
+pub mod compressed;
+pub mod encrypted;
 pub mod gdrive;
+pub mod key_encoding;
+pub mod kv;
+pub mod memfs;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod onedrive;
+pub mod pacer;
+pub mod registry;
+pub mod routing;
 pub mod s3;
+pub mod throttle;
+pub mod traced;
+pub mod union;
 
 use std::ffi::OsString;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::time::{Duration, SystemTime};
@@ -10,10 +27,13 @@ use async_trait::async_trait;
 use bytes::Bytes;
 use futures::Stream;
 
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::pacer::ThrottleStats;
 use crate::error::Result;
+use crate::integrity::ContentHash;
 
 /// File type enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FileType {
     File,
     Directory,
@@ -26,6 +46,8 @@ pub const DEFAULT_FILE_MODE: u32 = 0o644;
 pub const DEFAULT_DIR_MODE: u32 = 0o755;
 /// Default symlink mode (rwxrwxrwx) - symlinks always have 0o777 on Unix
 pub const DEFAULT_SYMLINK_MODE: u32 = 0o777;
+/// Read size used when copying a file through the client
+pub const COPY_CHUNK_SIZE: u32 = 1024 * 1024;
 
 /// Metadata for a file or directory
 #[derive(Debug, Clone)]
@@ -35,6 +57,8 @@ pub struct Metadata {
     pub mtime: SystemTime,
     /// POSIX permission bits (e.g., 0o644). None means use default.
     pub mode: Option<u32>,
+    /// Backend entity tag identifying this version of the content, if any
+    pub etag: Option<String>,
 }
 
 impl Metadata {
@@ -44,6 +68,7 @@ impl Metadata {
             size,
             mtime,
             mode: None,
+            etag: None,
         }
     }
 
@@ -53,6 +78,7 @@ impl Metadata {
             size,
             mtime,
             mode: Some(mode),
+            etag: None,
         }
     }
 
@@ -62,6 +88,7 @@ impl Metadata {
             size: 0,
             mtime,
             mode: None,
+            etag: None,
         }
     }
 
@@ -71,6 +98,7 @@ impl Metadata {
             size: 0,
             mtime,
             mode: Some(mode),
+            etag: None,
         }
     }
 
@@ -80,6 +108,7 @@ impl Metadata {
             size: 0,
             mtime,
             mode: None,
+            etag: None,
         }
     }
 
@@ -89,9 +118,16 @@ impl Metadata {
             size: 0,
             mtime,
             mode: Some(mode),
+            etag: None,
         }
     }
 
+    /// Attach a backend entity tag
+    pub fn with_etag(mut self, etag: Option<String>) -> Self {
+        self.etag = etag;
+        self
+    }
+
     /// Get the mode, using defaults if not set
     pub fn mode_or_default(&self) -> u32 {
         self.mode.unwrap_or(match self.file_type {
@@ -119,6 +155,9 @@ impl Metadata {
 pub struct DirEntry {
     pub name: OsString,
     pub file_type: FileType,
+    /// Metadata returned alongside the listing, if the backend provides it.
+    /// Cache layers use this to avoid a separate stat per entry.
+    pub metadata: Option<Metadata>,
 }
 
 impl DirEntry {
@@ -126,6 +165,7 @@ impl DirEntry {
         Self {
             name: name.into(),
             file_type: FileType::File,
+            metadata: None,
         }
     }
 
@@ -133,6 +173,7 @@ impl DirEntry {
         Self {
             name: name.into(),
             file_type: FileType::Directory,
+            metadata: None,
         }
     }
 
@@ -140,8 +181,104 @@ impl DirEntry {
         Self {
             name: name.into(),
             file_type: FileType::Symlink,
+            metadata: None,
+        }
+    }
+
+    /// Attach metadata obtained from the listing itself
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+/// A stored historical version of a file (for versioned backends)
+#[derive(Debug, Clone)]
+pub struct FileVersion {
+    /// Backend-specific version identifier
+    pub version_id: String,
+    /// Size of this version in bytes
+    pub size: u64,
+    /// When this version was written
+    pub mtime: SystemTime,
+    /// Whether this is the current version of the file
+    pub is_latest: bool,
+}
+
+/// The kind of region `SEEK_DATA`/`SEEK_HOLE` look for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekRegion {
+    Data,
+    Hole,
+}
+
+impl SeekRegion {
+    /// Where the next region starts in a file of `size` bytes with no holes
+    ///
+    /// Returns None if `offset` is at or past the end of the file.
+    pub fn in_dense_file(self, size: u64, offset: u64) -> Option<u64> {
+        if offset >= size {
+            return None;
+        }
+        match self {
+            SeekRegion::Data => Some(offset),
+            SeekRegion::Hole => Some(size),
+        }
+    }
+}
+
+/// Byte ranges of a file that changed since it was read from the backend
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirtyRanges {
+    /// Size of the backend copy the changes were made against
+    pub base_size: u64,
+    /// Sorted, non-overlapping, non-adjacent changed ranges
+    pub ranges: Vec<Range<u64>>,
+}
+
+impl DirtyRanges {
+    /// Start tracking changes to a backend copy of `base_size` bytes
+    pub fn new(base_size: u64) -> Self {
+        Self {
+            base_size,
+            ranges: Vec::new(),
         }
     }
+
+    /// Mark a range as changed, merging it with overlapping or adjacent ranges
+    pub fn add(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut start, mut end) = (range.start, range.end);
+        self.ranges.retain(|r| {
+            if r.end < start || r.start > end {
+                return true;
+            }
+            start = start.min(r.start);
+            end = end.max(r.end);
+            false
+        });
+        let index = self.ranges.partition_point(|r| r.start < start);
+        self.ranges.insert(index, start..end);
+    }
+
+    /// Whether any byte in `range` changed
+    pub fn intersects(&self, range: &Range<u64>) -> bool {
+        self.ranges
+            .iter()
+            .any(|r| r.start < range.end && range.start < r.end)
+    }
+
+    /// Total number of changed bytes
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|r| r.end - r.start).sum()
+    }
+
+    /// Whether nothing has changed
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
 }
 
 /// Connector capabilities declaration
@@ -244,6 +381,32 @@ pub trait Connector: Send + Sync {
         CacheRequirements::default()
     }
 
+    /// Get live statistics from the cache layer, if this is or wraps one
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
+    /// Get throttling seen from the backend, if this is or wraps a connector
+    /// that paces its requests
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        None
+    }
+
+    /// List paths with local changes not yet synced to the backend
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        Vec::new()
+    }
+
+    /// Drop cached metadata and directory listings so they are refetched
+    ///
+    /// Default implementation does nothing (no cached state)
+    fn invalidate_caches(&self) {}
+
+    /// Get the controls of the cache layer, if this is or wraps one
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        None
+    }
+
     /// Get metadata for a path
     async fn stat(&self, path: &Path) -> Result<Metadata>;
 
@@ -349,4 +512,337 @@ pub trait Connector: Send + Sync {
             "symlink not supported".to_string(),
         ))
     }
+
+    /// List stored versions of a file, newest first
+    ///
+    /// Default implementation returns NotSupported
+    async fn list_versions(&self, _path: &Path) -> Result<Vec<FileVersion>> {
+        Err(crate::error::FuseAdapterError::NotSupported(
+            "list_versions not supported".to_string(),
+        ))
+    }
+
+    /// Read bytes from a specific stored version of a file
+    ///
+    /// Default implementation returns NotSupported
+    async fn read_version(
+        &self,
+        _path: &Path,
+        _version_id: &str,
+        _offset: u64,
+        _size: u32,
+    ) -> Result<Bytes> {
+        Err(crate::error::FuseAdapterError::NotSupported(
+            "read_version not supported".to_string(),
+        ))
+    }
+
+    /// Push all locally pending changes to the backend now
+    ///
+    /// Default implementation does nothing (no local state)
+    async fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish outstanding work before the process exits
+    ///
+    /// Default implementation syncs pending changes via sync_all()
+    async fn shutdown(&self) -> Result<()> {
+        self.sync_all().await
+    }
+
+    /// Get a checksum of a file's content as stored by the backend
+    ///
+    /// Prefer the SHA-256 written alongside the file; otherwise fall back to
+    /// a checksum the backend computed itself (an MD5 or CRC32C). Returns
+    /// None if the backend has nothing that covers the whole content.
+    ///
+    /// With an `etag` (from `stat`), the checksum must be that version's;
+    /// returns None if the file has changed since, where the backend can tell.
+    async fn content_hash(&self, _path: &Path, _etag: Option<&str>) -> Result<Option<ContentHash>> {
+        Ok(None)
+    }
+
+    /// Replace a file with `data`, uploading only the `dirty` ranges
+    ///
+    /// `data` is the complete new content; everything outside `dirty` is
+    /// unchanged from the backend copy of `dirty.base_size` bytes. Returns
+    /// Ok(false) without touching the file if a partial upload isn't possible
+    /// or worthwhile, in which case the caller writes the whole file instead.
+    /// Default implementation returns Ok(false).
+    async fn write_dirty(&self, _path: &Path, _data: &[u8], _dirty: &DirtyRanges) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Check whether a file still matches a previously seen entity tag
+    ///
+    /// Returns Ok(true) if the content is unchanged, Ok(false) if it was
+    /// modified or removed. Default implementation returns NotSupported.
+    async fn revalidate(&self, _path: &Path, _etag: &str) -> Result<bool> {
+        Err(crate::error::FuseAdapterError::NotSupported(
+            "revalidate not supported".to_string(),
+        ))
+    }
+
+    /// Copy a file to `to`, creating or replacing it
+    ///
+    /// Default implementation reads the file and writes it back through
+    /// the connector (see `copy_content`). Backends with a server-side copy
+    /// override this so the data never passes through the client.
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        copy_content(self, from, to).await
+    }
+
+    /// Zero `len` bytes of a file starting at `offset` without changing its size
+    ///
+    /// The range is clipped to the end of the file. Backends that can should
+    /// deallocate the range, leaving a hole. Default implementation returns
+    /// NotSupported.
+    async fn zero_range(&self, _path: &Path, _offset: u64, _len: u64) -> Result<()> {
+        Err(crate::error::FuseAdapterError::NotSupported(
+            "zero_range not supported".to_string(),
+        ))
+    }
+
+    /// Find the start of the next data or hole region at or after `offset`
+    ///
+    /// Returns None if `offset` is at or past the end of the file. Default
+    /// implementation treats the file as all data, with the only hole at EOF.
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        let size = self.stat(path).await?.size;
+        Ok(region.in_dense_file(size, offset))
+    }
+}
+
+/// Shared connectors (e.g. ones built by a `ConnectorFactory`) can be wrapped
+/// like any other
+#[async_trait]
+impl<C: Connector + ?Sized> Connector for std::sync::Arc<C> {
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        (**self).cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        (**self).cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        (**self).throttle_stats()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        (**self).pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        (**self).invalidate_caches()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        (**self).cache_control()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        (**self).stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        (**self).exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        (**self).read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        (**self).write(path, offset, data).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        (**self).create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        (**self).create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        (**self).remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        (**self).remove_dir(path, recursive).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        (**self).list_dir(path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        (**self).rename(from, to).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        (**self).truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        (**self).flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        (**self).create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        (**self).create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        (**self).set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        (**self).readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        (**self).symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        (**self).list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        (**self).read_version(path, version_id, offset, size).await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        (**self).sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        (**self).shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        (**self).content_hash(path, etag).await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        (**self).write_dirty(path, data, dirty).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        (**self).revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        (**self).copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        (**self).zero_range(path, offset, len).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        (**self).seek_region(path, offset, region).await
+    }
+}
+
+/// Copy a file by reading it and writing it to `to`
+///
+/// The content is written in a single call at offset 0 so backends without
+/// random writes can take it. Symlinks are recreated rather than followed.
+pub async fn copy_content<C: Connector + ?Sized>(
+    connector: &C,
+    from: &Path,
+    to: &Path,
+) -> Result<()> {
+    let metadata = connector.stat(from).await?;
+    match metadata.file_type {
+        FileType::File => {}
+        FileType::Symlink => {
+            let target = connector.readlink(from).await?;
+            return connector.symlink(&target, to).await;
+        }
+        FileType::Directory => {
+            return Err(crate::error::FuseAdapterError::IsADirectory(
+                from.display().to_string(),
+            ))
+        }
+    }
+
+    let mut data = Vec::with_capacity(metadata.size as usize);
+    while (data.len() as u64) < metadata.size {
+        let remaining = metadata.size - data.len() as u64;
+        let chunk = connector
+            .read(
+                from,
+                data.len() as u64,
+                remaining.min(COPY_CHUNK_SIZE as u64) as u32,
+            )
+            .await?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    if !connector.exists(to).await? {
+        connector.create_file(to).await?;
+    } else if connector.capabilities().truncate {
+        connector.truncate(to, 0).await?;
+    }
+    if !data.is_empty() {
+        connector.write(to, 0, &data).await?;
+    }
+    connector.flush(to).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_ranges_merge() {
+        let mut dirty = DirtyRanges::new(100);
+        dirty.add(10..20);
+        dirty.add(40..50);
+        dirty.add(0..0);
+        assert_eq!(dirty.ranges, vec![10..20, 40..50]);
+
+        // Adjacent and overlapping ranges collapse
+        dirty.add(20..25);
+        dirty.add(45..60);
+        assert_eq!(dirty.ranges, vec![10..25, 40..60]);
+        dirty.add(5..45);
+        assert_eq!(dirty.ranges, vec![5..60]);
+        assert_eq!(dirty.len(), 55);
+
+        assert!(dirty.intersects(&(59..70)));
+        assert!(!dirty.intersects(&(60..70)));
+        assert!(!dirty.intersects(&(0..5)));
+    }
 }
@@ -0,0 +1,748 @@
+//! OneDrive / SharePoint connector implementation
+//!
+//! This connector mounts a OneDrive or SharePoint document library through
+//! Microsoft Graph. Items are addressed by path relative to the drive root
+//! (`/root:/path/to/item:`), so no path-to-ID cache is needed. Downloads use
+//! byte ranges, and large files are uploaded through upload sessions in
+//! chunks. Graph can't write into the middle of a file, so writes go through
+//! the cache layer.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::DateTime;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::header::{CONTENT_RANGE, RANGE, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use tracing::{debug, trace};
+
+use crate::auth::{MicrosoftTokenProvider, TokenProviderInner};
+use crate::config::{OneDriveAuthConfig, OneDriveConnectorConfig};
+use crate::connector::{
+    CacheRequirement, CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream,
+    Metadata,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Microsoft Graph endpoint of the global Azure cloud
+pub const DEFAULT_ENDPOINT: &str = "https://graph.microsoft.com/v1.0";
+
+/// Largest file uploaded in a single request; larger ones use an upload session
+const SIMPLE_UPLOAD_LIMIT: usize = 4 * 1024 * 1024;
+
+/// Upload session chunk size; Graph requires a multiple of 320 KiB
+const UPLOAD_CHUNK_SIZE: usize = 32 * 320 * 1024;
+
+/// Children requested per listing page
+const PAGE_SIZE: u32 = 200;
+
+/// Attempts per request when Graph throttles (429) or is unavailable (503)
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Characters escaped in item paths; `/` separates path segments
+const PATH_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// Query parameter choosing what happens when an item name is taken
+const CONFLICT_BEHAVIOR: &str = "@microsoft.graph.conflictBehavior";
+
+/// A file or folder, as returned by Graph
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveItem {
+    id: String,
+    name: String,
+    #[serde(default)]
+    size: u64,
+    last_modified_date_time: Option<String>,
+    folder: Option<serde_json::Value>,
+    file: Option<FileFacet>,
+    /// Changes only when the content changes
+    c_tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileFacet {
+    hashes: Option<Hashes>,
+}
+
+/// Content hashes; which ones are present depends on the kind of drive
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Hashes {
+    sha256_hash: Option<String>,
+}
+
+/// One page of a children listing
+#[derive(Debug, Deserialize)]
+struct ItemPage {
+    value: Vec<DriveItem>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadSession {
+    upload_url: String,
+}
+
+/// Graph error body: `{"error": {"code": ..., "message": ...}}`
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorDetail {
+    code: String,
+    message: String,
+}
+
+/// Graph URL of the drive to mount
+///
+/// Without a drive, site or user this is the signed-in user's own OneDrive.
+pub fn drive_url(
+    endpoint: &str,
+    drive_id: Option<&str>,
+    site_id: Option<&str>,
+    user: Option<&str>,
+) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    let escape = |s: &str| utf8_percent_encode(s, PATH_ESCAPE).to_string();
+    match (drive_id, site_id, user) {
+        (Some(drive_id), _, _) => format!("{}/drives/{}", endpoint, escape(drive_id)),
+        // A `hostname:/sites/name` path needs a closing colon before `/drive`
+        (None, Some(site_id), _) if site_id.contains(":/") && !site_id.ends_with(':') => {
+            format!("{}/sites/{}:/drive", endpoint, site_id)
+        }
+        (None, Some(site_id), _) => format!("{}/sites/{}/drive", endpoint, site_id),
+        (None, None, Some(user)) => format!("{}/users/{}/drive", endpoint, escape(user)),
+        (None, None, None) => format!("{}/me/drive", endpoint),
+    }
+}
+
+/// Graph URL of the item at `path` below `root` (both without slashes at the ends)
+fn item_url(drive_url: &str, root: &str, path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let path = path.trim_matches('/');
+    let full = match (root.is_empty(), path.is_empty()) {
+        (true, _) => path.to_string(),
+        (false, true) => root.to_string(),
+        (false, false) => format!("{}/{}", root, path),
+    };
+    if full.is_empty() {
+        format!("{}/root", drive_url)
+    } else {
+        format!(
+            "{}/root:/{}:",
+            drive_url,
+            utf8_percent_encode(&full, PATH_ESCAPE)
+        )
+    }
+}
+
+/// Split a path into its parent and file name
+fn split_parent(path: &Path) -> Result<(&Path, String)> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| FuseAdapterError::InvalidPath("No filename".to_string()))?;
+    Ok((path.parent().unwrap_or(Path::new("/")), name))
+}
+
+/// Convert a Graph drive item to our Metadata
+fn item_to_metadata(item: &DriveItem) -> Metadata {
+    let mtime = item
+        .last_modified_date_time
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .and_then(|t| u64::try_from(t.timestamp()).ok())
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap_or_else(SystemTime::now);
+
+    if item.folder.is_some() {
+        Metadata::directory(mtime)
+    } else {
+        Metadata::file(item.size, mtime).with_etag(item.c_tag.clone())
+    }
+}
+
+/// Map a failed Graph response to an error
+async fn graph_error(response: Response, path: &Path) -> FuseAdapterError {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<ErrorBody>(&body) {
+        Ok(body) => format!("{}: {}", body.error.code, body.error.message),
+        Err(_) => body,
+    };
+    match status {
+        StatusCode::NOT_FOUND => FuseAdapterError::NotFound(path.display().to_string()),
+        StatusCode::CONFLICT => FuseAdapterError::AlreadyExists(path.display().to_string()),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => FuseAdapterError::PermissionDenied,
+        StatusCode::PAYLOAD_TOO_LARGE => FuseAdapterError::FileTooLarge,
+        StatusCode::INSUFFICIENT_STORAGE => FuseAdapterError::NoSpace,
+        // Still throttled once `send` has used up its retries
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+            FuseAdapterError::TooManyRequests(format!("Graph API error {}: {}", status, message))
+        }
+        StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => {
+            FuseAdapterError::Timeout(format!("Graph API error {}: {}", status, message))
+        }
+        _ => FuseAdapterError::Backend(format!("Graph API error {}: {}", status, message)),
+    }
+}
+
+/// OneDrive / SharePoint connector
+#[derive(Clone)]
+pub struct OneDriveConnector {
+    client: reqwest::Client,
+    auth: Arc<MicrosoftTokenProvider>,
+    /// Graph URL of the drive, e.g. https://graph.microsoft.com/v1.0/drives/{id}
+    drive_url: String,
+    /// Folder within the drive the mount starts at ("" for the drive root)
+    root: String,
+}
+
+impl OneDriveConnector {
+    /// Create a new OneDrive connector from configuration
+    ///
+    /// Nothing is requested until the first operation, which is also when
+    /// the device code sign-in prompt is logged.
+    pub fn new(config: OneDriveConnectorConfig) -> Result<Self> {
+        // Tokens are for the Graph resource, e.g. https://graph.microsoft.com/.default
+        let resource = reqwest::Url::parse(&config.endpoint)
+            .map_err(|e| {
+                FuseAdapterError::Config(format!(
+                    "Invalid Graph endpoint {:?}: {}",
+                    config.endpoint, e
+                ))
+            })?
+            .origin()
+            .ascii_serialization();
+        let scope = format!("{}/.default", resource);
+
+        let auth = match &config.auth {
+            OneDriveAuthConfig::ClientCredentials {
+                authority,
+                tenant_id,
+                client_id,
+                client_secret,
+            } => MicrosoftTokenProvider::client_credentials(
+                authority,
+                tenant_id,
+                client_id,
+                client_secret,
+                &scope,
+            ),
+            OneDriveAuthConfig::DeviceCode {
+                authority,
+                tenant_id,
+                client_id,
+                token_path,
+            } => MicrosoftTokenProvider::device_code(
+                authority, tenant_id, client_id, token_path, &scope,
+            ),
+        };
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            auth: Arc::new(auth),
+            drive_url: drive_url(
+                &config.endpoint,
+                config.drive_id.as_deref(),
+                config.site_id.as_deref(),
+                config.user.as_deref(),
+            ),
+            root: config.root,
+        })
+    }
+
+    /// Graph URL of the item at a mount path
+    fn item_url(&self, path: &Path) -> String {
+        item_url(&self.drive_url, &self.root, path)
+    }
+
+    /// Send a request, retrying while Graph throttles or is unavailable
+    ///
+    /// Upload session URLs are pre-authorized and must be sent without a
+    /// token (`authorize` false).
+    async fn send(&self, request: RequestBuilder, authorize: bool) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let mut this = request.try_clone().ok_or_else(|| {
+                FuseAdapterError::Backend("Graph request can't be retried".to_string())
+            })?;
+            if authorize {
+                let token = self
+                    .auth
+                    .get_token(&[])
+                    .await
+                    .map_err(|e| {
+                        FuseAdapterError::Backend(format!("Microsoft sign-in failed: {}", e))
+                    })?
+                    .unwrap_or_default();
+                this = this.bearer_auth(token);
+            }
+
+            let response = this.send().await.map_err(|e| {
+                let message = format!("Graph request failed: {}", e);
+                if e.is_timeout() {
+                    FuseAdapterError::Timeout(message)
+                } else {
+                    FuseAdapterError::Backend(message)
+                }
+            })?;
+            let status = response.status();
+            let throttled = status == StatusCode::TOO_MANY_REQUESTS
+                || status == StatusCode::SERVICE_UNAVAILABLE;
+            if !throttled || attempt >= MAX_ATTEMPTS {
+                return Ok(response);
+            }
+
+            let delay = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(Duration::from_secs(1 << attempt));
+            debug!("Graph returned {}, retrying in {:?}", status, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send an authorized request, failing on an error status
+    async fn fetch(&self, request: RequestBuilder, path: &Path) -> Result<Response> {
+        let response = self.send(request, true).await?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(graph_error(response, path).await)
+        }
+    }
+
+    /// Get the drive item at a path
+    async fn get_item(&self, path: &Path) -> Result<DriveItem> {
+        let response = self
+            .fetch(self.client.get(self.item_url(path)), path)
+            .await?;
+        response
+            .json()
+            .await
+            .map_err(|e| FuseAdapterError::Backend(format!("Invalid Graph response: {}", e)))
+    }
+
+    /// Upload a file through an upload session, in chunks
+    async fn upload_in_session(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let request = self
+            .client
+            .post(format!("{}/createUploadSession", self.item_url(path)))
+            .json(&serde_json::json!({ "item": { CONFLICT_BEHAVIOR: "replace" } }));
+        let session: UploadSession = self
+            .fetch(request, path)
+            .await?
+            .json()
+            .await
+            .map_err(|e| FuseAdapterError::Backend(format!("Invalid Graph response: {}", e)))?;
+
+        for (i, chunk) in data.chunks(UPLOAD_CHUNK_SIZE).enumerate() {
+            let start = i * UPLOAD_CHUNK_SIZE;
+            let request = self
+                .client
+                .put(&session.upload_url)
+                .header(
+                    CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, start + chunk.len() - 1, data.len()),
+                )
+                .body(chunk.to_vec());
+            let response = self.send(request, false).await?;
+            if !response.status().is_success() {
+                let error = graph_error(response, path).await;
+                // Drop the partial upload rather than leave it until it expires
+                let _ = self.client.delete(&session.upload_url).send().await;
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Connector for OneDriveConnector {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            read: true,
+            write: true,
+            range_read: true,
+            random_write: false,
+            rename: true,
+            truncate: false,
+            set_mtime: false,
+            seekable: true,
+            set_mode: false,
+            symlink: false,
+        }
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        CacheRequirements {
+            write_buffer: CacheRequirement::Required,
+            read_cache: true,
+            metadata_cache_ttl: Some(Duration::from_secs(60)),
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        trace!("stat: {:?}", path);
+        Ok(item_to_metadata(&self.get_item(path).await?))
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        trace!("read: {:?} offset={} size={}", path, offset, size);
+
+        if size == 0 {
+            return Ok(Bytes::new());
+        }
+
+        let request = self
+            .client
+            .get(format!("{}/content", self.item_url(path)))
+            .header(
+                RANGE,
+                format!("bytes={}-{}", offset, offset + size as u64 - 1),
+            );
+        let response = self.send(request, true).await?;
+        let status = response.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // Reading at or past the end of the file
+            return Ok(Bytes::new());
+        }
+        if !status.is_success() {
+            return Err(graph_error(response, path).await);
+        }
+
+        let bytes = response.bytes().await.map_err(|e| {
+            FuseAdapterError::Backend(format!("Failed to read response body: {}", e))
+        })?;
+        if status == StatusCode::PARTIAL_CONTENT {
+            return Ok(bytes);
+        }
+
+        // The whole file came back; take the requested range
+        let start = std::cmp::min(offset as usize, bytes.len());
+        let end = std::cmp::min(start + size as usize, bytes.len());
+        Ok(bytes.slice(start..end))
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        // Graph replaces whole files
+        if offset != 0 {
+            return Err(FuseAdapterError::NotSupported(
+                "OneDrive doesn't support partial writes; use cache layer".to_string(),
+            ));
+        }
+
+        debug!("write: {:?} size={}", path, data.len());
+
+        if data.len() > SIMPLE_UPLOAD_LIMIT {
+            self.upload_in_session(path, data).await?;
+        } else {
+            let request = self
+                .client
+                .put(format!("{}/content", self.item_url(path)))
+                .body(data.to_vec());
+            self.fetch(request, path).await?;
+        }
+
+        Ok(data.len() as u64)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        debug!("create_file: {:?}", path);
+
+        let request = self
+            .client
+            .put(format!("{}/content", self.item_url(path)))
+            .query(&[(CONFLICT_BEHAVIOR, "fail")])
+            .body(Vec::new());
+        self.fetch(request, path).await?;
+        Ok(())
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        debug!("create_dir: {:?}", path);
+
+        let (parent, name) = split_parent(path)?;
+        let request = self
+            .client
+            .post(format!("{}/children", self.item_url(parent)))
+            .json(&serde_json::json!({
+                "name": name,
+                "folder": {},
+                CONFLICT_BEHAVIOR: "fail",
+            }));
+        self.fetch(request, path).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        debug!("remove_file: {:?}", path);
+
+        self.fetch(self.client.delete(self.item_url(path)), path)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        debug!("remove_dir: {:?} recursive={}", path, recursive);
+
+        if !recursive {
+            let request = self
+                .client
+                .get(format!("{}/children", self.item_url(path)))
+                .query(&[("$top", 1)]);
+            let page: ItemPage =
+                self.fetch(request, path).await?.json().await.map_err(|e| {
+                    FuseAdapterError::Backend(format!("Invalid Graph response: {}", e))
+                })?;
+            if !page.value.is_empty() {
+                return Err(FuseAdapterError::NotEmpty(format!(
+                    "Directory not empty: {:?}",
+                    path
+                )));
+            }
+        }
+
+        // Deleting a folder deletes its contents
+        self.fetch(self.client.delete(self.item_url(path)), path)
+            .await?;
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let connector = self.clone();
+        let path = path.to_path_buf();
+
+        Box::pin(try_stream! {
+            let mut request = connector
+                .client
+                .get(format!("{}/children", connector.item_url(&path)))
+                .query(&[("$top", PAGE_SIZE)]);
+
+            loop {
+                let page: ItemPage = connector
+                    .fetch(request, &path)
+                    .await?
+                    .json()
+                    .await
+                    .map_err(|e| {
+                        FuseAdapterError::Backend(format!("Invalid Graph response: {}", e))
+                    })?;
+
+                for item in page.value {
+                    let metadata = item_to_metadata(&item);
+                    let entry = if metadata.is_dir() {
+                        DirEntry::directory(item.name)
+                    } else {
+                        DirEntry::file(item.name)
+                    };
+                    yield entry.with_metadata(metadata);
+                }
+
+                match page.next_link {
+                    Some(next_link) => request = connector.client.get(next_link),
+                    None => break,
+                }
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        debug!("rename: {:?} -> {:?}", from, to);
+
+        let (parent, name) = split_parent(to)?;
+        let parent = self.get_item(parent).await?;
+        let body = serde_json::json!({
+            "name": name,
+            "parentReference": { "id": parent.id },
+        });
+        let request = || self.client.patch(self.item_url(from)).json(&body);
+
+        match self.fetch(request(), from).await {
+            Err(FuseAdapterError::AlreadyExists(_)) => {
+                // Like rename(2), replace a file already at the destination
+                let existing = self.get_item(to).await?;
+                if existing.folder.is_some() {
+                    return Err(FuseAdapterError::AlreadyExists(to.display().to_string()));
+                }
+                self.fetch(self.client.delete(self.item_url(to)), to)
+                    .await?;
+                self.fetch(request(), from).await?;
+            }
+            result => {
+                result?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+        Err(FuseAdapterError::NotSupported(
+            "OneDrive doesn't support truncate; use cache layer".to_string(),
+        ))
+    }
+
+    async fn flush(&self, _path: &Path) -> Result<()> {
+        // Uploads are durable once Graph acknowledges them
+        Ok(())
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        match self.get_item(path).await {
+            Ok(item) => Ok(item.c_tag.as_deref() == Some(etag)),
+            Err(FuseAdapterError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        // Only personal OneDrive reports SHA-256; business drives have
+        // quickXorHash, which we can't verify
+        let item = self.get_item(path).await?;
+        if etag.is_some_and(|etag| item.c_tag.as_deref() != Some(etag)) {
+            return Ok(None);
+        }
+        Ok(item
+            .file
+            .and_then(|file| file.hashes)
+            .and_then(|hashes| hashes.sha256_hash)
+            .map(|hash| ContentHash::Sha256(hash.to_ascii_lowercase())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn graph_errno(status: u16, body: &'static str) -> i32 {
+        let response = hyper::Response::builder()
+            .status(status)
+            .body(body)
+            .unwrap();
+        graph_error(Response::from(response), Path::new("/a.txt"))
+            .await
+            .to_errno()
+    }
+
+    #[tokio::test]
+    async fn test_graph_error_errno() {
+        let throttled = r#"{"error":{"code":"activityLimitReached","message":"slow down"}}"#;
+        assert_eq!(graph_errno(429, throttled).await, libc::EAGAIN);
+        assert_eq!(graph_errno(503, "").await, libc::EAGAIN);
+        assert_eq!(graph_errno(403, "").await, libc::EACCES);
+        assert_eq!(graph_errno(404, "").await, libc::ENOENT);
+        assert_eq!(graph_errno(504, "").await, libc::ETIMEDOUT);
+        assert_eq!(graph_errno(507, "").await, libc::ENOSPC);
+        assert_eq!(graph_errno(500, "").await, libc::EIO);
+    }
+
+    #[test]
+    fn test_drive_url() {
+        let endpoint = "https://graph.microsoft.com/v1.0/";
+        assert_eq!(
+            drive_url(endpoint, Some("b!abc"), None, None),
+            "https://graph.microsoft.com/v1.0/drives/b%21abc"
+        );
+        assert_eq!(
+            drive_url(
+                endpoint,
+                None,
+                Some("contoso.sharepoint.com:/sites/eng"),
+                None
+            ),
+            "https://graph.microsoft.com/v1.0/sites/contoso.sharepoint.com:/sites/eng:/drive"
+        );
+        assert_eq!(
+            drive_url(
+                endpoint,
+                None,
+                Some("contoso.sharepoint.com,1a2b,3c4d"),
+                None
+            ),
+            "https://graph.microsoft.com/v1.0/sites/contoso.sharepoint.com,1a2b,3c4d/drive"
+        );
+        assert_eq!(
+            drive_url(endpoint, None, None, Some("alice@contoso.com")),
+            "https://graph.microsoft.com/v1.0/users/alice%40contoso.com/drive"
+        );
+        assert_eq!(
+            drive_url(endpoint, None, None, None),
+            "https://graph.microsoft.com/v1.0/me/drive"
+        );
+    }
+
+    #[test]
+    fn test_item_url() {
+        let drive = "https://graph.microsoft.com/v1.0/me/drive";
+        assert_eq!(
+            item_url(drive, "", Path::new("/")),
+            format!("{}/root", drive)
+        );
+        assert_eq!(
+            item_url(drive, "", Path::new("/docs/Q1 report#2.docx")),
+            format!("{}/root:/docs/Q1%20report%232.docx:", drive)
+        );
+        assert_eq!(
+            item_url(drive, "Shared/Team", Path::new("/")),
+            format!("{}/root:/Shared/Team:", drive)
+        );
+        assert_eq!(
+            item_url(drive, "Shared/Team", Path::new("/a.txt")),
+            format!("{}/root:/Shared/Team/a.txt:", drive)
+        );
+    }
+
+    #[test]
+    fn test_item_to_metadata() {
+        let file: DriveItem = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "name": "a.txt",
+            "size": 12,
+            "lastModifiedDateTime": "2024-05-01T10:00:00Z",
+            "cTag": "\"c:{1},2\"",
+            "file": { "hashes": { "sha256Hash": "ABC" } }
+        }))
+        .unwrap();
+        let metadata = item_to_metadata(&file);
+        assert!(metadata.is_file());
+        assert_eq!(metadata.size, 12);
+        assert_eq!(
+            metadata.mtime,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1714557600)
+        );
+        assert_eq!(metadata.etag.as_deref(), Some("\"c:{1},2\""));
+
+        let folder: DriveItem = serde_json::from_value(serde_json::json!({
+            "id": "2",
+            "name": "docs",
+            "size": 4096,
+            "folder": { "childCount": 3 }
+        }))
+        .unwrap();
+        let metadata = item_to_metadata(&folder);
+        assert!(metadata.is_dir());
+        assert_eq!(metadata.size, 0);
+    }
+}
@@ -0,0 +1,228 @@
+//! Adaptive request pacing for backends that throttle
+//!
+//! When a backend answers with "slow down" (S3 `503 SlowDown`, HTTP 429),
+//! the SDK's retries back off with jitter, but new operations keep arriving
+//! at the same rate and are soon throttled too. A `RequestPacer` caps one
+//! mount's operation rate while that happens: the first throttled response
+//! halves the rate seen over the last second, later ones halve it again (at
+//! most once a second), and every second without throttling raises it by a
+//! tenth. Once nothing has been throttled for `RECOVERY` the cap is lifted.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::connector::throttle::RateLimiter;
+
+/// Lowest rate the cap is lowered to, in operations per second
+const MIN_RATE: u64 = 1;
+
+/// How long the cap stays after the last throttled response
+const RECOVERY: Duration = Duration::from_secs(60);
+
+/// Shortest time between two changes of the cap
+const ADJUST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Throttling seen by a connector, for the status overlay's `stats` file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThrottleStats {
+    /// Responses that asked for requests to slow down, including retries
+    pub throttled: u64,
+    /// Current cap in operations per second (None while not throttled)
+    pub rate_limit: Option<u64>,
+    /// When the most recent throttled response arrived
+    pub last_throttled: Option<DateTime<Utc>>,
+}
+
+/// Caps the operation rate of one mount while its backend is throttling
+pub struct RequestPacer {
+    limiter: RateLimiter,
+    state: Mutex<PacerState>,
+    throttled: AtomicU64,
+}
+
+struct PacerState {
+    /// Current cap (None = uncapped)
+    rate: Option<u64>,
+    /// Start of the window operations are being counted in
+    window_start: Instant,
+    /// Operations started in the current window
+    window_count: u64,
+    /// Operations per second over the last full window
+    recent_rate: f64,
+    /// When the cap last changed
+    adjusted_at: Instant,
+    /// When the last throttled response arrived
+    throttled_at: Option<Instant>,
+    last_throttled: Option<DateTime<Utc>>,
+}
+
+impl RequestPacer {
+    /// Create an uncapped pacer
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            limiter: RateLimiter::new(0),
+            state: Mutex::new(PacerState {
+                rate: None,
+                window_start: now,
+                window_count: 0,
+                recent_rate: 0.0,
+                adjusted_at: now,
+                throttled_at: None,
+                last_throttled: None,
+            }),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait until another operation may start
+    pub async fn acquire(&self) {
+        {
+            let mut state = self.state.lock();
+            let now = Instant::now();
+            let elapsed = now.duration_since(state.window_start);
+            if elapsed >= ADJUST_INTERVAL {
+                state.recent_rate = state.window_count as f64 / elapsed.as_secs_f64();
+                state.window_start = now;
+                state.window_count = 0;
+            }
+            state.window_count += 1;
+        }
+        self.limiter.acquire(1).await;
+    }
+
+    /// Note a response asking for requests to slow down
+    pub fn record_throttled(&self) {
+        self.throttled.fetch_add(1, Ordering::Relaxed);
+        let mut state = self.state.lock();
+        let now = Instant::now();
+        state.throttled_at = Some(now);
+        state.last_throttled = Some(Utc::now());
+
+        let current = match state.rate {
+            // Already lowered within the last second; let that take effect
+            Some(_) if now.duration_since(state.adjusted_at) < ADJUST_INTERVAL => return,
+            Some(rate) => rate as f64,
+            None => {
+                let elapsed = now.duration_since(state.window_start).as_secs_f64();
+                let current = state.window_count as f64 / elapsed.max(1.0);
+                state.recent_rate.max(current)
+            }
+        };
+        let rate = ((current / 2.0) as u64).max(MIN_RATE);
+        if state.rate.is_none() {
+            warn!(
+                "Backend is throttling requests, limiting to {} operations/s",
+                rate
+            );
+        }
+        self.set_rate(&mut state, Some(rate), now);
+    }
+
+    /// Note a response that wasn't throttled, raising the cap if one is set
+    pub fn record_success(&self) {
+        let mut state = self.state.lock();
+        let Some(rate) = state.rate else {
+            return;
+        };
+        let now = Instant::now();
+        if now.duration_since(state.adjusted_at) < ADJUST_INTERVAL {
+            return;
+        }
+        let calm = state
+            .throttled_at
+            .is_none_or(|at| now.duration_since(at) >= RECOVERY);
+        if calm {
+            info!("Backend stopped throttling requests, lifting the rate limit");
+            self.set_rate(&mut state, None, now);
+        } else {
+            let raised = rate + (rate / 10).max(1);
+            self.set_rate(&mut state, Some(raised), now);
+        }
+    }
+
+    fn set_rate(&self, state: &mut PacerState, rate: Option<u64>, now: Instant) {
+        state.rate = rate;
+        state.adjusted_at = now;
+        self.limiter.set_rate(rate.unwrap_or(0));
+    }
+
+    /// Throttling seen so far and the current cap
+    pub fn stats(&self) -> ThrottleStats {
+        let state = self.state.lock();
+        ThrottleStats {
+            throttled: self.throttled.load(Ordering::Relaxed),
+            rate_limit: state.rate,
+            last_throttled: state.last_throttled,
+        }
+    }
+}
+
+impl std::fmt::Debug for RequestPacer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestPacer")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl Default for RequestPacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttling_lowers_then_restores_rate() {
+        let pacer = RequestPacer::new();
+
+        // 40 operations/s for two seconds
+        for _ in 0..2 {
+            for _ in 0..40 {
+                pacer.acquire().await;
+                tokio::time::advance(Duration::from_millis(25)).await;
+            }
+        }
+        assert!(pacer.stats().rate_limit.is_none());
+
+        pacer.record_throttled();
+        assert_eq!(pacer.stats().rate_limit, Some(20));
+
+        // A burst of throttled retries only lowers the cap once
+        pacer.record_throttled();
+        pacer.record_throttled();
+        let stats = pacer.stats();
+        assert_eq!(stats.rate_limit, Some(20));
+        assert_eq!(stats.throttled, 3);
+        assert!(stats.last_throttled.is_some());
+
+        // Operations beyond the cap wait
+        let start = Instant::now();
+        for _ in 0..60 {
+            pacer.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        tokio::time::advance(ADJUST_INTERVAL).await;
+        pacer.record_throttled();
+        assert_eq!(pacer.stats().rate_limit, Some(10));
+
+        tokio::time::advance(ADJUST_INTERVAL).await;
+        pacer.record_success();
+        assert_eq!(pacer.stats().rate_limit, Some(11));
+
+        tokio::time::advance(RECOVERY).await;
+        pacer.record_success();
+        assert!(pacer.stats().rate_limit.is_none());
+        assert_eq!(pacer.stats().throttled, 4);
+    }
+}
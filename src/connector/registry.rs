@@ -0,0 +1,117 @@
+//! Registry of connector types provided outside this crate
+//!
+//! A mount whose connector `type` isn't one of the built-in ones (`s3`,
+//! `gdrive`, `union`, `kv`, `onedrive`, `archive`, `memfs`) is created by the
+//! `ConnectorFactory` registered under that name. Factories must be
+//! registered before the config is validated and mounted:
+//!
+//! ```ignore
+//! fuse_adapter::connector::registry::register(MyBackendFactory)?;
+//! let config = Config::from_file(&path)?;
+//! config.validate()?;
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+
+use crate::connector::Connector;
+use crate::error::{FuseAdapterError, Result};
+
+/// Connector types handled by the crate itself
+pub const BUILTIN_TYPES: &[&str] = &[
+    "s3", "gdrive", "union", "kv", "onedrive", "archive", "memfs",
+];
+
+/// Registered factories by connector type
+static FACTORIES: Lazy<RwLock<HashMap<String, Arc<dyn ConnectorFactory>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Creates connectors of one custom type from their config
+#[async_trait]
+pub trait ConnectorFactory: Send + Sync {
+    /// The `type` value of the mounts this factory handles
+    fn type_name(&self) -> &str;
+
+    /// Create a connector from the mount's connector section, minus `type`
+    ///
+    /// `options` is typically deserialized into the factory's own config
+    /// struct with `serde_yaml::from_value`.
+    async fn create(&self, options: &serde_yaml::Value) -> Result<Arc<dyn Connector>>;
+}
+
+/// Make `factory` handle mounts of its `type_name`
+///
+/// Fails for the built-in types; registering the same custom type again
+/// replaces the earlier factory.
+pub fn register(factory: impl ConnectorFactory + 'static) -> Result<()> {
+    let type_name = factory.type_name().to_string();
+    if BUILTIN_TYPES.contains(&type_name.as_str()) {
+        return Err(FuseAdapterError::AlreadyExists(format!(
+            "connector type {:?} is built in",
+            type_name
+        )));
+    }
+    FACTORIES
+        .write()
+        .unwrap()
+        .insert(type_name, Arc::new(factory));
+    Ok(())
+}
+
+/// The factory registered for `type_name`, if any
+pub fn get(type_name: &str) -> Option<Arc<dyn ConnectorFactory>> {
+    FACTORIES.read().unwrap().get(type_name).cloned()
+}
+
+/// Whether a factory is registered for `type_name`
+pub fn is_registered(type_name: &str) -> bool {
+    FACTORIES.read().unwrap().contains_key(type_name)
+}
+
+/// Create a connector of a custom type with its registered factory
+pub async fn create(type_name: &str, options: &serde_yaml::Value) -> Result<Arc<dyn Connector>> {
+    let factory = get(type_name).ok_or_else(|| {
+        FuseAdapterError::Config(format!("unknown connector type {:?}", type_name))
+    })?;
+    factory.create(options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestFactory(&'static str);
+
+    #[async_trait]
+    impl ConnectorFactory for TestFactory {
+        fn type_name(&self) -> &str {
+            self.0
+        }
+
+        async fn create(&self, _options: &serde_yaml::Value) -> Result<Arc<dyn Connector>> {
+            Err(FuseAdapterError::NotSupported("test factory".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_and_create() {
+        assert!(register(TestFactory("s3")).is_err());
+
+        assert!(!is_registered("registry-test"));
+        register(TestFactory("registry-test")).unwrap();
+        assert!(is_registered("registry-test"));
+
+        let options = serde_yaml::Value::Null;
+        assert!(matches!(
+            create("registry-test", &options).await,
+            Err(FuseAdapterError::NotSupported(_))
+        ));
+        assert!(matches!(
+            create("registry-missing", &options).await,
+            Err(FuseAdapterError::Config(_))
+        ));
+    }
+}
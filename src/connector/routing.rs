@@ -0,0 +1,656 @@
+//! Routing connector that sends parts of a mount to different backends
+//!
+//! Each route pairs a glob with a backend. A path belongs to the first route
+//! whose glob matches it or one of its ancestors, and to the mount's own
+//! connector otherwise; every operation on the path goes to that backend.
+//! Globs match the path relative to the mount root, like hide patterns
+//! (e.g. `raw/**` or `**/*.parquet`).
+//!
+//! A route's backend sees paths relative to the glob's leading literal
+//! directories: with `raw/**`, `/raw/2024/a.csv` is `/2024/a.csv` in the
+//! route's backend, while `**/*.parquet` keeps full paths.
+//!
+//! Directory listings merge every backend that can hold entries of the
+//! directory, keeping each entry only from the backend it belongs to;
+//! subdirectories are shown from all of them. The directories leading to a
+//! route (like `/raw`) always exist. Renaming between backends fails with
+//! EXDEV, so `mv` falls back to copying and deleting.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use globset::{Glob, GlobMatcher};
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    copy_content, CacheRequirement, CacheRequirements, Capabilities, Connector, DirEntry,
+    DirEntryStream, DirtyRanges, FileType, FileVersion, Metadata, SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Characters that make a glob component a pattern rather than a literal name
+const GLOB_META: &[char] = &['*', '?', '[', '{', '\\'];
+
+fn not_found(path: &Path) -> FuseAdapterError {
+    FuseAdapterError::NotFound(path.to_string_lossy().to_string())
+}
+
+fn cross_device() -> FuseAdapterError {
+    FuseAdapterError::Io(std::io::Error::from_raw_os_error(libc::EXDEV))
+}
+
+/// `path` relative to the mount root
+fn relative(path: &Path) -> &Path {
+    path.strip_prefix("/").unwrap_or(path)
+}
+
+/// A glob and the backend the paths it matches are sent to
+struct Route {
+    matcher: GlobMatcher,
+    /// Leading literal directories of the glob, relative to the mount root
+    base: PathBuf,
+    connector: Arc<dyn Connector>,
+}
+
+impl Route {
+    fn new(pattern: &str, connector: Arc<dyn Connector>) -> Result<Self> {
+        let pattern = pattern.trim_start_matches('/');
+        let matcher = Glob::new(pattern)
+            .map_err(|e| FuseAdapterError::Config(format!("Invalid route '{}': {}", pattern, e)))?
+            .compile_matcher();
+        // The last component is what's matched, never part of the base
+        let mut components: Vec<&str> = pattern.split('/').collect();
+        components.pop();
+        let base = components
+            .into_iter()
+            .take_while(|c| !c.is_empty() && !c.contains(GLOB_META))
+            .collect();
+        Ok(Self {
+            matcher,
+            base,
+            connector,
+        })
+    }
+
+    /// Whether the route's glob matches `path` or one of its ancestors
+    fn matches(&self, path: &Path) -> bool {
+        relative(path)
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .any(|p| self.matcher.is_match(p))
+    }
+
+    /// `path` as the route's backend sees it, if it lies beneath the base
+    fn inner_path(&self, path: &Path) -> Option<PathBuf> {
+        let rest = relative(path).strip_prefix(&self.base).ok()?;
+        Some(Path::new("/").join(rest))
+    }
+}
+
+/// The default backend and the routes, shared with running listings
+struct Routes {
+    default: Arc<dyn Connector>,
+    routes: Vec<Route>,
+}
+
+impl Routes {
+    /// Index of the route `path` belongs to (None for the default backend)
+    fn owner(&self, path: &Path) -> Option<usize> {
+        self.routes.iter().position(|r| r.matches(path))
+    }
+
+    /// Backend `path` belongs to and the path it has there
+    fn resolve(&self, path: &Path) -> (Option<usize>, &Arc<dyn Connector>, PathBuf) {
+        let owner = self.owner(path);
+        match owner {
+            Some(i) => {
+                let route = &self.routes[i];
+                let inner = route.inner_path(path).unwrap_or_else(|| path.to_path_buf());
+                (owner, &route.connector, inner)
+            }
+            None => (None, &self.default, path.to_path_buf()),
+        }
+    }
+
+    /// Backends that can hold entries of directory `dir`, with the path of
+    /// `dir` in each
+    fn sources(&self, dir: &Path) -> Vec<(Option<usize>, &Arc<dyn Connector>, PathBuf)> {
+        let mut sources = Vec::new();
+        // Everything beneath a directory a route matches belongs to a route
+        if self.owner(dir).is_none() {
+            sources.push((None, &self.default, dir.to_path_buf()));
+        }
+        for (i, route) in self.routes.iter().enumerate() {
+            if let Some(inner) = route.inner_path(dir) {
+                sources.push((Some(i), &route.connector, inner));
+            }
+        }
+        sources
+    }
+
+    /// Whether `path` leads to a route, and so always exists as a directory
+    fn is_boundary(&self, path: &Path) -> bool {
+        let path = relative(path);
+        self.routes.iter().any(|r| r.base.starts_with(path))
+    }
+
+    /// Names in `dir` of the directories leading to routes beneath it
+    fn boundaries(&self, dir: &Path) -> Vec<OsString> {
+        let dir = relative(dir);
+        self.routes
+            .iter()
+            .filter_map(|r| r.base.strip_prefix(dir).ok()?.components().next())
+            .map(|c| c.as_os_str().to_os_string())
+            .collect()
+    }
+
+    fn connectors(&self) -> impl Iterator<Item = &Arc<dyn Connector>> {
+        std::iter::once(&self.default).chain(self.routes.iter().map(|r| &r.connector))
+    }
+}
+
+/// Connector sending paths that match route globs to other backends
+pub struct RoutingConnector {
+    routes: Arc<Routes>,
+}
+
+impl RoutingConnector {
+    /// Route paths matching each glob to its backend, in order, and
+    /// everything else to `default`; fails if a glob is invalid
+    pub fn new(
+        default: Arc<dyn Connector>,
+        routes: Vec<(String, Arc<dyn Connector>)>,
+    ) -> Result<Self> {
+        let routes = routes
+            .into_iter()
+            .map(|(pattern, connector)| Route::new(&pattern, connector))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            routes: Arc::new(Routes { default, routes }),
+        })
+    }
+
+    /// Backend `path` belongs to and the path it has there
+    fn resolve(&self, path: &Path) -> (&Arc<dyn Connector>, PathBuf) {
+        let (_, connector, inner) = self.routes.resolve(path);
+        (connector, inner)
+    }
+
+    /// Run `op` to create `path` in the backend it belongs to, first
+    /// creating parent directories the mount has but a route's backend lacks
+    async fn create<F, Fut>(&self, path: &Path, op: F) -> Result<()>
+    where
+        F: Fn(Arc<dyn Connector>, PathBuf) -> Fut + Send,
+        Fut: Future<Output = Result<()>> + Send,
+    {
+        let (owner, connector, inner) = self.routes.resolve(path);
+        match op(connector.clone(), inner.clone()).await {
+            Err(FuseAdapterError::NotFound(_)) if owner.is_some() => {}
+            result => return result,
+        }
+
+        let parent = path.parent().ok_or_else(|| not_found(path))?;
+        if !self.stat(parent).await?.is_dir() {
+            return Err(not_found(path));
+        }
+        let mut missing: Vec<&Path> = inner.ancestors().skip(1).collect();
+        missing.pop();
+        for dir in missing.into_iter().rev() {
+            match connector.create_dir(dir).await {
+                Ok(()) | Err(FuseAdapterError::AlreadyExists(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        op(connector.clone(), inner).await
+    }
+}
+
+#[async_trait]
+impl Connector for RoutingConnector {
+    fn capabilities(&self) -> Capabilities {
+        let mut caps = self.routes.default.capabilities();
+        for route in &self.routes.routes {
+            let other = route.connector.capabilities();
+            caps.read &= other.read;
+            caps.write &= other.write;
+            caps.range_read &= other.range_read;
+            caps.random_write &= other.random_write;
+            caps.rename &= other.rename;
+            caps.truncate &= other.truncate;
+            caps.set_mtime &= other.set_mtime;
+            caps.seekable &= other.seekable;
+            caps.set_mode &= other.set_mode;
+            caps.symlink &= other.symlink;
+        }
+        caps
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        // The cache above serves every backend, so the strictest one wins
+        let mut requirements = self.routes.default.cache_requirements();
+        for route in &self.routes.routes {
+            let other = route.connector.cache_requirements();
+            requirements.write_buffer = match (requirements.write_buffer, other.write_buffer) {
+                (CacheRequirement::Required, _) | (_, CacheRequirement::Required) => {
+                    CacheRequirement::Required
+                }
+                (CacheRequirement::Recommended, _) | (_, CacheRequirement::Recommended) => {
+                    CacheRequirement::Recommended
+                }
+                _ => CacheRequirement::None,
+            };
+            requirements.read_cache |= other.read_cache;
+            requirements.metadata_cache_ttl =
+                match (requirements.metadata_cache_ttl, other.metadata_cache_ttl) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+        }
+        requirements
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.routes.default.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.routes.default.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.routes.default.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.routes.default.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        for connector in self.routes.connectors() {
+            connector.invalidate_caches();
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        let (owner, connector, inner) = self.routes.resolve(path);
+        match connector.stat(&inner).await {
+            Err(FuseAdapterError::NotFound(_)) => {}
+            result => return result,
+        }
+
+        // A directory may only exist in the backends of routes beneath it
+        for (source, connector, inner) in self.routes.sources(path) {
+            if source == owner {
+                continue;
+            }
+            match connector.stat(&inner).await {
+                Ok(meta) if meta.is_dir() => return Ok(meta),
+                Ok(_) | Err(FuseAdapterError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if self.routes.is_boundary(path) {
+            return Ok(Metadata::directory(SystemTime::now()));
+        }
+        Err(not_found(path))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        match self.stat(path).await {
+            Ok(_) => Ok(true),
+            Err(FuseAdapterError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        let (connector, inner) = self.resolve(path);
+        connector.read(&inner, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        let (connector, inner) = self.resolve(path);
+        connector.write(&inner, offset, data).await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        let (connector, inner) = self.resolve(path);
+        connector.write_dirty(&inner, data, dirty).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.create(path, |c, p| async move { c.create_file(&p).await })
+            .await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.create(path, |c, p| async move { c.create_dir(&p).await })
+            .await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        let (connector, inner) = self.resolve(path);
+        connector.remove_file(&inner).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        if !recursive {
+            let mut entries = self.list_dir(path);
+            if let Some(entry) = entries.next().await {
+                entry?;
+                return Err(FuseAdapterError::NotEmpty(
+                    path.to_string_lossy().to_string(),
+                ));
+            }
+        }
+
+        let mut removed = false;
+        for (source, connector, inner) in self.routes.sources(path) {
+            // A route's own root stays
+            if source.is_some() && inner == Path::new("/") {
+                continue;
+            }
+            match connector.remove_dir(&inner, recursive).await {
+                Ok(()) => removed = true,
+                Err(FuseAdapterError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        match (removed, self.routes.is_boundary(path)) {
+            (_, true) => Err(FuseAdapterError::PermissionDenied),
+            (true, false) => Ok(()),
+            (false, false) => Err(not_found(path)),
+        }
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let routes = self.routes.clone();
+        let path = path.to_path_buf();
+
+        Box::pin(try_stream! {
+            let mut seen: HashSet<OsString> = HashSet::new();
+            let mut found = false;
+
+            for (source, connector, inner) in routes.sources(&path) {
+                let mut entries = connector.list_dir(&inner);
+                let mut first = true;
+                while let Some(entry) = entries.next().await {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(FuseAdapterError::NotFound(_)) if first => break,
+                        Err(e) => Err(e)?,
+                    };
+                    first = false;
+                    found = true;
+                    // Subdirectories merge; anything else shows only from
+                    // the backend it belongs to
+                    let owned = routes.owner(&path.join(&entry.name)) == source;
+                    if (owned || entry.file_type == FileType::Directory)
+                        && seen.insert(entry.name.clone())
+                    {
+                        yield entry;
+                    }
+                }
+                // An empty listing still means the directory exists
+                found |= first && connector.exists(&inner).await?;
+            }
+
+            if !found && !routes.is_boundary(&path) {
+                Err(not_found(&path))?;
+            }
+            for name in routes.boundaries(&path) {
+                if seen.insert(name.clone()) {
+                    yield DirEntry::directory(name);
+                }
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from_owner, _, from_inner) = self.routes.resolve(from);
+        let (to_owner, _, _) = self.routes.resolve(to);
+        if from_owner != to_owner {
+            return Err(cross_device());
+        }
+        // A directory spread over several backends can't be moved in one
+        if self.stat(from).await?.is_dir()
+            && (self.routes.sources(from).len() > 1 || self.routes.is_boundary(from))
+        {
+            return Err(cross_device());
+        }
+        self.create(to, |c, p| {
+            let from = from_inner.clone();
+            async move { c.rename(&from, &p).await }
+        })
+        .await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        let (connector, inner) = self.resolve(path);
+        connector.truncate(&inner, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        let (connector, inner) = self.resolve(path);
+        connector.flush(&inner).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.create(path, |c, p| async move {
+            c.create_file_with_mode(&p, mode).await
+        })
+        .await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.create(path, |c, p| async move {
+            c.create_dir_with_mode(&p, mode).await
+        })
+        .await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        let (connector, inner) = self.resolve(path);
+        connector.set_mode(&inner, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        let (connector, inner) = self.resolve(path);
+        connector.readlink(&inner).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.create(link_path, |c, p| async move { c.symlink(target, &p).await })
+            .await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        let (connector, inner) = self.resolve(path);
+        connector.list_versions(&inner).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        let (connector, inner) = self.resolve(path);
+        connector
+            .read_version(&inner, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        for connector in self.routes.connectors() {
+            connector.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        let mut result = Ok(());
+        for connector in self.routes.connectors() {
+            if let Err(e) = connector.shutdown().await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        let (connector, inner) = self.resolve(path);
+        connector.content_hash(&inner, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        let (connector, inner) = self.resolve(path);
+        connector.revalidate(&inner, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from_owner, _, from_inner) = self.routes.resolve(from);
+        let (to_owner, _, _) = self.routes.resolve(to);
+        if from_owner == to_owner {
+            self.create(to, |c, p| {
+                let from = from_inner.clone();
+                async move { c.copy(&from, &p).await }
+            })
+            .await
+        } else {
+            copy_content(self, from, to).await
+        }
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        let (connector, inner) = self.resolve(path);
+        connector.zero_range(&inner, offset, len).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        let (connector, inner) = self.resolve(path);
+        connector.seek_region(&inner, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::memfs::MemfsConnector;
+
+    async fn names(connector: &dyn Connector, path: &str) -> Vec<String> {
+        let mut names: Vec<String> = connector
+            .list_dir(Path::new(path))
+            .map(|e| e.unwrap().name.to_string_lossy().to_string())
+            .collect()
+            .await;
+        names.sort();
+        names
+    }
+
+    fn router() -> (
+        RoutingConnector,
+        Arc<MemfsConnector>,
+        Arc<MemfsConnector>,
+        Arc<MemfsConnector>,
+    ) {
+        let default = Arc::new(MemfsConnector::new(None));
+        let raw = Arc::new(MemfsConnector::new(None));
+        let logs = Arc::new(MemfsConnector::new(None));
+        let router = RoutingConnector::new(
+            default.clone(),
+            vec![
+                (
+                    "/data/raw/**".to_string(),
+                    raw.clone() as Arc<dyn Connector>,
+                ),
+                ("**/*.log".to_string(), logs.clone() as Arc<dyn Connector>),
+            ],
+        )
+        .unwrap();
+        (router, default, raw, logs)
+    }
+
+    #[tokio::test]
+    async fn test_paths_go_to_their_route() {
+        let (router, default, raw, logs) = router();
+
+        router
+            .create_file(Path::new("/data/raw/a.csv"))
+            .await
+            .unwrap();
+        router
+            .write(Path::new("/data/raw/a.csv"), 0, b"raw")
+            .await
+            .unwrap();
+        router.create_dir(Path::new("/app")).await.unwrap();
+        router.create_file(Path::new("/app/run.log")).await.unwrap();
+        router.create_file(Path::new("/app/config")).await.unwrap();
+
+        // Routes see paths below the glob's literal directories
+        assert!(raw.exists(Path::new("/a.csv")).await.unwrap());
+        assert!(logs.exists(Path::new("/app/run.log")).await.unwrap());
+        assert!(default.exists(Path::new("/app/config")).await.unwrap());
+        assert!(!default.exists(Path::new("/app/run.log")).await.unwrap());
+        assert_eq!(
+            router
+                .read(Path::new("/data/raw/a.csv"), 0, 3)
+                .await
+                .unwrap(),
+            Bytes::from_static(b"raw")
+        );
+
+        // Directories leading to a route exist without being created
+        assert!(router.stat(Path::new("/data")).await.unwrap().is_dir());
+        assert!(router.stat(Path::new("/data/raw")).await.unwrap().is_dir());
+        assert_eq!(names(&router, "/").await, vec!["app", "data"]);
+        assert_eq!(names(&router, "/data").await, vec!["raw"]);
+        assert_eq!(names(&router, "/data/raw").await, vec!["a.csv"]);
+        assert_eq!(names(&router, "/app").await, vec!["config", "run.log"]);
+        assert!(matches!(
+            router.stat(Path::new("/missing")).await,
+            Err(FuseAdapterError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_moves_between_routes() {
+        let (router, _, raw, _) = router();
+        router.create_file(Path::new("/a.csv")).await.unwrap();
+        router.write(Path::new("/a.csv"), 0, b"data").await.unwrap();
+
+        let err = router
+            .rename(Path::new("/a.csv"), Path::new("/data/raw/a.csv"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::EXDEV);
+
+        router
+            .copy(Path::new("/a.csv"), Path::new("/data/raw/b.csv"))
+            .await
+            .unwrap();
+        assert_eq!(raw.stat(Path::new("/b.csv")).await.unwrap().size, 4);
+
+        router
+            .rename(Path::new("/data/raw/b.csv"), Path::new("/data/raw/c.csv"))
+            .await
+            .unwrap();
+        assert!(raw.exists(Path::new("/c.csv")).await.unwrap());
+    }
+}
@@ -3,36 +3,113 @@
 //! This connector provides access to Amazon S3 or S3-compatible storage
 //! backends (MinIO, LocalStack, etc.).
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 /// S3 metadata key for storing POSIX file mode
 const S3_MODE_METADATA_KEY: &str = "posix-mode";
 /// S3 metadata key for storing symlink target
 const S3_SYMLINK_METADATA_KEY: &str = "symlink-target";
+/// S3 metadata key for storing the SHA-256 of the object content
+const S3_SHA256_METADATA_KEY: &str = "sha256";
+/// Smallest part used for partial uploads (S3's minimum is 5 MiB)
+const DIRTY_PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Most parts S3 allows in a multipart upload
+const MAX_PARTS: u64 = 10_000;
+/// Largest object a single CopyObject can copy
+const MAX_COPY_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// How long a derived directory mtime is reused before it's looked up again
+const DIR_MTIME_TTL: Duration = Duration::from_secs(60);
+/// Directory under the mount root holding journals of renames in progress
+const RENAME_JOURNAL_DIR: &str = ".fuse-adapter-renames";
+/// Copies a rename runs at once
+const RENAME_CONCURRENCY: usize = 16;
+/// Most keys a DeleteObjects request can delete
+const MAX_DELETE_BATCH: usize = 1000;
+/// Characters escaped in the `x-amz-copy-source` header
+const COPY_SOURCE_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
 
 use async_stream::try_stream;
 use async_trait::async_trait;
 use aws_config::BehaviorVersion;
-use aws_sdk_s3::config::Region;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::interceptors::BeforeDeserializationInterceptorContextRef;
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::config::timeout::TimeoutConfig;
+use aws_sdk_s3::config::{ConfigBag, Intercept, Region, RuntimeComponents};
+use aws_sdk_s3::error::{BoxError, DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{
+    ChecksumAlgorithm, ChecksumMode, ChecksumType, CompletedMultipartUpload, CompletedPart,
+    ServerSideEncryption,
+};
 use aws_sdk_s3::Client;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use bytes::Bytes;
-use tracing::{debug, trace};
-
-use crate::config::S3ConnectorConfig;
+use dashmap::DashMap;
+use futures::{StreamExt, TryStreamExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{debug, info, trace, warn};
+
+use crate::config::{S3ConnectorConfig, S3DirMtime};
+use crate::connector::key_encoding::{KeyCodec, MAX_KEY_LEN};
+use crate::connector::pacer::{RequestPacer, ThrottleStats};
 use crate::connector::{
-    CacheRequirement, CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream,
-    Metadata,
+    copy_content, CacheRequirement, CacheRequirements, Capabilities, Connector, DirEntry,
+    DirEntryStream, DirtyRanges, FileType, FileVersion, Metadata,
 };
 use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+use crate::telemetry;
 
 /// S3 connector for Amazon S3 and S3-compatible storage
 pub struct S3Connector {
     client: Client,
     bucket: String,
     prefix: String,
+    /// Maps file names to key components and back
+    keys: KeyCodec,
+    /// Attach size/mtime/etag from ListObjectsV2 to directory entries
+    list_metadata: bool,
+    /// Keys requested per ListObjectsV2 page (None = S3's default)
+    max_keys: Option<i32>,
+    /// Where directory mtimes come from
+    dir_mtime: S3DirMtime,
+    /// Create a `name/` marker object for each new directory
+    directory_markers: bool,
+    /// Rename by journaled copy + delete instead of leaving it to the cache
+    atomic_rename: bool,
+    /// Derived directory mtimes and when they were looked up
+    dir_mtimes: Arc<DashMap<PathBuf, (SystemTime, Instant)>>,
+    /// Bounds the number of operations in flight (None = no limit)
+    requests: Option<Arc<Semaphore>>,
+    /// Slows operations down while S3 is throttling (None = disabled)
+    pacer: Option<Arc<RequestPacer>>,
+    /// Serve the bucket as it was at this time (read-only)
+    as_of: Option<SystemTime>,
+    /// Version each key resolved to at `as_of` (None = didn't exist)
+    snapshot_versions: DashMap<String, Option<SnapshotObject>>,
+}
+
+/// The version of an object that was current at a snapshot time
+#[derive(Debug, Clone)]
+struct SnapshotObject {
+    version_id: String,
+    size: u64,
+    mtime: SystemTime,
+    etag: Option<String>,
 }
 
 impl S3Connector {
@@ -44,6 +121,28 @@ impl S3Connector {
             sdk_config_builder = sdk_config_builder.region(Region::new(region.clone()));
         }
 
+        if config.connect_timeout.is_some() || config.read_timeout.is_some() {
+            let mut timeouts = TimeoutConfig::builder();
+            if let Some(timeout) = config.connect_timeout {
+                timeouts = timeouts.connect_timeout(timeout);
+            }
+            if let Some(timeout) = config.read_timeout {
+                timeouts = timeouts.read_timeout(timeout);
+            }
+            sdk_config_builder = sdk_config_builder.timeout_config(timeouts.build());
+        }
+
+        if config.max_attempts.is_some() || config.initial_backoff.is_some() {
+            let mut retry = RetryConfig::standard();
+            if let Some(attempts) = config.max_attempts {
+                retry = retry.with_max_attempts(attempts);
+            }
+            if let Some(backoff) = config.initial_backoff {
+                retry = retry.with_initial_backoff(backoff);
+            }
+            sdk_config_builder = sdk_config_builder.retry_config(retry);
+        }
+
         let sdk_config = sdk_config_builder.load().await;
 
         let mut s3_config_builder = aws_sdk_s3::config::Builder::from(&sdk_config);
@@ -56,26 +155,184 @@ impl S3Connector {
             s3_config_builder = s3_config_builder.force_path_style(true);
         }
 
+        if telemetry::enabled() {
+            s3_config_builder = s3_config_builder.interceptor(RecordRequestIds);
+        }
+
+        let pacer = config.adaptive_rate.then(|| Arc::new(RequestPacer::new()));
+        if let Some(pacer) = &pacer {
+            s3_config_builder = s3_config_builder.interceptor(PaceRequests(pacer.clone()));
+        }
+
         let client = Client::from_conf(s3_config_builder.build());
 
         let prefix = config.prefix.unwrap_or_default();
+        let create_prefix = config.create_prefix;
 
-        Ok(Self {
+        let connector = Self {
             client,
             bucket: config.bucket,
             prefix,
-        })
+            keys: KeyCodec::new(config.key_encoding, &config.escape_chars),
+            list_metadata: config.list_metadata,
+            max_keys: config.max_keys.map(|n| n as i32),
+            dir_mtime: config.dir_mtime,
+            directory_markers: config.directory_markers,
+            atomic_rename: config.atomic_rename,
+            dir_mtimes: Arc::new(DashMap::new()),
+            requests: config
+                .max_concurrent_requests
+                .map(|n| Arc::new(Semaphore::new(n))),
+            pacer,
+            as_of: config.as_of,
+            snapshot_versions: DashMap::new(),
+        };
+
+        // A missing bucket or denied prefix fails the mount, not the first
+        // operation on it
+        if let Some(marker) = prefix_marker(&connector.prefix) {
+            connector
+                .check_prefix(&marker, create_prefix && connector.as_of.is_none())
+                .await?;
+        }
+
+        // Finish or undo renames interrupted by a crash
+        if connector.atomic_rename && connector.as_of.is_none() {
+            connector.recover_renames().await;
+        }
+
+        Ok(connector)
+    }
+
+    /// Fail with EROFS if this is a point-in-time mount
+    fn check_writable(&self) -> Result<()> {
+        if self.as_of.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Wait for a slot under `max_concurrent_requests`
+    ///
+    /// Held for the duration of one connector operation. Operations never
+    /// call each other while holding it, so a limit of 1 can't deadlock.
+    async fn request_permit(&self) -> Option<SemaphorePermit<'_>> {
+        if let Some(pacer) = &self.pacer {
+            pacer.acquire().await;
+        }
+        match &self.requests {
+            // The semaphore is never closed
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        }
+    }
+
+    /// Mtime reported for the directory at `path`, whose keys start with
+    /// `dir_key`
+    ///
+    /// Directories with no marker or no objects of their own report the
+    /// epoch, so the result stays stable between lookups.
+    async fn dir_mtime(&self, path: &Path, dir_key: &str) -> Result<SystemTime> {
+        match self.dir_mtime {
+            S3DirMtime::Now => return Ok(SystemTime::now()),
+            S3DirMtime::Epoch => return Ok(SystemTime::UNIX_EPOCH),
+            S3DirMtime::LatestChild | S3DirMtime::Marker => {}
+        }
+        let cached = self
+            .dir_mtimes
+            .get(path)
+            .filter(|entry| entry.1.elapsed() < DIR_MTIME_TTL)
+            .map(|entry| entry.0);
+        if let Some(mtime) = cached {
+            return Ok(mtime);
+        }
+
+        let mtime = match self.dir_mtime {
+            S3DirMtime::Marker => self.marker_mtime(dir_key).await?,
+            _ => self.latest_child_mtime(dir_key).await?,
+        }
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+        self.dir_mtimes
+            .insert(path.to_path_buf(), (mtime, Instant::now()));
+        Ok(mtime)
+    }
+
+    /// LastModified of the `dir_key` marker object, if there is one
+    async fn marker_mtime(&self, dir_key: &str) -> Result<Option<SystemTime>> {
+        if dir_key.is_empty() {
+            return Ok(None);
+        }
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(dir_key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(output.last_modified().map(to_system_time)),
+            Err(e) => {
+                if e.as_service_error().is_some_and(|s| s.is_not_found()) {
+                    Ok(None)
+                } else {
+                    Err(s3_error("HeadObject", e))
+                }
+            }
+        }
+    }
+
+    /// Newest LastModified among the objects directly under `dir_key`,
+    /// including its marker
+    async fn latest_child_mtime(&self, dir_key: &str) -> Result<Option<SystemTime>> {
+        let mut latest = None;
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let result = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(dir_key)
+                .delimiter("/")
+                .set_max_keys(self.max_keys)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| s3_error(&format!("ListObjectsV2 for prefix '{}'", dir_key), e))?;
+
+            let page_latest = result
+                .contents()
+                .iter()
+                .filter_map(|obj| obj.last_modified().map(to_system_time))
+                .max();
+            latest = latest.max(page_latest);
+
+            if result.is_truncated().unwrap_or(false) {
+                continuation_token = result.next_continuation_token().map(|s| s.to_string());
+            } else {
+                break;
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// Drop the derived mtimes that a change at `path` may have moved
+    fn forget_dir_mtimes(&self, path: &Path) {
+        self.dir_mtimes.remove(path);
+        if let Some(parent) = path.parent() {
+            self.dir_mtimes.remove(parent);
+        }
     }
 
     /// Convert a filesystem path to an S3 key
     fn path_to_key(&self, path: &Path) -> String {
-        let path_str = path.to_string_lossy();
-        let path_str = path_str.trim_start_matches('/');
+        let path_str = self.keys.encode_path(path);
 
         if path_str.is_empty() {
             self.prefix.clone()
         } else if self.prefix.is_empty() {
-            path_str.to_string()
+            path_str
         } else {
             // Ensure proper separator between prefix and path
             if self.prefix.ends_with('/') {
@@ -86,6 +343,21 @@ impl S3Connector {
         }
     }
 
+    /// Convert a path that is about to be created to an S3 key, failing if
+    /// the key would be longer than S3 allows
+    fn new_key(&self, path: &Path) -> Result<String> {
+        let key = self.path_to_key(path);
+        if key.len() > MAX_KEY_LEN {
+            return Err(FuseAdapterError::NameTooLong(format!(
+                "S3 key for {:?} is {} bytes (max {})",
+                path,
+                key.len(),
+                MAX_KEY_LEN
+            )));
+        }
+        Ok(key)
+    }
+
     /// Convert an S3 key to a relative path (removing prefix)
     #[allow(dead_code)]
     fn key_to_path(&self, key: &str) -> std::path::PathBuf {
@@ -115,16 +387,904 @@ impl S3Connector {
     }
 }
 
+/// Convert an S3 timestamp to a `SystemTime`
+/// Attaches the IDs S3 returns with every response to the current span, so
+/// a slow or failed call can be looked up on the AWS side
+#[derive(Debug)]
+struct RecordRequestIds;
+
+impl Intercept for RecordRequestIds {
+    fn name(&self) -> &'static str {
+        "RecordRequestIds"
+    }
+
+    fn read_before_deserialization(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> std::result::Result<(), BoxError> {
+        let headers = context.response().headers();
+        if let Some(request_id) = headers.get("x-amz-request-id") {
+            telemetry::record_request_id(request_id, headers.get("x-amz-id-2"));
+        }
+        Ok(())
+    }
+}
+
+/// Feeds every response, retries included, to the mount's request pacer
+#[derive(Debug)]
+struct PaceRequests(Arc<RequestPacer>);
+
+impl Intercept for PaceRequests {
+    fn name(&self) -> &'static str {
+        "PaceRequests"
+    }
+
+    fn read_before_deserialization(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> std::result::Result<(), BoxError> {
+        // S3 throttles with 503 SlowDown; some compatible stores use 429
+        match context.response().status().as_u16() {
+            429 | 503 => self.0.record_throttled(),
+            _ => self.0.record_success(),
+        }
+        Ok(())
+    }
+}
+
+fn to_system_time(dt: &aws_sdk_s3::primitives::DateTime) -> SystemTime {
+    SystemTime::UNIX_EPOCH
+        .checked_add(Duration::new(dt.secs().max(0) as u64, dt.subsec_nanos()))
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Key of the marker object for a mount's `prefix` (None without one)
+fn prefix_marker(prefix: &str) -> Option<String> {
+    let prefix = prefix.trim_end_matches('/');
+    (!prefix.is_empty()).then(|| format!("{}/", prefix))
+}
+
+/// Map a failed S3 request to the error its errno should reflect
+///
+/// Denied requests become EACCES and throttling (503 / `SlowDown`, left over
+/// once the SDK's own retries are used up) becomes EAGAIN, so callers can
+/// tell them from a generic EIO. `operation` names the request in the message.
+fn s3_error<E>(operation: &str, e: SdkError<E, HttpResponse>) -> FuseAdapterError
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    let status = e.raw_response().map(|r| r.status().as_u16());
+    let message = format!("S3 {} error: {}", operation, DisplayErrorContext(&e));
+    if matches!(e, SdkError::TimeoutError(_)) {
+        return FuseAdapterError::Timeout(message);
+    }
+    match (status, e.code()) {
+        (
+            _,
+            Some(
+                "AccessDenied"
+                | "AllAccessDisabled"
+                | "InvalidAccessKeyId"
+                | "SignatureDoesNotMatch"
+                | "ExpiredToken"
+                | "InvalidToken",
+            ),
+        )
+        | (Some(401 | 403), _) => FuseAdapterError::PermissionDenied,
+        (_, Some("SlowDown" | "ServiceUnavailable" | "Throttling" | "RequestLimitExceeded"))
+        | (Some(429 | 503), _) => FuseAdapterError::TooManyRequests(message),
+        (_, Some("RequestTimeout")) => FuseAdapterError::Timeout(message),
+        (_, Some("EntityTooLarge")) => FuseAdapterError::FileTooLarge,
+        (_, Some("NoSuchKey" | "NotFound")) | (Some(404), None) => {
+            FuseAdapterError::NotFound(message)
+        }
+        _ => FuseAdapterError::Backend(message),
+    }
+}
+
+/// Picks, for each key, the newest version at or before a snapshot time
+struct SnapshotBuilder {
+    as_of: SystemTime,
+    /// Newest version seen so far (None = a delete marker)
+    latest: HashMap<String, (SystemTime, Option<SnapshotObject>)>,
+}
+
+impl SnapshotBuilder {
+    fn new(as_of: SystemTime) -> Self {
+        Self {
+            as_of,
+            latest: HashMap::new(),
+        }
+    }
+
+    /// Record a version (`None` for a delete marker) of `key` written at `mtime`
+    fn add(&mut self, key: &str, mtime: SystemTime, object: Option<SnapshotObject>) {
+        if mtime > self.as_of {
+            return;
+        }
+        match self.latest.get(key) {
+            Some((seen, _)) if *seen >= mtime => {}
+            _ => {
+                self.latest.insert(key.to_string(), (mtime, object));
+            }
+        }
+    }
+
+    /// Keys that existed at the snapshot time
+    fn build(self) -> BTreeMap<String, SnapshotObject> {
+        self.latest
+            .into_iter()
+            .filter_map(|(key, (_, object))| object.map(|o| (key, o)))
+            .collect()
+    }
+}
+
+/// Objects under `prefix` as they were at `as_of`, keyed by full S3 key
+async fn list_snapshot(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+    as_of: SystemTime,
+    requests: Option<&Semaphore>,
+) -> Result<BTreeMap<String, SnapshotObject>> {
+    let mut snapshot = SnapshotBuilder::new(as_of);
+    let mut key_marker: Option<String> = None;
+    let mut version_id_marker: Option<String> = None;
+
+    loop {
+        let request = client
+            .list_object_versions()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_key_marker(key_marker.take())
+            .set_version_id_marker(version_id_marker.take());
+
+        let permit = match requests {
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        };
+        let result = request.send().await;
+        drop(permit);
+        let result = result.map_err(|e| s3_error("ListObjectVersions", e))?;
+
+        for version in result.versions() {
+            let (Some(key), Some(version_id), Some(modified)) =
+                (version.key(), version.version_id(), version.last_modified())
+            else {
+                continue;
+            };
+            let mtime = to_system_time(modified);
+            let object = SnapshotObject {
+                version_id: version_id.to_string(),
+                size: version.size().unwrap_or(0) as u64,
+                mtime,
+                etag: version.e_tag().map(str::to_string),
+            };
+            snapshot.add(key, mtime, Some(object));
+        }
+        for marker in result.delete_markers() {
+            if let (Some(key), Some(modified)) = (marker.key(), marker.last_modified()) {
+                snapshot.add(key, to_system_time(modified), None);
+            }
+        }
+
+        if result.is_truncated().unwrap_or(false) {
+            key_marker = result.next_key_marker().map(|s| s.to_string());
+            version_id_marker = result.next_version_id_marker().map(|s| s.to_string());
+        } else {
+            break;
+        }
+    }
+
+    Ok(snapshot.build())
+}
+
+impl S3Connector {
+    /// Objects at or beneath `key` at the snapshot time
+    ///
+    /// Resolved files are remembered, since a past snapshot never changes.
+    async fn snapshot(
+        &self,
+        key: &str,
+        as_of: SystemTime,
+    ) -> Result<BTreeMap<String, SnapshotObject>> {
+        let objects = list_snapshot(
+            &self.client,
+            &self.bucket,
+            key,
+            as_of,
+            self.requests.as_deref(),
+        )
+        .await?;
+        for (key, object) in &objects {
+            self.snapshot_versions
+                .insert(key.clone(), Some(object.clone()));
+        }
+        Ok(objects)
+    }
+
+    /// The version of the file at `path` that was current at the snapshot time
+    async fn snapshot_object(&self, path: &Path, as_of: SystemTime) -> Result<SnapshotObject> {
+        let key = self.path_to_key(path);
+        let cached = self.snapshot_versions.get(&key).map(|v| v.clone());
+        let object = match cached {
+            Some(object) => object,
+            None => {
+                let object = self.snapshot(&key, as_of).await?.remove(&key);
+                self.snapshot_versions.insert(key, object.clone());
+                object
+            }
+        };
+        object.ok_or_else(|| FuseAdapterError::NotFound(format!("File not found: {:?}", path)))
+    }
+
+    async fn snapshot_stat(&self, path: &Path, as_of: SystemTime) -> Result<Metadata> {
+        let key = self.path_to_key(path);
+        trace!("snapshot stat: path={:?} key={}", path, key);
+
+        if key.is_empty() || key == self.prefix {
+            return Ok(Metadata::directory(as_of));
+        }
+
+        if let Some(Some(object)) = self.snapshot_versions.get(&key).map(|v| v.clone()) {
+            return Ok(Metadata::file(object.size, object.mtime).with_etag(object.etag));
+        }
+
+        let objects = self.snapshot(&key, as_of).await?;
+        if let Some(object) = objects.get(&key) {
+            return Ok(Metadata::file(object.size, object.mtime).with_etag(object.etag.clone()));
+        }
+        self.snapshot_versions.insert(key.clone(), None);
+
+        let dir_key = format!("{}/", key.trim_end_matches('/'));
+        if objects.keys().any(|k| k.starts_with(&dir_key)) {
+            return Ok(Metadata::directory(as_of));
+        }
+
+        Err(FuseAdapterError::NotFound(format!(
+            "Path not found: {:?}",
+            path
+        )))
+    }
+
+    fn snapshot_list_dir(&self, path: &Path, as_of: SystemTime) -> DirEntryStream {
+        let mut prefix = self.path_to_key(path);
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+
+        let client = self.client.clone();
+        let bucket = self.bucket.clone();
+        let keys = self.keys.clone();
+        let requests = self.requests.clone();
+
+        // Versions can't be listed with a delimiter usefully (a prefix may
+        // only hold objects created later), so the whole subtree is resolved
+        Box::pin(try_stream! {
+            let objects =
+                list_snapshot(&client, &bucket, &prefix, as_of, requests.as_deref()).await?;
+            let mut dirs = std::collections::BTreeSet::new();
+            for (key, object) in objects {
+                let rel_key = key.strip_prefix(&prefix).unwrap_or(&key);
+                match rel_key.split_once('/') {
+                    Some((dir, _)) => {
+                        if let Some(name) = keys.decode_name(dir) {
+                            dirs.insert(name);
+                        }
+                    }
+                    None => {
+                        let Some(name) = keys.decode_name(rel_key) else {
+                            trace!("snapshot_list_dir: skipping key {:?}", key);
+                            continue;
+                        };
+                        let metadata =
+                            Metadata::file(object.size, object.mtime).with_etag(object.etag);
+                        yield DirEntry::file(name).with_metadata(metadata);
+                    }
+                }
+            }
+            for dir in dirs {
+                yield DirEntry::directory(dir);
+            }
+        })
+    }
+}
+
+/// How far a journaled rename got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RenamePhase {
+    /// Copying sources to their destinations; undone by deleting the copies
+    Copying,
+    /// Every copy is verified; finished by deleting the sources
+    Deleting,
+}
+
+/// Journal object written before a rename touches any key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RenameJournal {
+    phase: RenamePhase,
+    /// Source and destination key of each object moved
+    keys: Vec<(String, String)>,
+    /// Destinations that existed before the rename, which undoing it keeps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    replaced: Vec<String>,
+}
+
+impl RenameJournal {
+    /// Keys to delete to undo the rename
+    fn copies(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|(_, dest)| dest)
+            .filter(|dest| !self.replaced.contains(dest))
+            .cloned()
+            .collect()
+    }
+
+    /// Keys to delete to finish the rename
+    fn sources(&self) -> Vec<String> {
+        self.keys.iter().map(|(src, _)| src.clone()).collect()
+    }
+}
+
+/// An object to be moved by a rename
+#[derive(Debug, Clone)]
+struct RenameSource {
+    key: String,
+    size: u64,
+    etag: Option<String>,
+}
+
+/// Pair each source key under `from` with its key under `to`
+fn plan_rename(from: &str, to: &str, sources: &[RenameSource]) -> Result<Vec<(String, String)>> {
+    sources
+        .iter()
+        .map(|source| {
+            let rel = source.key.strip_prefix(from).unwrap_or(&source.key);
+            let dest = format!("{}{}", to, rel);
+            if dest.len() > MAX_KEY_LEN {
+                return Err(FuseAdapterError::NameTooLong(format!(
+                    "S3 key {:?} is {} bytes (max {})",
+                    dest,
+                    dest.len(),
+                    MAX_KEY_LEN
+                )));
+            }
+            Ok((source.key.clone(), dest))
+        })
+        .collect()
+}
+
+/// Whether `etag` is a plain MD5 of the content, which a copy preserves
+fn is_md5_etag(etag: &str) -> bool {
+    let etag = etag.trim_matches('"');
+    etag.len() == 32 && etag.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Renames with `atomic_rename`
+///
+/// Every object is copied to its destination and checked before any source
+/// is deleted. A journal object under the mount root records the keys and
+/// how far the rename got: a failed copy deletes the copies made so far, and
+/// a rename interrupted by a crash is undone (still copying) or finished
+/// (deleting sources) when the connector next starts.
+impl S3Connector {
+    /// Key prefix of the rename journal
+    fn journal_prefix(&self) -> String {
+        let mut key = self.prefix.clone();
+        if !key.is_empty() && !key.ends_with('/') {
+            key.push('/');
+        }
+        format!("{}{}/", key, RENAME_JOURNAL_DIR)
+    }
+
+    /// Whether `path` is the rename journal directory, which is kept out of
+    /// the mount
+    fn is_journal_path(path: &Path) -> bool {
+        path.strip_prefix("/")
+            .is_ok_and(|rel| rel == Path::new(RENAME_JOURNAL_DIR))
+    }
+
+    async fn journaled_rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let _permit = self.request_permit().await;
+        let from_key = self.path_to_key(from);
+        let to_key = self.new_key(to)?;
+        debug!("rename: {} -> {}", from_key, to_key);
+
+        let (sources, from_prefix, to_prefix, replaced) = match self.head_source(&from_key).await? {
+            Some(source) => {
+                let replaced = self.head_source(&to_key).await?.map(|o| o.key);
+                (vec![source], from_key, to_key, Vec::from_iter(replaced))
+            }
+            None => {
+                let from_dir = format!("{}/", from_key);
+                let to_dir = format!("{}/", to_key);
+                let sources = self.list_sources(&from_dir).await?;
+                if sources.is_empty() {
+                    return Err(FuseAdapterError::NotFound(format!(
+                        "Path not found: {:?}",
+                        from
+                    )));
+                }
+                if to_dir.starts_with(&from_dir) {
+                    return Err(FuseAdapterError::InvalidArgument(format!(
+                        "Can't move {:?} into itself",
+                        from
+                    )));
+                }
+                // Only an empty directory (at most a marker) can be replaced
+                let existing = self.list_sources(&to_dir).await?;
+                if existing.iter().any(|o| o.key != to_dir) {
+                    return Err(FuseAdapterError::NotEmpty(format!(
+                        "Directory not empty: {:?}",
+                        to
+                    )));
+                }
+                let replaced = existing.into_iter().map(|o| o.key).collect();
+                (sources, from_dir, to_dir, replaced)
+            }
+        };
+        if let Some(large) = sources.iter().find(|o| o.size > MAX_COPY_OBJECT_SIZE) {
+            return Err(FuseAdapterError::NotSupported(format!(
+                "Can't rename {:?}: objects over 5 GiB can't be copied in one request",
+                large.key
+            )));
+        }
+
+        let journal_key = format!(
+            "{}{}-{}",
+            self.journal_prefix(),
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            std::process::id()
+        );
+        let mut journal = RenameJournal {
+            phase: RenamePhase::Copying,
+            keys: plan_rename(&from_prefix, &to_prefix, &sources)?,
+            replaced,
+        };
+        self.put_journal(&journal_key, &journal).await?;
+
+        if let Err(e) = self.copy_all(&journal.keys).await {
+            warn!(
+                "rename {:?} -> {:?} failed, removing copies: {}",
+                from, to, e
+            );
+            match self.delete_keys(&journal.copies()).await {
+                Ok(()) => {
+                    let _ = self.delete_keys(&[journal_key]).await;
+                }
+                // The journal stays so the next start can retry
+                Err(e) => warn!("Failed to remove copies of {:?}: {}", from, e),
+            }
+            return Err(e);
+        }
+
+        journal.phase = RenamePhase::Deleting;
+        self.put_journal(&journal_key, &journal).await?;
+        self.delete_keys(&journal.sources()).await?;
+        self.delete_keys(&[journal_key]).await
+    }
+
+    /// The object at `key`, if there is one
+    async fn head_source(&self, key: &str) -> Result<Option<RenameSource>> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(RenameSource {
+                key: key.to_string(),
+                size: output.content_length().unwrap_or(0) as u64,
+                etag: output.e_tag().map(str::to_string),
+            })),
+            Err(e) => {
+                if e.as_service_error().is_some_and(|s| s.is_not_found()) {
+                    Ok(None)
+                } else {
+                    Err(s3_error("HeadObject", e))
+                }
+            }
+        }
+    }
+
+    /// Every object beneath `prefix`
+    async fn list_sources(&self, prefix: &str) -> Result<Vec<RenameSource>> {
+        let mut sources = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let result = self
+                .client
+                .list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await
+                .map_err(|e| s3_error(&format!("ListObjectsV2 for prefix '{}'", prefix), e))?;
+
+            sources.extend(result.contents().iter().filter_map(|obj| {
+                Some(RenameSource {
+                    key: obj.key()?.to_string(),
+                    size: obj.size().unwrap_or(0) as u64,
+                    etag: obj.e_tag().map(str::to_string),
+                })
+            }));
+
+            if result.is_truncated().unwrap_or(false) {
+                continuation_token = result.next_continuation_token().map(|s| s.to_string());
+            } else {
+                break;
+            }
+        }
+
+        Ok(sources)
+    }
+
+    async fn put_journal(&self, key: &str, journal: &RenameJournal) -> Result<()> {
+        let body = serde_json::to_vec(journal)
+            .map_err(|e| FuseAdapterError::Backend(format!("Failed to encode journal: {}", e)))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type("application/json")
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| s3_error("PutObject", e))?;
+        Ok(())
+    }
+
+    /// Copy each source key to its destination and check the copy
+    async fn copy_all(&self, keys: &[(String, String)]) -> Result<()> {
+        futures::stream::iter(keys.iter().cloned())
+            .map(|(src, dest)| async move { self.copy_verified(&src, &dest).await })
+            .buffer_unordered(RENAME_CONCURRENCY)
+            .try_collect::<Vec<()>>()
+            .await?;
+        Ok(())
+    }
+
+    async fn copy_verified(&self, src: &str, dest: &str) -> Result<()> {
+        let source = self.head_source(src).await?.ok_or_else(|| {
+            FuseAdapterError::NotFound(format!("Object {:?} disappeared during rename", src))
+        })?;
+
+        // Metadata (mode, symlink target, content hash) is copied along
+        let copy_source = format!(
+            "{}/{}",
+            self.bucket,
+            utf8_percent_encode(src, COPY_SOURCE_ESCAPE)
+        );
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .key(dest)
+            .copy_source(copy_source)
+            .send()
+            .await
+            .map_err(|e| s3_error("CopyObject", e))?;
+
+        let copy = self.head_source(dest).await?;
+        let matches = copy.is_some_and(|copy| {
+            copy.size == source.size
+                && match (&source.etag, &copy.etag) {
+                    (Some(a), Some(b)) if is_md5_etag(a) => a == b,
+                    _ => true,
+                }
+        });
+        if !matches {
+            return Err(FuseAdapterError::IntegrityMismatch(format!(
+                "Copy of {:?} to {:?} doesn't match the source",
+                src, dest
+            )));
+        }
+        Ok(())
+    }
+
+    /// Delete `keys`, batched into DeleteObjects requests
+    async fn delete_keys(&self, keys: &[String]) -> Result<()> {
+        for batch in keys.chunks(MAX_DELETE_BATCH) {
+            let objects = batch
+                .iter()
+                .map(|k| {
+                    aws_sdk_s3::types::ObjectIdentifier::builder()
+                        .key(k)
+                        .build()
+                        .map_err(|e| {
+                            FuseAdapterError::Backend(format!("Failed to build delete: {}", e))
+                        })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let delete = aws_sdk_s3::types::Delete::builder()
+                .set_objects(Some(objects))
+                .quiet(true)
+                .build()
+                .map_err(|e| FuseAdapterError::Backend(format!("Failed to build delete: {}", e)))?;
+
+            let output = self
+                .client
+                .delete_objects()
+                .bucket(&self.bucket)
+                .delete(delete)
+                .send()
+                .await
+                .map_err(|e| s3_error("DeleteObjects", e))?;
+            if let Some(error) = output.errors().first() {
+                return Err(FuseAdapterError::Backend(format!(
+                    "S3 DeleteObjects error for {:?}: {}",
+                    error.key().unwrap_or_default(),
+                    error.message().unwrap_or_default()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the bucket can be listed under the prefix, creating the
+    /// prefix's `marker` object if asked to and nothing is there yet
+    async fn check_prefix(&self, marker: &str, create: bool) -> Result<()> {
+        let inaccessible = |e: FuseAdapterError| {
+            FuseAdapterError::Backend(format!(
+                "bucket '{}' is not accessible under prefix '{}': {}",
+                self.bucket, marker, e
+            ))
+        };
+
+        let listing = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(marker)
+            .max_keys(1)
+            .send()
+            .await
+            .map_err(|e| inaccessible(s3_error("ListObjectsV2", e)))?;
+        if listing.key_count().unwrap_or(0) > 0 {
+            return Ok(());
+        }
+
+        if !create {
+            warn!(
+                "Prefix '{}' in bucket '{}' is empty; set create_prefix to create it",
+                marker, self.bucket
+            );
+            return Ok(());
+        }
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(marker)
+            .body(ByteStream::from(Vec::new()))
+            .send()
+            .await
+            .map_err(|e| inaccessible(s3_error("PutObject", e)))?;
+        info!("Created prefix '{}' in bucket '{}'", marker, self.bucket);
+        Ok(())
+    }
+
+    /// Undo or finish every rename left in the journal
+    async fn recover_renames(&self) {
+        let prefix = self.journal_prefix();
+        let entries = match self.list_sources(&prefix).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to list rename journal {:?}: {}", prefix, e);
+                return;
+            }
+        };
+        for entry in entries {
+            if let Err(e) = self.recover_rename(&entry.key).await {
+                warn!("Failed to recover rename {:?}: {}", entry.key, e);
+            }
+        }
+    }
+
+    async fn recover_rename(&self, journal_key: &str) -> Result<()> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(journal_key)
+            .send()
+            .await
+            .map_err(|e| s3_error("GetObject", e))?;
+        let body = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| FuseAdapterError::Backend(format!("S3 read body error: {}", e)))?;
+        let journal: RenameJournal = serde_json::from_slice(&body.into_bytes())
+            .map_err(|e| FuseAdapterError::Backend(format!("Corrupt rename journal: {}", e)))?;
+
+        let keys = match journal.phase {
+            RenamePhase::Copying => journal.copies(),
+            RenamePhase::Deleting => journal.sources(),
+        };
+        info!(
+            "Recovering interrupted rename {:?}: {} {} keys",
+            journal_key,
+            match journal.phase {
+                RenamePhase::Copying => "removing copies of",
+                RenamePhase::Deleting => "removing sources of",
+            },
+            keys.len()
+        );
+        self.delete_keys(&keys).await?;
+        self.delete_keys(&[journal_key.to_string()]).await
+    }
+}
+
+/// A part of a partial upload
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UploadPart {
+    /// Copy this range from the existing object
+    Copy(Range<u64>),
+    /// Upload this range of the new content
+    Upload(Range<u64>),
+}
+
+/// Split a file of `len` bytes into multipart upload parts, copying the
+/// ranges that are unchanged from the existing object
+///
+/// Returns None if nothing could be copied, so a partial upload wouldn't
+/// save anything over a full one.
+fn plan_partial_upload(len: u64, dirty: &DirtyRanges) -> Option<Vec<UploadPart>> {
+    let part_size = DIRTY_PART_SIZE.max(len.div_ceil(MAX_PARTS));
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < len {
+        let range = start..(start + part_size).min(len);
+        start = range.end;
+        if range.end <= dirty.base_size && !dirty.intersects(&range) {
+            parts.push(UploadPart::Copy(range));
+        } else {
+            parts.push(UploadPart::Upload(range));
+        }
+    }
+    parts
+        .iter()
+        .any(|p| matches!(p, UploadPart::Copy(_)))
+        .then_some(parts)
+}
+
+/// The best checksum covering an object's full content, from a HeadObject
+///
+/// Prefers the SHA-256 fuse-adapter stores in metadata, then a full-object
+/// CRC32C, then the ETag when it is a plain MD5 (single-part uploads without
+/// KMS or customer-key encryption).
+fn head_content_hash(head: &HeadObjectOutput) -> Option<ContentHash> {
+    if let Some(hash) = head.metadata().and_then(|m| m.get(S3_SHA256_METADATA_KEY)) {
+        return Some(ContentHash::Sha256(hash.to_ascii_lowercase()));
+    }
+    let composite = matches!(head.checksum_type(), Some(ChecksumType::Composite));
+    if let Some(crc) = head.checksum_crc32_c().filter(|_| !composite) {
+        if let Ok(bytes) = STANDARD.decode(crc) {
+            if let Ok(bytes) = <[u8; 4]>::try_from(bytes.as_slice()) {
+                return Some(ContentHash::Crc32c(u32::from_be_bytes(bytes)));
+            }
+        }
+    }
+    let encrypted = head.sse_customer_algorithm().is_some()
+        || matches!(
+            head.server_side_encryption(),
+            Some(ServerSideEncryption::AwsKms | ServerSideEncryption::AwsKmsDsse)
+        );
+    let etag = head.e_tag()?.trim_matches('"');
+    if encrypted || etag.len() != 32 || !etag.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(ContentHash::Md5(etag.to_ascii_lowercase()))
+}
+
+impl S3Connector {
+    /// Upload the parts of a partial upload and complete it
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        etag: &str,
+        data: &[u8],
+        parts: Vec<UploadPart>,
+    ) -> Result<bool> {
+        let copy_source = format!(
+            "{}/{}",
+            self.bucket,
+            utf8_percent_encode(key, COPY_SOURCE_ESCAPE)
+        );
+        let mut completed = Vec::with_capacity(parts.len());
+        for (index, part) in parts.into_iter().enumerate() {
+            let part_number = index as i32 + 1;
+            let etag = match part {
+                UploadPart::Copy(range) => {
+                    // The source must still be the object the changes were made against
+                    let result = self
+                        .client
+                        .upload_part_copy()
+                        .bucket(&self.bucket)
+                        .key(key)
+                        .upload_id(upload_id)
+                        .part_number(part_number)
+                        .copy_source(&copy_source)
+                        .copy_source_range(format!("bytes={}-{}", range.start, range.end - 1))
+                        .copy_source_if_match(etag)
+                        .send()
+                        .await;
+                    match result {
+                        Ok(output) => output.copy_part_result.and_then(|r| r.e_tag),
+                        Err(e) => {
+                            debug!(
+                                "write_dirty: part copy failed for key={}, uploading in full: {}",
+                                key,
+                                e.into_service_error()
+                            );
+                            return Ok(false);
+                        }
+                    }
+                }
+                UploadPart::Upload(range) => {
+                    let body = data[range.start as usize..range.end as usize].to_vec();
+                    self.client
+                        .upload_part()
+                        .bucket(&self.bucket)
+                        .key(key)
+                        .upload_id(upload_id)
+                        .part_number(part_number)
+                        .body(ByteStream::from(body))
+                        .send()
+                        .await
+                        .map_err(|e| s3_error("UploadPart", e))?
+                        .e_tag
+                }
+            };
+            completed.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(etag)
+                    .build(),
+            );
+        }
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .upload_id(upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(completed))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| s3_error("CompleteMultipartUpload", e))?;
+        Ok(true)
+    }
+}
+
 #[async_trait]
 impl Connector for S3Connector {
     fn capabilities(&self) -> Capabilities {
         Capabilities {
             read: true,
-            write: true,
+            write: self.as_of.is_none(),
             range_read: true,
             random_write: false, // S3 doesn't support partial writes
-            rename: false,       // S3 has no native rename
-            truncate: false,     // Can't truncate in S3
+            // S3 has no native rename, but it can be journaled copy + delete
+            rename: self.atomic_rename && self.as_of.is_none(),
+            truncate: false, // Can't truncate in S3
             set_mtime: false,
             seekable: false, // Range requests work but aren't cheap
             set_mode: true,  // Stored in S3 user metadata
@@ -140,13 +1300,31 @@ impl Connector for S3Connector {
         }
     }
 
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.pacer.as_ref().map(|pacer| pacer.stats())
+    }
+
     async fn stat(&self, path: &Path) -> Result<Metadata> {
+        if let Some(as_of) = self.as_of {
+            return self.snapshot_stat(path, as_of).await;
+        }
+        if Self::is_journal_path(path) {
+            return Err(FuseAdapterError::NotFound(format!(
+                "Path not found: {:?}",
+                path
+            )));
+        }
+        let _permit = self.request_permit().await;
         let key = self.path_to_key(path);
         trace!("stat: path={:?} key={}", path, key);
 
         // Root directory always exists
         if key.is_empty() || key == self.prefix {
-            return Ok(Metadata::directory(SystemTime::now()));
+            let mut dir_key = key;
+            if !dir_key.is_empty() && !dir_key.ends_with('/') {
+                dir_key.push('/');
+            }
+            return Ok(Metadata::directory(self.dir_mtime(path, &dir_key).await?));
         }
 
         // First try as a file (HeadObject)
@@ -213,21 +1391,19 @@ impl Connector for S3Connector {
                     .and_then(|m| m.get(S3_MODE_METADATA_KEY))
                     .and_then(|v| u32::from_str_radix(v, 8).ok());
 
+                let etag = output.e_tag().map(str::to_string);
                 return Ok(if let Some(mode) = mode {
                     Metadata::file_with_mode(size, mtime, mode)
                 } else {
                     Metadata::file(size, mtime)
-                });
+                }
+                .with_etag(etag));
             }
             Err(e) => {
                 // Check if it's a "not found" error
-                let service_error = e.into_service_error();
-                if !service_error.is_not_found() {
+                if !e.as_service_error().is_some_and(|s| s.is_not_found()) {
                     // Some other error
-                    return Err(FuseAdapterError::Backend(format!(
-                        "S3 HeadObject error: {}",
-                        service_error
-                    )));
+                    return Err(s3_error("HeadObject", e));
                 }
             }
         }
@@ -247,21 +1423,11 @@ impl Connector for S3Connector {
             .max_keys(1)
             .send()
             .await
-            .map_err(|e| {
-                let service_error = e.into_service_error();
-                FuseAdapterError::Backend(format!(
-                    "S3 ListObjectsV2 error for prefix '{}': {:?}",
-                    dir_key, service_error
-                ))
-            })?;
-
-        if list_result.key_count().unwrap_or(0) > 0 {
-            return Ok(Metadata::directory(SystemTime::now()));
-        }
+            .map_err(|e| s3_error(&format!("ListObjectsV2 for prefix '{}'", dir_key), e))?;
 
-        // Also check for common prefixes
-        if !list_result.common_prefixes().is_empty() {
-            return Ok(Metadata::directory(SystemTime::now()));
+        // Any object under the prefix makes it a directory
+        if list_result.key_count().unwrap_or(0) > 0 || !list_result.common_prefixes().is_empty() {
+            return Ok(Metadata::directory(self.dir_mtime(path, &dir_key).await?));
         }
 
         Err(FuseAdapterError::NotFound(format!(
@@ -279,6 +1445,13 @@ impl Connector for S3Connector {
     }
 
     async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        if let Some(as_of) = self.as_of {
+            let object = self.snapshot_object(path, as_of).await?;
+            return self
+                .read_version(path, &object.version_id, offset, size)
+                .await;
+        }
+        let _permit = self.request_permit().await;
         let key = self.path_to_key(path);
         trace!(
             "read: path={:?} key={} offset={} size={}",
@@ -299,11 +1472,10 @@ impl Connector for S3Connector {
             .send()
             .await
             .map_err(|e| {
-                let service_error = e.into_service_error();
-                if service_error.is_no_such_key() {
+                if e.as_service_error().is_some_and(|s| s.is_no_such_key()) {
                     FuseAdapterError::NotFound(format!("File not found: {:?}", path))
                 } else {
-                    FuseAdapterError::Backend(format!("S3 GetObject error: {}", service_error))
+                    s3_error("GetObject", e)
                 }
             })?;
 
@@ -317,6 +1489,8 @@ impl Connector for S3Connector {
     }
 
     async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
         // S3 doesn't support partial writes, so this requires the cache layer
         // to buffer the entire file and upload on flush.
         //
@@ -327,23 +1501,97 @@ impl Connector for S3Connector {
             ));
         }
 
-        let key = self.path_to_key(path);
+        let key = self.new_key(path)?;
         debug!("write: path={:?} key={} size={}", path, key, data.len());
 
+        // Store a content hash so readers can verify the object end to end,
+        // and have S3 check a CRC32C of the body before accepting it
         self.client
             .put_object()
             .bucket(&self.bucket)
             .key(&key)
+            .metadata(S3_SHA256_METADATA_KEY, crate::integrity::sha256_hex(data))
+            .checksum_algorithm(ChecksumAlgorithm::Crc32C)
             .body(ByteStream::from(data.to_vec()))
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 PutObject error: {}", e)))?;
+            .map_err(|e| s3_error("PutObject", e))?;
 
+        self.forget_dir_mtimes(path);
         Ok(data.len() as u64)
     }
 
-    async fn create_file(&self, path: &Path) -> Result<()> {
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        self.check_writable()?;
+        let Some(parts) = plan_partial_upload(data.len() as u64, dirty) else {
+            return Ok(false);
+        };
+        let _permit = self.request_permit().await;
         let key = self.path_to_key(path);
+
+        // Only patch the object the changes were made against
+        let head = match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .send()
+            .await
+        {
+            Ok(head) => head,
+            Err(_) => return Ok(false),
+        };
+        let (Some(etag), Some(size)) = (head.e_tag(), head.content_length()) else {
+            return Ok(false);
+        };
+        if size as u64 != dirty.base_size {
+            debug!(
+                "write_dirty: key={} changed size since cached ({} != {}), uploading in full",
+                key, size, dirty.base_size
+            );
+            return Ok(false);
+        }
+
+        debug!(
+            "write_dirty: path={:?} key={} size={} dirty={} parts={}",
+            path,
+            key,
+            data.len(),
+            dirty.len(),
+            parts.len()
+        );
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&key)
+            .metadata(S3_SHA256_METADATA_KEY, crate::integrity::sha256_hex(data))
+            .send()
+            .await
+            .map_err(|e| s3_error("CreateMultipartUpload", e))?;
+        let upload_id = upload.upload_id().ok_or_else(|| {
+            FuseAdapterError::Backend("S3 CreateMultipartUpload returned no upload id".to_string())
+        })?;
+
+        let result = self.upload_parts(&key, upload_id, etag, data, parts).await;
+        if !matches!(result, Ok(true)) {
+            let _ = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket)
+                .key(&key)
+                .upload_id(upload_id)
+                .send()
+                .await;
+        }
+        self.forget_dir_mtimes(path);
+        result
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
+        let key = self.new_key(path)?;
         debug!("create_file: path={:?} key={}", path, key);
 
         // Create empty file
@@ -354,21 +1602,29 @@ impl Connector for S3Connector {
             .body(ByteStream::from(Vec::new()))
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 PutObject error: {}", e)))?;
+            .map_err(|e| s3_error("PutObject", e))?;
 
+        self.forget_dir_mtimes(path);
         Ok(())
     }
 
     async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
         // Directories in S3 are virtual - they exist if there are objects
         // with that prefix. We can create a placeholder object.
-        let mut key = self.path_to_key(path);
+        let mut key = self.new_key(path)?;
         if !key.ends_with('/') {
             key.push('/');
         }
 
         debug!("create_dir: path={:?} key={}", path, key);
 
+        // Without a marker the directory appears once something is put in it
+        if !self.directory_markers {
+            return Ok(());
+        }
+
         // Create a zero-byte object with trailing slash to represent directory
         self.client
             .put_object()
@@ -377,12 +1633,15 @@ impl Connector for S3Connector {
             .body(ByteStream::from(Vec::new()))
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 PutObject error: {}", e)))?;
+            .map_err(|e| s3_error("PutObject", e))?;
 
+        self.forget_dir_mtimes(path);
         Ok(())
     }
 
     async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
         let key = self.path_to_key(path);
         debug!("remove_file: path={:?} key={}", path, key);
 
@@ -392,12 +1651,15 @@ impl Connector for S3Connector {
             .key(&key)
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 DeleteObject error: {}", e)))?;
+            .map_err(|e| s3_error("DeleteObject", e))?;
 
+        self.forget_dir_mtimes(path);
         Ok(())
     }
 
     async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
         let mut key = self.path_to_key(path);
         if !key.ends_with('/') {
             key.push('/');
@@ -418,13 +1680,7 @@ impl Connector for S3Connector {
                 .max_keys(2) // 1 for dir placeholder + 1 for any content
                 .send()
                 .await
-                .map_err(|e| {
-                    let service_error = e.into_service_error();
-                    FuseAdapterError::Backend(format!(
-                        "S3 ListObjectsV2 remove_dir check error: {:?}",
-                        service_error
-                    ))
-                })?;
+                .map_err(|e| s3_error("ListObjectsV2 remove_dir check", e))?;
 
             let contents = list_result.contents();
             let non_dir_objects: Vec<_> = contents
@@ -454,13 +1710,10 @@ impl Connector for S3Connector {
                 request = request.continuation_token(token);
             }
 
-            let list_result = request.send().await.map_err(|e| {
-                let service_error = e.into_service_error();
-                FuseAdapterError::Backend(format!(
-                    "S3 ListObjectsV2 remove_dir delete error: {:?}",
-                    service_error
-                ))
-            })?;
+            let list_result = request
+                .send()
+                .await
+                .map_err(|e| s3_error("ListObjectsV2 remove_dir delete", e))?;
 
             let contents = list_result.contents();
 
@@ -490,9 +1743,7 @@ impl Connector for S3Connector {
                     .delete(delete)
                     .send()
                     .await
-                    .map_err(|e| {
-                        FuseAdapterError::Backend(format!("S3 DeleteObjects error: {}", e))
-                    })?;
+                    .map_err(|e| s3_error("DeleteObjects", e))?;
             }
 
             if list_result.is_truncated().unwrap_or(false) {
@@ -502,10 +1753,38 @@ impl Connector for S3Connector {
             }
         }
 
+        // Some stores mark directories with a `name` object of type
+        // application/x-directory instead, which would keep it alive
+        let marker_key = key.trim_end_matches('/');
+        if !marker_key.is_empty() && marker_key != self.prefix.trim_end_matches('/') {
+            let head = self
+                .client
+                .head_object()
+                .bucket(&self.bucket)
+                .key(marker_key)
+                .send()
+                .await;
+            if let Ok(output) = head {
+                if output.content_type() == Some("application/x-directory") {
+                    self.client
+                        .delete_object()
+                        .bucket(&self.bucket)
+                        .key(marker_key)
+                        .send()
+                        .await
+                        .map_err(|e| s3_error("DeleteObject", e))?;
+                }
+            }
+        }
+
+        self.forget_dir_mtimes(path);
         Ok(())
     }
 
     fn list_dir(&self, path: &Path) -> DirEntryStream {
+        if let Some(as_of) = self.as_of {
+            return self.snapshot_list_dir(path, as_of);
+        }
         let mut prefix = self.path_to_key(path);
         if !prefix.is_empty() && !prefix.ends_with('/') {
             prefix.push('/');
@@ -513,31 +1792,47 @@ impl Connector for S3Connector {
 
         let client = self.client.clone();
         let bucket = self.bucket.clone();
-
+        let keys = self.keys.clone();
+        let list_metadata = self.list_metadata;
+        let max_keys = self.max_keys;
+        let requests = self.requests.clone();
+        // A full listing sees every object the latest-child mtime is taken from
+        let dir_mtimes = (self.dir_mtime == S3DirMtime::LatestChild)
+            .then(|| (path.to_path_buf(), self.dir_mtimes.clone()));
+        let journal_prefix = self.journal_prefix();
+
+        // Pages are fetched as the consumer reaches them, so a huge prefix
+        // is never held in memory and the first entries arrive after one
+        // request
         Box::pin(try_stream! {
             let mut continuation_token: Option<String> = None;
+            let mut latest: Option<SystemTime> = None;
 
             loop {
                 let mut request = client
                     .list_objects_v2()
                     .bucket(&bucket)
                     .prefix(&prefix)
-                    .delimiter("/");
+                    .delimiter("/")
+                    .set_max_keys(max_keys);
 
                 if let Some(token) = continuation_token.take() {
                     request = request.continuation_token(token);
                 }
 
-                let result = request.send().await.map_err(|e| {
-                    let service_error = e.into_service_error();
-                    FuseAdapterError::Backend(format!(
-                        "S3 ListObjectsV2 list_dir error: {:?}",
-                        service_error
-                    ))
-                })?;
+                // One page at a time; the permit isn't held while the
+                // consumer works through the entries
+                let permit = match &requests {
+                    Some(semaphore) => semaphore.acquire().await.ok(),
+                    None => None,
+                };
+                let result = request.send().await;
+                drop(permit);
+                let result = result.map_err(|e| s3_error("ListObjectsV2 list_dir", e))?;
 
                 // Yield files (objects that aren't the directory marker)
                 for obj in result.contents() {
+                    latest = latest.max(obj.last_modified().map(to_system_time));
                     if let Some(key) = obj.key() {
                         // Skip directory marker objects
                         if key.ends_with('/') || key == prefix {
@@ -550,18 +1845,42 @@ impl Connector for S3Connector {
                             continue;
                         }
 
-                        yield DirEntry::file(rel_key.to_string());
+                        let Some(name) = keys.decode_name(rel_key) else {
+                            trace!("list_dir: skipping key {:?}", key);
+                            continue;
+                        };
+                        let entry = DirEntry::file(name);
+                        if list_metadata {
+                            // LIST carries no user metadata, so mode and the
+                            // symlink marker are unknown here
+                            let mtime = obj
+                                .last_modified()
+                                .and_then(|dt| {
+                                    SystemTime::UNIX_EPOCH
+                                        .checked_add(Duration::from_secs(dt.secs() as u64))
+                                })
+                                .unwrap_or(SystemTime::now());
+                            let size = obj.size().unwrap_or(0) as u64;
+                            let etag = obj.e_tag().map(str::to_string);
+                            yield entry.with_metadata(Metadata::file(size, mtime).with_etag(etag));
+                        } else {
+                            yield entry;
+                        }
                     }
                 }
 
                 // Yield directories (common prefixes)
                 for common_prefix in result.common_prefixes() {
                     if let Some(p) = common_prefix.prefix() {
+                        if p == journal_prefix {
+                            continue;
+                        }
                         // Extract directory name
                         let rel_prefix = p.strip_prefix(&prefix).unwrap_or(p);
-                        let name = rel_prefix.trim_end_matches('/');
-                        if !name.is_empty() {
-                            yield DirEntry::directory(name.to_string());
+                        let component = rel_prefix.trim_end_matches('/');
+                        match keys.decode_name(component) {
+                            Some(name) => yield DirEntry::directory(name),
+                            None => trace!("list_dir: skipping prefix {:?}", p),
                         }
                     }
                 }
@@ -572,15 +1891,27 @@ impl Connector for S3Connector {
                     break;
                 }
             }
+
+            if let Some((path, dir_mtimes)) = dir_mtimes {
+                let mtime = latest.unwrap_or(SystemTime::UNIX_EPOCH);
+                dir_mtimes.insert(path, (mtime, Instant::now()));
+            }
         })
     }
 
-    async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
-        // S3 doesn't have native rename - this should be handled by the cache
-        // layer or synthesized via copy+delete
-        Err(FuseAdapterError::NotSupported(
-            "S3 doesn't support native rename".to_string(),
-        ))
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        // S3 doesn't have native rename - unless atomic_rename is set this
+        // should be handled by the cache layer
+        if !self.atomic_rename {
+            return Err(FuseAdapterError::NotSupported(
+                "S3 doesn't support native rename".to_string(),
+            ));
+        }
+        self.check_writable()?;
+        self.journaled_rename(from, to).await?;
+        self.forget_dir_mtimes(from);
+        self.forget_dir_mtimes(to);
+        Ok(())
     }
 
     async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
@@ -596,7 +1927,9 @@ impl Connector for S3Connector {
     }
 
     async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
-        let key = self.path_to_key(path);
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
+        let key = self.new_key(path)?;
         debug!(
             "create_file_with_mode: path={:?} key={} mode={:o}",
             path, key, mode
@@ -610,13 +1943,16 @@ impl Connector for S3Connector {
             .set_metadata(Some(Self::mode_to_metadata(mode)))
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 PutObject error: {}", e)))?;
+            .map_err(|e| s3_error("PutObject", e))?;
 
+        self.forget_dir_mtimes(path);
         Ok(())
     }
 
     async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
-        let mut key = self.path_to_key(path);
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
+        let mut key = self.new_key(path)?;
         if !key.ends_with('/') {
             key.push('/');
         }
@@ -626,6 +1962,12 @@ impl Connector for S3Connector {
             path, key, mode
         );
 
+        // Without a marker the directory appears once something is put in
+        // it, and the mode (stored on the marker) is lost
+        if !self.directory_markers {
+            return Ok(());
+        }
+
         self.client
             .put_object()
             .bucket(&self.bucket)
@@ -634,14 +1976,19 @@ impl Connector for S3Connector {
             .set_metadata(Some(Self::mode_to_metadata(mode)))
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 PutObject error: {}", e)))?;
+            .map_err(|e| s3_error("PutObject", e))?;
 
+        self.forget_dir_mtimes(path);
         Ok(())
     }
 
     async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
         let key = self.path_to_key(path);
         debug!("set_mode: path={:?} key={} mode={:o}", path, key, mode);
+        // The copy moves the object's LastModified
+        self.forget_dir_mtimes(path);
 
         // S3 doesn't allow updating metadata in place, so we need to copy the object
         // to itself with new metadata.
@@ -665,9 +2012,7 @@ impl Connector for S3Connector {
             Ok(_) => Ok(()),
             Err(e) => {
                 // Check if this might be a directory (key with trailing slash)
-                let service_error = e.into_service_error();
-                use aws_sdk_s3::error::ProvideErrorMetadata;
-                let is_not_found = service_error
+                let is_not_found = e
                     .code()
                     .map(|c| c == "NoSuchKey" || c == "404" || c == "NotFound")
                     .unwrap_or(false);
@@ -687,22 +2032,22 @@ impl Connector for S3Connector {
                         .set_metadata(Some(Self::mode_to_metadata(mode)))
                         .send()
                         .await
-                        .map_err(|e| {
-                            FuseAdapterError::Backend(format!("S3 CopyObject error: {}", e))
-                        })?;
+                        .map_err(|e| s3_error("CopyObject", e))?;
 
                     Ok(())
                 } else {
-                    Err(FuseAdapterError::Backend(format!(
-                        "S3 CopyObject error: {}",
-                        service_error
-                    )))
+                    Err(s3_error("CopyObject", e))
                 }
             }
         }
     }
 
     async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        let version_id = match self.as_of {
+            Some(as_of) => Some(self.snapshot_object(path, as_of).await?.version_id),
+            None => None,
+        };
+        let _permit = self.request_permit().await;
         let key = self.path_to_key(path);
         debug!("readlink: path={:?} key={}", path, key);
 
@@ -711,14 +2056,14 @@ impl Connector for S3Connector {
             .head_object()
             .bucket(&self.bucket)
             .key(&key)
+            .set_version_id(version_id)
             .send()
             .await
             .map_err(|e| {
-                let service_error = e.into_service_error();
-                if service_error.is_not_found() {
+                if e.as_service_error().is_some_and(|s| s.is_not_found()) {
                     FuseAdapterError::NotFound(format!("Symlink not found: {:?}", path))
                 } else {
-                    FuseAdapterError::Backend(format!("S3 HeadObject error: {}", service_error))
+                    s3_error("HeadObject", e)
                 }
             })?;
 
@@ -734,7 +2079,9 @@ impl Connector for S3Connector {
     }
 
     async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
-        let key = self.path_to_key(link_path);
+        self.check_writable()?;
+        let _permit = self.request_permit().await;
+        let key = self.new_key(link_path)?;
         let target_str = target.to_string_lossy().to_string();
         debug!(
             "symlink: target={:?} link_path={:?} key={}",
@@ -754,8 +2101,462 @@ impl Connector for S3Connector {
             .set_metadata(Some(metadata))
             .send()
             .await
-            .map_err(|e| FuseAdapterError::Backend(format!("S3 PutObject error: {}", e)))?;
+            .map_err(|e| s3_error("PutObject", e))?;
+
+        self.forget_dir_mtimes(link_path);
+        Ok(())
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        let _permit = self.request_permit().await;
+        let key = self.path_to_key(path);
+        debug!("list_versions: path={:?} key={}", path, key);
+
+        let mut versions = Vec::new();
+        let mut key_marker: Option<String> = None;
+        let mut version_id_marker: Option<String> = None;
+
+        loop {
+            let mut request = self
+                .client
+                .list_object_versions()
+                .bucket(&self.bucket)
+                .prefix(&key);
+
+            if let Some(marker) = key_marker.take() {
+                request = request.key_marker(marker);
+            }
+            if let Some(marker) = version_id_marker.take() {
+                request = request.version_id_marker(marker);
+            }
+
+            let result = request
+                .send()
+                .await
+                .map_err(|e| s3_error("ListObjectVersions", e))?;
+
+            // The prefix also matches longer keys, so only keep exact matches
+            for version in result.versions() {
+                if version.key() != Some(key.as_str()) {
+                    continue;
+                }
+                let Some(version_id) = version.version_id() else {
+                    continue;
+                };
+                // Unversioned buckets report a single "null" version
+                if version_id == "null" {
+                    continue;
+                }
+
+                let mtime = version
+                    .last_modified()
+                    .and_then(|dt| {
+                        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(dt.secs() as u64))
+                    })
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+
+                versions.push(FileVersion {
+                    version_id: version_id.to_string(),
+                    size: version.size().unwrap_or(0) as u64,
+                    mtime,
+                    is_latest: version.is_latest().unwrap_or(false),
+                });
+            }
+
+            if result.is_truncated().unwrap_or(false) {
+                key_marker = result.next_key_marker().map(|s| s.to_string());
+                version_id_marker = result.next_version_id_marker().map(|s| s.to_string());
+            } else {
+                break;
+            }
+        }
+
+        if versions.is_empty() {
+            return Err(FuseAdapterError::NotFound(format!(
+                "No versions found: {:?}",
+                path
+            )));
+        }
+
+        versions.sort_by_key(|v| std::cmp::Reverse(v.mtime));
+        Ok(versions)
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        let _permit = self.request_permit().await;
+        let key = self.path_to_key(path);
+        trace!(
+            "read_version: path={:?} key={} version={} offset={} size={}",
+            path,
+            key,
+            version_id,
+            offset,
+            size
+        );
+
+        if size == 0 {
+            return Ok(Bytes::new());
+        }
+
+        let range = format!("bytes={}-{}", offset, offset + size as u64 - 1);
+
+        let result = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .version_id(version_id)
+            .range(range)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.as_service_error().is_some_and(|s| s.is_no_such_key()) {
+                    FuseAdapterError::NotFound(format!(
+                        "Version {} not found: {:?}",
+                        version_id, path
+                    ))
+                } else {
+                    s3_error("GetObject", e)
+                }
+            })?;
+
+        let body = result
+            .body
+            .collect()
+            .await
+            .map_err(|e| FuseAdapterError::Backend(format!("S3 read body error: {}", e)))?;
+
+        Ok(body.into_bytes())
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        let version_id = match self.as_of {
+            Some(as_of) => Some(self.snapshot_object(path, as_of).await?.version_id),
+            None => None,
+        };
+        let _permit = self.request_permit().await;
+        let key = self.path_to_key(path);
+        trace!("content_hash: path={:?} key={}", path, key);
+
+        let output = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .set_version_id(version_id)
+            .set_if_match(etag.map(str::to_string))
+            .checksum_mode(ChecksumMode::Enabled)
+            .send()
+            .await;
+        let output = match output {
+            Ok(output) => output,
+            Err(e) if e.raw_response().is_some_and(|r| r.status().as_u16() == 412) => {
+                debug!("content_hash: {:?} changed since it was read", path);
+                return Ok(None);
+            }
+            Err(e) if e.as_service_error().is_some_and(|s| s.is_not_found()) => {
+                return Err(FuseAdapterError::NotFound(format!(
+                    "File not found: {:?}",
+                    path
+                )));
+            }
+            Err(e) => return Err(s3_error("HeadObject", e)),
+        };
+
+        Ok(head_content_hash(&output))
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        if self.as_of.is_some() {
+            // A snapshot never changes
+            return Ok(true);
+        }
+        let _permit = self.request_permit().await;
+        let key = self.path_to_key(path);
+        trace!("revalidate: path={:?} key={} etag={}", path, key, etag);
+
+        let result = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .if_none_match(etag)
+            .send()
+            .await;
+
+        match result {
+            // A 200 means the ETag no longer matches
+            Ok(_) => Ok(false),
+            Err(e) => {
+                if e.raw_response().is_some_and(|r| r.status().as_u16() == 304) {
+                    return Ok(true);
+                }
+                if e.as_service_error().is_some_and(|s| s.is_not_found()) {
+                    Ok(false)
+                } else {
+                    Err(s3_error("HeadObject", e))
+                }
+            }
+        }
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_writable()?;
+        let metadata = self.stat(from).await?;
+        if metadata.file_type == FileType::Directory {
+            return Err(FuseAdapterError::IsADirectory(from.display().to_string()));
+        }
+        if metadata.size > MAX_COPY_OBJECT_SIZE {
+            return copy_content(self, from, to).await;
+        }
+
+        let _permit = self.request_permit().await;
+        let from_key = self.path_to_key(from);
+        let to_key = self.new_key(to)?;
+        debug!("copy: {} -> {}", from_key, to_key);
+
+        // Metadata (mode, symlink target, content hash) is copied along
+        let copy_source = format!(
+            "{}/{}",
+            self.bucket,
+            utf8_percent_encode(&from_key, COPY_SOURCE_ESCAPE)
+        );
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .key(&to_key)
+            .copy_source(copy_source)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.code() == Some("NoSuchKey") {
+                    FuseAdapterError::NotFound(format!("File not found: {:?}", from))
+                } else {
+                    s3_error("CopyObject", e)
+                }
+            })?;
 
+        self.forget_dir_mtimes(to);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s3_errno(status: u16, code: &str) -> i32 {
+        let error = aws_sdk_s3::operation::get_object::GetObjectError::generic(
+            aws_sdk_s3::error::ErrorMetadata::builder()
+                .code(code)
+                .build(),
+        );
+        let response = HttpResponse::new(
+            status.try_into().unwrap(),
+            aws_sdk_s3::primitives::SdkBody::empty(),
+        );
+        s3_error("GetObject", SdkError::service_error(error, response)).to_errno()
+    }
+
+    #[test]
+    fn test_prefix_marker() {
+        assert_eq!(prefix_marker(""), None);
+        assert_eq!(prefix_marker("/"), None);
+        assert_eq!(prefix_marker("data"), Some("data/".to_string()));
+        assert_eq!(prefix_marker("team/data/"), Some("team/data/".to_string()));
+    }
+
+    #[test]
+    fn test_s3_error_errno() {
+        assert_eq!(s3_errno(403, "AccessDenied"), libc::EACCES);
+        assert_eq!(s3_errno(400, "ExpiredToken"), libc::EACCES);
+        assert_eq!(s3_errno(503, "SlowDown"), libc::EAGAIN);
+        assert_eq!(s3_errno(503, "ServiceUnavailable"), libc::EAGAIN);
+        assert_eq!(s3_errno(400, "RequestTimeout"), libc::ETIMEDOUT);
+        assert_eq!(s3_errno(400, "EntityTooLarge"), libc::EFBIG);
+        assert_eq!(s3_errno(404, "NoSuchKey"), libc::ENOENT);
+        assert_eq!(s3_errno(404, "NoSuchBucket"), libc::EIO);
+        assert_eq!(s3_errno(500, "InternalError"), libc::EIO);
+
+        let timeout = SdkError::<
+            aws_sdk_s3::operation::get_object::GetObjectError,
+            HttpResponse,
+        >::timeout_error("deadline");
+        assert_eq!(s3_error("GetObject", timeout).to_errno(), libc::ETIMEDOUT);
+    }
+
+    #[test]
+    fn test_plan_partial_upload() {
+        const MB: u64 = 1024 * 1024;
+        let mut dirty = DirtyRanges::new(20 * MB);
+        dirty.add(9 * MB..9 * MB + 1);
+
+        // One changed byte in the second 8 MiB part
+        let parts = plan_partial_upload(20 * MB, &dirty).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                UploadPart::Copy(0..8 * MB),
+                UploadPart::Upload(8 * MB..16 * MB),
+                UploadPart::Copy(16 * MB..20 * MB),
+            ]
+        );
+
+        // Growth past the old size has to be uploaded
+        let parts = plan_partial_upload(30 * MB, &dirty).unwrap();
+        assert_eq!(parts[2], UploadPart::Upload(16 * MB..24 * MB));
+
+        // Small files have nothing worth copying
+        let mut dirty = DirtyRanges::new(MB);
+        dirty.add(0..1);
+        assert!(plan_partial_upload(MB, &dirty).is_none());
+    }
+
+    fn version(id: &str, mtime: SystemTime) -> Option<SnapshotObject> {
+        Some(SnapshotObject {
+            version_id: id.to_string(),
+            size: 1,
+            mtime,
+            etag: None,
+        })
+    }
+
+    #[test]
+    fn test_snapshot_picks_latest_version_before_as_of() {
+        let t = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let mut snapshot = SnapshotBuilder::new(t(100));
+
+        snapshot.add("a", t(10), version("a1", t(10)));
+        snapshot.add("a", t(90), version("a2", t(90)));
+        snapshot.add("a", t(110), version("a3", t(110)));
+        // Deleted before the snapshot
+        snapshot.add("b", t(20), version("b1", t(20)));
+        snapshot.add("b", t(50), None);
+        // Deleted only after it
+        snapshot.add("c", t(150), None);
+        snapshot.add("c", t(30), version("c1", t(30)));
+        // Created after it
+        snapshot.add("d", t(200), version("d1", t(200)));
+
+        let objects = snapshot.build();
+        let ids: Vec<_> = objects
+            .iter()
+            .map(|(k, o)| (k.as_str(), o.version_id.as_str()))
+            .collect();
+        assert_eq!(ids, vec![("a", "a2"), ("c", "c1")]);
+    }
+
+    fn source(key: &str) -> RenameSource {
+        RenameSource {
+            key: key.to_string(),
+            size: 1,
+            etag: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_rename() {
+        let sources = [source("p/a/"), source("p/a/x"), source("p/a/sub/y")];
+        let keys = plan_rename("p/a/", "p/b/", &sources).unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                ("p/a/".to_string(), "p/b/".to_string()),
+                ("p/a/x".to_string(), "p/b/x".to_string()),
+                ("p/a/sub/y".to_string(), "p/b/sub/y".to_string()),
+            ]
+        );
+
+        // Destinations past S3's key limit fail before anything is copied
+        let long = format!("p/{}/", "d".repeat(MAX_KEY_LEN));
+        assert!(matches!(
+            plan_rename("p/a/", &long, &sources),
+            Err(FuseAdapterError::NameTooLong(_))
+        ));
+    }
+
+    #[test]
+    fn test_rename_journal_round_trip() {
+        let journal = RenameJournal {
+            phase: RenamePhase::Deleting,
+            keys: vec![("a".to_string(), "b".to_string())],
+            replaced: Vec::new(),
+        };
+        let encoded = serde_json::to_string(&journal).unwrap();
+        assert_eq!(encoded, r#"{"phase":"deleting","keys":[["a","b"]]}"#);
+        let decoded: RenameJournal = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded.phase, RenamePhase::Deleting);
+        assert_eq!(decoded.keys, journal.keys);
+    }
+
+    #[test]
+    fn test_rename_journal_keeps_replaced_destinations() {
+        let journal = RenameJournal {
+            phase: RenamePhase::Copying,
+            keys: vec![
+                ("a/".to_string(), "b/".to_string()),
+                ("a/x".to_string(), "b/x".to_string()),
+            ],
+            replaced: vec!["b/".to_string()],
+        };
+        assert_eq!(journal.copies(), vec!["b/x".to_string()]);
+        assert_eq!(journal.sources(), vec!["a/".to_string(), "a/x".to_string()]);
+    }
+
+    #[test]
+    fn test_is_journal_path() {
+        assert!(S3Connector::is_journal_path(Path::new(
+            "/.fuse-adapter-renames"
+        )));
+        assert!(!S3Connector::is_journal_path(Path::new(
+            "/a/.fuse-adapter-renames"
+        )));
+        assert!(!S3Connector::is_journal_path(Path::new("/")));
+    }
+
+    #[test]
+    fn test_head_content_hash() {
+        let etag = "\"5d41402abc4b2a76b9719d911017c592\"";
+        let head = HeadObjectOutput::builder().e_tag(etag).build();
+        assert_eq!(
+            head_content_hash(&head),
+            Some(ContentHash::Md5("5d41402abc4b2a76b9719d911017c592".into()))
+        );
+
+        // Multipart and KMS-encrypted ETags aren't MD5s of the content
+        let multipart = HeadObjectOutput::builder()
+            .e_tag("\"5d41402abc4b2a76b9719d911017c592-3\"")
+            .build();
+        assert_eq!(head_content_hash(&multipart), None);
+        let kms = HeadObjectOutput::builder()
+            .e_tag(etag)
+            .server_side_encryption(ServerSideEncryption::AwsKms)
+            .build();
+        assert_eq!(head_content_hash(&kms), None);
+
+        let crc = HeadObjectOutput::builder()
+            .e_tag(etag)
+            .checksum_crc32_c("4waSgw==")
+            .checksum_type(ChecksumType::FullObject)
+            .build();
+        assert_eq!(
+            head_content_hash(&crc),
+            Some(ContentHash::Crc32c(0xe306_9283))
+        );
+
+        let stored = HeadObjectOutput::builder()
+            .e_tag(etag)
+            .metadata(S3_SHA256_METADATA_KEY, "ABCD")
+            .build();
+        assert_eq!(
+            head_content_hash(&stored),
+            Some(ContentHash::Sha256("abcd".into()))
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! Bandwidth throttling decorator
+//!
+//! Wraps a backend connector and paces the bytes it uploads and downloads
+//! through token-bucket rate limiters. It sits below the cache layer, so
+//! only traffic that actually reaches the backend (cache fills and syncs)
+//! is throttled; reads served from cache are unaffected.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+use crate::cache::{parse_size, CacheControl, CacheStats, PendingSync};
+use crate::config::RateLimitConfig;
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, DirtyRanges, FileVersion, Metadata,
+};
+use crate::error::Result;
+use crate::integrity::ContentHash;
+
+/// Token bucket limiting throughput in bytes per second
+///
+/// A rate of 0 means unlimited. The bucket holds at most one second's worth
+/// of tokens; a transfer larger than that goes into debt, and later callers
+/// wait for it to be paid off, so the long-run rate holds either way.
+pub struct RateLimiter {
+    rate: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    /// Available bytes; negative while in debt
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `bytes_per_sec` (0 = unlimited)
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            rate: AtomicU64::new(bytes_per_sec),
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Current limit in bytes per second (0 = unlimited)
+    pub fn rate(&self) -> u64 {
+        self.rate.load(Ordering::Relaxed)
+    }
+
+    /// Change the limit; takes effect for the next transfer
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        self.rate.store(bytes_per_sec, Ordering::Relaxed);
+    }
+
+    /// Wait until `bytes` may be transferred
+    pub async fn acquire(&self, bytes: u64) {
+        let rate = self.rate();
+        if rate == 0 || bytes == 0 {
+            return;
+        }
+
+        let wait = {
+            let mut bucket = self.bucket.lock();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate as f64).min(rate as f64);
+            bucket.refilled_at = now;
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens < 0.0 {
+                Duration::from_secs_f64(-bucket.tokens / rate as f64)
+            } else {
+                Duration::ZERO
+            }
+        };
+
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Upload and download limiters for one scope (a mount, or all mounts)
+#[derive(Clone)]
+pub struct BandwidthLimits {
+    pub upload: Arc<RateLimiter>,
+    pub download: Arc<RateLimiter>,
+}
+
+impl BandwidthLimits {
+    /// Limits with no cap in either direction
+    pub fn unlimited() -> Self {
+        Self {
+            upload: Arc::new(RateLimiter::new(0)),
+            download: Arc::new(RateLimiter::new(0)),
+        }
+    }
+
+    /// Build limits from configuration (unset directions are unlimited)
+    pub fn from_config(config: Option<&RateLimitConfig>) -> Self {
+        let limits = Self::unlimited();
+        limits.update(config);
+        limits
+    }
+
+    /// Apply new configuration to the existing limiters
+    pub fn update(&self, config: Option<&RateLimitConfig>) {
+        let rate = |s: Option<&String>| s.and_then(|s| parse_size(s)).unwrap_or(0);
+        self.upload
+            .set_rate(rate(config.and_then(|c| c.upload.as_ref())));
+        self.download
+            .set_rate(rate(config.and_then(|c| c.download.as_ref())));
+    }
+}
+
+/// Connector decorator that throttles transfers to and from the backend
+///
+/// Every transfer must pass all of the configured limits, so a per-mount
+/// limit and a limit shared by all mounts can be combined.
+pub struct ThrottledConnector<C: Connector> {
+    inner: C,
+    limits: Vec<BandwidthLimits>,
+}
+
+impl<C: Connector> ThrottledConnector<C> {
+    /// Wrap a connector with the given limits
+    pub fn new(connector: C, limits: Vec<BandwidthLimits>) -> Self {
+        Self {
+            inner: connector,
+            limits,
+        }
+    }
+
+    async fn upload(&self, bytes: u64) {
+        for limits in &self.limits {
+            limits.upload.acquire(bytes).await;
+        }
+    }
+
+    async fn download(&self, bytes: u64) {
+        for limits in &self.limits {
+            limits.download.acquire(bytes).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<C: Connector> Connector for ThrottledConnector<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        let data = self.inner.read(path, offset, size).await?;
+        // Charged after the fact since the backend may return less than asked
+        self.download(data.len() as u64).await;
+        Ok(data)
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.upload(data.len() as u64).await;
+        self.inner.write(path, offset, data).await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        // Only the changed ranges (rounded out to parts) cross the network
+        self.upload(dirty.len()).await;
+        self.inner.write_dirty(path, data, dirty).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.inner.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.inner.remove_dir(path, recursive).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        self.inner.list_dir(path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.inner.truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        let data = self
+            .inner
+            .read_version(path, version_id, offset, size)
+            .await?;
+        self.download(data.len() as u64).await;
+        Ok(data)
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.inner.zero_range(path, offset, len).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_paces_transfers() {
+        let limiter = RateLimiter::new(1000);
+        let start = tokio::time::Instant::now();
+
+        // The first second's worth is available immediately
+        limiter.acquire(1000).await;
+        assert!(start.elapsed() < Duration::from_millis(10));
+
+        // The next 2000 bytes have to wait for the bucket to refill
+        limiter.acquire(2000).await;
+        assert!(start.elapsed() >= Duration::from_millis(1900));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unlimited_never_waits() {
+        let limits = BandwidthLimits::from_config(Some(&RateLimitConfig {
+            upload: Some("1KB".to_string()),
+            download: None,
+        }));
+        let start = tokio::time::Instant::now();
+
+        limits.download.acquire(u64::MAX).await;
+        assert!(start.elapsed() < Duration::from_millis(10));
+        assert_eq!(limits.upload.rate(), 1024);
+
+        limits.update(None);
+        limits.upload.acquire(u64::MAX).await;
+        assert!(start.elapsed() < Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,264 @@
+//! Tracing decorator
+//!
+//! Wraps a backend connector so each call into it runs in its own span,
+//! nested under the FUSE operation (or background sync) that made it. It
+//! sits below the cache layer, so only calls that actually reach the backend
+//! show up. Spans are only created while export is enabled; see
+//! `crate::telemetry`.
+
+use std::path::{Path, PathBuf};
+
+use async_stream::stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use tracing::Instrument;
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, DirtyRanges, FileVersion, Metadata,
+    SeekRegion,
+};
+use crate::error::Result;
+use crate::integrity::ContentHash;
+use crate::telemetry::connector_span;
+
+/// Connector decorator that records a span per backend call
+pub struct TracedConnector<C: Connector> {
+    inner: C,
+}
+
+impl<C: Connector> TracedConnector<C> {
+    pub fn new(connector: C) -> Self {
+        Self { inner: connector }
+    }
+}
+
+#[async_trait]
+impl<C: Connector> Connector for TracedConnector<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.inner
+            .stat(path)
+            .instrument(connector_span("stat", path))
+            .await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner
+            .exists(path)
+            .instrument(connector_span("exists", path))
+            .await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.inner
+            .read(path, offset, size)
+            .instrument(connector_span("read", path))
+            .await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.inner
+            .write(path, offset, data)
+            .instrument(connector_span("write", path))
+            .await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        self.inner
+            .write_dirty(path, data, dirty)
+            .instrument(connector_span("write_dirty", path))
+            .await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.inner
+            .create_file(path)
+            .instrument(connector_span("create_file", path))
+            .await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner
+            .create_dir(path)
+            .instrument(connector_span("create_dir", path))
+            .await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.inner
+            .remove_file(path)
+            .instrument(connector_span("remove_file", path))
+            .await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.inner
+            .remove_dir(path, recursive)
+            .instrument(connector_span("remove_dir", path))
+            .await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let span = connector_span("list_dir", path);
+        let mut entries = self.inner.list_dir(path);
+        Box::pin(stream! {
+            while let Some(entry) = entries.next().instrument(span.clone()).await {
+                yield entry;
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner
+            .rename(from, to)
+            .instrument(connector_span("rename", from))
+            .await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.inner
+            .truncate(path, size)
+            .instrument(connector_span("truncate", path))
+            .await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner
+            .flush(path)
+            .instrument(connector_span("flush", path))
+            .await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner
+            .create_file_with_mode(path, mode)
+            .instrument(connector_span("create_file", path))
+            .await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner
+            .create_dir_with_mode(path, mode)
+            .instrument(connector_span("create_dir", path))
+            .await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner
+            .set_mode(path, mode)
+            .instrument(connector_span("set_mode", path))
+            .await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner
+            .readlink(path)
+            .instrument(connector_span("readlink", path))
+            .await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.inner
+            .symlink(target, link_path)
+            .instrument(connector_span("symlink", link_path))
+            .await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner
+            .list_versions(path)
+            .instrument(connector_span("list_versions", path))
+            .await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .instrument(connector_span("read_version", path))
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner
+            .content_hash(path, etag)
+            .instrument(connector_span("content_hash", path))
+            .await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner
+            .revalidate(path, etag)
+            .instrument(connector_span("revalidate", path))
+            .await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner
+            .copy(from, to)
+            .instrument(connector_span("copy", from))
+            .await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.inner
+            .zero_range(path, offset, len)
+            .instrument(connector_span("zero_range", path))
+            .await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.inner
+            .seek_region(path, offset, region)
+            .instrument(connector_span("seek_region", path))
+            .await
+    }
+}
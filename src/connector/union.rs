@@ -0,0 +1,712 @@
+//! Union connector that overlays several backends
+//!
+//! The first layer is the writable upper layer; the remaining layers are
+//! read-only lower layers, searched in order. A path resolves to the
+//! topmost layer that has it, and directory listings merge all layers.
+//!
+//! Modifying a file that only exists in a lower layer first copies it up
+//! into the upper layer. Deleting something a lower layer provides leaves a
+//! whiteout marker (`.wh.<name>`) in the upper layer that hides it, and a
+//! directory recreated over a deleted lower directory gets an opaque marker
+//! (`.wh..wh..opq`) so the old lower contents stay hidden. The markers are
+//! never shown in listings.
+//!
+//! Renaming a directory that exists in a lower layer fails with EXDEV, like
+//! overlayfs; `mv` then falls back to copying and deleting.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use tracing::debug;
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileType, FileVersion, Metadata,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Prefix of the marker hiding a lower-layer entry
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// Marker making an upper directory hide all lower-layer contents
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+
+/// Path of the whiteout that hides `path`
+fn whiteout_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    let mut whiteout = OsString::from(WHITEOUT_PREFIX);
+    whiteout.push(name);
+    Some(path.with_file_name(whiteout))
+}
+
+/// Treat a missing path as `None` rather than an error
+fn found<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(FuseAdapterError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn not_found(path: &Path) -> FuseAdapterError {
+    FuseAdapterError::NotFound(path.to_string_lossy().to_string())
+}
+
+/// Connector layering a writable upper backend over read-only lower ones
+pub struct UnionConnector {
+    /// Upper layer first, then lower layers in lookup order
+    layers: Vec<Arc<dyn Connector>>,
+}
+
+impl UnionConnector {
+    /// Create a union of `layers`; the first is the writable upper layer
+    pub fn new(layers: Vec<Arc<dyn Connector>>) -> Result<Self> {
+        if layers.is_empty() {
+            return Err(FuseAdapterError::Config(
+                "union connector needs at least one layer".to_string(),
+            ));
+        }
+        Ok(Self { layers })
+    }
+
+    fn upper(&self) -> &Arc<dyn Connector> {
+        &self.layers[0]
+    }
+
+    fn lowers(&self) -> &[Arc<dyn Connector>] {
+        &self.layers[1..]
+    }
+
+    /// Whether the upper layer hides `path` in the lower layers
+    async fn hidden(&self, path: &Path) -> Result<bool> {
+        let (Some(parent), Some(whiteout)) = (path.parent(), whiteout_path(path)) else {
+            return Ok(false);
+        };
+        if self.upper().exists(&whiteout).await? {
+            return Ok(true);
+        }
+        self.upper().exists(&parent.join(OPAQUE_MARKER)).await
+    }
+
+    /// Find the layer providing `path` and its metadata there
+    async fn locate(&self, path: &Path) -> Result<Option<(usize, Metadata)>> {
+        if let Some(meta) = found(self.upper().stat(path).await)? {
+            return Ok(Some((0, meta)));
+        }
+        if self.hidden(path).await? {
+            return Ok(None);
+        }
+        for (i, layer) in self.lowers().iter().enumerate() {
+            if let Some(meta) = found(layer.stat(path).await)? {
+                return Ok(Some((i + 1, meta)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the layer providing `path`, failing if none does
+    async fn layer_for(&self, path: &Path) -> Result<&Arc<dyn Connector>> {
+        match self.locate(path).await? {
+            Some((i, _)) => Ok(&self.layers[i]),
+            None => Err(not_found(path)),
+        }
+    }
+
+    /// Whether any lower layer has `path`, visible or not
+    async fn in_lower(&self, path: &Path) -> Result<bool> {
+        for layer in self.lowers() {
+            if found(layer.stat(path).await)?.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Hide `path` from the lower layers if any of them has it
+    async fn whiteout_lower(&self, path: &Path) -> Result<()> {
+        if !self.in_lower(path).await? {
+            return Ok(());
+        }
+        let Some(whiteout) = whiteout_path(path) else {
+            return Ok(());
+        };
+        debug!("union: whiteout {:?}", path);
+        self.copy_up_parents(path).await?;
+        self.upper().create_file(&whiteout).await
+    }
+
+    /// Drop the whiteout for `path` before something is created there
+    async fn remove_whiteout(&self, path: &Path) -> Result<()> {
+        if let Some(whiteout) = whiteout_path(path) {
+            if self.upper().exists(&whiteout).await? {
+                self.upper().remove_file(&whiteout).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure every ancestor of `path` exists in the upper layer
+    async fn copy_up_parents(&self, path: &Path) -> Result<()> {
+        let ancestors: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .filter(|p| p.parent().is_some())
+            .collect();
+        for dir in ancestors.into_iter().rev() {
+            if found(self.upper().stat(dir).await)?.is_some() {
+                continue;
+            }
+            let mode = match self.locate(dir).await? {
+                Some((_, meta)) => meta.mode_or_default(),
+                None => return Err(not_found(dir)),
+            };
+            self.upper().create_dir_with_mode(dir, mode).await?;
+        }
+        Ok(())
+    }
+
+    /// Copy `path` from the lower layer providing it into the upper layer
+    async fn copy_up(&self, path: &Path) -> Result<()> {
+        let (layer, meta) = match self.locate(path).await? {
+            Some((0, _)) => return Ok(()),
+            Some((i, meta)) => (&self.layers[i], meta),
+            None => return Err(not_found(path)),
+        };
+        debug!("union: copy up {:?}", path);
+        self.copy_up_parents(path).await?;
+
+        match meta.file_type {
+            FileType::Directory => {
+                self.upper()
+                    .create_dir_with_mode(path, meta.mode_or_default())
+                    .await
+            }
+            FileType::Symlink => {
+                let target = layer.readlink(path).await?;
+                self.upper().symlink(&target, path).await
+            }
+            FileType::File => {
+                let data = if meta.size > 0 {
+                    layer.read(path, 0, meta.size as u32).await?
+                } else {
+                    Bytes::new()
+                };
+                self.upper()
+                    .create_file_with_mode(path, meta.mode_or_default())
+                    .await?;
+                if !data.is_empty() {
+                    self.upper().write(path, 0, &data).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Prepare the upper layer for creating something new at `path`
+    async fn prepare_create(&self, path: &Path) -> Result<()> {
+        if self.locate(path).await?.is_some() {
+            return Err(FuseAdapterError::AlreadyExists(
+                path.to_string_lossy().to_string(),
+            ));
+        }
+        self.remove_whiteout(path).await?;
+        self.copy_up_parents(path).await
+    }
+
+    /// Hide lower contents of a directory just created over a deleted one
+    async fn mark_opaque_if_shadowing(&self, path: &Path) -> Result<()> {
+        if self.in_lower(path).await? {
+            self.upper().create_file(&path.join(OPAQUE_MARKER)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Connector for UnionConnector {
+    fn capabilities(&self) -> Capabilities {
+        let mut caps = self.upper().capabilities();
+        caps.range_read = self.layers.iter().all(|l| l.capabilities().range_read);
+        caps
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.upper().cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.upper().cache_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.upper().cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.upper().pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        for layer in &self.layers {
+            layer.invalidate_caches();
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        match self.locate(path).await? {
+            Some((_, meta)) => Ok(meta),
+            None => Err(not_found(path)),
+        }
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        Ok(self.locate(path).await?.is_some())
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.layer_for(path).await?.read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        if self.locate(path).await?.is_some() {
+            self.copy_up(path).await?;
+        } else {
+            self.remove_whiteout(path).await?;
+            self.copy_up_parents(path).await?;
+        }
+        self.upper().write(path, offset, data).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.prepare_create(path).await?;
+        self.upper().create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.prepare_create(path).await?;
+        self.upper().create_dir(path).await?;
+        self.mark_opaque_if_shadowing(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        match self.locate(path).await? {
+            Some((0, _)) => self.upper().remove_file(path).await?,
+            Some(_) => {}
+            None => return Err(not_found(path)),
+        }
+        self.whiteout_lower(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        let Some((layer, _)) = self.locate(path).await? else {
+            return Err(not_found(path));
+        };
+        if !recursive {
+            let mut entries = self.list_dir(path);
+            if let Some(entry) = entries.next().await {
+                entry?;
+                return Err(FuseAdapterError::NotEmpty(
+                    path.to_string_lossy().to_string(),
+                ));
+            }
+        }
+        if layer == 0 {
+            // Recursive even when the merged view is empty, to clear markers
+            self.upper().remove_dir(path, true).await?;
+        }
+        self.whiteout_lower(path).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let layers = self.layers.clone();
+        let path = path.to_path_buf();
+
+        Box::pin(try_stream! {
+            let mut seen: HashSet<OsString> = HashSet::new();
+            let mut whiteouts: HashSet<OsString> = HashSet::new();
+            let mut opaque = false;
+
+            let mut upper = layers[0].list_dir(&path);
+            while let Some(entry) = upper.next().await {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(FuseAdapterError::NotFound(_)) => break,
+                    Err(e) => Err(e)?,
+                };
+                let name = entry.name.to_string_lossy();
+                if name == OPAQUE_MARKER {
+                    opaque = true;
+                } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+                    whiteouts.insert(OsString::from(hidden));
+                } else {
+                    seen.insert(entry.name.clone());
+                    yield entry;
+                }
+            }
+
+            // A whited-out directory or one in an opaque parent shows only
+            // what the upper layer has
+            let hidden = match (path.parent(), whiteout_path(&path)) {
+                (Some(parent), Some(whiteout)) => {
+                    layers[0].exists(&whiteout).await?
+                        || layers[0].exists(&parent.join(OPAQUE_MARKER)).await?
+                }
+                _ => false,
+            };
+            if opaque || hidden {
+                return;
+            }
+
+            for layer in &layers[1..] {
+                let mut lower = layer.list_dir(&path);
+                while let Some(entry) = lower.next().await {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(FuseAdapterError::NotFound(_)) => break,
+                        Err(e) => Err(e)?,
+                    };
+                    if whiteouts.contains(&entry.name) || !seen.insert(entry.name.clone()) {
+                        continue;
+                    }
+                    yield entry;
+                }
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let Some((layer, meta)) = self.locate(from).await? else {
+            return Err(not_found(from));
+        };
+        if meta.is_dir() && (layer != 0 || self.in_lower(from).await?) {
+            // Moving a merged directory would need a recursive copy-up
+            return Err(FuseAdapterError::Io(std::io::Error::from_raw_os_error(
+                libc::EXDEV,
+            )));
+        }
+        self.copy_up(from).await?;
+        self.remove_whiteout(to).await?;
+        self.copy_up_parents(to).await?;
+        self.upper().rename(from, to).await?;
+        self.whiteout_lower(from).await?;
+        if meta.is_dir() {
+            self.mark_opaque_if_shadowing(to).await?;
+        }
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.copy_up(path).await?;
+        self.upper().truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        match found(self.upper().stat(path).await)? {
+            Some(_) => self.upper().flush(path).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.prepare_create(path).await?;
+        self.upper().create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.prepare_create(path).await?;
+        self.upper().create_dir_with_mode(path, mode).await?;
+        self.mark_opaque_if_shadowing(path).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.copy_up(path).await?;
+        self.upper().set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.layer_for(path).await?.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.prepare_create(link_path).await?;
+        self.upper().symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.layer_for(path).await?.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.layer_for(path)
+            .await?
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        for layer in &self.layers {
+            layer.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        let mut result = Ok(());
+        for layer in &self.layers {
+            if let Err(e) = layer.shutdown().await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.layer_for(path).await?.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        match self.locate(path).await? {
+            Some((i, _)) => self.layers[i].revalidate(path, etag).await,
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::DirEntry;
+    use dashmap::DashMap;
+    use futures::stream;
+    use std::time::UNIX_EPOCH;
+
+    /// In-memory backend; directories must be created explicitly
+    #[derive(Default)]
+    struct MemBackend {
+        files: DashMap<PathBuf, Vec<u8>>,
+        dirs: DashMap<PathBuf, ()>,
+    }
+
+    impl MemBackend {
+        fn with(files: &[(&str, &[u8])], dirs: &[&str]) -> Arc<Self> {
+            let backend = Self::default();
+            for (path, data) in files {
+                backend.files.insert(PathBuf::from(path), data.to_vec());
+            }
+            for dir in dirs {
+                backend.dirs.insert(PathBuf::from(dir), ());
+            }
+            Arc::new(backend)
+        }
+    }
+
+    #[async_trait]
+    impl Connector for MemBackend {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::full()
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            if path == Path::new("/") || self.dirs.contains_key(path) {
+                return Ok(Metadata::directory(UNIX_EPOCH));
+            }
+            match self.files.get(path) {
+                Some(data) => Ok(Metadata::file(data.len() as u64, UNIX_EPOCH)),
+                None => Err(not_found(path)),
+            }
+        }
+
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let data = self.files.get(path).ok_or_else(|| not_found(path))?;
+            let start = (offset as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            Ok(Bytes::copy_from_slice(&data[start..end]))
+        }
+
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            let mut file = self.files.entry(path.to_path_buf()).or_default();
+            let end = offset as usize + data.len();
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            file[offset as usize..end].copy_from_slice(data);
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.files.insert(path.to_path_buf(), Vec::new());
+            Ok(())
+        }
+
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.dirs.insert(path.to_path_buf(), ());
+            Ok(())
+        }
+
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.files.remove(path).ok_or_else(|| not_found(path))?;
+            Ok(())
+        }
+
+        async fn remove_dir(&self, path: &Path, _recursive: bool) -> Result<()> {
+            self.files.retain(|p, _| !p.starts_with(path));
+            self.dirs.retain(|p, _| !p.starts_with(path));
+            Ok(())
+        }
+
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            let children = |p: &PathBuf| p.parent() == Some(path);
+            let mut entries: Vec<Result<DirEntry>> = self
+                .files
+                .iter()
+                .filter(|e| children(e.key()))
+                .map(|e| Ok(DirEntry::file(e.key().file_name().unwrap())))
+                .collect();
+            entries.extend(
+                self.dirs
+                    .iter()
+                    .filter(|e| children(e.key()))
+                    .map(|e| Ok(DirEntry::directory(e.key().file_name().unwrap()))),
+            );
+            Box::pin(stream::iter(entries))
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            let (_, data) = self.files.remove(from).ok_or_else(|| not_found(from))?;
+            self.files.insert(to.to_path_buf(), data);
+            Ok(())
+        }
+
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            let mut file = self.files.get_mut(path).ok_or_else(|| not_found(path))?;
+            file.resize(size as usize, 0);
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn union(upper: &Arc<MemBackend>, lower: &Arc<MemBackend>) -> UnionConnector {
+        UnionConnector::new(vec![upper.clone(), lower.clone()]).unwrap()
+    }
+
+    async fn names(union: &UnionConnector, path: &str) -> Vec<String> {
+        let mut names: Vec<String> = union
+            .list_dir(Path::new(path))
+            .map(|e| e.unwrap().name.to_string_lossy().into_owned())
+            .collect()
+            .await;
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_upper_shadows_lower_and_listings_merge() {
+        let upper = MemBackend::with(&[("/a.txt", b"upper")], &[]);
+        let lower = MemBackend::with(&[("/a.txt", b"lower"), ("/b.txt", b"base")], &[]);
+        let union = union(&upper, &lower);
+
+        assert_eq!(
+            union.read(Path::new("/a.txt"), 0, 100).await.unwrap(),
+            "upper"
+        );
+        assert_eq!(
+            union.read(Path::new("/b.txt"), 0, 100).await.unwrap(),
+            "base"
+        );
+        assert_eq!(names(&union, "/").await, vec!["a.txt", "b.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_write_copies_up() {
+        let upper = MemBackend::with(&[], &[]);
+        let lower = MemBackend::with(&[("/data/file.txt", b"hello world")], &["/data"]);
+        let union = union(&upper, &lower);
+
+        union
+            .write(Path::new("/data/file.txt"), 6, b"union")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            union
+                .read(Path::new("/data/file.txt"), 0, 100)
+                .await
+                .unwrap(),
+            "hello union"
+        );
+        assert!(upper.dirs.contains_key(Path::new("/data")));
+        // The lower layer is never modified
+        assert_eq!(
+            *lower.files.get(Path::new("/data/file.txt")).unwrap(),
+            b"hello world"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_leaves_whiteout() {
+        let upper = MemBackend::with(&[], &[]);
+        let lower = MemBackend::with(&[("/gone.txt", b"x"), ("/kept.txt", b"y")], &[]);
+        let union = union(&upper, &lower);
+
+        union.remove_file(Path::new("/gone.txt")).await.unwrap();
+
+        assert!(!union.exists(Path::new("/gone.txt")).await.unwrap());
+        assert_eq!(names(&union, "/").await, vec!["kept.txt"]);
+        assert!(lower.files.contains_key(Path::new("/gone.txt")));
+
+        // Recreating the file removes the whiteout
+        union.create_file(Path::new("/gone.txt")).await.unwrap();
+        assert_eq!(union.stat(Path::new("/gone.txt")).await.unwrap().size, 0);
+        assert_eq!(names(&union, "/").await, vec!["gone.txt", "kept.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_recreated_dir_is_opaque() {
+        let upper = MemBackend::with(&[], &[]);
+        let lower = MemBackend::with(&[("/dir/old.txt", b"x")], &["/dir"]);
+        let union = union(&upper, &lower);
+
+        union.remove_dir(Path::new("/dir"), true).await.unwrap();
+        assert!(!union.exists(Path::new("/dir")).await.unwrap());
+
+        union.create_dir(Path::new("/dir")).await.unwrap();
+        assert!(names(&union, "/dir").await.is_empty());
+        assert!(!union.exists(Path::new("/dir/old.txt")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rename_of_lower_dir_is_cross_device() {
+        let upper = MemBackend::with(&[], &[]);
+        let lower = MemBackend::with(&[("/dir/a.txt", b"x")], &["/dir"]);
+        let union = union(&upper, &lower);
+
+        let err = union
+            .rename(Path::new("/dir"), Path::new("/moved"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::EXDEV);
+
+        // Files are copied up and renamed in the upper layer
+        union
+            .rename(Path::new("/dir/a.txt"), Path::new("/dir/b.txt"))
+            .await
+            .unwrap();
+        assert_eq!(names(&union, "/dir").await, vec!["b.txt"]);
+    }
+}
@@ -0,0 +1,378 @@
+//! Admin control socket
+//!
+//! When `control_socket` is configured, the daemon listens on a Unix stream
+//! socket for one command per connection. The client writes the command and
+//! closes its end for writing; the daemon answers with `ok` or `error: ...`
+//! (followed by any output) and closes the connection.
+//!
+//! Commands:
+//! - `mount add` - Mount the YAML mount entry on the following lines
+//! - `mount remove <path>` - Unmount `path` after a final sync
+//! - `mount list` - List active mounts, one per line as
+//!   `path<TAB>state<TAB>last error`
+//! - `fault set <operation> <path>` - Inject the YAML fault on the following
+//!   lines into a mount's operation (`mock` feature and `faults` mount
+//!   config required)
+//! - `fault clear <path>` - Stop injecting faults into a mount
+//! - `sync [<path>]` - Push pending changes of one mount (or all mounts) to
+//!   the backend, replying once they have been written
+//! - `sync pause [<path>]` - Stop background syncing of one mount (or all
+//!   mounts with a write-back cache) until resumed
+//! - `sync resume [<path>]` - Let background syncing run again
+
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crate::config::FaultOperation;
+
+/// Largest request accepted, in bytes
+const MAX_REQUEST_SIZE: u64 = 1024 * 1024;
+
+/// A command sent to the control socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Mount a new filesystem from a YAML mount entry
+    MountAdd(String),
+    /// Unmount the filesystem at a path
+    MountRemove(PathBuf),
+    /// List active mounts
+    MountList,
+    /// Inject a YAML fault into one operation of a mount
+    FaultSet {
+        path: PathBuf,
+        operation: FaultOperation,
+        fault: String,
+    },
+    /// Stop injecting faults into a mount
+    FaultClear(PathBuf),
+    /// Sync pending changes of one mount, or all mounts if None
+    Sync(Option<PathBuf>),
+    /// Pause background sync of one mount, or all mounts if None
+    SyncPause(Option<PathBuf>),
+    /// Resume background sync of one mount, or all mounts if None
+    SyncResume(Option<PathBuf>),
+}
+
+impl Command {
+    /// Parse a request: the command on the first line, then its input
+    pub fn parse(request: &str) -> Result<Self, String> {
+        let (line, rest) = request.split_once('\n').unwrap_or((request, ""));
+        let line = line.trim();
+        if line == "mount add" {
+            if rest.trim().is_empty() {
+                return Err("mount add expects a YAML mount entry".to_string());
+            }
+            return Ok(Command::MountAdd(rest.to_string()));
+        }
+        if line == "mount list" {
+            return Ok(Command::MountList);
+        }
+        if line == "sync" {
+            return Ok(Command::Sync(None));
+        }
+        if line == "sync pause" {
+            return Ok(Command::SyncPause(None));
+        }
+        if line == "sync resume" {
+            return Ok(Command::SyncResume(None));
+        }
+        if let Some(path) = line.strip_prefix("sync pause ") {
+            return absolute_path("sync pause", path).map(|p| Command::SyncPause(Some(p)));
+        }
+        if let Some(path) = line.strip_prefix("sync resume ") {
+            return absolute_path("sync resume", path).map(|p| Command::SyncResume(Some(p)));
+        }
+        if let Some(path) = line.strip_prefix("sync ") {
+            return absolute_path("sync", path).map(|p| Command::Sync(Some(p)));
+        }
+        if let Some(args) = line.strip_prefix("fault set ") {
+            let (operation, path) = args.trim().split_once(' ').unwrap_or((args, ""));
+            let operation = FaultOperation::parse(operation)
+                .ok_or_else(|| format!("unknown fault operation: {}", operation))?;
+            let path = absolute_path("fault set", path)?;
+            if rest.trim().is_empty() {
+                return Err("fault set expects a YAML fault".to_string());
+            }
+            return Ok(Command::FaultSet {
+                path,
+                operation,
+                fault: rest.to_string(),
+            });
+        }
+        if let Some(path) = line.strip_prefix("fault clear ") {
+            return absolute_path("fault clear", path).map(Command::FaultClear);
+        }
+        match line.strip_prefix("mount remove ") {
+            Some(path) => absolute_path("mount remove", path).map(Command::MountRemove),
+            None => Err(format!("unknown command: {}", line)),
+        }
+    }
+}
+
+/// Parse a command's path argument, which must be absolute
+fn absolute_path(command: &str, path: &str) -> Result<PathBuf, String> {
+    let path = path.trim();
+    if path.starts_with('/') {
+        Ok(PathBuf::from(path))
+    } else {
+        Err(format!("{} expects an absolute path", command))
+    }
+}
+
+/// A command waiting for its reply
+pub struct Request {
+    pub command: Command,
+    reply: oneshot::Sender<Result<String, String>>,
+}
+
+impl Request {
+    /// Send the command's output (or failure) back to the client
+    pub fn reply(self, result: Result<String, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// Listening control socket, removed again when dropped
+pub struct ControlSocket {
+    path: PathBuf,
+    requests: mpsc::Receiver<Request>,
+}
+
+impl ControlSocket {
+    /// Listen on `path`, replacing a socket left behind by an earlier run
+    ///
+    /// Fails if another process is listening on `path`, or if something
+    /// other than a socket is there. The socket is only accessible to the
+    /// daemon's user.
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        match std::fs::symlink_metadata(path) {
+            Ok(meta) if !meta.file_type().is_socket() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{:?} exists and is not a socket", path),
+                ));
+            }
+            Ok(_) => match std::os::unix::net::UnixStream::connect(path) {
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!("control socket {:?} is in use", path),
+                    ));
+                }
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    debug!("Replacing stale control socket {:?}", path)
+                }
+                Err(e) => return Err(e),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let listener = bind_private(path)?;
+
+        let (tx, requests) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Control socket accept failed: {}", e);
+                        continue;
+                    }
+                };
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, tx).await {
+                        debug!("Control connection failed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            requests,
+        })
+    }
+
+    /// Wait for the next command
+    pub async fn next(&mut self) -> Option<Request> {
+        self.requests.recv().await
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Bind a socket only the daemon's user can use and move it to `path`
+///
+/// The socket is created inside a private directory, so it is never reachable
+/// with the default permissions.
+fn bind_private(path: &Path) -> io::Result<UnixListener> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("bad socket path {:?}", path),
+        )
+    })?;
+    let dir = path.with_file_name(format!(
+        ".{}.{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let tmp = dir.join("control.sock");
+    let result = UnixListener::bind(&tmp).and_then(|listener| {
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&tmp, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Read one command from `stream`, pass it on and write back the reply
+async fn handle_connection(mut stream: UnixStream, tx: mpsc::Sender<Request>) -> io::Result<()> {
+    let mut request = String::new();
+    (&mut stream)
+        .take(MAX_REQUEST_SIZE)
+        .read_to_string(&mut request)
+        .await?;
+
+    let result = match Command::parse(&request) {
+        Ok(command) => {
+            let (reply, response) = oneshot::channel();
+            match tx.send(Request { command, reply }).await {
+                Ok(()) => response
+                    .await
+                    .unwrap_or_else(|_| Err("daemon is shutting down".to_string())),
+                Err(_) => Err("daemon is shutting down".to_string()),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    let response = match result {
+        Ok(output) => format!("ok\n{}", output),
+        Err(e) => format!("error: {}\n", e),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            Command::parse("mount remove /mnt/s3\n"),
+            Ok(Command::MountRemove(PathBuf::from("/mnt/s3")))
+        );
+        assert_eq!(Command::parse("mount list"), Ok(Command::MountList));
+        assert_eq!(
+            Command::parse("mount add\npath: /mnt/new\n"),
+            Ok(Command::MountAdd("path: /mnt/new\n".to_string()))
+        );
+
+        assert!(Command::parse("mount add\n").is_err());
+        assert_eq!(
+            Command::parse("mount remove /mnt/my drive"),
+            Ok(Command::MountRemove(PathBuf::from("/mnt/my drive")))
+        );
+        assert!(Command::parse("mount remove mnt").is_err());
+        assert!(Command::parse("unmount /mnt/s3").is_err());
+
+        assert_eq!(
+            Command::parse("fault set read /mnt/flaky\nerror_rate: 1\n"),
+            Ok(Command::FaultSet {
+                path: PathBuf::from("/mnt/flaky"),
+                operation: FaultOperation::Read,
+                fault: "error_rate: 1\n".to_string(),
+            })
+        );
+        assert_eq!(
+            Command::parse("fault clear /mnt/flaky"),
+            Ok(Command::FaultClear(PathBuf::from("/mnt/flaky")))
+        );
+        assert!(Command::parse("fault set read /mnt/flaky").is_err());
+        assert!(Command::parse("fault set reed /mnt/flaky\nhang: true").is_err());
+        assert!(Command::parse("fault clear flaky").is_err());
+
+        assert_eq!(Command::parse("sync\n"), Ok(Command::Sync(None)));
+        assert_eq!(
+            Command::parse("sync /mnt/s3"),
+            Ok(Command::Sync(Some(PathBuf::from("/mnt/s3"))))
+        );
+        assert!(Command::parse("sync s3").is_err());
+
+        assert_eq!(Command::parse("sync pause"), Ok(Command::SyncPause(None)));
+        assert_eq!(
+            Command::parse("sync resume /mnt/s3\n"),
+            Ok(Command::SyncResume(Some(PathBuf::from("/mnt/s3"))))
+        );
+        assert!(Command::parse("sync pause s3").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let mut socket = ControlSocket::bind(&path).unwrap();
+
+        let client = tokio::spawn({
+            let path = path.clone();
+            async move {
+                let mut stream = UnixStream::connect(&path).await.unwrap();
+                stream.write_all(b"mount list\n").await.unwrap();
+                stream.shutdown().await.unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).await.unwrap();
+                response
+            }
+        });
+
+        let request = socket.next().await.unwrap();
+        assert_eq!(request.command, Command::MountList);
+        request.reply(Ok("/mnt/s3\n".to_string()));
+        assert_eq!(client.await.unwrap(), "ok\n/mnt/s3\n");
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_bind_checks_existing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+
+        // A socket nobody listens on is replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let socket = ControlSocket::bind(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A running daemon's socket is left alone
+        let err = ControlSocket::bind(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+        drop(socket);
+
+        // So is anything that isn't a socket
+        std::fs::write(&path, "data").unwrap();
+        assert!(ControlSocket::bind(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
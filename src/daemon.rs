@@ -0,0 +1,149 @@
+//! Running as a traditional background daemon
+//!
+//! `daemonize` detaches the process from its controlling terminal using the
+//! classic double fork. It must be called before any threads are started
+//! (in particular before the tokio runtime is built), since only the calling
+//! thread survives a fork.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+/// Detach from the terminal and continue running in the background
+///
+/// On return the caller is running in a grandchild process that is not a
+/// session leader, has `/` as its working directory, and has stdin, stdout
+/// and stderr redirected to `/dev/null`. The original process exits.
+pub fn daemonize() -> io::Result<()> {
+    fork_and_exit_parent()?;
+
+    // Become session leader so we lose the controlling terminal
+    if unsafe { libc::setsid() } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Fork again so we can never reacquire a terminal
+    fork_and_exit_parent()?;
+
+    // Don't keep the launch directory (possibly a mount) busy
+    std::env::set_current_dir("/")?;
+
+    let devnull = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        if unsafe { libc::dup2(devnull.as_raw_fd(), fd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Fork, exiting immediately in the parent
+fn fork_and_exit_parent() -> io::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(()),
+        _ => unsafe { libc::_exit(0) },
+    }
+}
+
+/// A PID file that is removed again when dropped
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current process ID to `path`
+    ///
+    /// Fails if the file already names a process that is still running. A
+    /// stale file left by a process that no longer exists is replaced.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(pid) = read_pid(path) {
+            if process_exists(pid) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{:?} belongs to running process {}", path, pid),
+                ));
+            }
+        }
+
+        let mut file = fs::File::create(path)?;
+        writeln!(file, "{}", std::process::id())?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Path of the PID file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("Failed to remove PID file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Read the PID stored in a file, if any
+fn read_pid(path: &Path) -> Option<libc::pid_t> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Check whether a process with this PID exists
+fn process_exists(pid: libc::pid_t) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+    // Signal 0 only checks for existence; EPERM means it exists but isn't ours
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_written_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fuse-adapter.pid");
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id() as libc::pid_t));
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_pid_file_refuses_running_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fuse-adapter.pid");
+        fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+
+        let err = PidFile::create(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_pid_file_replaces_stale_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fuse-adapter.pid");
+        fs::write(&path, "garbage\n").unwrap();
+
+        let _pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id() as libc::pid_t));
+    }
+}
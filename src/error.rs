@@ -52,11 +52,26 @@ pub enum FuseAdapterError {
     #[error("No space left")]
     NoSpace,
 
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     #[error("Name too long: {0}")]
     NameTooLong(String),
 
     #[error("Operation interrupted")]
     Interrupted,
+
+    #[error("Integrity check failed: {0}")]
+    IntegrityMismatch(String),
+
+    #[error("Operation timed out: {0}")]
+    Timeout(String),
+
+    #[error("Backend is throttling requests: {0}")]
+    TooManyRequests(String),
+
+    #[error("Backend unavailable: {0}")]
+    Unavailable(String),
 }
 
 impl FuseAdapterError {
@@ -79,8 +94,13 @@ impl FuseAdapterError {
             FuseAdapterError::InvalidArgument(_) => libc::EINVAL,
             FuseAdapterError::FileTooLarge => libc::EFBIG,
             FuseAdapterError::NoSpace => libc::ENOSPC,
+            FuseAdapterError::QuotaExceeded(_) => libc::EDQUOT,
             FuseAdapterError::NameTooLong(_) => libc::ENAMETOOLONG,
             FuseAdapterError::Interrupted => libc::EINTR,
+            FuseAdapterError::IntegrityMismatch(_) => libc::EIO,
+            FuseAdapterError::Timeout(_) => libc::ETIMEDOUT,
+            FuseAdapterError::TooManyRequests(_) => libc::EAGAIN,
+            FuseAdapterError::Unavailable(_) => libc::EIO,
         }
     }
 }
@@ -0,0 +1,100 @@
+//! Runtime error events
+//!
+//! Errors from the FUSE layer and the caches' background sync otherwise only
+//! reach tracing. A mount with a status overlay creates one `ErrorEvents`
+//! that those layers push into, and the overlay exposes the most recent
+//! events as `errors.log`.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+/// A single runtime error
+#[derive(Debug, Clone)]
+struct ErrorEvent {
+    timestamp: DateTime<Utc>,
+    /// Layer the error came from (e.g. "fuse", "cache")
+    source: &'static str,
+    operation: String,
+    path: String,
+    error: String,
+}
+
+impl ErrorEvent {
+    fn format(&self) -> String {
+        format!(
+            "[{}] {} {} {}: {}\n",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
+            self.source,
+            self.operation,
+            self.path,
+            self.error
+        )
+    }
+}
+
+/// Most recent runtime errors of a mount (ring buffer)
+pub struct ErrorEvents {
+    /// Number of events to retain
+    capacity: usize,
+    events: Mutex<VecDeque<ErrorEvent>>,
+}
+
+impl ErrorEvents {
+    /// Create an empty log retaining up to `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record that `operation` on `path` failed in `source`
+    pub fn push(
+        &self,
+        source: &'static str,
+        operation: &str,
+        path: &Path,
+        error: impl fmt::Display,
+    ) {
+        let mut events = self.events.lock().unwrap();
+        events.push_back(ErrorEvent {
+            timestamp: Utc::now(),
+            source,
+            operation: operation.to_string(),
+            path: path.display().to_string(),
+            error: error.to_string(),
+        });
+        while events.len() > self.capacity {
+            events.pop_front();
+        }
+    }
+
+    /// Retained events, oldest first, one per line
+    pub fn report(&self) -> String {
+        let events = self.events.lock().unwrap();
+        events.iter().map(|e| e.format()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_most_recent_events() {
+        let events = ErrorEvents::new(2);
+        for name in ["a", "b", "c"] {
+            events.push("fuse", "read", Path::new(name), "Backend error");
+        }
+
+        let report = events.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("] fuse read b: Backend error"));
+        assert!(lines[1].ends_with("] fuse read c: Backend error"));
+    }
+}
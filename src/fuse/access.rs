@@ -0,0 +1,222 @@
+//! Per-user access control
+//!
+//! Evaluates a mount's `access` rules against the uid and gid of the process
+//! making each FUSE request. A caller that isn't in `allowed_uids` or
+//! `allowed_gids` (when either is set) gets no access at all. Otherwise the
+//! first path rule that matches both the path and the caller decides, and
+//! callers in `read_only_uids` or `read_only_gids` fall back to read access.
+//! Everyone else can read and write.
+//!
+//! Only the caller's primary gid is known to FUSE, so group rules don't see
+//! supplementary groups.
+//!
+//! Separately, `mode_allows` implements the classic owner/group/other check
+//! used when a mount sets `enforce_permissions`.
+
+use std::path::Path;
+
+use globset::{Glob, GlobMatcher};
+
+use crate::config::{AccessConfig, Permission};
+use crate::error::{FuseAdapterError, Result};
+
+/// A compiled path rule
+struct Rule {
+    matcher: GlobMatcher,
+    uids: Vec<u32>,
+    gids: Vec<u32>,
+    permission: Permission,
+}
+
+impl Rule {
+    /// Whether the rule names this caller (a rule naming nobody applies to all)
+    fn applies_to(&self, uid: u32, gid: u32) -> bool {
+        (self.uids.is_empty() && self.gids.is_empty())
+            || self.uids.contains(&uid)
+            || self.gids.contains(&gid)
+    }
+
+    /// Whether `path` or one of its ancestors matches the rule's pattern
+    fn matches(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        relative
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .any(|p| self.matcher.is_match(p))
+    }
+}
+
+/// Access rules for a mount
+pub struct AccessControl {
+    allowed_uids: Vec<u32>,
+    allowed_gids: Vec<u32>,
+    read_only_uids: Vec<u32>,
+    read_only_gids: Vec<u32>,
+    rules: Vec<Rule>,
+}
+
+impl AccessControl {
+    /// Compile access rules; fails if a rule's path isn't a valid glob
+    pub fn new(config: &AccessConfig) -> Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let glob = Glob::new(&rule.path).map_err(|e| {
+                    FuseAdapterError::Config(format!(
+                        "Invalid access rule path '{}': {}",
+                        rule.path, e
+                    ))
+                })?;
+                Ok(Rule {
+                    matcher: glob.compile_matcher(),
+                    uids: rule.uids.clone(),
+                    gids: rule.gids.clone(),
+                    permission: rule.permission,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            allowed_uids: config.allowed_uids.clone(),
+            allowed_gids: config.allowed_gids.clone(),
+            read_only_uids: config.read_only_uids.clone(),
+            read_only_gids: config.read_only_gids.clone(),
+            rules,
+        })
+    }
+
+    /// The access a caller has to `path`
+    pub fn permission(&self, path: &Path, uid: u32, gid: u32) -> Permission {
+        let restricted = !self.allowed_uids.is_empty() || !self.allowed_gids.is_empty();
+        if restricted && !self.allowed_uids.contains(&uid) && !self.allowed_gids.contains(&gid) {
+            return Permission::None;
+        }
+        if let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| rule.applies_to(uid, gid) && rule.matches(path))
+        {
+            return rule.permission;
+        }
+        if self.read_only_uids.contains(&uid) || self.read_only_gids.contains(&gid) {
+            return Permission::Read;
+        }
+        Permission::Write
+    }
+
+    /// Fail with EACCES unless the caller has at least `needed` access to `path`
+    pub fn check(&self, path: &Path, uid: u32, gid: u32, needed: Permission) -> Result<()> {
+        if self.permission(path, uid, gid) < needed {
+            return Err(FuseAdapterError::PermissionDenied);
+        }
+        Ok(())
+    }
+}
+
+/// Whether a file's permission bits grant `mask` (`R_OK`/`W_OK`/`X_OK`)
+///
+/// `owner` is the (uid, gid) the file is reported as belonging to and
+/// `caller` the (uid, gid) of the requesting process. Root may read and write
+/// anything, and execute anything with at least one execute bit set.
+pub fn mode_allows(mode: u32, owner: (u32, u32), caller: (u32, u32), mask: i32) -> bool {
+    let mask = (mask & (libc::R_OK | libc::W_OK | libc::X_OK)) as u32;
+    if caller.0 == 0 {
+        return mask & libc::X_OK as u32 == 0 || mode & 0o111 != 0;
+    }
+    let bits = if caller.0 == owner.0 {
+        (mode >> 6) & 0o7
+    } else if caller.1 == owner.1 {
+        (mode >> 3) & 0o7
+    } else {
+        mode & 0o7
+    };
+    bits & mask == mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AccessRule;
+
+    fn rule(path: &str, uids: &[u32], gids: &[u32], permission: Permission) -> AccessRule {
+        AccessRule {
+            path: path.to_string(),
+            uids: uids.to_vec(),
+            gids: gids.to_vec(),
+            permission,
+        }
+    }
+
+    #[test]
+    fn test_allowed_and_read_only_users() {
+        let acl = AccessControl::new(&AccessConfig {
+            allowed_uids: vec![1000, 1001],
+            allowed_gids: vec![200],
+            read_only_uids: vec![1001],
+            ..Default::default()
+        })
+        .unwrap();
+        let path = Path::new("/data/file");
+
+        assert_eq!(acl.permission(path, 1000, 100), Permission::Write);
+        assert_eq!(acl.permission(path, 1001, 100), Permission::Read);
+        assert_eq!(acl.permission(path, 1002, 200), Permission::Write);
+        assert_eq!(acl.permission(path, 1002, 100), Permission::None);
+        assert!(acl.check(path, 1001, 100, Permission::Read).is_ok());
+        assert_eq!(
+            acl.check(path, 1001, 100, Permission::Write)
+                .unwrap_err()
+                .to_errno(),
+            libc::EACCES
+        );
+    }
+
+    #[test]
+    fn test_mode_allows() {
+        let owner = (1000, 100);
+        let rw = libc::R_OK | libc::W_OK;
+
+        assert!(mode_allows(0o600, owner, (1000, 100), rw));
+        assert!(!mode_allows(0o400, owner, (1000, 100), libc::W_OK));
+        assert!(mode_allows(0o640, owner, (1001, 100), libc::R_OK));
+        assert!(!mode_allows(0o640, owner, (1001, 100), libc::W_OK));
+        assert!(!mode_allows(0o640, owner, (1001, 200), libc::R_OK));
+        assert!(mode_allows(0o604, owner, (1001, 200), libc::R_OK));
+        // Owner bits apply to the owner even when other bits are wider
+        assert!(!mode_allows(0o077, owner, (1000, 100), libc::R_OK));
+        assert!(mode_allows(0o000, owner, (0, 0), rw));
+        assert!(!mode_allows(0o644, owner, (0, 0), libc::X_OK));
+        assert!(mode_allows(0o744, owner, (0, 0), libc::X_OK));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let acl = AccessControl::new(&AccessConfig {
+            read_only_uids: vec![1000],
+            rules: vec![
+                rule("secrets", &[0], &[], Permission::Write),
+                rule("secrets", &[], &[], Permission::None),
+                rule("shared/**", &[], &[100], Permission::Write),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            acl.permission(Path::new("/secrets/key"), 0, 0),
+            Permission::Write
+        );
+        assert_eq!(
+            acl.permission(Path::new("/secrets"), 1000, 100),
+            Permission::None
+        );
+        assert_eq!(
+            acl.permission(Path::new("/shared/a/b"), 1000, 100),
+            Permission::Write
+        );
+        assert_eq!(
+            acl.permission(Path::new("/other"), 1000, 100),
+            Permission::Read
+        );
+    }
+}
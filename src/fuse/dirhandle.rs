@@ -0,0 +1,260 @@
+//! Open directory handles for streaming `readdir`
+//!
+//! Each `opendir` gets a handle holding the connector's listing stream, so a
+//! directory with millions of entries is read one page per `readdir` call
+//! instead of being collected up front. Entries are numbered in the order
+//! the stream yields them; those numbers are the offsets handed to the
+//! kernel, and a `readdir` at any offset resumes right after that entry. A
+//! rewind (or a stream lost to a timeout) restarts the listing and skips
+//! ahead, which keeps offsets stable as long as the listing itself is.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use crate::connector::{DirEntry, DirEntryStream};
+use crate::error::{FuseAdapterError, Result};
+
+/// Offset of the first listing entry; 1 and 2 belong to `.` and `..`
+pub const FIRST_ENTRY_OFFSET: i64 = 3;
+
+/// Maximum number of entries pulled from the stream per `readdir`
+const PAGE_SIZE: usize = 256;
+
+/// Listing state for one open directory handle
+struct DirHandle {
+    path: PathBuf,
+    /// Listing stream; taken out while a page is being fetched. The mutex
+    /// only makes the (non-`Sync`) stream storable in the table
+    stream: Option<Mutex<DirEntryStream>>,
+    /// Entries read from the stream but not yet consumed by the kernel,
+    /// with their offsets
+    buffered: VecDeque<(i64, DirEntry)>,
+    /// Offset assigned to the next entry read from the stream
+    next_offset: i64,
+    /// The stream has ended
+    exhausted: bool,
+}
+
+impl DirHandle {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            stream: None,
+            buffered: VecDeque::new(),
+            next_offset: FIRST_ENTRY_OFFSET,
+            exhausted: false,
+        }
+    }
+
+    /// Offset of the entry this handle would return next
+    fn cursor(&self) -> i64 {
+        self.buffered
+            .front()
+            .map(|(offset, _)| *offset)
+            .unwrap_or(self.next_offset)
+    }
+
+    fn reset(&mut self) {
+        self.stream = None;
+        self.buffered.clear();
+        self.next_offset = FIRST_ENTRY_OFFSET;
+        self.exhausted = false;
+    }
+}
+
+/// Tracks open directory handles by file handle number
+pub struct DirHandleTable {
+    handles: DashMap<u64, DirHandle>,
+    next_fh: AtomicU64,
+}
+
+impl Default for DirHandleTable {
+    fn default() -> Self {
+        Self {
+            handles: DashMap::new(),
+            next_fh: AtomicU64::new(1),
+        }
+    }
+}
+
+impl DirHandleTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a handle for listing `path`; the listing starts on first read
+    pub fn open(&self, path: &Path) -> u64 {
+        let fh = self.next_fh.fetch_add(1, Ordering::Relaxed);
+        self.handles.insert(fh, DirHandle::new(path.to_path_buf()));
+        fh
+    }
+
+    /// Drop a handle and its listing stream
+    pub fn release(&self, fh: u64) {
+        self.handles.remove(&fh);
+    }
+
+    /// Fetch the page of entries following `offset`
+    ///
+    /// `offset` is the offset of the last entry the kernel consumed (or
+    /// anything below [`FIRST_ENTRY_OFFSET`] to start from the beginning).
+    /// Returned entries carry their own offsets; any the kernel doesn't
+    /// take are returned again by the next call.
+    pub async fn page<F>(&self, fh: u64, offset: i64, open: F) -> Result<Vec<(i64, DirEntry)>>
+    where
+        F: Fn(&Path) -> DirEntryStream,
+    {
+        let target = offset.max(FIRST_ENTRY_OFFSET - 1) + 1;
+
+        let (path, mut stream, mut next_offset, mut wanted) = {
+            let mut handle = self.handles.get_mut(&fh).ok_or_else(bad_handle)?;
+            // Entries before the target were consumed by the previous reply
+            while handle
+                .buffered
+                .front()
+                .is_some_and(|(entry_offset, _)| *entry_offset < target)
+            {
+                handle.buffered.pop_front();
+            }
+            let lost_stream = handle.stream.is_none()
+                && !handle.exhausted
+                && handle.next_offset > FIRST_ENTRY_OFFSET;
+            if handle.cursor() > target || lost_stream {
+                handle.reset();
+            }
+            if handle.buffered.len() >= PAGE_SIZE || handle.exhausted {
+                return Ok(handle.buffered.iter().take(PAGE_SIZE).cloned().collect());
+            }
+            (
+                handle.path.clone(),
+                handle.stream.take().map(Mutex::into_inner),
+                handle.next_offset,
+                PAGE_SIZE - handle.buffered.len(),
+            )
+        };
+
+        let stream_ref = stream.get_or_insert_with(|| open(&path));
+        let mut fetched = Vec::new();
+        let mut exhausted = false;
+
+        while wanted > 0 {
+            match stream_ref.next().await {
+                Some(Ok(entry)) => {
+                    // Skip entries before the target (forward seek or restart)
+                    if next_offset >= target {
+                        fetched.push((next_offset, entry));
+                        wanted -= 1;
+                    }
+                    next_offset += 1;
+                }
+                Some(Err(e)) => {
+                    warn!("readdir entry error: {}", e);
+                }
+                None => {
+                    exhausted = true;
+                    break;
+                }
+            }
+        }
+
+        let mut handle = self.handles.get_mut(&fh).ok_or_else(bad_handle)?;
+        handle.buffered.extend(fetched);
+        handle.next_offset = next_offset;
+        handle.exhausted = exhausted;
+        handle.stream = if exhausted {
+            None
+        } else {
+            stream.map(Mutex::new)
+        };
+        Ok(handle.buffered.iter().take(PAGE_SIZE).cloned().collect())
+    }
+}
+
+fn bad_handle() -> FuseAdapterError {
+    FuseAdapterError::Io(std::io::Error::from_raw_os_error(libc::EBADF))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    fn lister(count: usize, opens: Arc<AtomicUsize>) -> impl Fn(&Path) -> DirEntryStream {
+        move |_| {
+            opens.fetch_add(1, Ordering::Relaxed);
+            Box::pin(stream::iter(
+                (0..count).map(|i| Ok(DirEntry::file(format!("f{}", i)))),
+            ))
+        }
+    }
+
+    fn names(page: &[(i64, DirEntry)]) -> Vec<String> {
+        page.iter()
+            .map(|(_, e)| e.name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_pages_resume_from_offset() {
+        let table = DirHandleTable::new();
+        let opens = Arc::new(AtomicUsize::new(0));
+        let list = lister(PAGE_SIZE + 10, opens.clone());
+        let fh = table.open(Path::new("/dir"));
+
+        let first = table.page(fh, 0, &list).await.unwrap();
+        assert_eq!(first.len(), PAGE_SIZE);
+        assert_eq!(first[0].0, FIRST_ENTRY_OFFSET);
+
+        // Kernel only took the first 5 entries
+        let last = first[4].0;
+        let second = table.page(fh, last, &list).await.unwrap();
+        assert_eq!(names(&second)[0], "f5");
+        assert_eq!(second.len(), PAGE_SIZE);
+
+        let last = second.last().unwrap().0;
+        let third = table.page(fh, last, &list).await.unwrap();
+        assert_eq!(names(&third), vec!["f261", "f262", "f263", "f264", "f265"]);
+
+        let last = third.last().unwrap().0;
+        assert!(table.page(fh, last, &list).await.unwrap().is_empty());
+        assert_eq!(opens.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rewind_restarts_listing() {
+        let table = DirHandleTable::new();
+        let opens = Arc::new(AtomicUsize::new(0));
+        let list = lister(PAGE_SIZE * 2, opens.clone());
+        let fh = table.open(Path::new("/dir"));
+
+        let first = table.page(fh, 0, &list).await.unwrap();
+        let last = first.last().unwrap().0;
+        table.page(fh, last, &list).await.unwrap();
+
+        // Seeking back to an already-discarded offset relists and skips ahead
+        let again = table.page(fh, FIRST_ENTRY_OFFSET + 1, &list).await.unwrap();
+        assert_eq!(names(&again)[0], "f2");
+        assert_eq!(opens.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_released_handle_is_rejected() {
+        let table = DirHandleTable::new();
+        let opens = Arc::new(AtomicUsize::new(0));
+        let list = lister(1, opens);
+        let fh = table.open(Path::new("/dir"));
+        table.release(fh);
+
+        let err = table.page(fh, 0, &list).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EBADF);
+    }
+}
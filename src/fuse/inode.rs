@@ -3,15 +3,26 @@
 //! The FUSE interface works with inodes (numeric identifiers) while
 //! connectors work with paths. This module provides bidirectional
 //! mapping between the two.
+//!
+//! Inodes handed to the kernel in entry replies carry a lookup count that the
+//! kernel later releases with `forget`; once it drops to zero the mapping is
+//! reclaimed so the table stays bounded by what the kernel actually holds.
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 
 /// Root directory inode (always 1 in FUSE)
 pub const ROOT_INODE: u64 = 1;
 
+/// High bit set on synthetic inode numbers so they never collide with
+/// allocated ones
+const SYNTHETIC_INODE_BIT: u64 = 1 << 63;
+
 /// Manages bidirectional inode <-> path mapping
 pub struct InodeTable {
     /// Maps inode -> path
@@ -20,6 +31,8 @@ pub struct InodeTable {
     path_to_inode: DashMap<PathBuf, u64>,
     /// Next inode number to allocate
     next_inode: AtomicU64,
+    /// Outstanding kernel lookup count per inode
+    lookups: DashMap<u64, u64>,
 }
 
 impl InodeTable {
@@ -29,6 +42,7 @@ impl InodeTable {
             inode_to_path: DashMap::new(),
             path_to_inode: DashMap::new(),
             next_inode: AtomicU64::new(ROOT_INODE + 1),
+            lookups: DashMap::new(),
         };
 
         // Register root directory
@@ -63,6 +77,65 @@ impl InodeTable {
         *self.path_to_inode.get(&normalized).unwrap()
     }
 
+    /// Get inode for a path and count a kernel reference to it
+    ///
+    /// Use this whenever the inode is returned in an entry reply (lookup,
+    /// create, mkdir, ...), each of which the kernel balances with `forget`.
+    pub fn lookup_inode(&self, path: &Path) -> u64 {
+        loop {
+            let inode = self.get_or_create_inode(path);
+            // `forget` holds the count's entry while it removes the mapping,
+            // so a mapping still present here can't be reclaimed under us
+            let entry = self.lookups.entry(inode);
+            if self.inode_to_path.contains_key(&inode) {
+                *entry.or_insert(0) += 1;
+                return inode;
+            }
+        }
+    }
+
+    /// Release `nlookup` kernel references to an inode
+    ///
+    /// When none remain the inode's mapping is removed. The root inode is
+    /// never removed. Returns whether the inode was reclaimed.
+    pub fn forget(&self, inode: u64, nlookup: u64) -> bool {
+        if inode == ROOT_INODE {
+            return false;
+        }
+
+        // The entry stays locked until the mapping is gone, so a concurrent
+        // `lookup_inode` either counts before this or sees no mapping
+        let entry = self.lookups.entry(inode);
+        if let Entry::Occupied(mut entry) = entry {
+            let count = entry.get_mut();
+            *count = count.saturating_sub(nlookup);
+            if *count > 0 {
+                return false;
+            }
+            if let Some((_, path)) = self.inode_to_path.remove(&inode) {
+                self.path_to_inode.remove_if(&path, |_, i| *i == inode);
+            }
+            entry.remove();
+        } else if let Some((_, path)) = self.inode_to_path.remove(&inode) {
+            self.path_to_inode.remove_if(&path, |_, i| *i == inode);
+        }
+        true
+    }
+
+    /// Inode number to report for a path in a directory listing
+    ///
+    /// Returns the path's inode if it has one, otherwise a stable synthetic
+    /// number so that listing large directories doesn't allocate inodes the
+    /// kernel never looks up (and therefore never forgets).
+    pub fn peek_inode(&self, path: &Path) -> u64 {
+        if let Some(inode) = self.get_inode(path) {
+            return inode;
+        }
+        let mut hasher = DefaultHasher::new();
+        normalize_path(path).hash(&mut hasher);
+        hasher.finish() | SYNTHETIC_INODE_BIT
+    }
+
     /// Get path for an inode
     pub fn get_path(&self, inode: u64) -> Option<PathBuf> {
         self.inode_to_path.get(&inode).map(|p| p.clone())
@@ -137,6 +210,7 @@ impl InodeTable {
     pub fn clear(&self) {
         self.inode_to_path.retain(|k, _| *k == ROOT_INODE);
         self.path_to_inode.retain(|_, v| *v == ROOT_INODE);
+        self.lookups.clear();
     }
 }
 
@@ -160,6 +234,82 @@ fn normalize_path(path: &Path) -> PathBuf {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_forget_reclaims_unreferenced_inodes() {
+        let table = InodeTable::new();
+        let path = Path::new("/foo");
+
+        let inode = table.lookup_inode(path);
+        assert_eq!(table.lookup_inode(path), inode);
+        assert_eq!(table.len(), 2);
+
+        assert!(!table.forget(inode, 1));
+        assert_eq!(table.get_path(inode), Some(PathBuf::from("/foo")));
+
+        assert!(table.forget(inode, 1));
+        assert_eq!(table.get_path(inode), None);
+        assert_eq!(table.get_inode(path), None);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_forget_races_with_lookup() {
+        let table = std::sync::Arc::new(InodeTable::new());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20_000 {
+                        let inode = table.lookup_inode(Path::new("/foo"));
+                        // Our reference keeps the mapping alive until forgotten
+                        assert_eq!(table.get_path(inode), Some(PathBuf::from("/foo")));
+                        table.forget(inode, 1);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(table.len(), 1);
+        assert!(table.lookups.is_empty());
+    }
+
+    #[test]
+    fn test_forget_keeps_root() {
+        let table = InodeTable::new();
+        assert!(!table.forget(ROOT_INODE, 10));
+        assert_eq!(table.get_path(ROOT_INODE), Some(PathBuf::from("/")));
+    }
+
+    #[test]
+    fn test_forget_after_rename_over_keeps_new_mapping() {
+        let table = InodeTable::new();
+        let path = Path::new("/foo");
+
+        let old = table.lookup_inode(path);
+        let new = table.lookup_inode(Path::new("/bar"));
+        table.rename_path(Path::new("/bar"), path);
+
+        table.forget(old, 1);
+        assert_eq!(table.get_inode(path), Some(new));
+    }
+
+    #[test]
+    fn test_peek_inode_does_not_allocate() {
+        let table = InodeTable::new();
+        let path = Path::new("/listed");
+
+        let synthetic = table.peek_inode(path);
+        assert_eq!(table.peek_inode(path), synthetic);
+        assert_ne!(synthetic & SYNTHETIC_INODE_BIT, 0);
+        assert_eq!(table.len(), 1);
+
+        let real = table.lookup_inode(path);
+        assert_eq!(table.peek_inode(path), real);
+    }
+
     #[test]
     fn test_root_inode() {
         let table = InodeTable::new();
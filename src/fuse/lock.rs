@@ -0,0 +1,343 @@
+//! Advisory file locks (`fcntl` and `flock`)
+//!
+//! Locks are kept in memory, so they exclude other processes using the same
+//! mount but mean nothing to other hosts or other mounts of the same bucket.
+//! They follow POSIX record-lock rules: any number of owners may hold
+//! overlapping read locks, a write lock excludes everyone else, and locking
+//! a range an owner already holds replaces that part of its old lock.
+//!
+//! The kernel forwards `flock()` as a whole-file lock with an owner per open
+//! file description, without saying which call it came from, so `flock` and
+//! `fcntl` locks on the same file conflict with each other here (as they do
+//! on NFS).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::Notify;
+
+/// A lock held on a byte range of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lock {
+    /// Lock owner as reported by the kernel
+    pub owner: u64,
+    /// First byte covered
+    pub start: u64,
+    /// Last byte covered (inclusive; `u64::MAX` extends to end of file)
+    pub end: u64,
+    /// `F_RDLCK`, `F_WRLCK` or `F_UNLCK`
+    pub typ: i32,
+    /// Process that took the lock (only reported back by `getlk`)
+    pub pid: u32,
+}
+
+impl Lock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start <= end && start <= self.end
+    }
+
+    /// Whether `other` can't be granted while this lock is held
+    fn conflicts(&self, other: &Lock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.typ == libc::F_WRLCK || other.typ == libc::F_WRLCK)
+    }
+}
+
+/// Blocked requests of one owner on one inode
+#[derive(Default)]
+struct Waiting {
+    count: usize,
+    /// Bumped when the owner's locks are released, which cancels the requests
+    generation: u64,
+}
+
+/// Locks held on each inode
+#[derive(Default)]
+pub struct LockTable {
+    files: Mutex<HashMap<u64, Vec<Lock>>>,
+    /// Blocked requests by `(ino, owner)`; always locked after `files`
+    waiting: Mutex<HashMap<(u64, u64), Waiting>>,
+    /// Woken whenever a lock is released, so blocked requests can retry
+    released: Notify,
+}
+
+/// Registration of a blocked request, removed when the request finishes or
+/// is dropped
+struct Waiter<'a> {
+    table: &'a LockTable,
+    key: (u64, u64),
+    generation: u64,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        let mut waiting = self.table.waiting.lock().unwrap();
+        if let Some(entry) = waiting.get_mut(&self.key) {
+            entry.count -= 1;
+            if entry.count == 0 {
+                waiting.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl LockTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first lock on `ino` that would prevent `lock` from being granted
+    pub fn conflict(&self, ino: u64, lock: &Lock) -> Option<Lock> {
+        let files = self.files.lock().unwrap();
+        files
+            .get(&ino)?
+            .iter()
+            .find(|held| held.conflicts(lock))
+            .copied()
+    }
+
+    /// Take, change or (with `F_UNLCK`) release a lock without waiting
+    ///
+    /// Fails with `EAGAIN` if another owner holds a conflicting lock.
+    pub fn set(&self, ino: u64, lock: Lock) -> Result<(), i32> {
+        self.try_set(ino, lock, None)
+    }
+
+    /// `set`, failing with `EINTR` if `waiter` was cancelled
+    fn try_set(&self, ino: u64, lock: Lock, waiter: Option<&Waiter<'_>>) -> Result<(), i32> {
+        let mut files = self.files.lock().unwrap();
+        if let Some(waiter) = waiter {
+            let waiting = self.waiting.lock().unwrap();
+            if waiting.get(&waiter.key).map(|w| w.generation) != Some(waiter.generation) {
+                return Err(libc::EINTR);
+            }
+        }
+        let locks = files.entry(ino).or_default();
+        if lock.typ != libc::F_UNLCK && locks.iter().any(|held| held.conflicts(&lock)) {
+            return Err(libc::EAGAIN);
+        }
+
+        // Merge with the owner's adjacent locks of the same type
+        let mut new = lock;
+        if new.typ != libc::F_UNLCK {
+            locks.retain(|held| {
+                let adjacent = held.owner == new.owner
+                    && held.typ == new.typ
+                    && held.start <= new.end.saturating_add(1)
+                    && new.start <= held.end.saturating_add(1);
+                if adjacent {
+                    new.start = new.start.min(held.start);
+                    new.end = new.end.max(held.end);
+                }
+                !adjacent
+            });
+        }
+
+        // Cut the range out of whatever else the owner holds
+        let mut unlocked = false;
+        let mut kept = Vec::with_capacity(locks.len() + 1);
+        for held in locks.drain(..) {
+            if held.owner != new.owner || !held.overlaps(new.start, new.end) {
+                kept.push(held);
+                continue;
+            }
+            unlocked = true;
+            if held.start < new.start {
+                kept.push(Lock {
+                    end: new.start - 1,
+                    ..held
+                });
+            }
+            if held.end > new.end {
+                kept.push(Lock {
+                    start: new.end + 1,
+                    ..held
+                });
+            }
+        }
+        if new.typ != libc::F_UNLCK {
+            kept.push(new);
+        }
+        *locks = kept;
+        if locks.is_empty() {
+            files.remove(&ino);
+        }
+        drop(files);
+
+        if unlocked {
+            self.released.notify_waiters();
+        }
+        Ok(())
+    }
+
+    /// Like `set`, but wait for conflicting locks to be released
+    ///
+    /// Fails with `EINTR` if the owner's locks are released while waiting,
+    /// since the owner has closed the file or exited.
+    pub async fn set_wait(&self, ino: u64, lock: Lock) -> Result<(), i32> {
+        let key = (ino, lock.owner);
+        let generation = {
+            let mut waiting = self.waiting.lock().unwrap();
+            let entry = waiting.entry(key).or_default();
+            entry.count += 1;
+            entry.generation
+        };
+        let waiter = Waiter {
+            table: self,
+            key,
+            generation,
+        };
+        loop {
+            // Register for wakeups before checking, so a release between the
+            // check and the wait isn't missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            match self.try_set(ino, lock, Some(&waiter)) {
+                Err(libc::EAGAIN) => released.await,
+                result => return result,
+            }
+        }
+    }
+
+    /// Drop every lock `owner` holds on `ino` and cancel its blocked
+    /// requests (on close)
+    pub fn release_owner(&self, ino: u64, owner: u64) {
+        let mut files = self.files.lock().unwrap();
+        let mut released = false;
+        if let Some(locks) = files.get_mut(&ino) {
+            let before = locks.len();
+            locks.retain(|held| held.owner != owner);
+            released = locks.len() != before;
+            if locks.is_empty() {
+                files.remove(&ino);
+            }
+        }
+        if let Some(entry) = self.waiting.lock().unwrap().get_mut(&(ino, owner)) {
+            entry.generation += 1;
+            released = true;
+        }
+        drop(files);
+
+        if released {
+            self.released.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn lock(owner: u64, start: u64, end: u64, typ: i32) -> Lock {
+        Lock {
+            owner,
+            start,
+            end,
+            typ,
+            pid: owner as u32,
+        }
+    }
+
+    #[test]
+    fn test_read_and_write_conflicts() {
+        let table = LockTable::new();
+        table.set(1, lock(1, 0, 99, libc::F_RDLCK)).unwrap();
+        table.set(1, lock(2, 50, 149, libc::F_RDLCK)).unwrap();
+
+        assert_eq!(
+            table.set(1, lock(3, 90, 90, libc::F_WRLCK)),
+            Err(libc::EAGAIN)
+        );
+        assert_eq!(
+            table.conflict(1, &lock(3, 90, 90, libc::F_WRLCK)),
+            Some(lock(1, 0, 99, libc::F_RDLCK))
+        );
+        // Ranges past both read locks, and other inodes, are free
+        table.set(1, lock(3, 150, u64::MAX, libc::F_WRLCK)).unwrap();
+        table.set(2, lock(3, 0, u64::MAX, libc::F_WRLCK)).unwrap();
+        // An owner never conflicts with itself
+        assert!(table.conflict(1, &lock(1, 0, 49, libc::F_WRLCK)).is_none());
+    }
+
+    #[test]
+    fn test_unlock_splits_and_merges_ranges() {
+        let table = LockTable::new();
+        table.set(1, lock(1, 0, 99, libc::F_WRLCK)).unwrap();
+        table.set(1, lock(1, 40, 59, libc::F_UNLCK)).unwrap();
+
+        assert!(table.conflict(1, &lock(2, 40, 59, libc::F_WRLCK)).is_none());
+        assert!(table.conflict(1, &lock(2, 30, 30, libc::F_RDLCK)).is_some());
+        assert!(table.conflict(1, &lock(2, 60, 60, libc::F_RDLCK)).is_some());
+
+        // Relocking the gap joins the pieces back into one lock
+        table.set(1, lock(1, 40, 59, libc::F_WRLCK)).unwrap();
+        assert_eq!(
+            table.conflict(1, &lock(2, 0, u64::MAX, libc::F_RDLCK)),
+            Some(lock(1, 0, 99, libc::F_WRLCK))
+        );
+
+        // Downgrading part of the range lets readers in there only
+        table.set(1, lock(1, 0, 49, libc::F_RDLCK)).unwrap();
+        table.set(1, lock(2, 0, 49, libc::F_RDLCK)).unwrap();
+        assert!(table.set(1, lock(2, 50, 50, libc::F_RDLCK)).is_err());
+
+        table.release_owner(1, 1);
+        table.set(1, lock(2, 0, u64::MAX, libc::F_WRLCK)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_set_wait_blocks_until_release() {
+        let table = Arc::new(LockTable::new());
+        table.set(1, lock(1, 0, u64::MAX, libc::F_WRLCK)).unwrap();
+
+        let waiter = tokio::spawn({
+            let table = table.clone();
+            async move { table.set_wait(1, lock(2, 0, u64::MAX, libc::F_WRLCK)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        table.release_owner(1, 1);
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(table.conflict(1, &lock(1, 0, 0, libc::F_RDLCK)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_release_cancels_waiting_request() {
+        let table = Arc::new(LockTable::new());
+        table.set(1, lock(1, 0, u64::MAX, libc::F_WRLCK)).unwrap();
+
+        let waiter = tokio::spawn({
+            let table = table.clone();
+            async move { table.set_wait(1, lock(2, 0, u64::MAX, libc::F_WRLCK)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The waiting owner goes away before the lock is free
+        table.release_owner(1, 2);
+        let result = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result, Err(libc::EINTR));
+
+        // Releasing the holder doesn't hand the lock to the dead owner
+        table.release_owner(1, 1);
+        assert!(table.conflict(1, &lock(3, 0, 0, libc::F_WRLCK)).is_none());
+        assert!(table.waiting.lock().unwrap().is_empty());
+
+        // A later request from a reused owner id isn't cancelled
+        table
+            .set_wait(1, lock(2, 0, 0, libc::F_WRLCK))
+            .await
+            .unwrap();
+    }
+}
@@ -1,21 +1,39 @@
+pub mod access;
+pub mod dirhandle;
 pub mod inode;
+pub mod lock;
+pub mod normalize;
+pub mod pagecache;
+pub mod writebuf;
 
 use std::ffi::OsStr;
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 use fuser::{
-    FileAttr, FileType as FuseFileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData,
-    ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow,
+    FileAttr, FileType as FuseFileType, Filesystem, KernelConfig, ReplyAttr, ReplyCreate,
+    ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyLock, ReplyLseek, ReplyOpen,
+    ReplyWrite, Request, TimeOrNow,
 };
 use tokio::runtime::Handle;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, trace, warn, Instrument, Span};
 
-use crate::connector::{Connector, FileType, Metadata};
+use crate::audit::{AuditEvent, AuditLog};
+use crate::config::{LockingMode, PageCacheMode, Permission, UnicodeNormalization};
+use crate::connector::{Connector, FileType, Metadata, SeekRegion, COPY_CHUNK_SIZE};
 use crate::error::FuseAdapterError;
+use crate::events::ErrorEvents;
+use crate::telemetry::fuse_span;
 
+use self::access::{mode_allows, AccessControl};
+use self::dirhandle::{DirHandleTable, FIRST_ENTRY_OFFSET};
 use self::inode::{InodeTable, ROOT_INODE};
+use self::lock::{Lock, LockTable};
+use self::normalize::normalize_name;
+use self::pagecache::PageCache;
+use self::writebuf::WriteBuffers;
 
 /// Default TTL for attribute caching (1 second)
 const ATTR_TTL: Duration = Duration::from_secs(1);
@@ -62,15 +80,43 @@ fn metadata_to_attr(ino: u64, meta: &Metadata, uid: u32, gid: u32) -> FileAttr {
 }
 
 /// FUSE filesystem implementation that delegates to a Connector
+///
+/// Requests are decoded on fuser's session thread, but every connector call
+/// is spawned onto the adapter's runtime and replied to from there, so a slow
+/// operation (e.g. a cold read) does not hold up other requests on the mount.
 pub struct FuseAdapter {
     connector: Arc<dyn Connector>,
-    inodes: InodeTable,
+    inodes: Arc<InodeTable>,
+    /// Listing state for open directory handles
+    dirs: Arc<DirHandleTable>,
     /// Dedicated runtime for FUSE async operations
     runtime: tokio::runtime::Runtime,
     /// User ID to report for all files (defaults to process uid)
     uid: u32,
     /// Group ID to report for all files (defaults to process gid)
     gid: u32,
+    /// Fail operations that take longer than this with ETIMEDOUT
+    operation_timeout: Option<Duration>,
+    /// Audit log for mutating operations (if enabled)
+    audit: Option<Arc<AuditLog>>,
+    /// Channel failed operations are reported to (if enabled)
+    errors: Option<Arc<ErrorEvents>>,
+    /// Per-user access rules (if enabled)
+    access: Option<Arc<AccessControl>>,
+    /// Check file modes against the caller on access, open and setattr
+    enforce_permissions: bool,
+    /// How `fcntl`/`flock` locks are handled
+    locking: LockingMode,
+    /// Locks granted in `LockingMode::Local`
+    locks: Arc<LockTable>,
+    /// Whether opens keep, drop or bypass the kernel page cache
+    page_cache: Arc<PageCache>,
+    /// Normalization form names are converted to before use
+    normalization: UnicodeNormalization,
+    /// Buffers coalescing sequential writes (if enabled)
+    buffers: Option<Arc<WriteBuffers>>,
+    /// Largest write request to accept from the kernel (None = fuser's limit)
+    max_write: Option<u32>,
 }
 
 impl FuseAdapter {
@@ -101,18 +147,121 @@ impl FuseAdapter {
 
         Self {
             connector,
-            inodes: InodeTable::new(),
+            inodes: Arc::new(InodeTable::new()),
+            dirs: Arc::new(DirHandleTable::new()),
             runtime,
             uid,
             gid,
+            operation_timeout: None,
+            audit: None,
+            errors: None,
+            access: None,
+            enforce_permissions: false,
+            locking: LockingMode::default(),
+            locks: Arc::new(LockTable::new()),
+            page_cache: Arc::new(PageCache::default()),
+            normalization: UnicodeNormalization::None,
+            buffers: None,
+            max_write: None,
         }
     }
 
+    /// Fail any single operation that takes longer than `timeout`
+    ///
+    /// Without a timeout a hung backend blocks the calling process (and the
+    /// FUSE worker) indefinitely.
+    pub fn with_operation_timeout(mut self, timeout: Duration) -> Self {
+        self.operation_timeout = Some(timeout);
+        self
+    }
+
+    /// Record every mutating operation in `log`
+    pub fn with_audit_log(mut self, log: Arc<AuditLog>) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    /// Report operations failing with an unexpected error to `errors`
+    pub fn with_error_events(mut self, errors: Arc<ErrorEvents>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    /// Check every request's uid/gid against `access`
+    pub fn with_access_control(mut self, access: Arc<AccessControl>) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    /// Enforce file permission bits against each caller's uid/gid
+    pub fn with_permission_checks(mut self) -> Self {
+        self.enforce_permissions = true;
+        self
+    }
+
+    /// Handle `fcntl`/`flock` locks according to `mode`
+    pub fn with_locking(mut self, mode: LockingMode) -> Self {
+        self.locking = mode;
+        self
+    }
+
+    /// Use the kernel page cache for file contents according to `mode`
+    pub fn with_page_cache(mut self, mode: PageCacheMode) -> Self {
+        self.page_cache = Arc::new(PageCache::new(mode));
+        self
+    }
+
+    /// Convert file names in requests and listings to `form`, so names sent
+    /// in another form find the same file
+    pub fn with_unicode_normalization(mut self, form: UnicodeNormalization) -> Self {
+        self.normalization = form;
+        self
+    }
+
+    /// Coalesce sequential writes into buffers of up to `max_size` bytes,
+    /// written out at least every `interval`
+    pub fn with_write_buffer(mut self, max_size: usize, interval: Duration) -> Self {
+        self.buffers = Some(Arc::new(WriteBuffers::new(
+            self.connector.clone(),
+            max_size,
+            interval,
+        )));
+        self
+    }
+
+    /// Ask the kernel to send writes of at most `max_write` bytes
+    pub fn with_max_write(mut self, max_write: u32) -> Self {
+        self.max_write = Some(max_write);
+        self
+    }
+
+    /// Check the caller behind `req` has at least `needed` access to `path`
+    fn check_access(&self, req: &Request<'_>, path: &Path, needed: Permission) -> Result<(), i32> {
+        match &self.access {
+            Some(access) => access
+                .check(path, req.uid(), req.gid(), needed)
+                .map_err(|e| e.to_errno()),
+            None => Ok(()),
+        }
+    }
+
+    /// Start auditing an operation by the process behind `req`, if enabled
+    fn audit_event(&self, req: &Request<'_>, op: &'static str, path: &Path) -> Option<AuditEvent> {
+        self.audit
+            .as_ref()
+            .map(|log| log.event(op, path, req.uid(), req.gid(), req.pid()))
+    }
+
     /// Get path for inode, returning ENOENT if not found
     fn inode_to_path(&self, ino: u64) -> Result<PathBuf, i32> {
         self.inodes.get_path(ino).ok_or(libc::ENOENT)
     }
 
+    /// Path of the entry `name` in `parent`, with the name normalized
+    fn child_path(&self, parent: &Path, name: &OsStr) -> PathBuf {
+        parent.join(normalize_name(name, self.normalization))
+    }
+
     /// Check if operation is supported, returning appropriate error
     fn check_write_capability(&self) -> Result<(), i32> {
         if !self.connector.capabilities().write {
@@ -149,18 +298,194 @@ impl FuseAdapter {
         Ok(())
     }
 
-    /// Run an async operation on the dedicated FUSE runtime and wait for the result.
-    /// Uses block_on which properly drives the runtime's I/O driver.
-    fn run_async<F, T>(&self, future: F) -> T
+    /// Run an operation on the FUSE runtime without blocking the session thread
+    ///
+    /// `complete` receives the result (subject to the operation timeout) on a
+    /// runtime worker and is responsible for sending the reply. The operation
+    /// stays in the caller's current span.
+    fn spawn_op<F, T, C>(&self, future: F, complete: C)
     where
-        F: std::future::Future<Output = T>,
+        F: Future<Output = crate::error::Result<T>> + Send + 'static,
+        T: Send + 'static,
+        C: FnOnce(crate::error::Result<T>) + Send + 'static,
     {
-        self.runtime.block_on(future)
+        let timeout = self.operation_timeout;
+        self.runtime.spawn(
+            async move {
+                complete(with_timeout(timeout, future).await);
+            }
+            .instrument(Span::current()),
+        );
     }
 }
 
+/// Write out data buffered for `ino` before an operation that depends on
+/// the file's contents or size
+async fn drain_writes(buffers: &Option<Arc<WriteBuffers>>, ino: u64) -> crate::error::Result<()> {
+    match buffers {
+        Some(buffers) => buffers.drain(ino).await,
+        None => Ok(()),
+    }
+}
+
+/// Fail with EACCES unless `path`'s mode grants `mask` to `caller`
+///
+/// Every file is reported as owned by `owner`, so that's what the mode is
+/// checked against.
+async fn check_mode(
+    connector: &dyn Connector,
+    path: &Path,
+    owner: (u32, u32),
+    caller: (u32, u32),
+    mask: i32,
+) -> crate::error::Result<()> {
+    let meta = connector.stat(path).await?;
+    if !mode_allows(meta.mode_or_default(), owner, caller, mask) {
+        return Err(FuseAdapterError::PermissionDenied);
+    }
+    Ok(())
+}
+
+/// Copy up to `len` bytes from `from` at `offset_in` to `to` at `offset_out`
+///
+/// A copy of a whole file over an empty or shorter one goes through
+/// `Connector::copy`, so backends can copy server-side. Anything else copies
+/// a single chunk through the client and the kernel asks again for the rest.
+async fn copy_range(
+    connector: &dyn Connector,
+    from: &Path,
+    offset_in: u64,
+    to: &Path,
+    offset_out: u64,
+    len: u64,
+) -> crate::error::Result<u32> {
+    let source_size = connector.stat(from).await?.size;
+    if offset_in >= source_size {
+        return Ok(0);
+    }
+    let whole_file =
+        offset_in == 0 && offset_out == 0 && len >= source_size && source_size <= u32::MAX as u64;
+    if whole_file && connector.stat(to).await?.size <= source_size {
+        connector.copy(from, to).await?;
+        return Ok(source_size as u32);
+    }
+
+    let size = len.min(source_size - offset_in).min(COPY_CHUNK_SIZE as u64) as u32;
+    let data = connector.read(from, offset_in, size).await?;
+    let written = connector.write(to, offset_out, &data).await?;
+    Ok(written as u32)
+}
+
+/// Apply an `fallocate` request to `path`
+///
+/// Preallocation only grows the file (there is nothing to reserve on a
+/// remote backend), while punching a hole or zeroing a range zeroes it
+/// through `Connector::zero_range`.
+async fn allocate(
+    connector: &dyn Connector,
+    path: &Path,
+    offset: u64,
+    len: u64,
+    mode: i32,
+) -> crate::error::Result<()> {
+    let supported =
+        libc::FALLOC_FL_KEEP_SIZE | libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_ZERO_RANGE;
+    if mode & !supported != 0 {
+        return Err(FuseAdapterError::NotSupported(format!(
+            "fallocate mode {:#x}",
+            mode
+        )));
+    }
+    if mode & libc::FALLOC_FL_PUNCH_HOLE != 0 {
+        if mode & libc::FALLOC_FL_KEEP_SIZE == 0 || mode & libc::FALLOC_FL_ZERO_RANGE != 0 {
+            return Err(FuseAdapterError::InvalidArgument(
+                "punching a hole requires FALLOC_FL_KEEP_SIZE alone".to_string(),
+            ));
+        }
+        return connector.zero_range(path, offset, len).await;
+    }
+    if mode & libc::FALLOC_FL_ZERO_RANGE != 0 {
+        connector.zero_range(path, offset, len).await?;
+    }
+    if mode & libc::FALLOC_FL_KEEP_SIZE == 0 {
+        let end = offset.saturating_add(len);
+        if connector.stat(path).await?.size < end {
+            connector.truncate(path, end).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Wrap a completion so the operation's outcome is audited before replying
+fn audited<T, C>(event: Option<AuditEvent>, complete: C) -> impl FnOnce(crate::error::Result<T>)
+where
+    C: FnOnce(crate::error::Result<T>),
+{
+    move |result| {
+        if let Some(event) = event {
+            event.finish(&result);
+        }
+        complete(result)
+    }
+}
+
+/// Record a failed operation on `path` in the error channel, if enabled
+fn report_error(
+    errors: &Option<Arc<ErrorEvents>>,
+    operation: &str,
+    path: &Path,
+    error: &FuseAdapterError,
+) {
+    if let Some(errors) = errors {
+        errors.push("fuse", operation, path, error);
+    }
+}
+
+/// Await `future`, failing with `FuseAdapterError::Timeout` after `timeout`
+async fn with_timeout<F, T>(timeout: Option<Duration>, future: F) -> crate::error::Result<T>
+where
+    F: Future<Output = crate::error::Result<T>>,
+{
+    let Some(timeout) = timeout else {
+        return future.await;
+    };
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or_else(|_| {
+            warn!("Operation timed out after {:?}", timeout);
+            Err(FuseAdapterError::Timeout(format!(
+                "no response within {:?}",
+                timeout
+            )))
+        })
+}
+
 impl Filesystem for FuseAdapter {
-    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+    fn init(&mut self, _req: &Request<'_>, config: &mut KernelConfig) -> Result<(), libc::c_int> {
+        // Have the kernel forward lock requests instead of handling them
+        // itself, in both modes, so `deny` can refuse them
+        let wanted = fuser::consts::FUSE_POSIX_LOCKS | fuser::consts::FUSE_FLOCK_LOCKS;
+        if let Err(unsupported) = config.add_capabilities(wanted) {
+            warn!(
+                "Kernel does not support lock capabilities {:#x}; locks stay local to the kernel",
+                unsupported
+            );
+            let _ = config.add_capabilities(wanted & !unsupported);
+        }
+
+        if let Some(max_write) = self.max_write {
+            if let Err(nearest) = config.set_max_write(max_write) {
+                warn!(
+                    "max_write of {} bytes is not supported; using {}",
+                    max_write, nearest
+                );
+                let _ = config.set_max_write(nearest);
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
         let parent_path = match self.inode_to_path(parent) {
             Ok(p) => p,
             Err(e) => {
@@ -169,28 +494,60 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let path = parent_path.join(name);
+        let path = self.child_path(&parent_path, name);
         trace!("lookup: {:?}", path);
+        let _span = fuse_span("lookup", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Read) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.stat(&path_for_async).await }) {
-            Ok(meta) => {
-                let ino = self.inodes.get_or_create_inode(&path);
-                let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                reply.entry(&ATTR_TTL, &attr, GENERATION);
-            }
-            Err(FuseAdapterError::NotFound(_)) => {
-                reply.error(libc::ENOENT);
-            }
-            Err(e) => {
-                error!("lookup error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
+        let inodes = self.inodes.clone();
+        let (uid, gid) = (self.uid, self.gid);
+        let buffers = self.buffers.clone();
+        let known = self.inodes.get_inode(&path);
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                if let Some(ino) = known {
+                    drain_writes(&buffers, ino).await?;
+                }
+                connector.stat(&path_for_async).await
+            },
+            move |result| match result {
+                Ok(meta) => {
+                    let ino = inodes.lookup_inode(&path);
+                    let attr = metadata_to_attr(ino, &meta, uid, gid);
+                    reply.entry(&ATTR_TTL, &attr, GENERATION);
+                }
+                Err(FuseAdapterError::NotFound(_)) => {
+                    reply.error(libc::ENOENT);
+                }
+                Err(e) => {
+                    error!("lookup error for {:?}: {}", path, e);
+                    report_error(&errors, "lookup", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
+    }
+
+    // fuser's default batch_forget hands each inode of a BATCH_FORGET to
+    // forget, so both kinds of request release lookups here
+    fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+        if self.inodes.forget(ino, nlookup) {
+            self.page_cache.forget(ino);
+            if let Some(buffers) = &self.buffers {
+                buffers.forget(ino);
             }
+            trace!("forget: reclaimed ino={}", ino);
         }
     }
 
-    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
         let path = match self.inode_to_path(ino) {
             Ok(p) => p,
             Err(e) => {
@@ -200,24 +557,38 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("getattr: {:?} (ino={})", path, ino);
+        let _span = fuse_span("getattr", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Read) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.stat(&path_for_async).await }) {
-            Ok(meta) => {
-                let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                reply.attr(&ATTR_TTL, &attr);
-            }
-            Err(e) => {
-                debug!("getattr error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let (uid, gid) = (self.uid, self.gid);
+        let buffers = self.buffers.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino).await?;
+                connector.stat(&path_for_async).await
+            },
+            move |result| match result {
+                Ok(meta) => {
+                    let attr = metadata_to_attr(ino, &meta, uid, gid);
+                    reply.attr(&ATTR_TTL, &attr);
+                }
+                Err(e) => {
+                    debug!("getattr error for {:?}: {}", path, e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
     }
 
     fn setattr(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         mode: Option<u32>,
         _uid: Option<u32>,
@@ -241,6 +612,15 @@ impl Filesystem for FuseAdapter {
             }
         };
 
+        if mode.is_some() || size.is_some() {
+            if let Err(e) = self.check_access(req, &path, Permission::Write) {
+                reply.error(e);
+                return;
+            }
+        }
+
+        let (uid, gid) = (self.uid, self.gid);
+
         // Handle mode change (chmod)
         if let Some(new_mode) = mode {
             if let Err(e) = self.check_set_mode_capability() {
@@ -248,25 +628,42 @@ impl Filesystem for FuseAdapter {
                 return;
             }
 
+            // Like chmod(2), only the owner (or root) may change modes
+            if self.enforce_permissions && req.uid() != 0 && req.uid() != self.uid {
+                reply.error(libc::EPERM);
+                return;
+            }
+
             trace!("setattr chmod: {:?} to {:o}", path, new_mode);
+            let _span = fuse_span("chmod", &path).entered();
 
             let connector = self.connector.clone();
             let path_for_async = path.clone();
             // Extract just the permission bits (lower 12 bits)
             let perm_bits = new_mode & 0o7777;
-            match self.run_async(async move {
-                connector.set_mode(&path_for_async, perm_bits).await?;
-                connector.stat(&path_for_async).await
-            }) {
-                Ok(meta) => {
-                    let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                    reply.attr(&ATTR_TTL, &attr);
-                }
-                Err(e) => {
-                    error!("setattr chmod error for ino {}: {}", ino, e);
-                    reply.error(e.to_errno());
-                }
-            }
+            let audit = self
+                .audit_event(req, "chmod", &path)
+                .map(|e| e.mode(perm_bits));
+            let buffers = self.buffers.clone();
+            let errors = self.errors.clone();
+            self.spawn_op(
+                async move {
+                    drain_writes(&buffers, ino).await?;
+                    connector.set_mode(&path_for_async, perm_bits).await?;
+                    connector.stat(&path_for_async).await
+                },
+                audited(audit, move |result| match result {
+                    Ok(meta) => {
+                        let attr = metadata_to_attr(ino, &meta, uid, gid);
+                        reply.attr(&ATTR_TTL, &attr);
+                    }
+                    Err(e) => {
+                        error!("setattr chmod error for ino {}: {}", ino, e);
+                        report_error(&errors, "chmod", &path, &e);
+                        reply.error(e.to_errno());
+                    }
+                }),
+            );
             return;
         }
 
@@ -278,31 +675,56 @@ impl Filesystem for FuseAdapter {
             }
 
             trace!("setattr truncate: {:?} to {} bytes", path, new_size);
+            let _span = fuse_span("truncate", &path).entered();
 
             let connector = self.connector.clone();
-            match self.run_async(async move {
-                connector.truncate(&path, new_size).await?;
-                connector.stat(&path).await
-            }) {
-                Ok(meta) => {
-                    let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                    reply.attr(&ATTR_TTL, &attr);
-                }
-                Err(e) => {
-                    error!("setattr error for ino {}: {}", ino, e);
-                    reply.error(e.to_errno());
-                }
-            }
+            let path_for_async = path.clone();
+            let audit = self
+                .audit_event(req, "truncate", &path)
+                .map(|e| e.size(new_size));
+            let enforce = self.enforce_permissions;
+            let owner = (self.uid, self.gid);
+            let caller = (req.uid(), req.gid());
+            let buffers = self.buffers.clone();
+            let errors = self.errors.clone();
+            self.spawn_op(
+                async move {
+                    if enforce {
+                        check_mode(
+                            connector.as_ref(),
+                            &path_for_async,
+                            owner,
+                            caller,
+                            libc::W_OK,
+                        )
+                        .await?;
+                    }
+                    drain_writes(&buffers, ino).await?;
+                    connector.truncate(&path_for_async, new_size).await?;
+                    connector.stat(&path_for_async).await
+                },
+                audited(audit, move |result| match result {
+                    Ok(meta) => {
+                        let attr = metadata_to_attr(ino, &meta, uid, gid);
+                        reply.attr(&ATTR_TTL, &attr);
+                    }
+                    Err(e) => {
+                        error!("setattr error for ino {}: {}", ino, e);
+                        report_error(&errors, "truncate", &path, &e);
+                        reply.error(e.to_errno());
+                    }
+                }),
+            );
             return;
         }
 
         // No changes requested, just return current attributes
-        self.getattr(_req, ino, reply);
+        self.getattr(req, ino, reply);
     }
 
     fn read(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         _fh: u64,
         offset: i64,
@@ -320,25 +742,38 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("read: {:?} offset={} size={}", path, offset, size);
+        let _span = fuse_span("read", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Read) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self
-            .run_async(async move { connector.read(&path_for_async, offset as u64, size).await })
-        {
-            Ok(data) => {
-                reply.data(&data);
-            }
-            Err(e) => {
-                error!("read error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino).await?;
+                connector.read(&path_for_async, offset as u64, size).await
+            },
+            move |result| match result {
+                Ok(data) => {
+                    reply.data(&data);
+                }
+                Err(e) => {
+                    error!("read error for {:?}: {}", path, e);
+                    report_error(&errors, "read", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
     }
 
     fn write(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         _fh: u64,
         offset: i64,
@@ -362,26 +797,263 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("write: {:?} offset={} size={}", path, offset, data.len());
+        let _span = fuse_span("write", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let data = data.to_vec();
         let path_for_async = path.clone();
-        match self
-            .run_async(async move { connector.write(&path_for_async, offset as u64, &data).await })
-        {
-            Ok(written) => {
-                reply.written(written as u32);
+        let audit = self
+            .audit_event(req, "write", &path)
+            .map(|e| e.size(data.len() as u64));
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                match buffers {
+                    Some(buffers) => {
+                        buffers
+                            .write(ino, &path_for_async, offset as u64, &data)
+                            .await
+                    }
+                    None => connector.write(&path_for_async, offset as u64, &data).await,
+                }
+            },
+            audited(audit, move |result| match result {
+                Ok(written) => {
+                    reply.written(written as u32);
+                }
+                Err(e) => {
+                    error!("write error for {:?}: {}", path, e);
+                    report_error(&errors, "write", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
+    }
+
+    fn copy_file_range(
+        &mut self,
+        req: &Request<'_>,
+        ino_in: u64,
+        _fh_in: u64,
+        offset_in: i64,
+        ino_out: u64,
+        _fh_out: u64,
+        offset_out: i64,
+        len: u64,
+        _flags: u32,
+        reply: ReplyWrite,
+    ) {
+        if let Err(e) = self.check_write_capability() {
+            reply.error(e);
+            return;
+        }
+
+        let (from, to) = match (self.inode_to_path(ino_in), self.inode_to_path(ino_out)) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                reply.error(e);
+                return;
             }
+        };
+
+        trace!(
+            "copy_file_range: {:?} offset={} -> {:?} offset={} len={}",
+            from,
+            offset_in,
+            to,
+            offset_out,
+            len
+        );
+        let _span = fuse_span("copy_file_range", &from).entered();
+
+        if let Err(e) = self.check_access(req, &from, Permission::Read) {
+            reply.error(e);
+            return;
+        }
+        if let Err(e) = self.check_access(req, &to, Permission::Write) {
+            reply.error(e);
+            return;
+        }
+
+        let connector = self.connector.clone();
+        let from_for_async = from.clone();
+        let to_for_async = to.clone();
+        let audit = self.audit_event(req, "copy", &from).map(|e| e.to(&to));
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino_in).await?;
+                drain_writes(&buffers, ino_out).await?;
+                copy_range(
+                    connector.as_ref(),
+                    &from_for_async,
+                    offset_in as u64,
+                    &to_for_async,
+                    offset_out as u64,
+                    len,
+                )
+                .await
+            },
+            audited(audit, move |result| match result {
+                Ok(copied) => reply.written(copied),
+                // ENOSYS would turn copy_file_range off for the whole mount
+                Err(FuseAdapterError::NotSupported(_)) => reply.error(libc::EOPNOTSUPP),
+                Err(e) => {
+                    error!("copy_file_range error {:?} -> {:?}: {}", from, to, e);
+                    report_error(&errors, "copy", &from, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
+    }
+
+    fn lseek(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        whence: i32,
+        reply: ReplyLseek,
+    ) {
+        // The kernel handles every other whence itself
+        let region = match whence {
+            libc::SEEK_DATA => SeekRegion::Data,
+            libc::SEEK_HOLE => SeekRegion::Hole,
+            _ => {
+                reply.error(libc::EINVAL);
+                return;
+            }
+        };
+        if offset < 0 {
+            reply.error(libc::ENXIO);
+            return;
+        }
+
+        let path = match self.inode_to_path(ino) {
+            Ok(p) => p,
             Err(e) => {
-                error!("write error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
+                reply.error(e);
+                return;
             }
+        };
+
+        trace!("lseek: {:?} offset={} region={:?}", path, offset, region);
+        let _span = fuse_span("lseek", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Read) {
+            reply.error(e);
+            return;
         }
+
+        let connector = self.connector.clone();
+        let path_for_async = path.clone();
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino).await?;
+                connector
+                    .seek_region(&path_for_async, offset as u64, region)
+                    .await
+            },
+            move |result| match result {
+                Ok(Some(found)) => reply.offset(found as i64),
+                Ok(None) => reply.error(libc::ENXIO),
+                Err(e) => {
+                    error!("lseek error for {:?}: {}", path, e);
+                    report_error(&errors, "lseek", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
+    }
+
+    fn fallocate(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        length: i64,
+        mode: i32,
+        reply: ReplyEmpty,
+    ) {
+        if let Err(e) = self.check_write_capability() {
+            reply.error(e);
+            return;
+        }
+
+        // Backends without truncate can't change a file's size in place
+        if !self.connector.capabilities().truncate {
+            reply.error(libc::EOPNOTSUPP);
+            return;
+        }
+
+        let path = match self.inode_to_path(ino) {
+            Ok(p) => p,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+
+        trace!(
+            "fallocate: {:?} offset={} length={} mode={:#x}",
+            path,
+            offset,
+            length,
+            mode
+        );
+        let _span = fuse_span("fallocate", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
+
+        let connector = self.connector.clone();
+        let path_for_async = path.clone();
+        let audit = self
+            .audit_event(req, "fallocate", &path)
+            .map(|e| e.size((offset + length) as u64));
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino).await?;
+                allocate(
+                    connector.as_ref(),
+                    &path_for_async,
+                    offset as u64,
+                    length as u64,
+                    mode,
+                )
+                .await
+            },
+            audited(audit, move |result| match result {
+                Ok(()) => reply.ok(),
+                // ENOSYS would turn fallocate off for the whole mount
+                Err(FuseAdapterError::NotSupported(_)) => reply.error(libc::EOPNOTSUPP),
+                Err(e) => {
+                    error!("fallocate error for {:?}: {}", path, e);
+                    report_error(&errors, "fallocate", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
     }
 
     fn create(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         mode: u32,
@@ -402,34 +1074,52 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let path = parent_path.join(name);
+        let path = self.child_path(&parent_path, name);
         // Apply umask to get effective mode (permission bits only)
         let effective_mode = (mode & !umask) & 0o7777;
         debug!("create: {:?} mode={:o}", path, effective_mode);
+        let _span = fuse_span("create", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move {
-            connector
-                .create_file_with_mode(&path_for_async, effective_mode)
-                .await?;
-            connector.stat(&path_for_async).await
-        }) {
-            Ok(meta) => {
-                let ino = self.inodes.get_or_create_inode(&path);
-                let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                reply.created(&ATTR_TTL, &attr, GENERATION, 0, 0);
-            }
-            Err(e) => {
-                error!("create error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let inodes = self.inodes.clone();
+        let page_cache = self.page_cache.clone();
+        let (uid, gid) = (self.uid, self.gid);
+        let audit = self
+            .audit_event(req, "create", &path)
+            .map(|e| e.mode(effective_mode));
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                connector
+                    .create_file_with_mode(&path_for_async, effective_mode)
+                    .await?;
+                connector.stat(&path_for_async).await
+            },
+            audited(audit, move |result| match result {
+                Ok(meta) => {
+                    let ino = inodes.lookup_inode(&path);
+                    let attr = metadata_to_attr(ino, &meta, uid, gid);
+                    let flags = page_cache.open_flags(ino, Some(&meta));
+                    reply.created(&ATTR_TTL, &attr, GENERATION, 0, flags);
+                }
+                Err(e) => {
+                    error!("create error for {:?}: {}", path, e);
+                    report_error(&errors, "create", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
     }
 
     fn mkdir(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         mode: u32,
@@ -449,32 +1139,48 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let path = parent_path.join(name);
+        let path = self.child_path(&parent_path, name);
         // Apply umask to get effective mode (permission bits only)
         let effective_mode = (mode & !umask) & 0o7777;
         debug!("mkdir: {:?} mode={:o}", path, effective_mode);
+        let _span = fuse_span("mkdir", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move {
-            connector
-                .create_dir_with_mode(&path_for_async, effective_mode)
-                .await?;
-            connector.stat(&path_for_async).await
-        }) {
-            Ok(meta) => {
-                let ino = self.inodes.get_or_create_inode(&path);
-                let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                reply.entry(&ATTR_TTL, &attr, GENERATION);
-            }
-            Err(e) => {
-                error!("mkdir error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let inodes = self.inodes.clone();
+        let (uid, gid) = (self.uid, self.gid);
+        let audit = self
+            .audit_event(req, "mkdir", &path)
+            .map(|e| e.mode(effective_mode));
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                connector
+                    .create_dir_with_mode(&path_for_async, effective_mode)
+                    .await?;
+                connector.stat(&path_for_async).await
+            },
+            audited(audit, move |result| match result {
+                Ok(meta) => {
+                    let ino = inodes.lookup_inode(&path);
+                    let attr = metadata_to_attr(ino, &meta, uid, gid);
+                    reply.entry(&ATTR_TTL, &attr, GENERATION);
+                }
+                Err(e) => {
+                    error!("mkdir error for {:?}: {}", path, e);
+                    report_error(&errors, "mkdir", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
     }
 
-    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
         if let Err(e) = self.check_write_capability() {
             reply.error(e);
             return;
@@ -488,24 +1194,44 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let path = parent_path.join(name);
+        let path = self.child_path(&parent_path, name);
         debug!("unlink: {:?}", path);
+        let _span = fuse_span("unlink", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.remove_file(&path_for_async).await }) {
-            Ok(()) => {
-                self.inodes.remove_path(&path);
-                reply.ok();
-            }
-            Err(e) => {
-                error!("unlink error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let inodes = self.inodes.clone();
+        let audit = self.audit_event(req, "unlink", &path);
+        let buffers = self.buffers.clone();
+        let known = self.inodes.get_inode(&path);
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                if let Some(ino) = known {
+                    drain_writes(&buffers, ino).await?;
+                }
+                connector.remove_file(&path_for_async).await
+            },
+            audited(audit, move |result| match result {
+                Ok(()) => {
+                    inodes.remove_path(&path);
+                    reply.ok();
+                }
+                Err(e) => {
+                    error!("unlink error for {:?}: {}", path, e);
+                    report_error(&errors, "unlink", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
     }
 
-    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+    fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
         if let Err(e) = self.check_write_capability() {
             reply.error(e);
             return;
@@ -519,26 +1245,39 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let path = parent_path.join(name);
+        let path = self.child_path(&parent_path, name);
         debug!("rmdir: {:?}", path);
+        let _span = fuse_span("rmdir", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.remove_dir(&path_for_async, false).await }) {
-            Ok(()) => {
-                self.inodes.remove_path(&path);
-                reply.ok();
-            }
-            Err(e) => {
-                error!("rmdir error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let inodes = self.inodes.clone();
+        let audit = self.audit_event(req, "rmdir", &path);
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move { connector.remove_dir(&path_for_async, false).await },
+            audited(audit, move |result| match result {
+                Ok(()) => {
+                    inodes.remove_path(&path);
+                    reply.ok();
+                }
+                Err(e) => {
+                    error!("rmdir error for {:?}: {}", path, e);
+                    report_error(&errors, "rmdir", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
     }
 
     fn rename(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         newparent: u64,
@@ -567,62 +1306,174 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let old_path = parent_path.join(name);
-        let new_path = new_parent_path.join(newname);
+        let old_path = self.child_path(&parent_path, name);
+        let new_path = self.child_path(&new_parent_path, newname);
         debug!("rename: {:?} -> {:?}", old_path, new_path);
+        let _span = fuse_span("rename", &old_path).entered();
+
+        if let Err(e) = self.check_access(req, &old_path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
+
+        if let Err(e) = self.check_access(req, &new_path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let old_path_for_async = old_path.clone();
         let new_path_for_async = new_path.clone();
-        match self.run_async(async move {
-            connector
-                .rename(&old_path_for_async, &new_path_for_async)
-                .await
-        }) {
-            Ok(()) => {
-                self.inodes.rename_path(&old_path, &new_path);
-                reply.ok();
+        let inodes = self.inodes.clone();
+        let audit = self
+            .audit_event(req, "rename", &old_path)
+            .map(|e| e.to(&new_path));
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                // The buffered file may be inside a renamed directory
+                if let Some(buffers) = &buffers {
+                    buffers.drain_all().await?;
+                }
+                connector
+                    .rename(&old_path_for_async, &new_path_for_async)
+                    .await
+            },
+            audited(audit, move |result| match result {
+                Ok(()) => {
+                    inodes.rename_path(&old_path, &new_path);
+                    reply.ok();
+                }
+                Err(e) => {
+                    error!("rename error {:?} -> {:?}: {}", old_path, new_path, e);
+                    report_error(&errors, "rename", &old_path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
+    }
+
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if self.access.is_some() {
+            let needed = if flags & libc::O_ACCMODE == libc::O_RDONLY {
+                Permission::Read
+            } else {
+                Permission::Write
+            };
+            let checked = self
+                .inode_to_path(ino)
+                .and_then(|path| self.check_access(req, &path, needed));
+            if let Err(e) = checked {
+                reply.error(e);
+                return;
             }
-            Err(e) => {
-                error!("rename error {:?} -> {:?}: {}", old_path, new_path, e);
-                reply.error(e.to_errno());
+        }
+
+        let check_mask = self.enforce_permissions.then(|| {
+            let mask = match flags & libc::O_ACCMODE {
+                libc::O_RDONLY => libc::R_OK,
+                libc::O_WRONLY => libc::W_OK,
+                _ => libc::R_OK | libc::W_OK,
+            };
+            if flags & libc::O_TRUNC != 0 {
+                mask | libc::W_OK
+            } else {
+                mask
             }
+        });
+        let need_meta = self.page_cache.needs_metadata();
+        if check_mask.is_some() || need_meta {
+            let path = match self.inode_to_path(ino) {
+                Ok(p) => p,
+                Err(e) => {
+                    reply.error(e);
+                    return;
+                }
+            };
+            let _span = fuse_span("open", &path).entered();
+            let connector = self.connector.clone();
+            let page_cache = self.page_cache.clone();
+            let owner = (self.uid, self.gid);
+            let caller = (req.uid(), req.gid());
+            self.spawn_op(
+                async move {
+                    if let Some(mask) = check_mask {
+                        check_mode(connector.as_ref(), &path, owner, caller, mask).await?;
+                    }
+                    if need_meta {
+                        connector.stat(&path).await.map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                },
+                move |result| match result {
+                    Ok(meta) => reply.opened(0, page_cache.open_flags(ino, meta.as_ref())),
+                    Err(e) => reply.error(e.to_errno()),
+                },
+            );
+            return;
         }
-    }
 
-    fn open(&mut self, _req: &Request<'_>, _ino: u64, _flags: i32, reply: ReplyOpen) {
         // Stateless - just return success with a dummy file handle
-        reply.opened(0, 0);
+        reply.opened(0, self.page_cache.open_flags(ino, None));
     }
 
     fn release(
         &mut self,
         _req: &Request<'_>,
-        _ino: u64,
+        ino: u64,
         _fh: u64,
         _flags: i32,
-        _lock_owner: Option<u64>,
+        lock_owner: Option<u64>,
         _flush: bool,
         reply: ReplyEmpty,
     ) {
-        // Stateless - nothing to do
+        // Set when the file description held a `flock` lock
+        if let Some(owner) = lock_owner {
+            self.locks.release_owner(ino, owner);
+        }
+
+        // Normally already drained by flush, but writes through a shared
+        // mapping can arrive after the last close
+        if let Some(buffers) = self.buffers.clone() {
+            self.spawn_op(async move { buffers.drain(ino).await }, move |result| {
+                if let Err(e) = result {
+                    warn!("release: buffered write for ino {} failed: {}", ino, e);
+                }
+                reply.ok();
+            });
+            return;
+        }
         reply.ok();
     }
 
-    fn opendir(&mut self, _req: &Request<'_>, _ino: u64, _flags: i32, reply: ReplyOpen) {
-        // Stateless - just return success
-        reply.opened(0, 0);
+    fn opendir(&mut self, req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        let path = match self.inode_to_path(ino) {
+            Ok(p) => p,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+        if let Err(e) = self.check_access(req, &path, Permission::Read) {
+            reply.error(e);
+            return;
+        }
+        // The listing itself is opened lazily by the first readdir
+        let fh = self.dirs.open(&path);
+        reply.opened(fh, 0);
     }
 
     fn releasedir(
         &mut self,
         _req: &Request<'_>,
         _ino: u64,
-        _fh: u64,
+        fh: u64,
         _flags: i32,
         reply: ReplyEmpty,
     ) {
-        // Stateless - nothing to do
+        self.dirs.release(fh);
         reply.ok();
     }
 
@@ -630,7 +1481,7 @@ impl Filesystem for FuseAdapter {
         &mut self,
         _req: &Request<'_>,
         ino: u64,
-        _fh: u64,
+        fh: u64,
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
@@ -643,66 +1494,65 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("readdir: {:?} offset={}", path, offset);
+        let _span = fuse_span("readdir", &path).entered();
 
         let connector = self.connector.clone();
-        let path_for_async = path.clone();
-
-        use futures::StreamExt;
-
-        let entries: Vec<_> = self.run_async(async move {
-            let stream = connector.list_dir(&path_for_async);
-            stream.collect().await
-        });
-
-        // Add . and ..
-        let mut idx = 0i64;
+        let dirs = self.dirs.clone();
+        let inodes = self.inodes.clone();
+        let normalization = self.normalization;
+
+        // Only one page of the listing is fetched per call; the handle keeps
+        // the stream positioned for the next one
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move { dirs.page(fh, offset, |p| connector.list_dir(p)).await },
+            move |result| {
+                let entries = match result {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        error!("readdir error for {:?}: {}", path, e);
+                        report_error(&errors, "readdir", &path, &e);
+                        reply.error(e.to_errno());
+                        return;
+                    }
+                };
 
-        if offset <= idx && reply.add(ino, idx + 1, FuseFileType::Directory, ".") {
-            reply.ok();
-            return;
-        }
-        idx += 1;
+                // Add . and ..
+                if offset < 1 && reply.add(ino, 1, FuseFileType::Directory, ".") {
+                    reply.ok();
+                    return;
+                }
 
-        if offset <= idx {
-            let parent_ino = if ino == ROOT_INODE {
-                ROOT_INODE
-            } else {
-                // Get parent inode
-                path.parent()
-                    .and_then(|p| self.inodes.get_inode(p))
-                    .unwrap_or(ROOT_INODE)
-            };
-            if reply.add(parent_ino, idx + 1, FuseFileType::Directory, "..") {
-                reply.ok();
-                return;
-            }
-        }
-        idx += 1;
+                if offset < FIRST_ENTRY_OFFSET - 1 {
+                    let parent_ino = if ino == ROOT_INODE {
+                        ROOT_INODE
+                    } else {
+                        // Get parent inode
+                        path.parent()
+                            .and_then(|p| inodes.get_inode(p))
+                            .unwrap_or(ROOT_INODE)
+                    };
+                    if reply.add(parent_ino, 2, FuseFileType::Directory, "..") {
+                        reply.ok();
+                        return;
+                    }
+                }
 
-        for entry_result in entries {
-            match entry_result {
-                Ok(entry) => {
-                    if offset <= idx {
-                        let entry_path = path.join(&entry.name);
-                        let entry_ino = self.inodes.get_or_create_inode(&entry_path);
-                        let ft = to_fuse_file_type(entry.file_type);
+                for (entry_offset, entry) in entries {
+                    let name = normalize_name(&entry.name, normalization);
+                    let entry_path = path.join(&name);
+                    let entry_ino = inodes.peek_inode(&entry_path);
+                    let ft = to_fuse_file_type(entry.file_type);
 
-                        if reply.add(entry_ino, idx + 1, ft, &entry.name) {
-                            // Buffer full
-                            reply.ok();
-                            return;
-                        }
+                    if reply.add(entry_ino, entry_offset, ft, &name) {
+                        // Buffer full; the rest stay buffered in the handle
+                        break;
                     }
-                    idx += 1;
-                }
-                Err(e) => {
-                    warn!("readdir entry error: {}", e);
-                    // Continue with other entries
                 }
-            }
-        }
 
-        reply.ok();
+                reply.ok();
+            },
+        );
     }
 
     fn fsync(
@@ -722,16 +1572,26 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("fsync: {:?}", path);
+        let _span = fuse_span("fsync", &path).entered();
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.flush(&path_for_async).await }) {
-            Ok(()) => reply.ok(),
-            Err(e) => {
-                error!("fsync error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino).await?;
+                connector.flush(&path_for_async).await
+            },
+            move |result| match result {
+                Ok(()) => reply.ok(),
+                Err(e) => {
+                    error!("fsync error for {:?}: {}", path, e);
+                    report_error(&errors, "fsync", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
     }
 
     fn flush(
@@ -739,9 +1599,13 @@ impl Filesystem for FuseAdapter {
         _req: &Request<'_>,
         ino: u64,
         _fh: u64,
-        _lock_owner: u64,
+        lock_owner: u64,
         reply: ReplyEmpty,
     ) {
+        // POSIX locks are dropped when their owner closes any descriptor for
+        // the file
+        self.locks.release_owner(ino, lock_owner);
+
         let path = match self.inode_to_path(ino) {
             Ok(p) => p,
             Err(e) => {
@@ -751,19 +1615,106 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("flush: {:?}", path);
+        let _span = fuse_span("flush", &path).entered();
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.flush(&path_for_async).await }) {
-            Ok(()) => reply.ok(),
-            Err(e) => {
-                error!("flush error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
+        let buffers = self.buffers.clone();
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                drain_writes(&buffers, ino).await?;
+                connector.flush(&path_for_async).await
+            },
+            move |result| match result {
+                Ok(()) => reply.ok(),
+                Err(e) => {
+                    error!("flush error for {:?}: {}", path, e);
+                    report_error(&errors, "flush", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
+    }
+
+    fn getlk(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+        reply: ReplyLock,
+    ) {
+        if self.locking == LockingMode::Deny {
+            reply.error(libc::ENOLCK);
+            return;
+        }
+        let lock = Lock {
+            owner: lock_owner,
+            start,
+            end,
+            typ,
+            pid,
+        };
+        match self.locks.conflict(ino, &lock) {
+            Some(held) => reply.locked(held.start, held.end, held.typ, held.pid),
+            None => reply.locked(start, end, libc::F_UNLCK, 0),
+        }
+    }
+
+    fn setlk(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+        sleep: bool,
+        reply: ReplyEmpty,
+    ) {
+        if self.locking == LockingMode::Deny {
+            reply.error(libc::ENOLCK);
+            return;
+        }
+        if ![libc::F_RDLCK, libc::F_WRLCK, libc::F_UNLCK].contains(&typ) || start > end {
+            reply.error(libc::EINVAL);
+            return;
+        }
+        let lock = Lock {
+            owner: lock_owner,
+            start,
+            end,
+            typ,
+            pid,
+        };
+        trace!("setlk: ino={} {:?} sleep={}", ino, lock, sleep);
+
+        if !sleep {
+            match self.locks.set(ino, lock) {
+                Ok(()) => reply.ok(),
+                Err(e) => reply.error(e),
             }
+            return;
         }
+        // A blocking lock waits as long as it takes, so it bypasses
+        // `spawn_op` and the operation timeout
+        let locks = self.locks.clone();
+        self.runtime.spawn(async move {
+            match locks.set_wait(ino, lock).await {
+                Ok(()) => reply.ok(),
+                Err(e) => reply.error(e),
+            }
+        });
     }
 
-    fn access(&mut self, _req: &Request<'_>, ino: u64, _mask: i32, reply: ReplyEmpty) {
+    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: ReplyEmpty) {
         // Check if file exists
         let path = match self.inode_to_path(ino) {
             Ok(p) => p,
@@ -773,12 +1724,39 @@ impl Filesystem for FuseAdapter {
             }
         };
 
+        let needed = if mask & libc::W_OK != 0 {
+            Permission::Write
+        } else {
+            Permission::Read
+        };
+        if let Err(e) = self.check_access(req, &path, needed) {
+            reply.error(e);
+            return;
+        }
+
+        let _span = fuse_span("access", &path).entered();
         let connector = self.connector.clone();
-        match self.run_async(async move { connector.exists(&path).await }) {
-            Ok(true) => reply.ok(),
-            Ok(false) => reply.error(libc::ENOENT),
-            Err(e) => reply.error(e.to_errno()),
+        if self.enforce_permissions && mask != libc::F_OK {
+            let owner = (self.uid, self.gid);
+            let caller = (req.uid(), req.gid());
+            self.spawn_op(
+                async move { check_mode(connector.as_ref(), &path, owner, caller, mask).await },
+                move |result| match result {
+                    Ok(()) => reply.ok(),
+                    Err(e) => reply.error(e.to_errno()),
+                },
+            );
+            return;
         }
+
+        self.spawn_op(
+            async move { connector.exists(&path).await },
+            move |result| match result {
+                Ok(true) => reply.ok(),
+                Ok(false) => reply.error(libc::ENOENT),
+                Err(e) => reply.error(e.to_errno()),
+            },
+        );
     }
 
     fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: fuser::ReplyStatfs) {
@@ -795,7 +1773,7 @@ impl Filesystem for FuseAdapter {
         );
     }
 
-    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+    fn readlink(&mut self, req: &Request<'_>, ino: u64, reply: ReplyData) {
         let path = match self.inode_to_path(ino) {
             Ok(p) => p,
             Err(e) => {
@@ -805,25 +1783,36 @@ impl Filesystem for FuseAdapter {
         };
 
         trace!("readlink: {:?} (ino={})", path, ino);
+        let _span = fuse_span("readlink", &path).entered();
+
+        if let Err(e) = self.check_access(req, &path, Permission::Read) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let path_for_async = path.clone();
-        match self.run_async(async move { connector.readlink(&path_for_async).await }) {
-            Ok(target) => {
-                // Return the target path as bytes
-                let target_bytes = target.as_os_str().as_encoded_bytes();
-                reply.data(target_bytes);
-            }
-            Err(e) => {
-                error!("readlink error for {:?}: {}", path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move { connector.readlink(&path_for_async).await },
+            move |result| match result {
+                Ok(target) => {
+                    // Return the target path as bytes
+                    let target_bytes = target.as_os_str().as_encoded_bytes();
+                    reply.data(target_bytes);
+                }
+                Err(e) => {
+                    error!("readlink error for {:?}: {}", path, e);
+                    report_error(&errors, "readlink", &path, &e);
+                    reply.error(e.to_errno());
+                }
+            },
+        );
     }
 
     fn symlink(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         parent: u64,
         link_name: &OsStr,
         target: &Path,
@@ -846,28 +1835,44 @@ impl Filesystem for FuseAdapter {
             }
         };
 
-        let link_path = parent_path.join(link_name);
+        let link_path = self.child_path(&parent_path, link_name);
         debug!("symlink: {:?} -> {:?}", link_path, target);
+        let _span = fuse_span("symlink", &link_path).entered();
+
+        if let Err(e) = self.check_access(req, &link_path, Permission::Write) {
+            reply.error(e);
+            return;
+        }
 
         let connector = self.connector.clone();
         let target_path = target.to_path_buf();
         let link_path_for_async = link_path.clone();
-        match self.run_async(async move {
-            connector
-                .symlink(&target_path, &link_path_for_async)
-                .await?;
-            connector.stat(&link_path_for_async).await
-        }) {
-            Ok(meta) => {
-                let ino = self.inodes.get_or_create_inode(&link_path);
-                let attr = metadata_to_attr(ino, &meta, self.uid, self.gid);
-                reply.entry(&ATTR_TTL, &attr, GENERATION);
-            }
-            Err(e) => {
-                error!("symlink error for {:?}: {}", link_path, e);
-                reply.error(e.to_errno());
-            }
-        }
+        let inodes = self.inodes.clone();
+        let (uid, gid) = (self.uid, self.gid);
+        let audit = self
+            .audit_event(req, "symlink", &link_path)
+            .map(|e| e.to(target));
+        let errors = self.errors.clone();
+        self.spawn_op(
+            async move {
+                connector
+                    .symlink(&target_path, &link_path_for_async)
+                    .await?;
+                connector.stat(&link_path_for_async).await
+            },
+            audited(audit, move |result| match result {
+                Ok(meta) => {
+                    let ino = inodes.lookup_inode(&link_path);
+                    let attr = metadata_to_attr(ino, &meta, uid, gid);
+                    reply.entry(&ATTR_TTL, &attr, GENERATION);
+                }
+                Err(e) => {
+                    error!("symlink error for {:?}: {}", link_path, e);
+                    report_error(&errors, "symlink", &link_path, &e);
+                    reply.error(e.to_errno());
+                }
+            }),
+        );
     }
 }
 
@@ -876,3 +1881,58 @@ impl Filesystem for FuseAdapter {
 pub fn current_time() -> SystemTime {
     SystemTime::now()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::StatusOverlayConfig;
+    use crate::overlay::StatusOverlay;
+
+    fn adapter() -> FuseAdapter {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let connector = Arc::new(StatusOverlay::new_failed(
+            "unused".to_string(),
+            StatusOverlayConfig::default(),
+        ));
+        FuseAdapter::new(connector, runtime.handle().clone(), None, None)
+    }
+
+    #[test]
+    fn test_operation_timeout() {
+        let adapter = adapter().with_operation_timeout(Duration::from_millis(50));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let slow = tx.clone();
+        adapter.spawn_op(
+            async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            },
+            move |result| slow.send(result.map(|()| 0)).unwrap(),
+        );
+        let err = rx.recv().unwrap().unwrap_err();
+        assert_eq!(err.to_errno(), libc::ETIMEDOUT);
+
+        adapter.spawn_op(async { Ok(7) }, move |result| tx.send(result).unwrap());
+        assert_eq!(rx.recv().unwrap().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_spawned_ops_run_concurrently() {
+        let adapter = adapter();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let slow = tx.clone();
+        adapter.spawn_op(
+            async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok("slow")
+            },
+            move |r| slow.send(r.unwrap()).unwrap(),
+        );
+        adapter.spawn_op(async { Ok("fast") }, move |r| tx.send(r.unwrap()).unwrap());
+
+        // The fast op must not wait behind the slow one
+        assert_eq!(rx.recv_timeout(Duration::from_secs(1)).unwrap(), "fast");
+    }
+}
@@ -0,0 +1,49 @@
+//! Unicode normalization of file names
+//!
+//! macOS clients send decomposed (NFD) names while most other systems store
+//! composed (NFC) ones, so the same name can arrive in two byte sequences.
+//! Names from requests and directory listings are converted to one form
+//! before they reach the connector or the inode table.
+
+use std::ffi::{OsStr, OsString};
+
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization as _};
+
+use crate::config::UnicodeNormalization;
+
+/// Convert `name` to the normalization form `form`
+///
+/// Names that aren't valid UTF-8 are returned unchanged.
+pub fn normalize_name(name: &OsStr, form: UnicodeNormalization) -> OsString {
+    let Some(s) = name.to_str() else {
+        return name.to_os_string();
+    };
+    match form {
+        UnicodeNormalization::None => name.to_os_string(),
+        UnicodeNormalization::Nfc if !is_nfc(s) => s.nfc().collect::<String>().into(),
+        UnicodeNormalization::Nfd if !is_nfd(s) => s.nfd().collect::<String>().into(),
+        _ => name.to_os_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    const COMPOSED: &str = "caf\u{e9}";
+    const DECOMPOSED: &str = "cafe\u{301}";
+
+    #[test]
+    fn test_normalize_name() {
+        for name in [COMPOSED, DECOMPOSED] {
+            let name = OsStr::new(name);
+            assert_eq!(normalize_name(name, UnicodeNormalization::Nfc), COMPOSED);
+            assert_eq!(normalize_name(name, UnicodeNormalization::Nfd), DECOMPOSED);
+            assert_eq!(normalize_name(name, UnicodeNormalization::None), name);
+        }
+
+        let invalid = OsStr::from_bytes(b"caf\xe9");
+        assert_eq!(normalize_name(invalid, UnicodeNormalization::Nfc), invalid);
+    }
+}
@@ -0,0 +1,102 @@
+//! Kernel page cache control
+//!
+//! Decides the `FOPEN_*` flags returned from `open` and `create`, which tell
+//! the kernel whether to keep pages it already cached for a file, drop them,
+//! or bypass the page cache entirely for that handle.
+
+use std::time::SystemTime;
+
+use dashmap::DashMap;
+use fuser::consts::{FOPEN_DIRECT_IO, FOPEN_KEEP_CACHE};
+
+use crate::config::PageCacheMode;
+use crate::connector::Metadata;
+
+/// Page cache policy for a mount
+#[derive(Default)]
+pub struct PageCache {
+    mode: PageCacheMode,
+    /// Size and mtime of each file when it was last opened (`Auto` only)
+    seen: DashMap<u64, (u64, SystemTime)>,
+}
+
+impl PageCache {
+    pub fn new(mode: PageCacheMode) -> Self {
+        Self {
+            mode,
+            seen: DashMap::new(),
+        }
+    }
+
+    /// Whether `open_flags` needs the file's current metadata
+    pub fn needs_metadata(&self) -> bool {
+        self.mode == PageCacheMode::Auto
+    }
+
+    /// Flags for a handle being opened on `ino`
+    ///
+    /// In `Auto` mode, `meta` is the file's current metadata; without it the
+    /// cache is dropped.
+    pub fn open_flags(&self, ino: u64, meta: Option<&Metadata>) -> u32 {
+        match self.mode {
+            PageCacheMode::Invalidate => 0,
+            PageCacheMode::Keep => FOPEN_KEEP_CACHE,
+            PageCacheMode::DirectIo => FOPEN_DIRECT_IO,
+            PageCacheMode::Auto => {
+                let Some(meta) = meta else {
+                    return 0;
+                };
+                let version = (meta.size, meta.mtime);
+                match self.seen.insert(ino, version) {
+                    Some(previous) if previous == version => FOPEN_KEEP_CACHE,
+                    _ => 0,
+                }
+            }
+        }
+    }
+
+    /// Drop what's remembered about an inode the kernel has forgotten
+    pub fn forget(&self, ino: u64) {
+        self.seen.remove(&ino);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_auto_keeps_cache_until_file_changes() {
+        let cache = PageCache::new(PageCacheMode::Auto);
+        let meta = Metadata::file(10, UNIX_EPOCH);
+
+        assert_eq!(cache.open_flags(2, Some(&meta)), 0);
+        assert_eq!(cache.open_flags(2, Some(&meta)), FOPEN_KEEP_CACHE);
+
+        let touched = Metadata::file(10, UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(cache.open_flags(2, Some(&touched)), 0);
+        assert_eq!(cache.open_flags(2, Some(&touched)), FOPEN_KEEP_CACHE);
+
+        let grown = Metadata::file(11, UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(cache.open_flags(2, Some(&grown)), 0);
+
+        cache.forget(2);
+        assert_eq!(cache.open_flags(2, Some(&grown)), 0);
+        assert_eq!(cache.open_flags(2, None), 0);
+    }
+
+    #[test]
+    fn test_fixed_modes() {
+        let meta = Metadata::file(10, UNIX_EPOCH);
+        assert_eq!(PageCache::default().open_flags(2, Some(&meta)), 0);
+        assert_eq!(
+            PageCache::new(PageCacheMode::Keep).open_flags(2, None),
+            FOPEN_KEEP_CACHE
+        );
+        assert_eq!(
+            PageCache::new(PageCacheMode::DirectIo).open_flags(2, None),
+            FOPEN_DIRECT_IO
+        );
+    }
+}
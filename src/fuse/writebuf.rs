@@ -0,0 +1,309 @@
+//! Coalescing of sequential writes
+//!
+//! The kernel splits a large write into requests of at most 128KB, and each
+//! one would otherwise be a separate connector write with its own locking
+//! and metadata invalidation. When a mount has `write_buffer` set, writes
+//! that continue where the previous one ended are appended to a per-file
+//! buffer instead and written out together once the buffer is full, after
+//! `interval`, or when the file is flushed, synced or released.
+//!
+//! Anything else that touches a file's contents or size drains its buffer
+//! first, so reads and stats never see stale data. A failed write-out is
+//! reported by the next operation that drains the buffer (usually `close`).
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use tokio::sync::Mutex;
+use tracing::{trace, warn};
+
+use crate::connector::Connector;
+use crate::error::{FuseAdapterError, Result};
+
+/// Buffer size used when `write_buffer.size` isn't set (1MB)
+pub const DEFAULT_WRITE_BUFFER_SIZE: u64 = 1024 * 1024;
+
+/// Data buffered for one file
+#[derive(Default)]
+struct Pending {
+    path: PathBuf,
+    /// File offset of the first buffered byte
+    offset: u64,
+    data: Vec<u8>,
+    /// Incremented each time the buffer is started, so a timed write-out
+    /// can tell whether the data it was scheduled for is still there
+    generation: u64,
+    /// Error from a write-out nobody has been told about yet
+    error: Option<FuseAdapterError>,
+}
+
+impl Pending {
+    /// Write the buffered data to the connector
+    async fn write_out(&mut self, connector: &dyn Connector) -> Result<()> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let data = std::mem::take(&mut self.data);
+        trace!(
+            "write buffer: writing {} bytes to {:?} at {}",
+            data.len(),
+            self.path,
+            self.offset
+        );
+        connector.write(&self.path, self.offset, &data).await?;
+        Ok(())
+    }
+
+    /// Write out buffered data, reporting any earlier failure first
+    async fn drain(&mut self, connector: &dyn Connector) -> Result<()> {
+        if let Some(e) = self.error.take() {
+            self.data.clear();
+            return Err(e);
+        }
+        self.write_out(connector).await
+    }
+}
+
+/// Write buffers for every file with buffered data
+pub struct WriteBuffers {
+    connector: Arc<dyn Connector>,
+    max_size: usize,
+    interval: Duration,
+    files: DashMap<u64, Arc<Mutex<Pending>>>,
+}
+
+impl WriteBuffers {
+    pub fn new(connector: Arc<dyn Connector>, max_size: usize, interval: Duration) -> Self {
+        Self {
+            connector,
+            max_size,
+            interval,
+            files: DashMap::new(),
+        }
+    }
+
+    fn entry(&self, ino: u64) -> Arc<Mutex<Pending>> {
+        self.files.entry(ino).or_default().clone()
+    }
+
+    /// Buffer a write of `data` at `offset` to `path` (inode `ino`)
+    ///
+    /// Writes that don't continue the buffered data drain the buffer first,
+    /// and writes at least as large as the buffer go straight through.
+    pub async fn write(
+        self: &Arc<Self>,
+        ino: u64,
+        path: &Path,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<u64> {
+        let entry = self.entry(ino);
+        let mut pending = entry.lock().await;
+
+        let contiguous = pending.path == path
+            && pending.offset + pending.data.len() as u64 == offset
+            && pending.error.is_none();
+        if !contiguous || pending.data.len() + data.len() > self.max_size {
+            pending.drain(self.connector.as_ref()).await?;
+        }
+        if data.len() >= self.max_size {
+            return self.connector.write(path, offset, data).await;
+        }
+
+        if pending.data.is_empty() {
+            pending.path = path.to_path_buf();
+            pending.offset = offset;
+            pending.generation += 1;
+            self.schedule_write_out(ino, pending.generation);
+        }
+        pending.data.extend_from_slice(data);
+        Ok(data.len() as u64)
+    }
+
+    /// Write out `ino`'s buffer after `interval` unless it was drained first
+    fn schedule_write_out(self: &Arc<Self>, ino: u64, generation: u64) {
+        let buffers = Arc::downgrade(self);
+        let interval = self.interval;
+        tokio::spawn(async move {
+            tokio::time::sleep(interval).await;
+            let Some(buffers) = buffers.upgrade() else {
+                return;
+            };
+            let Some(entry) = buffers.files.get(&ino).map(|e| e.clone()) else {
+                return;
+            };
+            let mut pending = entry.lock().await;
+            if pending.generation != generation || pending.error.is_some() {
+                return;
+            }
+            if let Err(e) = pending.write_out(buffers.connector.as_ref()).await {
+                warn!("Buffered write to {:?} failed: {}", pending.path, e);
+                pending.error = Some(e);
+            }
+        });
+    }
+
+    /// Write out whatever is buffered for `ino`
+    ///
+    /// Fails if this or an earlier write-out failed.
+    pub async fn drain(&self, ino: u64) -> Result<()> {
+        let Some(entry) = self.files.get(&ino).map(|e| e.clone()) else {
+            return Ok(());
+        };
+        let mut pending = entry.lock().await;
+        pending.drain(self.connector.as_ref()).await
+    }
+
+    /// Write out every buffer (before renames, which can move buffered
+    /// files by moving a parent directory)
+    pub async fn drain_all(&self) -> Result<()> {
+        let inos: Vec<u64> = self.files.iter().map(|e| *e.key()).collect();
+        let mut result = Ok(());
+        for ino in inos {
+            if let Err(e) = self.drain(ino).await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Forget an inode the kernel no longer references
+    pub fn forget(&self, ino: u64) {
+        self.files.remove(&ino);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::{Capabilities, DirEntryStream, Metadata};
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use std::sync::Mutex as StdMutex;
+    use std::time::UNIX_EPOCH;
+
+    /// Records each write it receives
+    #[derive(Default)]
+    struct RecordingConnector {
+        writes: StdMutex<Vec<(PathBuf, u64, usize)>>,
+        fail: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait]
+    impl Connector for RecordingConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::full()
+        }
+
+        async fn stat(&self, _path: &Path) -> Result<Metadata> {
+            Ok(Metadata::file(0, UNIX_EPOCH))
+        }
+
+        async fn read(&self, _path: &Path, _offset: u64, _size: u32) -> Result<Bytes> {
+            Ok(Bytes::new())
+        }
+
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            if self.fail.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(FuseAdapterError::Backend("write failed".to_string()));
+            }
+            self.writes
+                .lock()
+                .unwrap()
+                .push((path.to_path_buf(), offset, data.len()));
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, _path: &Path) -> DirEntryStream {
+            Box::pin(futures::stream::empty())
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn buffers(
+        max_size: usize,
+        interval: Duration,
+    ) -> (Arc<WriteBuffers>, Arc<RecordingConnector>) {
+        let connector = Arc::new(RecordingConnector::default());
+        let buffers = Arc::new(WriteBuffers::new(connector.clone(), max_size, interval));
+        (buffers, connector)
+    }
+
+    #[tokio::test]
+    async fn test_sequential_writes_are_coalesced() {
+        let (buffers, connector) = buffers(1000, Duration::from_secs(3600));
+        let path = Path::new("/file");
+
+        for i in 0..9 {
+            buffers.write(2, path, i * 100, &[0; 100]).await.unwrap();
+        }
+        assert!(connector.writes.lock().unwrap().is_empty());
+
+        // Filling the buffer writes out what came before
+        buffers.write(2, path, 900, &[0; 200]).await.unwrap();
+        // A write elsewhere in the file starts a new buffer
+        buffers.write(2, path, 5000, &[0; 10]).await.unwrap();
+        // Large writes go straight through
+        buffers.write(2, path, 5010, &[0; 1000]).await.unwrap();
+        buffers.drain(2).await.unwrap();
+
+        assert_eq!(
+            *connector.writes.lock().unwrap(),
+            vec![
+                (path.to_path_buf(), 0, 900),
+                (path.to_path_buf(), 900, 200),
+                (path.to_path_buf(), 5000, 10),
+                (path.to_path_buf(), 5010, 1000),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interval_writes_out_and_errors_are_deferred() {
+        let (buffers, connector) = buffers(1000, Duration::from_millis(20));
+        let path = Path::new("/file");
+
+        buffers.write(2, path, 0, b"hello").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(connector.writes.lock().unwrap().len(), 1);
+
+        connector
+            .fail
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        buffers.write(2, path, 5, b"world").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // The failure is reported once, by the next drain
+        assert!(buffers.drain(2).await.is_err());
+        assert!(buffers.drain(2).await.is_ok());
+    }
+}
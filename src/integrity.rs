@@ -0,0 +1,267 @@
+//! End-to-end content integrity checking
+//!
+//! Backends report a checksum for each object (see `Connector::content_hash`):
+//! a SHA-256 stored when fuse-adapter wrote the object, or failing that one
+//! the backend computed itself, such as an S3 CRC32C checksum or ETag or a
+//! Google Drive `md5Checksum`. When a cache layer fills from the backend it
+//! hands the downloaded bytes to an `IntegrityMonitor`, which recomputes the
+//! same checksum and records any mismatch so it can be surfaced through the
+//! status overlay.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use tracing::{trace, warn};
+
+use crate::config::IntegrityConfig;
+use crate::connector::Connector;
+use crate::error::{FuseAdapterError, Result};
+
+/// Compute the lowercase hex SHA-256 of some data
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Compute the lowercase hex MD5 of some data
+pub fn md5_hex(data: &[u8]) -> String {
+    hex::encode(Md5::digest(data))
+}
+
+/// A checksum of a file's full content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentHash {
+    /// SHA-256 stored by fuse-adapter when it wrote the file (lowercase hex)
+    Sha256(String),
+    /// MD5 computed by the backend (lowercase hex)
+    Md5(String),
+    /// CRC32C computed by the backend
+    Crc32c(u32),
+}
+
+impl ContentHash {
+    /// Compute a checksum of the same kind over `data`
+    pub fn compute(&self, data: &[u8]) -> ContentHash {
+        match self {
+            ContentHash::Sha256(_) => ContentHash::Sha256(sha256_hex(data)),
+            ContentHash::Md5(_) => ContentHash::Md5(md5_hex(data)),
+            ContentHash::Crc32c(_) => ContentHash::Crc32c(crc32c::crc32c(data)),
+        }
+    }
+
+    /// Whether `data` has this checksum
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match (self, &self.compute(data)) {
+            (ContentHash::Sha256(a), ContentHash::Sha256(b))
+            | (ContentHash::Md5(a), ContentHash::Md5(b)) => a.eq_ignore_ascii_case(b),
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentHash::Sha256(hash) => write!(f, "sha256:{}", hash),
+            ContentHash::Md5(hash) => write!(f, "md5:{}", hash),
+            ContentHash::Crc32c(crc) => write!(f, "crc32c:{:08x}", crc),
+        }
+    }
+}
+
+/// A single recorded verification failure
+#[derive(Debug, Clone)]
+struct IntegrityFailure {
+    timestamp: DateTime<Utc>,
+    path: String,
+    expected: String,
+    actual: String,
+}
+
+impl IntegrityFailure {
+    fn format(&self) -> String {
+        format!(
+            "[{}] {}: expected {} got {}\n",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
+            self.path,
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+/// Verifies downloaded content against stored hashes and tracks results
+pub struct IntegrityMonitor {
+    config: IntegrityConfig,
+    /// Files whose content matched the stored hash
+    verified: AtomicU64,
+    /// Files that had no stored hash to check against
+    unverified: AtomicU64,
+    /// Files whose content did not match the stored hash
+    mismatches: AtomicU64,
+    /// Most recent failures (ring buffer)
+    failures: Mutex<VecDeque<IntegrityFailure>>,
+}
+
+impl IntegrityMonitor {
+    /// Create a new monitor
+    pub fn new(config: IntegrityConfig) -> Self {
+        Self {
+            config,
+            verified: AtomicU64::new(0),
+            unverified: AtomicU64::new(0),
+            mismatches: AtomicU64::new(0),
+            failures: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Verify the full content of a file against the backend's checksum.
+    ///
+    /// `etag` is the version the data was read as. If the file has changed
+    /// since, there's nothing to compare against and it counts as unverified.
+    ///
+    /// Returns an error on mismatch only when `fail_on_mismatch` is set;
+    /// otherwise the mismatch is recorded and the data is served anyway.
+    pub async fn verify<C: Connector + ?Sized>(
+        &self,
+        backend: &C,
+        path: &Path,
+        etag: Option<&str>,
+        data: &[u8],
+    ) -> Result<()> {
+        let expected = match backend.content_hash(path, etag).await {
+            Ok(Some(hash)) => hash,
+            Ok(None) => {
+                self.unverified.fetch_add(1, Ordering::Relaxed);
+                trace!("integrity: no checksum for {:?}", path);
+                return Ok(());
+            }
+            Err(e) => {
+                // Can't check, but the read itself succeeded
+                warn!("integrity: failed to fetch hash for {:?}: {}", path, e);
+                self.unverified.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        };
+
+        self.check(path, &expected, data)
+    }
+
+    /// Compare data against an expected checksum and record the outcome
+    fn check(&self, path: &Path, expected: &ContentHash, data: &[u8]) -> Result<()> {
+        if expected.matches(data) {
+            self.verified.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        let actual = expected.compute(data);
+
+        self.mismatches.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            "integrity: hash mismatch for {:?}: expected {} got {}",
+            path, expected, actual
+        );
+
+        {
+            let mut failures = self.failures.lock().unwrap();
+            failures.push_back(IntegrityFailure {
+                timestamp: Utc::now(),
+                path: path.display().to_string(),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+            while failures.len() > self.config.max_failures {
+                failures.pop_front();
+            }
+        }
+
+        if self.config.fail_on_mismatch {
+            return Err(FuseAdapterError::IntegrityMismatch(
+                path.display().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Number of files that failed verification
+    pub fn mismatch_count(&self) -> u64 {
+        self.mismatches.load(Ordering::Relaxed)
+    }
+
+    /// Human-readable summary and recent failures
+    pub fn report(&self) -> String {
+        let mut out = format!(
+            "verified: {}\nunverified: {}\nmismatches: {}\n",
+            self.verified.load(Ordering::Relaxed),
+            self.unverified.load(Ordering::Relaxed),
+            self.mismatches.load(Ordering::Relaxed),
+        );
+        let failures = self.failures.lock().unwrap();
+        for failure in failures.iter() {
+            out.push_str(&failure.format());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_backend_checksums() {
+        let md5 = ContentHash::Md5("5D41402ABC4B2A76B9719D911017C592".to_string());
+        assert!(md5.matches(b"hello"));
+        assert!(!md5.matches(b"jello"));
+
+        let crc = ContentHash::Crc32c(0xe306_9283);
+        assert!(crc.matches(b"123456789"));
+        assert_eq!(crc.compute(b"").to_string(), "crc32c:00000000");
+    }
+
+    #[test]
+    fn test_check_records_mismatch() {
+        let monitor = IntegrityMonitor::new(IntegrityConfig::default());
+        let path = Path::new("/file.txt");
+
+        let expected = ContentHash::Sha256(sha256_hex(b"hello"));
+
+        assert!(monitor.check(path, &expected, b"hello").is_ok());
+        assert!(monitor.check(path, &expected, b"jello").is_ok());
+
+        assert_eq!(monitor.mismatch_count(), 1);
+        let report = monitor.report();
+        assert!(report.contains("verified: 1"));
+        assert!(report.contains("mismatches: 1"));
+        assert!(report.contains("/file.txt"));
+    }
+
+    #[test]
+    fn test_fail_on_mismatch() {
+        let monitor = IntegrityMonitor::new(IntegrityConfig {
+            fail_on_mismatch: true,
+            max_failures: 1,
+        });
+        let path = Path::new("/file.txt");
+        let expected = ContentHash::Md5("deadbeef".to_string());
+
+        assert!(matches!(
+            monitor.check(path, &expected, b"hello"),
+            Err(FuseAdapterError::IntegrityMismatch(_))
+        ));
+        let _ = monitor.check(path, &expected, b"again");
+        assert_eq!(monitor.failures.lock().unwrap().len(), 1);
+    }
+}
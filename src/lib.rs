@@ -21,7 +21,7 @@
 //!
 //! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
 //! // Load configuration
-//! let config = Config::from_file(&"config.yaml".into())?;
+//! let config = Config::from_file("config.yaml".as_ref())?;
 //!
 //! // Create mount manager with tokio runtime
 //! let handle = tokio::runtime::Handle::current();
@@ -33,14 +33,26 @@
 //! # }
 //! ```
 
+pub mod audit;
 pub mod auth;
+pub mod builder;
 pub mod cache;
 pub mod config;
 pub mod connector;
+pub mod control;
+pub mod daemon;
 pub mod env;
 pub mod error;
+pub mod events;
 pub mod fuse;
+pub mod integrity;
+pub mod logging;
 pub mod mount;
 pub mod overlay;
+pub mod quota;
+pub mod systemd;
+pub mod telemetry;
+pub mod write_access;
 
+pub use builder::{FuseAdapterBuilder, MountedAdapter};
 pub use error::{FuseAdapterError, Result};
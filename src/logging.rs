@@ -0,0 +1,176 @@
+//! Log file output with size and time based rotation
+//!
+//! The current log always lives at the configured path. On rotation it is
+//! renamed to `<path>.1`, older files move up one number, and anything past
+//! the configured count is deleted.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::cache::parse_size;
+use crate::config::{LogRotationConfig, RotationInterval};
+
+/// A log file that rotates itself as it is written
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    /// Bytes in the current file
+    size: u64,
+    max_size: Option<u64>,
+    interval: RotationInterval,
+    /// Rotated files to keep
+    keep: usize,
+    /// Period the current file was started in, when rotating by time
+    period: Option<String>,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending; an existing file counts towards the size
+    /// limit and is rotated on the first write if it is from an earlier period
+    pub fn open(path: &Path, rotation: &LogRotationConfig) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        let started = match metadata.modified() {
+            Ok(modified) if metadata.len() > 0 => DateTime::<Local>::from(modified),
+            _ => Local::now(),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size: metadata.len(),
+            max_size: rotation.max_size.as_deref().and_then(parse_size),
+            interval: rotation.interval,
+            keep: rotation.keep.max(1),
+            period: period(rotation.interval, started),
+        })
+    }
+
+    /// Rotate first if `incoming` more bytes written at `now` would go past
+    /// the size limit or into a new period
+    fn rotate_if_needed(&mut self, incoming: usize, now: DateTime<Local>) -> io::Result<()> {
+        let too_big = self
+            .max_size
+            .is_some_and(|max| self.size > 0 && self.size + incoming as u64 > max);
+        let current = period(self.interval, now);
+        if too_big || current != self.period {
+            self.rotate()?;
+            self.period = current;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        match fs::remove_file(self.numbered(self.keep)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        for n in (1..self.keep).rev() {
+            let from = self.numbered(n);
+            if from.exists() {
+                fs::rename(&from, self.numbered(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.numbered(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.rotate_if_needed(buf.len(), Local::now())?;
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Label for the rotation period containing `time`; files rotate whenever
+/// it changes
+fn period(interval: RotationInterval, time: DateTime<Local>) -> Option<String> {
+    match interval {
+        RotationInterval::Never => None,
+        RotationInterval::Hourly => Some(time.format("%Y-%m-%d %H").to_string()),
+        RotationInterval::Daily => Some(time.format("%Y-%m-%d").to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use tempfile::TempDir;
+
+    fn read(path: PathBuf) -> String {
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_rotates_by_size_and_keeps_limit() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("adapter.log");
+        let rotation = LogRotationConfig {
+            max_size: Some("10".to_string()),
+            interval: RotationInterval::Never,
+            keep: 2,
+        };
+        let mut log = RotatingFile::open(&path, &rotation).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        log.flush().unwrap();
+
+        assert_eq!(read(path.clone()), "fourth\n");
+        assert_eq!(read(dir.path().join("adapter.log.1")), "third\n");
+        assert_eq!(read(dir.path().join("adapter.log.2")), "second\n");
+        assert!(!dir.path().join("adapter.log.3").exists());
+
+        // Reopening picks up the existing file's size
+        let mut log = RotatingFile::open(&path, &rotation).unwrap();
+        log.write_all(b"fifth\n").unwrap();
+        assert_eq!(read(path), "fifth\n");
+        assert_eq!(read(dir.path().join("adapter.log.1")), "fourth\n");
+    }
+
+    #[test]
+    fn test_rotates_on_new_period() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("adapter.log");
+        let rotation = LogRotationConfig {
+            max_size: None,
+            interval: RotationInterval::Daily,
+            keep: 5,
+        };
+        let mut log = RotatingFile::open(&path, &rotation).unwrap();
+        log.write_all(b"today\n").unwrap();
+
+        let now = Local::now();
+        log.rotate_if_needed(8, now).unwrap();
+        assert!(!dir.path().join("adapter.log.1").exists());
+
+        log.rotate_if_needed(8, now + Duration::days(1)).unwrap();
+        log.file.write_all(b"tomorrow\n").unwrap();
+        assert_eq!(read(path), "tomorrow\n");
+        assert_eq!(read(dir.path().join("adapter.log.1")), "today\n");
+    }
+}
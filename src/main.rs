@@ -1,54 +1,81 @@
 //! fuse-adapter daemon entry point
 
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-
-use tracing::{debug, error, info};
-use tracing_subscriber::EnvFilter;
-
-use fuse_adapter::cache::filesystem::{FilesystemCache, FilesystemCacheConfig};
-use fuse_adapter::cache::memory::{MemoryCache, MemoryCacheConfig};
-use fuse_adapter::cache::none::NoCache;
-use fuse_adapter::cache::CacheConfig;
-use fuse_adapter::config::{Config, ConnectorConfig, ErrorMode};
-use fuse_adapter::connector::gdrive::GDriveConnector;
-use fuse_adapter::connector::s3::S3Connector;
-use fuse_adapter::connector::Connector;
+use std::sync::{Arc, Mutex};
+
+use clap::Parser;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter};
+
+use fuse_adapter::builder::FuseAdapterBuilder;
+use fuse_adapter::cache::peer::PeerSharing;
+use fuse_adapter::config::{
+    Config, ConfigFormat, ConfigOverride, ConfigSource, ErrorMode, LogFormat, LogOutput,
+    MountConfig,
+};
+use fuse_adapter::connector::throttle::BandwidthLimits;
+use fuse_adapter::control::{Command, ControlSocket, Request};
+use fuse_adapter::daemon::{self, PidFile};
+use fuse_adapter::logging::RotatingFile;
 use fuse_adapter::mount::MountManager;
-use fuse_adapter::overlay::StatusOverlay;
-
-/// Print usage information
-fn print_usage() {
-    eprintln!("Usage: fuse-adapter <config.yaml>");
-    eprintln!();
-    eprintln!("fuse-adapter - A FUSE filesystem framework with pluggable connectors");
-    eprintln!();
-    eprintln!("Arguments:");
-    eprintln!("  config.yaml    Path to configuration file");
-    eprintln!();
-    eprintln!("Example:");
-    eprintln!("  fuse-adapter /etc/fuse-adapter/config.yaml");
+use fuse_adapter::systemd::Notifier;
+use fuse_adapter::telemetry::Telemetry;
+
+/// fuse-adapter - A FUSE filesystem framework with pluggable connectors
+#[derive(Debug, Parser)]
+#[command(name = "fuse-adapter", version, about)]
+struct Cli {
+    /// Path to configuration file, or a directory of them
+    config: PathBuf,
+
+    /// Detach from the terminal and run in the background
+    #[arg(long)]
+    daemon: bool,
+
+    /// Write the process ID to this file (removed on exit)
+    #[arg(long, value_name = "PATH")]
+    pid_file: Option<PathBuf>,
+
+    /// Write logs to this file instead of stderr (overrides logging.file)
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Override a config value, e.g. `mounts.0.cache.type=none`; may be
+    /// repeated, and wins over FUSE_ADAPTER__* environment variables
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    overrides: Vec<ConfigOverride>,
+
+    /// Config file format: yaml, json or toml (default: from the extension)
+    #[arg(long, value_name = "FORMAT")]
+    format: Option<ConfigFormat>,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Install the default crypto provider for rustls
     rustls::crypto::ring::default_provider()
         .install_default()
         .expect("Failed to install rustls crypto provider");
 
-    // Parse arguments
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        print_usage();
-        std::process::exit(1);
+    let cli = Cli::parse();
+
+    // Resolve the config path up front; daemonizing changes directory and
+    // SIGHUP re-reads it later
+    let config_path = std::fs::canonicalize(&cli.config).unwrap_or_else(|_| cli.config.clone());
+    let mut source = ConfigSource::new(config_path);
+    if let Some(format) = cli.format {
+        source.format = format;
     }
 
-    let config_path = PathBuf::from(&args[1]);
+    // Environment overrides first, so --set wins; both are reapplied on reload
+    source.overrides = ConfigOverride::from_env();
+    source.overrides.extend(cli.overrides.iter().cloned());
 
-    // Load configuration
-    let config = match Config::from_file(&config_path) {
+    // Load and validate configuration
+    let mut config = match Config::load(&source) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Failed to load config: {}", e);
@@ -56,124 +83,146 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // Validate configuration
-    if let Err(e) = config.validate() {
-        eprintln!("Configuration error: {}", e);
-        std::process::exit(1);
+    if let Some(path) = &cli.log_file {
+        config.logging.file = Some(path.clone());
     }
 
-    // Initialize logging
-    let filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.logging.level));
+    // Open the log file before detaching so errors still reach the terminal
+    let log_file = match (config.logging.output(), &config.logging.file) {
+        (LogOutput::File, Some(path)) => match RotatingFile::open(path, &config.logging.rotation) {
+            Ok(f) => Some(f),
+            Err(e) => {
+                eprintln!("Failed to open log file {:?}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
+    if cli.daemon {
+        if log_file.is_none() {
+            eprintln!("Warning: running as a daemon without a log file; logs will be discarded");
+        }
+        // Must happen before the tokio runtime starts its threads
+        daemon::daemonize()?;
+    }
+
+    let _pid_file = match &cli.pid_file {
+        Some(path) => match PidFile::create(path) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                eprintln!("Failed to write PID file: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Initialize logging; the filter is reloadable so SIGHUP can change the level
+    let (filter, log_handle) = reload::Layer::new(log_filter(&config.logging.level));
+    let ansi = log_file.is_none() && config.logging.format == LogFormat::Text;
+    let writer = match log_file {
+        Some(f) => BoxMakeWriter::new(Mutex::new(f)),
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+    let output = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    let output = match config.logging.format {
+        LogFormat::Text => output.boxed(),
+        LogFormat::Json => output.json().boxed(),
+    };
+    // Span export runs on its own thread, so it starts before the runtime
+    let telemetry = match Telemetry::start(&config.tracing) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Failed to set up tracing: {}", e);
+            std::process::exit(1);
+        }
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .with(telemetry.as_ref().map(|t| t.layer()))
+        .init();
 
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let result = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(source, config, log_handle));
+    // Flush spans still waiting to be exported
+    drop(telemetry);
+    result
+}
 
+/// Mount everything and serve until a shutdown signal arrives
+async fn run<S>(
+    source: ConfigSource,
+    config: Config,
+    log_handle: reload::Handle<EnvFilter, S>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    S: 'static,
+{
     info!("fuse-adapter starting");
-    info!("Loaded configuration from {:?}", config_path);
+    info!("Loaded configuration from {:?}", source.path);
 
     // Create mount manager
     let handle = tokio::runtime::Handle::current();
     let manager = Arc::new(MountManager::new(handle.clone()));
 
-    // Set up signal handling for graceful shutdown
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    let m = manager.clone();
+    // Set up signal handling before mounting so an early signal isn't lost
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sighup = signal(SignalKind::hangup())?;
+    let mut sigusr1 = signal(SignalKind::user_defined1())?;
 
-    ctrlc::set_handler(move || {
-        info!("Received shutdown signal");
-        r.store(false, Ordering::SeqCst);
-        m.unmount_all();
-    })?;
-
-    // Mount all configured filesystems
-    for mount_config in &config.mounts {
-        info!("Setting up mount at {:?}", mount_config.path);
-
-        // Use per-mount error_mode (already resolved from global default)
-        let error_mode = mount_config.error_mode;
-        let has_status_overlay = mount_config.status_overlay.is_some();
-
-        // Try to create connector + cache
-        let connector_result: Result<Arc<dyn Connector>, String> = match &mount_config.connector {
-            ConnectorConfig::S3(s3_config) => match S3Connector::new(s3_config.clone()).await {
-                Ok(s3) => match wrap_with_cache(s3, &mount_config.cache) {
-                    Ok(c) => Ok(c),
-                    Err(e) => Err(format!("Failed to create cache: {}", e)),
-                },
-                Err(e) => Err(format!("Failed to create S3 connector: {}", e)),
-            },
-            ConnectorConfig::GDrive(gdrive_config) => {
-                match GDriveConnector::new(gdrive_config.clone()).await {
-                    Ok(gdrive) => match wrap_with_cache(gdrive, &mount_config.cache) {
-                        Ok(c) => Ok(c),
-                        Err(e) => Err(format!("Failed to create cache: {}", e)),
-                    },
-                    Err(e) => Err(format!("Failed to create GDrive connector: {}", e)),
-                }
+    // Admin commands arrive over the control socket, if configured
+    let mut control = match &config.control_socket {
+        Some(path) => match ControlSocket::bind(path) {
+            Ok(socket) => {
+                info!("Listening for admin commands on {:?}", path);
+                Some(socket)
             }
-        };
-
-        // Handle connector creation result
-        let connector: Arc<dyn Connector> = match connector_result {
-            Ok(c) => {
-                // Wrap with status overlay if configured
-                if let Some(ref overlay_config) = mount_config.status_overlay {
-                    Arc::new(StatusOverlay::new(c, overlay_config.clone()))
-                } else {
-                    c
-                }
+            Err(e) => {
+                error!("Failed to open control socket {:?}: {}", path, e);
+                std::process::exit(1);
             }
-            Err(init_error) => {
-                error!(
-                    "Connector failed for {:?}: {}",
-                    mount_config.path, init_error
-                );
-
-                // Can we mount with failed connector? Only if status_overlay is enabled and error_mode is Continue
-                if has_status_overlay && error_mode == ErrorMode::Continue {
-                    let overlay_config = mount_config.status_overlay.as_ref().unwrap();
-                    Arc::new(StatusOverlay::new_failed(
-                        init_error,
-                        overlay_config.clone(),
-                    ))
-                } else {
-                    if error_mode == ErrorMode::Exit {
+        },
+        None => None,
+    };
+
+    // Global bandwidth limits are shared by every mount and updated on reload
+    let global_limits = BandwidthLimits::from_config(config.rate_limit.as_ref());
+
+    // Cached content is shared with peers by every mount; changes to the
+    // peer settings take effect on restart
+    let peers = match &config.cache_peers {
+        Some(peers_config) => {
+            let peers = Arc::new(PeerSharing::from_config(peers_config));
+            if let Some(addr) = peers_config.listen {
+                match tokio::net::TcpListener::bind(addr).await {
+                    Ok(listener) => {
+                        tokio::spawn(peers.clone().serve(listener));
+                    }
+                    Err(e) => {
+                        error!("Failed to listen for cache peers on {}: {}", addr, e);
                         std::process::exit(1);
                     }
-                    continue; // Skip mount
-                }
-            }
-        };
-
-        // Create mount point directory if it doesn't exist
-        if !mount_config.path.exists() {
-            debug!("Creating mount point directory {:?}", mount_config.path);
-            if let Err(e) = std::fs::create_dir_all(&mount_config.path) {
-                error!(
-                    "Failed to create mount point {:?}: {}",
-                    mount_config.path, e
-                );
-                if error_mode == ErrorMode::Exit {
-                    std::process::exit(1);
                 }
-                continue;
             }
+            Some(peers)
         }
+        None => None,
+    };
 
-        // Mount the filesystem
-        if let Err(e) = manager.mount(
-            mount_config.path.clone(),
-            connector,
-            mount_config.read_only,
-            mount_config.uid,
-            mount_config.gid,
-        ) {
-            error!("Failed to mount {:?}: {}", mount_config.path, e);
-            if error_mode == ErrorMode::Exit {
+    // Mount all configured filesystems
+    for mount_config in &config.mounts {
+        if let Err(e) = setup_mount(&manager, mount_config, &global_limits, peers.as_ref()).await {
+            error!("{}", e);
+            if mount_config.error_mode == ErrorMode::Exit {
                 std::process::exit(1);
             }
-            continue;
         }
     }
 
@@ -185,66 +234,278 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("{} filesystem(s) mounted successfully", manager.count());
     info!("Press Ctrl+C to unmount and exit");
 
-    // Wait for shutdown signal
-    while running.load(Ordering::SeqCst) {
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    // Tell systemd (when started as a Type=notify service) the mounts are up
+    let notifier = Notifier::from_env().map(Arc::new);
+    if let Some(notifier) = &notifier {
+        notifier.ready(&manager);
+        notifier.spawn(&manager);
+    }
+
+    // Wait for shutdown signal, reloading the config on SIGHUP. Reloads
+    // and mount changes over the control socket take turns holding the
+    // current config
+    let config = Arc::new(tokio::sync::Mutex::new(config));
+    loop {
+        tokio::select! {
+            _ = sigint.recv() => break,
+            _ = sigterm.recv() => break,
+            _ = sighup.recv() => {
+                info!("Received SIGHUP, reloading {:?}", source.path);
+                // Waits for any mount change in progress, so it runs on its
+                // own task too
+                let (source, config, manager) = (source.clone(), config.clone(), manager.clone());
+                let (global_limits, peers, notifier) = (global_limits.clone(), peers.clone(), notifier.clone());
+                let log_handle = log_handle.clone();
+                tokio::spawn(async move {
+                    let mut config = config.lock().await;
+                    if let Some(new_config) = reload_config(&source, &config, &manager, &global_limits, peers.as_ref(), &log_handle).await {
+                        *config = new_config;
+                    }
+                    if let Some(notifier) = &notifier {
+                        notifier.report(&manager);
+                    }
+                });
+            }
+            _ = sigusr1.recv() => {
+                info!("Received SIGUSR1, syncing all mounts");
+                let manager = manager.clone();
+                tokio::spawn(async move {
+                    match manager.sync(None).await {
+                        Ok(()) => info!("Sync complete"),
+                        Err(e) => warn!("{}", e),
+                    }
+                });
+            }
+            Some(request) = next_control_request(&mut control) => {
+                // Commands like sync can take a while; signals are still
+                // handled meanwhile
+                let (source, config, manager) = (source.clone(), config.clone(), manager.clone());
+                let (global_limits, peers, notifier) = (global_limits.clone(), peers.clone(), notifier.clone());
+                tokio::spawn(async move {
+                    let result = run_command(&request.command, &source, &config, &manager, &global_limits, peers.as_ref()).await;
+                    request.reply(result);
+                    if let Some(notifier) = &notifier {
+                        notifier.report(&manager);
+                    }
+                });
+            }
+        }
     }
 
-    info!("Shutting down");
-    manager.unmount_all();
+    info!("Received shutdown signal");
+    if let Some(notifier) = &notifier {
+        notifier.notify("STOPPING=1");
+    }
+    let shutdown_timeout = config.lock().await.shutdown_timeout;
+    manager.shutdown(shutdown_timeout).await;
     info!("All filesystems unmounted, exiting");
 
     Ok(())
 }
 
-/// Wrap a connector with the appropriate cache layer based on configuration
-fn wrap_with_cache<C: Connector + 'static>(
-    connector: C,
-    cache_config: &CacheConfig,
-) -> Result<Arc<dyn Connector>, Box<dyn std::error::Error>> {
-    match cache_config {
-        CacheConfig::None => Ok(Arc::new(NoCache::new(connector))),
-        CacheConfig::Memory {
-            max_entries,
-            max_size,
-            flush_interval,
-            exclude_from_sync,
-        } => {
-            let config = MemoryCacheConfig {
-                max_entries: max_entries.unwrap_or(1000),
-                max_size: max_size
-                    .as_ref()
-                    .and_then(|s| fuse_adapter::cache::parse_size(s))
-                    .unwrap_or(100 * 1024 * 1024), // 100MB default
-                flush_interval: flush_interval.unwrap_or(std::time::Duration::from_secs(30)),
-                metadata_ttl: std::time::Duration::from_secs(60),
-                exclude_patterns: exclude_from_sync.clone().unwrap_or_default(),
-            };
-            let cache = Arc::new(MemoryCache::new(connector, config));
-            // Start background sync task for write-back caching
-            cache.start_background_sync();
-            Ok(cache)
-        }
-        CacheConfig::Filesystem {
+/// Wait for the next admin command (forever if there is no control socket)
+async fn next_control_request(control: &mut Option<ControlSocket>) -> Option<Request> {
+    match control {
+        Some(socket) => socket.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Carry out an admin command, returning its output
+///
+/// New mounts are resolved against the defaults in the config file, and
+/// mounts already running are left alone.
+async fn run_command(
+    command: &Command,
+    source: &ConfigSource,
+    config: &tokio::sync::Mutex<Config>,
+    manager: &Arc<MountManager>,
+    global_limits: &BandwidthLimits,
+    peers: Option<&Arc<PeerSharing>>,
+) -> Result<String, String> {
+    match command {
+        Command::MountAdd(entry) => {
+            // Held until mounted, so a second request for the path sees it
+            let _config = config.lock().await;
+            let content = source.yaml().map_err(|e| e.to_string())?;
+            let mount_config = Config::parse_mount(&content, entry).map_err(|e| e.to_string())?;
+            if manager.list_mounts().contains(&mount_config.path) {
+                return Err(format!("{:?} is already mounted", mount_config.path));
+            }
+            info!("Adding mount {:?} from control socket", mount_config.path);
+            setup_mount(manager, &mount_config, global_limits, peers).await?;
+            Ok(String::new())
+        }
+        Command::MountRemove(path) => {
+            info!("Removing mount {:?} from control socket", path);
+            let config = config.lock().await;
+            manager
+                .remove(path, config.shutdown_timeout)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(String::new())
+        }
+        Command::MountList => Ok(manager
+            .statuses()
+            .iter()
+            .map(|s| {
+                format!(
+                    "{}\t{}\t{}\n",
+                    s.path.display(),
+                    s.state.as_str(),
+                    s.last_error.as_deref().unwrap_or("")
+                )
+            })
+            .collect()),
+        Command::Sync(path) => {
+            info!("Syncing {:?} from control socket", path);
+            manager
+                .sync(path.as_deref())
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(String::new())
+        }
+        Command::SyncPause(path) | Command::SyncResume(path) => {
+            let paused = matches!(command, Command::SyncPause(_));
+            info!(
+                "{} background sync of {:?} from control socket",
+                if paused { "Pausing" } else { "Resuming" },
+                path
+            );
+            manager
+                .set_sync_paused(path.as_deref(), paused)
+                .map_err(|e| e.to_string())?;
+            Ok(String::new())
+        }
+        Command::FaultSet { .. } | Command::FaultClear(_) => run_fault_command(command),
+    }
+}
+
+/// Change the faults injected into a mount with a `faults` section
+#[cfg(feature = "mock")]
+fn run_fault_command(command: &Command) -> Result<String, String> {
+    use fuse_adapter::config::FaultConfig;
+    use fuse_adapter::connector::mock;
+
+    let controller = |path: &PathBuf| {
+        mock::controller(path).ok_or_else(|| format!("{:?} has no faults configured", path))
+    };
+    match command {
+        Command::FaultSet {
             path,
-            max_size,
-            flush_interval,
-            exclude_from_sync,
+            operation,
+            fault,
         } => {
-            let config = FilesystemCacheConfig {
-                cache_dir: PathBuf::from(path),
-                max_size: max_size
-                    .as_ref()
-                    .and_then(|s| fuse_adapter::cache::parse_size(s))
-                    .unwrap_or(1024 * 1024 * 1024),
-                flush_interval: flush_interval.unwrap_or(std::time::Duration::from_secs(30)),
-                metadata_ttl: std::time::Duration::from_secs(60),
-                exclude_patterns: exclude_from_sync.clone().unwrap_or_default(),
-            };
-            let cache = Arc::new(FilesystemCache::new(connector, config));
-            // Start background sync task for write-back caching
-            cache.start_background_sync();
-            Ok(cache)
+            let controller = controller(path)?;
+            let fault: FaultConfig =
+                serde_yaml::from_str(fault).map_err(|e| format!("Invalid fault: {}", e))?;
+            fault.validate()?;
+            info!(
+                "Injecting {} fault into {:?} from control socket",
+                operation.as_str(),
+                path
+            );
+            controller.set(*operation, fault);
+        }
+        Command::FaultClear(path) => {
+            info!("Clearing faults of {:?} from control socket", path);
+            controller(path)?.clear_all();
+        }
+        _ => unreachable!("not a fault command"),
+    }
+    Ok(String::new())
+}
+
+/// Change the faults injected into a mount with a `faults` section
+#[cfg(not(feature = "mock"))]
+fn run_fault_command(_command: &Command) -> Result<String, String> {
+    Err("fault injection requires building with the `mock` feature".to_string())
+}
+
+/// Build the logging filter; `RUST_LOG` takes precedence over the config
+fn log_filter(level: &str) -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level))
+}
+
+/// Re-read the config file and apply it to the running daemon
+///
+/// Mounts added to the file are mounted and mounts removed from it are
+/// unmounted; mounts present in both are left untouched. Returns the new
+/// config, or `None` (keeping everything as-is) if it fails to load.
+async fn reload_config<S>(
+    source: &ConfigSource,
+    current: &Config,
+    manager: &Arc<MountManager>,
+    global_limits: &BandwidthLimits,
+    peers: Option<&Arc<PeerSharing>>,
+    log_handle: &reload::Handle<EnvFilter, S>,
+) -> Option<Config> {
+    let config = match Config::load(source) {
+        Ok(c) => c,
+        Err(e) => {
+            error!(
+                "Failed to reload config, keeping current configuration: {}",
+                e
+            );
+            return None;
         }
+    };
+
+    if config.logging.level != current.logging.level {
+        match log_handle.reload(log_filter(&config.logging.level)) {
+            Ok(()) => info!("Log level set to {}", config.logging.level),
+            Err(e) => warn!("Failed to apply log level: {}", e),
+        }
+    }
+
+    global_limits.update(config.rate_limit.as_ref());
+
+    // Unmount mounts that were removed from the config. Mounts added with
+    // `mount add` aren't in the file and are left alone
+    let active = manager.list_mounts();
+    for path in current.mounts.iter().map(|m| &m.path) {
+        if active.contains(path) && !config.mounts.iter().any(|m| &m.path == path) {
+            info!("Mount {:?} removed from config", path);
+            if let Err(e) = manager.remove(path, config.shutdown_timeout).await {
+                warn!("Failed to unmount {:?}: {}", path, e);
+            }
+        }
+    }
+
+    // Mount new mounts; failures are logged but never exit the daemon
+    let active = manager.list_mounts();
+    for mount_config in &config.mounts {
+        if active.contains(&mount_config.path) {
+            continue;
+        }
+        if let Err(e) = setup_mount(manager, mount_config, global_limits, peers).await {
+            error!("{}", e);
+        }
+    }
+
+    info!("Config reloaded, {} filesystem(s) mounted", manager.count());
+    Some(config)
+}
+
+/// Create the connector stack for a mount and mount it
+///
+/// Returns an error describing the failure if the mount was skipped.
+async fn setup_mount(
+    manager: &Arc<MountManager>,
+    mount_config: &MountConfig,
+    global_limits: &BandwidthLimits,
+    peers: Option<&Arc<PeerSharing>>,
+) -> Result<(), String> {
+    info!("Setting up mount at {:?}", mount_config.path);
+
+    let mut builder = FuseAdapterBuilder::from_config(mount_config.clone())
+        .with_global_limits(global_limits.clone());
+    if let Some(peers) = peers {
+        builder = builder.with_cache_peers(peers.clone());
     }
+    builder
+        .mount_with(manager)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Skipping mount {:?}: {}", mount_config.path, e))
 }
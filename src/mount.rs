@@ -1,15 +1,28 @@
 //! Mount management and lifecycle
 
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use fuser::MountOption;
 use parking_lot::Mutex;
 use tokio::runtime::Handle;
-use tracing::info;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
 
+use crate::audit::AuditLog;
+use crate::cache::parse_size;
+use crate::config::{
+    HealthCheckConfig, LockingMode, MacosConfig, PageCacheMode, UnicodeNormalization,
+    WriteBufferConfig,
+};
 use crate::connector::Connector;
 use crate::error::{FuseAdapterError, Result};
+use crate::events::ErrorEvents;
+use crate::fuse::access::AccessControl;
+use crate::fuse::writebuf::DEFAULT_WRITE_BUFFER_SIZE;
 use crate::fuse::FuseAdapter;
 
 /// Represents an active mount
@@ -18,14 +31,122 @@ pub struct ActiveMount {
     pub path: PathBuf,
     /// Session handle (for unmounting)
     session: Option<fuser::BackgroundSession>,
+    /// Connector backing the mount (for the final sync on shutdown)
+    connector: Arc<dyn Connector>,
+    /// Options the mount was created with (for remounting)
+    options: MountOptions,
+    /// Current state, shared with health checks and `MountHandle`s
+    state: Arc<watch::Sender<MountInfo>>,
+}
+
+/// Lifecycle state of a mount
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountState {
+    /// Being served normally
+    Mounted,
+    /// Mounted, but the last health check failed
+    Degraded,
+    /// Unmounted, after the final sync of its cache (if any)
+    Unmounted,
+}
+
+impl MountState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MountState::Mounted => "mounted",
+            MountState::Degraded => "degraded",
+            MountState::Unmounted => "unmounted",
+        }
+    }
+}
+
+/// Snapshot of a mount's state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub path: PathBuf,
+    pub state: MountState,
+    /// Most recent health check, remount or final sync failure
+    pub last_error: Option<String>,
+}
+
+/// Observes one mount, including after it has been unmounted
+#[derive(Debug, Clone)]
+pub struct MountHandle {
+    state: watch::Receiver<MountInfo>,
+}
+
+impl MountHandle {
+    /// The mount's current state
+    pub fn info(&self) -> MountInfo {
+        self.state.borrow().clone()
+    }
+
+    /// Wait until the mount is unmounted and its final sync has finished
+    pub async fn unmounted(mut self) -> MountInfo {
+        // An error means the manager is gone, which unmounts everything
+        let _ = self
+            .state
+            .wait_for(|info| info.state == MountState::Unmounted)
+            .await;
+        self.info()
+    }
+}
+
+/// Per-mount FUSE session options (kept for remounting)
+#[derive(Clone, Default)]
+pub struct MountOptions {
+    /// Mount read-only at the FUSE level, preventing any write operations
+    /// regardless of connector capabilities
+    pub read_only: bool,
+    /// Owner reported for all files (defaults to the process uid)
+    pub uid: Option<u32>,
+    /// Group reported for all files (defaults to the process gid)
+    pub gid: Option<u32>,
+    /// Fail any filesystem operation that takes longer with `ETIMEDOUT`
+    /// instead of blocking the caller
+    pub operation_timeout: Option<Duration>,
+    /// Record every mutating operation in this log
+    pub audit: Option<Arc<AuditLog>>,
+    /// Check each request's uid/gid against these rules
+    pub access: Option<Arc<AccessControl>>,
+    /// Report failed operations to this channel
+    pub error_events: Option<Arc<ErrorEvents>>,
+    /// Check file modes against each caller's uid/gid
+    pub enforce_permissions: bool,
+    /// How `fcntl`/`flock` locks are handled
+    pub locking: LockingMode,
+    /// How the kernel page cache is used for file contents
+    pub page_cache: PageCacheMode,
+    /// Normalization form file names are converted to
+    pub unicode_normalization: UnicodeNormalization,
+    /// Coalesce sequential writes before passing them to the connector
+    pub write_buffer: Option<WriteBufferConfig>,
+    /// Largest write request the kernel may send (None = kernel limit)
+    pub max_write: Option<u32>,
+    /// Largest read request the kernel may send (None = kernel limit)
+    pub max_read: Option<u32>,
+    /// macFUSE volume name, icon and AppleDouble handling
+    pub macos: Option<MacosConfig>,
 }
 
 impl ActiveMount {
     /// Create a new active mount
-    fn new(path: PathBuf, session: fuser::BackgroundSession) -> Self {
+    fn new(
+        path: PathBuf,
+        session: fuser::BackgroundSession,
+        connector: Arc<dyn Connector>,
+        options: MountOptions,
+    ) -> Self {
         Self {
-            path,
+            path: path.clone(),
             session: Some(session),
+            connector,
+            options,
+            state: Arc::new(watch::Sender::new(MountInfo {
+                path,
+                state: MountState::Mounted,
+                last_error: None,
+            })),
         }
     }
 
@@ -44,6 +165,85 @@ impl Drop for ActiveMount {
     }
 }
 
+/// Detach a stale FUSE mount left behind at `path`, if there is one
+///
+/// When a FUSE daemon dies without unmounting, the mountpoint stays in the
+/// mount table but every access fails with `ENOTCONN` ("Transport endpoint
+/// is not connected"), which also prevents mounting over it. This performs
+/// the equivalent of `fusermount -uz` on such a path. Returns whether a stale
+/// mount was found and removed.
+pub fn cleanup_stale_mount(path: &Path) -> Result<bool> {
+    match std::fs::metadata(path) {
+        Err(e) if e.raw_os_error() == Some(libc::ENOTCONN) => {}
+        _ => return Ok(false),
+    }
+
+    warn!("Found stale FUSE mount at {:?}, unmounting", path);
+    lazy_unmount(path)?;
+    Ok(true)
+}
+
+/// Detach whatever is mounted at `path` (`fusermount -uz`)
+fn lazy_unmount(path: &Path) -> Result<()> {
+    // Lazy unmount directly when we have the privilege, otherwise go through
+    // the setuid fusermount helper
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| FuseAdapterError::InvalidArgument(e.to_string()))?;
+    if unsafe { libc::umount2(c_path.as_ptr(), libc::MNT_DETACH) } == 0 {
+        return Ok(());
+    }
+    debug!(
+        "umount2 on {:?} failed ({}), trying fusermount",
+        path,
+        std::io::Error::last_os_error()
+    );
+
+    for helper in ["fusermount3", "fusermount"] {
+        match Command::new(helper).arg("-uz").arg(path).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => debug!("{} -uz {:?} exited with {}", helper, path, status),
+            Err(e) => debug!("Could not run {}: {}", helper, e),
+        }
+    }
+
+    Err(FuseAdapterError::Backend(format!(
+        "Failed to unmount FUSE mount at {:?}",
+        path
+    )))
+}
+
+/// Delay before the next remount attempt after one fails
+fn next_backoff(current: Duration, max: Duration) -> Duration {
+    (current * 2).min(max)
+}
+
+/// macFUSE options for a mount's volume name, icon and AppleDouble handling
+///
+/// Other platforms have no equivalents, so nothing is returned there.
+fn macos_mount_options(path: &Path, macos: &MacosConfig) -> Vec<MountOption> {
+    let mut options = Vec::new();
+    if let Some(volname) = &macos.volname {
+        options.push(MountOption::CUSTOM(format!("volname={}", volname)));
+    }
+    if let Some(icon) = &macos.icon {
+        options.push(MountOption::CUSTOM(format!("volicon={}", icon.display())));
+    }
+    if macos.no_apple_double() {
+        options.push(MountOption::CUSTOM("noappledouble".to_string()));
+    }
+
+    if !cfg!(target_os = "macos") {
+        if macos.volname.is_some() || macos.icon.is_some() {
+            warn!(
+                "Ignoring macOS volume name and icon for {:?}: not running on macOS",
+                path
+            );
+        }
+        return Vec::new();
+    }
+    options
+}
+
 /// Mount manager - handles lifecycle of all mounts
 pub struct MountManager {
     /// Active mounts
@@ -62,22 +262,23 @@ impl MountManager {
     }
 
     /// Mount a connector at the specified path
-    ///
-    /// If `read_only` is true, the mount will be read-only at the FUSE level,
-    /// preventing any write operations regardless of connector capabilities.
-    ///
-    /// The `uid` and `gid` parameters configure the owner reported for all files.
-    /// If `None`, the process's uid/gid will be used.
     pub fn mount(
         &self,
         path: PathBuf,
         connector: Arc<dyn Connector>,
-        read_only: bool,
-        uid: Option<u32>,
-        gid: Option<u32>,
+        options: MountOptions,
     ) -> Result<()> {
         info!("Mounting at {:?}", path);
 
+        if self.mounts.lock().iter().any(|m| m.path == path) {
+            return Err(FuseAdapterError::AlreadyExists(format!(
+                "Already mounted: {:?}",
+                path
+            )));
+        }
+
+        cleanup_stale_mount(&path)?;
+
         // Ensure mount point exists
         if !path.exists() {
             return Err(FuseAdapterError::NotFound(format!(
@@ -93,8 +294,55 @@ impl MountManager {
             )));
         }
 
+        let session = self.spawn_session(&path, connector.clone(), options.clone())?;
+
+        // Track the mount
+        let active = ActiveMount::new(path.clone(), session, connector, options);
+        self.mounts.lock().push(active);
+
+        info!("Successfully mounted at {:?}", path);
+        Ok(())
+    }
+
+    /// Start a FUSE session serving `connector` at `path`
+    fn spawn_session(
+        &self,
+        path: &Path,
+        connector: Arc<dyn Connector>,
+        params: MountOptions,
+    ) -> Result<fuser::BackgroundSession> {
         // Create the FUSE adapter
-        let adapter = FuseAdapter::new(connector, self.handle.clone(), uid, gid);
+        let mut adapter = FuseAdapter::new(connector, self.handle.clone(), params.uid, params.gid);
+        if let Some(timeout) = params.operation_timeout {
+            adapter = adapter.with_operation_timeout(timeout);
+        }
+        if let Some(audit) = &params.audit {
+            adapter = adapter.with_audit_log(audit.clone());
+        }
+        if let Some(access) = &params.access {
+            adapter = adapter.with_access_control(access.clone());
+        }
+        if let Some(errors) = &params.error_events {
+            adapter = adapter.with_error_events(errors.clone());
+        }
+        if params.enforce_permissions {
+            adapter = adapter.with_permission_checks();
+        }
+        adapter = adapter
+            .with_locking(params.locking)
+            .with_page_cache(params.page_cache)
+            .with_unicode_normalization(params.unicode_normalization);
+        if let Some(buffer) = &params.write_buffer {
+            let size = buffer
+                .size
+                .as_deref()
+                .and_then(parse_size)
+                .unwrap_or(DEFAULT_WRITE_BUFFER_SIZE);
+            adapter = adapter.with_write_buffer(size as usize, buffer.interval);
+        }
+        if let Some(max_write) = params.max_write {
+            adapter = adapter.with_max_write(max_write);
+        }
 
         // Configure mount options
         let mut options = vec![
@@ -105,21 +353,208 @@ impl MountManager {
         ];
 
         // Add read-only mount option if configured
-        if read_only {
+        if params.read_only {
             info!("Mounting {:?} as read-only", path);
             options.push(MountOption::RO);
         }
 
+        if let Some(max_read) = params.max_read {
+            options.push(MountOption::CUSTOM(format!("max_read={}", max_read)));
+        }
+
+        if let Some(macos) = &params.macos {
+            options.extend(macos_mount_options(path, macos));
+        }
+
         // Mount in background
-        let session =
-            fuser::spawn_mount2(adapter, &path, &options).map_err(FuseAdapterError::Io)?;
+        fuser::spawn_mount2(adapter, path, &options).map_err(FuseAdapterError::Io)
+    }
 
-        // Track the mount
-        let active = ActiveMount::new(path.clone(), session);
-        self.mounts.lock().push(active);
+    /// Periodically health-check the mount at `path`
+    ///
+    /// Each check stats the mount root from a blocking thread and fails if it
+    /// errors or takes longer than `config.timeout`, or if the FUSE session
+    /// has exited. Failed mounts are marked unhealthy and, if
+    /// `config.auto_remount` is set, remounted with exponential backoff. The
+    /// watchdog stops once the mount is removed or the manager is dropped.
+    pub fn watch(self: &Arc<Self>, path: PathBuf, config: HealthCheckConfig) {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        self.handle.spawn(async move {
+            let mut delay = config.interval;
+            let mut backoff = config.interval;
+            loop {
+                tokio::time::sleep(delay).await;
+                delay = config.interval;
 
-        info!("Successfully mounted at {:?}", path);
-        Ok(())
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                let Some(state) = manager.state(&path) else {
+                    break;
+                };
+
+                match manager.probe(&path, config.timeout).await {
+                    Ok(()) => {
+                        if state.borrow().state == MountState::Degraded {
+                            info!("Mount {:?} is healthy again", path);
+                        }
+                        set_state(&state, MountState::Mounted, None);
+                        backoff = config.interval;
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Health check for {:?} failed: {}", path, e);
+                        set_state(&state, MountState::Degraded, Some(e.to_string()));
+                    }
+                }
+
+                if !config.auto_remount {
+                    continue;
+                }
+                match manager.remount(&path).await {
+                    Ok(()) => info!("Remounted {:?}", path),
+                    Err(e) => {
+                        set_state(&state, MountState::Degraded, Some(e.to_string()));
+                        backoff = next_backoff(backoff, config.max_backoff);
+                        warn!(
+                            "Remount of {:?} failed, retrying in {:?}: {}",
+                            path, backoff, e
+                        );
+                        delay = backoff;
+                    }
+                }
+            }
+            debug!("Health checks for {:?} stopped", path);
+        });
+    }
+
+    /// Whether the last health check of `path` succeeded (None if not mounted)
+    pub fn is_healthy(&self, path: &Path) -> Option<bool> {
+        self.status(path).map(|s| s.state == MountState::Mounted)
+    }
+
+    /// Mounts whose last health check failed
+    pub fn unhealthy_mounts(&self) -> Vec<PathBuf> {
+        self.statuses()
+            .into_iter()
+            .filter(|s| s.state == MountState::Degraded)
+            .map(|s| s.path)
+            .collect()
+    }
+
+    /// Current state of the mount at `path` (None if not mounted)
+    pub fn status(&self, path: &Path) -> Option<MountInfo> {
+        self.state(path).map(|s| s.borrow().clone())
+    }
+
+    /// Current state of every active mount
+    pub fn statuses(&self) -> Vec<MountInfo> {
+        self.mounts
+            .lock()
+            .iter()
+            .map(|m| m.state.borrow().clone())
+            .collect()
+    }
+
+    /// A handle observing the mount at `path` (None if not mounted)
+    ///
+    /// The handle stays usable after the mount is removed, so callers can
+    /// await the end of its unmount.
+    pub fn mount_handle(&self, path: &Path) -> Option<MountHandle> {
+        self.state(path).map(|s| MountHandle {
+            state: s.subscribe(),
+        })
+    }
+
+    /// Number of paths across all mounts with changes not yet synced to
+    /// their backends
+    pub fn pending_changes(&self) -> usize {
+        let connectors: Vec<Arc<dyn Connector>> = self
+            .mounts
+            .lock()
+            .iter()
+            .map(|m| m.connector.clone())
+            .collect();
+        connectors.iter().map(|c| c.pending_sync().len()).sum()
+    }
+
+    fn state(&self, path: &Path) -> Option<Arc<watch::Sender<MountInfo>>> {
+        self.mounts
+            .lock()
+            .iter()
+            .find(|m| m.path == path)
+            .map(|m| m.state.clone())
+    }
+
+    /// Check that the mount at `path` is still being served
+    async fn probe(&self, path: &Path, timeout: Duration) -> Result<()> {
+        let exited = self
+            .mounts
+            .lock()
+            .iter()
+            .find(|m| m.path == path)
+            .map(|m| m.session.as_ref().is_none_or(|s| s.guard.is_finished()))
+            .unwrap_or(true);
+        if exited {
+            return Err(FuseAdapterError::Backend(
+                "FUSE session has exited".to_string(),
+            ));
+        }
+
+        let target = path.to_path_buf();
+        let stat = tokio::task::spawn_blocking(move || std::fs::metadata(target));
+        match tokio::time::timeout(timeout, stat).await {
+            Ok(Ok(Ok(_))) => Ok(()),
+            Ok(Ok(Err(e))) => Err(FuseAdapterError::Io(e)),
+            Ok(Err(e)) => Err(FuseAdapterError::Backend(e.to_string())),
+            Err(_) => Err(FuseAdapterError::Timeout(format!(
+                "stat did not complete within {:?}",
+                timeout
+            ))),
+        }
+    }
+
+    /// Tear down the FUSE session at `path` and start a fresh one
+    ///
+    /// The connector (and therefore any cached or pending data) is reused.
+    async fn remount(&self, path: &Path) -> Result<()> {
+        let (session, connector, params) = {
+            let mut mounts = self.mounts.lock();
+            let mount = mounts
+                .iter_mut()
+                .find(|m| m.path == path)
+                .ok_or_else(|| FuseAdapterError::NotFound(format!("No mount at {:?}", path)))?;
+            (
+                mount.session.take(),
+                mount.connector.clone(),
+                mount.options.clone(),
+            )
+        };
+
+        // Dropping a session of a hung mount can block, so do it off the runtime
+        let target = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            drop(session);
+            if let Err(e) = lazy_unmount(&target) {
+                debug!("{}", e);
+            }
+        })
+        .await
+        .map_err(|e| FuseAdapterError::Backend(e.to_string()))?;
+
+        let session = self.spawn_session(path, connector, params)?;
+        let mut mounts = self.mounts.lock();
+        match mounts.iter_mut().find(|m| m.path == path) {
+            Some(mount) => {
+                mount.session = Some(session);
+                Ok(())
+            }
+            // Removed while we were remounting; let the new session unmount
+            None => Err(FuseAdapterError::NotFound(format!(
+                "No mount at {:?}",
+                path
+            ))),
+        }
     }
 
     /// Unmount a specific path
@@ -128,6 +563,7 @@ impl MountManager {
         if let Some(pos) = mounts.iter().position(|m| &m.path == path) {
             let mut mount = mounts.remove(pos);
             mount.unmount();
+            set_state(&mount.state, MountState::Unmounted, None);
             Ok(())
         } else {
             Err(FuseAdapterError::NotFound(format!(
@@ -143,7 +579,142 @@ impl MountManager {
         let mut mounts = self.mounts.lock();
         for mut mount in mounts.drain(..) {
             mount.unmount();
+            set_state(&mount.state, MountState::Unmounted, None);
+        }
+    }
+
+    /// Unmount all filesystems and wait for their connectors to shut down
+    ///
+    /// Pending cache changes are flushed to the backends. If the flush takes
+    /// longer than `timeout`, the remaining work is abandoned and logged.
+    pub async fn shutdown(&self, timeout: Duration) {
+        info!("Unmounting all filesystems");
+        let mounts: Vec<ActiveMount> = self.mounts.lock().drain(..).collect();
+        Self::finish(mounts, timeout).await;
+    }
+
+    /// Unmount a specific path and wait for its connector to shut down
+    pub async fn remove(&self, path: &PathBuf, timeout: Duration) -> Result<()> {
+        let mount = {
+            let mut mounts = self.mounts.lock();
+            let pos = mounts
+                .iter()
+                .position(|m| &m.path == path)
+                .ok_or_else(|| FuseAdapterError::NotFound(format!("No mount at {:?}", path)))?;
+            mounts.remove(pos)
+        };
+        Self::finish(vec![mount], timeout).await;
+        Ok(())
+    }
+
+    /// Unmount the given mounts, then flush their connectors within `timeout`
+    async fn finish(mounts: Vec<ActiveMount>, timeout: Duration) {
+        let mut connectors = Vec::with_capacity(mounts.len());
+        for mut mount in mounts {
+            mount.unmount();
+            connectors.push((
+                mount.path.clone(),
+                mount.connector.clone(),
+                mount.state.clone(),
+            ));
+        }
+
+        let flush =
+            futures::future::join_all(connectors.iter().map(|(path, c, state)| async move {
+                if let Err(e) = c.shutdown().await {
+                    warn!("Final sync for {:?} failed: {}", path, e);
+                    set_state(state, MountState::Unmounted, Some(e.to_string()));
+                }
+            }));
+
+        let timed_out = tokio::time::timeout(timeout, flush).await.is_err();
+        if timed_out {
+            warn!(
+                "Timed out after {:?} waiting for final sync; unsynced changes may be lost",
+                timeout
+            );
+        }
+        for (_, _, state) in &connectors {
+            if state.borrow().state != MountState::Unmounted {
+                let error = timed_out.then(|| format!("final sync timed out after {:?}", timeout));
+                set_state(state, MountState::Unmounted, error);
+            }
+        }
+    }
+
+    /// Push pending changes of the mount at `path` (or of every mount) to
+    /// the backend, returning once they have been written
+    pub async fn sync(&self, path: Option<&Path>) -> Result<()> {
+        let connectors: Vec<(PathBuf, Arc<dyn Connector>)> = {
+            let mounts = self.mounts.lock();
+            if let Some(path) = path {
+                if !mounts.iter().any(|m| m.path == path) {
+                    return Err(FuseAdapterError::NotFound(format!(
+                        "No mount at {:?}",
+                        path
+                    )));
+                }
+            }
+            mounts
+                .iter()
+                .filter(|m| path.is_none_or(|p| m.path == p))
+                .map(|m| (m.path.clone(), m.connector.clone()))
+                .collect()
+        };
+
+        let results = futures::future::join_all(
+            connectors
+                .iter()
+                .map(|(path, c)| async move { (path, c.sync_all().await) }),
+        )
+        .await;
+        let errors: Vec<String> = results
+            .into_iter()
+            .filter_map(|(path, result)| result.err().map(|e| format!("{:?}: {}", path, e)))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(FuseAdapterError::Backend(format!(
+                "Sync failed for {}",
+                errors.join("; ")
+            )))
+        }
+    }
+
+    /// Pause or resume background sync of the mount at `path`, or of every
+    /// mount with a write-back cache
+    pub fn set_sync_paused(&self, path: Option<&Path>, paused: bool) -> Result<()> {
+        let connectors: Vec<(PathBuf, Arc<dyn Connector>)> = {
+            let mounts = self.mounts.lock();
+            if let Some(path) = path {
+                if !mounts.iter().any(|m| m.path == path) {
+                    return Err(FuseAdapterError::NotFound(format!(
+                        "No mount at {:?}",
+                        path
+                    )));
+                }
+            }
+            mounts
+                .iter()
+                .filter(|m| path.is_none_or(|p| m.path == p))
+                .map(|m| (m.path.clone(), m.connector.clone()))
+                .collect()
+        };
+
+        for (mount_path, connector) in &connectors {
+            match connector.cache_control() {
+                Some(control) => control.set_sync_paused(paused),
+                None if path.is_some() => {
+                    return Err(FuseAdapterError::NotSupported(format!(
+                        "{:?} has no write-back cache",
+                        mount_path
+                    )))
+                }
+                None => {}
+            }
         }
+        Ok(())
     }
 
     /// Get list of active mount paths
@@ -157,8 +728,78 @@ impl MountManager {
     }
 }
 
+/// Move a mount to `state`, keeping its last error unless there is a new one
+fn set_state(sender: &watch::Sender<MountInfo>, state: MountState, error: Option<String>) {
+    sender.send_modify(|info| {
+        info.state = state;
+        if error.is_some() {
+            info.last_error = error;
+        }
+    });
+}
+
 impl Drop for MountManager {
     fn drop(&mut self) {
         self.unmount_all();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handle_waits_for_unmount() {
+        let state = watch::Sender::new(MountInfo {
+            path: PathBuf::from("/mnt/test"),
+            state: MountState::Mounted,
+            last_error: None,
+        });
+        let handle = MountHandle {
+            state: state.subscribe(),
+        };
+
+        set_state(&state, MountState::Degraded, Some("timed out".to_string()));
+        assert_eq!(handle.info().state, MountState::Degraded);
+
+        // Recovering keeps the last error around
+        set_state(&state, MountState::Mounted, None);
+        assert_eq!(handle.info().last_error.as_deref(), Some("timed out"));
+
+        let waiter = tokio::spawn(handle.clone().unmounted());
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        set_state(&state, MountState::Unmounted, None);
+        let info = waiter.await.unwrap();
+        assert_eq!(info.state, MountState::Unmounted);
+        assert_eq!(info.path, PathBuf::from("/mnt/test"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_unknown_mount() {
+        let manager = MountManager::new(Handle::current());
+        assert!(manager.sync(None).await.is_ok());
+        assert!(matches!(
+            manager.sync(Some(Path::new("/mnt/missing"))).await,
+            Err(FuseAdapterError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_next_backoff_is_capped() {
+        let max = Duration::from_secs(60);
+        assert_eq!(
+            next_backoff(Duration::from_secs(10), max),
+            Duration::from_secs(20)
+        );
+        assert_eq!(next_backoff(Duration::from_secs(40), max), max);
+    }
+
+    #[test]
+    fn test_cleanup_ignores_healthy_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!cleanup_stale_mount(dir.path()).unwrap());
+        assert!(!cleanup_stale_mount(&dir.path().join("missing")).unwrap());
+    }
+}
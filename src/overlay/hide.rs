@@ -0,0 +1,397 @@
+//! Hide overlay that filters paths out of a mount
+//!
+//! Paths matching any of the mount's `hide_patterns` globs are left out of
+//! directory listings and behave as if they don't exist: lookups return
+//! ENOENT, and creating or renaming something onto a hidden path is refused
+//! with EACCES. Hiding a directory hides everything beneath it.
+//!
+//! Patterns match the path relative to the mount root, e.g. `_tmp`,
+//! `**/.DS_Store` or `**/*.partial`.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileVersion, Metadata, SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Overlay hiding paths that match glob patterns
+pub struct HideOverlay {
+    /// Inner connector
+    inner: Arc<dyn Connector>,
+    /// Compiled hide patterns
+    patterns: GlobSet,
+}
+
+impl HideOverlay {
+    /// Create a hide overlay; fails if a pattern isn't a valid glob
+    pub fn new(connector: Arc<dyn Connector>, patterns: &[String]) -> Result<Self> {
+        Ok(Self {
+            inner: connector,
+            patterns: build_patterns(patterns)?,
+        })
+    }
+
+    /// Whether `path` or one of its ancestors matches a hide pattern
+    fn hidden(&self, path: &Path) -> bool {
+        is_hidden(&self.patterns, path)
+    }
+
+    /// Fail with ENOENT if `path` is hidden
+    fn check_visible(&self, path: &Path) -> Result<()> {
+        if self.hidden(path) {
+            return Err(FuseAdapterError::NotFound(path.display().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Fail with EACCES if something would be created at a hidden path
+    fn check_creatable(&self, path: &Path) -> Result<()> {
+        if self.hidden(path) {
+            return Err(FuseAdapterError::PermissionDenied);
+        }
+        Ok(())
+    }
+}
+
+/// Compile hide patterns into a glob set
+fn build_patterns(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| {
+            FuseAdapterError::Config(format!("Invalid hide pattern '{}': {}", pattern, e))
+        })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| FuseAdapterError::Config(format!("Invalid hide patterns: {}", e)))
+}
+
+fn is_hidden(patterns: &GlobSet, path: &Path) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+    let relative = path.strip_prefix("/").unwrap_or(path);
+    relative
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| patterns.is_match(p))
+}
+
+#[async_trait]
+impl Connector for HideOverlay {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.check_visible(path)?;
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        if self.hidden(path) {
+            return Ok(false);
+        }
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.check_visible(path)?;
+        self.inner.read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.check_creatable(path)?;
+        self.inner.write(path, offset, data).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.check_creatable(path)?;
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.check_creatable(path)?;
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.check_visible(path)?;
+        self.inner.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.check_visible(path)?;
+        self.inner.remove_dir(path, recursive).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        if self.hidden(path) {
+            let missing = FuseAdapterError::NotFound(path.display().to_string());
+            return Box::pin(futures::stream::once(async move { Err(missing) }));
+        }
+        let patterns = self.patterns.clone();
+        let dir = path.to_path_buf();
+        Box::pin(self.inner.list_dir(path).filter(move |entry| {
+            let visible = match entry {
+                Ok(entry) => !is_hidden(&patterns, &dir.join(&entry.name)),
+                Err(_) => true,
+            };
+            futures::future::ready(visible)
+        }))
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_visible(from)?;
+        self.check_creatable(to)?;
+        self.inner.rename(from, to).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.check_visible(path)?;
+        self.inner.truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.check_creatable(path)?;
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.check_creatable(path)?;
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.check_visible(path)?;
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.check_visible(path)?;
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.check_creatable(link_path)?;
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.check_visible(path)?;
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.check_visible(path)?;
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.check_visible(path)?;
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_visible(from)?;
+        self.check_creatable(to)?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.check_visible(path)?;
+        self.inner.zero_range(path, offset, len).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.check_visible(path)?;
+        self.inner.seek_region(path, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::DirEntry;
+    use futures::stream;
+    use std::time::UNIX_EPOCH;
+
+    /// Read-only connector with a fixed tree
+    struct TreeConnector;
+
+    const FILES: &[&str] = &["/a.txt", "/.DS_Store", "/_tmp/part", "/docs/.DS_Store"];
+
+    #[async_trait]
+    impl Connector for TreeConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::full()
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            match path.to_str() {
+                Some("/") | Some("/_tmp") | Some("/docs") => Ok(Metadata::directory(UNIX_EPOCH)),
+                Some(p) if FILES.contains(&p) => Ok(Metadata::file(1, UNIX_EPOCH)),
+                _ => Err(FuseAdapterError::NotFound(path.display().to_string())),
+            }
+        }
+
+        async fn read(&self, _path: &Path, _offset: u64, _size: u32) -> Result<Bytes> {
+            Ok(Bytes::from_static(b"x"))
+        }
+
+        async fn write(&self, _path: &Path, _offset: u64, data: &[u8]) -> Result<u64> {
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            let entries: Vec<Result<DirEntry>> = match path.to_str() {
+                Some("/") => vec![
+                    Ok(DirEntry::file("a.txt")),
+                    Ok(DirEntry::file(".DS_Store")),
+                    Ok(DirEntry::directory("_tmp")),
+                    Ok(DirEntry::directory("docs")),
+                ],
+                Some("/docs") => vec![Ok(DirEntry::file(".DS_Store"))],
+                _ => vec![],
+            };
+            Box::pin(stream::iter(entries))
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn overlay() -> HideOverlay {
+        let patterns = vec!["**/.DS_Store".to_string(), "_tmp".to_string()];
+        HideOverlay::new(Arc::new(TreeConnector), &patterns).unwrap()
+    }
+
+    async fn names(overlay: &HideOverlay, path: &str) -> Vec<String> {
+        overlay
+            .list_dir(Path::new(path))
+            .map(|e| e.unwrap().name.to_string_lossy().into_owned())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_hidden_paths_are_filtered_and_missing() {
+        let overlay = overlay();
+
+        assert_eq!(names(&overlay, "/").await, vec!["a.txt", "docs"]);
+        assert!(names(&overlay, "/docs").await.is_empty());
+
+        for path in ["/.DS_Store", "/docs/.DS_Store", "/_tmp", "/_tmp/part"] {
+            let err = overlay.stat(Path::new(path)).await.unwrap_err();
+            assert_eq!(err.to_errno(), libc::ENOENT, "{}", path);
+        }
+        assert!(overlay.stat(Path::new("/a.txt")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_creating_hidden_paths_is_refused() {
+        let overlay = overlay();
+
+        let err = overlay
+            .create_file(Path::new("/docs/.DS_Store"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::EACCES);
+        let err = overlay
+            .rename(Path::new("/a.txt"), Path::new("/_tmp/a.txt"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::EACCES);
+        overlay.create_file(Path::new("/b.txt")).await.unwrap();
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_rejected() {
+        let patterns = vec!["[unclosed".to_string()];
+        assert!(HideOverlay::new(Arc::new(TreeConnector), &patterns).is_err());
+    }
+}
@@ -1,5 +1,15 @@
 //! Overlay modules for wrapping connectors with additional functionality
 
+mod hide;
+mod quota;
 mod status;
+mod trash;
+mod versions;
+mod write_access;
 
+pub use hide::HideOverlay;
+pub use quota::QuotaOverlay;
 pub use status::StatusOverlay;
+pub use trash::TrashOverlay;
+pub use versions::VersionsOverlay;
+pub use write_access::WriteAccessOverlay;
@@ -0,0 +1,387 @@
+//! Quota overlay that enforces a mount's storage limits
+//!
+//! Before any write or truncate that would grow a file, the overlay works
+//! out the file's new size and asks the mount's `QuotaTracker` whether it
+//! fits: a file larger than `max_file_size` fails with EFBIG, and growth that
+//! would push the mount past `max_total_size` fails with EDQUOT. Successful
+//! operations (including removals and renames over existing files) update
+//! the tracked usage.
+//!
+//! The overlay sits directly above the cache, so limits apply as soon as data
+//! is written locally rather than when it is eventually uploaded.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileType, FileVersion, Metadata,
+    SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+use crate::quota::{total_size, QuotaTracker};
+
+/// Overlay enforcing per-mount size limits
+pub struct QuotaOverlay {
+    /// Inner connector
+    inner: Arc<dyn Connector>,
+    /// Limits and usage for this mount
+    tracker: Arc<QuotaTracker>,
+}
+
+impl QuotaOverlay {
+    /// Create a quota overlay reporting usage through `tracker`
+    pub fn new(connector: Arc<dyn Connector>, tracker: Arc<QuotaTracker>) -> Self {
+        Self {
+            inner: connector,
+            tracker,
+        }
+    }
+
+    /// Current size of the file at `path`, or 0 if there is no file there
+    async fn file_size(&self, path: &Path) -> Result<u64> {
+        match self.inner.stat(path).await {
+            Ok(metadata) if metadata.file_type == FileType::File => Ok(metadata.size),
+            Ok(_) | Err(FuseAdapterError::NotFound(_)) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl Connector for QuotaOverlay {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.inner.read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        let old = self.file_size(path).await?;
+        let end = offset + data.len() as u64;
+        // Backends without random writes replace the whole object at offset 0
+        let new = if offset == 0 && !self.inner.capabilities().random_write {
+            end
+        } else {
+            old.max(end)
+        };
+        self.tracker.check_resize(old, new)?;
+        let written = self.inner.write(path, offset, data).await?;
+        self.tracker.record_resize(old, new);
+        Ok(written)
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        let size = self.file_size(path).await.unwrap_or(0);
+        self.inner.remove_file(path).await?;
+        self.tracker.release(size);
+        Ok(())
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        let size = if recursive {
+            total_size(self.inner.as_ref(), path).await.unwrap_or(0)
+        } else {
+            0
+        };
+        self.inner.remove_dir(path, recursive).await?;
+        self.tracker.release(size);
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        self.inner.list_dir(path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        // A file replaced by the rename no longer counts towards usage
+        let replaced = self.file_size(to).await.unwrap_or(0);
+        self.inner.rename(from, to).await?;
+        self.tracker.release(replaced);
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        let old = self.file_size(path).await?;
+        self.tracker.check_resize(old, size)?;
+        self.inner.truncate(path, size).await?;
+        self.tracker.record_resize(old, size);
+        Ok(())
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        let old = self.file_size(to).await?;
+        let new = self.file_size(from).await?;
+        self.tracker.check_resize(old, new)?;
+        self.inner.copy(from, to).await?;
+        self.tracker.record_resize(old, new);
+        Ok(())
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.inner.zero_range(path, offset, len).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.inner.seek_region(path, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuotaConfig;
+    use crate::connector::DirEntry;
+    use futures::stream;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::time::UNIX_EPOCH;
+
+    /// Flat in-memory connector with random writes
+    #[derive(Default)]
+    struct MemConnector {
+        files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl Connector for MemConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::full()
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            if path == Path::new("/") {
+                return Ok(Metadata::directory(UNIX_EPOCH));
+            }
+            match self.files.lock().unwrap().get(path) {
+                Some(data) => Ok(Metadata::file(data.len() as u64, UNIX_EPOCH)),
+                None => Err(FuseAdapterError::NotFound(path.display().to_string())),
+            }
+        }
+
+        async fn read(&self, _path: &Path, _offset: u64, _size: u32) -> Result<Bytes> {
+            Ok(Bytes::new())
+        }
+
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            let mut files = self.files.lock().unwrap();
+            let file = files.entry(path.to_path_buf()).or_default();
+            let end = offset as usize + data.len();
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            file[offset as usize..end].copy_from_slice(data);
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), Vec::new());
+            Ok(())
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.files.lock().unwrap().remove(path);
+            Ok(())
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, _path: &Path) -> DirEntryStream {
+            let entries: Vec<Result<DirEntry>> = self
+                .files
+                .lock()
+                .unwrap()
+                .keys()
+                .map(|p| Ok(DirEntry::file(p.file_name().unwrap())))
+                .collect();
+            Box::pin(stream::iter(entries))
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            let mut files = self.files.lock().unwrap();
+            let data = files.remove(from).unwrap_or_default();
+            files.insert(to.to_path_buf(), data);
+            Ok(())
+        }
+
+        async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+            if let Some(file) = self.files.lock().unwrap().get_mut(path) {
+                file.resize(size as usize, 0);
+            }
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn overlay(total: &str, file: &str) -> (QuotaOverlay, Arc<QuotaTracker>) {
+        let tracker = Arc::new(QuotaTracker::new(&QuotaConfig {
+            max_total_size: Some(total.to_string()),
+            max_file_size: Some(file.to_string()),
+        }));
+        let overlay = QuotaOverlay::new(Arc::new(MemConnector::default()), tracker.clone());
+        (overlay, tracker)
+    }
+
+    #[tokio::test]
+    async fn test_limits_are_enforced() {
+        let (overlay, tracker) = overlay("100", "60");
+        let a = Path::new("/a");
+        let b = Path::new("/b");
+
+        overlay.write(a, 0, &[1; 50]).await.unwrap();
+        let err = overlay.write(a, 50, &[1; 11]).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EFBIG);
+        overlay.write(b, 0, &[1; 40]).await.unwrap();
+        assert_eq!(tracker.used(), 90);
+
+        let err = overlay.truncate(b, 60).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EDQUOT);
+        // Overwriting in place doesn't grow usage
+        overlay.write(b, 0, &[2; 40]).await.unwrap();
+        assert_eq!(tracker.used(), 90);
+    }
+
+    #[tokio::test]
+    async fn test_usage_follows_removals_and_renames() {
+        let (overlay, tracker) = overlay("1KB", "1KB");
+        overlay.write(Path::new("/a"), 0, &[1; 100]).await.unwrap();
+        overlay.write(Path::new("/b"), 0, &[1; 30]).await.unwrap();
+
+        overlay
+            .rename(Path::new("/b"), Path::new("/a"))
+            .await
+            .unwrap();
+        assert_eq!(tracker.used(), 30);
+        overlay.truncate(Path::new("/a"), 10).await.unwrap();
+        assert_eq!(tracker.used(), 10);
+        overlay.remove_file(Path::new("/a")).await.unwrap();
+        assert_eq!(tracker.used(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scan_seeds_usage() {
+        let inner = Arc::new(MemConnector::default());
+        inner.write(Path::new("/a"), 0, &[1; 70]).await.unwrap();
+        inner.write(Path::new("/b"), 0, &[1; 5]).await.unwrap();
+
+        let tracker = QuotaTracker::new(&QuotaConfig::default());
+        tracker.scan(inner.as_ref()).await;
+        assert_eq!(tracker.used(), 75);
+        assert!(tracker.report().contains("scan_complete: true"));
+    }
+}
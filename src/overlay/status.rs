@@ -4,10 +4,22 @@
 //! - `status` - "healthy\n" or "error\n"
 //! - `error` - Current error message or empty
 //! - `error_log` - Timestamped log of errors
+//! - `errors.log` - Recent errors from the FUSE layer and cache sync (if
+//!   the mount reports them)
+//! - `integrity` - Content verification counts and failures (if enabled)
+//! - `quota` - Storage usage and limits (if the mount has a quota)
+//! - `stats` - JSON cache statistics (if the mount has a cache)
+//! - `pending` - Paths not yet synced to the backend, one per line as
+//!   `state<TAB>retries<TAB>path<TAB>last error` (if the mount has a cache)
+//!
+//! and write-only control files; writing `1` to one triggers an action:
+//! - `flush` - Sync all pending changes to the backend now
+//! - `invalidate` - Drop cached metadata, directory listings and clean
+//!   content; writing an absolute path instead drops only that subtree
 
 use std::collections::VecDeque;
 use std::ffi::OsString;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::SystemTime;
 
@@ -17,11 +29,19 @@ use chrono::{DateTime, Utc};
 use futures::stream;
 use tracing::warn;
 
+use crate::cache::{CacheControl, CacheStats, PendingSync};
 use crate::config::StatusOverlayConfig;
 use crate::connector::{
     CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream, FileType, Metadata,
+    SeekRegion,
 };
 use crate::error::{FuseAdapterError, Result};
+use crate::events::ErrorEvents;
+use crate::integrity::IntegrityMonitor;
+use crate::quota::QuotaTracker;
+
+/// Control files that trigger an action when `1` is written to them
+const CONTROL_FILES: &[&str] = &["flush", "invalidate"];
 
 /// Mount health status
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -67,6 +87,12 @@ pub struct StatusOverlay {
     config: StatusOverlayConfig,
     /// Error log (ring buffer)
     error_log: Mutex<VecDeque<ErrorLogEntry>>,
+    /// Integrity monitor to report through the `integrity` file
+    integrity: Option<Arc<IntegrityMonitor>>,
+    /// Quota tracker to report through the `quota` file
+    quota: Option<Arc<QuotaTracker>>,
+    /// Runtime errors to report through the `errors.log` file
+    errors: Option<Arc<ErrorEvents>>,
 }
 
 impl StatusOverlay {
@@ -80,6 +106,9 @@ impl StatusOverlay {
             }),
             config,
             error_log: Mutex::new(VecDeque::new()),
+            integrity: None,
+            quota: None,
+            errors: None,
         }
     }
 
@@ -104,9 +133,30 @@ impl StatusOverlay {
             }),
             config,
             error_log: Mutex::new(error_log),
+            integrity: None,
+            quota: None,
+            errors: None,
         }
     }
 
+    /// Expose integrity verification results as a virtual file
+    pub fn with_integrity(mut self, monitor: Arc<IntegrityMonitor>) -> Self {
+        self.integrity = Some(monitor);
+        self
+    }
+
+    /// Expose quota usage and limits as a virtual file
+    pub fn with_quota(mut self, tracker: Arc<QuotaTracker>) -> Self {
+        self.quota = Some(tracker);
+        self
+    }
+
+    /// Expose runtime errors reported by other layers as a virtual file
+    pub fn with_error_events(mut self, errors: Arc<ErrorEvents>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
     /// Check if a path is within the virtual status directory
     fn is_virtual_path(&self, path: &Path) -> bool {
         let prefix = &self.config.prefix;
@@ -180,12 +230,26 @@ impl StatusOverlay {
                 let content: String = log.iter().map(|e| e.format()).collect();
                 Some(content)
             }
+            "integrity" => self.integrity.as_ref().map(|m| m.report()),
+            "quota" => self.quota.as_ref().map(|q| q.report()),
+            "errors.log" => self.errors.as_ref().map(|e| e.report()),
+            "stats" => self.cache_stats().map(|s| s.to_json()),
+            "pending" => self
+                .cache_stats()
+                .map(|_| self.pending_sync().iter().map(|p| p.format()).collect()),
+            name if CONTROL_FILES.contains(&name) => self.inner.as_ref().map(|_| String::new()),
             _ => None,
         }
     }
 
     /// Get metadata for a virtual file
     fn get_virtual_metadata(&self, name: &str) -> Option<Metadata> {
+        if CONTROL_FILES.contains(&name) {
+            return self
+                .inner
+                .as_ref()
+                .map(|_| Metadata::file_with_mode(0, SystemTime::now(), 0o200));
+        }
         let content = self.get_virtual_content(name)?;
         Some(Metadata::file_with_mode(
             content.len() as u64,
@@ -194,6 +258,40 @@ impl StatusOverlay {
         ))
     }
 
+    /// Run the action for a control file write
+    async fn run_control(&self, name: &str, path: &Path, data: &[u8]) -> Result<()> {
+        let arg = String::from_utf8_lossy(data).trim().to_string();
+        let target = match (name, arg.as_str()) {
+            (_, "1") => None,
+            ("invalidate", arg) if arg.starts_with('/') => Some(PathBuf::from(arg)),
+            _ => {
+                return Err(FuseAdapterError::InvalidArgument(format!(
+                    "write 1 to {} to trigger it",
+                    name
+                )))
+            }
+        };
+
+        match name {
+            "flush" => {
+                self.with_error_logging("flush", path, |c| async move { c.sync_all().await })
+                    .await
+            }
+            "invalidate" => {
+                self.with_error_logging("invalidate", path, |c| async move {
+                    match (c.cache_control(), target) {
+                        (Some(control), Some(target)) => control.invalidate(&target),
+                        (Some(control), None) => control.invalidate_all(),
+                        (None, _) => c.invalidate_caches(),
+                    }
+                    Ok(())
+                })
+                .await
+            }
+            _ => Err(FuseAdapterError::ReadOnly),
+        }
+    }
+
     /// Execute an operation on the inner connector, logging errors
     async fn with_error_logging<T, F, Fut>(&self, operation: &str, path: &Path, f: F) -> Result<T>
     where
@@ -235,6 +333,41 @@ impl Connector for StatusOverlay {
         }
     }
 
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.as_ref().and_then(|c| c.cache_stats())
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.as_ref().and_then(|c| c.cache_control())
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner
+            .as_ref()
+            .map(|c| c.pending_sync())
+            .unwrap_or_default()
+    }
+
+    fn invalidate_caches(&self) {
+        if let Some(c) = &self.inner {
+            c.invalidate_caches();
+        }
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        match &self.inner {
+            Some(c) => c.sync_all().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        match &self.inner {
+            Some(c) => c.shutdown().await,
+            None => Ok(()),
+        }
+    }
+
     async fn stat(&self, path: &Path) -> Result<Metadata> {
         // Check if this is the virtual directory itself
         let prefix = &self.config.prefix;
@@ -289,9 +422,15 @@ impl Connector for StatusOverlay {
     }
 
     async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
-        // Virtual files are read-only
+        // Virtual files are read-only, except for control files
         if self.is_virtual_path(path) {
-            return Err(FuseAdapterError::ReadOnly);
+            return match self.virtual_file_name(path) {
+                Some(name) if CONTROL_FILES.contains(&name.as_str()) => {
+                    self.run_control(&name, path, data).await?;
+                    Ok(data.len() as u64)
+                }
+                _ => Err(FuseAdapterError::ReadOnly),
+            };
         }
 
         // Delegate to inner
@@ -360,11 +499,27 @@ impl Connector for StatusOverlay {
             || path == Path::new(&prefix)
             || path == Path::new(&format!("/{}", prefix))
         {
-            let entries = vec![
+            let mut entries = vec![
                 Ok(DirEntry::file("status")),
                 Ok(DirEntry::file("error")),
                 Ok(DirEntry::file("error_log")),
             ];
+            if self.integrity.is_some() {
+                entries.push(Ok(DirEntry::file("integrity")));
+            }
+            if self.quota.is_some() {
+                entries.push(Ok(DirEntry::file("quota")));
+            }
+            if self.errors.is_some() {
+                entries.push(Ok(DirEntry::file("errors.log")));
+            }
+            if self.cache_stats().is_some() {
+                entries.push(Ok(DirEntry::file("stats")));
+                entries.push(Ok(DirEntry::file("pending")));
+            }
+            if self.inner.is_some() {
+                entries.extend(CONTROL_FILES.iter().map(|name| Ok(DirEntry::file(*name))));
+            }
             return Box::pin(stream::iter(entries));
         }
 
@@ -378,6 +533,7 @@ impl Connector for StatusOverlay {
                 yield Ok(DirEntry {
                     name: OsString::from(&prefix_for_injection),
                     file_type: FileType::Directory,
+                    metadata: None,
                 });
 
                 // Then yield entries from the inner connector
@@ -409,7 +565,11 @@ impl Connector for StatusOverlay {
 
     async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
         if self.is_virtual_path(path) {
-            return Err(FuseAdapterError::ReadOnly);
+            // Opening a control file with O_TRUNC (e.g. `echo 1 >`) is harmless
+            return match self.virtual_file_name(path) {
+                Some(name) if CONTROL_FILES.contains(&name.as_str()) => Ok(()),
+                _ => Err(FuseAdapterError::ReadOnly),
+            };
         }
 
         self.with_error_logging(
@@ -464,6 +624,43 @@ impl Connector for StatusOverlay {
         )
         .await
     }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        if self.is_virtual_path(from) || self.is_virtual_path(to) {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+
+        self.with_error_logging("copy", from, |c| async move { c.copy(from, to).await })
+            .await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        if self.is_virtual_path(path) {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+
+        self.with_error_logging("zero_range", path, |c| async move {
+            c.zero_range(path, offset, len).await
+        })
+        .await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        if self.is_virtual_path(path) {
+            let size = self.stat(path).await?.size;
+            return Ok(region.in_dense_file(size, offset));
+        }
+
+        self.with_error_logging("seek_region", path, |c| async move {
+            c.seek_region(path, offset, region).await
+        })
+        .await
+    }
 }
 
 #[cfg(test)]
@@ -537,6 +734,160 @@ mod tests {
         assert!(log_content.contains("Test error"));
     }
 
+    #[test]
+    fn test_integrity_file() {
+        let config = StatusOverlayConfig::default();
+        let overlay = StatusOverlay::new_failed("test".to_string(), config);
+        assert_eq!(overlay.get_virtual_content("integrity"), None);
+
+        let monitor = Arc::new(IntegrityMonitor::new(Default::default()));
+        let overlay = overlay.with_integrity(monitor);
+        let content = overlay.get_virtual_content("integrity").unwrap();
+        assert!(content.contains("mismatches: 0"));
+    }
+
+    #[test]
+    fn test_quota_file() {
+        let config = StatusOverlayConfig::default();
+        let overlay = StatusOverlay::new_failed("test".to_string(), config);
+        assert_eq!(overlay.get_virtual_content("quota"), None);
+
+        let tracker = Arc::new(QuotaTracker::new(&Default::default()));
+        let overlay = overlay.with_quota(tracker);
+        let content = overlay.get_virtual_content("quota").unwrap();
+        assert!(content.contains("max_total_size: unlimited"));
+    }
+
+    #[test]
+    fn test_errors_file() {
+        let config = StatusOverlayConfig::default();
+        let overlay = StatusOverlay::new_failed("test".to_string(), config);
+        assert_eq!(overlay.get_virtual_content("errors.log"), None);
+
+        let errors = Arc::new(ErrorEvents::new(10));
+        let overlay = overlay.with_error_events(errors.clone());
+        assert_eq!(overlay.get_virtual_content("errors.log").unwrap(), "");
+
+        errors.push("fuse", "write", Path::new("/a.txt"), "I/O error");
+        let content = overlay.get_virtual_content("errors.log").unwrap();
+        assert!(content.contains("fuse write /a.txt: I/O error"));
+    }
+
+    #[test]
+    fn test_stats_file_requires_cache() {
+        let config = StatusOverlayConfig::default();
+        let overlay = StatusOverlay::new_failed("test".to_string(), config);
+        assert_eq!(overlay.get_virtual_content("stats"), None);
+        assert!(overlay.get_virtual_metadata("stats").is_none());
+    }
+
+    /// Connector that only counts control actions
+    #[derive(Default)]
+    struct ControlledConnector {
+        syncs: std::sync::atomic::AtomicUsize,
+        invalidations: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Connector for ControlledConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::full()
+        }
+
+        fn invalidate_caches(&self) {
+            self.invalidations
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            Err(FuseAdapterError::NotFound(path.display().to_string()))
+        }
+
+        async fn read(&self, path: &Path, _offset: u64, _size: u32) -> Result<Bytes> {
+            Err(FuseAdapterError::NotFound(path.display().to_string()))
+        }
+
+        async fn write(&self, path: &Path, _offset: u64, _data: &[u8]) -> Result<u64> {
+            Err(FuseAdapterError::NotFound(path.display().to_string()))
+        }
+
+        async fn create_file(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_file(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, _path: &Path) -> DirEntryStream {
+            Box::pin(stream::empty())
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn sync_all(&self) -> Result<()> {
+            self.syncs
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_control_files() {
+        let inner = Arc::new(ControlledConnector::default());
+        let overlay = StatusOverlay::new(inner.clone(), StatusOverlayConfig::default());
+        let flush = Path::new(".fuse-adapter/flush");
+        let invalidate = Path::new(".fuse-adapter/invalidate");
+
+        assert_eq!(overlay.stat(flush).await.unwrap().mode, Some(0o200));
+        overlay.truncate(flush, 0).await.unwrap();
+        assert_eq!(overlay.write(flush, 0, b"1\n").await.unwrap(), 2);
+        assert_eq!(overlay.write(invalidate, 0, b"1").await.unwrap(), 1);
+        assert_eq!(overlay.write(invalidate, 0, b"/dir\n").await.unwrap(), 5);
+        assert!(matches!(
+            overlay.write(flush, 0, b"/dir").await,
+            Err(FuseAdapterError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            overlay.write(flush, 0, b"yes").await,
+            Err(FuseAdapterError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            overlay
+                .write(Path::new(".fuse-adapter/status"), 0, b"1")
+                .await,
+            Err(FuseAdapterError::ReadOnly)
+        ));
+
+        use std::sync::atomic::Ordering;
+        assert_eq!(inner.syncs.load(Ordering::Relaxed), 1);
+        assert_eq!(inner.invalidations.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_control_files_hidden_when_failed() {
+        let overlay = StatusOverlay::new_failed("test".to_string(), Default::default());
+        assert!(overlay.get_virtual_metadata("flush").is_none());
+    }
+
     #[test]
     fn test_custom_prefix() {
         let config = StatusOverlayConfig {
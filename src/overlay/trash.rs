@@ -0,0 +1,548 @@
+//! Trash overlay that turns deletions into moves
+//!
+//! With `trash` configured, unlinking a file or removing a directory moves it
+//! into `/<directory>/<timestamp>/` at the mount root instead, keeping its
+//! path relative to the mount. For example, deleting `/data/report.csv` at
+//! noon UTC moves it to `/.trash/20240131T120000Z/data/report.csv`. Restoring
+//! is a plain `mv` back out of the trash, and anything deleted from inside
+//! the trash is removed for good.
+//!
+//! A background task purges timestamp directories older than the configured
+//! retention. Backends without rename fall back to copying the file into the
+//! trash and deleting the original.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{NaiveDateTime, Utc};
+use futures::StreamExt;
+use tracing::{debug, info, warn};
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::config::TrashConfig;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileType, FileVersion, Metadata,
+    SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// Format of the per-deletion timestamp directories
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Overlay moving deleted files into a trash directory
+pub struct TrashOverlay {
+    /// Inner connector
+    inner: Arc<dyn Connector>,
+    /// Trash settings
+    config: TrashConfig,
+    /// Root of the trash (e.g., "/.trash")
+    root: PathBuf,
+}
+
+impl TrashOverlay {
+    /// Create a trash overlay with the given settings
+    pub fn new(connector: Arc<dyn Connector>, config: TrashConfig) -> Self {
+        let root = Path::new("/").join(&config.directory);
+        Self {
+            inner: connector,
+            config,
+            root,
+        }
+    }
+
+    /// Whether `path` is the trash or something inside it
+    fn in_trash(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    /// Pick a trash location for `path` deleted now
+    async fn trash_path(&self, path: &Path) -> Result<PathBuf> {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        let target = self
+            .root
+            .join(Utc::now().format(TIMESTAMP_FORMAT).to_string())
+            .join(relative);
+
+        // Two deletions of the same path within a second get distinct names
+        let mut candidate = target.clone();
+        let mut n = 1;
+        while self.inner.exists(&candidate).await? {
+            let mut name = target.file_name().unwrap_or_default().to_os_string();
+            name.push(format!("~{}", n));
+            candidate = target.with_file_name(name);
+            n += 1;
+        }
+        Ok(candidate)
+    }
+
+    /// Create `dir` and any missing ancestors beneath the mount root
+    async fn create_dir_all(&self, dir: &Path) -> Result<()> {
+        let mut missing = Vec::new();
+        for ancestor in dir.ancestors() {
+            if ancestor == Path::new("/") || self.inner.exists(ancestor).await? {
+                break;
+            }
+            missing.push(ancestor);
+        }
+        for ancestor in missing.into_iter().rev() {
+            match self.inner.create_dir(ancestor).await {
+                Ok(()) | Err(FuseAdapterError::AlreadyExists(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Move a single file to `to`, copying if the backend can't rename
+    async fn move_file(&self, from: &Path, to: &Path) -> Result<()> {
+        if let Some(parent) = to.parent() {
+            self.create_dir_all(parent).await?;
+        }
+        match self.inner.rename(from, to).await {
+            Err(FuseAdapterError::NotSupported(_)) => {}
+            result => return result,
+        }
+
+        self.inner.copy(from, to).await?;
+        self.inner.remove_file(from).await
+    }
+
+    /// Move a directory tree to `to` one file at a time, then remove it
+    async fn move_tree(&self, from: &Path, to: &Path) -> Result<()> {
+        self.create_dir_all(to).await?;
+        let mut pending = vec![(from.to_path_buf(), to.to_path_buf())];
+        while let Some((src, dst)) = pending.pop() {
+            let mut entries = self.inner.list_dir(&src);
+            let mut files = Vec::new();
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                let (child_src, child_dst) = (src.join(&entry.name), dst.join(&entry.name));
+                match entry.file_type {
+                    FileType::Directory => {
+                        self.create_dir_all(&child_dst).await?;
+                        pending.push((child_src, child_dst));
+                    }
+                    FileType::File | FileType::Symlink => files.push((child_src, child_dst)),
+                }
+            }
+            for (child_src, child_dst) in files {
+                self.move_file(&child_src, &child_dst).await?;
+            }
+        }
+        self.inner.remove_dir(from, true).await
+    }
+
+    /// Remove timestamp directories older than the retention period
+    pub async fn purge(&self) -> Result<usize> {
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        let mut entries = self.inner.list_dir(&self.root);
+        while let Some(entry) = entries.next().await {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(FuseAdapterError::NotFound(_)) => return Ok(0),
+                Err(e) => return Err(e),
+            };
+            let Some(name) = entry.name.to_str() else {
+                continue;
+            };
+            let Ok(deleted) = NaiveDateTime::parse_from_str(name, TIMESTAMP_FORMAT) else {
+                continue;
+            };
+            let age = now.signed_duration_since(deleted.and_utc());
+            if age.to_std().is_ok_and(|age| age > self.config.retention) {
+                expired.push(self.root.join(name));
+            }
+        }
+
+        for dir in &expired {
+            debug!("Purging expired trash {:?}", dir);
+            self.inner.remove_dir(dir, true).await?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Purge expired trash every `purge_interval` until the overlay is dropped
+    pub fn spawn_purge(self: &Arc<Self>) {
+        let overlay: Weak<Self> = Arc::downgrade(self);
+        let interval = self.config.purge_interval;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(overlay) = overlay.upgrade() else {
+                    break;
+                };
+                match overlay.purge().await {
+                    Ok(0) => {}
+                    Ok(n) => info!("Purged {} expired trash directories", n),
+                    Err(e) => warn!("Trash purge failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl Connector for TrashOverlay {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.inner.read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.inner.write(path, offset, data).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.in_trash(path) {
+            return self.inner.remove_file(path).await;
+        }
+        let target = self.trash_path(path).await?;
+        debug!("Moving {:?} to trash at {:?}", path, target);
+        self.move_file(path, &target).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        if self.in_trash(path) {
+            return self.inner.remove_dir(path, recursive).await;
+        }
+        let target = self.trash_path(path).await?;
+        debug!("Moving {:?} to trash at {:?}", path, target);
+        if recursive {
+            return self.move_tree(path, &target).await;
+        }
+
+        // An empty directory has nothing to move; keep its place in the tree
+        self.inner.remove_dir(path, false).await?;
+        if let Err(e) = self.create_dir_all(&target).await {
+            warn!("Failed to record {:?} in trash: {}", path, e);
+        }
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        self.inner.list_dir(path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.inner.truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.inner.zero_range(path, offset, len).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.inner.seek_region(path, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::DirEntry;
+    use futures::stream;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Mutex;
+    use std::time::UNIX_EPOCH;
+
+    /// In-memory tree without rename, so moves go through the copy fallback
+    #[derive(Default)]
+    struct MemConnector {
+        files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+        dirs: Mutex<BTreeSet<PathBuf>>,
+    }
+
+    impl MemConnector {
+        fn children(&self, path: &Path) -> Vec<DirEntry> {
+            let files = self.files.lock().unwrap();
+            let dirs = self.dirs.lock().unwrap();
+            let mut entries: Vec<DirEntry> = dirs
+                .iter()
+                .filter(|d| d.parent() == Some(path))
+                .map(|d| DirEntry::directory(d.file_name().unwrap()))
+                .collect();
+            entries.extend(
+                files
+                    .keys()
+                    .filter(|f| f.parent() == Some(path))
+                    .map(|f| DirEntry::file(f.file_name().unwrap())),
+            );
+            entries
+        }
+    }
+
+    #[async_trait]
+    impl Connector for MemConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::full()
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            if path == Path::new("/") || self.dirs.lock().unwrap().contains(path) {
+                return Ok(Metadata::directory(UNIX_EPOCH));
+            }
+            match self.files.lock().unwrap().get(path) {
+                Some(data) => Ok(Metadata::file(data.len() as u64, UNIX_EPOCH)),
+                None => Err(FuseAdapterError::NotFound(path.display().to_string())),
+            }
+        }
+
+        async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+            let files = self.files.lock().unwrap();
+            let data = files.get(path).cloned().unwrap_or_default();
+            let start = (offset as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            Ok(Bytes::copy_from_slice(&data[start..end]))
+        }
+
+        async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+            let mut files = self.files.lock().unwrap();
+            let file = files.entry(path.to_path_buf()).or_default();
+            let end = offset as usize + data.len();
+            if file.len() < end {
+                file.resize(end, 0);
+            }
+            file[offset as usize..end].copy_from_slice(data);
+            Ok(data.len() as u64)
+        }
+
+        async fn create_file(&self, path: &Path) -> Result<()> {
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), Vec::new());
+            Ok(())
+        }
+
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.dirs.lock().unwrap().insert(path.to_path_buf());
+            Ok(())
+        }
+
+        async fn remove_file(&self, path: &Path) -> Result<()> {
+            self.files.lock().unwrap().remove(path);
+            Ok(())
+        }
+
+        async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+            if !recursive && !self.children(path).is_empty() {
+                return Err(FuseAdapterError::NotEmpty(path.display().to_string()));
+            }
+            self.files
+                .lock()
+                .unwrap()
+                .retain(|f, _| !f.starts_with(path));
+            self.dirs.lock().unwrap().retain(|d| !d.starts_with(path));
+            Ok(())
+        }
+
+        fn list_dir(&self, path: &Path) -> DirEntryStream {
+            let entries: Vec<Result<DirEntry>> = self.children(path).into_iter().map(Ok).collect();
+            Box::pin(stream::iter(entries))
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Err(FuseAdapterError::NotSupported("rename".to_string()))
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Ok(())
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn overlay() -> (TrashOverlay, Arc<MemConnector>) {
+        let inner = Arc::new(MemConnector::default());
+        let overlay = TrashOverlay::new(inner.clone(), TrashConfig::default());
+        (overlay, inner)
+    }
+
+    fn trashed(inner: &MemConnector) -> Vec<String> {
+        inner
+            .files
+            .lock()
+            .unwrap()
+            .keys()
+            .filter_map(|p| p.strip_prefix("/.trash").ok())
+            .map(|p| {
+                // Drop the timestamp component
+                let rest: PathBuf = p.components().skip(1).collect();
+                rest.display().to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_unlink_moves_to_trash() {
+        let (overlay, inner) = overlay();
+        inner.create_dir(Path::new("/data")).await.unwrap();
+        inner
+            .write(Path::new("/data/a.txt"), 0, b"one")
+            .await
+            .unwrap();
+
+        overlay.remove_file(Path::new("/data/a.txt")).await.unwrap();
+        assert!(!inner.exists(Path::new("/data/a.txt")).await.unwrap());
+        inner
+            .write(Path::new("/data/a.txt"), 0, b"two")
+            .await
+            .unwrap();
+        overlay.remove_file(Path::new("/data/a.txt")).await.unwrap();
+
+        // Same-second deletions of the same path don't clobber each other
+        let names = trashed(&inner);
+        assert_eq!(names.len(), 2);
+        assert!(names.iter().all(|n| n.starts_with("data/a.txt")));
+        let contents: BTreeSet<Vec<u8>> = inner.files.lock().unwrap().values().cloned().collect();
+        assert_eq!(contents, BTreeSet::from([b"one".to_vec(), b"two".to_vec()]));
+    }
+
+    #[tokio::test]
+    async fn test_recursive_remove_and_delete_inside_trash() {
+        let (overlay, inner) = overlay();
+        inner.create_dir(Path::new("/dir")).await.unwrap();
+        inner.create_dir(Path::new("/dir/sub")).await.unwrap();
+        inner.write(Path::new("/dir/sub/b"), 0, b"b").await.unwrap();
+
+        overlay.remove_dir(Path::new("/dir"), true).await.unwrap();
+        assert!(!inner.exists(Path::new("/dir")).await.unwrap());
+        assert_eq!(trashed(&inner), vec!["dir/sub/b"]);
+
+        // Deleting from the trash is permanent
+        let path = inner.files.lock().unwrap().keys().next().unwrap().clone();
+        overlay.remove_file(&path).await.unwrap();
+        assert!(inner.files.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_purge_removes_expired() {
+        let (overlay, inner) = overlay();
+        let old = Utc::now() - chrono::Duration::days(30);
+        let old_dir = Path::new("/.trash").join(old.format(TIMESTAMP_FORMAT).to_string());
+        let new_dir = Path::new("/.trash").join(Utc::now().format(TIMESTAMP_FORMAT).to_string());
+        for dir in [Path::new("/.trash"), &old_dir, &new_dir] {
+            inner.create_dir(dir).await.unwrap();
+        }
+        inner.write(&old_dir.join("x"), 0, b"x").await.unwrap();
+        inner.write(&new_dir.join("y"), 0, b"y").await.unwrap();
+
+        assert_eq!(overlay.purge().await.unwrap(), 1);
+        assert!(!inner.exists(&old_dir).await.unwrap());
+        assert!(inner.exists(&new_dir.join("y")).await.unwrap());
+    }
+}
@@ -0,0 +1,816 @@
+//! Versions overlay that exposes prior versions of files
+//!
+//! For every file `foo.txt` on a versioned backend, provides a virtual
+//! read-only directory `foo.txt{suffix}/` (default suffix `.versions`)
+//! containing one file per stored version, named
+//! `<timestamp>_<version_id>` (e.g. `20240131T120000Z_3HL4kqtJlcpXroDTDmJ`).
+//!
+//! With `directory` set (e.g. `.versions`), versions are instead grouped
+//! under one virtual directory per real directory: `dir/.versions/` holds a
+//! directory per file, so the versions of `dir/foo.txt` are in
+//! `dir/.versions/foo.txt/`.
+//!
+//! The versions directories are not included in directory listings; they are
+//! reachable by path only, so they don't clutter `ls` or recursive tools.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use tracing::trace;
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::config::VersionsOverlayConfig;
+use crate::connector::{
+    copy_content, CacheRequirements, Capabilities, Connector, DirEntry, DirEntryStream, FileType,
+    FileVersion, Metadata, SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::integrity::ContentHash;
+
+/// How long a version listing is reused before asking the backend again
+const VERSION_LIST_TTL: Duration = Duration::from_secs(5);
+
+/// Number of listings kept before expired ones are swept out
+const MAX_LISTINGS: usize = 1024;
+
+/// Timestamp format used in version file names
+const VERSION_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// What a path refers to within the versions overlay
+#[derive(Debug, PartialEq, Eq)]
+enum VirtualPath {
+    /// The directory of per-file versions directories for the given directory
+    /// (`directory` layout only)
+    Root(PathBuf),
+    /// The versions directory of the given file
+    Dir(PathBuf),
+    /// A single version of the given file
+    Version(PathBuf, String),
+}
+
+/// Versions overlay that wraps a connector with virtual version directories
+pub struct VersionsOverlay {
+    /// Inner connector
+    inner: Arc<dyn Connector>,
+    /// Configuration
+    config: VersionsOverlayConfig,
+    /// Recently fetched version listings
+    listings: DashMap<PathBuf, (Instant, Vec<FileVersion>)>,
+}
+
+impl VersionsOverlay {
+    /// Create a new versions overlay wrapping a connector
+    pub fn new(connector: Arc<dyn Connector>, config: VersionsOverlayConfig) -> Self {
+        Self {
+            inner: connector,
+            config,
+            listings: DashMap::new(),
+        }
+    }
+
+    /// Get the file whose versions directory this path names, if any
+    /// (e.g., "dir/foo.txt.versions" -> "dir/foo.txt")
+    fn versions_dir_base(&self, path: &Path) -> Option<PathBuf> {
+        let name = path.file_name()?.to_str()?;
+        let base = name.strip_suffix(&self.config.suffix)?;
+        if base.is_empty() {
+            return None;
+        }
+        Some(path.with_file_name(base))
+    }
+
+    /// Classify a path as a versions directory or a version file
+    fn classify(&self, path: &Path) -> Option<VirtualPath> {
+        if let Some(directory) = &self.config.directory {
+            return Self::classify_in_directory(directory, path);
+        }
+
+        if let Some(base) = self.versions_dir_base(path) {
+            return Some(VirtualPath::Dir(base));
+        }
+
+        let parent = path.parent()?;
+        let base = self.versions_dir_base(parent)?;
+        let name = path.file_name()?.to_str()?;
+        let version_id = Self::parse_version_name(name)?;
+        Some(VirtualPath::Version(base, version_id.to_string()))
+    }
+
+    /// Classify a path under the `directory` layout
+    /// (e.g., "dir/.versions/foo.txt/<version>")
+    fn classify_in_directory(directory: &str, path: &Path) -> Option<VirtualPath> {
+        let is_root = |p: &Path| p.file_name().is_some_and(|n| n == directory);
+
+        if is_root(path) {
+            return Some(VirtualPath::Root(path.parent()?.to_path_buf()));
+        }
+        let parent = path.parent()?;
+        let name = path.file_name()?;
+        if is_root(parent) {
+            return Some(VirtualPath::Dir(parent.parent()?.join(name)));
+        }
+        let grandparent = parent.parent()?;
+        if is_root(grandparent) {
+            let base = grandparent.parent()?.join(parent.file_name()?);
+            let version_id = Self::parse_version_name(name.to_str()?)?;
+            return Some(VirtualPath::Version(base, version_id.to_string()));
+        }
+        None
+    }
+
+    /// Build the virtual file name for a version
+    fn version_name(version: &FileVersion) -> String {
+        let timestamp: DateTime<Utc> = version.mtime.into();
+        format!(
+            "{}_{}",
+            timestamp.format(VERSION_TIME_FORMAT),
+            version.version_id
+        )
+    }
+
+    /// Extract the version id from a virtual file name
+    fn parse_version_name(name: &str) -> Option<&str> {
+        let (_, version_id) = name.split_once('_')?;
+        if version_id.is_empty() {
+            return None;
+        }
+        Some(version_id)
+    }
+
+    /// Check whether a path is a regular file in the inner connector
+    async fn is_backend_file(&self, path: &Path) -> Result<bool> {
+        match self.inner.stat(path).await {
+            Ok(meta) => Ok(meta.file_type == FileType::File),
+            Err(FuseAdapterError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// List versions of a file, reusing a recent listing when possible
+    async fn versions(&self, base: &Path) -> Result<Vec<FileVersion>> {
+        if let Some(entry) = self.listings.get(base) {
+            let (fetched, versions) = entry.value();
+            if fetched.elapsed() < VERSION_LIST_TTL {
+                return Ok(versions.clone());
+            }
+        }
+        self.listings.remove_if(base, |_, (fetched, _)| {
+            fetched.elapsed() >= VERSION_LIST_TTL
+        });
+
+        let versions = self.inner.list_versions(base).await?;
+        if self.listings.len() >= MAX_LISTINGS {
+            self.listings
+                .retain(|_, (fetched, _)| fetched.elapsed() < VERSION_LIST_TTL);
+        }
+        self.listings
+            .insert(base.to_path_buf(), (Instant::now(), versions.clone()));
+        Ok(versions)
+    }
+
+    /// Drop the cached listing of a file changed through the overlay
+    fn forget_listing(&self, path: &Path) {
+        self.listings.remove(path);
+    }
+
+    /// Drop cached listings of a path and everything below it
+    fn forget_listings_under(&self, path: &Path) {
+        self.listings.retain(|base, _| !base.starts_with(path));
+    }
+
+    /// Look up a single version of a file
+    async fn find_version(&self, base: &Path, version_id: &str) -> Result<FileVersion> {
+        self.versions(base)
+            .await?
+            .into_iter()
+            .find(|v| v.version_id == version_id)
+            .ok_or_else(|| {
+                FuseAdapterError::NotFound(format!("Version {} of {:?}", version_id, base))
+            })
+    }
+
+    /// Resolve a path to its virtual meaning, falling back to the inner
+    /// connector when the base file doesn't exist (so a real file that
+    /// happens to end in the suffix still works)
+    async fn resolve(&self, path: &Path) -> Result<Option<VirtualPath>> {
+        match self.classify(path) {
+            Some(VirtualPath::Root(dir)) => match self.inner.stat(&dir).await {
+                Ok(meta) if meta.is_dir() => Ok(Some(VirtualPath::Root(dir))),
+                Ok(_) | Err(FuseAdapterError::NotFound(_)) => Ok(None),
+                Err(e) => Err(e),
+            },
+            Some(VirtualPath::Dir(base)) => {
+                if self.is_backend_file(&base).await? {
+                    Ok(Some(VirtualPath::Dir(base)))
+                } else {
+                    Ok(None)
+                }
+            }
+            Some(VirtualPath::Version(base, id)) => {
+                if self.is_backend_file(&base).await? {
+                    Ok(Some(VirtualPath::Version(base, id)))
+                } else {
+                    Ok(None)
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// List a `directory`-layout root: one versions directory per file in `dir`
+    fn list_root(&self, path: &Path, dir: PathBuf) -> DirEntryStream {
+        use futures::StreamExt;
+
+        let inner = self.inner.clone();
+        let path_owned = path.to_path_buf();
+
+        Box::pin(async_stream::stream! {
+            let is_dir = match inner.stat(&dir).await {
+                Ok(meta) => meta.is_dir(),
+                Err(FuseAdapterError::NotFound(_)) => false,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+
+            if !is_dir {
+                let mut inner_stream = inner.list_dir(&path_owned);
+                while let Some(entry) = inner_stream.next().await {
+                    yield entry;
+                }
+                return;
+            }
+
+            let mut entries = inner.list_dir(&dir);
+            while let Some(entry) = entries.next().await {
+                match entry {
+                    Ok(entry) if entry.file_type == FileType::File => {
+                        yield Ok(DirEntry::directory(entry.name));
+                    }
+                    Ok(_) => {}
+                    Err(e) => yield Err(e),
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Connector for VersionsOverlay {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.listings.clear();
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        match self.resolve(path).await? {
+            Some(VirtualPath::Root(_)) => {
+                Ok(Metadata::directory_with_mode(SystemTime::now(), 0o555))
+            }
+            Some(VirtualPath::Dir(base)) => {
+                // Only show the directory when the backend actually keeps versions
+                match self.versions(&base).await {
+                    Ok(_) => {}
+                    Err(FuseAdapterError::NotSupported(_)) => {
+                        return Err(FuseAdapterError::NotFound(path.display().to_string()));
+                    }
+                    Err(e) => return Err(e),
+                }
+                Ok(Metadata::directory_with_mode(SystemTime::now(), 0o555))
+            }
+            Some(VirtualPath::Version(base, id)) => {
+                let version = self.find_version(&base, &id).await?;
+                Ok(Metadata::file_with_mode(version.size, version.mtime, 0o444))
+            }
+            None => self.inner.stat(path).await,
+        }
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        match self.stat(path).await {
+            Ok(_) => Ok(true),
+            Err(FuseAdapterError::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        match self.resolve(path).await? {
+            Some(VirtualPath::Root(_)) | Some(VirtualPath::Dir(_)) => {
+                Err(FuseAdapterError::IsADirectory(path.display().to_string()))
+            }
+            Some(VirtualPath::Version(base, id)) => {
+                let version = self.find_version(&base, &id).await?;
+                if offset >= version.size {
+                    return Ok(Bytes::new());
+                }
+                let size = size.min((version.size - offset).min(u32::MAX as u64) as u32);
+                trace!(
+                    "read_version: base={:?} version={} offset={} size={}",
+                    base,
+                    id,
+                    offset,
+                    size
+                );
+                self.inner.read_version(&base, &id, offset, size).await
+            }
+            None => self.inner.read(path, offset, size).await,
+        }
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.write(path, offset, data).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.create_file(path).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.remove_file(path).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.remove_dir(path, recursive).await;
+        self.forget_listings_under(path);
+        result
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        let base = match self.classify(path) {
+            Some(VirtualPath::Dir(base)) => base,
+            Some(VirtualPath::Root(dir)) => return self.list_root(path, dir),
+            _ => return self.inner.list_dir(path),
+        };
+
+        let inner = self.inner.clone();
+        let path_owned = path.to_path_buf();
+        let suffix = self.config.suffix.clone();
+
+        Box::pin(async_stream::stream! {
+            let is_file = match inner.stat(&base).await {
+                Ok(meta) => meta.file_type == FileType::File,
+                Err(FuseAdapterError::NotFound(_)) => false,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+
+            if !is_file {
+                // Not a versions directory after all
+                trace!("list_dir: {:?} is not a {} directory", path_owned, suffix);
+                use futures::StreamExt;
+                let mut inner_stream = inner.list_dir(&path_owned);
+                while let Some(entry) = inner_stream.next().await {
+                    yield entry;
+                }
+                return;
+            }
+
+            match inner.list_versions(&base).await {
+                Ok(versions) => {
+                    for version in &versions {
+                        yield Ok(DirEntry::file(VersionsOverlay::version_name(version)));
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        })
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if self.resolve(from).await?.is_some() || self.resolve(to).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.rename(from, to).await;
+        self.forget_listings_under(from);
+        self.forget_listings_under(to);
+        result
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.truncate(path, size).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Ok(());
+        }
+        let result = self.inner.flush(path).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.create_file_with_mode(path, mode).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        if self.resolve(link_path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        if self.resolve(to).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        // Copying a version out restores it; read it through the overlay
+        let result = if self.resolve(from).await?.is_some() {
+            copy_content(self, from, to).await
+        } else {
+            self.inner.copy(from, to).await
+        };
+        self.forget_listing(to);
+        result
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        if self.resolve(path).await?.is_some() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        let result = self.inner.zero_range(path, offset, len).await;
+        self.forget_listing(path);
+        result
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        if self.resolve(path).await?.is_some() {
+            let size = self.stat(path).await?.size;
+            return Ok(region.in_dense_file(size, offset));
+        }
+        self.inner.seek_region(path, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use std::time::UNIX_EPOCH;
+
+    struct VersionedConnector;
+
+    #[async_trait]
+    impl Connector for VersionedConnector {
+        fn capabilities(&self) -> Capabilities {
+            Capabilities::read_only()
+        }
+
+        fn cache_requirements(&self) -> CacheRequirements {
+            CacheRequirements::default()
+        }
+
+        async fn stat(&self, path: &Path) -> Result<Metadata> {
+            match path.to_str() {
+                Some("/") => Ok(Metadata::directory(UNIX_EPOCH)),
+                Some("/a.txt") => Ok(Metadata::file(5, UNIX_EPOCH)),
+                _ => Err(FuseAdapterError::NotFound(path.display().to_string())),
+            }
+        }
+
+        async fn read(&self, _path: &Path, _offset: u64, _size: u32) -> Result<Bytes> {
+            Ok(Bytes::from_static(b"hello"))
+        }
+
+        async fn write(&self, _path: &Path, _offset: u64, _data: &[u8]) -> Result<u64> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn create_file(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn create_dir(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn remove_file(&self, _path: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn remove_dir(&self, _path: &Path, _recursive: bool) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn rename(&self, _from: &Path, _to: &Path) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn truncate(&self, _path: &Path, _size: u64) -> Result<()> {
+            Err(FuseAdapterError::ReadOnly)
+        }
+
+        async fn flush(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        fn list_dir(&self, _path: &Path) -> DirEntryStream {
+            Box::pin(stream::iter(vec![Ok(DirEntry::file("a.txt"))]))
+        }
+
+        async fn list_versions(&self, _path: &Path) -> Result<Vec<FileVersion>> {
+            Ok(vec![
+                FileVersion {
+                    version_id: "v2".to_string(),
+                    size: 5,
+                    mtime: UNIX_EPOCH + Duration::from_secs(200),
+                    is_latest: true,
+                },
+                FileVersion {
+                    version_id: "v1".to_string(),
+                    size: 3,
+                    mtime: UNIX_EPOCH + Duration::from_secs(100),
+                    is_latest: false,
+                },
+            ])
+        }
+
+        async fn read_version(
+            &self,
+            _path: &Path,
+            version_id: &str,
+            offset: u64,
+            size: u32,
+        ) -> Result<Bytes> {
+            let data: &[u8] = if version_id == "v1" { b"old" } else { b"hello" };
+            let start = offset as usize;
+            let end = (start + size as usize).min(data.len());
+            Ok(Bytes::copy_from_slice(&data[start..end]))
+        }
+    }
+
+    fn overlay() -> VersionsOverlay {
+        VersionsOverlay::new(
+            Arc::new(VersionedConnector),
+            VersionsOverlayConfig::default(),
+        )
+    }
+
+    #[test]
+    fn test_classify() {
+        let overlay = overlay();
+
+        assert_eq!(
+            overlay.classify(Path::new("/dir/a.txt.versions")),
+            Some(VirtualPath::Dir(PathBuf::from("/dir/a.txt")))
+        );
+        assert_eq!(
+            overlay.classify(Path::new("/a.txt.versions/19700101T000140Z_v1")),
+            Some(VirtualPath::Version(
+                PathBuf::from("/a.txt"),
+                "v1".to_string()
+            ))
+        );
+        assert_eq!(overlay.classify(Path::new("/a.txt")), None);
+        assert_eq!(overlay.classify(Path::new("/.versions")), None);
+        assert_eq!(overlay.classify(Path::new("/a.txt.versions/bogus")), None);
+    }
+
+    #[test]
+    fn test_version_name_round_trip() {
+        let version = FileVersion {
+            version_id: "abc_def".to_string(),
+            size: 0,
+            mtime: UNIX_EPOCH + Duration::from_secs(100),
+            is_latest: false,
+        };
+        let name = VersionsOverlay::version_name(&version);
+        assert_eq!(name, "19700101T000140Z_abc_def");
+        assert_eq!(VersionsOverlay::parse_version_name(&name), Some("abc_def"));
+    }
+
+    #[tokio::test]
+    async fn test_list_and_read_versions() {
+        use futures::StreamExt;
+
+        let overlay = overlay();
+
+        let meta = overlay.stat(Path::new("/a.txt.versions")).await.unwrap();
+        assert_eq!(meta.file_type, FileType::Directory);
+
+        let names: Vec<_> = overlay
+            .list_dir(Path::new("/a.txt.versions"))
+            .map(|e| e.unwrap().name.to_string_lossy().to_string())
+            .collect()
+            .await;
+        assert_eq!(names, vec!["19700101T000320Z_v2", "19700101T000140Z_v1"]);
+
+        let path = Path::new("/a.txt.versions/19700101T000140Z_v1");
+        let meta = overlay.stat(path).await.unwrap();
+        assert_eq!(meta.size, 3);
+        assert_eq!(meta.mode, Some(0o444));
+
+        let data = overlay.read(path, 0, 4096).await.unwrap();
+        assert_eq!(&data[..], b"old");
+        assert!(overlay.read(path, 3, 4096).await.unwrap().is_empty());
+
+        assert!(matches!(
+            overlay.write(path, 0, b"x").await,
+            Err(FuseAdapterError::ReadOnly)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_listings_dropped_on_change_and_expiry() {
+        let overlay = overlay();
+        let base = Path::new("/a.txt");
+
+        overlay.stat(Path::new("/a.txt.versions")).await.unwrap();
+        assert!(overlay.listings.contains_key(base));
+        let _ = overlay.write(base, 0, b"x").await;
+        assert!(!overlay.listings.contains_key(base));
+
+        overlay.stat(Path::new("/a.txt.versions")).await.unwrap();
+        let _ = overlay.rename(base, Path::new("/b.txt")).await;
+        assert!(!overlay.listings.contains_key(base));
+
+        // Expired listings are swept out once there are enough of them
+        let expired = Instant::now().checked_sub(VERSION_LIST_TTL).unwrap();
+        for i in 0..MAX_LISTINGS {
+            overlay
+                .listings
+                .insert(PathBuf::from(format!("/old{}", i)), (expired, Vec::new()));
+        }
+        overlay.stat(Path::new("/a.txt.versions")).await.unwrap();
+        assert_eq!(overlay.listings.len(), 1);
+        assert!(overlay.listings.contains_key(base));
+    }
+
+    #[tokio::test]
+    async fn test_directory_layout() {
+        use futures::StreamExt;
+
+        let overlay = VersionsOverlay::new(
+            Arc::new(VersionedConnector),
+            VersionsOverlayConfig {
+                directory: Some(".versions".to_string()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            overlay.classify(Path::new("/dir/.versions")),
+            Some(VirtualPath::Root(PathBuf::from("/dir")))
+        );
+        assert_eq!(
+            overlay.classify(Path::new("/.versions/a.txt/19700101T000140Z_v1")),
+            Some(VirtualPath::Version(
+                PathBuf::from("/a.txt"),
+                "v1".to_string()
+            ))
+        );
+        assert_eq!(overlay.classify(Path::new("/a.txt.versions")), None);
+
+        let meta = overlay.stat(Path::new("/.versions")).await.unwrap();
+        assert_eq!(meta.file_type, FileType::Directory);
+        let names: Vec<_> = overlay
+            .list_dir(Path::new("/.versions"))
+            .map(|e| e.unwrap().name.to_string_lossy().to_string())
+            .collect()
+            .await;
+        assert_eq!(names, vec!["a.txt"]);
+
+        let names: Vec<_> = overlay
+            .list_dir(Path::new("/.versions/a.txt"))
+            .map(|e| e.unwrap().name.to_string_lossy().to_string())
+            .collect()
+            .await;
+        assert_eq!(names, vec!["19700101T000320Z_v2", "19700101T000140Z_v1"]);
+
+        let data = overlay
+            .read(Path::new("/.versions/a.txt/19700101T000140Z_v1"), 0, 4096)
+            .await
+            .unwrap();
+        assert_eq!(&data[..], b"old");
+        assert!(overlay.stat(Path::new("/missing/.versions")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_base_falls_through() {
+        let overlay = overlay();
+
+        assert!(matches!(
+            overlay.stat(Path::new("/missing.versions")).await,
+            Err(FuseAdapterError::NotFound(_))
+        ));
+        assert!(!overlay
+            .exists(Path::new("/a.txt.versions/0_v9"))
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,235 @@
+//! Write access overlay that turns a mount read-only while writes are denied
+//!
+//! While the mount's `WriteAccess` reports that the backend denies writes,
+//! every operation that would change the mount fails with EROFS. Reads, and
+//! syncing changes accepted earlier, carry on as usual.
+//!
+//! The overlay sits directly above the cache, so no new changes pile up
+//! locally that could never be uploaded.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, FileVersion, Metadata, SeekRegion,
+};
+use crate::error::Result;
+use crate::integrity::ContentHash;
+use crate::write_access::WriteAccess;
+
+/// Overlay rejecting changes while the backend denies writes
+pub struct WriteAccessOverlay {
+    /// Inner connector
+    inner: Arc<dyn Connector>,
+    /// Whether the backend currently accepts writes
+    access: Arc<WriteAccess>,
+}
+
+impl WriteAccessOverlay {
+    /// Create an overlay gated on `access`
+    pub fn new(connector: Arc<dyn Connector>, access: Arc<WriteAccess>) -> Self {
+        Self {
+            inner: connector,
+            access,
+        }
+    }
+}
+
+#[async_trait]
+impl Connector for WriteAccessOverlay {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.inner.read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.access.check()?;
+        self.inner.write(path, offset, data).await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.access.check()?;
+        self.inner.create_file(path).await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.access.check()?;
+        self.inner.create_dir(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.access.check()?;
+        self.inner.remove_file(path).await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.access.check()?;
+        self.inner.remove_dir(path, recursive).await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        self.inner.list_dir(path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.access.check()?;
+        self.inner.rename(from, to).await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.access.check()?;
+        self.inner.truncate(path, size).await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        self.inner.flush(path).await
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.access.check()?;
+        self.inner.create_file_with_mode(path, mode).await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.access.check()?;
+        self.inner.create_dir_with_mode(path, mode).await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.access.check()?;
+        self.inner.set_mode(path, mode).await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.access.check()?;
+        self.inner.symlink(target, link_path).await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.access.check()?;
+        self.inner.copy(from, to).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.access.check()?;
+        self.inner.zero_range(path, offset, len).await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.inner.seek_region(path, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ReadOnlyFallbackConfig;
+    use crate::connector::memfs::MemfsConnector;
+    use crate::error::FuseAdapterError;
+
+    #[tokio::test]
+    async fn test_changes_rejected_while_access_is_lost() {
+        let access = Arc::new(WriteAccess::new(&ReadOnlyFallbackConfig::default(), None));
+        let overlay = WriteAccessOverlay::new(Arc::new(MemfsConnector::new(None)), access.clone());
+        overlay.create_file(Path::new("/a")).await.unwrap();
+        overlay.write(Path::new("/a"), 0, b"data").await.unwrap();
+
+        access.denied(
+            "write",
+            Path::new("/a"),
+            &FuseAdapterError::PermissionDenied,
+        );
+        assert!(matches!(
+            overlay.write(Path::new("/a"), 0, b"more").await,
+            Err(FuseAdapterError::ReadOnly)
+        ));
+        assert!(matches!(
+            overlay.remove_file(Path::new("/a")).await,
+            Err(FuseAdapterError::ReadOnly)
+        ));
+        assert_eq!(
+            overlay.read(Path::new("/a"), 0, 4).await.unwrap(),
+            Bytes::from_static(b"data")
+        );
+        overlay.flush(Path::new("/a")).await.unwrap();
+
+        access.allowed("write", Path::new("/a"));
+        overlay.remove_file(Path::new("/a")).await.unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+//! Per-mount storage quotas
+//!
+//! A `QuotaTracker` holds a mount's `max_total_size` and `max_file_size`
+//! limits together with its current usage. The quota overlay consults it
+//! before every operation that can grow a file and keeps the usage up to
+//! date as files are written, truncated, renamed and removed; the status
+//! overlay reports it through the `quota` file.
+//!
+//! Usage starts at zero and is seeded by a background scan of the mount
+//! when it comes up. Changes made while the scan runs are counted on top,
+//! so the figure is approximate until the scan completes and can drift if
+//! the backend is modified from outside the mount.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use futures::StreamExt;
+use tracing::{debug, warn};
+
+use crate::cache::parse_size;
+use crate::config::QuotaConfig;
+use crate::connector::{Connector, FileType};
+use crate::error::{FuseAdapterError, Result};
+
+/// Quota limits and current usage for a mount
+pub struct QuotaTracker {
+    /// Limit on the total size of all files
+    max_total_size: Option<u64>,
+    /// Limit on the size of a single file
+    max_file_size: Option<u64>,
+    /// Bytes currently stored in the mount
+    used: AtomicU64,
+    /// Whether the initial usage scan has completed
+    scanned: AtomicBool,
+}
+
+impl QuotaTracker {
+    /// Create a tracker from a (validated) quota configuration
+    pub fn new(config: &QuotaConfig) -> Self {
+        Self {
+            max_total_size: config.max_total_size.as_deref().and_then(parse_size),
+            max_file_size: config.max_file_size.as_deref().and_then(parse_size),
+            used: AtomicU64::new(0),
+            scanned: AtomicBool::new(false),
+        }
+    }
+
+    /// Bytes currently counted against the quota
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Check whether a file may change size from `old` to `new` bytes
+    ///
+    /// Fails with EFBIG if the file would exceed `max_file_size`, and with
+    /// EDQUOT if the growth would push the mount past `max_total_size`.
+    /// Shrinking a file is always allowed.
+    pub fn check_resize(&self, old: u64, new: u64) -> Result<()> {
+        if new <= old {
+            return Ok(());
+        }
+        if self.max_file_size.is_some_and(|max| new > max) {
+            return Err(FuseAdapterError::FileTooLarge);
+        }
+        if let Some(max) = self.max_total_size {
+            if self.used().saturating_add(new - old) > max {
+                return Err(FuseAdapterError::QuotaExceeded(format!(
+                    "{} of {} bytes used",
+                    self.used(),
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record that a file changed size from `old` to `new` bytes
+    pub fn record_resize(&self, old: u64, new: u64) {
+        if new >= old {
+            self.used.fetch_add(new - old, Ordering::Relaxed);
+        } else {
+            self.release(old - new);
+        }
+    }
+
+    /// Record that `size` bytes were freed
+    pub fn release(&self, size: u64) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(size))
+            });
+    }
+
+    /// Walk the mount and add the size of every file to the usage
+    pub async fn scan(&self, connector: &dyn Connector) {
+        match total_size(connector, Path::new("/")).await {
+            Ok(total) => {
+                self.used.fetch_add(total, Ordering::Relaxed);
+                debug!("Quota scan complete: {} bytes in use", total);
+            }
+            Err(e) => warn!("Quota usage scan failed: {}", e),
+        }
+        self.scanned.store(true, Ordering::Relaxed);
+    }
+
+    /// Usage and limits, one `key: value` per line
+    pub fn report(&self) -> String {
+        let limit = |max: Option<u64>| max.map_or("unlimited".to_string(), |m| m.to_string());
+        format!(
+            "used: {}\nmax_total_size: {}\nmax_file_size: {}\nscan_complete: {}\n",
+            self.used(),
+            limit(self.max_total_size),
+            limit(self.max_file_size),
+            self.scanned.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Sum the sizes of all files at or beneath `path`
+pub async fn total_size(connector: &dyn Connector, path: &Path) -> Result<u64> {
+    let metadata = connector.stat(path).await?;
+    if metadata.file_type != FileType::Directory {
+        return Ok(metadata.size);
+    }
+
+    let mut total = 0;
+    let mut pending: Vec<PathBuf> = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = connector.list_dir(&dir);
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let child = dir.join(&entry.name);
+            match entry.file_type {
+                FileType::Directory => pending.push(child),
+                FileType::File => {
+                    total += match entry.metadata {
+                        Some(metadata) => metadata.size,
+                        None => connector.stat(&child).await?.size,
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(total: &str, file: &str) -> QuotaTracker {
+        QuotaTracker::new(&QuotaConfig {
+            max_total_size: Some(total.to_string()),
+            max_file_size: Some(file.to_string()),
+        })
+    }
+
+    #[test]
+    fn test_check_resize_limits() {
+        let quota = tracker("100", "60");
+        quota.record_resize(0, 50);
+
+        assert!(quota.check_resize(0, 40).is_ok());
+        assert_eq!(
+            quota.check_resize(0, 61).unwrap_err().to_errno(),
+            libc::EFBIG
+        );
+        assert_eq!(
+            quota.check_resize(10, 61).unwrap_err().to_errno(),
+            libc::EFBIG
+        );
+        assert_eq!(
+            quota.check_resize(0, 51).unwrap_err().to_errno(),
+            libc::EDQUOT
+        );
+        // Growing an existing file only counts the difference
+        assert!(quota.check_resize(20, 60).is_ok());
+        // Shrinking is always allowed
+        assert!(quota.check_resize(500, 10).is_ok());
+    }
+
+    #[test]
+    fn test_usage_accounting() {
+        let quota = tracker("1KB", "1KB");
+        quota.record_resize(0, 300);
+        quota.record_resize(300, 100);
+        assert_eq!(quota.used(), 100);
+        quota.release(500);
+        assert_eq!(quota.used(), 0);
+
+        let report = quota.report();
+        assert!(report.contains("used: 0\n"));
+        assert!(report.contains("max_total_size: 1024\n"));
+        assert!(report.contains("scan_complete: false\n"));
+    }
+}
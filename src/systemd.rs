@@ -0,0 +1,185 @@
+//! systemd service notifications
+//!
+//! When started by systemd with `Type=notify`, the daemon reports through
+//! the `$NOTIFY_SOCKET` datagram socket (the `sd_notify` protocol):
+//! `READY=1` once all mounts are up, `STATUS=` lines describing the mounts
+//! and their pending syncs, `STOPPING=1` on shutdown, and, if the unit sets
+//! `WatchdogSec=`, `WATCHDOG=1` heartbeats for as long as every mount passes
+//! its health checks. Without `$NOTIFY_SOCKET` nothing is sent.
+
+use std::ffi::OsStr;
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::mount::MountManager;
+
+/// How often `STATUS=` is refreshed when the unit has no watchdog
+const STATUS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Connection to the service manager's notification socket
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    /// Interval within which systemd expects `WATCHDOG=1` (if enabled)
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Connect to `$NOTIFY_SOCKET`, or None if the process wasn't started
+    /// by systemd with notifications enabled
+    pub fn from_env() -> Option<Self> {
+        let socket = std::env::var_os("NOTIFY_SOCKET")?;
+        let watchdog = watchdog_timeout(
+            std::env::var("WATCHDOG_USEC").ok().as_deref(),
+            std::env::var("WATCHDOG_PID").ok().as_deref(),
+            std::process::id(),
+        );
+        match Self::connect(&socket, watchdog) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                warn!("Failed to open systemd notify socket {:?}: {}", socket, e);
+                None
+            }
+        }
+    }
+
+    /// Open a socket sending to `path`, where a leading `@` names an
+    /// abstract socket
+    pub fn connect(path: &OsStr, watchdog: Option<Duration>) -> io::Result<Self> {
+        let addr = match path.as_bytes().strip_prefix(b"@") {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            addr,
+            watchdog,
+        })
+    }
+
+    /// Send a newline-separated list of `KEY=value` assignments
+    pub fn notify(&self, state: &str) {
+        if let Err(e) = self.socket.send_to_addr(state.as_bytes(), &self.addr) {
+            debug!("Failed to notify systemd ({:?}): {}", state, e);
+        }
+    }
+
+    /// Report the mounts' state, sending a watchdog heartbeat if they are
+    /// all healthy
+    pub fn report(&self, manager: &MountManager) {
+        let unhealthy = manager.unhealthy_mounts();
+        let status = format!(
+            "STATUS={}",
+            format_status(manager.count(), unhealthy.len(), manager.pending_changes())
+        );
+        if self.watchdog.is_some() && unhealthy.is_empty() {
+            self.notify(&format!("{}\nWATCHDOG=1", status));
+        } else {
+            self.notify(&status);
+        }
+    }
+
+    /// Tell systemd startup has finished, with the initial status
+    pub fn ready(&self, manager: &MountManager) {
+        self.notify("READY=1");
+        self.report(manager);
+    }
+
+    /// Keep reporting status and heartbeats until the manager is dropped
+    ///
+    /// Heartbeats are sent at half the watchdog timeout, and only while
+    /// every mount passes its health checks, so a mount that stays broken
+    /// gets the service restarted.
+    pub fn spawn(self: &Arc<Self>, manager: &Arc<MountManager>) {
+        let interval = self.watchdog.map_or(STATUS_INTERVAL, |t| t / 2);
+        let notifier = self.clone();
+        let manager = Arc::downgrade(manager);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                notifier.report(&manager);
+            }
+        });
+    }
+}
+
+/// The watchdog timeout from `$WATCHDOG_USEC`, if it is meant for `pid`
+fn watchdog_timeout(usec: Option<&str>, watchdog_pid: Option<&str>, pid: u32) -> Option<Duration> {
+    if let Some(target) = watchdog_pid {
+        if target.parse::<u32>().ok()? != pid {
+            return None;
+        }
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// One-line summary of the daemon's state for `STATUS=`
+fn format_status(mounts: usize, unhealthy: usize, pending: usize) -> String {
+    let health = if unhealthy == 0 {
+        "all healthy".to_string()
+    } else {
+        format!("{} unhealthy", unhealthy)
+    };
+    format!(
+        "{} mount(s), {}, {} change(s) pending sync",
+        mounts, health, pending
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_timeout() {
+        assert_eq!(
+            watchdog_timeout(Some("10000000"), None, 42),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            watchdog_timeout(Some("10000000"), Some("42"), 42),
+            Some(Duration::from_secs(10))
+        );
+        // Meant for another process, disabled or malformed
+        assert_eq!(watchdog_timeout(Some("10000000"), Some("7"), 42), None);
+        assert_eq!(watchdog_timeout(Some("0"), None, 42), None);
+        assert_eq!(watchdog_timeout(Some("soon"), None, 42), None);
+        assert_eq!(watchdog_timeout(None, None, 42), None);
+    }
+
+    #[test]
+    fn test_notify_sends_datagram() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = Notifier::connect(path.as_os_str(), None).unwrap();
+        notifier.notify("READY=1");
+
+        let mut buf = [0; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+
+    #[test]
+    fn test_format_status() {
+        assert_eq!(
+            format_status(2, 0, 3),
+            "2 mount(s), all healthy, 3 change(s) pending sync"
+        );
+        assert_eq!(
+            format_status(2, 1, 0),
+            "2 mount(s), 1 unhealthy, 0 change(s) pending sync"
+        );
+    }
+}
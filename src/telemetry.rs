@@ -0,0 +1,158 @@
+//! Span export over OTLP
+//!
+//! With `tracing.otlp_endpoint` set, every FUSE operation gets a span
+//! carrying its name and path, connector calls made on its behalf become
+//! child spans, and S3 request IDs are attached to the spans of the calls
+//! that made them. Without an endpoint no spans are created at all, so the
+//! FUSE path pays nothing for tracing it doesn't use.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracerProvider, Tracer};
+use opentelemetry_sdk::Resource;
+use tracing::{info_span, warn, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::TracingConfig;
+use crate::error::{FuseAdapterError, Result};
+
+/// Path OTLP/HTTP collectors accept spans on
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Whether spans are being exported
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Exports spans to an OTLP collector; pending spans are flushed on drop
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Telemetry {
+    /// Set up span export if an endpoint is configured
+    ///
+    /// Must be called before the tokio runtime starts: the exporter runs on
+    /// its own thread with a blocking HTTP client.
+    pub fn start(config: &TracingConfig) -> Result<Option<Self>> {
+        let Some(endpoint) = &config.otlp_endpoint else {
+            return Ok(None);
+        };
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(traces_url(endpoint))
+            .build()
+            .map_err(|e| FuseAdapterError::Config(format!("Invalid OTLP endpoint: {}", e)))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .build();
+        ENABLED.store(true, Ordering::Relaxed);
+        Ok(Some(Self { provider }))
+    }
+
+    /// Layer turning `tracing` spans into exported spans
+    pub fn layer<S>(&self) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.provider.tracer("fuse-adapter"))
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        ENABLED.store(false, Ordering::Relaxed);
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Whether spans are being exported
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Span for a FUSE operation on `path`
+pub fn fuse_span(op: &'static str, path: &Path) -> Span {
+    if !enabled() {
+        return Span::none();
+    }
+    info_span!(
+        "fuse",
+        otel.name = %format_args!("fuse {}", op),
+        op,
+        path = %path.display()
+    )
+}
+
+/// Span for a call into a backend connector
+pub fn connector_span(op: &'static str, path: &Path) -> Span {
+    if !enabled() {
+        return Span::none();
+    }
+    info_span!(
+        "connector",
+        otel.name = %format_args!("connector {}", op),
+        op,
+        path = %path.display()
+    )
+}
+
+/// Attach a backend's ID for the request just made to the current span
+pub fn record_request_id(request_id: &str, extended_request_id: Option<&str>) {
+    if !enabled() {
+        return;
+    }
+    let span = Span::current();
+    span.set_attribute("aws.request_id", request_id.to_string());
+    if let Some(id) = extended_request_id {
+        span.set_attribute("aws.extended_request_id", id.to_string());
+    }
+}
+
+/// The collector URL spans are sent to; a bare endpoint gets the standard
+/// traces path
+fn traces_url(endpoint: &str) -> String {
+    if endpoint.trim_end_matches('/').ends_with(TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", endpoint.trim_end_matches('/'), TRACES_PATH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_url() {
+        assert_eq!(
+            traces_url("http://localhost:4318"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://localhost:4318/"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("https://collector:4318/v1/traces"),
+            "https://collector:4318/v1/traces"
+        );
+    }
+
+    #[test]
+    fn test_no_spans_without_endpoint() {
+        let config = TracingConfig::default();
+        assert!(Telemetry::start(&config).unwrap().is_none());
+        assert!(fuse_span("lookup", Path::new("/a")).is_none());
+        assert!(connector_span("stat", Path::new("/a")).is_none());
+    }
+}
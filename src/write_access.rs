@@ -0,0 +1,414 @@
+//! Read-only fallback when a mount loses write access
+//!
+//! Credentials can expire or lose their write permissions while a mount is
+//! up. With a write-back cache every change made after that is accepted
+//! locally and then fails to sync forever. A `WriteAccess` notices the first
+//! write the backend denies and turns the mount read-only: the write access
+//! overlay above the cache fails new changes with EROFS, while changes that
+//! were already accepted stay pending. A background probe creates and
+//! removes a small file on the backend every `probe_interval`; once that (or
+//! any other write) succeeds, the mount accepts changes again.
+//!
+//! Both transitions are logged and, with a status overlay, reported in its
+//! `errors.log`.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::Future;
+use tracing::{debug, info, warn};
+
+use crate::cache::{CacheControl, CacheStats, PendingSync};
+use crate::config::ReadOnlyFallbackConfig;
+use crate::connector::pacer::ThrottleStats;
+use crate::connector::{
+    CacheRequirements, Capabilities, Connector, DirEntryStream, DirtyRanges, FileVersion, Metadata,
+    SeekRegion,
+};
+use crate::error::{FuseAdapterError, Result};
+use crate::events::ErrorEvents;
+use crate::integrity::ContentHash;
+
+/// Whether a mount's backend currently accepts writes
+pub struct WriteAccess {
+    /// Time between write probes while access is lost
+    probe_interval: Duration,
+    /// File the probe creates and removes
+    probe_path: PathBuf,
+    /// Whether the backend is currently denying writes
+    lost: AtomicBool,
+    /// Where transitions are reported
+    events: Option<Arc<ErrorEvents>>,
+}
+
+impl WriteAccess {
+    /// Create a tracker for a mount whose writes currently work
+    pub fn new(config: &ReadOnlyFallbackConfig, events: Option<Arc<ErrorEvents>>) -> Self {
+        Self {
+            probe_interval: config.probe_interval,
+            probe_path: config.probe_path.clone(),
+            lost: AtomicBool::new(false),
+            events,
+        }
+    }
+
+    /// Whether the mount is currently read-only because writes are denied
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Fail with EROFS while write access is lost
+    pub fn check(&self) -> Result<()> {
+        if self.is_lost() {
+            return Err(FuseAdapterError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Note that the backend denied `operation` on `path`
+    pub fn denied(&self, operation: &str, path: &Path, error: &FuseAdapterError) {
+        if self.lost.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        warn!(
+            "Backend denied {} on {:?} ({}), mount is read-only until writes work again",
+            operation, path, error
+        );
+        if let Some(events) = &self.events {
+            events.push(
+                "backend",
+                operation,
+                path,
+                format!("{}; write access lost, mount is read-only", error),
+            );
+        }
+    }
+
+    /// Note that the backend accepted `operation` on `path`
+    pub fn allowed(&self, operation: &str, path: &Path) {
+        if !self.lost.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        info!(
+            "Backend accepted {} on {:?}, mount is writable again",
+            operation, path
+        );
+        if let Some(events) = &self.events {
+            events.push(
+                "backend",
+                operation,
+                path,
+                "write access restored, mount is writable again",
+            );
+        }
+    }
+
+    /// Test whether `backend` accepts writes again
+    ///
+    /// `backend` is expected to report its results here, as a
+    /// `WriteAccessMonitor` does.
+    pub async fn probe(&self, backend: &dyn Connector) {
+        let created = match backend.create_file(&self.probe_path).await {
+            Ok(()) | Err(FuseAdapterError::AlreadyExists(_)) => true,
+            Err(e) => {
+                debug!("Write probe {:?} failed: {}", self.probe_path, e);
+                false
+            }
+        };
+        if created {
+            if let Err(e) = backend.remove_file(&self.probe_path).await {
+                debug!("Removing write probe {:?} failed: {}", self.probe_path, e);
+            }
+        }
+    }
+
+    /// Probe `backend` every `probe_interval` while access is lost
+    ///
+    /// The task ends once the tracker or the backend is dropped.
+    pub fn spawn_probe(self: &Arc<Self>, backend: Weak<dyn Connector>) {
+        let access: Weak<Self> = Arc::downgrade(self);
+        let interval = self.probe_interval;
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let (Some(access), Some(backend)) = (access.upgrade(), backend.upgrade()) else {
+                    break;
+                };
+                if access.is_lost() {
+                    access.probe(backend.as_ref()).await;
+                }
+            }
+        });
+    }
+}
+
+/// Connector decorator that reports whether the backend accepts writes
+///
+/// Sits below the cache, so the cache's background uploads are seen too.
+pub struct WriteAccessMonitor<C: Connector> {
+    inner: C,
+    /// None when no fallback is configured; calls then pass straight through
+    access: Option<Arc<WriteAccess>>,
+}
+
+impl<C: Connector> WriteAccessMonitor<C> {
+    /// Wrap a backend, reporting to `access` if given
+    pub fn new(connector: C, access: Option<Arc<WriteAccess>>) -> Self {
+        Self {
+            inner: connector,
+            access,
+        }
+    }
+
+    /// Run a change on the backend and record whether it was denied
+    async fn change<T>(
+        &self,
+        operation: &str,
+        path: &Path,
+        change: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let result = change.await;
+        if let Some(access) = &self.access {
+            match &result {
+                Ok(_) => access.allowed(operation, path),
+                Err(e @ FuseAdapterError::PermissionDenied) => access.denied(operation, path, e),
+                Err(_) => {}
+            }
+        }
+        result
+    }
+}
+
+impl<C: Connector + 'static> WriteAccessMonitor<C> {
+    /// Wrap a backend and, if `access` is given, probe it through the
+    /// monitor whenever writes are denied
+    pub fn start(connector: C, access: Option<Arc<WriteAccess>>) -> Arc<Self> {
+        let monitor = Arc::new(Self::new(connector, access));
+        if let Some(access) = &monitor.access {
+            let backend: Weak<dyn Connector> = Arc::downgrade(&monitor) as Weak<Self>;
+            access.spawn_probe(backend);
+        }
+        monitor
+    }
+}
+
+#[async_trait]
+impl<C: Connector + 'static> Connector for WriteAccessMonitor<C> {
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn cache_requirements(&self) -> CacheRequirements {
+        self.inner.cache_requirements()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn throttle_stats(&self) -> Option<ThrottleStats> {
+        self.inner.throttle_stats()
+    }
+
+    fn cache_control(&self) -> Option<&dyn CacheControl> {
+        self.inner.cache_control()
+    }
+
+    fn pending_sync(&self) -> Vec<PendingSync> {
+        self.inner.pending_sync()
+    }
+
+    fn invalidate_caches(&self) {
+        self.inner.invalidate_caches()
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Metadata> {
+        self.inner.stat(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path, offset: u64, size: u32) -> Result<Bytes> {
+        self.inner.read(path, offset, size).await
+    }
+
+    async fn write(&self, path: &Path, offset: u64, data: &[u8]) -> Result<u64> {
+        self.change("write", path, self.inner.write(path, offset, data))
+            .await
+    }
+
+    async fn write_dirty(&self, path: &Path, data: &[u8], dirty: &DirtyRanges) -> Result<bool> {
+        self.change("write", path, self.inner.write_dirty(path, data, dirty))
+            .await
+    }
+
+    async fn create_file(&self, path: &Path) -> Result<()> {
+        self.change("create", path, self.inner.create_file(path))
+            .await
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        self.change("mkdir", path, self.inner.create_dir(path))
+            .await
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<()> {
+        self.change("unlink", path, self.inner.remove_file(path))
+            .await
+    }
+
+    async fn remove_dir(&self, path: &Path, recursive: bool) -> Result<()> {
+        self.change("rmdir", path, self.inner.remove_dir(path, recursive))
+            .await
+    }
+
+    fn list_dir(&self, path: &Path) -> DirEntryStream {
+        self.inner.list_dir(path)
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.change("rename", from, self.inner.rename(from, to))
+            .await
+    }
+
+    async fn truncate(&self, path: &Path, size: u64) -> Result<()> {
+        self.change("truncate", path, self.inner.truncate(path, size))
+            .await
+    }
+
+    async fn flush(&self, path: &Path) -> Result<()> {
+        // A flush with nothing to upload succeeds without testing access,
+        // so only a denial counts
+        let result = self.inner.flush(path).await;
+        if let (Some(access), Err(e @ FuseAdapterError::PermissionDenied)) = (&self.access, &result)
+        {
+            access.denied("flush", path, e);
+        }
+        result
+    }
+
+    async fn create_file_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.change("create", path, self.inner.create_file_with_mode(path, mode))
+            .await
+    }
+
+    async fn create_dir_with_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.change("mkdir", path, self.inner.create_dir_with_mode(path, mode))
+            .await
+    }
+
+    async fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        self.change("chmod", path, self.inner.set_mode(path, mode))
+            .await
+    }
+
+    async fn readlink(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.readlink(path).await
+    }
+
+    async fn symlink(&self, target: &Path, link_path: &Path) -> Result<()> {
+        self.change("symlink", link_path, self.inner.symlink(target, link_path))
+            .await
+    }
+
+    async fn list_versions(&self, path: &Path) -> Result<Vec<FileVersion>> {
+        self.inner.list_versions(path).await
+    }
+
+    async fn read_version(
+        &self,
+        path: &Path,
+        version_id: &str,
+        offset: u64,
+        size: u32,
+    ) -> Result<Bytes> {
+        self.inner
+            .read_version(path, version_id, offset, size)
+            .await
+    }
+
+    async fn sync_all(&self) -> Result<()> {
+        self.inner.sync_all().await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        self.inner.shutdown().await
+    }
+
+    async fn content_hash(&self, path: &Path, etag: Option<&str>) -> Result<Option<ContentHash>> {
+        self.inner.content_hash(path, etag).await
+    }
+
+    async fn revalidate(&self, path: &Path, etag: &str) -> Result<bool> {
+        self.inner.revalidate(path, etag).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> Result<()> {
+        self.change("copy", to, self.inner.copy(from, to)).await
+    }
+
+    async fn zero_range(&self, path: &Path, offset: u64, len: u64) -> Result<()> {
+        self.change("zero", path, self.inner.zero_range(path, offset, len))
+            .await
+    }
+
+    async fn seek_region(
+        &self,
+        path: &Path,
+        offset: u64,
+        region: SeekRegion,
+    ) -> Result<Option<u64>> {
+        self.inner.seek_region(path, offset, region).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector::memfs::MemfsConnector;
+
+    fn monitor() -> (WriteAccessMonitor<MemfsConnector>, Arc<WriteAccess>) {
+        let events = Arc::new(ErrorEvents::new(10));
+        let access = Arc::new(WriteAccess::new(
+            &ReadOnlyFallbackConfig::default(),
+            Some(events),
+        ));
+        let monitor = WriteAccessMonitor::new(MemfsConnector::new(None), Some(access.clone()));
+        (monitor, access)
+    }
+
+    #[tokio::test]
+    async fn test_denied_write_is_lost_until_probe_succeeds() {
+        let (monitor, access) = monitor();
+        access.check().unwrap();
+
+        // Errors other than a denial leave access alone
+        let missing = monitor.remove_file(Path::new("/missing")).await;
+        assert!(missing.is_err());
+        assert!(!access.is_lost());
+
+        access.denied(
+            "write",
+            Path::new("/a"),
+            &FuseAdapterError::PermissionDenied,
+        );
+        assert!(matches!(access.check(), Err(FuseAdapterError::ReadOnly)));
+        let report = access.events.as_ref().unwrap().report();
+        assert!(report.contains("backend write /a: Permission denied; write access lost"));
+
+        access.probe(&monitor).await;
+        access.check().unwrap();
+        assert!(!monitor
+            .exists(Path::new("/.fuse-adapter-write-probe"))
+            .await
+            .unwrap());
+        let report = access.events.as_ref().unwrap().report();
+        assert!(report.contains("write access restored"));
+    }
+}
@@ -1,14 +1,21 @@
 //! Filesystem assertions for e2e tests
 //!
-//! Provides assertion functions for verifying filesystem state.
+//! Provides assertion functions for verifying filesystem state, and polling
+//! helpers (`wait_for_object`, `wait_for_file`, ...) for state that only
+//! becomes visible eventually, such as write-back cache syncs.
 
+use crate::minio::TestBucket;
 use anyhow::{Context, Result};
 use std::fs;
+use std::future::Future;
 use std::io::Read;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::{sleep, timeout};
 
+/// How often eventual-consistency helpers re-check their condition
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Assert that a file exists at the given path
 pub fn assert_file_exists(path: &Path) {
     assert!(
@@ -249,30 +256,189 @@ where
     F: Fn() -> Result<(), E>,
     E: std::fmt::Debug,
 {
-    let poll_interval = Duration::from_millis(100);
-    let mut last_error = None;
+    poll_until("Assertion", timeout_duration, || {
+        let result = f().map_err(|e| format!("{:?}", e));
+        async move { result }
+    })
+    .await
+}
 
-    timeout(timeout_duration, async {
-        loop {
-            match f() {
-                Ok(()) => return Ok::<_, anyhow::Error>(()),
-                Err(e) => {
-                    last_error = Some(format!("{:?}", e));
-                    sleep(poll_interval).await;
-                }
-            }
+/// Poll `check` until it returns `Ok` or `timeout_duration` has passed
+///
+/// The check always runs at least once, and once more at the deadline. On
+/// timeout the error names `what`, how long and how often it was checked,
+/// and the last failure `check` reported.
+async fn poll_until<T, F, Fut>(what: &str, timeout_duration: Duration, mut check: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let start = Instant::now();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let last_error = match check().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout_duration {
+            anyhow::bail!(
+                "{} did not succeed within {:?} ({} attempts over {:?}). Last error: {}",
+                what,
+                timeout_duration,
+                attempts,
+                elapsed,
+                last_error
+            );
         }
-    })
+        sleep(POLL_INTERVAL.min(timeout_duration - elapsed)).await;
+    }
+}
+
+/// Wait until `key` exists in `bucket`, returning its content
+///
+/// On timeout the error lists the objects next to where `key` was expected.
+pub async fn wait_for_object(
+    bucket: &TestBucket,
+    key: &str,
+    timeout_duration: Duration,
+) -> Result<Vec<u8>> {
+    let result = poll_until(
+        &format!("Waiting for object {:?}", key),
+        timeout_duration,
+        || async {
+            match bucket.object_exists(key).await {
+                Ok(true) => bucket.get_object(key).await.map_err(|e| format!("{:#}", e)),
+                Ok(false) => Err("object not found".to_string()),
+                Err(e) => Err(format!("{:#}", e)),
+            }
+        },
+    )
+    .await;
+    match result {
+        Ok(value) => Ok(value),
+        Err(e) => Err(anyhow::anyhow!("{}. {}", e, siblings_of(bucket, key).await)),
+    }
+}
+
+/// Wait until `key` exists in `bucket` with exactly `expected` as content
+pub async fn wait_for_object_content(
+    bucket: &TestBucket,
+    key: &str,
+    expected: &[u8],
+    timeout_duration: Duration,
+) -> Result<()> {
+    let result = poll_until(
+        &format!("Waiting for object {:?} to match", key),
+        timeout_duration,
+        || async {
+            match bucket.object_exists(key).await {
+                Ok(true) => {}
+                Ok(false) => return Err("object not found".to_string()),
+                Err(e) => return Err(format!("{:#}", e)),
+            }
+            let actual = bucket
+                .get_object(key)
+                .await
+                .map_err(|e| format!("{:#}", e))?;
+            if actual == expected {
+                Ok(())
+            } else {
+                Err(format!(
+                    "content mismatch: expected {} bytes ({:?}), got {} bytes ({:?})",
+                    expected.len(),
+                    preview(expected),
+                    actual.len(),
+                    preview(&actual)
+                ))
+            }
+        },
+    )
+    .await;
+    match result {
+        Ok(value) => Ok(value),
+        Err(e) => Err(anyhow::anyhow!("{}. {}", e, siblings_of(bucket, key).await)),
+    }
+}
+
+/// Wait until `key` no longer exists in `bucket`
+pub async fn wait_for_object_removed(
+    bucket: &TestBucket,
+    key: &str,
+    timeout_duration: Duration,
+) -> Result<()> {
+    poll_until(
+        &format!("Waiting for object {:?} to be removed", key),
+        timeout_duration,
+        || async {
+            match bucket.object_exists(key).await {
+                Ok(false) => Ok(()),
+                Ok(true) => Err("object still exists".to_string()),
+                Err(e) => Err(format!("{:#}", e)),
+            }
+        },
+    )
     .await
-    .map_err(|_| {
-        anyhow::anyhow!(
-            "Assertion did not succeed within {:?}. Last error: {:?}",
-            timeout_duration,
-            last_error
-        )
-    })??;
+}
+
+/// Wait until a regular file exists at `path`
+///
+/// On timeout the error lists the entries of the file's parent directory.
+pub async fn wait_for_file(path: &Path, timeout_duration: Duration) -> Result<()> {
+    let result = poll_until(
+        &format!("Waiting for file {:?}", path),
+        timeout_duration,
+        || {
+            let result = match fs::metadata(path) {
+                Ok(m) if m.is_file() => Ok(()),
+                Ok(_) => Err("path exists but is not a file".to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            async move { result }
+        },
+    )
+    .await;
+    result.map_err(|e| {
+        let parent = path.parent().unwrap_or(path);
+        let entries: Vec<String> = fs::read_dir(parent)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.file_name().to_string_lossy().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        anyhow::anyhow!("{}. Directory {:?} contains: {:?}", e, parent, entries)
+    })
+}
 
-    Ok(())
+/// Describe the objects sharing `key`'s parent prefix, for failure messages
+async fn siblings_of(bucket: &TestBucket, key: &str) -> String {
+    let prefix = key.rsplit_once('/').map_or("", |(parent, _)| parent);
+    let listing = bucket
+        .list_objects((!prefix.is_empty()).then_some(prefix))
+        .await;
+    match listing {
+        Ok(keys) => format!(
+            "Bucket {} has under {:?}: {:?}",
+            bucket.name(),
+            prefix,
+            keys
+        ),
+        Err(e) => format!("Failed to list bucket {}: {:#}", bucket.name(), e),
+    }
+}
+
+/// The start of `data` as text, for failure messages
+fn preview(data: &[u8]) -> String {
+    const MAX: usize = 64;
+    let text = String::from_utf8_lossy(&data[..data.len().min(MAX)]).to_string();
+    if data.len() > MAX {
+        format!("{}...", text)
+    } else {
+        text
+    }
 }
 
 /// Retry a fallible operation until it succeeds or times out
@@ -338,3 +504,39 @@ pub fn verify_file_integrity(path: &Path, expected_hash: &str) {
         path, expected_hash, actual_hash
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wait_for_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("late.txt");
+        std::thread::spawn({
+            let path = path.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(200));
+                fs::write(path, b"here").unwrap();
+            }
+        });
+        wait_for_file(&path, Duration::from_secs(5)).await.unwrap();
+
+        let err = wait_for_file(&dir.path().join("never.txt"), Duration::from_millis(250))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("never.txt"), "{}", err);
+        assert!(err.contains("late.txt"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_assert_eventually_reports_last_error() {
+        let err = assert_eventually(|| Err::<(), _>("still stale"), Duration::from_millis(250))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("still stale"), "{}", err);
+        assert!(err.contains("attempts"), "{}", err);
+    }
+}
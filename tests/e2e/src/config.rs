@@ -4,6 +4,7 @@
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 /// Status overlay configuration for virtual status directory
@@ -41,6 +42,12 @@ pub enum CacheConfig {
     None,
     Memory {
         max_entries: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_size: Option<String>,
+        #[serde(default = "default_flush_interval")]
+        flush_interval: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exclude_from_sync: Option<Vec<String>>,
     },
     Filesystem {
         path: PathBuf,
@@ -95,6 +102,63 @@ impl Default for S3ConnectorConfig {
     }
 }
 
+/// Google Drive connector authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GDriveAuthConfig {
+    Token { access_token: String },
+}
+
+/// Google Drive connector configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GDriveConnectorConfig {
+    #[serde(rename = "type")]
+    pub connector_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_folder_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export_formats: Option<BTreeMap<String, String>>,
+    pub auth: GDriveAuthConfig,
+}
+
+/// Connector configuration for a mount
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConnectorConfig {
+    S3(S3ConnectorConfig),
+    GDrive(GDriveConnectorConfig),
+}
+
+/// Fault injected into one connector operation
+///
+/// Only honored by a fuse-adapter binary built with `--features mock`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaultConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_next: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial_reads: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hang: Option<bool>,
+}
+
+/// Fault injection for a mount, by operation name (e.g. "read", "all")
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaultsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(flatten)]
+    pub operations: BTreeMap<String, FaultConfig>,
+}
+
 /// Mount point configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MountConfig {
@@ -109,7 +173,9 @@ pub struct MountConfig {
     pub error_mode: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status_overlay: Option<StatusOverlayConfig>,
-    pub connector: S3ConnectorConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub faults: Option<FaultsConfig>,
+    pub connector: ConnectorConfig,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cache: Option<CacheConfig>,
 }
@@ -134,6 +200,8 @@ pub struct TestConfig {
     pub logging: LoggingConfig,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub control_socket: Option<PathBuf>,
     pub mounts: Vec<MountConfig>,
 }
 
@@ -142,6 +210,7 @@ impl Default for TestConfig {
         Self {
             logging: LoggingConfig::default(),
             error_mode: Some("exit".to_string()),
+            control_socket: None,
             mounts: Vec::new(),
         }
     }
@@ -196,6 +265,12 @@ impl TestConfigBuilder {
         self
     }
 
+    /// Set the adapter's control socket path
+    pub fn control_socket(mut self, path: PathBuf) -> Self {
+        self.config.control_socket = Some(path);
+        self
+    }
+
     /// Set the default S3 endpoint for all mounts
     pub fn default_endpoint(mut self, endpoint: &str) -> Self {
         self.default_endpoint = Some(endpoint.to_string());
@@ -229,14 +304,15 @@ impl TestConfigBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.default_bucket.clone().unwrap_or_default(),
                 region: Some("us-east-1".to_string()),
                 prefix: None,
                 endpoint: self.default_endpoint.clone(),
                 force_path_style: Some(true),
-            },
+            }),
             cache: None,
         };
         self.config.mounts.push(mount);
@@ -261,14 +337,15 @@ impl TestConfigBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.default_bucket.clone().unwrap_or_default(),
                 region: Some("us-east-1".to_string()),
                 prefix,
                 endpoint: self.default_endpoint.clone(),
                 force_path_style: Some(true),
-            },
+            }),
             cache: None,
         };
         self.config.mounts.push(mount);
@@ -279,11 +356,14 @@ impl TestConfigBuilder {
     pub fn build(mut self) -> TestConfig {
         // Apply defaults to all mounts that don't have explicit values
         for mount in &mut self.config.mounts {
-            if mount.connector.endpoint.is_none() {
-                mount.connector.endpoint = self.default_endpoint.clone();
+            let ConnectorConfig::S3(connector) = &mut mount.connector else {
+                continue;
+            };
+            if connector.endpoint.is_none() {
+                connector.endpoint = self.default_endpoint.clone();
             }
-            if mount.connector.bucket.is_empty() {
-                mount.connector.bucket = self.default_bucket.clone().unwrap_or_default();
+            if connector.bucket.is_empty() {
+                connector.bucket = self.default_bucket.clone().unwrap_or_default();
             }
         }
         self.config
@@ -316,14 +396,15 @@ pub fn standard_test_config(
         gid: None,
         error_mode: None,
         status_overlay: None,
-        connector: S3ConnectorConfig {
+        faults: None,
+        connector: ConnectorConfig::S3(S3ConnectorConfig {
             connector_type: "s3".to_string(),
             bucket: bucket.to_string(),
             region: Some("us-east-1".to_string()),
             prefix: None,
             endpoint: Some(endpoint.to_string()),
             force_path_style: Some(true),
-        },
+        }),
         cache,
     };
 
@@ -356,9 +437,14 @@ pub fn filesystem_cache_with_interval(cache_dir: PathBuf, flush_interval_secs: u
     }
 }
 
-/// Create a memory cache config
+/// Create a memory cache config with the default test flush interval
 pub fn memory_cache(max_entries: usize) -> CacheConfig {
-    CacheConfig::Memory { max_entries }
+    CacheConfig::Memory {
+        max_entries,
+        max_size: Some("256MB".to_string()),
+        flush_interval: format!("{}s", DEFAULT_TEST_FLUSH_INTERVAL_SECS),
+        exclude_from_sync: None,
+    }
 }
 
 #[cfg(test)]
@@ -376,7 +462,10 @@ mod tests {
 
         assert_eq!(config.logging.level, "trace");
         assert_eq!(config.mounts.len(), 1);
-        assert_eq!(config.mounts[0].connector.bucket, "test-bucket");
+        assert!(matches!(
+            &config.mounts[0].connector,
+            ConnectorConfig::S3(s3) if s3.bucket == "test-bucket"
+        ));
     }
 
     #[test]
@@ -393,4 +482,19 @@ mod tests {
         assert!(yaml.contains("localhost:9000"));
         assert!(yaml.contains("filesystem"));
     }
+
+    #[test]
+    fn test_memory_cache_to_yaml() {
+        let config = standard_test_config(
+            "/mnt/test".into(),
+            "test-bucket",
+            "http://localhost:9000",
+            Some(memory_cache(100)),
+        );
+
+        let yaml = config.to_yaml().unwrap();
+        assert!(yaml.contains("max_entries: 100"));
+        assert!(yaml.contains("max_size: 256MB"));
+        assert!(yaml.contains("flush_interval: 5s"));
+    }
 }
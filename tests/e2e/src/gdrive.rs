@@ -0,0 +1,700 @@
+//! In-process fake of the Google Drive API
+//!
+//! Implements the part of the Drive v3 REST surface the gdrive connector
+//! uses: `files.list` with parent/name/mimeType queries, `files.get` for
+//! metadata and media, multipart `files.create` and `files.update`, parent
+//! moves, `files.copy`, `files.delete` and `files.export`. Files live in
+//! memory keyed by ID, with `root` as My Drive. The adapter is pointed at
+//! it through the gdrive connector's `endpoint` and authenticates with the
+//! static token from `access_token()`.
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use md5::{Digest, Md5};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+/// Bearer token the fake accepts
+const ACCESS_TOKEN: &str = "fake-drive-token";
+
+/// ID of My Drive's root folder
+const ROOT_ID: &str = "root";
+
+/// MIME type for Drive folders
+const FOLDER_MIME_TYPE: &str = "application/vnd.google-apps.folder";
+
+/// MIME type prefix for native Google files (Docs, Sheets, ...)
+const GOOGLE_APPS_MIME_PREFIX: &str = "application/vnd.google-apps.";
+
+/// Default and maximum page size of files.list
+const MAX_PAGE_SIZE: usize = 100;
+
+/// A file or folder stored by the fake
+#[derive(Debug, Clone)]
+struct DriveFile {
+    id: String,
+    name: String,
+    mime_type: String,
+    parents: Vec<String>,
+    content: Vec<u8>,
+    modified: DateTime<Utc>,
+}
+
+impl DriveFile {
+    fn is_folder(&self) -> bool {
+        self.mime_type == FOLDER_MIME_TYPE
+    }
+
+    /// Whether this is a native Google file (or folder) without media
+    fn is_native(&self) -> bool {
+        self.mime_type.starts_with(GOOGLE_APPS_MIME_PREFIX)
+    }
+
+    fn to_json(&self) -> Value {
+        let mut file = json!({
+            "kind": "drive#file",
+            "id": self.id,
+            "name": self.name,
+            "mimeType": self.mime_type,
+            "parents": self.parents,
+            "modifiedTime": self.modified.to_rfc3339_opts(SecondsFormat::Millis, true),
+        });
+        // Like Drive, only files with media have a size and checksum
+        if !self.is_native() {
+            file["size"] = json!(self.content.len().to_string());
+            file["md5Checksum"] = json!(hex::encode(Md5::digest(&self.content)));
+        }
+        file
+    }
+}
+
+/// Files of the fake drive by ID
+#[derive(Default)]
+struct State {
+    files: HashMap<String, DriveFile>,
+    next_id: u64,
+}
+
+impl State {
+    fn insert(&mut self, name: &str, mime_type: &str, parent: &str, content: Vec<u8>) -> String {
+        self.next_id += 1;
+        let id = format!("fake-{}", self.next_id);
+        self.files.insert(
+            id.clone(),
+            DriveFile {
+                id: id.clone(),
+                name: name.to_string(),
+                mime_type: mime_type.to_string(),
+                parents: vec![parent.to_string()],
+                content,
+                modified: Utc::now(),
+            },
+        );
+        id
+    }
+
+    /// Children of `parent`, ordered by name then ID
+    fn children(&self, parent: &str) -> Vec<&DriveFile> {
+        let mut children: Vec<_> = self
+            .files
+            .values()
+            .filter(|f| f.parents.iter().any(|p| p == parent))
+            .collect();
+        children.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+        children
+    }
+
+    fn child(&self, parent: &str, name: &str) -> Option<&DriveFile> {
+        self.children(parent).into_iter().find(|f| f.name == name)
+    }
+
+    /// Resolve a slash-separated path from the root to a file ID
+    fn resolve(&self, path: &str) -> Option<String> {
+        let mut id = ROOT_ID.to_string();
+        for name in path.split('/').filter(|s| !s.is_empty()) {
+            id = self.child(&id, name)?.id.clone();
+        }
+        Some(id)
+    }
+
+    /// Resolve the parent folder of `path`, creating missing folders
+    fn parent_of(&mut self, path: &str) -> (String, String) {
+        let mut names: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let name = names.pop().unwrap_or_default().to_string();
+        let mut id = ROOT_ID.to_string();
+        for folder in names {
+            id = match self.child(&id, folder) {
+                Some(f) => f.id.clone(),
+                None => self.insert(folder, FOLDER_MIME_TYPE, &id, Vec::new()),
+            };
+        }
+        (id, name)
+    }
+
+    /// Remove a file and, for folders, everything below it
+    fn remove(&mut self, id: &str) {
+        let children: Vec<String> = self.children(id).iter().map(|f| f.id.clone()).collect();
+        for child in children {
+            self.remove(&child);
+        }
+        self.files.remove(id);
+    }
+}
+
+/// Fake Google Drive API server
+pub struct FakeDrive {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    accept_task: JoinHandle<()>,
+}
+
+impl FakeDrive {
+    /// Start serving on a local port
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn start() -> Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")
+            .context("Failed to bind fake Drive listener")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let listener = TcpListener::from_std(listener)?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        info!("Fake Drive API listening on {}", addr);
+
+        let accept_task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    let service = service_fn(move |req| handle(state.clone(), req));
+                    tokio::spawn(async move {
+                        if let Err(e) = http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await
+                        {
+                            debug!("Fake Drive connection failed: {}", e);
+                        }
+                    });
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            accept_task,
+        })
+    }
+
+    /// Endpoint URL to configure the gdrive connector with
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Static access token the fake accepts
+    pub fn access_token(&self) -> &str {
+        ACCESS_TOKEN
+    }
+
+    /// Create a folder (and any missing parents), returning its ID
+    pub fn create_folder(&self, path: &str) -> String {
+        let mut state = self.state.lock().unwrap();
+        match state.resolve(path) {
+            Some(id) => id,
+            None => {
+                let (parent, name) = state.parent_of(path);
+                state.insert(&name, FOLDER_MIME_TYPE, &parent, Vec::new())
+            }
+        }
+    }
+
+    /// Create or replace a regular file
+    pub fn put_file(&self, path: &str, content: &[u8]) {
+        self.put(path, "application/octet-stream", content);
+    }
+
+    /// Create a native Google file of `kind` (e.g. "document") whose
+    /// exports return `content` regardless of the requested format
+    pub fn put_native(&self, path: &str, kind: &str, content: &[u8]) {
+        self.put(
+            path,
+            &format!("{}{}", GOOGLE_APPS_MIME_PREFIX, kind),
+            content,
+        );
+    }
+
+    fn put(&self, path: &str, mime_type: &str, content: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        let (parent, name) = state.parent_of(path);
+        if let Some(id) = state.child(&parent, &name).map(|f| f.id.clone()) {
+            state.remove(&id);
+        }
+        state.insert(&name, mime_type, &parent, content.to_vec());
+    }
+
+    /// Content of the file at `path`, if it exists
+    pub fn get_file(&self, path: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        let id = state.resolve(path)?;
+        Some(state.files.get(&id)?.content.clone())
+    }
+
+    /// Whether a file or folder exists at `path`
+    pub fn exists(&self, path: &str) -> bool {
+        self.state.lock().unwrap().resolve(path).is_some()
+    }
+
+    /// Names of the entries in the folder at `path`
+    pub fn list(&self, path: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        match state.resolve(path) {
+            Some(id) => state.children(&id).iter().map(|f| f.name.clone()).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Drop for FakeDrive {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+type FakeResponse = Response<Full<Bytes>>;
+
+fn json_response(status: StatusCode, body: Value) -> FakeResponse {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+/// Error in Drive's format; the connector looks for the reason (e.g. "notFound")
+fn error_response(status: StatusCode, reason: &str, message: &str) -> FakeResponse {
+    json_response(
+        status,
+        json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message,
+                "errors": [{ "domain": "global", "reason": reason, "message": message }],
+            }
+        }),
+    )
+}
+
+fn not_found(id: &str) -> FakeResponse {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "notFound",
+        &format!("File not found: {}", id),
+    )
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    req: Request<Incoming>,
+) -> std::result::Result<FakeResponse, Infallible> {
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v == format!("Bearer {}", ACCESS_TOKEN));
+    if !authorized {
+        return Ok(error_response(
+            StatusCode::UNAUTHORIZED,
+            "authError",
+            "Invalid Credentials",
+        ));
+    }
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let params: HashMap<String, String> = req
+        .uri()
+        .query()
+        .map(|q| form_urlencoded::parse(q.as_bytes()).into_owned().collect())
+        .unwrap_or_default();
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = match req.into_body().collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) => {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                "badRequest",
+                &e.to_string(),
+            ))
+        }
+    };
+
+    debug!("Fake Drive: {} {} {:?}", method, path, params);
+
+    // Metadata and media, from either a multipart upload or a JSON body
+    let (metadata, media) = if path.starts_with("/upload/") {
+        match parse_multipart(&content_type, &body) {
+            Some((metadata, media)) => (metadata, Some(media)),
+            None => {
+                return Ok(error_response(
+                    StatusCode::BAD_REQUEST,
+                    "badRequest",
+                    "Expected a multipart/related upload",
+                ))
+            }
+        }
+    } else {
+        (serde_json::from_slice(&body).unwrap_or(Value::Null), None)
+    };
+
+    let route = path
+        .strip_prefix("/upload/drive/v3/files")
+        .or_else(|| path.strip_prefix("/drive/v3/files"));
+    let Some(route) = route else {
+        return Ok(error_response(
+            StatusCode::NOT_FOUND,
+            "notFound",
+            &format!("Unknown path {}", path),
+        ));
+    };
+    let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut state = state.lock().unwrap();
+    let response = match (&method, segments.as_slice()) {
+        (&Method::GET, []) => list(&state, &params),
+        (&Method::POST, []) => create(&mut state, &metadata, media),
+        (&Method::GET, [id]) => get(&state, id, &params),
+        (&Method::PATCH, [id]) => update(&mut state, id, &metadata, media, &params),
+        (&Method::DELETE, [id]) => {
+            if state.files.contains_key(*id) {
+                state.remove(id);
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Full::new(Bytes::new()))
+                    .unwrap()
+            } else {
+                not_found(id)
+            }
+        }
+        (&Method::POST, [id, "copy"]) => copy(&mut state, id, &metadata),
+        (&Method::GET, [id, "export"]) => export(&state, id),
+        _ => error_response(
+            StatusCode::NOT_FOUND,
+            "notFound",
+            &format!("Unsupported request {} {}", method, path),
+        ),
+    };
+    Ok(response)
+}
+
+fn list(state: &State, params: &HashMap<String, String>) -> FakeResponse {
+    let query = parse_query(params.get("q").map(String::as_str).unwrap_or_default());
+    let Some(parent) = query.parent else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid",
+            "The fake only supports queries by parent",
+        );
+    };
+
+    let matching: Vec<&DriveFile> = state
+        .children(&parent)
+        .into_iter()
+        .filter(|f| query.name.as_ref().is_none_or(|n| *n == f.name))
+        .filter(|f| query.mime_type.as_ref().is_none_or(|m| *m == f.mime_type))
+        .collect();
+
+    let page_size = params
+        .get("pageSize")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(MAX_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let start: usize = params
+        .get("pageToken")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    let end = (start + page_size).min(matching.len());
+
+    let mut body = json!({
+        "kind": "drive#fileList",
+        "files": matching
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|f| f.to_json())
+            .collect::<Vec<_>>(),
+    });
+    if end < matching.len() {
+        body["nextPageToken"] = json!(end.to_string());
+    }
+    json_response(StatusCode::OK, body)
+}
+
+fn get(state: &State, id: &str, params: &HashMap<String, String>) -> FakeResponse {
+    let Some(file) = state.files.get(id) else {
+        return not_found(id);
+    };
+    if params.get("alt").map(String::as_str) != Some("media") {
+        return json_response(StatusCode::OK, file.to_json());
+    }
+    if file.is_native() {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "fileNotDownloadable",
+            "Only files with binary content can be downloaded",
+        );
+    }
+    Response::builder()
+        .header(header::CONTENT_TYPE, &file.mime_type)
+        .body(Full::new(Bytes::from(file.content.clone())))
+        .unwrap()
+}
+
+fn create(state: &mut State, metadata: &Value, media: Option<Bytes>) -> FakeResponse {
+    let name = metadata["name"].as_str().unwrap_or("Untitled");
+    let mime_type = metadata["mimeType"]
+        .as_str()
+        .unwrap_or("application/octet-stream");
+    let parent = metadata["parents"][0].as_str().unwrap_or(ROOT_ID);
+    if parent != ROOT_ID && !state.files.contains_key(parent) {
+        return not_found(parent);
+    }
+
+    let content = media.map(|m| m.to_vec()).unwrap_or_default();
+    let id = state.insert(name, mime_type, parent, content);
+    json_response(StatusCode::OK, state.files[&id].to_json())
+}
+
+fn update(
+    state: &mut State,
+    id: &str,
+    metadata: &Value,
+    media: Option<Bytes>,
+    params: &HashMap<String, String>,
+) -> FakeResponse {
+    let Some(file) = state.files.get_mut(id) else {
+        return not_found(id);
+    };
+
+    if let Some(name) = metadata["name"].as_str() {
+        file.name = name.to_string();
+    }
+    if let Some(remove) = params.get("removeParents") {
+        file.parents.retain(|p| !remove.split(',').any(|r| r == p));
+    }
+    if let Some(add) = params.get("addParents") {
+        file.parents.extend(add.split(',').map(str::to_string));
+    }
+    if let Some(media) = media {
+        file.content = media.to_vec();
+    }
+    file.modified = Utc::now();
+    json_response(StatusCode::OK, file.to_json())
+}
+
+fn copy(state: &mut State, id: &str, metadata: &Value) -> FakeResponse {
+    let Some(source) = state.files.get(id).cloned() else {
+        return not_found(id);
+    };
+    let name = metadata["name"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Copy of {}", source.name));
+    let parent = metadata["parents"][0]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| source.parents[0].clone());
+
+    let id = state.insert(&name, &source.mime_type, &parent, source.content);
+    json_response(StatusCode::OK, state.files[&id].to_json())
+}
+
+fn export(state: &State, id: &str) -> FakeResponse {
+    let Some(file) = state.files.get(id) else {
+        return not_found(id);
+    };
+    if !file.is_native() || file.is_folder() {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "fileNotExportable",
+            "Export only supports Docs Editors files",
+        );
+    }
+    Response::builder()
+        .body(Full::new(Bytes::from(file.content.clone())))
+        .unwrap()
+}
+
+/// The parts of a files.list query the connector sends
+#[derive(Debug, Default, PartialEq)]
+struct Query {
+    parent: Option<String>,
+    name: Option<String>,
+    mime_type: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+}
+
+/// Parse a Drive search query such as
+/// `'<id>' in parents and name = '<name>' and trashed = false`
+///
+/// Unrecognized terms are ignored; nothing is ever trashed in the fake.
+fn parse_query(q: &str) -> Query {
+    let mut tokens = Vec::new();
+    let mut chars = q.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '\'' {
+            chars.next();
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => s.extend(chars.next()),
+                    '\'' => break,
+                    c => s.push(c),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '\'' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+
+    let word = |t: Option<&Token>, w: &str| matches!(t, Some(Token::Word(x)) if x == w);
+    let mut query = Query::default();
+    for i in 0..tokens.len() {
+        match &tokens[i] {
+            Token::Str(s)
+                if word(tokens.get(i + 1), "in") && word(tokens.get(i + 2), "parents") =>
+            {
+                query.parent = Some(s.clone());
+            }
+            Token::Word(field) if word(tokens.get(i + 1), "=") => {
+                if let Some(Token::Str(value)) = tokens.get(i + 2) {
+                    match field.as_str() {
+                        "name" => query.name = Some(value.clone()),
+                        "mimeType" => query.mime_type = Some(value.clone()),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    query
+}
+
+/// Split a multipart/related upload into its JSON metadata and media
+fn parse_multipart(content_type: &str, body: &[u8]) -> Option<(Value, Bytes)> {
+    let boundary = content_type
+        .split(';')
+        .find_map(|p| p.trim().strip_prefix("boundary="))?
+        .trim_matches('"');
+    let delimiter = format!("--{}", boundary);
+
+    let mut parts = Vec::new();
+    let mut rest = body;
+    while let Some(pos) = find(rest, delimiter.as_bytes()) {
+        parts.push(&rest[..pos]);
+        rest = &rest[pos + delimiter.len()..];
+    }
+    // parts[0] is the preamble; what follows the last delimiter is "--"
+    let mut contents = parts.into_iter().skip(1).map(|part| {
+        let part = part.strip_prefix(b"\r\n").unwrap_or(part);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        let start = find(part, b"\r\n\r\n").map_or(part.len(), |p| p + 4);
+        &part[start..]
+    });
+
+    let metadata = serde_json::from_slice(contents.next()?).ok()?;
+    let media = Bytes::copy_from_slice(contents.next().unwrap_or_default());
+    Some((metadata, media))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query(
+            "'abc' in parents and name = 'it\\'s' and mimeType = 'text/plain' and trashed = false",
+        );
+        assert_eq!(
+            query,
+            Query {
+                parent: Some("abc".to_string()),
+                name: Some("it's".to_string()),
+                mime_type: Some("text/plain".to_string()),
+            }
+        );
+
+        let query = parse_query("'root' in parents and trashed = false");
+        assert_eq!(query.parent.as_deref(), Some("root"));
+        assert_eq!(query.name, None);
+    }
+
+    #[test]
+    fn test_parse_multipart() {
+        let body = b"\r\n--B\r\ncontent-type: application/json\r\n\r\n{\"name\":\"a\"}\
+            \r\n--B\r\ncontent-type: application/octet-stream\r\n\r\nline1\r\nline2\
+            \r\n--B--\r\n";
+        let (metadata, media) = parse_multipart("multipart/related; boundary=B", body).unwrap();
+        assert_eq!(metadata["name"], "a");
+        assert_eq!(&media[..], b"line1\r\nline2");
+
+        assert!(parse_multipart("application/json", body).is_none());
+    }
+
+    #[test]
+    fn test_fake_paths() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let drive = FakeDrive::start().unwrap();
+
+        drive.put_file("a/b/file.txt", b"one");
+        drive.put_file("a/b/file.txt", b"two");
+        assert_eq!(drive.get_file("a/b/file.txt").unwrap(), b"two");
+        assert_eq!(drive.list("a/b"), vec!["file.txt"]);
+
+        let id = drive.create_folder("a/c");
+        assert_eq!(drive.create_folder("a/c"), id);
+        assert_eq!(drive.list("a"), vec!["b", "c"]);
+        assert!(!drive.exists("a/missing"));
+    }
+}
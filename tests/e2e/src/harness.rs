@@ -35,19 +35,26 @@
 //! }
 //! ```
 
+use crate::assertions::wait_for_object;
 use crate::config::{
-    filesystem_cache, filesystem_cache_fast, CacheConfig, MountConfig, S3ConnectorConfig,
-    StatusOverlayConfig, TestConfig, TestConfigBuilder, FAST_FLUSH_INTERVAL_SECS,
+    filesystem_cache, filesystem_cache_fast, memory_cache, CacheConfig, ConnectorConfig,
+    FaultConfig, FaultsConfig, GDriveAuthConfig, GDriveConnectorConfig, MountConfig,
+    S3ConnectorConfig, StatusOverlayConfig, TestConfig, TestConfigBuilder,
+    FAST_FLUSH_INTERVAL_SECS,
 };
+use crate::gdrive::FakeDrive;
 use crate::minio::{MinioContainer, TestBucket};
-use crate::mount::MountedAdapter;
+use crate::mount::{AdapterLogs, MountedAdapter};
+use crate::proxy::NetworkProxy;
 use anyhow::{Context, Result};
 use std::env;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
 use tokio::time::sleep;
 use tracing::{debug, info};
 use tracing_subscriber::EnvFilter;
@@ -88,12 +95,59 @@ impl TestCacheType {
     pub fn flush_interval_secs(&self) -> u64 {
         match self {
             TestCacheType::FilesystemFast => FAST_FLUSH_INTERVAL_SECS,
-            TestCacheType::Filesystem => crate::config::DEFAULT_TEST_FLUSH_INTERVAL_SECS,
-            _ => 0, // No cache or memory cache don't have flush intervals
+            TestCacheType::Filesystem | TestCacheType::Memory => {
+                crate::config::DEFAULT_TEST_FLUSH_INTERVAL_SECS
+            }
+            TestCacheType::None => 0, // Writes go straight to the backend
         }
     }
 }
 
+/// Send a command to the control socket of the adapter running `config`
+async fn control(config: &TestConfig, command: &str) -> Result<String> {
+    let socket = config
+        .control_socket
+        .as_ref()
+        .context("No control socket configured")?;
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("Failed to connect to control socket {:?}", socket))?;
+    stream.write_all(command.as_bytes()).await?;
+    stream.shutdown().await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+
+    match response.strip_prefix("ok\n") {
+        Some(output) => Ok(output.to_string()),
+        None => anyhow::bail!(
+            "Control command {:?} failed: {}",
+            command.lines().next().unwrap_or(""),
+            response.trim()
+        ),
+    }
+}
+
+/// Have the adapter running `config` sync every mount's pending changes
+async fn force_sync(config: &TestConfig) -> Result<()> {
+    debug!("Forcing cache sync");
+    control(config, "sync").await?;
+    Ok(())
+}
+
+/// Start the adapter for `config`, in-process or as a child process
+async fn start_adapter(
+    config: &TestConfig,
+    config_path: &Path,
+    logs: AdapterLogs,
+    in_process: bool,
+) -> Result<MountedAdapter> {
+    if in_process {
+        MountedAdapter::start_in_process(config, config_path, logs).await
+    } else {
+        MountedAdapter::start_with_logs(config, config_path, logs).await
+    }
+}
+
 // ============================================================================
 // SharedHarness - Efficient shared harness for parallel tests
 // ============================================================================
@@ -108,7 +162,6 @@ pub struct SharedHarness {
     minio: Arc<MinioContainer>,
     bucket: TestBucket,
     adapter: MountedAdapter,
-    #[allow(dead_code)]
     config: TestConfig,
     #[allow(dead_code)]
     temp_dir: TempDir, // Kept alive to preserve mount/cache directories
@@ -117,6 +170,7 @@ pub struct SharedHarness {
     cache_path: PathBuf,
     flush_interval_secs: u64,
     context_counter: AtomicU64,
+    proxy: Option<NetworkProxy>,
 }
 
 impl SharedHarness {
@@ -127,6 +181,19 @@ impl SharedHarness {
 
     /// Create a shared harness with a specific cache type
     pub async fn with_cache(cache_type: TestCacheType) -> Result<Self> {
+        Self::start(cache_type, false).await
+    }
+
+    /// Create a shared harness whose adapter reaches MinIO through a
+    /// network proxy, so contexts can degrade the connection mid-test
+    ///
+    /// Network conditions apply to the whole harness, so tests changing
+    /// them shouldn't run concurrently with each other.
+    pub async fn with_proxy(cache_type: TestCacheType) -> Result<Self> {
+        Self::start(cache_type, true).await
+    }
+
+    async fn start(cache_type: TestCacheType, proxied: bool) -> Result<Self> {
         init_logging();
 
         info!(
@@ -140,11 +207,21 @@ impl SharedHarness {
         // Create a shared test bucket
         let bucket = minio.create_test_bucket().await?;
 
+        let proxy = if proxied {
+            Some(minio.with_proxy().await?)
+        } else {
+            None
+        };
+        let endpoint = proxy
+            .as_ref()
+            .map_or_else(|| minio.endpoint().to_string(), |p| p.endpoint());
+
         // Create temp directories
         let temp_dir = TempDir::new().context("Failed to create temp directory")?;
         let mount_path = temp_dir.path().join("mount");
         let cache_path = temp_dir.path().join("cache");
         let config_path = temp_dir.path().join("config.yaml");
+        let control_socket = temp_dir.path().join("control.sock");
 
         std::fs::create_dir_all(&mount_path)?;
         std::fs::create_dir_all(&cache_path)?;
@@ -154,7 +231,7 @@ impl SharedHarness {
         // Build cache config
         let cache_config = match cache_type {
             TestCacheType::None => None,
-            TestCacheType::Memory => Some(CacheConfig::Memory { max_entries: 1000 }),
+            TestCacheType::Memory => Some(memory_cache(1000)),
             TestCacheType::Filesystem => Some(filesystem_cache(cache_path.clone())),
             TestCacheType::FilesystemFast => Some(filesystem_cache_fast(cache_path.clone())),
         };
@@ -163,6 +240,7 @@ impl SharedHarness {
         let config = TestConfigBuilder::new()
             .logging_level("debug")
             .error_mode("exit")
+            .control_socket(control_socket)
             .add_mount(MountConfig {
                 path: mount_path.clone(),
                 read_only: None,
@@ -170,14 +248,15 @@ impl SharedHarness {
                 gid: None,
                 error_mode: None,
                 status_overlay: None,
-                connector: S3ConnectorConfig {
+                faults: None,
+                connector: ConnectorConfig::S3(S3ConnectorConfig {
                     connector_type: "s3".to_string(),
                     bucket: bucket.name().to_string(),
                     region: Some("us-east-1".to_string()),
                     prefix: None, // No prefix - tests use subdirectories
-                    endpoint: Some(minio.endpoint().to_string()),
+                    endpoint: Some(endpoint),
                     force_path_style: Some(true),
-                },
+                }),
                 cache: cache_config,
             })
             .build();
@@ -197,6 +276,7 @@ impl SharedHarness {
             cache_path,
             flush_interval_secs,
             context_counter: AtomicU64::new(0),
+            proxy,
         })
     }
 
@@ -218,6 +298,7 @@ impl SharedHarness {
             harness: self,
             prefix,
             test_dir,
+            log_cursor: AtomicUsize::new(self.adapter.logs().cursor()),
         })
     }
 
@@ -236,21 +317,19 @@ impl SharedHarness {
         &self.bucket
     }
 
+    /// Get the adapter's captured output
+    pub fn logs(&self) -> &AdapterLogs {
+        self.adapter.logs()
+    }
+
     /// Get the flush interval in seconds
     pub fn flush_interval_secs(&self) -> u64 {
         self.flush_interval_secs
     }
 
-    /// Force a cache sync by waiting for the flush interval plus buffer
+    /// Sync pending cache changes to MinIO, returning once they're written
     pub async fn force_sync(&self) -> Result<()> {
-        if self.flush_interval_secs == 0 {
-            // No cache or memory cache - no sync needed
-            return Ok(());
-        }
-        let wait_secs = self.flush_interval_secs + 1;
-        info!("Forcing cache sync (waiting {}s)...", wait_secs);
-        sleep(Duration::from_secs(wait_secs)).await;
-        Ok(())
+        force_sync(&self.config).await
     }
 
     /// Shutdown the shared harness.
@@ -298,6 +377,8 @@ pub struct TestContext<'a> {
     harness: &'a SharedHarness,
     prefix: String,
     test_dir: PathBuf,
+    /// Start of the adapter output not yet taken by this context
+    log_cursor: AtomicUsize,
 }
 
 impl<'a> TestContext<'a> {
@@ -338,6 +419,12 @@ impl<'a> TestContext<'a> {
         self.harness.bucket.get_object(&key).await
     }
 
+    /// Wait until an object appears in this test's prefix, returning its content
+    pub async fn wait_for_object(&self, name: &str, timeout: Duration) -> Result<Vec<u8>> {
+        let key = format!("{}/{}", self.prefix, name);
+        wait_for_object(&self.harness.bucket, &key, timeout).await
+    }
+
     /// List objects in this test's prefix
     pub async fn list_objects(&self) -> Result<Vec<String>> {
         let prefix = format!("{}/", self.prefix);
@@ -354,6 +441,61 @@ impl<'a> TestContext<'a> {
         self.harness.force_sync().await
     }
 
+    /// Take the adapter output logged since this context was created (or
+    /// since the last `take_logs`)
+    ///
+    /// The adapter is shared, so this includes lines caused by other tests
+    /// running in parallel; filter on `prefix()` where that matters.
+    pub fn take_logs(&self) -> String {
+        self.harness.logs().take(&self.log_cursor)
+    }
+
+    /// Assert the adapter logged a line containing `pattern` since this
+    /// context was created (or since the last `take_logs`)
+    pub async fn assert_log_contains(&self, pattern: &str) {
+        self.harness
+            .logs()
+            .assert_contains(self.log_cursor.load(Ordering::SeqCst), pattern)
+            .await;
+    }
+
+    fn proxy(&self) -> Result<&NetworkProxy> {
+        self.harness
+            .proxy
+            .as_ref()
+            .context("Harness has no network proxy (use SharedHarness::with_proxy)")
+    }
+
+    /// Delay traffic between the adapter and MinIO by `latency` each way
+    pub fn add_latency(&self, latency: Duration) -> Result<()> {
+        self.proxy()?.add_latency(latency);
+        Ok(())
+    }
+
+    /// Limit traffic between the adapter and MinIO to `bytes_per_sec`
+    pub fn throttle_bandwidth(&self, bytes_per_sec: u64) -> Result<()> {
+        self.proxy()?.throttle_bandwidth(bytes_per_sec);
+        Ok(())
+    }
+
+    /// Drop the adapter's connections to MinIO until restored
+    pub fn cut_connection(&self) -> Result<()> {
+        self.proxy()?.cut_connection();
+        Ok(())
+    }
+
+    /// Let the adapter reach MinIO again after `cut_connection`
+    pub fn restore_connection(&self) -> Result<()> {
+        self.proxy()?.restore_connection();
+        Ok(())
+    }
+
+    /// Remove all latency, throttling and cuts
+    pub fn reset_network(&self) -> Result<()> {
+        self.proxy()?.reset();
+        Ok(())
+    }
+
     /// Cleanup this test context.
     ///
     /// This removes all files created by this test from both the filesystem
@@ -415,6 +557,13 @@ pub struct TestHarness {
     cache_path: PathBuf,
     config_path: PathBuf,
     flush_interval_secs: u64,
+    gdrive: Option<FakeDrive>,
+    /// Output of the adapter, kept across restarts
+    logs: AdapterLogs,
+    /// Start of the adapter output not yet taken by `take_logs`
+    log_cursor: AtomicUsize,
+    /// Whether the adapter runs inside the test process
+    in_process: bool,
 }
 
 impl TestHarness {
@@ -440,6 +589,7 @@ impl TestHarness {
         let mount_path = temp_dir.path().join("mount");
         let cache_path = temp_dir.path().join("cache");
         let config_path = temp_dir.path().join("config.yaml");
+        let control_socket = temp_dir.path().join("control.sock");
 
         std::fs::create_dir_all(&mount_path)?;
         std::fs::create_dir_all(&cache_path)?;
@@ -449,7 +599,7 @@ impl TestHarness {
         // Build cache config
         let cache_config = match cache_type {
             TestCacheType::None => None,
-            TestCacheType::Memory => Some(CacheConfig::Memory { max_entries: 1000 }),
+            TestCacheType::Memory => Some(memory_cache(1000)),
             TestCacheType::Filesystem => Some(filesystem_cache(cache_path.clone())),
             TestCacheType::FilesystemFast => Some(filesystem_cache_fast(cache_path.clone())),
         };
@@ -458,6 +608,7 @@ impl TestHarness {
         let config = TestConfigBuilder::new()
             .logging_level("debug")
             .error_mode("exit")
+            .control_socket(control_socket)
             .add_mount(MountConfig {
                 path: mount_path.clone(),
                 read_only: None,
@@ -465,20 +616,22 @@ impl TestHarness {
                 gid: None,
                 error_mode: None,
                 status_overlay: None,
-                connector: S3ConnectorConfig {
+                faults: None,
+                connector: ConnectorConfig::S3(S3ConnectorConfig {
                     connector_type: "s3".to_string(),
                     bucket: bucket.name().to_string(),
                     region: Some("us-east-1".to_string()),
                     prefix: None,
                     endpoint: Some(minio.endpoint().to_string()),
                     force_path_style: Some(true),
-                },
+                }),
                 cache: cache_config,
             })
             .build();
 
         // Start the adapter
-        let adapter = MountedAdapter::start(&config, &config_path).await?;
+        let logs = AdapterLogs::new();
+        let adapter = MountedAdapter::start_with_logs(&config, &config_path, logs.clone()).await?;
 
         Ok(Self {
             minio,
@@ -490,6 +643,10 @@ impl TestHarness {
             cache_path,
             config_path,
             flush_interval_secs,
+            gdrive: None,
+            logs,
+            log_cursor: AtomicUsize::new(0),
+            in_process: false,
         })
     }
 
@@ -525,6 +682,11 @@ impl TestHarness {
         &self.bucket
     }
 
+    /// Get the fake Drive API backing gdrive mounts, if any were added
+    pub fn gdrive(&self) -> Option<&FakeDrive> {
+        self.gdrive.as_ref()
+    }
+
     /// Get the adapter (for restart tests)
     pub fn adapter(&self) -> Option<&MountedAdapter> {
         self.adapter.as_ref()
@@ -540,16 +702,13 @@ impl TestHarness {
         self.flush_interval_secs
     }
 
-    /// Force a cache sync by waiting for the flush interval plus buffer
+    /// Sync pending cache changes of all mounts to their backends,
+    /// returning once they're written
     pub async fn force_sync(&self) -> Result<()> {
-        if self.flush_interval_secs == 0 {
-            // No cache or memory cache - no sync needed
-            return Ok(());
+        match self.adapter.as_ref().and_then(|a| a.in_process()) {
+            Some(adapter) => adapter.sync().await,
+            None => force_sync(&self.config).await,
         }
-        let wait_secs = self.flush_interval_secs + 1;
-        info!("Forcing cache sync (waiting {}s)...", wait_secs);
-        sleep(Duration::from_secs(wait_secs)).await;
-        Ok(())
     }
 
     /// Restart the adapter (for persistence tests)
@@ -572,11 +731,70 @@ impl TestHarness {
     /// Start the adapter again after stopping
     pub async fn start_adapter(&mut self) -> Result<()> {
         if self.adapter.is_none() {
-            self.adapter = Some(MountedAdapter::start(&self.config, &self.config_path).await?);
+            self.adapter = Some(
+                start_adapter(
+                    &self.config,
+                    &self.config_path,
+                    self.logs.clone(),
+                    self.in_process,
+                )
+                .await?,
+            );
         }
         Ok(())
     }
 
+    /// Get the adapter's captured output (across restarts)
+    pub fn logs(&self) -> &AdapterLogs {
+        &self.logs
+    }
+
+    /// Take the adapter output logged since the harness started (or since
+    /// the last `take_logs`)
+    pub fn take_logs(&self) -> String {
+        self.logs.take(&self.log_cursor)
+    }
+
+    /// Assert the adapter logged a line containing `pattern` since the
+    /// harness started (or since the last `take_logs`)
+    pub async fn assert_log_contains(&self, pattern: &str) {
+        self.logs
+            .assert_contains(self.log_cursor.load(Ordering::SeqCst), pattern)
+            .await;
+    }
+
+    /// Send a command to the adapter's control socket, returning its output
+    pub async fn control(&self, command: &str) -> Result<String> {
+        control(&self.config, command).await
+    }
+
+    /// Inject a fault into one operation of a mount configured with faults
+    ///
+    /// Requires a fuse-adapter binary built with `--features mock`.
+    pub async fn set_fault(
+        &self,
+        mount: &Path,
+        operation: &str,
+        fault: &FaultConfig,
+    ) -> Result<()> {
+        let yaml = serde_yaml::to_string(fault)?;
+        self.control(&format!(
+            "fault set {} {}\n{}",
+            operation,
+            mount.display(),
+            yaml
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// Stop injecting faults into a mount
+    pub async fn clear_faults(&self, mount: &Path) -> Result<()> {
+        self.control(&format!("fault clear {}", mount.display()))
+            .await?;
+        Ok(())
+    }
+
     /// Cleanup the test environment
     pub async fn cleanup(mut self) -> Result<()> {
         info!("Cleaning up test harness...");
@@ -635,6 +853,8 @@ pub struct HarnessBuilder {
     logging_level: String,
     error_mode: String,
     flush_interval_secs: u64,
+    gdrive: Option<FakeDrive>,
+    in_process: bool,
 }
 
 impl HarnessBuilder {
@@ -652,9 +872,18 @@ impl HarnessBuilder {
             logging_level: "debug".to_string(),
             error_mode: "exit".to_string(),
             flush_interval_secs: FAST_FLUSH_INTERVAL_SECS,
+            gdrive: None,
+            in_process: false,
         })
     }
 
+    /// Run the adapter inside the test process instead of as a child
+    /// process (see `InProcessAdapter`)
+    pub fn in_process(&mut self) -> &mut Self {
+        self.in_process = true;
+        self
+    }
+
     /// Set the logging level
     pub fn logging_level(&mut self, level: &str) -> &mut Self {
         self.logging_level = level.to_string();
@@ -679,19 +908,41 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(format!("{}/", name)),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache,
         });
         self
     }
 
+    /// Add a mount whose backend has faults injected
+    ///
+    /// Requires a fuse-adapter binary built with `--features mock`; the
+    /// faults can be changed later with `TestHarness::set_fault`.
+    pub fn add_mount_with_faults(
+        &mut self,
+        name: &str,
+        cached: bool,
+        faults: FaultsConfig,
+    ) -> &mut Self {
+        if cached {
+            self.add_cached_mount(name);
+        } else {
+            self.add_uncached_mount(name);
+        }
+        if let Some(mount) = self.mounts.last_mut() {
+            mount.faults = Some(faults);
+        }
+        self
+    }
+
     /// Add a mount with filesystem cache (fast)
     pub fn add_cached_mount(&mut self, name: &str) -> &mut Self {
         let cache_path = self.temp_dir.path().join(format!("cache-{}", name));
@@ -704,6 +955,44 @@ impl HarnessBuilder {
         self.add_mount(name, None)
     }
 
+    /// Add a Google Drive mount backed by an in-process fake Drive API
+    ///
+    /// The mount's root is the folder `name` in the fake's My Drive, and
+    /// native Docs are exported as `txt`. Use `TestHarness::gdrive` to seed
+    /// or inspect its content.
+    pub fn add_gdrive_mount(&mut self, name: &str) -> &mut Self {
+        let mount_path = self.temp_dir.path().join(format!("mount-{}", name));
+        let cache_path = self.temp_dir.path().join(format!("cache-{}", name));
+        std::fs::create_dir_all(&mount_path).ok();
+        std::fs::create_dir_all(&cache_path).ok();
+
+        let drive = self
+            .gdrive
+            .get_or_insert_with(|| FakeDrive::start().expect("Failed to start fake Drive API"));
+        let root_folder_id = drive.create_folder(name);
+
+        self.mounts.push(MountConfig {
+            path: mount_path,
+            read_only: None,
+            uid: None,
+            gid: None,
+            error_mode: None,
+            status_overlay: None,
+            faults: None,
+            connector: ConnectorConfig::GDrive(GDriveConnectorConfig {
+                connector_type: "gdrive".to_string(),
+                root_folder_id: Some(root_folder_id),
+                endpoint: Some(drive.endpoint()),
+                export_formats: Some([("document".to_string(), "txt".to_string())].into()),
+                auth: GDriveAuthConfig::Token {
+                    access_token: drive.access_token().to_string(),
+                },
+            }),
+            cache: Some(filesystem_cache_fast(cache_path)),
+        });
+        self
+    }
+
     /// Add a read-only mount
     pub fn add_read_only_mount(&mut self, name: &str) -> &mut Self {
         let mount_path = self.temp_dir.path().join(format!("mount-{}", name));
@@ -716,14 +1005,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(format!("{}/", name)),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: None,
         });
         self
@@ -748,14 +1038,15 @@ impl HarnessBuilder {
             gid,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(format!("{}/", name)),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: Some(filesystem_cache_fast(cache_path)),
         });
         self
@@ -775,14 +1066,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(prefix.to_string()),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: Some(filesystem_cache_fast(cache_path)),
         });
         self
@@ -802,14 +1094,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(format!("{}/", name)),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: Some(CacheConfig::Filesystem {
                 path: cache_path,
                 max_size: max_size.to_string(),
@@ -834,14 +1127,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: Some(StatusOverlayConfig::default()),
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(format!("{}/", name)),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: Some(filesystem_cache_fast(cache_path)),
         });
         self
@@ -864,14 +1158,15 @@ impl HarnessBuilder {
                 prefix: prefix.to_string(),
                 ..Default::default()
             }),
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: self.bucket.name().to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: Some(format!("{}/", name)),
                 endpoint: Some(self.minio.endpoint().to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: Some(filesystem_cache_fast(cache_path)),
         });
         self
@@ -889,14 +1184,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: "nonexistent-bucket".to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: None,
                 endpoint: Some("http://invalid-endpoint:9999".to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: None,
         });
         self
@@ -914,14 +1210,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: None,
             status_overlay: Some(StatusOverlayConfig::default()),
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: "nonexistent-bucket".to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: None,
                 endpoint: Some("http://invalid-endpoint:9999".to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: None,
         });
         self
@@ -943,14 +1240,15 @@ impl HarnessBuilder {
             gid: None,
             error_mode: Some(error_mode.to_string()),
             status_overlay: None,
-            connector: S3ConnectorConfig {
+            faults: None,
+            connector: ConnectorConfig::S3(S3ConnectorConfig {
                 connector_type: "s3".to_string(),
                 bucket: "nonexistent-bucket".to_string(),
                 region: Some("us-east-1".to_string()),
                 prefix: None,
                 endpoint: Some("http://invalid-endpoint:9999".to_string()),
                 force_path_style: Some(true),
-            },
+            }),
             cache: None,
         });
         self
@@ -966,16 +1264,19 @@ impl HarnessBuilder {
         let mount_path = self.mounts[0].path.clone();
         let cache_path = self.temp_dir.path().join("cache-default");
         let config_path = self.temp_dir.path().join("config.yaml");
+        let control_socket = self.temp_dir.path().join("control.sock");
 
         let config = TestConfig {
             logging: crate::config::LoggingConfig {
                 level: self.logging_level,
             },
             error_mode: Some(self.error_mode),
+            control_socket: Some(control_socket),
             mounts: self.mounts,
         };
 
-        let adapter = MountedAdapter::start(&config, &config_path).await?;
+        let logs = AdapterLogs::new();
+        let adapter = start_adapter(&config, &config_path, logs.clone(), self.in_process).await?;
 
         Ok(TestHarness {
             minio: self.minio,
@@ -987,6 +1288,10 @@ impl HarnessBuilder {
             cache_path,
             config_path,
             flush_interval_secs: self.flush_interval_secs,
+            gdrive: self.gdrive,
+            logs,
+            log_cursor: AtomicUsize::new(0),
+            in_process: self.in_process,
         })
     }
 
@@ -1009,6 +1314,7 @@ impl HarnessBuilder {
                 level: self.logging_level,
             },
             error_mode: Some(self.error_mode),
+            control_socket: None,
             mounts: self.mounts,
         };
 
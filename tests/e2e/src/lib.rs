@@ -1,7 +1,8 @@
 //! E2E test harness for fuse-adapter
 //!
 //! This crate provides infrastructure for comprehensive end-to-end testing
-//! of the fuse-adapter with MinIO as the S3 backend.
+//! of the fuse-adapter with MinIO as the S3 backend, plus an in-process
+//! fake of the Google Drive API for the gdrive connector.
 //!
 //! ## Quick Start
 //!
@@ -42,16 +43,23 @@
 
 pub mod assertions;
 pub mod config;
+pub mod gdrive;
 pub mod harness;
 pub mod minio;
 pub mod mount;
+pub mod proxy;
+pub mod workload;
 
 pub use assertions::*;
 pub use config::{
     filesystem_cache, filesystem_cache_fast, filesystem_cache_with_interval, CacheConfig,
+    ConnectorConfig, FaultConfig, FaultsConfig, GDriveAuthConfig, GDriveConnectorConfig,
     MountConfig, S3ConnectorConfig, StatusOverlayConfig, TestConfig, TestConfigBuilder,
     DEFAULT_TEST_FLUSH_INTERVAL_SECS, FAST_FLUSH_INTERVAL_SECS,
 };
+pub use gdrive::FakeDrive;
 pub use harness::{HarnessBuilder, SharedHarness, TestCacheType, TestContext, TestHarness};
 pub use minio::{MinioContainer, TestBucket};
-pub use mount::{MountedAdapter, StartResult};
+pub use mount::{AdapterLogs, InProcessAdapter, MountedAdapter, StartResult};
+pub use proxy::NetworkProxy;
+pub use workload::{OperationMix, SizeDistribution, Workload, WorkloadConfig, WorkloadStats};
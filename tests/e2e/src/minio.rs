@@ -19,6 +19,8 @@ use tokio::time::sleep;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+use crate::proxy::NetworkProxy;
+
 /// Global shared MinIO container instance.
 /// This ensures only one container is created even when tests run in parallel.
 static SHARED_MINIO: OnceCell<Arc<MinioContainer>> = OnceCell::const_new();
@@ -286,6 +288,15 @@ impl MinioContainer {
         &self.endpoint
     }
 
+    /// Start a network proxy in front of this MinIO
+    ///
+    /// Configure the adapter with the proxy's endpoint to degrade its
+    /// connection mid-test; this container's S3 client keeps talking to
+    /// MinIO directly, so verification is unaffected.
+    pub async fn with_proxy(&self) -> Result<NetworkProxy> {
+        NetworkProxy::start(&self.endpoint).await
+    }
+
     /// Get the access key
     pub fn access_key(&self) -> &str {
         &self.access_key
@@ -1,16 +1,34 @@
 //! Mount management for e2e tests
 //!
 //! Handles starting/stopping the fuse-adapter process and waiting for mounts.
+//! The adapter's output is captured into `AdapterLogs` so tests can assert
+//! on what it logged.
+//!
+//! The adapter can also run inside the test process (`InProcessAdapter`),
+//! which lets a debugger step from a test into the daemon and lets coverage
+//! tools see the daemon's code.
 
 use crate::config::TestConfig;
 use anyhow::{Context, Result};
+use fuse_adapter::builder::FuseAdapterBuilder;
+use fuse_adapter::config::{Config, ErrorMode};
+use fuse_adapter::connector::throttle::BandwidthLimits;
+use fuse_adapter::mount::MountManager;
 use nix::sys::signal::{self, Signal};
 use nix::unistd::Pid;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-use std::time::Duration;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
 use tokio::time::{sleep, timeout};
+use tracing::dispatcher::{self, DefaultGuard, Dispatch};
 use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
 
 /// Default timeout for waiting for mounts to become ready
 const DEFAULT_MOUNT_TIMEOUT: Duration = Duration::from_secs(30);
@@ -21,6 +39,9 @@ const POLL_INTERVAL: Duration = Duration::from_millis(50);
 /// Timeout for waiting for adapter to fail (for error mode tests)
 const FAILURE_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// How long log assertions wait for a matching line to be captured
+const LOG_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Result of trying to start the adapter
 #[derive(Debug)]
 pub enum StartResult {
@@ -35,13 +56,320 @@ pub enum StartResult {
     },
 }
 
-/// Manages a running fuse-adapter process
+/// Output captured from the adapter process, one entry per line
+///
+/// Cloning shares the buffer, so logs can outlive (and span restarts of)
+/// the `MountedAdapter` they came from. Lines are only ever appended;
+/// positions from `cursor()` stay valid for `lines_since`.
+#[derive(Debug, Clone, Default)]
+pub struct AdapterLogs {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl AdapterLogs {
+    /// Create an empty log buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append every line read from `reader` on a background thread
+    ///
+    /// With `echo`, lines are also written to the test's stderr.
+    fn capture(&self, reader: impl Read + Send + 'static, echo: bool) {
+        let lines = self.lines.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else { break };
+                push_line(&lines, &line, echo);
+            }
+        });
+    }
+
+    /// Capture the piped stdout and stderr of `process`
+    fn capture_process(&self, process: &mut Child, echo: bool) {
+        if let Some(stdout) = process.stdout.take() {
+            self.capture(stdout, echo);
+        }
+        if let Some(stderr) = process.stderr.take() {
+            self.capture(stderr, echo);
+        }
+    }
+
+    /// Position after the last line captured so far
+    pub fn cursor(&self) -> usize {
+        self.lines.lock().unwrap().len()
+    }
+
+    /// Lines captured after `cursor`
+    pub fn lines_since(&self, cursor: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.get(cursor..).unwrap_or_default().to_vec()
+    }
+
+    /// Everything captured so far
+    pub fn contents(&self) -> String {
+        self.lines_since(0).join("\n")
+    }
+
+    /// Wait until a line after `cursor` contains `pattern`
+    ///
+    /// Output reaches the buffer asynchronously, so a line logged just
+    /// before this call may take a moment to show up.
+    pub async fn wait_for(&self, cursor: usize, pattern: &str, max_wait: Duration) -> bool {
+        let deadline = Instant::now() + max_wait;
+        loop {
+            if self
+                .lines_since(cursor)
+                .iter()
+                .any(|line| line.contains(pattern))
+            {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Panic unless a line after `cursor` contains `pattern`, waiting
+    /// briefly for output still in flight
+    pub async fn assert_contains(&self, cursor: usize, pattern: &str) {
+        if !self.wait_for(cursor, pattern, LOG_TIMEOUT).await {
+            panic!(
+                "Adapter logs do not contain {:?}. Captured:\n{}",
+                pattern,
+                self.lines_since(cursor).join("\n")
+            );
+        }
+    }
+
+    /// Lines after `cursor`, advancing it past them
+    pub fn take(&self, cursor: &AtomicUsize) -> String {
+        let lines = self.lines_since(cursor.load(Ordering::SeqCst));
+        cursor.fetch_add(lines.len(), Ordering::SeqCst);
+        lines.join("\n")
+    }
+}
+
+/// Append one line of output, echoing it to the test's stderr with `echo`
+fn push_line(lines: &Mutex<Vec<String>>, line: &str, echo: bool) {
+    if echo {
+        eprintln!("{}", line);
+    }
+    lines.lock().unwrap().push(strip_ansi(line));
+}
+
+/// Writer appending formatted log events to an `AdapterLogs`
+struct LogWriter {
+    logs: AdapterLogs,
+    echo: bool,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // The fmt layer writes each event in a single call
+        for line in String::from_utf8_lossy(buf).lines() {
+            push_line(&self.logs.lines, line, self.echo);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Remove terminal color codes from a log line
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip "[" and parameters up to the final byte of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+thread_local! {
+    /// Keeps an in-process adapter's subscriber the default on its runtime threads
+    static LOG_GUARD: RefCell<Option<DefaultGuard>> = const { RefCell::new(None) };
+}
+
+/// The adapter's mounts served from within the test process
+///
+/// Each mount's stack is built with the library's `FuseAdapterBuilder`, as
+/// the daemon does, on a runtime of its own: FUSE requests block on that
+/// runtime, so it can't be the test's (usually single-threaded) one. Output
+/// logged on the runtime's threads goes to `AdapterLogs`; output logged on
+/// FUSE request threads goes to the test's own subscriber. There is no
+/// control socket, so commands other than `sync` are unavailable.
+pub struct InProcessAdapter {
+    runtime: Option<Runtime>,
+    manager: Arc<MountManager>,
+    shutdown_timeout: Duration,
+}
+
+impl InProcessAdapter {
+    /// Mount everything in `config`, logging to `logs`
+    ///
+    /// Like the daemon, mounts that fail are skipped unless their
+    /// `error_mode` is `exit`, and it fails if nothing could be mounted.
+    pub async fn start(config: &TestConfig, logs: AdapterLogs) -> Result<Self> {
+        let config = Config::parse(&config.to_yaml()?).context("Invalid adapter config")?;
+        config.validate().context("Invalid adapter config")?;
+
+        // The S3 connector reads the MinIO credentials from the environment,
+        // where the harness passes them to the daemon
+        for (var, source, default) in [
+            ("AWS_ACCESS_KEY_ID", "MINIO_ACCESS_KEY", "minioadmin"),
+            ("AWS_SECRET_ACCESS_KEY", "MINIO_SECRET_KEY", "minioadmin"),
+        ] {
+            if std::env::var_os(var).is_none() {
+                std::env::set_var(var, std::env::var(source).unwrap_or(default.to_string()));
+            }
+        }
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        // `RUST_LOG` takes precedence over the config, as in the daemon
+        let filter = EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(&config.logging.level));
+        let echo = std::env::var("CI").is_ok();
+        let dispatch = Dispatch::new(
+            tracing_subscriber::fmt()
+                .with_env_filter(filter)
+                .with_ansi(false)
+                .with_writer(move || LogWriter {
+                    logs: logs.clone(),
+                    echo,
+                })
+                .finish(),
+        );
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("fuse-adapter")
+            .on_thread_start(move || {
+                let guard = dispatcher::set_default(&dispatch);
+                LOG_GUARD.with(|g| *g.borrow_mut() = Some(guard));
+            })
+            .build()
+            .context("Failed to start adapter runtime")?;
+
+        let adapter = Self {
+            manager: Arc::new(MountManager::new(runtime.handle().clone())),
+            runtime: Some(runtime),
+            shutdown_timeout: config.shutdown_timeout,
+        };
+        info!("Started fuse-adapter in-process");
+
+        let manager = adapter.manager.clone();
+        adapter
+            .spawn(async move {
+                let limits = BandwidthLimits::from_config(config.rate_limit.as_ref());
+                for mount_config in config.mounts {
+                    let path = mount_config.path.clone();
+                    let error_mode = mount_config.error_mode;
+                    if let Err(e) = FuseAdapterBuilder::from_config(mount_config)
+                        .with_global_limits(limits.clone())
+                        .mount_with(&manager)
+                        .await
+                    {
+                        if error_mode == ErrorMode::Exit {
+                            anyhow::bail!("Failed to mount {:?}: {}", path, e);
+                        }
+                        warn!("Skipping mount {:?}: {}", path, e);
+                    }
+                }
+                if manager.count() == 0 {
+                    anyhow::bail!("No filesystems were mounted successfully");
+                }
+                Ok(())
+            })
+            .await??;
+
+        Ok(adapter)
+    }
+
+    /// Run `future` on the adapter's runtime
+    async fn spawn<F>(&self, future: F) -> Result<F::Output>
+    where
+        F: std::future::Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let runtime = self.runtime.as_ref().context("Adapter is stopped")?;
+        Ok(runtime.spawn(future).await?)
+    }
+
+    /// Whether the mounts are still being served
+    pub fn is_running(&self) -> bool {
+        self.runtime.is_some()
+    }
+
+    /// Sync every mount's pending changes, returning once they're written
+    pub async fn sync(&self) -> Result<()> {
+        let manager = self.manager.clone();
+        self.spawn(async move { manager.sync(None).await })
+            .await??;
+        Ok(())
+    }
+
+    /// Unmount everything, syncing pending changes first
+    pub async fn stop(&mut self) -> Result<()> {
+        let manager = self.manager.clone();
+        let shutdown_timeout = self.shutdown_timeout;
+        self.spawn(async move { manager.shutdown(shutdown_timeout).await })
+            .await?;
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for InProcessAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InProcessAdapter")
+            .field("mounts", &self.manager.list_mounts())
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+impl Drop for InProcessAdapter {
+    fn drop(&mut self) {
+        // Unmount before the runtime goes so in-flight requests can finish
+        self.manager.unmount_all();
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// What serves an adapter's mounts
+#[derive(Debug)]
+enum Runner {
+    /// A fuse-adapter child process
+    Process(Child),
+    /// The test process itself
+    InProcess(InProcessAdapter),
+}
+
+/// Manages a running fuse-adapter, usually as a child process
 #[derive(Debug)]
 pub struct MountedAdapter {
-    process: Child,
+    runner: Runner,
     config_path: PathBuf,
     mount_points: Vec<PathBuf>,
     stopped: bool,
+    logs: AdapterLogs,
 }
 
 impl MountedAdapter {
@@ -80,7 +408,7 @@ impl MountedAdapter {
             .arg(config_path)
             .env("AWS_ACCESS_KEY_ID", &access_key)
             .env("AWS_SECRET_ACCESS_KEY", &secret_key)
-            .stdout(Stdio::null())
+            .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .with_context(|| format!("Failed to start fuse-adapter: {:?}", binary))?;
@@ -99,7 +427,6 @@ impl MountedAdapter {
                 let exit_code = status.code();
                 let stderr = if let Some(ref mut stderr_handle) = process.stderr {
                     let mut stderr_buf = String::new();
-                    use std::io::Read;
                     let _ = stderr_handle.read_to_string(&mut stderr_buf);
                     stderr_buf
                 } else {
@@ -119,14 +446,16 @@ impl MountedAdapter {
             }
         }
 
-        // Drop the stderr handle before waiting for mounts (can't read and wait simultaneously)
-        drop(process.stderr.take());
+        // Keep draining the output while the adapter runs
+        let logs = AdapterLogs::new();
+        logs.capture_process(&mut process, false);
 
         let adapter = Self {
-            process,
+            runner: Runner::Process(process),
             config_path: config_path.to_path_buf(),
             mount_points,
             stopped: false,
+            logs,
         };
 
         // Try to wait for mounts
@@ -186,6 +515,15 @@ impl MountedAdapter {
 
     /// Start fuse-adapter with the given configuration
     pub async fn start(config: &TestConfig, config_path: &Path) -> Result<Self> {
+        Self::start_with_logs(config, config_path, AdapterLogs::new()).await
+    }
+
+    /// Start fuse-adapter, appending its output to `logs`
+    pub async fn start_with_logs(
+        config: &TestConfig,
+        config_path: &Path,
+        logs: AdapterLogs,
+    ) -> Result<Self> {
         // Write config to file
         config.write_to_file(config_path)?;
 
@@ -217,23 +555,18 @@ impl MountedAdapter {
         let secret_key =
             std::env::var("MINIO_SECRET_KEY").unwrap_or_else(|_| "minioadmin".to_string());
 
-        // In CI, capture output for debugging; locally, discard for cleaner output
-        let is_ci = std::env::var("CI").is_ok();
-        let (stdout, stderr) = if is_ci {
-            (Stdio::inherit(), Stdio::inherit())
-        } else {
-            (Stdio::null(), Stdio::null())
-        };
-
         let mut process = Command::new(&binary)
             .arg(config_path)
             .env("AWS_ACCESS_KEY_ID", &access_key)
             .env("AWS_SECRET_ACCESS_KEY", &secret_key)
-            .stdout(stdout)
-            .stderr(stderr)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
             .with_context(|| format!("Failed to start fuse-adapter: {:?}", binary))?;
 
+        // In CI, also echo output for debugging; locally, only capture it
+        logs.capture_process(&mut process, std::env::var("CI").is_ok());
+
         let pid = process.id();
         info!("Started fuse-adapter with PID {}", pid);
 
@@ -254,10 +587,11 @@ impl MountedAdapter {
         }
 
         let adapter = Self {
-            process,
+            runner: Runner::Process(process),
             config_path: config_path.to_path_buf(),
             mount_points,
             stopped: false,
+            logs,
         };
 
         // Wait for mounts to be ready
@@ -266,6 +600,37 @@ impl MountedAdapter {
         Ok(adapter)
     }
 
+    /// Start the adapter inside the test process (see `InProcessAdapter`),
+    /// appending its output to `logs`
+    pub async fn start_in_process(
+        config: &TestConfig,
+        config_path: &Path,
+        logs: AdapterLogs,
+    ) -> Result<Self> {
+        // Write config to file, for reference and restarts
+        config.write_to_file(config_path)?;
+
+        let mount_points: Vec<PathBuf> = config.mounts.iter().map(|m| m.path.clone()).collect();
+        for mount_point in &mount_points {
+            if !mount_point.exists() {
+                std::fs::create_dir_all(mount_point)
+                    .with_context(|| format!("Failed to create mount point: {:?}", mount_point))?;
+            }
+        }
+
+        let adapter = Self {
+            runner: Runner::InProcess(InProcessAdapter::start(config, logs.clone()).await?),
+            config_path: config_path.to_path_buf(),
+            mount_points,
+            stopped: false,
+            logs,
+        };
+
+        adapter.wait_ready(DEFAULT_MOUNT_TIMEOUT).await?;
+
+        Ok(adapter)
+    }
+
     /// Wait for all mount points to become ready
     pub async fn wait_ready(&self, max_wait: Duration) -> Result<()> {
         info!(
@@ -306,12 +671,17 @@ impl MountedAdapter {
         }
     }
 
-    /// Check if the adapter process is still running
+    /// Check if the adapter is still running
     pub fn is_running(&self) -> bool {
+        let process = match &self.runner {
+            Runner::Process(process) => process,
+            Runner::InProcess(adapter) => return adapter.is_running(),
+        };
+
         // Try to get process status without blocking
         match Command::new("kill")
             .arg("-0")
-            .arg(self.process.id().to_string())
+            .arg(process.id().to_string())
             .output()
         {
             Ok(output) => output.status.success(),
@@ -334,9 +704,25 @@ impl MountedAdapter {
         &self.mount_points
     }
 
-    /// Get the PID of the adapter process
-    pub fn pid(&self) -> u32 {
-        self.process.id()
+    /// Get the PID of the adapter process (None when running in-process)
+    pub fn pid(&self) -> Option<u32> {
+        match &self.runner {
+            Runner::Process(process) => Some(process.id()),
+            Runner::InProcess(_) => None,
+        }
+    }
+
+    /// Get the in-process adapter, if it runs in the test process
+    pub fn in_process(&self) -> Option<&InProcessAdapter> {
+        match &self.runner {
+            Runner::Process(_) => None,
+            Runner::InProcess(adapter) => Some(adapter),
+        }
+    }
+
+    /// Get the adapter's captured output
+    pub fn logs(&self) -> &AdapterLogs {
+        &self.logs
     }
 
     /// Stop the adapter gracefully
@@ -345,10 +731,21 @@ impl MountedAdapter {
             return Ok(());
         }
 
-        info!("Stopping fuse-adapter (PID {})", self.process.id());
+        let process = match &mut self.runner {
+            Runner::Process(process) => process,
+            Runner::InProcess(adapter) => {
+                info!("Stopping in-process fuse-adapter");
+                adapter.stop().await?;
+                self.stopped = true;
+                info!("fuse-adapter stopped");
+                return Ok(());
+            }
+        };
+
+        info!("Stopping fuse-adapter (PID {})", process.id());
 
         // Send SIGTERM for graceful shutdown
-        let pid = Pid::from_raw(self.process.id() as i32);
+        let pid = Pid::from_raw(process.id() as i32);
         if let Err(e) = signal::kill(pid, Signal::SIGTERM) {
             warn!("Failed to send SIGTERM: {}", e);
         }
@@ -356,7 +753,7 @@ impl MountedAdapter {
         // Wait for process to exit (with timeout)
         let wait_result = timeout(Duration::from_secs(10), async {
             loop {
-                if !self.is_running() {
+                if !matches!(process.try_wait(), Ok(None)) {
                     return;
                 }
                 sleep(Duration::from_millis(100)).await;
@@ -370,7 +767,7 @@ impl MountedAdapter {
             if let Err(e) = signal::kill(pid, Signal::SIGKILL) {
                 error!("Failed to send SIGKILL: {}", e);
             }
-            let _ = self.process.wait();
+            let _ = process.wait();
         }
 
         self.stopped = true;
@@ -394,18 +791,28 @@ impl MountedAdapter {
         // Small delay to ensure cleanup
         sleep(Duration::from_millis(500)).await;
 
-        // Start fresh adapter with same config
-        Self::start(config, &self.config_path).await
+        // Start fresh adapter with same config, continuing the same logs
+        match self.runner {
+            Runner::Process(_) => {
+                Self::start_with_logs(config, &self.config_path, self.logs.clone()).await
+            }
+            Runner::InProcess(_) => {
+                Self::start_in_process(config, &self.config_path, self.logs.clone()).await
+            }
+        }
     }
 }
 
 impl Drop for MountedAdapter {
     fn drop(&mut self) {
         if !self.stopped {
-            // Best-effort cleanup in drop
-            let pid = Pid::from_raw(self.process.id() as i32);
-            let _ = signal::kill(pid, Signal::SIGKILL);
-            let _ = self.process.wait();
+            // Best-effort cleanup in drop; an in-process adapter unmounts
+            // when dropped itself
+            if let Runner::Process(process) = &mut self.runner {
+                let pid = Pid::from_raw(process.id() as i32);
+                let _ = signal::kill(pid, Signal::SIGKILL);
+                let _ = process.wait();
+            }
 
             // Try to unmount
             for mount_point in &self.mount_points {
@@ -604,3 +1011,29 @@ pub async fn force_unmount_all(paths: &[PathBuf]) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[2m2024-01-01\x1b[0m \x1b[32m INFO\x1b[0m sync complete"),
+            "2024-01-01  INFO sync complete"
+        );
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+
+    #[tokio::test]
+    async fn test_logs_cursor() {
+        let logs = AdapterLogs::new();
+        logs.capture(&b"first\nsecond\n"[..], false);
+        assert!(logs.wait_for(0, "second", LOG_TIMEOUT).await);
+
+        let cursor = AtomicUsize::new(0);
+        assert_eq!(logs.take(&cursor), "first\nsecond");
+        assert_eq!(logs.take(&cursor), "");
+        assert!(!logs.wait_for(2, "first", Duration::from_millis(50)).await);
+    }
+}
@@ -0,0 +1,254 @@
+//! Network proxy for simulating a degraded backend
+//!
+//! A small TCP proxy in the spirit of toxiproxy. The adapter is pointed at
+//! the proxy instead of MinIO, and tests change the network conditions
+//! mid-test: add latency, throttle bandwidth, or cut every connection until
+//! it is restored. Direct S3 clients used for verification bypass the proxy.
+
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+/// Largest chunk forwarded at once
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Network conditions shared by all proxied connections
+struct Conditions {
+    latency_ms: AtomicU64,
+    /// Bytes per second in each direction (0 = unlimited)
+    bandwidth: AtomicU64,
+    /// Whether connections are currently cut
+    cut: watch::Sender<bool>,
+}
+
+/// TCP proxy in front of an upstream endpoint
+pub struct NetworkProxy {
+    addr: SocketAddr,
+    conditions: Arc<Conditions>,
+    accept_task: JoinHandle<()>,
+}
+
+impl NetworkProxy {
+    /// Start proxying a local port to `upstream` (e.g. "http://localhost:9000")
+    pub async fn start(upstream: &str) -> Result<Self> {
+        let upstream = upstream
+            .split_once("://")
+            .map_or(upstream, |(_, rest)| rest)
+            .trim_end_matches('/')
+            .to_string();
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to bind proxy listener")?;
+        let addr = listener.local_addr()?;
+        let conditions = Arc::new(Conditions {
+            latency_ms: AtomicU64::new(0),
+            bandwidth: AtomicU64::new(0),
+            cut: watch::channel(false).0,
+        });
+
+        info!("Network proxy {} -> {}", addr, upstream);
+
+        let accept_task = tokio::spawn({
+            let conditions = conditions.clone();
+            async move {
+                while let Ok((client, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(
+                        client,
+                        upstream.clone(),
+                        conditions.clone(),
+                    ));
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            conditions,
+            accept_task,
+        })
+    }
+
+    /// Endpoint URL to configure the adapter with
+    pub fn endpoint(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Delay every chunk of traffic by `latency` in each direction
+    pub fn add_latency(&self, latency: Duration) {
+        debug!("Proxy latency set to {:?}", latency);
+        self.conditions
+            .latency_ms
+            .store(latency.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Limit traffic to `bytes_per_sec` in each direction (0 = unlimited)
+    pub fn throttle_bandwidth(&self, bytes_per_sec: u64) {
+        debug!("Proxy bandwidth set to {} bytes/s", bytes_per_sec);
+        self.conditions
+            .bandwidth
+            .store(bytes_per_sec, Ordering::Relaxed);
+    }
+
+    /// Close all open connections and refuse new ones until restored
+    pub fn cut_connection(&self) {
+        debug!("Proxy connections cut");
+        self.conditions.cut.send_replace(true);
+    }
+
+    /// Accept connections again after `cut_connection`
+    pub fn restore_connection(&self) {
+        debug!("Proxy connections restored");
+        self.conditions.cut.send_replace(false);
+    }
+
+    /// Remove all latency, throttling and cuts
+    pub fn reset(&self) {
+        self.add_latency(Duration::ZERO);
+        self.throttle_bandwidth(0);
+        self.restore_connection();
+    }
+}
+
+impl Drop for NetworkProxy {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        self.conditions.cut.send_replace(true);
+    }
+}
+
+/// Forward one client connection until either side closes or it is cut
+async fn handle_connection(client: TcpStream, upstream: String, conditions: Arc<Conditions>) {
+    let mut cut = conditions.cut.subscribe();
+    if *cut.borrow() {
+        // Dropping the client refuses the connection
+        return;
+    }
+
+    let server = match TcpStream::connect(&upstream).await {
+        Ok(server) => server,
+        Err(e) => {
+            debug!("Proxy failed to connect to {}: {}", upstream, e);
+            return;
+        }
+    };
+
+    let (client_read, client_write) = client.into_split();
+    let (server_read, server_write) = server.into_split();
+    let forward = async {
+        tokio::try_join!(
+            pipe(client_read, server_write, &conditions),
+            pipe(server_read, client_write, &conditions),
+        )
+    };
+
+    tokio::select! {
+        result = forward => {
+            if let Err(e) = result {
+                debug!("Proxy connection failed: {}", e);
+            }
+        }
+        _ = cut.wait_for(|cut| *cut) => {
+            debug!("Proxy connection cut");
+        }
+    }
+}
+
+/// Copy one direction of a connection, applying latency and throttling
+async fn pipe(
+    mut from: OwnedReadHalf,
+    mut to: OwnedWriteHalf,
+    conditions: &Conditions,
+) -> std::io::Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        // Smaller chunks at low bandwidth keep the pacing smooth
+        let rate = conditions.bandwidth.load(Ordering::Relaxed);
+        let limit = if rate > 0 {
+            (rate as usize / 10).clamp(1, CHUNK_SIZE)
+        } else {
+            CHUNK_SIZE
+        };
+
+        let n = from.read(&mut buf[..limit]).await?;
+        if n == 0 {
+            return to.shutdown().await;
+        }
+
+        let latency = conditions.latency_ms.load(Ordering::Relaxed);
+        if latency > 0 {
+            sleep(Duration::from_millis(latency)).await;
+        }
+        if rate > 0 {
+            sleep(Duration::from_secs_f64(n as f64 / rate as f64)).await;
+        }
+
+        to.write_all(&buf[..n]).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start a TCP server echoing back whatever it receives
+    async fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut read, mut write) = stream.into_split();
+                    let _ = tokio::io::copy(&mut read, &mut write).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn round_trip(proxy: &NetworkProxy) -> std::io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(proxy.addr).await?;
+        stream.write_all(b"ping").await?;
+        let mut buf = vec![0u8; 4];
+        stream.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+
+    #[tokio::test]
+    async fn test_proxy_conditions() {
+        let upstream = echo_server().await;
+        let proxy = NetworkProxy::start(&format!("http://{}", upstream))
+            .await
+            .unwrap();
+        assert_eq!(round_trip(&proxy).await.unwrap(), b"ping");
+
+        proxy.add_latency(Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        assert_eq!(round_trip(&proxy).await.unwrap(), b"ping");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // An open connection is dropped and new ones are refused
+        proxy.reset();
+        let mut open = TcpStream::connect(proxy.addr).await.unwrap();
+        open.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        open.read_exact(&mut buf).await.unwrap();
+        proxy.cut_connection();
+        let mut rest = Vec::new();
+        let _ = open.read_to_end(&mut rest).await;
+        assert!(rest.is_empty());
+        assert!(round_trip(&proxy).await.is_err());
+
+        proxy.restore_connection();
+        assert_eq!(round_trip(&proxy).await.unwrap(), b"ping");
+    }
+}
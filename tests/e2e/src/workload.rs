@@ -0,0 +1,735 @@
+//! Workload generation for stress tests
+//!
+//! Builds a random tree of files and directories under a mount, then runs
+//! concurrent writers and readers against it while keeping a model of what
+//! every file should contain. Readers check that whatever they see was
+//! actually written at some point, catching torn, misplaced or phantom data
+//! in the cache layers, and `verify` compares the final tree with the model
+//! once the workers are done. Everything derives from a seed, which failures
+//! report so a run can be replayed.
+
+use crate::minio::TestBucket;
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// Granularity at which readers compare what they read with the model
+///
+/// A read racing an in-place overwrite may legitimately mix pages of the old
+/// and new content, so each page is checked on its own.
+const READ_CHECK_BLOCK: usize = 4096;
+
+/// Most problems listed in a verification failure
+const MAX_REPORTED_PROBLEMS: usize = 20;
+
+/// How file sizes are picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeDistribution {
+    /// Always the same size
+    Fixed(usize),
+    /// Uniformly between `min` and `max` bytes (inclusive)
+    Uniform { min: usize, max: usize },
+    /// Log-uniformly between `min` and `max` bytes: mostly small files with
+    /// the occasional large one, like a typical tree
+    LogUniform { min: usize, max: usize },
+}
+
+impl SizeDistribution {
+    /// Pick a size
+    pub fn sample(&self, rng: &mut impl Rng) -> usize {
+        match *self {
+            SizeDistribution::Fixed(size) => size,
+            SizeDistribution::Uniform { min, max } => rng.gen_range(min..=max),
+            SizeDistribution::LogUniform { min, max } => {
+                let low = (min.max(1) as f64).ln();
+                let high = (max.max(1) as f64).ln();
+                (rng.gen_range(low..=high).exp() as usize).clamp(min, max)
+            }
+        }
+    }
+}
+
+/// Relative weights of the operations writers perform on existing files
+///
+/// Files a writer finds deleted are always recreated.
+#[derive(Debug, Clone, Copy)]
+pub struct OperationMix {
+    /// Truncate and rewrite with new content
+    pub overwrite: u32,
+    /// Append new content
+    pub append: u32,
+    /// Remove the file
+    pub delete: u32,
+    /// Move the file to a new name in a random directory
+    pub rename: u32,
+}
+
+impl Default for OperationMix {
+    fn default() -> Self {
+        Self {
+            overwrite: 50,
+            append: 25,
+            delete: 10,
+            rename: 15,
+        }
+    }
+}
+
+/// A writer operation
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    Overwrite,
+    Append,
+    Delete,
+    Rename,
+}
+
+impl OperationMix {
+    fn pick(&self, rng: &mut impl Rng) -> Operation {
+        let total = self.overwrite + self.append + self.delete + self.rename;
+        if total == 0 {
+            return Operation::Overwrite;
+        }
+        let mut n = rng.gen_range(0..total);
+        for (weight, op) in [
+            (self.overwrite, Operation::Overwrite),
+            (self.append, Operation::Append),
+            (self.delete, Operation::Delete),
+        ] {
+            if n < weight {
+                return op;
+            }
+            n -= weight;
+        }
+        Operation::Rename
+    }
+}
+
+/// Shape and intensity of a workload
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    /// Seed for the tree and every worker (random by default)
+    pub seed: u64,
+    /// Directories to create below the root
+    pub dirs: usize,
+    /// Deepest directory nesting below the root
+    pub max_depth: usize,
+    /// Files to create before the workers start
+    pub files: usize,
+    /// Size of new file contents and appended chunks
+    pub sizes: SizeDistribution,
+    /// Concurrent writers; each owns a disjoint set of files
+    pub writers: usize,
+    /// Concurrent readers, running until the writers finish
+    pub readers: usize,
+    /// Operations each writer performs
+    pub ops_per_writer: usize,
+    /// What the writers do
+    pub mix: OperationMix,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            seed: rand::random(),
+            dirs: 8,
+            max_depth: 3,
+            files: 32,
+            sizes: SizeDistribution::LogUniform {
+                min: 16,
+                max: 256 * 1024,
+            },
+            writers: 4,
+            readers: 4,
+            ops_per_writer: 100,
+            mix: OperationMix::default(),
+        }
+    }
+}
+
+/// Counts of what a run did
+#[derive(Debug, Clone, Default)]
+pub struct WorkloadStats {
+    pub overwrites: u64,
+    pub appends: u64,
+    pub deletes: u64,
+    pub renames: u64,
+    pub reads: u64,
+    pub listings: u64,
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+    pub elapsed: Duration,
+}
+
+impl WorkloadStats {
+    fn merge(&mut self, other: &WorkloadStats) {
+        self.overwrites += other.overwrites;
+        self.appends += other.appends;
+        self.deletes += other.deletes;
+        self.renames += other.renames;
+        self.reads += other.reads;
+        self.listings += other.listings;
+        self.bytes_written += other.bytes_written;
+        self.bytes_read += other.bytes_read;
+    }
+}
+
+/// What the model knows about one file
+#[derive(Debug)]
+struct FileState {
+    /// The writer allowed to change it
+    owner: usize,
+    /// Every content it has had, oldest first
+    versions: Vec<Arc<Vec<u8>>>,
+    /// Whether it should exist now
+    exists: bool,
+    /// Whether readers may find it missing (it was created by a rename, or
+    /// has been deleted or renamed away at some point)
+    may_be_missing: bool,
+}
+
+impl FileState {
+    fn current(&self) -> &Arc<Vec<u8>> {
+        self.versions
+            .last()
+            .expect("files have at least one version")
+    }
+}
+
+/// A generated tree and the model of its expected contents
+pub struct Workload {
+    root: PathBuf,
+    config: WorkloadConfig,
+    /// Directories relative to the root, including the root itself
+    dirs: Vec<PathBuf>,
+    /// Files relative to the root, including deleted ones
+    files: Mutex<BTreeMap<PathBuf, FileState>>,
+}
+
+impl Workload {
+    /// Create the random tree described by `config` under `root`
+    pub fn generate(root: impl Into<PathBuf>, config: WorkloadConfig) -> Result<Self> {
+        let root = root.into();
+        info!(
+            "Generating workload under {:?} (seed {}): {} dirs, {} files",
+            root, config.seed, config.dirs, config.files
+        );
+        let mut rng = StdRng::seed_from_u64(config.seed);
+
+        let mut dirs = vec![PathBuf::new()];
+        for i in 0..config.dirs {
+            let parents: Vec<&PathBuf> = dirs
+                .iter()
+                .filter(|d| d.components().count() < config.max_depth)
+                .collect();
+            if parents.is_empty() {
+                break;
+            }
+            let dir = parents[rng.gen_range(0..parents.len())].join(format!("dir{}", i));
+            fs::create_dir(root.join(&dir))
+                .with_context(|| format!("Failed to create directory {:?}", dir))?;
+            dirs.push(dir);
+        }
+
+        let mut files = BTreeMap::new();
+        for i in 0..config.files {
+            let path = dirs[rng.gen_range(0..dirs.len())].join(format!("file{}", i));
+            let content = random_content(&mut rng, config.sizes);
+            fs::write(root.join(&path), &content)
+                .with_context(|| format!("Failed to create {:?}", path))?;
+            files.insert(
+                path,
+                FileState {
+                    owner: i % config.writers.max(1),
+                    versions: vec![Arc::new(content)],
+                    exists: true,
+                    may_be_missing: false,
+                },
+            );
+        }
+
+        Ok(Self {
+            root,
+            config,
+            dirs,
+            files: Mutex::new(files),
+        })
+    }
+
+    /// The seed to replay this workload with
+    pub fn seed(&self) -> u64 {
+        self.config.seed
+    }
+
+    /// Run the writers and readers to completion, blocking the calling thread
+    ///
+    /// Fails on the first unexpected I/O error or inconsistent read.
+    pub fn run(&self) -> Result<WorkloadStats> {
+        let start = Instant::now();
+        let writers_done = AtomicBool::new(false);
+
+        let result = std::thread::scope(|scope| {
+            let readers: Vec<_> = (0..self.config.readers)
+                .map(|id| {
+                    let writers_done = &writers_done;
+                    scope.spawn(move || self.reader(id, writers_done))
+                })
+                .collect();
+            let writers: Vec<_> = (0..self.config.writers)
+                .map(|id| scope.spawn(move || self.writer(id)))
+                .collect();
+
+            let mut stats = WorkloadStats::default();
+            let mut first_error = None;
+            let mut join =
+                |handle: std::thread::ScopedJoinHandle<'_, Result<WorkloadStats>>| match handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Worker panicked")))
+                {
+                    Ok(worker) => stats.merge(&worker),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                };
+            for handle in writers {
+                join(handle);
+            }
+            writers_done.store(true, Ordering::SeqCst);
+            for handle in readers {
+                join(handle);
+            }
+
+            match first_error {
+                Some(e) => Err(e),
+                None => Ok(stats),
+            }
+        });
+
+        let mut stats =
+            result.with_context(|| format!("Workload failed (seed {})", self.config.seed))?;
+        stats.elapsed = start.elapsed();
+        info!("Workload finished: {:?}", stats);
+        Ok(stats)
+    }
+
+    /// Change files owned by writer `id`
+    fn writer(&self, id: usize) -> Result<WorkloadStats> {
+        let mut rng = self.worker_rng(id);
+        let mut stats = WorkloadStats::default();
+        let mut renames = 0;
+
+        for _ in 0..self.config.ops_per_writer {
+            let (path, exists) = {
+                let files = self.files.lock().unwrap();
+                let owned: Vec<(&PathBuf, bool)> = files
+                    .iter()
+                    .filter(|(_, f)| f.owner == id)
+                    .map(|(p, f)| (p, f.exists))
+                    .collect();
+                if owned.is_empty() {
+                    break;
+                }
+                let (path, exists) = owned[rng.gen_range(0..owned.len())];
+                (path.clone(), exists)
+            };
+            let full = self.root.join(&path);
+
+            let op = if exists {
+                self.config.mix.pick(&mut rng)
+            } else {
+                Operation::Overwrite
+            };
+            debug!("Writer {}: {:?} {:?}", id, op, path);
+
+            // The model is updated first, so readers never see content it
+            // doesn't know about
+            match op {
+                Operation::Overwrite => {
+                    let content = Arc::new(random_content(&mut rng, self.config.sizes));
+                    self.update(&path, |f| {
+                        f.versions.push(content.clone());
+                        f.exists = true;
+                    });
+                    fs::write(&full, &*content)
+                        .with_context(|| format!("Failed to write {:?}", path))?;
+                    stats.overwrites += 1;
+                    stats.bytes_written += content.len() as u64;
+                }
+                Operation::Append => {
+                    let chunk = random_content(&mut rng, self.config.sizes);
+                    self.update(&path, |f| {
+                        let mut content = f.current().to_vec();
+                        content.extend_from_slice(&chunk);
+                        f.versions.push(Arc::new(content));
+                    });
+                    OpenOptions::new()
+                        .append(true)
+                        .open(&full)
+                        .and_then(|mut file| file.write_all(&chunk))
+                        .with_context(|| format!("Failed to append to {:?}", path))?;
+                    stats.appends += 1;
+                    stats.bytes_written += chunk.len() as u64;
+                }
+                Operation::Delete => {
+                    self.update(&path, |f| f.may_be_missing = true);
+                    fs::remove_file(&full)
+                        .with_context(|| format!("Failed to remove {:?}", path))?;
+                    self.update(&path, |f| f.exists = false);
+                    stats.deletes += 1;
+                }
+                Operation::Rename => {
+                    renames += 1;
+                    let dir = &self.dirs[rng.gen_range(0..self.dirs.len())];
+                    let new_path = dir.join(format!("w{}-moved{}", id, renames));
+                    {
+                        let mut files = self.files.lock().unwrap();
+                        let old = files.get_mut(&path).unwrap();
+                        old.may_be_missing = true;
+                        let content = old.current().clone();
+                        files.insert(
+                            new_path.clone(),
+                            FileState {
+                                owner: id,
+                                versions: vec![content],
+                                exists: true,
+                                may_be_missing: true,
+                            },
+                        );
+                    }
+                    fs::rename(&full, self.root.join(&new_path)).with_context(|| {
+                        format!("Failed to rename {:?} to {:?}", path, new_path)
+                    })?;
+                    self.update(&path, |f| f.exists = false);
+                    stats.renames += 1;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Read random files and list random directories until the writers are
+    /// done, checking everything seen against the model
+    fn reader(&self, id: usize, writers_done: &AtomicBool) -> Result<WorkloadStats> {
+        let mut rng = self.worker_rng(self.config.writers + id);
+        let mut stats = WorkloadStats::default();
+
+        while !writers_done.load(Ordering::SeqCst) {
+            if rng.gen_ratio(1, 8) {
+                let dir = &self.dirs[rng.gen_range(0..self.dirs.len())];
+                self.check_listing(dir)?;
+                stats.listings += 1;
+                continue;
+            }
+
+            let path = {
+                let files = self.files.lock().unwrap();
+                let n = rng.gen_range(0..files.len());
+                files.keys().nth(n).unwrap().clone()
+            };
+            match fs::read(self.root.join(&path)) {
+                Ok(data) => {
+                    self.check_read(&path, &data)?;
+                    stats.reads += 1;
+                    stats.bytes_read += data.len() as u64;
+                }
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    let files = self.files.lock().unwrap();
+                    let file = &files[&path];
+                    anyhow::ensure!(
+                        !file.exists || file.may_be_missing,
+                        "{:?} vanished although it was never removed",
+                        path
+                    );
+                }
+                Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Check that every block of `data` read from `path` matches the same
+    /// block of some version written to it
+    fn check_read(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let files = self.files.lock().unwrap();
+        let versions = &files[path].versions;
+        for (i, block) in data.chunks(READ_CHECK_BLOCK).enumerate() {
+            let start = i * READ_CHECK_BLOCK;
+            let range = start..start + block.len();
+            if !versions.iter().any(|v| v.get(range.clone()) == Some(block)) {
+                anyhow::bail!(
+                    "Read {} bytes of {:?} that don't match any written version at offset {} \
+                     (version sizes: {:?})",
+                    data.len(),
+                    path,
+                    start,
+                    versions.iter().map(|v| v.len()).collect::<Vec<_>>()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every file listed in `dir` is one the model knows about
+    fn check_listing(&self, dir: &Path) -> Result<()> {
+        let entries = fs::read_dir(self.root.join(dir))
+            .with_context(|| format!("Failed to list {:?}", dir))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("Failed to list {:?}", dir))?;
+            let path = dir.join(entry.file_name());
+            let known = self.files.lock().unwrap().contains_key(&path) || self.dirs.contains(&path);
+            anyhow::ensure!(known, "Listing of {:?} has unknown entry {:?}", dir, path);
+        }
+        Ok(())
+    }
+
+    /// Compare the tree on disk with the model once the workers are done
+    ///
+    /// Reports every file with the wrong content, missing or left behind,
+    /// and anything on disk the workload never created.
+    pub fn verify(&self) -> Result<()> {
+        let files = self.files.lock().unwrap();
+        let mut problems = Vec::new();
+
+        for (path, state) in files.iter() {
+            match (state.exists, fs::read(self.root.join(path))) {
+                (true, Ok(data)) => {
+                    if let Some(problem) = compare(&data, state.current()) {
+                        problems.push(format!("{:?}: {}", path, problem));
+                    }
+                }
+                (false, Err(e)) if e.kind() == ErrorKind::NotFound => {}
+                (false, Ok(_)) => problems.push(format!("{:?}: should have been removed", path)),
+                (_, Err(e)) => problems.push(format!("{:?}: {}", path, e)),
+            }
+        }
+
+        let mut on_disk = Vec::new();
+        collect_tree(&self.root, Path::new(""), &mut on_disk)?;
+        for (path, is_dir) in on_disk {
+            let known = if is_dir {
+                self.dirs.contains(&path)
+            } else {
+                files.get(&path).is_some_and(|f| f.exists)
+            };
+            if !known {
+                problems.push(format!("{:?}: unexpected entry", path));
+            }
+        }
+
+        report(self.config.seed, "tree", problems)
+    }
+
+    /// Compare the objects below `prefix` in `bucket` with the model, once
+    /// the workers are done and the mount has synced
+    pub async fn verify_bucket(&self, bucket: &TestBucket, prefix: &str) -> Result<()> {
+        let key = |path: &Path| format!("{}{}", prefix, path.to_string_lossy());
+        let expected: Vec<(String, Option<Arc<Vec<u8>>>)> = self
+            .files
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, f)| (key(path), f.exists.then(|| f.current().clone())))
+            .collect();
+
+        let mut problems = Vec::new();
+        for (key, content) in expected {
+            match (content, bucket.object_exists(&key).await?) {
+                (Some(content), true) => {
+                    let data = bucket.get_object(&key).await?;
+                    if let Some(problem) = compare(&data, &content) {
+                        problems.push(format!("{}: {}", key, problem));
+                    }
+                }
+                (Some(_), false) => problems.push(format!("{}: missing", key)),
+                (None, true) => problems.push(format!("{}: should have been removed", key)),
+                (None, false) => {}
+            }
+        }
+
+        report(self.config.seed, "bucket", problems)
+    }
+
+    /// Apply `change` to the model of `path`
+    fn update(&self, path: &Path, change: impl FnOnce(&mut FileState)) {
+        change(self.files.lock().unwrap().get_mut(path).unwrap());
+    }
+
+    /// Random source for worker `n`, derived from the seed
+    fn worker_rng(&self, n: usize) -> StdRng {
+        StdRng::seed_from_u64(
+            self.config
+                .seed
+                .wrapping_add((n as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+        )
+    }
+}
+
+/// Random bytes with a size from `sizes`
+fn random_content(rng: &mut impl Rng, sizes: SizeDistribution) -> Vec<u8> {
+    let mut content = vec![0u8; sizes.sample(rng)];
+    rng.fill(&mut content[..]);
+    content
+}
+
+/// Describe how `actual` differs from `expected`, if it does
+fn compare(actual: &[u8], expected: &[u8]) -> Option<String> {
+    if actual == expected {
+        return None;
+    }
+    let offset = actual
+        .iter()
+        .zip(expected)
+        .position(|(a, e)| a != e)
+        .unwrap_or(actual.len().min(expected.len()));
+    Some(format!(
+        "expected {} bytes, got {} bytes, first difference at offset {}",
+        expected.len(),
+        actual.len(),
+        offset
+    ))
+}
+
+/// Collect every entry below `root.join(dir)`, relative to `root`
+fn collect_tree(root: &Path, dir: &Path, out: &mut Vec<(PathBuf, bool)>) -> Result<()> {
+    let entries =
+        fs::read_dir(root.join(dir)).with_context(|| format!("Failed to list {:?}", dir))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        let is_dir = entry.file_type()?.is_dir();
+        if is_dir {
+            collect_tree(root, &path, out)?;
+        }
+        out.push((path, is_dir));
+    }
+    Ok(())
+}
+
+/// Fail with the first `MAX_REPORTED_PROBLEMS` problems, if there are any
+fn report(seed: u64, what: &str, problems: Vec<String>) -> Result<()> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!(
+        "Workload {} verification failed (seed {}), {} problem(s):",
+        what,
+        seed,
+        problems.len()
+    );
+    for problem in problems.iter().take(MAX_REPORTED_PROBLEMS) {
+        message.push_str("\n  ");
+        message.push_str(problem);
+    }
+    anyhow::bail!(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config(seed: u64) -> WorkloadConfig {
+        WorkloadConfig {
+            seed,
+            files: 16,
+            sizes: SizeDistribution::LogUniform {
+                min: 1,
+                max: 3 * READ_CHECK_BLOCK,
+            },
+            writers: 2,
+            readers: 2,
+            ops_per_writer: 50,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_size_distribution() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for dist in [
+            SizeDistribution::Fixed(10),
+            SizeDistribution::Uniform { min: 5, max: 50 },
+            SizeDistribution::LogUniform {
+                min: 0,
+                max: 1 << 20,
+            },
+        ] {
+            let (min, max) = match dist {
+                SizeDistribution::Fixed(n) => (n, n),
+                SizeDistribution::Uniform { min, max }
+                | SizeDistribution::LogUniform { min, max } => (min, max),
+            };
+            for _ in 0..100 {
+                let size = dist.sample(&mut rng);
+                assert!((min..=max).contains(&size), "{:?} gave {}", dist, size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let trees: Vec<Vec<(PathBuf, bool)>> = (0..2)
+            .map(|_| {
+                let dir = tempfile::tempdir().unwrap();
+                Workload::generate(dir.path(), small_config(7)).unwrap();
+                let mut tree = Vec::new();
+                collect_tree(dir.path(), Path::new(""), &mut tree).unwrap();
+                tree.sort();
+                tree
+            })
+            .collect();
+        assert_eq!(trees[0], trees[1]);
+        assert_eq!(trees[0].iter().filter(|(_, is_dir)| !is_dir).count(), 16);
+    }
+
+    #[test]
+    fn test_run_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let workload = Workload::generate(dir.path(), small_config(42)).unwrap();
+        let stats = workload.run().unwrap();
+        assert_eq!(
+            stats.overwrites + stats.appends + stats.deletes + stats.renames,
+            100
+        );
+        workload.verify().unwrap();
+
+        // Tampering with the tree is reported
+        fs::write(dir.path().join("stray"), b"x").unwrap();
+        let err = workload.verify().unwrap_err().to_string();
+        assert!(err.contains("\"stray\": unexpected entry"), "{}", err);
+        assert!(err.contains("seed 42"), "{}", err);
+    }
+
+    #[test]
+    fn test_check_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = WorkloadConfig {
+            dirs: 0,
+            files: 1,
+            sizes: SizeDistribution::Fixed(2 * READ_CHECK_BLOCK),
+            ..small_config(3)
+        };
+        let workload = Workload::generate(dir.path(), config).unwrap();
+        let path = PathBuf::from("file0");
+        let original = workload.files.lock().unwrap()[&path].current().clone();
+        let rewritten = vec![0xAB; READ_CHECK_BLOCK];
+        workload.update(&path, |f| f.versions.push(Arc::new(rewritten.clone())));
+
+        // Any version, a prefix of one, or pages of different versions pass
+        workload.check_read(&path, &original).unwrap();
+        workload.check_read(&path, &original[..100]).unwrap();
+        let mut mixed = rewritten.clone();
+        mixed.extend_from_slice(&original[READ_CHECK_BLOCK..]);
+        workload.check_read(&path, &mixed).unwrap();
+
+        // Data that was never written fails
+        assert!(workload.check_read(&path, &[0xCD; 10]).is_err());
+    }
+}
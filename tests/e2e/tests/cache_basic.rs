@@ -67,6 +67,7 @@ async fn test_sync_to_backend() -> Result<()> {
 
     // Force sync
     harness.force_sync().await?;
+    harness.assert_log_contains("cache sync complete").await;
 
     // Verify in S3
     let s3_content = harness.bucket().get_object(&filename).await?;
@@ -231,6 +232,12 @@ async fn test_memory_cache() -> Result<()> {
     create_file_str(&filepath, "content")?;
     assert_file_content_str(&filepath, "content");
 
+    harness.force_sync().await?;
+
+    // Writes held in memory must reach the backend on the flush interval
+    let s3_content = harness.bucket().get_object(&filename).await?;
+    assert_eq!(s3_content, b"content");
+
     harness.cleanup().await?;
     Ok(())
 }
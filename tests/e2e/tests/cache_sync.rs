@@ -7,8 +7,8 @@ mod common;
 use anyhow::Result;
 use common::*;
 use fuse_adapter_e2e::{
-    assert_file_content_str, assert_file_exists, assert_not_exists, random_filename, TestCacheType,
-    TestHarness,
+    assert_file_content_str, assert_file_exists, assert_not_exists, random_filename,
+    wait_for_object_content, TestCacheType, TestHarness,
 };
 use std::fs;
 use std::time::Duration;
@@ -28,11 +28,9 @@ async fn test_background_sync_interval() -> Result<()> {
     // Not in S3 yet
     assert!(!harness.bucket().object_exists(&filename).await?);
 
-    // Wait for sync (default 5s interval + buffer)
-    harness.force_sync().await?;
-
-    // Now should be in S3
-    assert!(harness.bucket().object_exists(&filename).await?);
+    // Background sync uploads it within the interval (plus a buffer)
+    let timeout = Duration::from_secs(harness.flush_interval_secs() + 5);
+    wait_for_object_content(harness.bucket(), &filename, b"content", timeout).await?;
 
     harness.cleanup().await?;
     Ok(())
@@ -0,0 +1,85 @@
+//! Fault injection tests
+//!
+//! Tests error and retry paths by injecting backend failures below the
+//! cache. Needs a fuse-adapter binary built with `--features mock`:
+//!
+//! ```text
+//! cargo build --features mock
+//! cargo test -p fuse-adapter-e2e --features mock --test fault_injection
+//! ```
+
+#![cfg(feature = "mock")]
+
+mod common;
+
+use anyhow::Result;
+use common::*;
+use fuse_adapter_e2e::{random_filename, FaultConfig, FaultsConfig, TestHarness};
+use std::fs;
+
+fn always_fail() -> FaultConfig {
+    FaultConfig {
+        error_rate: Some(1.0),
+        ..Default::default()
+    }
+}
+
+/// Test that backend read failures surface as EIO and stop once cleared
+#[tokio::test]
+async fn test_read_failure_surfaces_as_eio() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| {
+        builder.add_mount_with_faults("faults", false, FaultsConfig::default())
+    })
+    .await?;
+    let mount = harness.mount();
+
+    let filename = random_filename("read");
+    harness
+        .bucket()
+        .put_object(&format!("faults/{}", filename), b"content")
+        .await?;
+
+    harness.set_fault(mount, "read", &always_fail()).await?;
+    let err = fs::read(mount.join(&filename)).expect_err("read should fail");
+    assert_eq!(err.raw_os_error(), Some(libc::EIO));
+
+    harness.clear_faults(mount).await?;
+    assert_eq!(fs::read(mount.join(&filename))?, b"content");
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test that uploads failing in the background are retried on the next sync
+#[tokio::test]
+async fn test_failed_sync_is_retried() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| {
+        builder.add_mount_with_faults("faults", true, FaultsConfig::default())
+    })
+    .await?;
+    let mount = harness.mount();
+    let key = |name: &str| format!("faults/{}", name);
+
+    harness.set_fault(mount, "write", &always_fail()).await?;
+
+    let filename = random_filename("retry");
+    create_file_str(&mount.join(&filename), "content")?;
+
+    // The upload keeps failing, but the cache still serves the file
+    harness.force_sync().await?;
+    let synced = harness.bucket().object_exists(&key(&filename)).await?
+        && harness.bucket().get_object(&key(&filename)).await? == b"content";
+    assert!(!synced, "Upload should not have succeeded");
+    assert_eq!(fs::read_to_string(mount.join(&filename))?, "content");
+
+    // Once the backend recovers the pending upload goes through
+    harness.clear_faults(mount).await?;
+    harness.force_sync().await?;
+    assert_eq!(
+        harness.bucket().get_object(&key(&filename)).await?,
+        b"content"
+    );
+
+    harness.cleanup().await?;
+    Ok(())
+}
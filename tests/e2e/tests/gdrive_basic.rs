@@ -0,0 +1,112 @@
+//! Google Drive connector tests
+//!
+//! Tests the gdrive connector against the harness's in-process fake of the
+//! Drive API: reads of existing files, writes synced from the cache,
+//! directories, renames and exported native Docs.
+
+mod common;
+
+use anyhow::Result;
+use common::*;
+use fuse_adapter_e2e::{assert_dir_contains, random_filename, TestHarness};
+use std::fs;
+
+/// Test that files already in Drive can be listed and read
+#[tokio::test]
+async fn test_read_existing_file() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| builder.add_gdrive_mount("gdrive")).await?;
+    let mount = harness.mount();
+    let drive = harness.gdrive().unwrap();
+
+    let filename = random_filename("seeded");
+    drive.put_file(&format!("gdrive/docs/{}", filename), b"from drive");
+
+    assert_dir_contains(&mount.join("docs"), &[filename.as_str()]);
+    assert_eq!(
+        read_file_str(&mount.join("docs").join(&filename))?,
+        "from drive"
+    );
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test that written files are uploaded to Drive
+#[tokio::test]
+async fn test_write_syncs_to_drive() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| builder.add_gdrive_mount("gdrive")).await?;
+    let mount = harness.mount();
+
+    let filename = random_filename("write");
+    fs::create_dir(mount.join("dir"))?;
+    create_file_str(&mount.join("dir").join(&filename), "Hello, Drive!")?;
+    harness.force_sync().await?;
+
+    let drive = harness.gdrive().unwrap();
+    assert_eq!(
+        drive.get_file(&format!("gdrive/dir/{}", filename)),
+        Some(b"Hello, Drive!".to_vec())
+    );
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test that renaming a file moves it without losing its content
+#[tokio::test]
+async fn test_rename_keeps_content() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| builder.add_gdrive_mount("gdrive")).await?;
+    let mount = harness.mount();
+    let drive = harness.gdrive().unwrap();
+
+    drive.put_file("gdrive/old.txt", b"content");
+    fs::create_dir(mount.join("moved"))?;
+    fs::rename(mount.join("old.txt"), mount.join("moved/new.txt"))?;
+    harness.force_sync().await?;
+
+    assert!(!drive.exists("gdrive/old.txt"));
+    assert_eq!(
+        drive.get_file("gdrive/moved/new.txt"),
+        Some(b"content".to_vec())
+    );
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test that deleted files and directories are removed from Drive
+#[tokio::test]
+async fn test_delete() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| builder.add_gdrive_mount("gdrive")).await?;
+    let mount = harness.mount();
+    let drive = harness.gdrive().unwrap();
+
+    drive.put_file("gdrive/file.txt", b"file");
+    drive.put_file("gdrive/dir/nested.txt", b"nested");
+
+    fs::remove_file(mount.join("file.txt"))?;
+    fs::remove_dir_all(mount.join("dir"))?;
+    harness.force_sync().await?;
+
+    assert!(drive.list("gdrive").is_empty());
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test that native Docs are listed with their export extension and read-only
+#[tokio::test]
+async fn test_native_doc_export() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| builder.add_gdrive_mount("gdrive")).await?;
+    let mount = harness.mount();
+    let drive = harness.gdrive().unwrap();
+
+    drive.put_native("gdrive/Notes", "document", b"exported text");
+
+    let path = mount.join("Notes.txt");
+    assert_eq!(read_file_str(&path)?, "exported text");
+    assert!(fs::metadata(&path)?.permissions().readonly());
+
+    harness.cleanup().await?;
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! In-process adapter tests
+//!
+//! Runs the adapter inside the test process through the library API and
+//! checks it behaves like the daemon: writes sync, restarts keep data and
+//! the sync control path and log capture still work.
+
+mod common;
+
+use anyhow::Result;
+use common::*;
+use fuse_adapter_e2e::{random_filename, TestHarness};
+
+/// Test that writes through an in-process adapter reach the backend
+#[tokio::test]
+async fn test_write_syncs_to_backend() -> Result<()> {
+    let harness = TestHarness::with_config(|builder| builder.in_process()).await?;
+    assert!(harness.adapter().unwrap().in_process().is_some());
+    let mount = harness.mount();
+
+    let filename = random_filename("in-process");
+    create_file_str(&mount.join(&filename), "served in-process")?;
+    harness.force_sync().await?;
+    harness.assert_log_contains("cache sync complete").await;
+
+    let content = harness.bucket().get_object(&filename).await?;
+    assert_eq!(String::from_utf8(content)?, "served in-process");
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test that an in-process adapter can be restarted
+#[tokio::test]
+async fn test_restart() -> Result<()> {
+    let mut harness = TestHarness::with_config(|builder| builder.in_process()).await?;
+
+    let filename = random_filename("restart");
+    create_file_str(&harness.mount().join(&filename), "kept")?;
+    harness.restart().await?;
+
+    assert!(harness.adapter().unwrap().in_process().is_some());
+    assert_eq!(read_file_str(&harness.mount().join(&filename))?, "kept");
+
+    harness.cleanup().await?;
+    Ok(())
+}
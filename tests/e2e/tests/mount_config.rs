@@ -12,7 +12,8 @@ mod common;
 use anyhow::Result;
 use common::*;
 use fuse_adapter_e2e::{
-    assert_file_content_str, assert_file_exists, random_filename, TestCacheType, TestHarness,
+    assert_file_content_str, random_filename, wait_for_file, wait_for_object, TestCacheType,
+    TestHarness,
 };
 use std::fs::{self, OpenOptions};
 use std::os::unix::fs::MetadataExt;
@@ -38,11 +39,9 @@ async fn test_read_only_mount_allows_reads() -> Result<()> {
         .put_object(&format!("readonly/{}", filename), content)
         .await?;
 
-    // Small delay to ensure S3 consistency
-    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
     // Reading should succeed
     let filepath = mount.join(filename);
+    wait_for_file(&filepath, std::time::Duration::from_secs(5)).await?;
     let read_content = fs::read_to_string(&filepath)?;
     assert_eq!(read_content, "This content can be read");
 
@@ -89,12 +88,10 @@ async fn test_read_only_mount_rejects_delete() -> Result<()> {
         .put_object(&format!("readonly-delete/{}", filename), b"content")
         .await?;
 
-    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
     let filepath = mount.join(filename);
 
     // Verify file exists
-    assert!(filepath.exists());
+    wait_for_file(&filepath, std::time::Duration::from_secs(5)).await?;
 
     // Attempting to delete should fail
     let result = fs::remove_file(&filepath);
@@ -138,9 +135,8 @@ async fn test_read_only_mount_rejects_modification() -> Result<()> {
         )
         .await?;
 
-    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
     let filepath = mount.join(filename);
+    wait_for_file(&filepath, std::time::Duration::from_secs(5)).await?;
 
     // Attempting to open for write should fail
     let result = OpenOptions::new().write(true).open(&filepath);
@@ -290,13 +286,8 @@ async fn test_prefix_mounting_isolation() -> Result<()> {
         .put_object("subdir/inside.txt", b"inside")
         .await?;
 
-    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
     // The mount should only see the file inside the prefix
-    assert!(
-        mount.join("inside.txt").exists(),
-        "File inside prefix should be visible"
-    );
+    wait_for_file(&mount.join("inside.txt"), std::time::Duration::from_secs(5)).await?;
 
     // Root listing should only contain prefixed content
     let entries: Vec<_> = fs::read_dir(mount)?
@@ -457,10 +448,13 @@ async fn test_cache_custom_flush_interval() -> Result<()> {
     assert_file_content_str(&filepath, "content");
 
     // But S3 might not have it yet (before flush)
-    // After flush interval + buffer, S3 should have it
-    harness.force_sync().await?;
-
-    let s3_content = harness.bucket().get_object(&filename).await?;
+    // Within the flush interval + buffer, the background sync should run
+    let s3_content = wait_for_object(
+        harness.bucket(),
+        &filename,
+        std::time::Duration::from_secs(harness.flush_interval_secs() + 5),
+    )
+    .await?;
     assert_eq!(String::from_utf8(s3_content)?, "content");
 
     harness.cleanup().await?;
@@ -0,0 +1,128 @@
+//! Network fault tests
+//!
+//! Tests degraded-backend behavior by routing the adapter's traffic to
+//! MinIO through a proxy and changing the network conditions mid-test.
+
+mod common;
+
+use anyhow::Result;
+use common::*;
+use fuse_adapter_e2e::{random_filename, SharedHarness, TestCacheType, TestContext};
+use std::fs;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard, OnceCell};
+
+/// Harness whose adapter reaches MinIO through the proxy
+static PROXIED_HARNESS: OnceCell<SharedHarness> = OnceCell::const_new();
+
+/// Network conditions are shared by every context, so tests take turns
+static NETWORK: Mutex<()> = Mutex::const_new(());
+
+async fn proxied_context() -> Result<(MutexGuard<'static, ()>, TestContext<'static>)> {
+    let guard = NETWORK.lock().await;
+    let harness = PROXIED_HARNESS
+        .get_or_init(|| async {
+            SharedHarness::with_proxy(TestCacheType::FilesystemFast)
+                .await
+                .expect("Failed to initialize proxied harness")
+        })
+        .await;
+    let ctx = harness.context().await?;
+    ctx.reset_network()?;
+    Ok((guard, ctx))
+}
+
+/// Upload an object directly, bypassing the adapter and its cache
+async fn put_object(ctx: &TestContext<'_>, name: &str, data: &[u8]) -> Result<()> {
+    ctx.bucket()
+        .put_object(&format!("{}/{}", ctx.prefix(), name), data)
+        .await
+}
+
+/// Test that latency on the backend connection slows uncached reads
+#[tokio::test]
+async fn test_latency_slows_uncached_reads() -> Result<()> {
+    let (_guard, ctx) = proxied_context().await?;
+    let filename = random_filename("latency");
+    put_object(&ctx, &filename, b"slow content").await?;
+
+    ctx.add_latency(Duration::from_millis(500))?;
+    let start = Instant::now();
+    assert_eq!(fs::read(ctx.mount().join(&filename))?, b"slow content");
+    assert!(
+        start.elapsed() >= Duration::from_millis(500),
+        "Read took only {:?}",
+        start.elapsed()
+    );
+
+    ctx.reset_network()?;
+    ctx.cleanup().await?;
+    Ok(())
+}
+
+/// Test that throttled bandwidth paces downloads
+#[tokio::test]
+async fn test_throttled_download() -> Result<()> {
+    let (_guard, ctx) = proxied_context().await?;
+    let filename = random_filename("throttle");
+    let data = vec![0x5a; 256 * 1024];
+    put_object(&ctx, &filename, &data).await?;
+
+    ctx.throttle_bandwidth(128 * 1024)?;
+    let start = Instant::now();
+    assert_eq!(fs::read(ctx.mount().join(&filename))?, data);
+    assert!(
+        start.elapsed() >= Duration::from_secs(1),
+        "Download took only {:?}",
+        start.elapsed()
+    );
+
+    ctx.reset_network()?;
+    ctx.cleanup().await?;
+    Ok(())
+}
+
+/// Test that an uncached read fails while the backend is unreachable and
+/// works again once the connection is restored
+#[tokio::test]
+async fn test_read_during_outage() -> Result<()> {
+    let (_guard, ctx) = proxied_context().await?;
+    let filename = random_filename("outage");
+    put_object(&ctx, &filename, b"content").await?;
+
+    ctx.cut_connection()?;
+    assert!(fs::read(ctx.mount().join(&filename)).is_err());
+
+    ctx.restore_connection()?;
+    assert_eq!(fs::read(ctx.mount().join(&filename))?, b"content");
+
+    ctx.cleanup().await?;
+    Ok(())
+}
+
+/// Test that writes made during an outage are served from cache and synced
+/// once the backend is reachable again
+#[tokio::test]
+async fn test_sync_retries_after_outage() -> Result<()> {
+    let (_guard, ctx) = proxied_context().await?;
+    let filename = random_filename("retry");
+    let filepath = ctx.mount().join(&filename);
+
+    create_file_str(&filepath, "written before the outage")?;
+    ctx.cut_connection()?;
+
+    // Syncs fail while cut, but the cached copy stays readable
+    ctx.force_sync().await?;
+    assert_eq!(fs::read_to_string(&filepath)?, "written before the outage");
+
+    ctx.restore_connection()?;
+    ctx.force_sync().await?;
+    ctx.force_sync().await?;
+    assert_eq!(
+        ctx.get_object(&filename).await?,
+        b"written before the outage"
+    );
+
+    ctx.cleanup().await?;
+    Ok(())
+}
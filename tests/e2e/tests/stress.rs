@@ -0,0 +1,85 @@
+//! Stress tests
+//!
+//! Runs generated workloads (concurrent writers and readers over a random
+//! tree) against the mount, then checks the tree and, after a sync, the
+//! bucket. The short run is part of the normal suite; the soak tests need
+//! the `stress` feature:
+//!
+//! ```text
+//! cargo test -p fuse-adapter-e2e --features stress --test stress
+//! ```
+//!
+//! Failures report the workload's seed; set `WORKLOAD_SEED` to replay it.
+
+use anyhow::Result;
+use fuse_adapter_e2e::{TestCacheType, TestHarness, Workload, WorkloadConfig};
+
+/// Workload config with the seed from `WORKLOAD_SEED`, if set
+fn config(config: WorkloadConfig) -> WorkloadConfig {
+    match std::env::var("WORKLOAD_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+    {
+        Some(seed) => WorkloadConfig { seed, ..config },
+        None => config,
+    }
+}
+
+/// Run `config` against a fresh harness with `cache_type`, verifying the
+/// mount and then the bucket
+async fn run_workload(cache_type: TestCacheType, config: WorkloadConfig) -> Result<()> {
+    let harness = TestHarness::with_cache(cache_type).await?;
+
+    let workload = Workload::generate(harness.mount(), config)?;
+    workload.run()?;
+    workload.verify()?;
+
+    harness.force_sync().await?;
+    workload.verify_bucket(harness.bucket(), "").await?;
+
+    harness.cleanup().await?;
+    Ok(())
+}
+
+/// Test a short workload through the fast filesystem cache
+#[tokio::test]
+async fn test_short_workload() -> Result<()> {
+    let config = config(WorkloadConfig {
+        files: 16,
+        writers: 2,
+        readers: 2,
+        ops_per_writer: 25,
+        ..Default::default()
+    });
+    run_workload(TestCacheType::FilesystemFast, config).await
+}
+
+/// Soak the memory cache with a larger tree and more workers
+#[cfg(feature = "stress")]
+#[tokio::test]
+async fn test_soak_memory_cache() -> Result<()> {
+    let config = config(WorkloadConfig {
+        dirs: 32,
+        files: 256,
+        writers: 8,
+        readers: 8,
+        ops_per_writer: 500,
+        ..Default::default()
+    });
+    run_workload(TestCacheType::Memory, config).await
+}
+
+/// Soak the filesystem cache with a larger tree and more workers
+#[cfg(feature = "stress")]
+#[tokio::test]
+async fn test_soak_filesystem_cache() -> Result<()> {
+    let config = config(WorkloadConfig {
+        dirs: 32,
+        files: 256,
+        writers: 8,
+        readers: 8,
+        ops_per_writer: 500,
+        ..Default::default()
+    });
+    run_workload(TestCacheType::FilesystemFast, config).await
+}